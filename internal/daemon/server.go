@@ -9,6 +9,7 @@ import (
 	"fmt"
 	"net/http"
 	"strings"
+	"time"
 
 	"github.com/dotandev/hintents/internal/errors"
 	"github.com/dotandev/hintents/internal/logger"
@@ -20,6 +21,10 @@ import (
 	"go.opentelemetry.io/otel/attribute"
 )
 
+// shutdownTimeout bounds how long in-flight requests may take to finish
+// once the daemon is asked to stop.
+const shutdownTimeout = 30 * time.Second
+
 // Server represents the JSON-RPC daemon server
 type Server struct {
 	rpcClient *stellarrpc.Client
@@ -201,6 +206,8 @@ func (s *Server) Start(ctx context.Context, port string) error {
 
 	// Wait for context cancellation
 	<-ctx.Done()
-	logger.Logger.Info("Shutting down JSON-RPC server")
-	return srv.Shutdown(context.Background())
+	logger.Logger.Info("Shutting down JSON-RPC server", "drain_timeout", shutdownTimeout)
+	shutdownCtx, cancel := context.WithTimeout(context.Background(), shutdownTimeout)
+	defer cancel()
+	return srv.Shutdown(shutdownCtx)
 }
@@ -0,0 +1,24 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Records the git commit the simulator is built from as `ERST_GIT_HASH`,
+//! reported in every response. An `ERST_GIT_HASH` set in the environment
+//! wins, for builds outside a checkout (e.g. Docker).
+
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=ERST_GIT_HASH");
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/refs/heads");
+    let hash = std::env::var("ERST_GIT_HASH").ok().or_else(|| {
+        Command::new("git")
+            .args(["rev-parse", "--short=12", "HEAD"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+            .map(|hash| hash.trim().to_string())
+    });
+    println!("cargo:rustc-env=ERST_GIT_HASH={}", hash.unwrap_or_default());
+}
@@ -0,0 +1,504 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Transaction assembly: the request's envelope with the simulation applied.
+//!
+//! With `assemble`, a successful simulation returns the envelope ready to be
+//! signed, as soroban-cli and the SDKs' `assembleTransaction` prepare it:
+//! the computed `SorobanTransactionData` (footprint, instructions, bytes and
+//! resource fee), the authorization entries on the `InvokeHostFunction`
+//! operation, and a fee raised by the resource fee. Signatures are left
+//! off; the transaction they would sign has changed.
+//!
+//! The resource fee is priced with the host's own
+//! [`compute_transaction_resource_fee`] plus the rent the run paid, from the
+//! fee settings among the `ConfigSetting` entries of the simulated state, or
+//! [`FeeParams::default`], which approximates pubnet. Resources are
+//! declared as metered unless the request's `resource_padding` adds margins
+//! to them, and to the fee, for state that drifts before the transaction
+//! is applied. The transaction's size, which the fee pays for, counts the
+//! signatures the envelope will carry once signed: as many as it carries
+//! now, and at least the source account's.
+
+use crate::resources::{xdr_len, Resources};
+use crate::source::{self, SourceEntry};
+use crate::types::ResourcePadding;
+use base64::Engine as _;
+use schemars::JsonSchema;
+use serde::Serialize;
+use soroban_env_host::fees::{
+    compute_transaction_resource_fee, FeeConfiguration, TransactionResources,
+};
+use soroban_env_host::xdr::{
+    ConfigSettingEntry, ConfigSettingId, FeeBumpTransactionInnerTx, LedgerFootprint, LedgerKey,
+    Limits, OperationBody, ReadXdr, SorobanAuthorizationEntry, SorobanResources,
+    SorobanResourcesExtV0, SorobanTransactionData, SorobanTransactionDataExt, Transaction,
+    TransactionEnvelope, TransactionExt, WriteXdr,
+};
+
+/// Network settings the resource fee is computed from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct FeeParams {
+    pub fee_per_instruction_increment: i64,
+    pub fee_per_disk_read_entry: i64,
+    pub fee_per_write_entry: i64,
+    pub fee_per_disk_read_1kb: i64,
+    pub fee_per_write_1kb: i64,
+    pub fee_per_historical_1kb: i64,
+    pub fee_per_contract_event_1kb: i64,
+    pub fee_per_transaction_size_1kb: i64,
+}
+
+impl Default for FeeParams {
+    fn default() -> Self {
+        Self {
+            fee_per_instruction_increment: 25,
+            fee_per_disk_read_entry: 6_250,
+            fee_per_write_entry: 10_000,
+            fee_per_disk_read_1kb: 1_786,
+            fee_per_write_1kb: 3_500,
+            fee_per_historical_1kb: 16_235,
+            fee_per_contract_event_1kb: 10_000,
+            fee_per_transaction_size_1kb: 1_624,
+        }
+    }
+}
+
+impl FeeParams {
+    /// Reads the parameters from the network's `ConfigSetting` entries.
+    /// Returns whether every setting was found.
+    pub fn from_state(lookup: impl Fn(&LedgerKey) -> Option<SourceEntry>) -> (Self, bool) {
+        let setting = |id| source::config_setting(id, &lookup);
+        let mut params = Self::default();
+        let mut found = 0;
+        if let Some(ConfigSettingEntry::ContractComputeV0(compute)) =
+            setting(ConfigSettingId::ContractComputeV0)
+        {
+            params.fee_per_instruction_increment = compute.fee_rate_per_instructions_increment;
+            found += 1;
+        }
+        if let Some(ConfigSettingEntry::ContractLedgerCostV0(cost)) =
+            setting(ConfigSettingId::ContractLedgerCostV0)
+        {
+            params.fee_per_disk_read_entry = cost.fee_disk_read_ledger_entry;
+            params.fee_per_write_entry = cost.fee_write_ledger_entry;
+            params.fee_per_disk_read_1kb = cost.fee_disk_read1_kb;
+            found += 1;
+        }
+        if let Some(ConfigSettingEntry::ContractLedgerCostExtV0(ext)) =
+            setting(ConfigSettingId::ContractLedgerCostExtV0)
+        {
+            params.fee_per_write_1kb = ext.fee_write1_kb;
+            found += 1;
+        }
+        if let Some(ConfigSettingEntry::ContractHistoricalDataV0(history)) =
+            setting(ConfigSettingId::ContractHistoricalDataV0)
+        {
+            params.fee_per_historical_1kb = history.fee_historical1_kb;
+            found += 1;
+        }
+        if let Some(ConfigSettingEntry::ContractEventsV0(events)) =
+            setting(ConfigSettingId::ContractEventsV0)
+        {
+            params.fee_per_contract_event_1kb = events.fee_contract_events1_kb;
+            found += 1;
+        }
+        if let Some(ConfigSettingEntry::ContractBandwidthV0(bandwidth)) =
+            setting(ConfigSettingId::ContractBandwidthV0)
+        {
+            params.fee_per_transaction_size_1kb = bandwidth.fee_tx_size1_kb;
+            found += 1;
+        }
+        (params, found == 6)
+    }
+
+    fn fee_configuration(&self) -> FeeConfiguration {
+        FeeConfiguration {
+            fee_per_instruction_increment: self.fee_per_instruction_increment,
+            fee_per_disk_read_entry: self.fee_per_disk_read_entry,
+            fee_per_write_entry: self.fee_per_write_entry,
+            fee_per_disk_read_1kb: self.fee_per_disk_read_1kb,
+            fee_per_write_1kb: self.fee_per_write_1kb,
+            fee_per_historical_1kb: self.fee_per_historical_1kb,
+            fee_per_contract_event_1kb: self.fee_per_contract_event_1kb,
+            fee_per_transaction_size_1kb: self.fee_per_transaction_size_1kb,
+        }
+    }
+}
+
+/// The assembled transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct Assembled {
+    /// Base64 `TransactionEnvelope`, unsigned.
+    pub envelope_xdr: String,
+    /// The envelope's fee: its inclusion fee bid plus `resource_fee`. The
+    /// outer fee, for a fee bump.
+    pub fee: i64,
+    /// The declared resource fee, rent included.
+    pub resource_fee: i64,
+    /// Declared instructions.
+    pub instructions: u32,
+    /// Declared disk read bytes.
+    pub disk_read_bytes: u32,
+    /// Declared write bytes.
+    pub write_bytes: u32,
+    /// Parameters the fee was computed with.
+    pub params: FeeParams,
+    /// Whether every parameter was read from the ledger rather than
+    /// defaulted.
+    pub params_from_ledger: bool,
+}
+
+/// What a run used, for the resource fee.
+pub struct Metered {
+    pub instructions: u64,
+    /// Contract events of successful calls plus the return values.
+    pub events_bytes: u64,
+    /// Rent the run paid.
+    pub rent_fee: i64,
+}
+
+/// Applies `resources`, `metered` and the authorization `entries` to
+/// `envelope` with `padding`, pricing the resource fee with `params`, and
+/// whether they were read from the ledger.
+pub fn assemble(
+    envelope: &TransactionEnvelope,
+    resources: &Resources,
+    metered: &Metered,
+    entries: Vec<SorobanAuthorizationEntry>,
+    padding: &ResourcePadding,
+    (params, params_from_ledger): (FeeParams, bool),
+) -> Result<Assembled, String> {
+    let padded = |value: u32, pct, absolute| {
+        u32::try_from(ResourcePadding::pad(value.into(), pct, absolute)).unwrap_or(u32::MAX)
+    };
+    let resources = &Resources {
+        disk_read_bytes: padded(
+            resources.disk_read_bytes,
+            padding.read_bytes_pct,
+            padding.read_bytes,
+        ),
+        write_bytes: padded(
+            resources.write_bytes,
+            padding.write_bytes_pct,
+            padding.write_bytes,
+        ),
+        ..resources.clone()
+    };
+    let instructions = ResourcePadding::pad(
+        metered.instructions,
+        padding.instructions_pct,
+        padding.instructions,
+    );
+    let instructions = u32::try_from(instructions).unwrap_or(u32::MAX);
+
+    let signatures = expected_signatures(envelope);
+    let mut envelope = envelope.clone();
+    let tx = unsigned_transaction(&mut envelope)?;
+    // The declared fee includes the previous resource fee.
+    let previous = match &tx.ext {
+        TransactionExt::V1(data) => data.resource_fee,
+        TransactionExt::V0 => 0,
+    };
+    tx.ext = TransactionExt::V1(transaction_data(resources, instructions.into())?);
+    let invoke = tx.operations.iter_mut().find_map(|op| match &mut op.body {
+        OperationBody::InvokeHostFunction(invoke) => Some(invoke),
+        _ => None,
+    });
+    if let Some(invoke) = invoke {
+        invoke.auth = entries.try_into().map_err(|e| format!("{e:?}"))?;
+    }
+
+    // The size of the resource fee does not depend on its value.
+    let (non_refundable, refundable) = compute_transaction_resource_fee(
+        &TransactionResources {
+            instructions,
+            disk_read_entries: resources.disk_read_entries,
+            write_entries: u32::try_from(resources.read_write.len()).unwrap_or(u32::MAX),
+            disk_read_bytes: resources.disk_read_bytes,
+            write_bytes: resources.write_bytes,
+            contract_events_size_bytes: u32::try_from(metered.events_bytes).unwrap_or(u32::MAX),
+            transaction_size_bytes: xdr_len(&envelope)?
+                .saturating_add(signatures.saturating_mul(SIGNATURE_XDR_BYTES)),
+        },
+        &params.fee_configuration(),
+    );
+    let resource_fee = non_refundable
+        .saturating_add(refundable)
+        .saturating_add(metered.rent_fee);
+    let resource_fee = i64::try_from(ResourcePadding::pad(
+        resource_fee.max(0).unsigned_abs(),
+        padding.fee_pct,
+        padding.fee,
+    ))
+    .unwrap_or(i64::MAX);
+    let raise = |fee: i64| {
+        fee.saturating_sub(previous)
+            .max(0)
+            .saturating_add(resource_fee)
+    };
+
+    let tx = unsigned_transaction(&mut envelope)?;
+    if let TransactionExt::V1(data) = &mut tx.ext {
+        data.resource_fee = resource_fee;
+    }
+    let inner_fee = raise(tx.fee.into());
+    tx.fee = u32::try_from(inner_fee)
+        .map_err(|_| format!("A fee of {inner_fee} stroops does not fit a transaction"))?;
+    let fee = match &mut envelope {
+        TransactionEnvelope::TxFeeBump(bump) => {
+            bump.tx.fee = raise(bump.tx.fee);
+            bump.tx.fee
+        }
+        _ => inner_fee,
+    };
+    Ok(Assembled {
+        envelope_xdr: base64::engine::general_purpose::STANDARD
+            .encode(envelope.to_xdr(Limits::none()).map_err(|e| e.to_string())?),
+        fee,
+        resource_fee,
+        instructions,
+        disk_read_bytes: resources.disk_read_bytes,
+        write_bytes: resources.write_bytes,
+        params,
+        params_from_ledger,
+    })
+}
+
+/// Size of a `DecoratedSignature`: a 4-byte hint and a 64-byte ed25519
+/// signature with its length.
+const SIGNATURE_XDR_BYTES: u32 = 4 + 4 + 64;
+
+/// Signatures `envelope` will carry once signed: those on each of its
+/// transactions, and at least one, the source's, on each.
+fn expected_signatures(envelope: &TransactionEnvelope) -> u32 {
+    let count = |signatures: usize| u32::try_from(signatures).unwrap_or(u32::MAX).max(1);
+    match envelope {
+        TransactionEnvelope::TxV0(envelope) => count(envelope.signatures.len()),
+        TransactionEnvelope::Tx(envelope) => count(envelope.signatures.len()),
+        TransactionEnvelope::TxFeeBump(bump) => {
+            let FeeBumpTransactionInnerTx::Tx(inner) = &bump.tx.inner_tx;
+            count(inner.signatures.len()).saturating_add(count(bump.signatures.len()))
+        }
+    }
+}
+
+/// The (inner) transaction of `envelope`, whose signatures are dropped.
+fn unsigned_transaction(envelope: &mut TransactionEnvelope) -> Result<&mut Transaction, String> {
+    match envelope {
+        TransactionEnvelope::Tx(envelope) => {
+            envelope.signatures = Default::default();
+            Ok(&mut envelope.tx)
+        }
+        TransactionEnvelope::TxFeeBump(bump) => {
+            bump.signatures = Default::default();
+            let FeeBumpTransactionInnerTx::Tx(inner) = &mut bump.tx.inner_tx;
+            inner.signatures = Default::default();
+            Ok(&mut inner.tx)
+        }
+        TransactionEnvelope::TxV0(_) => {
+            Err("A v0 envelope cannot declare Soroban resources".to_string())
+        }
+    }
+}
+
+/// The `SorobanTransactionData` declaring `resources` and `instructions`,
+/// with no resource fee yet.
+pub fn transaction_data(
+    resources: &Resources,
+    instructions: u64,
+) -> Result<SorobanTransactionData, String> {
+    let keys = |keys: &[String]| {
+        keys.iter()
+            .map(|key| LedgerKey::from_xdr_base64(key, Limits::none()))
+            .collect::<Result<Vec<_>, _>>()
+            .and_then(TryInto::try_into)
+            .map_err(|e| e.to_string())
+    };
+    Ok(SorobanTransactionData {
+        ext: if resources.archived_entries.is_empty() {
+            SorobanTransactionDataExt::V0
+        } else {
+            SorobanTransactionDataExt::V1(SorobanResourcesExtV0 {
+                archived_soroban_entries: resources
+                    .archived_entries
+                    .clone()
+                    .try_into()
+                    .map_err(|e: soroban_env_host::xdr::Error| e.to_string())?,
+            })
+        },
+        resources: SorobanResources {
+            footprint: LedgerFootprint {
+                read_only: keys(&resources.read_only)?,
+                read_write: keys(&resources.read_write)?,
+            },
+            instructions: u32::try_from(instructions).unwrap_or(u32::MAX),
+            disk_read_bytes: resources.disk_read_bytes,
+            write_bytes: resources.write_bytes,
+        },
+        resource_fee: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::xdr::{
+        ContractId, DecoratedSignature, Hash, HostFunction, InvokeContractArgs,
+        InvokeHostFunctionOp, LedgerKeyContractData, Memo, MuxedAccount, Operation, Preconditions,
+        ScAddress, ScVal, SequenceNumber, Signature, SignatureHint, SorobanAuthorizedFunction,
+        SorobanAuthorizedInvocation, SorobanCredentials, TransactionV1Envelope, Uint256,
+    };
+
+    fn invoke_args() -> InvokeContractArgs {
+        InvokeContractArgs {
+            contract_address: ScAddress::Contract(ContractId(Hash([7; 32]))),
+            function_name: "transfer".try_into().unwrap(),
+            args: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_assembled_envelope_declares_resources_and_pays_for_them() {
+        let envelope = TransactionEnvelope::Tx(TransactionV1Envelope {
+            tx: Transaction {
+                source_account: MuxedAccount::Ed25519(Uint256([1; 32])),
+                fee: 100,
+                seq_num: SequenceNumber(2),
+                cond: Preconditions::None,
+                memo: Memo::None,
+                operations: vec![Operation {
+                    source_account: None,
+                    body: OperationBody::InvokeHostFunction(InvokeHostFunctionOp {
+                        host_function: HostFunction::InvokeContract(invoke_args()),
+                        auth: Default::default(),
+                    }),
+                }]
+                .try_into()
+                .unwrap(),
+                ext: TransactionExt::V0,
+            },
+            signatures: vec![DecoratedSignature {
+                hint: SignatureHint([0; 4]),
+                signature: Signature(vec![0; 64].try_into().unwrap()),
+            }]
+            .try_into()
+            .unwrap(),
+        });
+        let key = LedgerKey::ContractData(LedgerKeyContractData {
+            contract: ScAddress::Contract(ContractId(Hash([7; 32]))),
+            key: ScVal::LedgerKeyContractInstance,
+            durability: soroban_env_host::xdr::ContractDataDurability::Persistent,
+        });
+        let resources = Resources {
+            read_only: vec![key.to_xdr_base64(Limits::none()).unwrap()],
+            ..Resources::default()
+        };
+        let metered = Metered {
+            instructions: 1_000_000,
+            events_bytes: 100,
+            rent_fee: 500,
+        };
+        let entry = SorobanAuthorizationEntry {
+            credentials: SorobanCredentials::SourceAccount,
+            root_invocation: SorobanAuthorizedInvocation {
+                function: SorobanAuthorizedFunction::ContractFn(invoke_args()),
+                sub_invocations: Default::default(),
+            },
+        };
+
+        let assembled = assemble(
+            &envelope,
+            &resources,
+            &metered,
+            vec![entry.clone()],
+            &ResourcePadding::default(),
+            (FeeParams::default(), false),
+        )
+        .unwrap();
+        let TransactionEnvelope::Tx(tx) =
+            TransactionEnvelope::from_xdr_base64(&assembled.envelope_xdr, Limits::none()).unwrap()
+        else {
+            panic!("not a v1 envelope");
+        };
+        assert!(tx.signatures.is_empty());
+        let TransactionExt::V1(data) = &tx.tx.ext else {
+            panic!("no Soroban data");
+        };
+        assert_eq!(data.resources.footprint.read_only.to_vec(), vec![key]);
+        assert_eq!(data.resources.instructions, 1_000_000);
+        // 100 instruction increments at 25, plus history, bandwidth, events
+        // and rent.
+        assert!(assembled.resource_fee > 2_500 + 1_000 + 500);
+        assert_eq!(data.resource_fee, assembled.resource_fee);
+        assert_eq!(assembled.fee, 100 + assembled.resource_fee);
+        assert_eq!(i64::from(tx.tx.fee), assembled.fee);
+        let OperationBody::InvokeHostFunction(invoke) = &tx.tx.operations[0].body else {
+            panic!("not an invocation");
+        };
+        assert_eq!(invoke.auth.len(), 1);
+
+        // Assembling again replaces the resource fee rather than adding to it.
+        let again = assemble(
+            &TransactionEnvelope::Tx(tx),
+            &resources,
+            &metered,
+            vec![entry.clone()],
+            &ResourcePadding::default(),
+            (FeeParams::default(), false),
+        )
+        .unwrap();
+        assert_eq!(again.fee, assembled.fee);
+
+        let padding = ResourcePadding {
+            instructions_pct: Some(10),
+            write_bytes: Some(100),
+            fee_pct: Some(20),
+            ..ResourcePadding::default()
+        };
+        let padded = assemble(
+            &envelope,
+            &resources,
+            &metered,
+            vec![entry],
+            &padding,
+            (FeeParams::default(), false),
+        )
+        .unwrap();
+        assert_eq!(padded.instructions, 1_100_000);
+        assert_eq!(padded.disk_read_bytes, 0);
+        assert_eq!(padded.write_bytes, 100);
+        // 10_000 more instructions cost 250, and the whole is padded by 20%.
+        assert!(padded.resource_fee >= (assembled.resource_fee + 250) * 6 / 5);
+
+        // Two more signatures to come make the transaction 144 bytes larger.
+        let mut multisig = envelope.clone();
+        if let TransactionEnvelope::Tx(tx) = &mut multisig {
+            let signature = tx.signatures[0].clone();
+            tx.signatures = vec![signature; 3].try_into().unwrap();
+        }
+        assert_eq!(expected_signatures(&multisig), 3);
+        let multisig = assemble(
+            &multisig,
+            &resources,
+            &metered,
+            vec![],
+            &ResourcePadding::default(),
+            (FeeParams::default(), false),
+        )
+        .unwrap();
+        let single = assemble(
+            &envelope,
+            &resources,
+            &metered,
+            vec![],
+            &ResourcePadding::default(),
+            (FeeParams::default(), false),
+        )
+        .unwrap();
+        // Bandwidth and history are both charged by size.
+        let params = FeeParams::default();
+        let per_kb = params.fee_per_transaction_size_1kb + params.fee_per_historical_1kb;
+        assert!(multisig.resource_fee - single.resource_fee >= 144 * per_kb / 1024);
+    }
+}
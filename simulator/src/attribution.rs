@@ -0,0 +1,345 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Itemized cost attribution for profiled simulations.
+//!
+//! With `profile`, a simulation reports where its resources went. Every ledger
+//! entry it accessed is listed with the bytes read and written, and every
+//! event it emitted with its size. Bytes, entry counts and event sizes are
+//! what the network's resource fee is computed from. Each item also carries
+//! the CPU and memory the cost model the simulation ran under assigns to
+//! decoding or encoding it, so items can be ranked against each other. The
+//! whole budget, including contract execution, is broken down per host cost
+//! type.
+
+use crate::resources::{self, xdr_len};
+use crate::source::SourceEntry;
+use schemars::JsonSchema;
+use serde::Serialize;
+use soroban_env_host::budget::Budget;
+use soroban_env_host::events::HostEvent;
+use soroban_env_host::storage::{AccessType, Storage};
+use soroban_env_host::xdr::{ContractCostType, ContractEventType, LedgerKey, Limits, WriteXdr};
+
+/// Resources a simulation spent, itemized.
+#[derive(Debug, Clone, Default, Serialize, JsonSchema)]
+pub struct CostAttribution {
+    /// Ledger entries in the footprint, most expensive first.
+    pub storage: Vec<StorageCost>,
+    /// Events emitted by successful calls, in emission order.
+    pub events: Vec<EventCost>,
+    /// The run's budget per host cost type, most expensive first.
+    pub cost_types: Vec<CostTypeCharge>,
+}
+
+/// One ledger entry the simulation accessed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct StorageCost {
+    /// Base64 `LedgerKey`.
+    pub key: String,
+    /// Entry type, e.g. `ContractData`.
+    pub entry_type: String,
+    /// `read_only` or `read_write`.
+    pub access: &'static str,
+    /// XDR size of the entry before the simulation; 0 if it did not exist.
+    pub read_bytes: u32,
+    /// Whether the read counts as a disk read: classic entries, and archived
+    /// persistent entries that are restored.
+    pub disk_read: bool,
+    /// XDR size of the entry written back; 0 for read-only or removed
+    /// entries.
+    pub write_bytes: u32,
+    /// CPU instructions the cost model assigns to decoding and writing the
+    /// entry.
+    pub cpu_insns: u64,
+    /// Memory bytes the cost model assigns to decoding and writing the entry.
+    pub mem_bytes: u64,
+}
+
+/// One event the simulation emitted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct EventCost {
+    /// `contract` or `system`.
+    pub event_type: &'static str,
+    pub contract_id: Option<String>,
+    /// XDR size of the `ContractEvent`.
+    pub bytes: u32,
+    /// CPU instructions the cost model assigns to encoding the event.
+    pub cpu_insns: u64,
+    /// Memory bytes the cost model assigns to encoding the event.
+    pub mem_bytes: u64,
+}
+
+/// What the budget charged for one host cost type.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct CostTypeCharge {
+    /// `ContractCostType` name, e.g. `WasmInsnExec`.
+    pub cost_type: &'static str,
+    pub iterations: u64,
+    pub cpu_insns: u64,
+    pub mem_bytes: u64,
+}
+
+/// Charges of every cost type `budget` was charged for.
+pub fn cost_types(budget: &Budget) -> Vec<CostTypeCharge> {
+    let mut charges = ContractCostType::variants()
+        .iter()
+        .filter_map(|ty| {
+            let tracker = budget.get_tracker(*ty).ok()?;
+            (tracker.cpu > 0 || tracker.mem > 0).then(|| CostTypeCharge {
+                cost_type: ty.name(),
+                iterations: tracker.iterations,
+                cpu_insns: tracker.cpu,
+                mem_bytes: tracker.mem,
+            })
+        })
+        .collect::<Vec<_>>();
+    charges.sort_by_key(|charge| std::cmp::Reverse(charge.cpu_insns));
+    charges
+}
+
+/// Itemizes a finished simulation. `storage` is the host's storage after
+/// execution, `before` the state it started from, `budget` the budget it
+/// ran under and `model` a fresh budget with the same cost model.
+pub fn attribute(
+    storage: &Storage,
+    before: impl Fn(&LedgerKey) -> Option<SourceEntry>,
+    events: &[HostEvent],
+    budget: &Budget,
+    model: impl Fn() -> Budget,
+    ledger_sequence: u32,
+) -> Result<CostAttribution, String> {
+    let metering = Budget::default();
+    let cost = |charges: &[(ContractCostType, u32)]| {
+        let budget = model();
+        for (ty, bytes) in charges.iter().filter(|(_, bytes)| *bytes > 0) {
+            budget
+                .charge(*ty, Some(u64::from(*bytes)))
+                .map_err(|e| format!("{e:?}"))?;
+        }
+        Ok::<_, String>((
+            budget.get_cpu_insns_consumed().unwrap_or_default(),
+            budget.get_mem_bytes_consumed().unwrap_or_default(),
+        ))
+    };
+    let footprint = storage
+        .footprint
+        .0
+        .iter(&metering)
+        .map_err(|e| format!("{e:?}"))?;
+    let mut items = Vec::new();
+    for (key, access) in footprint {
+        let old = before(key);
+        let read_bytes = old.as_ref().map_or(Ok(0), |old| xdr_len(&old.entry))?;
+        let written = if *access == AccessType::ReadWrite {
+            storage
+                .map
+                .get::<LedgerKey>(key, &metering)
+                .map_err(|e| format!("{e:?}"))?
+                .and_then(Option::as_ref)
+                .map(|(entry, _)| xdr_len(entry.as_ref()))
+                .transpose()?
+                .unwrap_or(0)
+        } else {
+            0
+        };
+        let disk_read = resources::is_disk_read(key, old.as_ref(), ledger_sequence);
+        let (cpu_insns, mem_bytes) = cost(&[
+            (ContractCostType::ValDeser, read_bytes),
+            (ContractCostType::ValSer, written),
+        ])?;
+        items.push(StorageCost {
+            key: key
+                .to_xdr_base64(Limits::none())
+                .map_err(|e| e.to_string())?,
+            entry_type: key.name().to_string(),
+            access: match access {
+                AccessType::ReadOnly => "read_only",
+                AccessType::ReadWrite => "read_write",
+            },
+            read_bytes,
+            disk_read,
+            write_bytes: written,
+            cpu_insns,
+            mem_bytes,
+        });
+    }
+    items.sort_by(|a, b| {
+        b.cpu_insns
+            .cmp(&a.cpu_insns)
+            .then(b.read_bytes.cmp(&a.read_bytes))
+    });
+
+    let events = events
+        .iter()
+        .filter(|e| !e.failed_call && e.event.type_ != ContractEventType::Diagnostic)
+        .map(|e| {
+            let bytes = xdr_len(&e.event)?;
+            let (cpu_insns, mem_bytes) = cost(&[(ContractCostType::ValSer, bytes)])?;
+            Ok(EventCost {
+                event_type: match e.event.type_ {
+                    ContractEventType::System => "system",
+                    _ => "contract",
+                },
+                contract_id: e.event.contract_id.as_ref().map(ToString::to_string),
+                bytes,
+                cpu_insns,
+                mem_bytes,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    Ok(CostAttribution {
+        storage: items,
+        events,
+        cost_types: cost_types(budget),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::storage::{Footprint, FootprintMap, StorageMap};
+    use soroban_env_host::xdr::{
+        ContractDataDurability, ContractDataEntry, ContractEvent, ContractEventBody,
+        ContractEventV0, ContractId, ExtensionPoint, Hash, LedgerEntry, LedgerEntryData,
+        LedgerEntryExt, LedgerKeyContractData, ScAddress, ScVal,
+    };
+    use std::rc::Rc;
+
+    fn contract() -> ScAddress {
+        ScAddress::Contract(ContractId(Hash([7; 32])))
+    }
+
+    fn data_key(name: &str) -> LedgerKey {
+        LedgerKey::ContractData(LedgerKeyContractData {
+            contract: contract(),
+            key: ScVal::Symbol(name.try_into().unwrap()),
+            durability: ContractDataDurability::Persistent,
+        })
+    }
+
+    fn data_entry(name: &str, value: ScVal) -> LedgerEntry {
+        LedgerEntry {
+            last_modified_ledger_seq: 10,
+            data: LedgerEntryData::ContractData(ContractDataEntry {
+                ext: ExtensionPoint::V0,
+                contract: contract(),
+                key: ScVal::Symbol(name.try_into().unwrap()),
+                durability: ContractDataDurability::Persistent,
+                val: value,
+            }),
+            ext: LedgerEntryExt::V0,
+        }
+    }
+
+    #[test]
+    fn test_cost_types_lists_charged_types_by_cpu() {
+        let budget = Budget::default();
+        budget
+            .charge(ContractCostType::ValSer, Some(1_000))
+            .unwrap();
+        budget
+            .charge(ContractCostType::ComputeSha256Hash, Some(64))
+            .unwrap();
+        let charges = cost_types(&budget);
+        assert_eq!(charges.len(), 2);
+        assert!(charges[0].cpu_insns >= charges[1].cpu_insns);
+        assert!(charges.iter().any(|c| c.cost_type == "ValSer"));
+        assert_eq!(
+            charges.iter().map(|c| c.cpu_insns).sum::<u64>(),
+            budget.get_cpu_insns_consumed().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_attribute_itemizes_footprint_and_events() {
+        let budget = Budget::default();
+        let blob = ScVal::Bytes(vec![0; 512].try_into().unwrap());
+        // (key, access, before, after): a large entry that is only read, an
+        // archived entry that is restored and rewritten, and a new entry.
+        let cases = [
+            ("large", AccessType::ReadOnly, Some((blob, 100))),
+            ("archived", AccessType::ReadWrite, Some((ScVal::U32(1), 20))),
+            ("created", AccessType::ReadWrite, None),
+        ];
+        let mut footprint = Vec::new();
+        let mut map = Vec::new();
+        for (name, access, before) in &cases {
+            let key = Rc::new(data_key(name));
+            footprint.push((key.clone(), *access));
+            let after = match (name, before) {
+                (&"created", _) => Some((Rc::new(data_entry(name, ScVal::U32(3))), Some(200))),
+                (_, Some((value, _))) => {
+                    Some((Rc::new(data_entry(name, value.clone())), Some(200)))
+                }
+                (_, None) => None,
+            };
+            map.push((key, after));
+        }
+        footprint.sort_by(|a, b| a.0.cmp(&b.0));
+        map.sort_by(|a, b| a.0.cmp(&b.0));
+        let storage = Storage::with_enforcing_footprint_and_map(
+            Footprint(FootprintMap::from_map(footprint, &budget).unwrap()),
+            StorageMap::from_map(map, &budget).unwrap(),
+        );
+        let before = |key: &LedgerKey| {
+            let (name, _, before) = cases.iter().find(|(name, ..)| data_key(name) == *key)?;
+            before.clone().map(|(value, live_until)| SourceEntry {
+                entry: data_entry(name, value),
+                live_until: Some(live_until),
+            })
+        };
+        let event = |type_, failed_call| HostEvent {
+            event: ContractEvent {
+                ext: ExtensionPoint::V0,
+                contract_id: Some(ContractId(Hash([7; 32]))),
+                type_,
+                body: ContractEventBody::V0(ContractEventV0 {
+                    topics: Default::default(),
+                    data: ScVal::U32(1),
+                }),
+            },
+            failed_call,
+        };
+        let events = [
+            event(ContractEventType::Contract, false),
+            event(ContractEventType::Diagnostic, false),
+            event(ContractEventType::Contract, true),
+        ];
+
+        let attribution =
+            attribute(&storage, before, &events, &budget, Budget::default, 50).unwrap();
+        let item = |name: &str| {
+            let key = data_key(name).to_xdr_base64(Limits::none()).unwrap();
+            attribution
+                .storage
+                .iter()
+                .find(|item| item.key == key)
+                .unwrap()
+                .clone()
+        };
+
+        assert_eq!(attribution.storage[0], item("large"));
+        let large = item("large");
+        assert_eq!(large.access, "read_only");
+        assert!(large.read_bytes > 512);
+        assert_eq!(large.write_bytes, 0);
+        assert!(!large.disk_read);
+        let archived = item("archived");
+        assert!(archived.disk_read);
+        assert_eq!(archived.write_bytes, archived.read_bytes);
+        let created = item("created");
+        assert_eq!(created.read_bytes, 0);
+        assert!(created.write_bytes > 0 && created.cpu_insns > 0);
+
+        assert_eq!(attribution.events.len(), 1);
+        assert_eq!(attribution.events[0].event_type, "contract");
+        assert_eq!(
+            attribution.events[0].contract_id.as_deref(),
+            Some(contract().to_string().as_str())
+        );
+        assert!(attribution.events[0].bytes > 0);
+        assert!(!attribution.cost_types.is_empty());
+    }
+}
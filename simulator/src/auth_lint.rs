@@ -0,0 +1,264 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! First-pass missing-authorization lint over a simulated execution.
+//!
+//! An address normally authorizes changes to its own state: a token debits
+//! `from` only after `from.require_auth()`. [`missing_auth`] flags changes
+//! to an address's state that no authorization of that address covers:
+//!
+//! - `transfer` and `burn` events debiting an address, and
+//! - contract data entries written under a key that names an address, such
+//!   as `Balance(G...)`, unless the transaction only credited it.
+//!
+//! An address counts as authorized when it appears in the recorded (or
+//! enforced) authorization tree, or is a contract on the call stack, since
+//! contracts authorize their own calls. The lint is a heuristic for auditors
+//! to triage: allowances (`transfer_from`) and admin operations legitimately
+//! change state the owner did not authorize.
+
+use crate::pretty;
+use schemars::JsonSchema;
+use serde::Serialize;
+use soroban_env_host::events::HostEvent;
+use soroban_env_host::xdr::{
+    ContractEventBody, ContractEventType, ContractId, Hash, LedgerKey, ScAddress, ScVal,
+};
+use std::collections::HashSet;
+
+/// A change to an address's state without its authorization.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct AuthWarning {
+    /// Address whose state changed (`G...` or `C...`).
+    pub address: String,
+    /// Contract that changed it.
+    pub contract: String,
+    /// Function of the call that changed it, when diagnostic events tell.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub function: Option<String>,
+    /// What changed, e.g. `transfer of 100 from the address`.
+    pub change: String,
+}
+
+/// Data entry written by the transaction: its key and `create`, `update`
+/// or `delete`.
+pub type Write<'a> = (&'a LedgerKey, &'a str);
+
+/// Flags the changes in `events` and `writes` to addresses outside
+/// `authorized`.
+pub fn missing_auth(
+    events: &[HostEvent],
+    writes: &[Write<'_>],
+    authorized: &HashSet<ScAddress>,
+) -> Vec<AuthWarning> {
+    let mut warnings = Vec::new();
+    let mut push = |warning: AuthWarning| {
+        if !warnings.contains(&warning) {
+            warnings.push(warning);
+        }
+    };
+    // Contracts that were called, and those still executing.
+    let mut called = HashSet::new();
+    let mut stack: Vec<(ScAddress, String)> = Vec::new();
+    let mut credited = HashSet::new();
+    for event in events {
+        let ContractEventBody::V0(body) = &event.event.body;
+        let topics = body.topics.as_slice();
+        if event.event.type_ == ContractEventType::Diagnostic {
+            match topics {
+                [ScVal::Symbol(name), ScVal::Bytes(id), ScVal::Symbol(function)]
+                    if name.0.as_slice() == b"fn_call" =>
+                {
+                    if let Ok(id) = <[u8; 32]>::try_from(id.as_slice()) {
+                        let contract = ScAddress::Contract(ContractId(Hash(id)));
+                        called.insert(contract.clone());
+                        stack.push((contract, function.to_utf8_string_lossy()));
+                    }
+                }
+                [ScVal::Symbol(name), ScVal::Symbol(function)]
+                    if name.0.as_slice() == b"fn_return" =>
+                {
+                    let function = function.to_utf8_string_lossy();
+                    while let Some((_, returned)) = stack.pop() {
+                        if returned == function {
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+        if event.failed_call || event.event.type_ != ContractEventType::Contract {
+            continue;
+        }
+        let Some(contract_id) = &event.event.contract_id else {
+            continue;
+        };
+        let Some(ScVal::Symbol(name)) = topics.first() else {
+            continue;
+        };
+        let address = |index: usize| match topics.get(index) {
+            Some(ScVal::Address(address)) => Some(address),
+            _ => None,
+        };
+        let (name, from, to) = match name.0.as_slice() {
+            b"transfer" => ("transfer", address(1), address(2)),
+            b"burn" => ("burn", address(1), None),
+            // SACs name the admin before the recipient; the recipient is
+            // the last address either way.
+            b"mint" => (
+                "mint",
+                None,
+                topics.iter().rev().find_map(|topic| match topic {
+                    ScVal::Address(address) => Some(address),
+                    _ => None,
+                }),
+            ),
+            _ => continue,
+        };
+        if let Some(to) = to {
+            credited.insert(to.clone());
+        }
+        let Some(from) = from else {
+            continue;
+        };
+        if authorized.contains(from) || stack.iter().any(|(contract, _)| contract == from) {
+            continue;
+        }
+        push(AuthWarning {
+            address: from.to_string(),
+            contract: ScAddress::Contract(contract_id.clone()).to_string(),
+            function: stack.last().map(|(_, function)| function.clone()),
+            change: format!("{name} of {} from the address", pretty::value(&body.data)),
+        });
+    }
+
+    for (key, operation) in writes {
+        let LedgerKey::ContractData(data) = key else {
+            continue;
+        };
+        let mut addresses = Vec::new();
+        addresses_in(&data.key, &mut addresses);
+        for address in addresses {
+            if *address == data.contract
+                || authorized.contains(address)
+                || called.contains(address)
+                || credited.contains(address)
+            {
+                continue;
+            }
+            push(AuthWarning {
+                address: address.to_string(),
+                contract: data.contract.to_string(),
+                function: None,
+                change: format!("{operation} of entry {}", pretty::value(&data.key)),
+            });
+        }
+    }
+    warnings
+}
+
+/// Addresses anywhere in `value`.
+fn addresses_in<'a>(value: &'a ScVal, out: &mut Vec<&'a ScAddress>) {
+    match value {
+        ScVal::Address(address) => out.push(address),
+        ScVal::Vec(Some(items)) => items.iter().for_each(|item| addresses_in(item, out)),
+        ScVal::Map(Some(map)) => map.iter().for_each(|entry| {
+            addresses_in(&entry.key, out);
+            addresses_in(&entry.val, out);
+        }),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::xdr::{
+        AccountId, ContractDataDurability, ContractEvent, ContractEventV0, ExtensionPoint,
+        Int128Parts, LedgerKeyContractData, PublicKey, Uint256,
+    };
+
+    fn account(byte: u8) -> ScAddress {
+        ScAddress::Account(AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(
+            [byte; 32],
+        ))))
+    }
+
+    fn contract(byte: u8) -> ScAddress {
+        ScAddress::Contract(ContractId(Hash([byte; 32])))
+    }
+
+    fn transfer(token: u8, from: ScAddress, to: ScAddress) -> HostEvent {
+        HostEvent {
+            event: ContractEvent {
+                ext: ExtensionPoint::V0,
+                contract_id: Some(ContractId(Hash([token; 32]))),
+                type_: ContractEventType::Contract,
+                body: ContractEventBody::V0(ContractEventV0 {
+                    topics: vec![
+                        ScVal::Symbol("transfer".try_into().unwrap()),
+                        ScVal::Address(from),
+                        ScVal::Address(to),
+                    ]
+                    .try_into()
+                    .unwrap(),
+                    data: ScVal::I128(Int128Parts { hi: 0, lo: 100 }),
+                }),
+            },
+            failed_call: false,
+        }
+    }
+
+    fn balance_key(token: u8, holder: ScAddress) -> LedgerKey {
+        LedgerKey::ContractData(LedgerKeyContractData {
+            contract: contract(token),
+            key: ScVal::Vec(Some(
+                vec![
+                    ScVal::Symbol("Balance".try_into().unwrap()),
+                    ScVal::Address(holder),
+                ]
+                .try_into()
+                .unwrap(),
+            )),
+            durability: ContractDataDurability::Persistent,
+        })
+    }
+
+    #[test]
+    fn test_missing_auth_flags_unauthorized_debits_only() {
+        let events = [
+            transfer(9, account(1), account(2)),
+            transfer(9, account(3), account(2)),
+        ];
+        let keys = [
+            balance_key(9, account(1)),
+            balance_key(9, account(2)),
+            balance_key(9, account(3)),
+        ];
+        let writes = keys.iter().map(|key| (key, "update")).collect::<Vec<_>>();
+        let authorized = HashSet::from([account(1)]);
+
+        let warnings = missing_auth(&events, &writes, &authorized);
+        let flagged = account(3).to_string();
+        assert_eq!(
+            warnings
+                .iter()
+                .map(|w| (w.address.as_str(), w.change.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (flagged.as_str(), "transfer of 100 from the address"),
+                (
+                    flagged.as_str(),
+                    format!(
+                        "update of entry [Balance, {}]",
+                        pretty::address(&account(3))
+                    )
+                    .as_str()
+                ),
+            ]
+        );
+        assert_eq!(warnings[0].contract, contract(9).to_string());
+    }
+}
@@ -0,0 +1,488 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Soroban authorization for simulated invocations.
+//!
+//! By default, invocations run in recording auth mode, like soroban-rpc's
+//! `simulateTransaction`: every `require_auth` is recorded instead of
+//! checked. For each recorded authorization with address credentials,
+//! [`signature_payloads`] returns the unsigned `SorobanAuthorizationEntry`
+//! and the hash its signer must sign, so a wallet can prompt for signatures
+//! straight from the simulation result.
+//!
+//! When the request supplies signed entries, either in `auth_entries` or on
+//! the envelope's operation, the host enforces them instead: signatures,
+//! nonces and expiration ledgers are checked exactly as on submission.
+//! Supplied nonces are checked against the nonce entries in ledger state
+//! beforehand, so a replayed entry is reported as such rather than as a
+//! generic auth failure.
+
+use crate::network;
+use crate::simulation;
+use base64::Engine as _;
+use schemars::JsonSchema;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use soroban_env_host::auth::RecordedAuthPayload;
+use soroban_env_host::storage::SnapshotSource;
+use soroban_env_host::xdr::{
+    self, AccountId, ContractDataDurability, FeeBumpTransactionInnerTx, Hash, HashIdPreimage,
+    HashIdPreimageSorobanAuthorization, LedgerKey, LedgerKeyContractData, Limits, MuxedAccount,
+    Operation, OperationBody, PublicKey, ScAddress, ScNonceKey, ScVal, SorobanAddressCredentials,
+    SorobanAuthorizationEntry, SorobanCredentials, TransactionEnvelope, WriteXdr,
+};
+use std::collections::HashSet;
+use std::fmt;
+use std::rc::Rc;
+
+/// Ledgers, past the simulated one, until a requested signature expires.
+pub const SIGNATURE_EXPIRATION_LEDGERS: u32 = 100;
+
+/// An authorization that must be signed before submission.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct AuthPayload {
+    /// Address that must sign (`G...` account or `C...` contract).
+    pub address: String,
+    /// Nonce chosen for the authorization.
+    pub nonce: i64,
+    /// Last ledger at which the signature is valid.
+    pub signature_expiration_ledger: u32,
+    /// Base64 `SorobanAuthorizationEntry` with an empty signature, to be
+    /// filled in and attached to the operation.
+    pub entry_xdr: String,
+    /// Hex SHA-256 of the `HashIdPreimage::SorobanAuthorization` to sign.
+    pub payload_hash: String,
+}
+
+/// A nonce consumed by an authorization with address credentials.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct ConsumedNonce {
+    /// Address whose nonce it is.
+    pub address: String,
+    pub nonce: i64,
+}
+
+impl fmt::Display for ConsumedNonce {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "nonce {} of {}", self.nonce, self.address)
+    }
+}
+
+/// A source account, with the ID of a muxed (`M...`) address reported
+/// separately from the account it multiplexes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct SourceAccount {
+    /// Underlying account (`G...`): it signs, pays fees and holds balances.
+    pub account: String,
+    /// Muxed ID, e.g. an exchange's deposit ID.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub muxed_id: Option<u64>,
+    /// The `M...` address as given in the envelope.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub muxed_address: Option<String>,
+}
+
+impl From<&MuxedAccount> for SourceAccount {
+    fn from(source: &MuxedAccount) -> Self {
+        let muxed_id = match source {
+            MuxedAccount::Ed25519(_) => None,
+            MuxedAccount::MuxedEd25519(muxed) => Some(muxed.id),
+        };
+        Self {
+            account: account_id(source.clone()).to_string(),
+            muxed_id,
+            muxed_address: muxed_id.map(|_| source.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for SourceAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.muxed_address, self.muxed_id) {
+            (Some(address), Some(id)) => {
+                write!(f, "{address} ({}, muxed ID {id})", self.account)
+            }
+            _ => write!(f, "{}", self.account),
+        }
+    }
+}
+
+/// Returns the (inner) transaction's source account.
+pub fn transaction_source(envelope: &TransactionEnvelope) -> MuxedAccount {
+    match envelope {
+        TransactionEnvelope::TxV0(env) => {
+            MuxedAccount::Ed25519(env.tx.source_account_ed25519.clone())
+        }
+        TransactionEnvelope::Tx(env) => env.tx.source_account.clone(),
+        TransactionEnvelope::TxFeeBump(env) => match &env.tx.inner_tx {
+            FeeBumpTransactionInnerTx::Tx(inner) => inner.tx.source_account.clone(),
+        },
+    }
+}
+
+/// Returns the account that invokes the envelope's host function: the
+/// operation's source if set, otherwise the transaction's source. Muxed
+/// sources authorize as their underlying account.
+pub fn invoker(envelope: &TransactionEnvelope) -> AccountId {
+    let op_source = invoke_operation(envelope).and_then(|op| op.source_account.clone());
+    account_id(op_source.unwrap_or_else(|| transaction_source(envelope)))
+}
+
+/// Returns the authorization entries attached to the envelope's
+/// `InvokeHostFunction` operation.
+pub fn envelope_entries(envelope: &TransactionEnvelope) -> Vec<SorobanAuthorizationEntry> {
+    match invoke_operation(envelope).map(|op| &op.body) {
+        Some(OperationBody::InvokeHostFunction(invoke)) => invoke.auth.to_vec(),
+        _ => vec![],
+    }
+}
+
+fn invoke_operation(envelope: &TransactionEnvelope) -> Option<&Operation> {
+    simulation::envelope_operations(envelope)
+        .iter()
+        .find(|op| matches!(op.body, OperationBody::InvokeHostFunction(_)))
+}
+
+/// The account behind a (possibly muxed) source account.
+pub fn account_id(account: MuxedAccount) -> AccountId {
+    let key = match account {
+        MuxedAccount::Ed25519(key) => key,
+        MuxedAccount::MuxedEd25519(muxed) => muxed.ed25519,
+    };
+    AccountId(PublicKey::PublicKeyTypeEd25519(key))
+}
+
+/// Addresses whose authorization `entries` carry. Source account
+/// credentials authorize as `invoker`.
+pub fn credential_addresses(
+    entries: &[SorobanAuthorizationEntry],
+    invoker: &AccountId,
+) -> HashSet<ScAddress> {
+    entries
+        .iter()
+        .map(|entry| match &entry.credentials {
+            SorobanCredentials::Address(credentials) => credentials.address.clone(),
+            SorobanCredentials::SourceAccount => ScAddress::Account(invoker.clone()),
+        })
+        .collect()
+}
+
+/// Addresses the host recorded `require_auth` calls for. Payloads without
+/// an address authorize as `invoker`.
+pub fn recorded_addresses(
+    recorded: &[RecordedAuthPayload],
+    invoker: &AccountId,
+) -> HashSet<ScAddress> {
+    recorded
+        .iter()
+        .map(|payload| {
+            payload
+                .address
+                .clone()
+                .unwrap_or_else(|| ScAddress::Account(invoker.clone()))
+        })
+        .collect()
+}
+
+/// Returns the nonces consumed by `entries`, in order.
+pub fn consumed_nonces(entries: &[SorobanAuthorizationEntry]) -> Vec<ConsumedNonce> {
+    address_nonces(entries)
+        .map(|(address, nonce)| ConsumedNonce {
+            address: address.to_string(),
+            nonce,
+        })
+        .collect()
+}
+
+/// Returns the nonces in `entries` that have already been used: those with a
+/// live nonce entry in `state` as of `ledger_sequence`, and those repeated
+/// within `entries`. Nonce entries that cannot be read are left for the host
+/// to report.
+pub fn reused_nonces(
+    entries: &[SorobanAuthorizationEntry],
+    state: &impl SnapshotSource,
+    ledger_sequence: u32,
+) -> Vec<ConsumedNonce> {
+    let mut seen = HashSet::new();
+    address_nonces(entries)
+        .filter(|&(address, nonce)| {
+            let repeated = !seen.insert((address, nonce));
+            let recorded = matches!(
+                state.get(&Rc::new(nonce_key(address, nonce))),
+                Ok(Some((_, live_until))) if live_until.is_none_or(|l| l >= ledger_sequence)
+            );
+            repeated || recorded
+        })
+        .map(|(address, nonce)| ConsumedNonce {
+            address: address.to_string(),
+            nonce,
+        })
+        .collect()
+}
+
+fn address_nonces(
+    entries: &[SorobanAuthorizationEntry],
+) -> impl Iterator<Item = (&ScAddress, i64)> {
+    entries.iter().filter_map(|entry| match &entry.credentials {
+        SorobanCredentials::Address(credentials) => Some((&credentials.address, credentials.nonce)),
+        SorobanCredentials::SourceAccount => None,
+    })
+}
+
+/// The temporary entry the host writes when `address` consumes `nonce`.
+fn nonce_key(address: &ScAddress, nonce: i64) -> LedgerKey {
+    LedgerKey::ContractData(LedgerKeyContractData {
+        contract: address.clone(),
+        key: ScVal::LedgerKeyNonce(ScNonceKey { nonce }),
+        durability: ContractDataDurability::Temporary,
+    })
+}
+
+/// Builds the signature payloads for recorded authorizations.
+///
+/// Authorizations without address credentials (those covered by the
+/// transaction source account's signature) need no payload and are skipped.
+pub fn signature_payloads(
+    recorded: Vec<RecordedAuthPayload>,
+    network_id: [u8; 32],
+    signature_expiration_ledger: u32,
+) -> Result<Vec<AuthPayload>, xdr::Error> {
+    let b64 = base64::engine::general_purpose::STANDARD;
+    let mut payloads = Vec::new();
+    for payload in recorded {
+        let (Some(address), Some(nonce)) = (payload.address, payload.nonce) else {
+            continue;
+        };
+        let preimage = HashIdPreimage::SorobanAuthorization(HashIdPreimageSorobanAuthorization {
+            network_id: Hash(network_id),
+            nonce,
+            signature_expiration_ledger,
+            invocation: payload.invocation.clone(),
+        });
+        let entry = SorobanAuthorizationEntry {
+            credentials: SorobanCredentials::Address(SorobanAddressCredentials {
+                address: address.clone(),
+                nonce,
+                signature_expiration_ledger,
+                signature: ScVal::Void,
+            }),
+            root_invocation: payload.invocation,
+        };
+        payloads.push(AuthPayload {
+            address: address.to_string(),
+            nonce,
+            signature_expiration_ledger,
+            entry_xdr: b64.encode(entry.to_xdr(Limits::none())?),
+            payload_hash: network::hex(&Sha256::digest(preimage.to_xdr(Limits::none())?)),
+        });
+    }
+    Ok(payloads)
+}
+
+/// The authorization entries of `recorded`, unsigned, for the transaction
+/// to carry: address credentials valid until `signature_expiration_ledger`,
+/// and source account credentials for the rest.
+pub fn recorded_entries(
+    recorded: Vec<RecordedAuthPayload>,
+    signature_expiration_ledger: u32,
+) -> Vec<SorobanAuthorizationEntry> {
+    recorded
+        .into_iter()
+        .map(|payload| SorobanAuthorizationEntry {
+            credentials: match (payload.address, payload.nonce) {
+                (Some(address), Some(nonce)) => {
+                    SorobanCredentials::Address(SorobanAddressCredentials {
+                        address,
+                        nonce,
+                        signature_expiration_ledger,
+                        signature: ScVal::Void,
+                    })
+                }
+                _ => SorobanCredentials::SourceAccount,
+            },
+            root_invocation: payload.invocation,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::xdr::{
+        ContractId, InvokeContractArgs, ScAddress, SorobanAuthorizedFunction,
+        SorobanAuthorizedInvocation, Uint256,
+    };
+
+    fn invocation() -> SorobanAuthorizedInvocation {
+        SorobanAuthorizedInvocation {
+            function: SorobanAuthorizedFunction::ContractFn(InvokeContractArgs {
+                contract_address: ScAddress::Contract(ContractId(Hash([1; 32]))),
+                function_name: "transfer".try_into().unwrap(),
+                args: Default::default(),
+            }),
+            sub_invocations: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_payloads_only_for_address_credentials() {
+        let signer =
+            ScAddress::Account(AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([2; 32]))));
+        let recorded = vec![
+            RecordedAuthPayload {
+                address: None,
+                nonce: None,
+                invocation: invocation(),
+            },
+            RecordedAuthPayload {
+                address: Some(signer.clone()),
+                nonce: Some(7),
+                invocation: invocation(),
+            },
+        ];
+
+        let payloads = signature_payloads(recorded, [9; 32], 1_100).unwrap();
+        assert_eq!(payloads.len(), 1);
+        let payload = &payloads[0];
+        assert_eq!(payload.address, signer.to_string());
+        assert!(payload.address.starts_with('G'));
+        assert_eq!(
+            (payload.nonce, payload.signature_expiration_ledger),
+            (7, 1_100)
+        );
+
+        let expected = HashIdPreimage::SorobanAuthorization(HashIdPreimageSorobanAuthorization {
+            network_id: Hash([9; 32]),
+            nonce: 7,
+            signature_expiration_ledger: 1_100,
+            invocation: invocation(),
+        });
+        assert_eq!(
+            payload.payload_hash,
+            network::hex(&Sha256::digest(expected.to_xdr(Limits::none()).unwrap()))
+        );
+    }
+
+    #[test]
+    fn test_envelope_entries_and_invoker_come_from_invoke_operation() {
+        use soroban_env_host::xdr::{
+            HostFunction, InvokeHostFunctionOp, Memo, MuxedAccountMed25519, Preconditions,
+            SequenceNumber, Transaction, TransactionExt, TransactionV1Envelope,
+        };
+
+        let entry = SorobanAuthorizationEntry {
+            credentials: SorobanCredentials::SourceAccount,
+            root_invocation: invocation(),
+        };
+        let op = Operation {
+            source_account: Some(MuxedAccount::MuxedEd25519(MuxedAccountMed25519 {
+                id: 42,
+                ed25519: Uint256([4; 32]),
+            })),
+            body: OperationBody::InvokeHostFunction(InvokeHostFunctionOp {
+                host_function: HostFunction::UploadContractWasm(Default::default()),
+                auth: vec![entry.clone()].try_into().unwrap(),
+            }),
+        };
+        let envelope = TransactionEnvelope::Tx(TransactionV1Envelope {
+            tx: Transaction {
+                source_account: MuxedAccount::Ed25519(Uint256([3; 32])),
+                fee: 100,
+                seq_num: SequenceNumber(1),
+                cond: Preconditions::None,
+                memo: Memo::None,
+                operations: vec![op].try_into().unwrap(),
+                ext: TransactionExt::V0,
+            },
+            signatures: Default::default(),
+        });
+
+        assert_eq!(envelope_entries(&envelope), vec![entry]);
+        assert_eq!(
+            invoker(&envelope),
+            AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([4; 32])))
+        );
+    }
+
+    #[test]
+    fn test_source_account_splits_muxed_id() {
+        use soroban_env_host::xdr::MuxedAccountMed25519;
+
+        let plain = SourceAccount::from(&MuxedAccount::Ed25519(Uint256([6; 32])));
+        let muxed = SourceAccount::from(&MuxedAccount::MuxedEd25519(MuxedAccountMed25519 {
+            id: 1234,
+            ed25519: Uint256([6; 32]),
+        }));
+
+        assert_eq!(muxed.account, plain.account);
+        assert!(muxed.account.starts_with('G'));
+        assert_eq!((plain.muxed_id, plain.muxed_address), (None, None));
+        assert_eq!(muxed.muxed_id, Some(1234));
+        assert!(muxed.muxed_address.as_ref().unwrap().starts_with('M'));
+        assert!(muxed.to_string().ends_with("muxed ID 1234)"));
+    }
+
+    #[test]
+    fn test_reused_nonces_include_live_entries_and_repeats() {
+        use crate::snapshot::LedgerSnapshot;
+        use crate::source::{LayeredSource, SnapshotLedgerSource};
+        use soroban_env_host::xdr::{
+            ContractDataEntry, ExtensionPoint, LedgerEntry, LedgerEntryData, LedgerEntryExt,
+            LedgerKeyTtl, TtlEntry,
+        };
+
+        let signer =
+            ScAddress::Account(AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([2; 32]))));
+        let entry = |nonce| SorobanAuthorizationEntry {
+            credentials: SorobanCredentials::Address(SorobanAddressCredentials {
+                address: signer.clone(),
+                nonce,
+                signature_expiration_ledger: 200,
+                signature: ScVal::Void,
+            }),
+            root_invocation: invocation(),
+        };
+        let mut snapshot = LedgerSnapshot::new();
+        for (nonce, live_until) in [(1, 150), (2, 50)] {
+            let key = nonce_key(&signer, nonce);
+            let LedgerKey::ContractData(data_key) = &key else {
+                unreachable!()
+            };
+            let data = LedgerEntry {
+                last_modified_ledger_seq: 40,
+                data: LedgerEntryData::ContractData(ContractDataEntry {
+                    ext: ExtensionPoint::V0,
+                    contract: data_key.contract.clone(),
+                    key: data_key.key.clone(),
+                    durability: data_key.durability,
+                    val: ScVal::Void,
+                }),
+                ext: LedgerEntryExt::V0,
+            };
+            let key_hash = Hash(Sha256::digest(key.to_xdr(Limits::none()).unwrap()).into());
+            let ttl = LedgerEntry {
+                last_modified_ledger_seq: 40,
+                data: LedgerEntryData::Ttl(TtlEntry {
+                    key_hash: key_hash.clone(),
+                    live_until_ledger_seq: live_until,
+                }),
+                ext: LedgerEntryExt::V0,
+            };
+            snapshot.insert_entry(&key, data).unwrap();
+            snapshot
+                .insert_entry(&LedgerKey::Ttl(LedgerKeyTtl { key_hash }), ttl)
+                .unwrap();
+        }
+        let state = LayeredSource::new(SnapshotLedgerSource::new(snapshot), None, 100);
+
+        // Nonce 1 is live, nonce 2's entry has expired, nonce 3 is repeated.
+        let entries = [entry(1), entry(2), entry(3), entry(3)];
+        let reused = reused_nonces(&entries, &state, 100);
+        assert_eq!(
+            reused.iter().map(|n| n.nonce).collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+        assert_eq!(consumed_nonces(&entries).len(), 4);
+        assert_eq!(reused[0].to_string(), format!("nonce 1 of {signer}"));
+    }
+}
@@ -0,0 +1,339 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Token balance changes read from SEP-41 token events.
+//!
+//! Tokens, the Stellar Asset Contract (SAC) among them, announce every
+//! balance change with a `transfer`, `mint`, `burn` or `clawback` event.
+//! [`token_events`] decodes them, and `approve` events, from any contract;
+//! [`changes`] nets them per holder and token. SACs add the asset (`native`
+//! or `CODE:ISSUER`) as a last topic, which names the token.
+
+use crate::network;
+use schemars::JsonSchema;
+use serde::Serialize;
+use soroban_env_host::events::HostEvent;
+use soroban_env_host::xdr::{ContractEventBody, ContractEventType, ScAddress, ScVal};
+
+/// Net change of one holder's balance of one token.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct BalanceChange {
+    /// Holder (`G...` account or `C...` contract).
+    pub address: String,
+    /// Token contract (`C...`).
+    pub token: String,
+    /// SAC asset, `native` or `CODE:ISSUER`; absent for other tokens.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asset: Option<String>,
+    /// Signed amount in the token's smallest unit, as a decimal string.
+    pub amount: String,
+}
+
+/// A SEP-41 token event with its topics and data decoded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct TokenEvent {
+    /// `transfer`, `mint`, `burn`, `clawback` or `approve`.
+    pub kind: &'static str,
+    /// Token contract (`C...`).
+    pub token: String,
+    /// SAC asset, `native` or `CODE:ISSUER`; absent for other tokens.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asset: Option<String>,
+    /// Holder whose balance goes down, or for `approve` the owner of the
+    /// allowance.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    /// Holder whose balance goes up, or for `approve` the spender.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    /// Amount in the token's smallest unit, as a decimal string.
+    pub amount: String,
+    /// Muxed ID of the recipient, since protocol 23.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_muxed_id: Option<String>,
+    /// Ledger an `approve`d allowance expires at.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiration_ledger: Option<u32>,
+}
+
+/// The token events among the contract events of calls that succeeded,
+/// whichever contract emitted them, in emission order.
+pub fn token_events(events: &[HostEvent]) -> Vec<TokenEvent> {
+    events
+        .iter()
+        .filter_map(token_event)
+        .map(|(event, _)| event)
+        .collect()
+}
+
+/// Net balance changes announced by the contract events of calls that
+/// succeeded, in the order the holders were first affected.
+pub fn changes(events: &[HostEvent]) -> Vec<BalanceChange> {
+    let mut totals: Vec<(String, String, Option<String>, i128)> = Vec::new();
+    let mut add = |address: &str, token: &str, asset: &Option<String>, amount: i128| match totals
+        .iter_mut()
+        .find(|(a, t, ..)| a == address && t == token)
+    {
+        Some(total) => total.3 = total.3.saturating_add(amount),
+        None => totals.push((
+            address.to_string(),
+            token.to_string(),
+            asset.clone(),
+            amount,
+        )),
+    };
+    for (event, amount) in events.iter().filter_map(token_event) {
+        if event.kind == "approve" {
+            continue;
+        }
+        if let Some(from) = &event.from {
+            add(from, &event.token, &event.asset, amount.saturating_neg());
+        }
+        if let Some(to) = &event.to {
+            add(to, &event.token, &event.asset, amount);
+        }
+    }
+    totals
+        .into_iter()
+        .filter(|(.., amount)| *amount != 0)
+        .map(|(address, token, asset, amount)| BalanceChange {
+            address,
+            token,
+            asset,
+            amount: amount.to_string(),
+        })
+        .collect()
+}
+
+/// Decodes `event` if it is a token event of a call that succeeded, along
+/// with its amount.
+fn token_event(event: &HostEvent) -> Option<(TokenEvent, i128)> {
+    if event.failed_call || event.event.type_ != ContractEventType::Contract {
+        return None;
+    }
+    let contract_id = event.event.contract_id.as_ref()?;
+    let ContractEventBody::V0(body) = &event.event.body;
+    let topics = body.topics.as_slice();
+    let Some(ScVal::Symbol(name)) = topics.first() else {
+        return None;
+    };
+    let address = |index: usize| match topics.get(index) {
+        Some(ScVal::Address(address)) => Some(address.to_string()),
+        _ => None,
+    };
+    let mut expiration_ledger = None;
+    let (kind, from, to, amount) = match name.0.as_slice() {
+        b"transfer" => ("transfer", address(1), address(2), amount(&body.data)?),
+        // Before protocol 23 the admin preceded the holder.
+        b"mint" => (
+            "mint",
+            None,
+            address(2).or_else(|| address(1)),
+            amount(&body.data)?,
+        ),
+        b"clawback" => (
+            "clawback",
+            address(2).or_else(|| address(1)),
+            None,
+            amount(&body.data)?,
+        ),
+        b"burn" => ("burn", address(1), None, amount(&body.data)?),
+        b"approve" => {
+            // The data is `[amount, expiration_ledger]`.
+            let ScVal::Vec(Some(data)) = &body.data else {
+                return None;
+            };
+            let [value, ScVal::U32(ledger)] = data.as_slice() else {
+                return None;
+            };
+            expiration_ledger = Some(*ledger);
+            ("approve", address(1), address(2), amount(value)?)
+        }
+        _ => return None,
+    };
+    if from.is_none() && to.is_none() {
+        return None;
+    }
+    let asset = match topics.last() {
+        Some(ScVal::String(asset)) => Some(asset.0.to_utf8_string_lossy()),
+        _ => None,
+    };
+    let event = TokenEvent {
+        kind,
+        token: ScAddress::Contract(contract_id.clone()).to_string(),
+        asset,
+        from,
+        to,
+        amount: amount.to_string(),
+        to_muxed_id: muxed_id(&body.data),
+        expiration_ledger,
+    };
+    Some((event, amount))
+}
+
+/// The amount of a token event: an `i128`, or since protocol 23 a map with
+/// an `amount` and the recipient's muxed ID.
+fn amount(data: &ScVal) -> Option<i128> {
+    match data {
+        ScVal::I128(parts) => Some((i128::from(parts.hi) << 64) | i128::from(parts.lo)),
+        ScVal::Map(Some(map)) => map.iter().find_map(|entry| match &entry.key {
+            ScVal::Symbol(key) if key.0.as_slice() == b"amount" => amount(&entry.val),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+/// The recipient's muxed ID in the map data of a token event: a `u64`,
+/// bytes (as hex) or a string.
+fn muxed_id(data: &ScVal) -> Option<String> {
+    let ScVal::Map(Some(map)) = data else {
+        return None;
+    };
+    map.iter().find_map(|entry| match (&entry.key, &entry.val) {
+        (ScVal::Symbol(key), value) if key.0.as_slice() == b"to_muxed_id" => match value {
+            ScVal::U64(id) => Some(id.to_string()),
+            ScVal::Bytes(bytes) => Some(network::hex(bytes.as_slice())),
+            ScVal::String(s) => Some(s.0.to_utf8_string_lossy()),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::xdr::{
+        AccountId, ContractEvent, ContractEventV0, ContractId, ExtensionPoint, Hash, Int128Parts,
+        PublicKey, ScMapEntry, ScString, Uint256,
+    };
+
+    fn account(seed: u8) -> ScAddress {
+        ScAddress::Account(AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(
+            [seed; 32],
+        ))))
+    }
+
+    fn event(topics: Vec<ScVal>, amount: i128, failed_call: bool) -> HostEvent {
+        HostEvent {
+            event: ContractEvent {
+                ext: ExtensionPoint::V0,
+                contract_id: Some(ContractId(Hash([7; 32]))),
+                type_: ContractEventType::Contract,
+                body: ContractEventBody::V0(ContractEventV0 {
+                    topics: topics.try_into().unwrap(),
+                    data: ScVal::I128(Int128Parts {
+                        hi: (amount >> 64) as i64,
+                        lo: amount as u64,
+                    }),
+                }),
+            },
+            failed_call,
+        }
+    }
+
+    #[test]
+    fn test_token_events_decode_approve_and_muxed_transfers() {
+        let symbol = |s: &str| ScVal::Symbol(s.try_into().unwrap());
+        let (alice, bob) = (account(1), account(2));
+        let mut approve = event(
+            vec![
+                symbol("approve"),
+                ScVal::Address(alice.clone()),
+                ScVal::Address(bob.clone()),
+            ],
+            0,
+            false,
+        );
+        let mut transfer = event(
+            vec![
+                symbol("transfer"),
+                ScVal::Address(alice.clone()),
+                ScVal::Address(bob.clone()),
+            ],
+            0,
+            false,
+        );
+        let ContractEventBody::V0(body) = &mut approve.event.body;
+        body.data = ScVal::Vec(Some(
+            vec![ScVal::I128(Int128Parts { hi: 0, lo: 50 }), ScVal::U32(900)]
+                .try_into()
+                .unwrap(),
+        ));
+        let ContractEventBody::V0(body) = &mut transfer.event.body;
+        body.data = ScVal::Map(Some(
+            vec![
+                ScMapEntry {
+                    key: symbol("amount"),
+                    val: ScVal::I128(Int128Parts { hi: 0, lo: 25 }),
+                },
+                ScMapEntry {
+                    key: symbol("to_muxed_id"),
+                    val: ScVal::U64(42),
+                },
+            ]
+            .try_into()
+            .unwrap(),
+        ));
+        let unknown = event(vec![symbol("swap")], 1, false);
+
+        let events = token_events(&[approve, transfer, unknown]);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].kind, "approve");
+        assert_eq!(events[0].from, Some(alice.to_string()));
+        assert_eq!(events[0].to, Some(bob.to_string()));
+        assert_eq!(events[0].amount, "50");
+        assert_eq!(events[0].expiration_ledger, Some(900));
+        assert_eq!(events[1].kind, "transfer");
+        assert_eq!(events[1].amount, "25");
+        assert_eq!(events[1].to_muxed_id.as_deref(), Some("42"));
+        assert_eq!(events[1].asset, None);
+    }
+
+    #[test]
+    fn test_changes_net_token_events() {
+        let symbol = |s: &str| ScVal::Symbol(s.try_into().unwrap());
+        let native = ScVal::String(ScString("native".try_into().unwrap()));
+        let (alice, bob, admin) = (account(1), account(2), account(3));
+        let events = [
+            event(
+                vec![
+                    symbol("transfer"),
+                    ScVal::Address(alice.clone()),
+                    ScVal::Address(bob.clone()),
+                    native.clone(),
+                ],
+                100,
+                false,
+            ),
+            event(
+                vec![
+                    symbol("mint"),
+                    ScVal::Address(admin.clone()),
+                    ScVal::Address(alice.clone()),
+                    native.clone(),
+                ],
+                30,
+                false,
+            ),
+            event(
+                vec![symbol("burn"), ScVal::Address(bob.clone()), native],
+                5,
+                true,
+            ),
+        ];
+
+        let changes = changes(&events);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].address, alice.to_string());
+        assert_eq!(changes[0].amount, "-70");
+        assert_eq!(changes[0].asset.as_deref(), Some("native"));
+        assert_eq!(changes[1].address, bob.to_string());
+        assert_eq!(changes[1].amount, "100");
+        assert_eq!(
+            changes[1].token,
+            ScAddress::Contract(ContractId(Hash([7; 32]))).to_string()
+        );
+    }
+}
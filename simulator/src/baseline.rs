@@ -0,0 +1,214 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Cost baselines of scenario runs, for gating contract changes on fee
+//! regressions.
+//!
+//! `erst-sim baseline record` runs a scenario and stores the CPU
+//! instructions and memory bytes of each step in a JSON baseline, which is
+//! committed next to the contract. `erst-sim baseline check` runs the
+//! scenario again and fails when a step costs more than the baseline by
+//! over the threshold percentage.
+
+use crate::scenario::ScenarioOutcome;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Format version of baseline files.
+pub const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Baseline {
+    pub version: u32,
+    pub scenario: String,
+    pub steps: Vec<StepBaseline>,
+}
+
+/// Recorded cost of one scenario step.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepBaseline {
+    pub step: String,
+    /// Top-level call, e.g. `CBXK…WXYZ::deposit`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub function: Option<String>,
+    pub cpu_instructions: u64,
+    pub memory_bytes: u64,
+}
+
+/// Result of checking a run against a baseline.
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub threshold_percent: f64,
+    /// Every baseline step ran and none regressed beyond the threshold.
+    pub passed: bool,
+    pub comparisons: Vec<Comparison>,
+    /// Baseline steps the run did not execute, or that failed.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<String>,
+    /// Steps the baseline does not have.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub new: Vec<String>,
+}
+
+/// One cost of one step, before and after.
+#[derive(Debug, Serialize)]
+pub struct Comparison {
+    pub step: String,
+    /// `cpu_instructions` or `memory_bytes`.
+    pub metric: &'static str,
+    pub baseline: u64,
+    pub current: u64,
+    /// Change relative to the baseline; positive is more expensive.
+    pub change_percent: f64,
+    pub regressed: bool,
+}
+
+/// The baseline of `outcome`: the costs of the steps that ran.
+pub fn record(outcome: &ScenarioOutcome) -> Baseline {
+    Baseline {
+        version: VERSION,
+        scenario: outcome.name.clone(),
+        steps: outcome
+            .steps
+            .iter()
+            .filter_map(|step| {
+                let cost = step.cost.as_ref()?;
+                Some(StepBaseline {
+                    step: step.name.clone(),
+                    function: cost.function.clone(),
+                    cpu_instructions: cost.cpu_instructions,
+                    memory_bytes: cost.memory_bytes,
+                })
+            })
+            .collect(),
+    }
+}
+
+/// Compares the costs of `outcome` with `baseline`. A cost regresses when it
+/// exceeds the baseline by more than `threshold_percent`.
+pub fn check(baseline: &Baseline, outcome: &ScenarioOutcome, threshold_percent: f64) -> Check {
+    let current = record(outcome);
+    let mut comparisons = Vec::new();
+    let mut missing = Vec::new();
+    for before in &baseline.steps {
+        let Some(after) = current.steps.iter().find(|s| s.step == before.step) else {
+            missing.push(before.step.clone());
+            continue;
+        };
+        for (metric, baseline, current) in [
+            (
+                "cpu_instructions",
+                before.cpu_instructions,
+                after.cpu_instructions,
+            ),
+            ("memory_bytes", before.memory_bytes, after.memory_bytes),
+        ] {
+            let change_percent = if baseline == 0 {
+                if current == 0 {
+                    0.0
+                } else {
+                    100.0
+                }
+            } else {
+                (current as f64 - baseline as f64) * 100.0 / baseline as f64
+            };
+            comparisons.push(Comparison {
+                step: before.step.clone(),
+                metric,
+                baseline,
+                current,
+                change_percent,
+                regressed: change_percent > threshold_percent,
+            });
+        }
+    }
+    let new = current
+        .steps
+        .iter()
+        .filter(|after| !baseline.steps.iter().any(|s| s.step == after.step))
+        .map(|after| after.step.clone())
+        .collect();
+    Check {
+        threshold_percent,
+        passed: missing.is_empty() && !comparisons.iter().any(|c| c.regressed),
+        comparisons,
+        missing,
+        new,
+    }
+}
+
+pub fn load(path: &Path) -> Result<Baseline, String> {
+    let file =
+        std::fs::read(path).map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let baseline: Baseline = serde_json::from_slice(&file)
+        .map_err(|e| format!("Invalid baseline {}: {e}", path.display()))?;
+    if baseline.version != VERSION {
+        return Err(format!(
+            "Unsupported baseline version {} in {}; expected {VERSION}",
+            baseline.version,
+            path.display()
+        ));
+    }
+    Ok(baseline)
+}
+
+pub fn save(path: &Path, baseline: &Baseline) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(baseline).map_err(|e| e.to_string())?;
+    std::fs::write(path, json + "\n")
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenario::{StepCost, StepOutcome};
+    use std::time::Duration;
+
+    fn outcome(costs: &[(&str, u64, u64)]) -> ScenarioOutcome {
+        ScenarioOutcome {
+            name: "vault".to_string(),
+            passed: true,
+            steps: costs
+                .iter()
+                .map(|(name, cpu, mem)| StepOutcome {
+                    name: name.to_string(),
+                    status: "success".to_string(),
+                    error: None,
+                    assertions: vec![],
+                    expired: vec![],
+                    duration: Duration::ZERO,
+                    cost: Some(StepCost {
+                        function: None,
+                        cpu_instructions: *cpu,
+                        memory_bytes: *mem,
+                    }),
+                    findings: vec![],
+                })
+                .collect(),
+            storage_growth: vec![],
+        }
+    }
+
+    #[test]
+    fn test_check_flags_regressions_beyond_threshold() {
+        let baseline = record(&outcome(&[("deposit", 1000, 500), ("withdraw", 2000, 800)]));
+        let check = super::check(
+            &baseline,
+            &outcome(&[("deposit", 1040, 560), ("claim", 10, 10)]),
+            5.0,
+        );
+
+        assert!(!check.passed);
+        let changes = check
+            .comparisons
+            .iter()
+            .map(|c| (c.metric, c.change_percent.round() as i64, c.regressed))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![("cpu_instructions", 4, false), ("memory_bytes", 12, true)]
+        );
+        assert_eq!(check.missing, vec!["withdraw"]);
+        assert_eq!(check.new, vec!["claim"]);
+    }
+}
@@ -0,0 +1,275 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Benchmarks of the simulator itself, for capacity planning.
+//!
+//! `erst-sim bench --request req.json --iterations 1000 --jobs 8` simulates
+//! the request over and over on `jobs` threads sharing one state source,
+//! like concurrent clients of `serve`, and reports latency percentiles,
+//! throughput and what the simulations allocated. Allocations are counted
+//! by [`CountingAlloc`], the binary's global allocator.
+//!
+//! `erst-sim bench --snapshot snapshot.json` instead times decoding the
+//! snapshot's entries with the scalar and SIMD base64 decoders on one
+//! thread, and on all of them (see [`snapshot`]).
+
+use crate::memory::MemoryLimits;
+use crate::simulation;
+use crate::snapshot::{simd, Decoder, LedgerSnapshot, SnapshotError};
+use crate::source::cache::CachedSource;
+use crate::types::SimulationRequest;
+use serde::Serialize;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+static PEAK_LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// The system allocator, counting allocations and live bytes.
+pub struct CountingAlloc;
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new = System.realloc(ptr, layout, new_size);
+        if !new.is_null() {
+            LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+            allocated(new_size);
+        }
+        new
+    }
+}
+
+fn allocated(size: usize) {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    ALLOCATED_BYTES.fetch_add(size, Ordering::Relaxed);
+    let live = LIVE_BYTES.fetch_add(size, Ordering::Relaxed) + size;
+    PEAK_LIVE_BYTES.fetch_max(live, Ordering::Relaxed);
+}
+
+#[derive(Debug, Serialize)]
+pub struct BenchReport {
+    pub iterations: usize,
+    pub jobs: usize,
+    /// Simulations that returned an error instead of a response.
+    pub errors: usize,
+    pub wall_ms: f64,
+    /// Simulations per second over the wall time.
+    pub throughput_per_sec: f64,
+    pub latency_ms: Latency,
+    pub allocations: Allocations,
+}
+
+/// Per-simulation latency in milliseconds.
+#[derive(Debug, Serialize)]
+pub struct Latency {
+    pub min: f64,
+    pub mean: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+/// Heap allocations of the whole process during the benchmark.
+#[derive(Debug, Serialize)]
+pub struct Allocations {
+    pub count: usize,
+    pub bytes: usize,
+    pub count_per_iteration: usize,
+    pub bytes_per_iteration: usize,
+    /// Most heap in use at once, including what was live before the run.
+    pub peak_live_bytes: usize,
+}
+
+/// Simulates `request` `iterations` times on `jobs` threads.
+pub fn run(
+    request: &SimulationRequest,
+    limits: &MemoryLimits,
+    remote: Option<Arc<CachedSource>>,
+    iterations: usize,
+    jobs: usize,
+) -> BenchReport {
+    let jobs = jobs.clamp(1, iterations.max(1));
+    let next = AtomicUsize::new(0);
+    let errors = AtomicUsize::new(0);
+    let latencies = Mutex::new(Vec::with_capacity(iterations));
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed);
+    PEAK_LIVE_BYTES.store(LIVE_BYTES.load(Ordering::Relaxed), Ordering::Relaxed);
+    let started = Instant::now();
+    std::thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| {
+                let mut own = Vec::new();
+                while next.fetch_add(1, Ordering::Relaxed) < iterations {
+                    let run_started = Instant::now();
+                    let result = simulation::run(request, limits, None, remote.clone(), None);
+                    own.push(run_started.elapsed());
+                    if result.is_err() {
+                        errors.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+                if let Ok(mut latencies) = latencies.lock() {
+                    latencies.extend(own);
+                }
+            });
+        }
+    });
+    let wall = started.elapsed();
+    let count = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes;
+    let mut latencies = latencies.into_inner().unwrap_or_default();
+    latencies.sort_unstable();
+    BenchReport {
+        iterations,
+        jobs,
+        errors: errors.into_inner(),
+        wall_ms: millis(wall),
+        throughput_per_sec: if wall.is_zero() {
+            0.0
+        } else {
+            iterations as f64 / wall.as_secs_f64()
+        },
+        latency_ms: latency(&latencies),
+        allocations: Allocations {
+            count,
+            bytes,
+            count_per_iteration: count / iterations.max(1),
+            bytes_per_iteration: bytes / iterations.max(1),
+            peak_live_bytes: PEAK_LIVE_BYTES.load(Ordering::Relaxed),
+        },
+    }
+}
+
+/// Time to decode a snapshot's entries.
+#[derive(Debug, Serialize)]
+pub struct SnapshotReport {
+    pub entries: usize,
+    pub iterations: usize,
+    /// The decoder snapshots are loaded with: `simd` when the binary is
+    /// built with `simd-base64` and the CPU has AVX2, `scalar` otherwise.
+    pub decoder: &'static str,
+    pub threads: usize,
+    /// Mean decoding time on one thread with the scalar decoder.
+    pub sequential_ms: f64,
+    /// Mean decoding time on one thread with the SIMD decoder, if the
+    /// binary and the CPU support it.
+    pub simd_ms: Option<f64>,
+    /// `sequential_ms` over `simd_ms`.
+    pub simd_speedup: Option<f64>,
+    /// Mean decoding time on `threads` threads with `decoder`.
+    pub parallel_ms: f64,
+    /// `sequential_ms` over `parallel_ms`.
+    pub speedup: f64,
+}
+
+/// Decodes `entries` `iterations` times on one thread with the scalar
+/// decoder and, where available, the SIMD one, then as many times on every
+/// available thread with the decoder snapshots are loaded with.
+pub fn snapshot(
+    entries: &HashMap<String, String>,
+    iterations: usize,
+) -> Result<SnapshotReport, SnapshotError> {
+    let iterations = iterations.max(1);
+    let threads = std::thread::available_parallelism().map_or(1, usize::from);
+    let decoder = Decoder::best();
+    let time = |threads: usize, decoder: Decoder| -> Result<f64, SnapshotError> {
+        let started = Instant::now();
+        for _ in 0..iterations {
+            LedgerSnapshot::from_base64_map_on(entries, threads, decoder)?;
+        }
+        Ok(millis(started.elapsed()) / iterations as f64)
+    };
+    let sequential_ms = time(1, Decoder::Scalar)?;
+    let simd_ms = match simd::available() {
+        true => Some(time(1, Decoder::Simd)?),
+        false => None,
+    };
+    let parallel_ms = time(threads, decoder)?;
+    Ok(SnapshotReport {
+        entries: entries.len(),
+        iterations,
+        decoder: decoder.name(),
+        threads,
+        sequential_ms,
+        simd_ms,
+        simd_speedup: simd_ms.map(|simd_ms| ratio(sequential_ms, simd_ms)),
+        parallel_ms,
+        speedup: ratio(sequential_ms, parallel_ms),
+    })
+}
+
+fn ratio(before: f64, after: f64) -> f64 {
+    if after > 0.0 {
+        before / after
+    } else {
+        0.0
+    }
+}
+
+/// Summary of `sorted` latencies.
+fn latency(sorted: &[Duration]) -> Latency {
+    let total = sorted.iter().sum::<Duration>();
+    Latency {
+        min: sorted.first().copied().map_or(0.0, millis),
+        mean: if sorted.is_empty() {
+            0.0
+        } else {
+            millis(total) / sorted.len() as f64
+        },
+        p50: percentile(sorted, 50.0),
+        p95: percentile(sorted, 95.0),
+        p99: percentile(sorted, 99.0),
+        max: sorted.last().copied().map_or(0.0, millis),
+    }
+}
+
+/// Nearest-rank percentile of `sorted`, in milliseconds.
+fn percentile(sorted: &[Duration], percent: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (percent / 100.0 * sorted.len() as f64).ceil() as usize;
+    millis(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_percentiles_use_nearest_rank() {
+        let sorted = (1..=100).map(Duration::from_millis).collect::<Vec<_>>();
+        let latency = latency(&sorted);
+        assert_eq!(latency.min, 1.0);
+        assert_eq!(latency.p50, 50.0);
+        assert_eq!(latency.p95, 95.0);
+        assert_eq!(latency.p99, 99.0);
+        assert_eq!(latency.max, 100.0);
+        assert!((latency.mean - 50.5).abs() < 1e-9);
+        assert_eq!(percentile(&sorted[..1], 99.0), 1.0);
+        assert_eq!(percentile(&[], 50.0), 0.0);
+    }
+}
@@ -0,0 +1,201 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Reproducers for failed simulations.
+//!
+//! `erst-sim --bundle failure.json < request.json` writes, when the
+//! simulation fails, one file holding what it takes to reproduce the failure
+//! elsewhere: the request, the ledger entries the simulation read, the
+//! versions of the simulator and host, and the full response with its
+//! diagnostics. `jq .request failure.json | erst-sim` replays it.
+//!
+//! The bundled request carries the entries the failed run read, including
+//! those fetched from RPC, in place of the request's `ledger_entries`, and is
+//! pinned to the ledger sequence and close time it was simulated at, so it
+//! no longer needs `rpc_url`. A request rejected before the host ran is
+//! bundled with its own entries.
+
+use crate::hosts;
+use crate::source::{self, LayeredSource};
+use crate::types::{ResponseMeta, SimulationRequest, SimulationResponse};
+use serde::Serialize;
+use serde_json::Value;
+use soroban_env_host::xdr::{
+    LedgerEntry, LedgerEntryData, LedgerEntryExt, LedgerKey, Limits, TtlEntry, WriteXdr,
+};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// A failed simulation, packed to be replayed elsewhere.
+#[derive(Debug, Serialize)]
+pub struct Bundle<'a> {
+    /// Versions of the simulator and host that failed.
+    pub versions: ResponseMeta,
+    /// The request, reduced to the ledger entries the simulation read.
+    pub request: Value,
+    pub response: &'a SimulationResponse,
+}
+
+/// Bundles the failed `response` to the request made of `fields`, every
+/// field but `ledger_entries`, and `request`, the same request parsed.
+pub fn bundle<'a>(
+    fields: &Value,
+    request: &SimulationRequest,
+    response: &'a SimulationResponse,
+) -> Bundle<'a> {
+    let mut document = fields.clone();
+    if let Value::Object(fields) = &mut document {
+        let entries = if response.read_entries.is_empty() {
+            request
+                .ledger_entries
+                .iter()
+                .flatten()
+                .collect::<BTreeMap<_, _>>()
+        } else {
+            fields.remove("rpc_url");
+            fields.remove("horizon_url");
+            response.read_entries.iter().collect()
+        };
+        let entries = entries
+            .into_iter()
+            .map(|(key, entry)| (key.clone(), Value::from(entry.as_str())))
+            .collect();
+        fields.insert("ledger_entries".into(), Value::Object(entries));
+        // The network may come from a settings file the bundle goes without.
+        if let Some(network) = &request.network {
+            fields
+                .entry("network")
+                .or_insert_with(|| Value::from(network.as_str()));
+        }
+        if let Some(sequence) = response.ledger_sequence {
+            fields.insert("ledger_sequence".into(), sequence.into());
+        }
+        if let Some(close_time) = response.ledger_close_time {
+            fields.insert("ledger_close_time".into(), close_time.into());
+        }
+    }
+    Bundle {
+        versions: hosts::response_meta(),
+        request: document,
+        response,
+    }
+}
+
+/// Writes `bundle` to `path` as JSON.
+pub fn write(path: &Path, bundle: &Bundle) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(bundle).map_err(|e| e.to_string())?;
+    std::fs::write(path, json).map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+/// The entries behind every read of `state`, with their TTLs, as base64 key
+/// to base64 entry.
+pub fn read_entries(state: &LayeredSource) -> BTreeMap<String, String> {
+    let mut entries = BTreeMap::new();
+    let mut add = |key: &LedgerKey, entry: &LedgerEntry| {
+        if let (Ok(key), Ok(entry)) = (
+            key.to_xdr_base64(Limits::none()),
+            entry.to_xdr_base64(Limits::none()),
+        ) {
+            entries.insert(key, entry);
+        }
+    };
+    for key in state.reads() {
+        let Ok(Some(found)) = state.peek(&key) else {
+            continue;
+        };
+        add(&key, &found.entry);
+        if let (Some(live_until), Ok(LedgerKey::Ttl(ttl))) =
+            (found.live_until, source::ttl_key(&key))
+        {
+            let entry = LedgerEntry {
+                last_modified_ledger_seq: found.entry.last_modified_ledger_seq,
+                data: LedgerEntryData::Ttl(TtlEntry {
+                    key_hash: ttl.key_hash.clone(),
+                    live_until_ledger_seq: live_until,
+                }),
+                ext: LedgerEntryExt::V0,
+            };
+            add(&LedgerKey::Ttl(ttl), &entry);
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation;
+    use crate::snapshot::LedgerSnapshot;
+    use crate::source::SnapshotLedgerSource;
+    use serde_json::json;
+    use soroban_env_host::xdr::{
+        ContractDataDurability, ContractDataEntry, ContractId, ExtensionPoint, Hash,
+        LedgerKeyContractData, ScAddress, ScVal,
+    };
+
+    fn data(key: u32) -> (LedgerKey, LedgerEntry) {
+        let contract = ScAddress::Contract(ContractId(Hash([1; 32])));
+        let key = ScVal::U32(key);
+        let entry = LedgerEntry {
+            last_modified_ledger_seq: 7,
+            data: LedgerEntryData::ContractData(ContractDataEntry {
+                ext: ExtensionPoint::V0,
+                contract: contract.clone(),
+                key: key.clone(),
+                durability: ContractDataDurability::Persistent,
+                val: ScVal::Bool(true),
+            }),
+            ext: LedgerEntryExt::V0,
+        };
+        let key = LedgerKey::ContractData(LedgerKeyContractData {
+            contract,
+            key,
+            durability: ContractDataDurability::Persistent,
+        });
+        (key, entry)
+    }
+
+    #[test]
+    fn test_bundles_the_entries_a_failure_read() {
+        let (read, read_entry) = data(1);
+        let (unread, unread_entry) = data(2);
+        let mut snapshot = LedgerSnapshot::new();
+        snapshot.insert_entry(&read, read_entry).unwrap();
+        snapshot.insert_entry(&unread, unread_entry).unwrap();
+        let state = LayeredSource::new(SnapshotLedgerSource::new(snapshot), None, 7);
+        state.read(&read).unwrap();
+
+        let mut response = simulation::error_response("HostError".to_string());
+        response.read_entries = read_entries(&state);
+        // The entry and its TTL.
+        assert_eq!(response.read_entries.len(), 2);
+        assert!(response
+            .read_entries
+            .contains_key(&read.to_xdr_base64(Limits::none()).unwrap()));
+        response.ledger_sequence = Some(7);
+
+        let fields = json!({
+            "envelope_xdr": "AAAA",
+            "rpc_url": "https://rpc.example",
+            "timestamp": "",
+        });
+        let request = serde_json::from_value::<SimulationRequest>(json!({
+            "envelope_xdr": "AAAA",
+            "result_meta_xdr": "",
+            "enable_optimization_advisor": false,
+            "timestamp": "",
+        }))
+        .unwrap();
+        let bundled = bundle(&fields, &request, &response);
+        assert_eq!(bundled.request["ledger_sequence"], 7);
+        assert!(bundled.request.get("rpc_url").is_none());
+        assert_eq!(
+            bundled.request["ledger_entries"].as_object().unwrap().len(),
+            2
+        );
+        assert_eq!(
+            bundled.versions.simulator_version,
+            env!("CARGO_PKG_VERSION")
+        );
+    }
+}
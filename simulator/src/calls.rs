@@ -0,0 +1,483 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Contract call tree rebuilt from diagnostic events.
+//!
+//! In debug mode the host brackets every contract call with an `fn_call`
+//! event (topics: `fn_call`, the called contract's ID, the function; data:
+//! the arguments) and an `fn_return` event carrying the result. A call that
+//! fails has no `fn_return`, so its frame closes when its caller's does.
+//!
+//! Those events are also the boundaries at which [`attribute`] splits the
+//! budget between calls, given what the budget had charged at each.
+
+use crate::pretty;
+use schemars::JsonSchema;
+use serde::Serialize;
+use soroban_env_host::events::HostEvent;
+use soroban_env_host::xdr::{
+    ContractEventBody, ContractEventType, ContractId, Hash, ScAddress, ScVal,
+};
+
+/// A contract call and the calls it made, in order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct Call {
+    /// Called contract (`C...`).
+    pub contract: String,
+    pub function: String,
+    /// Arguments, rendered like `pretty_invocations`.
+    pub args: Vec<String>,
+    /// Return value; absent if the call failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+    /// For a failed call, the first error the host reported in it, like
+    /// `Error(Contract(3))`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The name of a contract error in the spec of the contract.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_case: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub calls: Vec<Call>,
+    /// Budget the call consumed, when the request asked for `call_costs`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost: Option<CallCost>,
+}
+
+/// CPU instructions and memory bytes charged to the budget.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct Charged {
+    pub cpu_instructions: u64,
+    pub memory_bytes: u64,
+}
+
+impl Charged {
+    fn saturating_sub(self, other: Charged) -> Charged {
+        Charged {
+            cpu_instructions: self.cpu_instructions.saturating_sub(other.cpu_instructions),
+            memory_bytes: self.memory_bytes.saturating_sub(other.memory_bytes),
+        }
+    }
+}
+
+/// Budget consumed by a call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct CallCost {
+    /// Everything charged between the call and its return.
+    pub inclusive: Charged,
+    /// `inclusive` less the inclusive cost of the calls it made.
+    pub own: Charged,
+}
+
+/// A call boundary: an `fn_call` or `fn_return` diagnostic event.
+pub enum Boundary<'a> {
+    Call {
+        /// Called contract (`C...`).
+        contract: String,
+        function: String,
+        args: &'a ScVal,
+    },
+    Return {
+        function: String,
+        result: &'a ScVal,
+    },
+}
+
+/// The call boundary `event` marks, if any.
+pub fn boundary(event: &HostEvent) -> Option<Boundary<'_>> {
+    if event.event.type_ != ContractEventType::Diagnostic {
+        return None;
+    }
+    let ContractEventBody::V0(body) = &event.event.body;
+    match body.topics.as_slice() {
+        [ScVal::Symbol(name), ScVal::Bytes(id), ScVal::Symbol(function)]
+            if name.0.as_slice() == b"fn_call" =>
+        {
+            let id = <[u8; 32]>::try_from(id.as_slice()).ok()?;
+            Some(Boundary::Call {
+                contract: ScAddress::Contract(ContractId(Hash(id))).to_string(),
+                function: function.to_utf8_string_lossy(),
+                args: &body.data,
+            })
+        }
+        [ScVal::Symbol(name), ScVal::Symbol(function)] if name.0.as_slice() == b"fn_return" => {
+            Some(Boundary::Return {
+                function: function.to_utf8_string_lossy(),
+                result: &body.data,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// The error an `error` diagnostic event reports, if `event` is one.
+fn error(event: &HostEvent) -> Option<&ScVal> {
+    if event.event.type_ != ContractEventType::Diagnostic {
+        return None;
+    }
+    let ContractEventBody::V0(body) = &event.event.body;
+    match body.topics.as_slice() {
+        [ScVal::Symbol(name), error @ ScVal::Error(_), ..] if name.0.as_slice() == b"error" => {
+            Some(error)
+        }
+        _ => None,
+    }
+}
+
+/// Rebuilds the calls of a transaction from its events. Top-level calls
+/// come first in the result, one per contract invocation.
+pub fn call_tree(events: &[HostEvent]) -> Vec<Call> {
+    let mut roots = Vec::new();
+    let mut stack: Vec<Call> = Vec::new();
+    for event in events {
+        match boundary(event) {
+            Some(Boundary::Call {
+                contract,
+                function,
+                args,
+            }) => {
+                // The host sends one argument as is and several as a vector.
+                let args = match args {
+                    ScVal::Void => Vec::new(),
+                    ScVal::Vec(Some(args)) => args.iter().map(pretty::value).collect(),
+                    arg => vec![pretty::value(arg)],
+                };
+                stack.push(Call {
+                    contract,
+                    function,
+                    args,
+                    result: None,
+                    error: None,
+                    error_case: None,
+                    calls: Vec::new(),
+                    cost: None,
+                });
+            }
+            Some(Boundary::Return { function, result }) => {
+                // Frames above the returning one failed without returning.
+                while let Some(mut call) = stack.pop() {
+                    let returned = call.function == function;
+                    if returned {
+                        call.result = Some(pretty::value(result));
+                        // It recovered from whatever error it reported.
+                        call.error = None;
+                    }
+                    attach(&mut stack, &mut roots, call);
+                    if returned {
+                        break;
+                    }
+                }
+            }
+            None => {
+                let Some(error) = error(event) else {
+                    continue;
+                };
+                // The host reports errors in the contract it was running.
+                let contract = event
+                    .event
+                    .contract_id
+                    .clone()
+                    .map(|id| ScAddress::Contract(id).to_string());
+                let frame = match stack
+                    .iter()
+                    .rposition(|call| Some(&call.contract) == contract.as_ref())
+                {
+                    Some(index) => stack.get_mut(index),
+                    None => stack.last_mut(),
+                };
+                if let Some(frame) = frame.filter(|frame| frame.error.is_none()) {
+                    frame.error = Some(pretty::value(error));
+                }
+            }
+        }
+    }
+    while let Some(call) = stack.pop() {
+        attach(&mut stack, &mut roots, call);
+    }
+    roots
+}
+
+/// Sets the `error_case` of the failed calls in `tree` that report a
+/// contract error, from `name`, which names error `code` of `contract`.
+pub fn name_errors(tree: &mut [Call], name: &mut impl FnMut(&str, u32) -> Option<String>) {
+    for call in tree {
+        let code = call.error.as_deref().and_then(|error| {
+            error
+                .strip_prefix("Error(Contract(")?
+                .strip_suffix("))")?
+                .parse()
+                .ok()
+        });
+        call.error_case = code.and_then(|code| name(&call.contract, code));
+        name_errors(&mut call.calls, name);
+    }
+}
+
+/// The calls without a return along the last path of `tree`, outermost
+/// first, each like `CBXK…WXYZ::swap`: the call stack of a run stopped
+/// mid-execution.
+pub fn open_frames(tree: &[Call]) -> Vec<String> {
+    let mut frames = Vec::new();
+    let mut calls = tree;
+    while let Some(call) = calls.last().filter(|call| call.result.is_none()) {
+        frames.push(format!(
+            "{}::{}",
+            pretty::short(&call.contract),
+            call.function
+        ));
+        calls = &call.calls;
+    }
+    frames
+}
+
+/// Number of call boundaries in `tree`: its `fn_call` and `fn_return`
+/// events, which come in the order of a depth-first walk.
+pub fn boundaries(tree: &[Call]) -> usize {
+    tree.iter()
+        .map(|call| 1 + usize::from(call.result.is_some()) + boundaries(&call.calls))
+        .sum()
+}
+
+/// Sets the cost of every call in `tree` from `marks`, what the budget had
+/// charged at each of its [`boundaries`] followed by the total. A failed
+/// call ends at the boundary after its last.
+pub fn attribute(tree: &mut [Call], marks: &[Charged]) {
+    fn walk(calls: &mut [Call], marks: &[Charged], next: &mut usize) -> Charged {
+        let mark = |i: usize| marks.get(i).or(marks.last()).copied().unwrap_or_default();
+        let mut total = Charged::default();
+        for call in calls {
+            let start = mark(*next);
+            *next += 1;
+            let inner = walk(&mut call.calls, marks, next);
+            let end = mark(*next);
+            if call.result.is_some() {
+                *next += 1;
+            }
+            let inclusive = end.saturating_sub(start);
+            call.cost = Some(CallCost {
+                inclusive,
+                own: inclusive.saturating_sub(inner),
+            });
+            total.cpu_instructions += inclusive.cpu_instructions;
+            total.memory_bytes += inclusive.memory_bytes;
+        }
+        total
+    }
+    walk(tree, marks, &mut 0);
+}
+
+/// A call into a contract that was already on the call stack.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct Reentrancy {
+    /// Re-entered contract (`C...`).
+    pub contract: String,
+    /// Calls from the top-level one to the re-entering one, each like
+    /// `CBXK…WXYZ::swap`.
+    pub path: Vec<String>,
+}
+
+/// Calls in `tree` into a contract already on their own call stack, a
+/// contract calling itself included.
+pub fn reentrancy(tree: &[Call]) -> Vec<Reentrancy> {
+    fn walk<'a>(call: &'a Call, stack: &mut Vec<&'a Call>, found: &mut Vec<Reentrancy>) {
+        if stack.iter().any(|caller| caller.contract == call.contract) {
+            found.push(Reentrancy {
+                contract: call.contract.clone(),
+                path: stack
+                    .iter()
+                    .chain(std::iter::once(&call))
+                    .map(|call| format!("{}::{}", pretty::short(&call.contract), call.function))
+                    .collect(),
+            });
+        }
+        stack.push(call);
+        for inner in &call.calls {
+            walk(inner, stack, found);
+        }
+        stack.pop();
+    }
+    let mut found = Vec::new();
+    for call in tree {
+        walk(call, &mut Vec::new(), &mut found);
+    }
+    found
+}
+
+fn attach(stack: &mut [Call], roots: &mut Vec<Call>, call: Call) {
+    match stack.last_mut() {
+        Some(parent) => parent.calls.push(call),
+        None => roots.push(call),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::xdr::{ContractEvent, ContractEventV0, ExtensionPoint, ScBytes, ScError};
+
+    fn event(topics: Vec<ScVal>, data: ScVal) -> HostEvent {
+        HostEvent {
+            event: ContractEvent {
+                ext: ExtensionPoint::V0,
+                contract_id: None,
+                type_: ContractEventType::Diagnostic,
+                body: ContractEventBody::V0(ContractEventV0 {
+                    topics: topics.try_into().unwrap(),
+                    data,
+                }),
+            },
+            failed_call: false,
+        }
+    }
+
+    fn symbol(s: &str) -> ScVal {
+        ScVal::Symbol(s.try_into().unwrap())
+    }
+
+    fn fn_call(id: u8, function: &str, data: ScVal) -> HostEvent {
+        let id = ScVal::Bytes(ScBytes(vec![id; 32].try_into().unwrap()));
+        event(vec![symbol("fn_call"), id, symbol(function)], data)
+    }
+
+    fn fn_return(function: &str, data: ScVal) -> HostEvent {
+        event(vec![symbol("fn_return"), symbol(function)], data)
+    }
+
+    #[test]
+    fn test_call_tree_nests_calls_and_closes_failed_frames() {
+        let events = [
+            fn_call(1, "swap", ScVal::U32(5)),
+            fn_call(2, "transfer", ScVal::Void),
+            fn_return("transfer", ScVal::Void),
+            fn_call(3, "price", ScVal::Void),
+            fn_call(4, "fail", ScVal::Void),
+            fn_return("price", ScVal::U32(7)),
+            fn_return("swap", ScVal::Bool(true)),
+            fn_call(1, "swap", ScVal::Void),
+        ];
+
+        let tree = call_tree(&events);
+        assert_eq!(tree.len(), 2);
+        let swap = &tree[0];
+        assert_eq!(swap.function, "swap");
+        assert_eq!(swap.args, vec!["5"]);
+        assert_eq!(swap.result.as_deref(), Some("true"));
+        assert_eq!(
+            swap.calls
+                .iter()
+                .map(|c| c.function.as_str())
+                .collect::<Vec<_>>(),
+            vec!["transfer", "price"]
+        );
+        let price = &swap.calls[1];
+        assert_eq!(price.result.as_deref(), Some("7"));
+        assert_eq!(price.calls[0].function, "fail");
+        assert_eq!(price.calls[0].result, None);
+        assert_eq!(tree[1].result, None);
+        assert!(reentrancy(&tree).is_empty());
+        assert_eq!(
+            open_frames(&tree),
+            vec![format!("{}::swap", pretty::short(&tree[1].contract))]
+        );
+        assert!(open_frames(&tree[..1]).is_empty());
+    }
+
+    #[test]
+    fn test_failed_calls_keep_their_error() {
+        let error = |id: u8, code: u32| {
+            let mut event = event(
+                vec![symbol("error"), ScVal::Error(ScError::Contract(code))],
+                ScVal::Void,
+            );
+            event.event.contract_id = Some(ContractId(Hash([id; 32])));
+            event
+        };
+        let events = [
+            fn_call(1, "__check_auth", ScVal::Void),
+            fn_call(2, "try_limit", ScVal::Void),
+            error(2, 4),
+            fn_return("try_limit", ScVal::Void),
+            fn_call(2, "enforce", ScVal::Void),
+            error(2, 3),
+            // The error escalates through the caller.
+            error(1, 3),
+        ];
+
+        let mut tree = call_tree(&events);
+        let check_auth = &tree[0];
+        assert_eq!(check_auth.error.as_deref(), Some("Error(Contract(3))"));
+        assert_eq!(check_auth.calls[0].error, None);
+        assert_eq!(
+            check_auth.calls[1].error.as_deref(),
+            Some("Error(Contract(3))")
+        );
+
+        name_errors(&mut tree, &mut |contract, code| {
+            (contract == address(2) && code == 3).then(|| "OverLimit".to_string())
+        });
+        assert_eq!(tree[0].error_case, None);
+        assert_eq!(tree[0].calls[1].error_case.as_deref(), Some("OverLimit"));
+    }
+
+    fn address(id: u8) -> String {
+        ScAddress::Contract(ContractId(Hash([id; 32]))).to_string()
+    }
+
+    #[test]
+    fn test_attribute_splits_own_and_inclusive_costs() {
+        let events = [
+            fn_call(1, "route", ScVal::Void),
+            fn_call(2, "swap", ScVal::Void),
+            fn_return("swap", ScVal::Void),
+            fn_call(3, "swap", ScVal::Void),
+            fn_return("route", ScVal::Void),
+        ];
+        let mut tree = call_tree(&events);
+        assert_eq!(boundaries(&tree), 5);
+        let marks = [10, 20, 50, 60, 90, 100].map(|cpu| Charged {
+            cpu_instructions: cpu,
+            memory_bytes: cpu * 2,
+        });
+
+        attribute(&mut tree, &marks);
+        let cost = |call: &Call| call.cost.unwrap();
+        let route = cost(&tree[0]);
+        assert_eq!(route.inclusive.cpu_instructions, 80);
+        assert_eq!(route.inclusive.memory_bytes, 160);
+        // The failed swap ends where its caller returns.
+        assert_eq!(cost(&tree[0].calls[0]).inclusive.cpu_instructions, 30);
+        assert_eq!(cost(&tree[0].calls[1]).inclusive.cpu_instructions, 30);
+        assert_eq!(route.own.cpu_instructions, 20);
+        assert_eq!(
+            cost(&tree[0].calls[1]).own,
+            cost(&tree[0].calls[1]).inclusive
+        );
+    }
+
+    #[test]
+    fn test_reentrancy_reports_the_path() {
+        let events = [
+            fn_call(1, "swap", ScVal::Void),
+            fn_call(2, "transfer", ScVal::Void),
+            fn_call(1, "on_transfer", ScVal::Void),
+            fn_return("on_transfer", ScVal::Void),
+            fn_return("transfer", ScVal::Void),
+            fn_return("swap", ScVal::Void),
+        ];
+
+        let found = reentrancy(&call_tree(&events));
+        assert_eq!(found.len(), 1);
+        let first = pretty::short(&found[0].contract);
+        assert_eq!(
+            found[0].path,
+            vec![
+                format!("{first}::swap"),
+                format!(
+                    "{}::transfer",
+                    pretty::short(&call_tree(&events)[0].calls[0].contract)
+                ),
+                format!("{first}::on_transfer"),
+            ]
+        );
+    }
+}
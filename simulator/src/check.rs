@@ -0,0 +1,270 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Validation of a request without simulating it.
+//!
+//! `erst-sim --check < request.json` checks a request as far as it can
+//! without running the host and reports every problem at once, so pipelines
+//! can lint generated requests cheaply. Beyond the shape and base64 checks
+//! every request gets (see [`schema::validate_request`]), it decodes each
+//! XDR field, checks that each ledger entry matches its key, and checks the
+//! envelope's footprint: every key once, and only the kinds of entry a
+//! Soroban transaction can access.
+
+use crate::protocol;
+use crate::schema::{self, FieldError};
+use crate::simulation;
+use crate::snapshot::{decode_ledger_entry, decode_ledger_key, entry_key};
+use crate::types::SimulationRequest;
+use crate::xdr_limits;
+use base64::Engine as _;
+use serde::Serialize;
+use serde_json::Value;
+use soroban_env_host::xdr::{
+    LedgerKey, ReadXdr, ScVal, SorobanAuthorizationEntry, TransactionResultMeta,
+};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CheckReport {
+    /// Whether the request has no problems.
+    pub valid: bool,
+    pub problems: Vec<FieldError>,
+}
+
+/// Checks the request document `request`.
+pub fn check(request: &Value) -> CheckReport {
+    let problems = match schema::validate_request(request) {
+        // Content checks only make sense once the shape is right.
+        Err(problems) => problems,
+        Ok(()) => match serde_json::from_value::<SimulationRequest>(request.clone()) {
+            Ok(request) => check_content(&request),
+            Err(e) => vec![FieldError {
+                path: String::new(),
+                message: e.to_string(),
+            }],
+        },
+    };
+    CheckReport {
+        valid: problems.is_empty(),
+        problems,
+    }
+}
+
+/// Problems with the XDR of a well-formed `request`.
+fn check_content(request: &SimulationRequest) -> Vec<FieldError> {
+    let mut problems = Vec::new();
+    let mut problem = |path: String, message: String| problems.push(FieldError { path, message });
+
+    let envelope_field = if request.invoke.is_some() {
+        "invoke"
+    } else {
+        "envelope_xdr"
+    };
+    match simulation::envelope(request) {
+        Ok(envelope) => {
+            if let Some(data) = simulation::soroban_data(&envelope) {
+                let footprint = &data.resources.footprint;
+                let keys = [
+                    ("read_only", &footprint.read_only),
+                    ("read_write", &footprint.read_write),
+                ];
+                let mut seen = HashMap::new();
+                for (list, keys) in keys {
+                    for (index, key) in keys.iter().enumerate() {
+                        let path = format!("{envelope_field}.footprint.{list}[{index}]");
+                        if !footprint_kind(key) {
+                            problem(
+                                path.clone(),
+                                format!("{} keys cannot be in a Soroban footprint", key.name()),
+                            );
+                        }
+                        match seen.get(key) {
+                            Some(first) => problem(path, format!("duplicates {first}")),
+                            None => {
+                                seen.insert(key, format!("{list}[{index}]"));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Err(e) => problem(envelope_field.to_string(), e.to_string()),
+    }
+
+    if !request.result_meta_xdr.is_empty() {
+        if let Err(e) = decode::<TransactionResultMeta>(&request.result_meta_xdr) {
+            problem("result_meta_xdr".to_string(), e);
+        }
+    }
+    if let Some(entries) = &request.ledger_entries {
+        let mut entries = entries.iter().collect::<Vec<_>>();
+        entries.sort();
+        for (key_xdr, entry_xdr) in entries {
+            let path = format!("ledger_entries[{}]", Value::from(key_xdr.as_str()));
+            match (decode_ledger_key(key_xdr), decode_ledger_entry(entry_xdr)) {
+                (Ok(key), Ok(entry)) if entry_key(&entry) != key => problem(
+                    path,
+                    format!(
+                        "{} key does not match its {} entry",
+                        key.name(),
+                        entry.data.name()
+                    ),
+                ),
+                (Ok(_), Ok(_)) => {}
+                (key, entry) => {
+                    for e in [key.err(), entry.err()].into_iter().flatten() {
+                        problem(path.clone(), e.to_string());
+                    }
+                }
+            }
+        }
+    }
+    for (index, entry) in request.auth_entries.iter().flatten().enumerate() {
+        if let Err(e) = decode::<SorobanAuthorizationEntry>(entry) {
+            problem(format!("auth_entries[{index}]"), e);
+        }
+    }
+    for (index, arg) in request.invoke.iter().flat_map(|i| &i.args).enumerate() {
+        if let Err(e) = decode::<ScVal>(arg) {
+            problem(format!("invoke.args[{index}]"), e);
+        }
+    }
+    for (field, config) in [
+        ("protocol", &request.protocol),
+        ("compare_protocol", &request.compare_protocol),
+    ] {
+        if let Some(Err(e)) = config.as_ref().map(|config| protocol::budget(Some(config))) {
+            problem(field.to_string(), e.to_string());
+        }
+    }
+    for (index, key) in request.fault_injection.drop_entries.iter().enumerate() {
+        if let Err(e) = decode_ledger_key(key) {
+            problem(
+                format!("fault_injection.drop_entries[{index}]"),
+                e.to_string(),
+            );
+        }
+    }
+    problems
+}
+
+/// Whether a Soroban transaction's footprint can hold `key`.
+fn footprint_kind(key: &LedgerKey) -> bool {
+    matches!(
+        key,
+        LedgerKey::Account(_)
+            | LedgerKey::Trustline(_)
+            | LedgerKey::ContractData(_)
+            | LedgerKey::ContractCode(_)
+    )
+}
+
+/// Decodes base64 XDR `value` under the request limits.
+fn decode<T: ReadXdr>(value: &str) -> Result<T, String> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(value)
+        .map_err(|e| format!("invalid base64: {e}"))?;
+    xdr_limits::read::<T>(&bytes).map_err(|e| format!("invalid XDR: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use soroban_env_host::xdr::{
+        ContractDataDurability, ContractId, Hash, LedgerEntry, LedgerEntryData, LedgerEntryExt,
+        LedgerFootprint, LedgerKeyContractData, LedgerKeyOffer, Limits, Memo, MuxedAccount,
+        Preconditions, PublicKey, ScAddress, SequenceNumber, SorobanResources,
+        SorobanTransactionData, SorobanTransactionDataExt, Transaction, TransactionEnvelope,
+        TransactionExt, TransactionV1Envelope, TtlEntry, Uint256, WriteXdr,
+    };
+
+    fn data_key() -> LedgerKey {
+        LedgerKey::ContractData(LedgerKeyContractData {
+            contract: ScAddress::Contract(ContractId(Hash([3; 32]))),
+            key: ScVal::U32(1),
+            durability: ContractDataDurability::Persistent,
+        })
+    }
+
+    fn base64(value: &impl WriteXdr) -> String {
+        value.to_xdr_base64(Limits::none()).unwrap()
+    }
+
+    #[test]
+    fn test_reports_every_problem_at_once() {
+        let offer = LedgerKey::Offer(LedgerKeyOffer {
+            seller_id: soroban_env_host::xdr::AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(
+                [1; 32],
+            ))),
+            offer_id: 1,
+        });
+        let envelope = TransactionEnvelope::Tx(TransactionV1Envelope {
+            tx: Transaction {
+                source_account: MuxedAccount::Ed25519(Uint256([1; 32])),
+                fee: 100,
+                seq_num: SequenceNumber(1),
+                cond: Preconditions::None,
+                memo: Memo::None,
+                operations: Default::default(),
+                ext: TransactionExt::V1(SorobanTransactionData {
+                    ext: SorobanTransactionDataExt::V0,
+                    resources: SorobanResources {
+                        footprint: LedgerFootprint {
+                            read_only: vec![data_key(), offer].try_into().unwrap(),
+                            read_write: vec![data_key()].try_into().unwrap(),
+                        },
+                        instructions: 0,
+                        disk_read_bytes: 0,
+                        write_bytes: 0,
+                    },
+                    resource_fee: 0,
+                }),
+            },
+            signatures: Default::default(),
+        });
+        // A TTL entry filed under a contract data key.
+        let ttl = LedgerEntry {
+            last_modified_ledger_seq: 1,
+            data: LedgerEntryData::Ttl(TtlEntry {
+                key_hash: Hash([0; 32]),
+                live_until_ledger_seq: 10,
+            }),
+            ext: LedgerEntryExt::V0,
+        };
+        let request = json!({
+            "envelope_xdr": base64(&envelope),
+            "result_meta_xdr": "",
+            "enable_optimization_advisor": false,
+            "timestamp": "",
+            "ledger_entries": { base64(&data_key()): base64(&ttl) },
+            "auth_entries": ["AAAA"],
+        });
+
+        let report = check(&request);
+        assert!(!report.valid);
+        let problems = report
+            .problems
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(problems.len(), 4, "{problems:?}");
+        assert!(
+            problems[0].starts_with("envelope_xdr.footprint.read_only[1]: Offer keys cannot be in")
+        );
+        assert_eq!(
+            problems[1],
+            "envelope_xdr.footprint.read_write[0]: duplicates read_only[0]"
+        );
+        assert!(problems[2].ends_with("ContractData key does not match its Ttl entry"));
+        assert!(problems[3].starts_with("auth_entries[0]: invalid XDR"));
+
+        let shape = check(&json!({ "envelope_xdr": 5 }));
+        assert!(!shape.valid);
+        assert!(shape
+            .problems
+            .iter()
+            .any(|problem| problem.path == "envelope_xdr"));
+    }
+}
@@ -0,0 +1,290 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Compact binary encodings of the stdin protocol.
+//!
+//! Besides JSON, the simulator reads requests and writes responses as CBOR or
+//! MessagePack (`--input-format`, `--output-format`). Both formats carry byte
+//! strings natively, so XDR can be sent as raw bytes instead of base64 inside
+//! JSON, roughly halving the payload.
+//!
+//! Binary documents are converted to and from the JSON data model, so the
+//! field names, schema validation and error messages are the same in every
+//! format:
+//! - On input, every byte string (including map keys, for `ledger_entries`)
+//!   is read as the base64 string JSON would carry. Text strings are still
+//!   accepted, so base64 XDR keeps working.
+//! - On output, the base64 XDR fields listed in [`BINARY_FIELDS`] are written
+//!   as byte strings.
+
+use base64::Engine as _;
+use serde_json::{Map, Number, Value};
+
+/// Response fields holding base64 XDR, written as byte strings.
+const BINARY_FIELDS: &[&str] = &["entry_xdr"];
+
+/// Encoding of the stdin request or the stdout response.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    #[default]
+    Json,
+    Cbor,
+    Msgpack,
+}
+
+/// Decodes a request document into the JSON data model.
+pub fn decode(format: Format, bytes: &[u8]) -> Result<Value, String> {
+    match format {
+        Format::Json => serde_json::from_slice(bytes).map_err(|e| format!("Invalid JSON: {e}")),
+        Format::Cbor => ciborium::from_reader::<ciborium::Value, _>(bytes)
+            .map_err(|e| e.to_string())
+            .and_then(from_cbor)
+            .map_err(|e| format!("Invalid CBOR: {e}")),
+        Format::Msgpack => rmpv::decode::read_value(&mut &bytes[..])
+            .map_err(|e| e.to_string())
+            .and_then(from_msgpack)
+            .map_err(|e| format!("Invalid MessagePack: {e}")),
+    }
+}
+
+/// Encodes a response document.
+pub fn encode<T: serde::Serialize>(format: Format, document: &T) -> Result<Vec<u8>, String> {
+    let value = || serde_json::to_value(document).map_err(|e| e.to_string());
+    let mut out = Vec::new();
+    match format {
+        Format::Json => serde_json::to_writer(&mut out, document).map_err(|e| e.to_string())?,
+        Format::Cbor => {
+            ciborium::into_writer(&to_cbor(value()?, false), &mut out).map_err(|e| e.to_string())?
+        }
+        Format::Msgpack => rmpv::encode::write_value(&mut out, &to_msgpack(value()?, false))
+            .map_err(|e| e.to_string())?,
+    }
+    Ok(out)
+}
+
+fn base64(bytes: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+/// The bytes of a base64 string in a binary field, if it decodes.
+fn binary(value: &Value, is_binary: bool) -> Option<Vec<u8>> {
+    match value {
+        Value::String(s) if is_binary => base64::engine::general_purpose::STANDARD.decode(s).ok(),
+        _ => None,
+    }
+}
+
+fn integer(value: i128) -> Result<Value, String> {
+    if let Ok(v) = u64::try_from(value) {
+        Ok(Value::from(v))
+    } else {
+        i64::try_from(value)
+            .map(Value::from)
+            .map_err(|_| format!("integer {value} out of range"))
+    }
+}
+
+fn float(value: f64) -> Result<Value, String> {
+    Number::from_f64(value)
+        .map(Value::Number)
+        .ok_or_else(|| format!("non-finite number {value}"))
+}
+
+fn from_cbor(value: ciborium::Value) -> Result<Value, String> {
+    use ciborium::Value as C;
+    Ok(match value {
+        C::Null => Value::Null,
+        C::Bool(b) => Value::Bool(b),
+        C::Integer(i) => integer(i.into())?,
+        C::Float(f) => float(f)?,
+        C::Text(s) => Value::String(s),
+        C::Bytes(b) => Value::String(base64(&b)),
+        C::Tag(_, inner) => from_cbor(*inner)?,
+        C::Array(items) => {
+            Value::Array(items.into_iter().map(from_cbor).collect::<Result<_, _>>()?)
+        }
+        C::Map(entries) => {
+            let mut map = Map::new();
+            for (key, value) in entries {
+                let key = match key {
+                    C::Text(s) => s,
+                    C::Bytes(b) => base64(&b),
+                    other => return Err(format!("unsupported map key {other:?}")),
+                };
+                map.insert(key, from_cbor(value)?);
+            }
+            Value::Object(map)
+        }
+        other => return Err(format!("unsupported value {other:?}")),
+    })
+}
+
+fn to_cbor(value: Value, is_binary: bool) -> ciborium::Value {
+    use ciborium::Value as C;
+    if let Some(bytes) = binary(&value, is_binary) {
+        return C::Bytes(bytes);
+    }
+    match value {
+        Value::Null => C::Null,
+        Value::Bool(b) => C::Bool(b),
+        Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+            (Some(u), _) => C::Integer(u.into()),
+            (None, Some(i)) => C::Integer(i.into()),
+            _ => C::Float(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => C::Text(s),
+        Value::Array(items) => C::Array(items.into_iter().map(|v| to_cbor(v, false)).collect()),
+        Value::Object(map) => C::Map(
+            map.into_iter()
+                .map(|(k, v)| {
+                    let is_binary = BINARY_FIELDS.contains(&k.as_str());
+                    (C::Text(k), to_cbor(v, is_binary))
+                })
+                .collect(),
+        ),
+    }
+}
+
+fn from_msgpack(value: rmpv::Value) -> Result<Value, String> {
+    use rmpv::Value as M;
+    Ok(match value {
+        M::Nil => Value::Null,
+        M::Boolean(b) => Value::Bool(b),
+        M::Integer(i) => match (i.as_u64(), i.as_i64()) {
+            (Some(u), _) => Value::from(u),
+            (None, Some(i)) => Value::from(i),
+            _ => return Err(format!("integer {i} out of range")),
+        },
+        M::F32(f) => float(f64::from(f))?,
+        M::F64(f) => float(f)?,
+        M::String(s) => Value::String(
+            s.into_str()
+                .ok_or_else(|| "string is not valid UTF-8".to_string())?,
+        ),
+        M::Binary(b) => Value::String(base64(&b)),
+        M::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(from_msgpack)
+                .collect::<Result<_, _>>()?,
+        ),
+        M::Map(entries) => {
+            let mut map = Map::new();
+            for (key, value) in entries {
+                let key = match key {
+                    M::String(s) => s
+                        .into_str()
+                        .ok_or_else(|| "map key is not valid UTF-8".to_string())?,
+                    M::Binary(b) => base64(&b),
+                    other => return Err(format!("unsupported map key {other}")),
+                };
+                map.insert(key, from_msgpack(value)?);
+            }
+            Value::Object(map)
+        }
+        M::Ext(kind, _) => return Err(format!("unsupported extension type {kind}")),
+    })
+}
+
+fn to_msgpack(value: Value, is_binary: bool) -> rmpv::Value {
+    use rmpv::Value as M;
+    if let Some(bytes) = binary(&value, is_binary) {
+        return M::Binary(bytes);
+    }
+    match value {
+        Value::Null => M::Nil,
+        Value::Bool(b) => M::Boolean(b),
+        Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+            (Some(u), _) => M::from(u),
+            (None, Some(i)) => M::from(i),
+            _ => M::F64(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => M::from(s),
+        Value::Array(items) => M::Array(items.into_iter().map(|v| to_msgpack(v, false)).collect()),
+        Value::Object(map) => M::Map(
+            map.into_iter()
+                .map(|(k, v)| {
+                    let is_binary = BINARY_FIELDS.contains(&k.as_str());
+                    (M::from(k), to_msgpack(v, is_binary))
+                })
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_binary_input_reads_bytes_as_base64() {
+        let request = ciborium::Value::Map(vec![
+            (
+                ciborium::Value::Text("envelope_xdr".into()),
+                ciborium::Value::Bytes(vec![0, 0, 0, 1]),
+            ),
+            (
+                ciborium::Value::Text("ledger_entries".into()),
+                ciborium::Value::Map(vec![(
+                    ciborium::Value::Bytes(vec![0xff]),
+                    ciborium::Value::Text("AAAA".into()),
+                )]),
+            ),
+        ]);
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&request, &mut bytes).unwrap();
+        let expected = json!({
+            "envelope_xdr": "AAAAAQ==",
+            "ledger_entries": { "/w==": "AAAA" },
+        });
+        assert_eq!(decode(Format::Cbor, &bytes).unwrap(), expected);
+
+        let request = rmpv::Value::Map(vec![
+            (
+                rmpv::Value::from("envelope_xdr"),
+                rmpv::Value::Binary(vec![0, 0, 0, 1]),
+            ),
+            (
+                rmpv::Value::from("ledger_entries"),
+                rmpv::Value::Map(vec![(
+                    rmpv::Value::Binary(vec![0xff]),
+                    rmpv::Value::from("AAAA"),
+                )]),
+            ),
+        ]);
+        let mut bytes = Vec::new();
+        rmpv::encode::write_value(&mut bytes, &request).unwrap();
+        assert_eq!(decode(Format::Msgpack, &bytes).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_binary_output_writes_xdr_as_bytes() {
+        let response = json!({
+            "status": "success",
+            "auth_payloads": [{ "entry_xdr": "AAAAAQ==", "nonce": -1 }],
+        });
+        let bytes = encode(Format::Cbor, &response).unwrap();
+        let value: ciborium::Value = ciborium::from_reader(&bytes[..]).unwrap();
+        let payload = &value.as_map().unwrap()[0].1.as_array().unwrap()[0];
+        assert_eq!(
+            payload.as_map().unwrap()[0].1,
+            ciborium::Value::Bytes(vec![0, 0, 0, 1])
+        );
+        // Decoding turns the bytes back into the JSON representation.
+        assert_eq!(decode(Format::Cbor, &bytes).unwrap(), response);
+
+        let bytes = encode(Format::Msgpack, &response).unwrap();
+        assert_eq!(decode(Format::Msgpack, &bytes).unwrap(), response);
+    }
+
+    #[test]
+    fn test_malformed_input_names_the_format() {
+        assert!(decode(Format::Cbor, &[0xff, 0x00])
+            .unwrap_err()
+            .starts_with("Invalid CBOR"));
+        assert!(decode(Format::Json, b"{")
+            .unwrap_err()
+            .starts_with("Invalid JSON"));
+    }
+}
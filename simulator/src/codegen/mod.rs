@@ -0,0 +1,161 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Code generated from simulations, for the test suites of contracts and
+//! their clients.
+//!
+//! - [`rust`]: `erst-sim codegen rust-test`, a soroban-sdk test that makes
+//!   the simulated call.
+//! - [`typescript`]: `erst-sim codegen ts`, fixtures of the simulated outcome
+//!   for stellar-sdk JS tests.
+//!
+//! Both embed values as base64 XDR, each under a comment rendering it.
+
+pub mod rust;
+pub mod typescript;
+
+use crate::pretty;
+use crate::simulation;
+use crate::trustline;
+use crate::types::{SimulationRequest, SimulationResponse};
+use crate::xdr_limits;
+use base64::Engine as _;
+use soroban_env_host::xdr::{
+    ContractEvent, ContractEventBody, ContractEventType, InvokeContractArgs, Limits, ScVal,
+    TransactionMeta, WriteXdr,
+};
+use std::fmt::Write as _;
+
+/// The contract call `request` makes.
+pub fn invocation(request: &SimulationRequest) -> Result<InvokeContractArgs, String> {
+    let envelope = simulation::envelope(request).map_err(|e| e.to_string())?;
+    trustline::invoked_contract(&envelope)
+        .cloned()
+        .ok_or_else(|| "The request does not call a contract".to_string())
+}
+
+/// The return value and contract events in the result meta of `response`.
+fn outcome(response: &SimulationResponse) -> Result<(Option<ScVal>, Vec<ContractEvent>), String> {
+    let Some(meta) = &response.result_meta_xdr else {
+        return Ok((None, Vec::new()));
+    };
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(meta)
+        .map_err(|e| format!("failed to decode result_meta_xdr: {e}"))?;
+    let meta = xdr_limits::read::<TransactionMeta>(&bytes)
+        .map_err(|e| format!("failed to parse result_meta_xdr: {e}"))?;
+    let TransactionMeta::V4(meta) = meta else {
+        return Err("result_meta_xdr is not TransactionMeta v4".to_string());
+    };
+    // The events soroban-sdk's `env.events().all()` returns.
+    let events = meta
+        .operations
+        .iter()
+        .flat_map(|op| op.events.iter())
+        .filter(|event| event.type_ == ContractEventType::Contract && event.contract_id.is_some())
+        .cloned()
+        .collect();
+    Ok((
+        meta.soroban_meta.and_then(|soroban| soroban.return_value),
+        events,
+    ))
+}
+
+fn event_comment(event: &ContractEvent) -> String {
+    let ContractEventBody::V0(body) = &event.body;
+    let mut text = String::from("[");
+    for (i, topic) in body.topics.iter().enumerate() {
+        if i > 0 {
+            text.push_str(", ");
+        }
+        let _ = write!(text, "{}", pretty::value(topic));
+    }
+    let _ = write!(text, "] {}", pretty::value(&body.data));
+    text
+}
+
+/// The first line of the message of `error`, a simulation error rendered
+/// as JSON or plain text.
+fn error_message(error: &str) -> String {
+    let message = serde_json::from_str::<serde_json::Value>(error)
+        .ok()
+        .and_then(|error| error.get("message")?.as_str().map(str::to_string))
+        .unwrap_or_else(|| error.to_string());
+    message.lines().next().unwrap_or_default().to_string()
+}
+
+/// `text` on one line.
+fn comment(text: &str) -> String {
+    text.replace(['\n', '\r'], " ")
+}
+
+fn b64(xdr: &impl WriteXdr) -> Result<String, String> {
+    xdr.to_xdr_base64(Limits::none()).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryLimits;
+    use crate::watch;
+    use soroban_env_host::meta::INTERFACE_VERSION;
+    use soroban_env_host::xdr::{
+        ContractId, Hash, ScAddress, ScEnvMetaEntry, ScEnvMetaEntryInterfaceVersion,
+    };
+    use std::collections::HashMap;
+
+    /// Simulates `function` of a contract whose `get() -> u32` returns 7.
+    pub fn simulate(function: &str) -> (SimulationRequest, SimulationResponse) {
+        let env_meta =
+            ScEnvMetaEntry::ScEnvMetaKindInterfaceVersion(ScEnvMetaEntryInterfaceVersion {
+                protocol: INTERFACE_VERSION.protocol,
+                pre_release: INTERFACE_VERSION.pre_release,
+            });
+        let section = env_meta
+            .to_xdr(Limits::none())
+            .unwrap()
+            .iter()
+            .map(|b| format!("\\{b:02x}"))
+            .collect::<String>();
+        let wasm = wat::parse_str(format!(
+            r#"(module
+                 (func $get (result i64) (i64.const 0x0000000700000004))
+                 (memory 1)
+                 (export "get" (func $get))
+                 (export "memory" (memory 0))
+                 (@custom "contractenvmetav0" "{section}"))"#
+        ))
+        .unwrap();
+        let contract = ScAddress::Contract(ContractId(Hash([4; 32]))).to_string();
+        let mut entries = HashMap::new();
+        watch::install(&mut entries, &wasm, std::slice::from_ref(&contract)).unwrap();
+        let mut request: SimulationRequest = serde_json::from_value(serde_json::json!({
+            "result_meta_xdr": "",
+            "enable_optimization_advisor": false,
+            "timestamp": "0",
+            "invoke": { "contract_id": contract, "function": function, "args": [] },
+            "ledger_entries": entries,
+            "ledger_sequence": 100,
+            "ledger_close_time": 1_700_000_000,
+        }))
+        .unwrap();
+        request.record_reads = true;
+        request.assemble = true;
+        let response =
+            simulation::run(&request, &MemoryLimits::default(), None, None, None).unwrap();
+        (request, response)
+    }
+
+    #[test]
+    fn test_error_message_is_the_first_line() {
+        let error = serde_json::json!({
+            "error_type": "HostError",
+            "message": "HostError: Error(WasmVm, MissingValue)\nDebugInfo not available\n",
+        });
+        assert_eq!(
+            error_message(&error.to_string()),
+            "HostError: Error(WasmVm, MissingValue)"
+        );
+        assert_eq!(error_message("boom\nat 0x10"), "boom");
+    }
+}
@@ -0,0 +1,217 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! `erst-sim codegen rust-test`: a soroban-sdk test from a simulation.
+//!
+//! `erst-sim codegen rust-test request.json > tests/transfer.rs` simulates
+//! a contract call, or takes a failure bundle's request, and turns it into a
+//! test that makes the same call in a soroban-sdk `Env`:
+//!
+//! - the ledger entries the simulation read are written as a soroban-sdk
+//!   test snapshot (see [`crate::snapshot::sdk`]), which the test loads with
+//!   `Env::from_snapshot_file`;
+//! - the ledger close time is pinned to the one simulated at, and all
+//!   authorizations are mocked;
+//! - the arguments, return value and contract events are embedded as base64
+//!   XDR.
+//!
+//! A call that succeeded asserts its return value and events, and one that
+//! failed asserts that the call fails, so an ad-hoc simulation becomes a
+//! regression test.
+
+use super::{b64, comment, error_message, event_comment, invocation, outcome};
+use crate::network::Network;
+use crate::pretty;
+use crate::snapshot::{file::SnapshotFile, sdk};
+use crate::types::{SimulationRequest, SimulationResponse};
+
+/// The test's name when none is given: `test_` and the function called.
+pub fn default_name(request: &SimulationRequest) -> Result<String, String> {
+    let call = invocation(request)?;
+    Ok(format!(
+        "test_{}",
+        call.function_name.to_utf8_string_lossy()
+    ))
+}
+
+/// The entries `response` read, as a soroban-sdk test snapshot at the
+/// ledger it was simulated at. `response` must come from a request with
+/// `record_reads` set.
+pub fn snapshot(
+    request: &SimulationRequest,
+    response: &SimulationResponse,
+) -> Result<String, String> {
+    let network = Network::resolve(
+        request.network.as_deref(),
+        request.network_passphrase.as_deref(),
+    )?;
+    let file = SnapshotFile {
+        ledger_sequence: response.ledger_sequence,
+        entries: response.read_entries.clone().into_iter().collect(),
+    };
+    sdk::to_json(&file, &network).map_err(|e| e.to_string())
+}
+
+/// The test `name` making the call of `request` on the state in the test
+/// snapshot at `snapshot_path`, and checking it behaves as in `response`.
+pub fn rust_test(
+    request: &SimulationRequest,
+    response: &SimulationResponse,
+    name: &str,
+    snapshot_path: &str,
+) -> Result<String, String> {
+    if name.is_empty()
+        || name.starts_with(|c: char| c.is_ascii_digit())
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(format!("{name:?} is not a valid test name"));
+    }
+    let call = invocation(request)?;
+    let succeeded = response.status == "success";
+    let (return_value, events) = if succeeded {
+        outcome(response)?
+    } else {
+        (None, Vec::new())
+    };
+
+    let mut code = String::new();
+    let mut line = |text: &str| {
+        code.push_str(text);
+        code.push('\n');
+    };
+    line("// Generated by `erst-sim codegen rust-test` from a simulation of");
+    line(&format!("// {}", comment(&pretty::invocation(&call, None))));
+    if let Some(sequence) = response.ledger_sequence {
+        line(&format!("// at ledger {sequence}."));
+    }
+    line("");
+    if succeeded {
+        line("use soroban_sdk::testutils::{Events as _, Ledger as _};");
+        line("use soroban_sdk::xdr::{ContractEvent, Limits, ReadXdr, ScVal};");
+    } else {
+        line("use soroban_sdk::testutils::Ledger as _;");
+        line("use soroban_sdk::xdr::{Limits, ReadXdr, ScVal};");
+    }
+    line("use soroban_sdk::{Address, Env, Symbol, TryFromVal, Val, Vec};");
+    line("");
+    line("fn xdr<T: ReadXdr>(base64: &str) -> T {");
+    line("    T::from_xdr_base64(base64, Limits::none()).unwrap()");
+    line("}");
+    line("");
+    line("#[test]");
+    line(&format!("fn {name}() {{"));
+    line(&format!(
+        "    let env = Env::from_snapshot_file({snapshot_path:?});"
+    ));
+    if let Some(close_time) = response.ledger_close_time {
+        line(&format!("    env.ledger().set_timestamp({close_time});"));
+    }
+    line("    env.mock_all_auths();");
+    line("");
+    line(&format!(
+        "    let contract = Address::from_str(&env, {:?});",
+        call.contract_address.to_string()
+    ));
+    line("    let args = Vec::<Val>::from_array(");
+    line("        &env,");
+    line("        [");
+    for arg in call.args.iter() {
+        line(&format!("            // {}", comment(&pretty::value(arg))));
+        line(&format!(
+            "            Val::try_from_val(&env, &xdr::<ScVal>({:?})).unwrap(),",
+            b64(arg)?
+        ));
+    }
+    line("        ],");
+    line("    );");
+    let function = format!(
+        "&Symbol::new(&env, {:?})",
+        call.function_name.to_utf8_string_lossy()
+    );
+    if !succeeded {
+        line("    let result = env.try_invoke_contract::<Val, soroban_sdk::Error>(");
+        line("        &contract,");
+        line(&format!("        {function},"));
+        line("        args,");
+        line("    );");
+        if let Some(error) = &response.error {
+            line(&format!("    // {}", comment(&error_message(error))));
+        }
+        line("    assert!(result.is_err());");
+        line("}");
+        return Ok(code);
+    }
+    line(&format!(
+        "    let result: Val = env.invoke_contract(&contract, {function}, args);"
+    ));
+    line("");
+    if let Some(value) = &return_value {
+        line(&format!("    // {}", comment(&pretty::value(value))));
+        line("    assert_eq!(");
+        line("        ScVal::try_from_val(&env, &result).unwrap(),");
+        line(&format!("        xdr::<ScVal>({:?}),", b64(value)?));
+        line("    );");
+    }
+    if events.is_empty() {
+        line("    assert_eq!(env.events().all(), [] as [ContractEvent; 0]);");
+    } else {
+        line("    assert_eq!(");
+        line("        env.events().all(),");
+        line("        [");
+        for event in &events {
+            line(&format!(
+                "            // {}",
+                comment(&event_comment(event))
+            ));
+            line(&format!(
+                "            xdr::<ContractEvent>({:?}),",
+                b64(event)?
+            ));
+        }
+        line("        ]");
+        line("    );");
+    }
+    line("}");
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::simulate;
+    use super::*;
+
+    #[test]
+    fn test_rust_test_asserts_the_result() {
+        let (request, response) = simulate("get");
+        assert_eq!(default_name(&request).unwrap(), "test_get");
+        let code = rust_test(&request, &response, "test_get", "snapshots/get.json").unwrap();
+        assert!(code.contains("fn test_get() {"), "{code}");
+        assert!(code.contains(r#"Env::from_snapshot_file("snapshots/get.json")"#));
+        assert!(code.contains("env.ledger().set_timestamp(1700000000);"));
+        // The u32 7.
+        assert!(code.contains(r#"xdr::<ScVal>("AAAAAwAAAAc="),"#), "{code}");
+        assert!(code.contains("[] as [ContractEvent; 0]"));
+        assert!(rust_test(&request, &response, "1st", "get.json").is_err());
+
+        // The contract's instance and code.
+        let snapshot: serde_json::Value =
+            serde_json::from_str(&snapshot(&request, &response).unwrap()).unwrap();
+        assert_eq!(snapshot["ledger"]["sequence_number"], 100);
+        assert_eq!(
+            snapshot["ledger"]["ledger_entries"]
+                .as_array()
+                .map(Vec::len),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn test_rust_test_expects_a_failure() {
+        let (request, response) = simulate("missing");
+        assert_ne!(response.status, "success");
+        let code = rust_test(&request, &response, "fails", "fails.json").unwrap();
+        assert!(code.contains("env.try_invoke_contract::<Val, soroban_sdk::Error>("));
+        assert!(code.contains("assert!(result.is_err());"));
+        assert!(!code.contains("Events"), "{code}");
+    }
+}
@@ -0,0 +1,267 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! `erst-sim codegen ts`: TypeScript fixtures of a simulation's outcome.
+//!
+//! `erst-sim codegen ts request.json > test/fixtures/transfer.ts` simulates a
+//! contract call and prints a module exporting what it did, typed for tests
+//! written with `@stellar/stellar-sdk`: the call, its return value or error,
+//! its contract events, the token balance changes they announce, and the fee
+//! and resources to declare. Values are `xdr.ScVal`s decoded from base64 XDR
+//! and amounts and fees are `bigint`s, so a test can compare them with what
+//! the RPC server returns for the same call.
+//!
+//! Fees come from assembling the transaction, so the request is simulated
+//! with `assemble` set.
+
+use super::{b64, comment, error_message, event_comment, invocation, outcome};
+use crate::pretty;
+use crate::types::{SimulationRequest, SimulationResponse};
+use soroban_env_host::xdr::{ContractEventBody, ScAddress, ScVal};
+
+/// The declarations every fixture module starts with.
+const PRELUDE: &str = r#"import { xdr } from "@stellar/stellar-sdk";
+
+export interface SimulatedEvent {
+  /** Contract that emitted the event (`C...`). */
+  contractId: string;
+  topics: xdr.ScVal[];
+  data: xdr.ScVal;
+  /** The event as base64 `xdr.ContractEvent`. */
+  xdr: string;
+}
+
+export interface SimulatedBalanceChange {
+  /** Holder (`G...` account or `C...` contract). */
+  address: string;
+  /** Token contract (`C...`). */
+  token: string;
+  /** `native` or `CODE:ISSUER`, for a Stellar Asset Contract. */
+  asset?: string;
+  /** Signed amount in the token's smallest unit. */
+  amount: bigint;
+}
+
+export interface SimulatedFee {
+  /** Resource fee to declare, rent included, in stroops (`minResourceFee`). */
+  resourceFee: bigint;
+  /** Part of `resourceFee` paid as rent. */
+  rentFee: bigint;
+  instructions: number;
+  diskReadBytes: number;
+  writeBytes: number;
+}
+
+export interface Simulation {
+  contractId: string;
+  function: string;
+  args: xdr.ScVal[];
+  /** Ledger simulated at. */
+  ledgerSequence?: number;
+  success: boolean;
+  /** Why the call failed. */
+  error?: string;
+  returnValue?: xdr.ScVal;
+  events: SimulatedEvent[];
+  balanceChanges: SimulatedBalanceChange[];
+  fee?: SimulatedFee;
+}
+
+const scVal = (base64: string): xdr.ScVal => xdr.ScVal.fromXDR(base64, "base64");
+"#;
+
+/// The fixture's name when none is given: the function called in camel
+/// case, then `Simulation`.
+pub fn default_name(request: &SimulationRequest) -> Result<String, String> {
+    let call = invocation(request)?;
+    let function = call.function_name.to_utf8_string_lossy();
+    let mut name = String::new();
+    for (i, word) in function
+        .split('_')
+        .filter(|word| !word.is_empty())
+        .enumerate()
+    {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            if i == 0 {
+                name.extend(first.to_lowercase());
+            } else {
+                name.extend(first.to_uppercase());
+            }
+            name.push_str(chars.as_str());
+        }
+    }
+    Ok(format!("{name}Simulation"))
+}
+
+/// A module exporting `name`, the fixture of the call of `request` that
+/// behaved as in `response`.
+pub fn fixture(
+    request: &SimulationRequest,
+    response: &SimulationResponse,
+    name: &str,
+) -> Result<String, String> {
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+    {
+        return Err(format!("{name:?} is not a valid TypeScript name"));
+    }
+    let call = invocation(request)?;
+    let succeeded = response.status == "success";
+    let (return_value, events) = if succeeded {
+        outcome(response)?
+    } else {
+        (None, Vec::new())
+    };
+
+    let mut code = String::new();
+    let mut line = |text: &str| {
+        code.push_str(text);
+        code.push('\n');
+    };
+    line("// Generated by `erst-sim codegen ts` from a simulation of");
+    line(&format!("// {}", comment(&pretty::invocation(&call, None))));
+    if let Some(sequence) = response.ledger_sequence {
+        line(&format!("// at ledger {sequence}."));
+    }
+    line("");
+    // Ends with a newline, so a blank line follows.
+    line(PRELUDE);
+    line(&format!("export const {name}: Simulation = {{"));
+    line(&format!(
+        "  contractId: {},",
+        string(&call.contract_address.to_string())
+    ));
+    line(&format!(
+        "  function: {},",
+        string(&call.function_name.to_utf8_string_lossy())
+    ));
+    line("  args: [");
+    for arg in call.args.iter() {
+        line(&format!("    // {}", comment(&pretty::value(arg))));
+        line(&format!("    {},", sc_val(arg)?));
+    }
+    line("  ],");
+    if let Some(sequence) = response.ledger_sequence {
+        line(&format!("  ledgerSequence: {sequence},"));
+    }
+    line(&format!("  success: {succeeded},"));
+    if !succeeded {
+        let error = response.error.as_deref().unwrap_or(&response.status);
+        line(&format!("  error: {},", string(&error_message(error))));
+    }
+    if let Some(value) = &return_value {
+        line(&format!("  // {}", comment(&pretty::value(value))));
+        line(&format!("  returnValue: {},", sc_val(value)?));
+    }
+
+    if events.is_empty() {
+        line("  events: [],");
+    } else {
+        line("  events: [");
+        for event in &events {
+            let ContractEventBody::V0(body) = &event.body;
+            let topics = body
+                .topics
+                .iter()
+                .map(sc_val)
+                .collect::<Result<Vec<_>, _>>()?;
+            let contract = event
+                .contract_id
+                .as_ref()
+                .map(|id| ScAddress::Contract(id.clone()).to_string())
+                .unwrap_or_default();
+            line(&format!("    // {}", comment(&event_comment(event))));
+            line("    {");
+            line(&format!("      contractId: {},", string(&contract)));
+            line(&format!("      topics: [{}],", topics.join(", ")));
+            line(&format!("      data: {},", sc_val(&body.data)?));
+            line(&format!("      xdr: {},", string(&b64(event)?)));
+            line("    },");
+        }
+        line("  ],");
+    }
+
+    if response.balance_changes.is_empty() {
+        line("  balanceChanges: [],");
+    } else {
+        line("  balanceChanges: [");
+        for change in &response.balance_changes {
+            let amount = change
+                .amount
+                .parse::<i128>()
+                .map_err(|e| format!("Invalid balance change {:?}: {e}", change.amount))?;
+            let asset = change
+                .asset
+                .as_deref()
+                .map(|asset| format!(", asset: {}", string(asset)))
+                .unwrap_or_default();
+            line(&format!(
+                "    {{ address: {}, token: {}{asset}, amount: {amount}n }},",
+                string(&change.address),
+                string(&change.token),
+            ));
+        }
+        line("  ],");
+    }
+
+    if let Some(assembled) = &response.assembled {
+        let rent = response.rent.as_ref().map_or(0, |rent| rent.total_fee);
+        line("  fee: {");
+        line(&format!("    resourceFee: {}n,", assembled.resource_fee));
+        line(&format!("    rentFee: {rent}n,"));
+        line(&format!("    instructions: {},", assembled.instructions));
+        line(&format!(
+            "    diskReadBytes: {},",
+            assembled.disk_read_bytes
+        ));
+        line(&format!("    writeBytes: {},", assembled.write_bytes));
+        line("  },");
+    }
+    line("};");
+    Ok(code)
+}
+
+/// `val` decoded from base64 XDR.
+fn sc_val(val: &ScVal) -> Result<String, String> {
+    Ok(format!("scVal({})", string(&b64(val)?)))
+}
+
+/// `text` as a string literal.
+fn string(text: &str) -> String {
+    serde_json::Value::from(text).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::simulate;
+    use super::*;
+
+    #[test]
+    fn test_fixture_of_a_call() {
+        let (request, response) = simulate("get");
+        assert_eq!(default_name(&request).unwrap(), "getSimulation");
+        let code = fixture(&request, &response, "getSimulation").unwrap();
+        assert!(code.contains("export const getSimulation: Simulation = {"));
+        assert!(
+            code.contains("  function: \"get\",\n  args: [\n  ],"),
+            "{code}"
+        );
+        assert!(code.contains("  success: true,"));
+        // The u32 7.
+        assert!(code.contains(r#"  returnValue: scVal("AAAAAwAAAAc="),"#));
+        assert!(code.contains("  events: [],\n  balanceChanges: [],\n  fee: {"));
+        assert!(fixture(&request, &response, "get-simulation").is_err());
+
+        let (request, response) = simulate("missing");
+        let code = fixture(&request, &response, "missing").unwrap();
+        assert!(
+            code.contains("  success: false,\n  error: \"VM Trap: Host error -- HostError: "),
+            "{code}"
+        );
+        assert!(!code.contains("  returnValue: "));
+        assert!(!code.contains("  fee: {"));
+    }
+}
@@ -0,0 +1,183 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! gzip and zstd compression of requests and responses.
+//!
+//! Snapshots of full contract state routinely reach tens of megabytes, most
+//! of it highly compressible base64 XDR. Compressed requests are accepted on
+//! stdin (detected from their magic bytes) and in server mode (declared with
+//! `Content-Encoding`); responses are compressed on request.
+//!
+//! Decompression is streamed, and the decoded bytes are bounded by the same
+//! request ceiling as an uncompressed payload, so a small compressed body
+//! cannot expand past it.
+
+use std::io::{self, Read, Write};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// zstd level used for responses; favours speed over ratio.
+const ZSTD_LEVEL: i32 = 3;
+
+/// A supported compression format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Encoding {
+    Gzip,
+    Zstd,
+}
+
+impl Encoding {
+    /// Name of the encoding in `Content-Encoding`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Zstd => "zstd",
+        }
+    }
+
+    /// Parses a `Content-Encoding` value. `Ok(None)` means uncompressed;
+    /// `Err` carries an unsupported encoding.
+    pub fn from_header(value: &str) -> Result<Option<Self>, String> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "" | "identity" => Ok(None),
+            "gzip" | "x-gzip" => Ok(Some(Encoding::Gzip)),
+            "zstd" => Ok(Some(Encoding::Zstd)),
+            other => Err(other.to_string()),
+        }
+    }
+
+    /// Picks the encoding to compress a response with from an
+    /// `Accept-Encoding` value, preferring zstd.
+    pub fn negotiate(accept_encoding: &str) -> Option<Self> {
+        let accepted = |name: &str| {
+            accept_encoding.split(',').any(|item| {
+                let mut parts = item.split(';');
+                let coding = parts.next().unwrap_or_default().trim();
+                let quality = parts
+                    .filter_map(|p| p.trim().strip_prefix("q="))
+                    .find_map(|q| q.trim().parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                coding.eq_ignore_ascii_case(name) && quality > 0.0
+            })
+        };
+        [Encoding::Zstd, Encoding::Gzip]
+            .into_iter()
+            .find(|encoding| accepted(encoding.name()))
+    }
+
+    /// Detects a compressed payload from its magic bytes.
+    pub fn detect(payload: &[u8]) -> Option<Self> {
+        if payload.starts_with(GZIP_MAGIC) {
+            Some(Encoding::Gzip)
+        } else if payload.starts_with(ZSTD_MAGIC) {
+            Some(Encoding::Zstd)
+        } else {
+            None
+        }
+    }
+}
+
+/// Wraps `reader` in a decoder for `encoding`; `None` reads it as is.
+///
+/// Callers bound the decoded stream (see [`memory::read_to_end_limited`]),
+/// not the compressed one.
+pub fn decoder<'a, R: Read + 'a>(
+    encoding: Option<Encoding>,
+    reader: R,
+) -> io::Result<Box<dyn Read + 'a>> {
+    Ok(match encoding {
+        None => Box::new(reader),
+        Some(Encoding::Gzip) => Box::new(flate2::read::GzDecoder::new(reader)),
+        Some(Encoding::Zstd) => Box::new(zstd::stream::read::Decoder::new(reader)?),
+    })
+}
+
+/// Like [`decoder`], detecting the encoding from the stream's magic bytes.
+pub fn sniffing_decoder<'a, R: Read + 'a>(mut reader: R) -> io::Result<Box<dyn Read + 'a>> {
+    let mut magic = Vec::with_capacity(ZSTD_MAGIC.len());
+    (&mut reader)
+        .take(ZSTD_MAGIC.len() as u64)
+        .read_to_end(&mut magic)?;
+    let encoding = Encoding::detect(&magic);
+    decoder(encoding, io::Cursor::new(magic).chain(reader))
+}
+
+/// Compresses `payload`.
+pub fn compress(encoding: Encoding, payload: &[u8]) -> io::Result<Vec<u8>> {
+    match encoding {
+        Encoding::Gzip => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(payload)?;
+            encoder.finish()
+        }
+        Encoding::Zstd => zstd::encode_all(payload, ZSTD_LEVEL),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{read_to_end_limited, MemoryError};
+
+    fn decompress(
+        encoding: Encoding,
+        payload: &[u8],
+        limit: usize,
+    ) -> Result<Vec<u8>, MemoryError> {
+        read_to_end_limited(decoder(Some(encoding), payload).unwrap(), limit)
+    }
+
+    #[test]
+    fn test_round_trip_and_detection() {
+        let payload = br#"{"envelope_xdr":"AAAA"}"#.repeat(100);
+        for encoding in [Encoding::Gzip, Encoding::Zstd] {
+            let compressed = compress(encoding, &payload).unwrap();
+            assert!(compressed.len() < payload.len());
+            assert_eq!(Encoding::detect(&compressed), Some(encoding));
+            let mut sniffed = Vec::new();
+            sniffing_decoder(&compressed[..])
+                .unwrap()
+                .read_to_end(&mut sniffed)
+                .unwrap();
+            assert_eq!(sniffed, payload);
+            assert_eq!(
+                decompress(encoding, &compressed, payload.len()).unwrap(),
+                payload
+            );
+            // Expanding past the request ceiling is refused.
+            assert_eq!(
+                decompress(encoding, &compressed, payload.len() - 1).unwrap_err(),
+                MemoryError::RequestTooLarge {
+                    limit: payload.len() - 1
+                }
+            );
+        }
+        assert_eq!(Encoding::detect(b"{}"), None);
+        let mut plain = Vec::new();
+        sniffing_decoder(&b"{}"[..])
+            .unwrap()
+            .read_to_end(&mut plain)
+            .unwrap();
+        assert_eq!(plain, b"{}");
+        assert!(matches!(
+            decompress(Encoding::Gzip, b"\x1f\x8bnot gzip", 100),
+            Err(MemoryError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_header_parsing() {
+        assert_eq!(Encoding::from_header("identity"), Ok(None));
+        assert_eq!(Encoding::from_header("GZIP"), Ok(Some(Encoding::Gzip)));
+        assert_eq!(Encoding::from_header("br"), Err("br".to_string()));
+
+        assert_eq!(
+            Encoding::negotiate("gzip, zstd;q=0.5"),
+            Some(Encoding::Zstd)
+        );
+        assert_eq!(Encoding::negotiate("gzip, zstd;q=0"), Some(Encoding::Gzip));
+        assert_eq!(Encoding::negotiate("br"), None);
+    }
+}
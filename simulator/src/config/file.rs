@@ -0,0 +1,451 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! `hintents.toml`: defaults for flags, environment variables and requests.
+//!
+//! Daemon deployments configure erst-sim in one file instead of a wall of
+//! flags. The file is the one given with `--config`, or `hintents.toml` in
+//! the working directory when there is one. Every setting in it is the
+//! lowest layer of its own: `ERST_*` variables override `[limits]` and
+//! `[cache]`, `serve` flags override `[server]`, and request fields override
+//! `[network]`.
+//!
+//! `HINTENTS_*` environment variables override the file for container
+//! deployments: `HINTENTS_NETWORK` sets `[network]`'s `name`, the other keys
+//! of `[network]`, `[limits]` and `[cache]` are set by their names in upper
+//! case (`HINTENTS_RPC_URL`, `HINTENTS_MAX_EVENTS`), and a variable named
+//! after a `serve` flag sets that `[server]` key (`HINTENTS_MAX_CONCURRENCY`).
+//! Other `HINTENTS_*` variables are ignored with a warning, as containers
+//! may carry unrelated ones; unknown keys in the file are an error.
+//! `HINTENTS_CONFIG` names the file. `erst-sim config show` prints the
+//! merged result.
+//!
+//! ```toml
+//! [network]
+//! name = "testnet"
+//! rpc_url = "https://soroban-testnet.stellar.org"
+//!
+//! [limits]
+//! max_request_bytes = 16777216
+//!
+//! [cache]
+//! entry_cache_size = 50000
+//!
+//! [server]
+//! listen = "0.0.0.0:8080"
+//! max_concurrency = 16
+//! cors_origin = ["https://app.example"]
+//! ```
+//!
+//! `[server]` takes any `serve` flag by its long name. The `[network]`
+//! endpoints and `[cache]` settings also serve as `serve`'s `--rpc-url`,
+//! `--horizon-url`, `--entry-cache-size` and `--entry-cache-dir`.
+
+use crate::memory::MemoryLimits;
+use crate::server::ServeArgs;
+use crate::types::SimulationRequest;
+use clap::Args as _;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// File read when `--config` is not given, if it exists.
+pub const DEFAULT_PATH: &str = "hintents.toml";
+/// Prefix of the environment variables that override the file.
+pub const ENV_PREFIX: &str = "HINTENTS_";
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    #[serde(default)]
+    pub network: NetworkConfig,
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    /// `serve` flags by long name, e.g. `max_concurrency = 16`.
+    #[serde(default)]
+    pub server: toml::Table,
+}
+
+/// Defaults for the network fields of requests.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct NetworkConfig {
+    /// Network preset for requests that name none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rpc_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub horizon_url: Option<String>,
+}
+
+/// Memory and response ceilings (see [`MemoryLimits`]).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct LimitsConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_request_bytes: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_snapshot_bytes: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_host_memory_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub global_memory_bytes: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_events: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_event_bytes: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_logs: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_log_bytes: Option<usize>,
+}
+
+/// The cache of entries fetched from remote sources.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CacheConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entry_cache_size: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entry_cache_dir: Option<PathBuf>,
+}
+
+impl ConfigFile {
+    /// Reads and parses the file at `path`.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        let config = toml::from_str::<Self>(&text)
+            .map_err(|e| format!("Invalid config {}: {e}", path.display()))?;
+        config
+            .check_server_keys()
+            .map_err(|e| format!("Invalid config {}: {e}", path.display()))?;
+        Ok(config)
+    }
+
+    /// Fails on a `[server]` key that is not a `serve` flag.
+    fn check_server_keys(&self) -> Result<(), String> {
+        match self.server.keys().find(|key| !is_serve_flag(key)) {
+            Some(key) => Err(format!("[server] {key}: not a flag of serve")),
+            None => Ok(()),
+        }
+    }
+
+    /// Loads `explicit`, or [`DEFAULT_PATH`] if there is such a file, or
+    /// else no settings at all.
+    pub fn discover(explicit: Option<&Path>) -> Result<Self, String> {
+        match explicit {
+            Some(path) => Self::load(path),
+            None if Path::new(DEFAULT_PATH).is_file() => Self::load(Path::new(DEFAULT_PATH)),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// `self` with the settings of the `HINTENTS_*` environment variables.
+    pub fn with_env(self) -> Result<Self, String> {
+        self.with_vars(std::env::vars())
+    }
+
+    /// `self` with the settings of the `HINTENTS_*` variables among `vars`.
+    pub fn with_vars(
+        mut self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, String> {
+        for (name, value) in vars {
+            let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            if key == "CONFIG" || value.is_empty() {
+                continue;
+            }
+            self.set(&key.to_ascii_lowercase(), value)
+                .map_err(|e| format!("{name}: {e}"))?;
+        }
+        Ok(self)
+    }
+
+    fn set(&mut self, key: &str, value: String) -> Result<(), String> {
+        let limits = &mut self.limits;
+        match key {
+            "network" => self.network.name = Some(value),
+            "rpc_url" => self.network.rpc_url = Some(value),
+            "horizon_url" => self.network.horizon_url = Some(value),
+            "max_request_bytes" => limits.max_request_bytes = Some(parse(&value)?),
+            "max_snapshot_bytes" => limits.max_snapshot_bytes = Some(parse(&value)?),
+            "max_host_memory_bytes" => limits.max_host_memory_bytes = Some(parse(&value)?),
+            "global_memory_bytes" => limits.global_memory_bytes = Some(parse(&value)?),
+            "max_events" => limits.max_events = Some(parse(&value)?),
+            "max_event_bytes" => limits.max_event_bytes = Some(parse(&value)?),
+            "max_logs" => limits.max_logs = Some(parse(&value)?),
+            "max_log_bytes" => limits.max_log_bytes = Some(parse(&value)?),
+            "entry_cache_size" => self.cache.entry_cache_size = Some(parse(&value)?),
+            "entry_cache_dir" => self.cache.entry_cache_dir = Some(PathBuf::from(value)),
+            _ if is_serve_flag(key) => {
+                self.server
+                    .insert(key.to_string(), toml::Value::String(value));
+                return Ok(());
+            }
+            _ => {
+                tracing::warn!(
+                    event = "unknown_config_variable",
+                    variable = %format!("{ENV_PREFIX}{}", key.to_ascii_uppercase()),
+                );
+                return Ok(());
+            }
+        }
+        // The variable outranks the file's `[server]` copy of the setting,
+        // which would otherwise outrank it as a `serve` default.
+        self.server.remove(key);
+        self.server.remove(&key.replace('_', "-"));
+        Ok(())
+    }
+
+    /// Default values of `serve` flags, by long name: `[server]`, then the
+    /// `[network]` endpoints and `[cache]` settings `[server]` leaves out.
+    pub fn serve_defaults(&self) -> Result<Vec<(String, Vec<String>)>, String> {
+        let mut defaults = Vec::new();
+        for (key, value) in &self.server {
+            let values = match value {
+                toml::Value::Array(values) => values.iter().map(flag_value).collect(),
+                value => flag_value(value).map(|value| vec![value]),
+            }
+            .map_err(|e| format!("[server] {key}: {e}"))?;
+            defaults.push((key.replace('_', "-"), values));
+        }
+        let inherited = [
+            ("rpc-url", self.network.rpc_url.clone()),
+            ("horizon-url", self.network.horizon_url.clone()),
+            (
+                "entry-cache-size",
+                self.cache.entry_cache_size.map(|size| size.to_string()),
+            ),
+            (
+                "entry-cache-dir",
+                self.cache
+                    .entry_cache_dir
+                    .as_ref()
+                    .map(|dir| dir.display().to_string()),
+            ),
+        ];
+        for (flag, value) in inherited {
+            if let Some(value) = value {
+                if !defaults.iter().any(|(set, _)| set == flag) {
+                    defaults.push((flag.to_string(), vec![value]));
+                }
+            }
+        }
+        Ok(defaults)
+    }
+}
+
+impl NetworkConfig {
+    /// Fills in the network fields `request` leaves unset.
+    pub fn apply(&self, request: &mut SimulationRequest) {
+        let fill = |field: &mut Option<String>, default: &Option<String>| {
+            if field.is_none() {
+                field.clone_from(default);
+            }
+        };
+        fill(&mut request.network, &self.name);
+        fill(&mut request.rpc_url, &self.rpc_url);
+        fill(&mut request.horizon_url, &self.horizon_url);
+    }
+}
+
+impl From<MemoryLimits> for LimitsConfig {
+    fn from(limits: MemoryLimits) -> Self {
+        Self {
+            max_request_bytes: Some(limits.max_request_bytes),
+            max_snapshot_bytes: Some(limits.max_snapshot_bytes),
+            max_host_memory_bytes: Some(limits.max_host_memory_bytes),
+            global_memory_bytes: Some(limits.global_memory_bytes),
+            max_events: Some(limits.max_events),
+            max_event_bytes: Some(limits.max_event_bytes),
+            max_logs: Some(limits.max_logs),
+            max_log_bytes: Some(limits.max_log_bytes),
+        }
+    }
+}
+
+impl LimitsConfig {
+    /// `limits` with the ceilings this file sets.
+    pub fn apply(&self, limits: MemoryLimits) -> MemoryLimits {
+        MemoryLimits {
+            max_request_bytes: self.max_request_bytes.unwrap_or(limits.max_request_bytes),
+            max_snapshot_bytes: self.max_snapshot_bytes.unwrap_or(limits.max_snapshot_bytes),
+            max_host_memory_bytes: self
+                .max_host_memory_bytes
+                .unwrap_or(limits.max_host_memory_bytes),
+            global_memory_bytes: self
+                .global_memory_bytes
+                .unwrap_or(limits.global_memory_bytes),
+            max_events: self.max_events.unwrap_or(limits.max_events),
+            max_event_bytes: self.max_event_bytes.unwrap_or(limits.max_event_bytes),
+            max_logs: self.max_logs.unwrap_or(limits.max_logs),
+            max_log_bytes: self.max_log_bytes.unwrap_or(limits.max_log_bytes),
+        }
+    }
+}
+
+/// Whether `serve` has a flag named `key`, with `_` or `-` between words.
+fn is_serve_flag(key: &str) -> bool {
+    let flag = key.replace('_', "-");
+    ServeArgs::augment_args(clap::Command::new("serve"))
+        .get_arguments()
+        .any(|arg| arg.get_long() == Some(flag.as_str()))
+}
+
+fn parse<T: FromStr>(value: &str) -> Result<T, String>
+where
+    T::Err: Display,
+{
+    value
+        .trim()
+        .parse()
+        .map_err(|e| format!("invalid value {value:?}: {e}"))
+}
+
+/// `value` as it would be written on the command line.
+fn flag_value(value: &toml::Value) -> Result<String, String> {
+    match value {
+        toml::Value::String(value) => Ok(value.clone()),
+        toml::Value::Integer(value) => Ok(value.to_string()),
+        toml::Value::Float(value) => Ok(value.to_string()),
+        toml::Value::Boolean(value) => Ok(value.to_string()),
+        other => Err(format!(
+            "expected a string, number or boolean, not {}",
+            other.type_str()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_layers_and_serve_defaults() {
+        let config: ConfigFile = toml::from_str(
+            r#"
+            [network]
+            name = "testnet"
+            rpc_url = "https://rpc.example"
+
+            [limits]
+            max_events = 100
+
+            [cache]
+            entry_cache_size = 5
+
+            [server]
+            max_concurrency = 16
+            cors_origin = ["https://a.example", "https://b.example"]
+            rpc-url = "https://server-rpc.example"
+            "#,
+        )
+        .unwrap();
+
+        let limits = config.limits.apply(MemoryLimits::default());
+        assert_eq!(limits.max_events, 100);
+        assert_eq!(limits.max_logs, MemoryLimits::default().max_logs);
+
+        let mut defaults = config.serve_defaults().unwrap();
+        defaults.sort();
+        assert_eq!(
+            defaults,
+            vec![
+                (
+                    "cors-origin".to_string(),
+                    vec![
+                        "https://a.example".to_string(),
+                        "https://b.example".to_string()
+                    ]
+                ),
+                ("entry-cache-size".to_string(), vec!["5".to_string()]),
+                ("max-concurrency".to_string(), vec!["16".to_string()]),
+                (
+                    "rpc-url".to_string(),
+                    vec!["https://server-rpc.example".to_string()]
+                ),
+            ]
+        );
+
+        let mut request = serde_json::from_value::<SimulationRequest>(serde_json::json!({
+            "envelope_xdr": "",
+            "result_meta_xdr": "",
+            "enable_optimization_advisor": false,
+            "timestamp": "",
+            "network": "futurenet",
+        }))
+        .unwrap();
+        config.network.apply(&mut request);
+        assert_eq!(request.network.as_deref(), Some("futurenet"));
+        assert_eq!(request.rpc_url.as_deref(), Some("https://rpc.example"));
+
+        let nested = toml::from_str::<ConfigFile>("[server]\nlisten = { port = 1 }").unwrap();
+        assert!(nested
+            .serve_defaults()
+            .unwrap_err()
+            .starts_with("[server] listen"));
+        assert!(toml::from_str::<ConfigFile>("[limits]\nmax_cpu = 1").is_err());
+        let unknown = toml::from_str::<ConfigFile>("[server]\nmax_threads = 1").unwrap();
+        assert_eq!(
+            unknown.check_server_keys().unwrap_err(),
+            "[server] max_threads: not a flag of serve"
+        );
+        assert!(config.check_server_keys().is_ok());
+    }
+
+    #[test]
+    fn test_environment_overrides_the_file() {
+        let file: ConfigFile = toml::from_str(
+            r#"
+            [limits]
+            max_events = 100
+
+            [server]
+            rpc_url = "https://file.example"
+            listen = "0.0.0.0:8080"
+            "#,
+        )
+        .unwrap();
+        let vars = [
+            ("HINTENTS_NETWORK", "testnet"),
+            ("HINTENTS_RPC_URL", "https://env.example"),
+            ("HINTENTS_MAX_EVENTS", "7"),
+            ("HINTENTS_MAX_CONCURRENCY", "3"),
+            ("HINTENTS_CONFIG", "elsewhere.toml"),
+            ("HINTENTS_RELEASE", "2024.1"),
+            ("ERST_MAX_LOGS", "1"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+        let config = file.clone().with_vars(vars).unwrap();
+        assert_eq!(config.network.name.as_deref(), Some("testnet"));
+        assert_eq!(config.limits.max_events, Some(7));
+        assert_eq!(config.limits.max_logs, None);
+
+        // HINTENTS_RELEASE is no serve flag, and is left out.
+        assert!(config.check_server_keys().is_ok());
+        let mut defaults = config.serve_defaults().unwrap();
+        defaults.sort();
+        assert_eq!(
+            defaults,
+            [
+                ("listen", "0.0.0.0:8080"),
+                ("max-concurrency", "3"),
+                ("rpc-url", "https://env.example"),
+            ]
+            .map(|(flag, value)| (flag.to_string(), vec![value.to_string()]))
+        );
+
+        let bad = [("HINTENTS_MAX_EVENTS".to_string(), "many".to_string())];
+        assert!(file
+            .with_vars(bad)
+            .unwrap_err()
+            .starts_with("HINTENTS_MAX_EVENTS: invalid value"));
+    }
+}
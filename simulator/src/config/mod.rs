@@ -1,4 +1,5 @@
 // Copyright 2025 Erst Users
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod file;
 pub mod paths;
@@ -0,0 +1,449 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Custom account (smart wallet) authorization.
+//!
+//! When enforced authorization entries have a contract address, the host
+//! authorizes them by calling that contract's `__check_auth` with the
+//! payload hash and the entry's signature. The simulation runs it like any
+//! other call, so its cost is part of the transaction's. [`accounts`]
+//! describes each such entry before the run: the kind of credential its
+//! signature carries and, for WebAuthn (passkey) credentials, whether the
+//! signed `client_data_json` challenges the right payload, the usual reason
+//! a passkey signature is rejected. [`link`] then adds how `__check_auth`
+//! went from the call tree and, when it failed, which call rejected the
+//! entry: often not the account itself but a policy contract it consults
+//! (a spending limit, a function allow-list), failing with an error its
+//! spec names.
+
+use crate::calls::{Call, CallCost};
+use crate::network;
+use crate::pretty;
+use base64::Engine as _;
+use schemars::JsonSchema;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use soroban_env_host::xdr::{
+    Hash, HashIdPreimage, HashIdPreimageSorobanAuthorization, Limits, ScAddress, ScVal,
+    SorobanAuthorizationEntry, SorobanCredentials, WriteXdr,
+};
+
+/// First protocol with the `secp256r1` host function passkeys verify with.
+const SECP256R1_PROTOCOL: u32 = 21;
+
+/// An enforced authorization entry of a custom account.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct CustomAccountAuth {
+    /// The custom account (`C...`).
+    pub address: String,
+    /// Index of the entry among the enforced authorization entries.
+    pub entry: usize,
+    /// What the signature carries: `webauthn`, `secp256r1`, `ed25519`,
+    /// `none` or `other`.
+    pub credential: &'static str,
+    /// Hex SHA-256 of the payload `__check_auth` is given to verify.
+    pub payload_hash: String,
+    /// For WebAuthn credentials, whether the `challenge` in
+    /// `client_data_json` is the payload hash (base64url), as passkey
+    /// wallets check.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub challenge_matches: Option<bool>,
+    /// Whether `__check_auth` returned; absent if it was not called.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checked: Option<bool>,
+    /// Budget `__check_auth` consumed, when the request asked for
+    /// `call_costs`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost: Option<CallCost>,
+    /// The call that rejected the entry, when `__check_auth` failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rejected_by: Option<Rejection>,
+    /// Why the entry would not authorize, when that can be told.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub problem: Option<String>,
+}
+
+/// The innermost failed call under a failed `__check_auth` that reported an
+/// error: `__check_auth` itself or a policy contract it called.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct Rejection {
+    /// The rejecting contract (`C...`).
+    pub contract: String,
+    pub function: String,
+    /// The error it failed with, like `Error(Contract(3))`.
+    pub error: String,
+    /// The name of the error in the contract's spec: the rule that
+    /// rejected the entry.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_case: Option<String>,
+}
+
+/// The custom account entries among `entries`, enforced on network
+/// `network_id` under `protocol_version`.
+pub fn accounts(
+    entries: &[SorobanAuthorizationEntry],
+    network_id: [u8; 32],
+    protocol_version: u32,
+) -> Vec<CustomAccountAuth> {
+    entries
+        .iter()
+        .enumerate()
+        .filter_map(|(index, entry)| {
+            let SorobanCredentials::Address(credentials) = &entry.credentials else {
+                return None;
+            };
+            if !matches!(credentials.address, ScAddress::Contract(_)) {
+                return None;
+            }
+            let preimage =
+                HashIdPreimage::SorobanAuthorization(HashIdPreimageSorobanAuthorization {
+                    network_id: Hash(network_id),
+                    nonce: credentials.nonce,
+                    signature_expiration_ledger: credentials.signature_expiration_ledger,
+                    invocation: entry.root_invocation.clone(),
+                });
+            let payload: [u8; 32] = Sha256::digest(preimage.to_xdr(Limits::none()).ok()?).into();
+            let credential = credential(&credentials.signature);
+            let challenge_matches = (credential == "webauthn")
+                .then(|| challenge(&credentials.signature).map(|c| c == payload))
+                .flatten();
+            let problem = if credential == "none" {
+                Some("The entry is unsigned: `__check_auth` gets `()`".to_string())
+            } else if challenge_matches == Some(false) {
+                Some(
+                    "The passkey signed another payload: client_data_json's challenge is not \
+                     the payload hash (check the network, nonce and expiration ledger)"
+                        .to_string(),
+                )
+            } else if matches!(credential, "webauthn" | "secp256r1")
+                && protocol_version < SECP256R1_PROTOCOL
+            {
+                Some(format!(
+                    "secp256r1 signatures need protocol {SECP256R1_PROTOCOL}, not \
+                     {protocol_version}"
+                ))
+            } else {
+                None
+            };
+            Some(CustomAccountAuth {
+                address: credentials.address.to_string(),
+                entry: index,
+                credential,
+                payload_hash: network::hex(&payload),
+                challenge_matches,
+                checked: None,
+                cost: None,
+                rejected_by: None,
+                problem,
+            })
+        })
+        .collect()
+}
+
+/// Adds to `accounts` how their `__check_auth` calls in `tree` went. Calls
+/// and entries of the same account are matched in order.
+pub fn link(accounts: &mut [CustomAccountAuth], tree: &[Call]) {
+    fn walk<'a>(calls: &'a [Call], found: &mut Vec<&'a Call>) {
+        for call in calls {
+            if call.function == "__check_auth" {
+                found.push(call);
+            }
+            walk(&call.calls, found);
+        }
+    }
+    let mut checks = Vec::new();
+    walk(tree, &mut checks);
+    for account in accounts {
+        let Some(position) = checks.iter().position(|c| c.contract == account.address) else {
+            continue;
+        };
+        let check = checks.remove(position);
+        account.checked = Some(check.result.is_some());
+        account.cost = check.cost;
+        if check.result.is_some() {
+            continue;
+        }
+        account.rejected_by = rejection(check);
+        if account.problem.is_none() {
+            account.problem = Some(match &account.rejected_by {
+                None => "`__check_auth` failed".to_string(),
+                Some(rejection) => {
+                    let error = match &rejection.error_case {
+                        Some(case) => format!("{case} ({})", rejection.error),
+                        None => rejection.error.clone(),
+                    };
+                    if rejection.function == "__check_auth" {
+                        format!("`__check_auth` failed with {error}")
+                    } else {
+                        format!(
+                            "`__check_auth` failed: {}::{} rejected the entry with {error}",
+                            pretty::short(&rejection.contract),
+                            rejection.function
+                        )
+                    }
+                }
+            });
+        }
+    }
+}
+
+/// The innermost failed call in `call`, a failed call, that reported an
+/// error.
+fn rejection(call: &Call) -> Option<Rejection> {
+    call.calls
+        .iter()
+        .rev()
+        .filter(|inner| inner.result.is_none())
+        .find_map(rejection)
+        .or_else(|| {
+            Some(Rejection {
+                contract: call.contract.clone(),
+                function: call.function.clone(),
+                error: call.error.clone()?,
+                error_case: call.error_case.clone(),
+            })
+        })
+}
+
+/// The kind of credential `signature` carries, found by the shapes smart
+/// wallets use: WebAuthn assertions are maps with `authenticator_data` and
+/// `client_data_json`, and signer enums are vectors tagged with the key
+/// type.
+fn credential(signature: &ScVal) -> &'static str {
+    fn find(value: &ScVal) -> Option<&'static str> {
+        match value {
+            ScVal::Map(Some(map)) => {
+                let has = |name: &str| {
+                    map.iter().any(
+                        |e| matches!(&e.key, ScVal::Symbol(s) if s.as_slice() == name.as_bytes()),
+                    )
+                };
+                if has("authenticator_data") && has("client_data_json") {
+                    return Some("webauthn");
+                }
+                map.iter()
+                    .find_map(|e| find(&e.key).or_else(|| find(&e.val)))
+            }
+            ScVal::Vec(Some(items)) => {
+                let tag = match items.first() {
+                    Some(ScVal::Symbol(tag)) => match tag.as_slice() {
+                        b"Secp256r1" => Some("secp256r1"),
+                        b"Ed25519" => Some("ed25519"),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                // A WebAuthn assertion under a `Secp256r1` tag is WebAuthn.
+                match items.iter().find_map(find) {
+                    Some("webauthn") => Some("webauthn"),
+                    inner => tag.or(inner),
+                }
+            }
+            _ => None,
+        }
+    }
+    match signature {
+        ScVal::Void => "none",
+        ScVal::Vec(Some(items)) if items.is_empty() => "none",
+        _ => find(signature).unwrap_or("other"),
+    }
+}
+
+/// The decoded `challenge` of the first `client_data_json` in `signature`.
+fn challenge(signature: &ScVal) -> Option<Vec<u8>> {
+    match signature {
+        ScVal::Map(Some(map)) => map.iter().find_map(|e| match (&e.key, &e.val) {
+            (ScVal::Symbol(key), ScVal::Bytes(json)) if key.as_slice() == b"client_data_json" => {
+                let json: serde_json::Value = serde_json::from_slice(json).ok()?;
+                base64::engine::general_purpose::URL_SAFE_NO_PAD
+                    .decode(json.get("challenge")?.as_str()?.trim_end_matches('='))
+                    .ok()
+            }
+            _ => challenge(&e.val),
+        }),
+        ScVal::Vec(Some(items)) => items.iter().find_map(challenge),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calls::Charged;
+    use soroban_env_host::xdr::{
+        ContractId, InvokeContractArgs, ScBytes, ScMap, ScMapEntry, SorobanAddressCredentials,
+        SorobanAuthorizedFunction, SorobanAuthorizedInvocation,
+    };
+
+    fn symbol(name: &str) -> ScVal {
+        ScVal::Symbol(name.try_into().unwrap())
+    }
+
+    fn bytes(bytes: &[u8]) -> ScVal {
+        ScVal::Bytes(ScBytes(bytes.to_vec().try_into().unwrap()))
+    }
+
+    fn webauthn(challenge: &[u8]) -> ScVal {
+        let client_data = format!(
+            r#"{{"type":"webauthn.get","challenge":"{}"}}"#,
+            base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(challenge)
+        );
+        let assertion = ScVal::Map(Some(ScMap(
+            vec![
+                ScMapEntry {
+                    key: symbol("authenticator_data"),
+                    val: bytes(&[0; 37]),
+                },
+                ScMapEntry {
+                    key: symbol("client_data_json"),
+                    val: bytes(client_data.as_bytes()),
+                },
+                ScMapEntry {
+                    key: symbol("signature"),
+                    val: bytes(&[0; 64]),
+                },
+            ]
+            .try_into()
+            .unwrap(),
+        )));
+        ScVal::Vec(Some(
+            vec![symbol("Secp256r1"), assertion].try_into().unwrap(),
+        ))
+    }
+
+    fn entry(signature: ScVal) -> SorobanAuthorizationEntry {
+        SorobanAuthorizationEntry {
+            credentials: SorobanCredentials::Address(SorobanAddressCredentials {
+                address: ScAddress::Contract(ContractId(Hash([3; 32]))),
+                nonce: 1,
+                signature_expiration_ledger: 100,
+                signature,
+            }),
+            root_invocation: SorobanAuthorizedInvocation {
+                function: SorobanAuthorizedFunction::ContractFn(InvokeContractArgs {
+                    contract_address: ScAddress::Contract(ContractId(Hash([1; 32]))),
+                    function_name: "transfer".try_into().unwrap(),
+                    args: Default::default(),
+                }),
+                sub_invocations: Default::default(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_webauthn_challenge_must_be_the_payload_hash() {
+        let unsigned = accounts(&[entry(ScVal::Void)], [9; 32], 25);
+        assert_eq!(unsigned[0].credential, "none");
+        let payload = unsigned[0].payload_hash.clone();
+        let payload_bytes = (0..32)
+            .map(|i| u8::from_str_radix(&payload[2 * i..2 * i + 2], 16).unwrap())
+            .collect::<Vec<_>>();
+
+        let signed = accounts(&[entry(webauthn(&payload_bytes))], [9; 32], 25);
+        assert_eq!(signed[0].credential, "webauthn");
+        assert_eq!(signed[0].challenge_matches, Some(true));
+        assert_eq!(signed[0].problem, None);
+
+        // The same signature does not authorize on another network.
+        let elsewhere = accounts(&[entry(webauthn(&payload_bytes))], [8; 32], 25);
+        assert_eq!(elsewhere[0].challenge_matches, Some(false));
+        assert!(elsewhere[0].problem.is_some());
+
+        let old = accounts(&[entry(webauthn(&payload_bytes))], [9; 32], 20);
+        assert!(old[0].problem.as_deref().unwrap().contains("protocol 21"));
+    }
+
+    #[test]
+    fn test_link_finds_check_auth_calls() {
+        let mut accounts = accounts(
+            &[entry(ScVal::Vec(Some(
+                vec![symbol("Ed25519"), bytes(&[0; 64])].try_into().unwrap(),
+            )))],
+            [9; 32],
+            25,
+        );
+        assert_eq!(accounts[0].credential, "ed25519");
+        let cost = CallCost {
+            inclusive: Charged {
+                cpu_instructions: 500,
+                memory_bytes: 10,
+            },
+            own: Charged::default(),
+        };
+        let check_auth = Call {
+            contract: accounts[0].address.clone(),
+            function: "__check_auth".to_string(),
+            args: vec![],
+            result: None,
+            error: None,
+            error_case: None,
+            calls: vec![],
+            cost: Some(cost),
+        };
+        let tree = [Call {
+            contract: ScAddress::Contract(ContractId(Hash([1; 32]))).to_string(),
+            function: "transfer".to_string(),
+            args: vec![],
+            result: None,
+            error: None,
+            error_case: None,
+            calls: vec![check_auth],
+            cost: None,
+        }];
+
+        link(&mut accounts, &tree);
+        assert_eq!(accounts[0].checked, Some(false));
+        assert_eq!(accounts[0].cost, Some(cost));
+        assert_eq!(
+            accounts[0].problem.as_deref(),
+            Some("`__check_auth` failed")
+        );
+    }
+
+    #[test]
+    fn test_link_names_the_rejecting_policy() {
+        let mut accounts = accounts(&[entry(webauthn(&[0; 32]))], [9; 32], 25);
+        let policy = ScAddress::Contract(ContractId(Hash([5; 32]))).to_string();
+        let failed = |contract: &str, function: &str, calls: Vec<Call>| Call {
+            contract: contract.to_string(),
+            function: function.to_string(),
+            args: vec![],
+            result: None,
+            error: Some("Error(Contract(3))".to_string()),
+            error_case: None,
+            calls,
+            cost: None,
+        };
+        let enforce = Call {
+            error_case: Some("OverLimit".to_string()),
+            ..failed(&policy, "enforce", vec![])
+        };
+        let tree = [failed(&accounts[0].address, "__check_auth", vec![enforce])];
+
+        // The passkey problem comes first, but the rejection is kept.
+        link(&mut accounts, &tree);
+        let rejection = accounts[0].rejected_by.clone().unwrap();
+        assert_eq!(rejection.contract, policy);
+        assert_eq!(rejection.function, "enforce");
+        assert_eq!(rejection.error_case.as_deref(), Some("OverLimit"));
+        assert!(accounts[0]
+            .problem
+            .as_deref()
+            .unwrap()
+            .contains("challenge"));
+
+        accounts[0].problem = None;
+        link(&mut accounts, &tree);
+        assert_eq!(
+            accounts[0].problem.as_deref(),
+            Some(
+                format!(
+                    "`__check_auth` failed: {}::enforce rejected the entry with OverLimit \
+                     (Error(Contract(3)))",
+                    pretty::short(&policy)
+                )
+                .as_str()
+            )
+        );
+    }
+}
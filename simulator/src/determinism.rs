@@ -0,0 +1,196 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Self-check that a request simulates the same way every time.
+//!
+//! `erst-sim --check-determinism 10 < request.json` simulates the request
+//! ten times in one process and compares the serialized responses byte for
+//! byte: the outcome, events, ledger changes, budget and every other field
+//! but the wall-clock `timings`, the `cache_stats` and the `logs`, which
+//! report them. Anything else that differs between runs, down to the order
+//! of a map's keys, is nondeterminism, in the simulator (iteration over an
+//! unordered map, a timestamp leaking in) or in the contract (a PRNG seeded
+//! from something other than the ledger). Differences are reported per
+//! top-level field.
+//!
+//! The first run settles the ledger sequence and close time, which default
+//! to the latest ledger and the current time, and later runs reuse them.
+
+use crate::memory::MemoryLimits;
+use crate::simulation;
+use crate::source::cache::CachedSource;
+use crate::types::{SimulationRequest, SimulationResponse};
+use serde::Serialize;
+use serde_json::value::RawValue;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeterminismReport {
+    pub runs: usize,
+    /// Whether every run produced the same response.
+    pub deterministic: bool,
+    /// Response fields that differed from the first run's.
+    pub differences: Vec<Nondeterminism>,
+}
+
+/// A response field that did not come out the same in every run.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Nondeterminism {
+    pub field: String,
+    /// Runs, counted from 1, whose value differs from the first run's.
+    pub runs: Vec<usize>,
+    /// The field in the first run.
+    pub first: Value,
+    /// The field in the earliest run that differs.
+    pub differing: Value,
+}
+
+/// Simulates `request` `runs` times and compares the responses.
+pub fn check(
+    request: &SimulationRequest,
+    limits: &MemoryLimits,
+    remote: Option<Arc<CachedSource>>,
+    runs: usize,
+) -> DeterminismReport {
+    let simulate = |request: &SimulationRequest| {
+        let mut response = simulation::run(request, limits, None, remote.clone(), None)
+            .unwrap_or_else(|e| simulation::error_response(e.to_string()));
+        let pinned = (response.ledger_sequence, response.ledger_close_time);
+        clear_machine_fields(&mut response);
+        (serde_json::to_vec(&response).unwrap_or_default(), pinned)
+    };
+    let (first, (ledger_sequence, ledger_close_time)) = simulate(request);
+    let mut pinned = request.clone();
+    pinned.ledger_sequence = pinned.ledger_sequence.or(ledger_sequence);
+    pinned.ledger_close_time = pinned.ledger_close_time.or(ledger_close_time);
+
+    let mut responses = vec![first];
+    responses.extend((1..runs).map(|_| simulate(&pinned).0));
+    let differences = compare(&responses);
+    DeterminismReport {
+        runs: responses.len(),
+        deterministic: differences.is_empty(),
+        differences,
+    }
+}
+
+/// Clears the fields that describe the machine rather than the simulation.
+fn clear_machine_fields(response: &mut SimulationResponse) {
+    response.timings = None;
+    response.cache_stats = None;
+    response.logs.clear();
+}
+
+/// A serialized response's top-level fields, as they were serialized.
+type Fields<'a> = BTreeMap<String, &'a RawValue>;
+
+/// Top-level fields of the serialized `responses` whose bytes differ from
+/// the first response's.
+fn compare(responses: &[Vec<u8>]) -> Vec<Nondeterminism> {
+    let Some((first, rest)) = responses.split_first() else {
+        return Vec::new();
+    };
+    if rest.iter().all(|response| response == first) {
+        return Vec::new();
+    }
+    let first = fields(first);
+    let rest = rest
+        .iter()
+        .map(|response| fields(response))
+        .collect::<Vec<_>>();
+
+    let mut names = first.keys().collect::<Vec<_>>();
+    names.extend(rest.iter().flat_map(BTreeMap::keys));
+    names.sort();
+    names.dedup();
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let expected = raw(&first, name);
+            let differing = rest
+                .iter()
+                .enumerate()
+                .filter(|(_, fields)| raw(fields, name) != expected)
+                .map(|(run, _)| run + 2)
+                .collect::<Vec<_>>();
+            let first_differing = rest.get(*differing.first()? - 2)?;
+            Some(Nondeterminism {
+                field: name.clone(),
+                runs: differing,
+                first: value(&first, name),
+                differing: value(first_differing, name),
+            })
+        })
+        .collect()
+}
+
+fn fields(response: &[u8]) -> Fields<'_> {
+    serde_json::from_slice(response).unwrap_or_default()
+}
+
+fn raw<'a>(fields: &Fields<'a>, name: &str) -> Option<&'a str> {
+    fields.get(name).map(|raw| raw.get())
+}
+
+/// The field, to show where runs differ; the value hides the order of keys.
+fn value(fields: &Fields<'_>, name: &str) -> Value {
+    raw(fields, name)
+        .and_then(|raw| serde_json::from_str(raw).ok())
+        .unwrap_or(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_compare_flags_fields_that_change_between_runs() {
+        let responses = [
+            r#"{"status":"success","events":["a","b"],"changes":{"x":1,"y":2}}"#,
+            r#"{"status":"success","events":["a","b"],"changes":{"x":1,"y":2}}"#,
+            r#"{"status":"success","events":["b","a"],"changes":{"x":1,"y":2}}"#,
+            r#"{"status":"success","events":["a","b"],"changes":{"y":2,"x":1},"extra":1}"#,
+        ]
+        .map(|response| response.as_bytes().to_vec());
+        let differences = compare(&responses);
+        assert_eq!(
+            differences,
+            vec![
+                // The same map, with its keys in another order.
+                Nondeterminism {
+                    field: "changes".into(),
+                    runs: vec![4],
+                    first: json!({"x": 1, "y": 2}),
+                    differing: json!({"x": 1, "y": 2}),
+                },
+                Nondeterminism {
+                    field: "events".into(),
+                    runs: vec![3],
+                    first: json!(["a", "b"]),
+                    differing: json!(["b", "a"]),
+                },
+                Nondeterminism {
+                    field: "extra".into(),
+                    runs: vec![4],
+                    first: Value::Null,
+                    differing: json!(1),
+                },
+            ]
+        );
+        assert!(compare(&responses[..2]).is_empty());
+    }
+
+    #[test]
+    fn test_machine_fields_are_left_out() {
+        let response = |log: &str| {
+            let mut response = simulation::error_response("failed".to_string());
+            response.logs.push(log.to_string());
+            clear_machine_fields(&mut response);
+            serde_json::to_vec(&response).unwrap()
+        };
+        assert!(compare(&[response("took 3ms"), response("took 5ms")]).is_empty());
+    }
+}
@@ -0,0 +1,429 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Semantic comparison of two saved simulation responses.
+//!
+//! `erst-sim diff a.json b.json` compares what the two simulations did
+//! rather than their text: the outcome, the return value and final ledger
+//! state decoded from `result_meta_xdr`, the events, the budget and the
+//! mocked fee. Fields that differ on every run, such as timings or
+//! `last_modified_ledger_seq`, are ignored, so comparing before and after a
+//! contract change, or two simulator versions, shows only the behaviour that
+//! changed. Requests with `compare_protocol` use the same comparison through
+//! [`compare`].
+
+use crate::snapshot::entry_key;
+use crate::types::SimulationResponse;
+use crate::xdr_limits;
+use base64::Engine as _;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use soroban_env_host::xdr::{
+    LedgerEntry, LedgerEntryChange, LedgerKey, Limits, TransactionMeta, WriteXdr,
+};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// The parts of a `SimulationResponse` that are compared.
+#[derive(Debug, Default, Deserialize)]
+struct SavedResponse {
+    #[serde(default)]
+    status: String,
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    diagnostic_events: Vec<serde_json::Value>,
+    #[serde(default)]
+    budget_usage: Option<SavedBudget>,
+    #[serde(default)]
+    result_meta_xdr: Option<String>,
+    #[serde(default)]
+    required_fee: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SavedBudget {
+    #[serde(default)]
+    cpu_instructions: u64,
+    #[serde(default)]
+    memory_bytes: u64,
+}
+
+/// A value that differs between the two responses.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct Change<T> {
+    pub before: T,
+    pub after: T,
+}
+
+impl<T: PartialEq> Change<T> {
+    fn of(before: T, after: T) -> Option<Self> {
+        (before != after).then_some(Self { before, after })
+    }
+}
+
+/// A budget metric that differs between the two responses.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct BudgetDelta {
+    pub metric: &'static str,
+    pub before: u64,
+    pub after: u64,
+    pub delta: i128,
+}
+
+/// Events emitted by only one of the two simulations, in emission order.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, JsonSchema)]
+pub struct EventsDiff {
+    /// Emitted by the first simulation only.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub removed: Vec<serde_json::Value>,
+    /// Emitted by the second simulation only.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub added: Vec<serde_json::Value>,
+}
+
+impl EventsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.added.is_empty()
+    }
+}
+
+/// What a simulation left in a ledger entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryState {
+    /// The simulation did not write the entry.
+    Unchanged,
+    /// The simulation created, updated or restored the entry to this base64
+    /// `LedgerEntry`.
+    Written(String),
+    /// The simulation removed the entry.
+    Removed,
+}
+
+/// A ledger entry the two simulations left in different states.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct StateDelta {
+    /// Base64 `LedgerKey`.
+    pub key: String,
+    pub before: EntryState,
+    pub after: EntryState,
+}
+
+/// Everything that differs between two simulation responses. Empty when
+/// the two simulations behaved the same.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, JsonSchema)]
+pub struct ResponseDiff {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<Change<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<Change<Option<String>>>,
+    /// Debug rendering of the returned `ScVal`, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub return_value: Option<Change<Option<String>>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub budget: Vec<BudgetDelta>,
+    /// Fee required by the mocked fee model.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required_fee: Option<Change<Option<u64>>>,
+    #[serde(skip_serializing_if = "EventsDiff::is_empty")]
+    pub events: EventsDiff,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub state: Vec<StateDelta>,
+}
+
+impl ResponseDiff {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Compares the two response JSON files at `before` and `after`.
+pub fn diff_files(before: &Path, after: &Path) -> Result<ResponseDiff, String> {
+    diff(&load(before)?, &load(after)?)
+}
+
+/// Compares two responses produced in this process.
+pub fn compare(
+    before: &SimulationResponse,
+    after: &SimulationResponse,
+) -> Result<ResponseDiff, String> {
+    let saved = |response| {
+        serde_json::to_value(response)
+            .and_then(serde_json::from_value::<SavedResponse>)
+            .map_err(|e| e.to_string())
+    };
+    diff(&saved(before)?, &saved(after)?)
+}
+
+fn load(path: &Path) -> Result<SavedResponse, String> {
+    let json = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    serde_json::from_str(&json)
+        .map_err(|e| format!("{}: invalid response JSON: {e}", path.display()))
+}
+
+fn diff(before: &SavedResponse, after: &SavedResponse) -> Result<ResponseDiff, String> {
+    let meta_before = Execution::decode(before.result_meta_xdr.as_deref())?;
+    let meta_after = Execution::decode(after.result_meta_xdr.as_deref())?;
+    Ok(ResponseDiff {
+        status: Change::of(before.status.clone(), after.status.clone()),
+        error: Change::of(before.error.clone(), after.error.clone()),
+        return_value: Change::of(meta_before.return_value, meta_after.return_value),
+        budget: budget(before.budget_usage.as_ref(), after.budget_usage.as_ref()),
+        required_fee: Change::of(before.required_fee, after.required_fee),
+        events: events(&before.diagnostic_events, &after.diagnostic_events),
+        state: state(&meta_before.writes, &meta_after.writes)?,
+    })
+}
+
+fn budget(before: Option<&SavedBudget>, after: Option<&SavedBudget>) -> Vec<BudgetDelta> {
+    let metrics =
+        |b: Option<&SavedBudget>| b.map_or([0, 0], |b| [b.cpu_instructions, b.memory_bytes]);
+    ["cpu_instructions", "memory_bytes"]
+        .into_iter()
+        .zip(metrics(before).into_iter().zip(metrics(after)))
+        .filter(|(_, (before, after))| before != after)
+        .map(|(metric, (before, after))| BudgetDelta {
+            metric,
+            before,
+            after,
+            delta: i128::from(after) - i128::from(before),
+        })
+        .collect()
+}
+
+/// Events not on the longest common subsequence of the two event lists, so
+/// an event inserted in the middle does not shift every later one.
+fn events(before: &[serde_json::Value], after: &[serde_json::Value]) -> EventsDiff {
+    let (n, m) = (before.len(), after.len());
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if before[i] == after[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut diff = EventsDiff::default();
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if before[i] == after[j] {
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            diff.removed.push(before[i].clone());
+            i += 1;
+        } else {
+            diff.added.push(after[j].clone());
+            j += 1;
+        }
+    }
+    diff.removed.extend_from_slice(&before[i..]);
+    diff.added.extend_from_slice(&after[j..]);
+    diff
+}
+
+fn state(
+    before: &BTreeMap<LedgerKey, EntryWrite>,
+    after: &BTreeMap<LedgerKey, EntryWrite>,
+) -> Result<Vec<StateDelta>, String> {
+    let encode = |e: Option<&EntryWrite>| match e {
+        None => Ok(EntryState::Unchanged),
+        Some(EntryWrite::Removed) => Ok(EntryState::Removed),
+        Some(EntryWrite::Written(entry)) => entry
+            .to_xdr_base64(Limits::none())
+            .map(EntryState::Written)
+            .map_err(|e| e.to_string()),
+    };
+    before
+        .keys()
+        .chain(after.keys().filter(|key| !before.contains_key(key)))
+        .filter(|key| !same_write(before.get(key), after.get(key)))
+        .map(|key| {
+            Ok(StateDelta {
+                key: key
+                    .to_xdr_base64(Limits::none())
+                    .map_err(|e| e.to_string())?,
+                before: encode(before.get(key))?,
+                after: encode(after.get(key))?,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()
+        .map(|mut deltas| {
+            deltas.sort_by(|a, b| a.key.cmp(&b.key));
+            deltas
+        })
+}
+
+/// Whether two writes leave the same entry, ignoring the ledger they were
+/// made at.
+fn same_write(before: Option<&EntryWrite>, after: Option<&EntryWrite>) -> bool {
+    match (before, after) {
+        (Some(EntryWrite::Written(a)), Some(EntryWrite::Written(b))) => {
+            a.data == b.data && a.ext == b.ext
+        }
+        (a, b) => a == b,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum EntryWrite {
+    Written(Box<LedgerEntry>),
+    Removed,
+}
+
+/// The return value and ledger writes recorded in a `result_meta_xdr`.
+#[derive(Debug, Default)]
+struct Execution {
+    return_value: Option<String>,
+    writes: BTreeMap<LedgerKey, EntryWrite>,
+}
+
+impl Execution {
+    fn decode(meta: Option<&str>) -> Result<Self, String> {
+        let Some(meta) = meta else {
+            return Ok(Self::default());
+        };
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(meta)
+            .map_err(|e| format!("failed to decode result_meta_xdr: {e}"))?;
+        let meta = xdr_limits::read::<TransactionMeta>(&bytes)
+            .map_err(|e| format!("failed to parse result_meta_xdr: {e}"))?;
+        let TransactionMeta::V4(meta) = meta else {
+            return Err("result_meta_xdr is not TransactionMeta v4".to_string());
+        };
+        let mut writes = BTreeMap::new();
+        let changes = meta
+            .tx_changes_before
+            .iter()
+            .chain(meta.operations.iter().flat_map(|op| op.changes.iter()))
+            .chain(meta.tx_changes_after.iter());
+        for change in changes {
+            match change {
+                LedgerEntryChange::Created(entry)
+                | LedgerEntryChange::Updated(entry)
+                | LedgerEntryChange::Restored(entry) => {
+                    writes.insert(
+                        entry_key(entry),
+                        EntryWrite::Written(Box::new(entry.clone())),
+                    );
+                }
+                LedgerEntryChange::Removed(key) => {
+                    writes.insert(key.clone(), EntryWrite::Removed);
+                }
+                LedgerEntryChange::State(_) => {}
+            }
+        }
+        Ok(Self {
+            return_value: meta
+                .soroban_meta
+                .and_then(|soroban| soroban.return_value)
+                .map(|value| format!("{value:?}")),
+            writes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::invoke;
+    use crate::replay::{self, Outcome};
+    use crate::types::InvokeSpec;
+    use serde_json::json;
+    use soroban_env_host::xdr::{
+        ContractDataDurability, ContractDataEntry, ContractId, ExtensionPoint, Hash,
+        LedgerEntryData, LedgerEntryExt, ScAddress, ScVal,
+    };
+
+    fn data(key: u32, val: u32, ledger: u32) -> LedgerEntry {
+        LedgerEntry {
+            last_modified_ledger_seq: ledger,
+            data: LedgerEntryData::ContractData(ContractDataEntry {
+                ext: ExtensionPoint::V0,
+                contract: ScAddress::Contract(ContractId(Hash([7; 32]))),
+                key: ScVal::U32(key),
+                durability: ContractDataDurability::Persistent,
+                val: ScVal::U32(val),
+            }),
+            ext: LedgerEntryExt::V0,
+        }
+    }
+
+    fn response(returned: u32, writes: Vec<LedgerEntry>, cpu: u64) -> SavedResponse {
+        let envelope = invoke::envelope(&InvokeSpec {
+            contract_id: ScAddress::Contract(ContractId(Hash([7; 32]))).to_string(),
+            function: "run".into(),
+            args: vec![],
+            source_account: None,
+        })
+        .unwrap();
+        let changes = writes
+            .into_iter()
+            .map(LedgerEntryChange::Updated)
+            .collect::<Vec<_>>();
+        let meta = replay::transaction_meta(
+            &envelope,
+            &Outcome::Succeeded(vec![ScVal::U32(returned)]),
+            &[],
+            changes.try_into().unwrap(),
+        )
+        .unwrap();
+        SavedResponse {
+            status: "success".into(),
+            budget_usage: Some(SavedBudget {
+                cpu_instructions: cpu,
+                memory_bytes: 10,
+            }),
+            result_meta_xdr: Some(meta.to_xdr_base64(Limits::none()).unwrap()),
+            ..SavedResponse::default()
+        }
+    }
+
+    #[test]
+    fn test_identical_behaviour_is_empty() {
+        let a = response(1, vec![data(1, 5, 10)], 100);
+        let b = response(1, vec![data(1, 5, 20)], 100);
+        assert!(diff(&a, &b).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_diff_reports_return_value_budget_and_state() {
+        let a = response(1, vec![data(1, 5, 10), data(2, 5, 10)], 100);
+        let b = response(2, vec![data(1, 6, 10), data(3, 5, 10)], 150);
+        let delta = diff(&a, &b).unwrap();
+        assert_eq!(
+            delta.return_value,
+            Some(Change {
+                before: Some("U32(1)".into()),
+                after: Some("U32(2)".into()),
+            })
+        );
+        assert_eq!(delta.budget.len(), 1);
+        assert_eq!(delta.budget[0].metric, "cpu_instructions");
+        assert_eq!(delta.budget[0].delta, 50);
+
+        assert_eq!(delta.state.len(), 3);
+        let unchanged = delta
+            .state
+            .iter()
+            .filter(|s| s.before == EntryState::Unchanged || s.after == EntryState::Unchanged)
+            .count();
+        assert_eq!(unchanged, 2);
+    }
+
+    #[test]
+    fn test_events_diff_keeps_common_subsequence() {
+        let before = vec![json!(1), json!(2), json!(3)];
+        let after = vec![json!(1), json!(4), json!(2), json!(3)];
+        let diff = events(&before, &after);
+        assert!(diff.removed.is_empty());
+        assert_eq!(diff.added, vec![json!(4)]);
+
+        let diff = events(&after, &[]);
+        assert_eq!(diff.removed.len(), 4);
+    }
+}
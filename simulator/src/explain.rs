@@ -0,0 +1,198 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Plain-English account of what a transaction does, for signers.
+//!
+//! `erst-sim explain` simulates a request and sums it up the way a wallet's
+//! review screen would: "Account GABC…DEFG calls swap on CBXK…WXYZ, spends
+//! 100 USDC, receives 99.5 XLM, pays ~0.01 XLM in fees." Operations come from
+//! the envelope; authorizations, balance changes and the required fee from
+//! the simulation. Amounts are those of the simulated ledger state and can
+//! differ when the transaction lands.
+
+use crate::authorization;
+use crate::balances::BalanceChange;
+use crate::pretty;
+use crate::simulation;
+use crate::types::{SimulationRequest, SimulationResponse};
+use soroban_env_host::xdr::{ContractExecutable, HostFunction, OperationBody};
+
+/// Decimal places of Stellar asset amounts and of XLM fees in stroops.
+const ASSET_DECIMALS: u32 = 7;
+
+/// Describes the transaction of `request`, as simulated in `response`.
+pub fn explain(
+    request: &SimulationRequest,
+    response: &SimulationResponse,
+) -> Result<String, String> {
+    let envelope = simulation::envelope(request).map_err(|e| e.to_string())?;
+    let source = response
+        .source_account
+        .as_ref()
+        .map(|source| source.account.clone())
+        .unwrap_or_else(|| {
+            authorization::SourceAccount::from(&authorization::transaction_source(&envelope))
+                .account
+        });
+
+    let mut clauses: Vec<String> = simulation::envelope_operations(&envelope)
+        .iter()
+        .map(|op| match &op.body {
+            OperationBody::InvokeHostFunction(op) => host_function(&op.host_function),
+            body => format!("submits a {} operation", body.name()),
+        })
+        .collect();
+    let mut others: Vec<(&str, Vec<String>)> = Vec::new();
+    for change in &response.balance_changes {
+        let (verb, amount) = movement(change);
+        if change.address == source {
+            clauses.push(format!("{verb} {amount}"));
+        } else {
+            match others
+                .iter_mut()
+                .find(|(address, _)| *address == change.address)
+            {
+                Some((_, moves)) => moves.push(format!("{verb} {amount}")),
+                None => others.push((&change.address, vec![format!("{verb} {amount}")])),
+            }
+        }
+    }
+    clauses.push(match response.required_fee {
+        Some(fee) => format!(
+            "pays ~{} XLM in fees",
+            units(i128::from(fee), ASSET_DECIMALS)
+        ),
+        None => format!(
+            "pays at most {} XLM in fees",
+            units(
+                i128::from(simulation::transaction_fee_stroops(&envelope)),
+                ASSET_DECIMALS
+            )
+        ),
+    });
+
+    let mut sentences = vec![format!(
+        "Account {} {}.",
+        pretty::short(&source),
+        clauses.join(", ")
+    )];
+    if response.status != "success" {
+        sentences.push(format!(
+            "The simulation failed: {}.",
+            response
+                .error
+                .as_deref()
+                .unwrap_or("no reason given")
+                .trim_end_matches('.')
+        ));
+    }
+    let mut signers: Vec<String> = Vec::new();
+    for payload in &response.auth_payloads {
+        let signer = pretty::short(&payload.address);
+        if payload.address != source && !signers.contains(&signer) {
+            signers.push(signer);
+        }
+    }
+    if !signers.is_empty() {
+        sentences.push(format!("{} must also authorize it.", list(&signers)));
+    }
+    for (address, moves) in others {
+        sentences.push(format!("{} {}.", pretty::short(address), list(&moves)));
+    }
+    Ok(sentences.join(" "))
+}
+
+fn host_function(function: &HostFunction) -> String {
+    match function {
+        HostFunction::InvokeContract(args) => format!(
+            "calls {} on {}",
+            args.function_name.to_utf8_string_lossy(),
+            pretty::address(&args.contract_address)
+        ),
+        HostFunction::CreateContract(args) => create_contract(&args.executable),
+        HostFunction::CreateContractV2(args) => create_contract(&args.executable),
+        HostFunction::UploadContractWasm(code) => {
+            format!("uploads {} bytes of contract code", code.len())
+        }
+    }
+}
+
+fn create_contract(executable: &ContractExecutable) -> String {
+    match executable {
+        ContractExecutable::Wasm(_) => "deploys a contract".to_string(),
+        ContractExecutable::StellarAsset => "deploys an asset contract".to_string(),
+    }
+}
+
+/// `("spends", "100 USDC")` for a balance that went down by 100 USDC.
+fn movement(change: &BalanceChange) -> (&'static str, String) {
+    let amount = change.amount.parse::<i128>().unwrap_or(0);
+    let verb = if amount < 0 { "spends" } else { "receives" };
+    let amount = amount.saturating_abs();
+    let amount = match change.asset.as_deref() {
+        Some("native") => format!("{} XLM", units(amount, ASSET_DECIMALS)),
+        Some(asset) => format!(
+            "{} {}",
+            units(amount, ASSET_DECIMALS),
+            asset.split(':').next().unwrap_or(asset)
+        ),
+        None => format!("{amount} units of {}", pretty::short(&change.token)),
+    };
+    (verb, amount)
+}
+
+/// `amount` smallest units with `decimals` decimal places: 5000000 with 7
+/// is `0.5`.
+fn units(amount: i128, decimals: u32) -> String {
+    let scale = 10i128.pow(decimals);
+    let fraction = (amount % scale).abs();
+    let whole = amount / scale;
+    if fraction == 0 {
+        return whole.to_string();
+    }
+    let fraction = format!("{fraction:0width$}", width = decimals as usize);
+    let sign = if amount < 0 && whole == 0 { "-" } else { "" };
+    format!("{sign}{whole}.{}", fraction.trim_end_matches('0'))
+}
+
+/// `a`, `a and b`, `a, b and c`.
+fn list(items: &[String]) -> String {
+    match items {
+        [] => String::new(),
+        [item] => item.clone(),
+        [init @ .., last] => format!("{} and {last}", init.join(", ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_units_and_movements() {
+        assert_eq!(units(1_000_000_000, 7), "100");
+        assert_eq!(units(5_000_000, 7), "0.5");
+        assert_eq!(units(12_345, 7), "0.0012345");
+        assert_eq!(units(-5_000_000, 7), "-0.5");
+
+        let change = |asset: Option<&str>, amount: &str| BalanceChange {
+            address: "G".into(),
+            token: "CBXKAAAAAAAAAAAAAAAAAAAAWXYZ".into(),
+            asset: asset.map(Into::into),
+            amount: amount.into(),
+        };
+        assert_eq!(
+            movement(&change(Some("USDC:GA5Z"), "-1000000000")),
+            ("spends", "100 USDC".to_string())
+        );
+        assert_eq!(
+            movement(&change(Some("native"), "995000000")),
+            ("receives", "99.5 XLM".to_string())
+        );
+        assert_eq!(
+            movement(&change(None, "42")),
+            ("receives", "42 units of CBXK…WXYZ".to_string())
+        );
+        assert_eq!(list(&["a".into(), "b".into(), "c".into()]), "a, b and c");
+    }
+}
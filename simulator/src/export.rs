@@ -0,0 +1,143 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! The ledger after a simulation, as a snapshot file.
+//!
+//! `erst-sim --export-state after.json < setup.json` writes the state the
+//! simulated transaction leaves behind in the snapshot format, so a setup
+//! that takes a complex transaction (deploying and initialising a contract,
+//! say) can be simulated once and then serve as the snapshot of many later
+//! runs. The state is the request's `ledger_entries`, plus every entry the
+//! simulation fetched from the network, with the transaction's ledger
+//! changes applied when it succeeds. With `--export-scope touched` it holds
+//! only the entries the transaction read or wrote.
+//!
+//! The snapshot is taken at the ledger the transaction was simulated at.
+
+use crate::scenario;
+use crate::snapshot::file::SnapshotFile;
+use crate::types::{SimulationRequest, SimulationResponse};
+use std::collections::HashMap;
+
+/// Which entries an exported snapshot holds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Scope {
+    /// The request's entries and every entry the simulation read.
+    #[default]
+    All,
+    /// Only the entries the transaction read or wrote.
+    Touched,
+}
+
+/// The ledger `response` leaves behind, for `request`, which must have set
+/// `record_reads`.
+pub fn state(
+    request: &SimulationRequest,
+    response: &SimulationResponse,
+    scope: Scope,
+) -> Result<SnapshotFile, String> {
+    let mut entries = match scope {
+        Scope::All => request.ledger_entries.clone().unwrap_or_default(),
+        Scope::Touched => HashMap::new(),
+    };
+    entries.extend(response.read_entries.clone());
+    if response.status == "success" {
+        if let Some(meta) = response.result_meta_xdr.as_deref() {
+            scenario::apply_changes(&mut entries, meta)?;
+        }
+    }
+    Ok(SnapshotFile {
+        ledger_sequence: response.ledger_sequence,
+        entries,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation;
+    use base64::Engine;
+    use soroban_env_host::xdr::{
+        ContractDataDurability, ContractDataEntry, ContractId, ExtensionPoint, Hash, LedgerEntry,
+        LedgerEntryChange, LedgerEntryChanges, LedgerEntryData, LedgerEntryExt, LedgerKey,
+        LedgerKeyContractData, Limits, OperationMeta, ScAddress, ScVal, TransactionMeta,
+        TransactionMetaV3, WriteXdr,
+    };
+
+    fn data(key: u32, value: u32) -> (LedgerKey, LedgerEntry) {
+        let contract = ScAddress::Contract(ContractId(Hash([1; 32])));
+        let entry = LedgerEntry {
+            last_modified_ledger_seq: 7,
+            data: LedgerEntryData::ContractData(ContractDataEntry {
+                ext: ExtensionPoint::V0,
+                contract: contract.clone(),
+                key: ScVal::U32(key),
+                durability: ContractDataDurability::Persistent,
+                val: ScVal::U32(value),
+            }),
+            ext: LedgerEntryExt::V0,
+        };
+        let key = LedgerKey::ContractData(LedgerKeyContractData {
+            contract,
+            key: ScVal::U32(key),
+            durability: ContractDataDurability::Persistent,
+        });
+        (key, entry)
+    }
+
+    fn b64(xdr: &impl WriteXdr) -> String {
+        xdr.to_xdr_base64(Limits::none()).unwrap()
+    }
+
+    fn pair(key: u32, value: u32) -> (String, String) {
+        let (key, entry) = data(key, value);
+        (b64(&key), b64(&entry))
+    }
+
+    /// A meta writing 2 for key 1 and removing key 2.
+    fn meta() -> String {
+        let changes = vec![
+            LedgerEntryChange::Updated(data(1, 2).1),
+            LedgerEntryChange::Removed(data(2, 0).0),
+        ];
+        let meta = TransactionMeta::V3(TransactionMetaV3 {
+            operations: vec![OperationMeta {
+                changes: LedgerEntryChanges(changes.try_into().unwrap()),
+            }]
+            .try_into()
+            .unwrap(),
+            ..TransactionMetaV3::default()
+        });
+        base64::engine::general_purpose::STANDARD.encode(meta.to_xdr(Limits::none()).unwrap())
+    }
+
+    #[test]
+    fn test_state_applies_changes_to_the_scope() {
+        let (key, entry) = pair(3, 3);
+        let request = serde_json::from_value::<SimulationRequest>(serde_json::json!({
+            "envelope_xdr": "AAAA",
+            "result_meta_xdr": "",
+            "enable_optimization_advisor": false,
+            "timestamp": "",
+            "ledger_entries": { key: entry },
+        }))
+        .unwrap();
+        let mut response = simulation::error_response(String::new());
+        response.status = "success".to_string();
+        response.ledger_sequence = Some(20);
+        response.read_entries = [pair(1, 1), pair(2, 2)].into_iter().collect();
+        response.result_meta_xdr = Some(meta());
+
+        let all = state(&request, &response, Scope::All).unwrap();
+        assert_eq!(all.ledger_sequence, Some(20));
+        assert_eq!(all.entries, HashMap::from([pair(1, 2), pair(3, 3)]));
+
+        let touched = state(&request, &response, Scope::Touched).unwrap();
+        assert_eq!(touched.entries, HashMap::from([pair(1, 2)]));
+
+        // A failed transaction changes nothing.
+        response.status = "error".to_string();
+        let failed = state(&request, &response, Scope::Touched).unwrap();
+        assert_eq!(failed.entries, HashMap::from([pair(1, 1), pair(2, 2)]));
+    }
+}
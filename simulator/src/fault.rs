@@ -0,0 +1,406 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Failures injected into a simulation.
+//!
+//! A request's `fault_injection` makes a simulation fail in a chosen way, so
+//! wallets and backends can be tested against each failure mode without
+//! waiting for it on a network:
+//!
+//! - `drop_entries` answers ledger keys as missing, as if they had never
+//!   been created;
+//! - `cpu_limit` and `memory_limit` lower the transaction's budget so that
+//!   it runs out;
+//! - `contract_errors` make a contract function fail with a contract error,
+//!   as `panic_with_error!` would, instead of running.
+//!
+//! A failing function is patched into the contract's Wasm: a new function
+//! of the same type returns the error, and the export is pointed at it, so
+//! no other function moves. The patched code is served under its own hash
+//! with the contract instance pointed at it, leaving the contract's storage
+//! as it was.
+
+use crate::simulation::SimulationError;
+use crate::snapshot::decode_ledger_key;
+use crate::source::LayeredSource;
+use crate::types::{ContractFault, FaultInjection};
+use sha2::{Digest, Sha256};
+use soroban_env_host::xdr::{
+    ContractCodeEntry, ContractCodeEntryExt, ContractDataDurability, ContractExecutable, Hash,
+    LedgerEntry, LedgerEntryData, LedgerKey, LedgerKeyContractCode, LedgerKeyContractData,
+    ScAddress, ScVal,
+};
+use soroban_env_host::{Error as EnvError, Val};
+use wasmparser::{ExternalKind, FuncType, Parser, Payload, TypeRef, ValType};
+
+/// Applies the state faults of `faults` to `state`; the budget limits are
+/// left to the caller. Returns a description of each fault.
+pub fn inject(
+    state: &mut LayeredSource,
+    faults: &FaultInjection,
+) -> Result<Vec<String>, SimulationError> {
+    let mut injected = Vec::new();
+    // The code of a failing contract is read before any entry is dropped.
+    for (index, fault) in faults.contract_errors.iter().enumerate() {
+        fail_function(state, fault).map_err(|e| {
+            SimulationError::InvalidRequest(format!(
+                "Invalid fault_injection.contract_errors[{index}]: {e}"
+            ))
+        })?;
+        injected.push(format!(
+            "{}::{} fails with Error(Contract, #{})",
+            fault.contract_id, fault.function, fault.code
+        ));
+    }
+    for (index, key) in faults.drop_entries.iter().enumerate() {
+        let key = decode_ledger_key(key).map_err(|e| {
+            SimulationError::InvalidRequest(format!(
+                "Invalid fault_injection.drop_entries[{index}]: {e}"
+            ))
+        })?;
+        injected.push(format!("{} entry dropped", key.name()));
+        state.drop_entry(key);
+    }
+    if let Some(limit) = faults.cpu_limit {
+        injected.push(format!("CPU limited to {limit} instructions"));
+    }
+    if let Some(limit) = faults.memory_limit {
+        injected.push(format!("Memory limited to {limit} bytes"));
+    }
+    Ok(injected)
+}
+
+/// Points `fault`'s contract at a copy of its code whose function fails.
+fn fail_function(state: &mut LayeredSource, fault: &ContractFault) -> Result<(), String> {
+    let contract: ScAddress = fault
+        .contract_id
+        .parse()
+        .map_err(|e| format!("contract_id: {e}"))?;
+    let key = LedgerKey::ContractData(LedgerKeyContractData {
+        contract,
+        key: ScVal::LedgerKeyContractInstance,
+        durability: ContractDataDurability::Persistent,
+    });
+    let mut entry = state
+        .peek(&key)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("{} is not in the ledger state", fault.contract_id))?
+        .entry;
+    let LedgerEntryData::ContractData(data) = &mut entry.data else {
+        return Err(format!("{} has no contract instance", fault.contract_id));
+    };
+    let ScVal::ContractInstance(instance) = &mut data.val else {
+        return Err(format!("{} has no contract instance", fault.contract_id));
+    };
+    let ContractExecutable::Wasm(hash) = &instance.executable else {
+        return Err(format!("{} is not a Wasm contract", fault.contract_id));
+    };
+    let wasm = {
+        let code = state
+            .peek_code(hash)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| {
+                format!(
+                    "the code of {} is not in the ledger state",
+                    fault.contract_id
+                )
+            })?;
+        let error = Val::from(EnvError::from_contract_error(fault.code)).get_payload();
+        fail_export(&code.wasm, &fault.function, error as i64)?
+    };
+
+    let hash = Hash(Sha256::digest(&wasm).into());
+    let code = LedgerEntry {
+        last_modified_ledger_seq: entry.last_modified_ledger_seq,
+        data: LedgerEntryData::ContractCode(ContractCodeEntry {
+            ext: ContractCodeEntryExt::V0,
+            hash: hash.clone(),
+            code: wasm.try_into().map_err(|e| format!("patched code: {e}"))?,
+        }),
+        ext: entry.ext.clone(),
+    };
+    state
+        .insert_local(
+            &LedgerKey::ContractCode(LedgerKeyContractCode { hash: hash.clone() }),
+            code,
+        )
+        .map_err(|e| e.to_string())?;
+    instance.executable = ContractExecutable::Wasm(hash);
+    state.insert_local(&key, entry).map_err(|e| e.to_string())
+}
+
+/// `wasm` with its export `function` returning the value `result` instead.
+fn fail_export(wasm: &[u8], function: &str, result: i64) -> Result<Vec<u8>, String> {
+    let mut types: Vec<FuncType> = Vec::new();
+    let mut functions: Vec<u32> = Vec::new();
+    let mut defined = 0;
+    let mut exported = None;
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload.map_err(|e| e.to_string())? {
+            Payload::TypeSection(section) => {
+                for ty in section.into_iter_err_on_gc_types() {
+                    types.push(ty.map_err(|e| e.to_string())?);
+                }
+            }
+            Payload::ImportSection(section) => {
+                for import in section {
+                    if let TypeRef::Func(ty) = import.map_err(|e| e.to_string())?.ty {
+                        functions.push(ty);
+                    }
+                }
+            }
+            Payload::FunctionSection(section) => {
+                for ty in section {
+                    functions.push(ty.map_err(|e| e.to_string())?);
+                    defined += 1;
+                }
+            }
+            Payload::ExportSection(section) => {
+                for export in section {
+                    let export = export.map_err(|e| e.to_string())?;
+                    if export.kind == ExternalKind::Func && export.name == function {
+                        exported = Some(export.index);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    let ty = exported
+        .and_then(|index| functions.get(index as usize))
+        .copied()
+        .ok_or_else(|| format!("the contract exports no function `{function}`"))?;
+    if types.get(ty as usize).map(FuncType::results) != Some(&[ValType::I64][..]) {
+        return Err(format!("`{function}` does not return a value"));
+    }
+    if defined == 0 {
+        return Err("the contract defines no functions".to_string());
+    }
+    let failing = u32::try_from(functions.len()).map_err(|e| e.to_string())?;
+
+    let mut patched = wasm[..8].to_vec();
+    for payload in Parser::new(0).parse_all(wasm) {
+        let payload = payload.map_err(|e| e.to_string())?;
+        let Some((id, range)) = payload.as_section() else {
+            continue;
+        };
+        let mut content = Vec::new();
+        match payload {
+            Payload::FunctionSection(section) => {
+                leb(&mut content, u64::from(section.count()) + 1);
+                for ty in section {
+                    leb(&mut content, u64::from(ty.map_err(|e| e.to_string())?));
+                }
+                leb(&mut content, u64::from(ty));
+            }
+            Payload::ExportSection(section) => {
+                leb(&mut content, u64::from(section.count()));
+                for export in section {
+                    let export = export.map_err(|e| e.to_string())?;
+                    leb(&mut content, export.name.len() as u64);
+                    content.extend_from_slice(export.name.as_bytes());
+                    content.push(export.kind as u8);
+                    let index = match export.kind {
+                        ExternalKind::Func if export.name == function => failing,
+                        _ => export.index,
+                    };
+                    leb(&mut content, u64::from(index));
+                }
+            }
+            Payload::CodeSectionStart { count, .. } => {
+                leb(&mut content, u64::from(count) + 1);
+                let bodies = &wasm[range];
+                let count_len = bodies.iter().position(|byte| byte & 0x80 == 0).unwrap_or(0) + 1;
+                content.extend_from_slice(&bodies[count_len..]);
+                // No locals; `i64.const result`, `end`.
+                let mut body = vec![0, 0x42];
+                sleb(&mut body, result);
+                body.push(0x0b);
+                leb(&mut content, body.len() as u64);
+                content.extend_from_slice(&body);
+            }
+            _ => content.extend_from_slice(&wasm[range]),
+        }
+        patched.push(id);
+        leb(&mut patched, content.len() as u64);
+        patched.extend_from_slice(&content);
+    }
+    Ok(patched)
+}
+
+/// Appends `value` as unsigned LEB128.
+fn leb(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Appends `value` as signed LEB128.
+fn sleb(out: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::LedgerSnapshot;
+    use crate::source::SnapshotLedgerSource;
+    use soroban_env_host::xdr::{
+        ContractDataEntry, ContractId, ExtensionPoint, LedgerEntryExt, Limits, ScContractInstance,
+        WriteXdr,
+    };
+    use wasmparser::Operator;
+
+    const MODULE: &str = r#"
+        (module
+          (type (func (param i64) (result i64)))
+          (import "x" "_" (func (type 0)))
+          (func $hello (type 0) local.get 0)
+          (func $other (type 0) local.get 0)
+          (memory 1)
+          (export "hello" (func $hello))
+          (export "other" (func $other))
+          (export "memory" (memory 0)))
+    "#;
+
+    fn exports(wasm: &[u8]) -> Vec<(String, u32)> {
+        let mut exports = Vec::new();
+        for payload in Parser::new(0).parse_all(wasm) {
+            if let Payload::ExportSection(section) = payload.unwrap() {
+                for export in section {
+                    let export = export.unwrap();
+                    exports.push((export.name.to_string(), export.index));
+                }
+            }
+        }
+        exports
+    }
+
+    #[test]
+    fn test_failing_export_returns_the_error() {
+        let wasm = wat::parse_str(MODULE).unwrap();
+        let error = Val::from(EnvError::from_contract_error(7)).get_payload() as i64;
+        let patched = fail_export(&wasm, "hello", error).unwrap();
+        wasmparser::validate(&patched).unwrap();
+
+        // One import and two functions come before the failing one.
+        assert_eq!(
+            exports(&patched),
+            vec![
+                ("hello".to_string(), 3),
+                ("other".to_string(), 2),
+                ("memory".to_string(), 0)
+            ]
+        );
+        let mut bodies = Vec::new();
+        for payload in Parser::new(0).parse_all(&patched) {
+            if let Payload::CodeSectionEntry(body) = payload.unwrap() {
+                bodies.push(body);
+            }
+        }
+        assert_eq!(bodies.len(), 3);
+        let operators = bodies[2]
+            .get_operators_reader()
+            .unwrap()
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert!(matches!(
+            operators[..],
+            [Operator::I64Const { value }, Operator::End] if value == error
+        ));
+
+        let missing = fail_export(&wasm, "goodbye", error).unwrap_err();
+        assert!(missing.contains("exports no function `goodbye`"));
+    }
+
+    #[test]
+    fn test_inject_drops_entries_and_repoints_the_contract() {
+        let wasm = wat::parse_str(MODULE).unwrap();
+        let hash = Hash(Sha256::digest(&wasm).into());
+        let contract = ScAddress::Contract(ContractId(Hash([4; 32])));
+        let instance_key = LedgerKey::ContractData(LedgerKeyContractData {
+            contract: contract.clone(),
+            key: ScVal::LedgerKeyContractInstance,
+            durability: ContractDataDurability::Persistent,
+        });
+        let code_key = LedgerKey::ContractCode(LedgerKeyContractCode { hash: hash.clone() });
+        let entry = |data| LedgerEntry {
+            last_modified_ledger_seq: 5,
+            data,
+            ext: LedgerEntryExt::V0,
+        };
+        let mut snapshot = LedgerSnapshot::new();
+        snapshot
+            .insert_entry(
+                &instance_key,
+                entry(LedgerEntryData::ContractData(ContractDataEntry {
+                    ext: ExtensionPoint::V0,
+                    contract: contract.clone(),
+                    key: ScVal::LedgerKeyContractInstance,
+                    durability: ContractDataDurability::Persistent,
+                    val: ScVal::ContractInstance(ScContractInstance {
+                        executable: ContractExecutable::Wasm(hash.clone()),
+                        storage: None,
+                    }),
+                })),
+            )
+            .unwrap();
+        snapshot
+            .insert_entry(
+                &code_key,
+                entry(LedgerEntryData::ContractCode(ContractCodeEntry {
+                    ext: ContractCodeEntryExt::V0,
+                    hash: hash.clone(),
+                    code: wasm.try_into().unwrap(),
+                })),
+            )
+            .unwrap();
+
+        let mut state = LayeredSource::new(SnapshotLedgerSource::new(snapshot), None, 5);
+        let faults = FaultInjection {
+            drop_entries: vec![code_key.to_xdr_base64(Limits::none()).unwrap()],
+            cpu_limit: Some(1_000),
+            memory_limit: None,
+            contract_errors: vec![ContractFault {
+                contract_id: contract.to_string(),
+                function: "other".to_string(),
+                code: 2,
+            }],
+        };
+        let injected = inject(&mut state, &faults).unwrap();
+        assert_eq!(injected.len(), 3);
+        assert!(injected[0].ends_with("::other fails with Error(Contract, #2)"));
+
+        assert!(state.peek(&code_key).unwrap().is_none());
+        let LedgerEntryData::ContractData(data) =
+            state.peek(&instance_key).unwrap().unwrap().entry.data
+        else {
+            panic!("instance is not contract data");
+        };
+        let ScVal::ContractInstance(ScContractInstance {
+            executable: ContractExecutable::Wasm(patched),
+            ..
+        }) = data.val
+        else {
+            panic!("instance is not a Wasm contract");
+        };
+        assert_ne!(patched, hash);
+        let code = state.peek_code(&patched).unwrap().unwrap();
+        assert_eq!(exports(&code.wasm)[1], ("other".to_string(), 3));
+    }
+}
@@ -0,0 +1,301 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Who pays for a transaction, and whether they can.
+//!
+//! The fee source pays the whole fee: the transaction source, or for a fee
+//! bump the bump's fee source, leaving the inner source to pay nothing. The
+//! fee is the inclusion fee bid plus the declared resource fee, of which
+//! rent is the refundable part. stellar-core rejects the transaction when
+//! the fee source's available balance, what it holds above its minimum
+//! balance and selling liabilities, does not cover the full fee; the
+//! minimum balance counts the reserves of its subentries and of the entries
+//! it sponsors, less those a sponsor pays for. [`fee_source`] reports each
+//! portion with the account it is charged to and checks the fee source's
+//! balance in the simulated state.
+
+use crate::authorization;
+use crate::simulation;
+use crate::source::SourceEntry;
+use schemars::JsonSchema;
+use serde::Serialize;
+use soroban_env_host::xdr::{
+    AccountEntry, AccountEntryExt, AccountEntryExtensionV1Ext, LedgerEntryData, LedgerEntryExt,
+    LedgerKey, LedgerKeyAccount, TransactionEnvelope,
+};
+
+/// The fee of a transaction and the account paying it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct FeeSource {
+    /// The account charged the fee (`G...`).
+    pub account: String,
+    /// The inner transaction's source, which a fee bump relieves of the fee.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee_bump_of: Option<String>,
+    /// The most the fee source can be charged, in stroops.
+    pub max_fee: i64,
+    /// Portions of `max_fee` and who pays them.
+    pub charges: Vec<FeeCharge>,
+    /// Balance of the fee source, in stroops, when the state has its
+    /// account.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balance: Option<i64>,
+    /// Balance the fee source must keep for its reserves.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub minimum_balance: Option<i64>,
+    /// Balance the fee source can spend on fees: `balance` less
+    /// `minimum_balance` and selling liabilities.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub available: Option<i64>,
+    /// Account paying the reserve of the fee source's own account entry.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reserve_sponsor: Option<String>,
+    /// Why the fee source could not pay, when that can be told.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub problem: Option<String>,
+}
+
+/// A portion of the fee.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct FeeCharge {
+    /// `inclusion_fee`, the bid for a place in the ledger; `resource_fee`,
+    /// declared in `SorobanTransactionData`; or `rent`, the part of the
+    /// resource fee paid for keeping entries live.
+    pub portion: &'static str,
+    /// The account it is charged to (`G...`).
+    pub account: String,
+    pub stroops: i64,
+}
+
+/// Who pays the fee of `envelope`, whose run charged `rent` stroops, with
+/// accounts looked up by `lookup` and reserves of `base_reserve` stroops.
+pub fn fee_source(
+    envelope: &TransactionEnvelope,
+    lookup: impl Fn(&LedgerKey) -> Option<SourceEntry>,
+    base_reserve: u32,
+    rent: Option<i64>,
+) -> FeeSource {
+    let inner_source = authorization::account_id(authorization::transaction_source(envelope));
+    let (account_id, fee_bump_of) = match envelope {
+        TransactionEnvelope::TxFeeBump(bump) => (
+            authorization::account_id(bump.tx.fee_source.clone()),
+            Some(inner_source.to_string()),
+        ),
+        _ => (inner_source, None),
+    };
+    let account = account_id.to_string();
+    let max_fee = i64::try_from(simulation::transaction_fee_stroops(envelope)).unwrap_or(i64::MAX);
+    let resource_fee = simulation::soroban_data(envelope).map(|data| data.resource_fee);
+
+    let charge = |portion, stroops| FeeCharge {
+        portion,
+        account: account.clone(),
+        stroops,
+    };
+    let mut charges = vec![charge(
+        "inclusion_fee",
+        max_fee.saturating_sub(resource_fee.unwrap_or(0)).max(0),
+    )];
+    charges.extend(resource_fee.map(|fee| charge("resource_fee", fee)));
+    charges.extend(rent.map(|rent| charge("rent", rent)));
+
+    let entry = lookup(&LedgerKey::Account(LedgerKeyAccount {
+        account_id: account_id.clone(),
+    }));
+    let reserve_sponsor = entry.as_ref().and_then(|entry| match &entry.entry.ext {
+        LedgerEntryExt::V1(ext) => ext.sponsoring_id.0.as_ref().map(ToString::to_string),
+        LedgerEntryExt::V0 => None,
+    });
+    let balances = entry.and_then(|entry| match entry.entry.data {
+        LedgerEntryData::Account(entry) => Some(balances(&entry, base_reserve)),
+        _ => None,
+    });
+
+    let problem = match (resource_fee, balances) {
+        (Some(resource_fee), _) if max_fee < resource_fee => Some(format!(
+            "The fee of {max_fee} stroops does not cover the declared resource fee of \
+             {resource_fee}"
+        )),
+        (_, None) => Some(format!("{account} is not in the ledger state")),
+        (_, Some((_, minimum, available))) if available < max_fee => Some(format!(
+            "{account} is underfunded: it has {available} stroops above its minimum balance of \
+             {minimum} to pay a fee of up to {max_fee}"
+        )),
+        _ => None,
+    };
+
+    FeeSource {
+        account,
+        fee_bump_of,
+        max_fee,
+        charges,
+        balance: balances.map(|(balance, _, _)| balance),
+        minimum_balance: balances.map(|(_, minimum, _)| minimum),
+        available: balances.map(|(_, _, available)| available),
+        reserve_sponsor,
+        problem,
+    }
+}
+
+/// Balance, minimum balance and available balance of `account`, as
+/// stellar-core counts them.
+fn balances(account: &AccountEntry, base_reserve: u32) -> (i64, i64, i64) {
+    let (selling_liabilities, sponsoring, sponsored) = match &account.ext {
+        AccountEntryExt::V0 => (0, 0, 0),
+        AccountEntryExt::V1(v1) => match &v1.ext {
+            AccountEntryExtensionV1Ext::V0 => (v1.liabilities.selling, 0, 0),
+            AccountEntryExtensionV1Ext::V2(v2) => (
+                v1.liabilities.selling,
+                i64::from(v2.num_sponsoring),
+                i64::from(v2.num_sponsored),
+            ),
+        },
+    };
+    let reserves = 2 + i64::from(account.num_sub_entries) + sponsoring - sponsored;
+    let minimum = reserves.saturating_mul(i64::from(base_reserve));
+    let available = account
+        .balance
+        .saturating_sub(minimum)
+        .saturating_sub(selling_liabilities);
+    (account.balance, minimum, available)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::xdr::{
+        AccountEntryExtensionV1, AccountEntryExtensionV2, AccountEntryExtensionV2Ext, AccountId,
+        FeeBumpTransaction, FeeBumpTransactionEnvelope, FeeBumpTransactionExt,
+        FeeBumpTransactionInnerTx, LedgerEntry, LedgerEntryExtensionV1, LedgerEntryExtensionV1Ext,
+        Liabilities, Memo, MuxedAccount, Preconditions, PublicKey, SequenceNumber,
+        SorobanResources, SorobanTransactionData, SorobanTransactionDataExt, Thresholds,
+        Transaction, TransactionExt, TransactionV1Envelope, Uint256,
+    };
+
+    fn account_id(byte: u8) -> AccountId {
+        AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([byte; 32])))
+    }
+
+    fn account(id: u8, balance: i64, sponsor: Option<u8>) -> SourceEntry {
+        SourceEntry {
+            entry: LedgerEntry {
+                last_modified_ledger_seq: 1,
+                data: LedgerEntryData::Account(AccountEntry {
+                    account_id: account_id(id),
+                    balance,
+                    seq_num: SequenceNumber(1),
+                    num_sub_entries: 3,
+                    inflation_dest: None,
+                    flags: 0,
+                    home_domain: Default::default(),
+                    thresholds: Thresholds([1, 0, 0, 0]),
+                    signers: Default::default(),
+                    ext: AccountEntryExt::V1(AccountEntryExtensionV1 {
+                        liabilities: Liabilities {
+                            buying: 0,
+                            selling: 1_000,
+                        },
+                        ext: AccountEntryExtensionV1Ext::V2(AccountEntryExtensionV2 {
+                            num_sponsored: 1,
+                            num_sponsoring: 0,
+                            signer_sponsoring_i_ds: Default::default(),
+                            ext: AccountEntryExtensionV2Ext::V0,
+                        }),
+                    }),
+                }),
+                ext: LedgerEntryExt::V1(LedgerEntryExtensionV1 {
+                    sponsoring_id: sponsor.map(account_id).into(),
+                    ext: LedgerEntryExtensionV1Ext::V0,
+                }),
+            },
+            live_until: None,
+        }
+    }
+
+    fn transaction(fee: u32, resource_fee: i64) -> Transaction {
+        Transaction {
+            source_account: MuxedAccount::Ed25519(Uint256([1; 32])),
+            fee,
+            seq_num: SequenceNumber(2),
+            cond: Preconditions::None,
+            memo: Memo::None,
+            operations: Default::default(),
+            ext: TransactionExt::V1(SorobanTransactionData {
+                ext: SorobanTransactionDataExt::V0,
+                resources: SorobanResources {
+                    footprint: Default::default(),
+                    instructions: 0,
+                    disk_read_bytes: 0,
+                    write_bytes: 0,
+                },
+                resource_fee,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_transaction_source_pays_every_portion() {
+        let envelope = TransactionEnvelope::Tx(TransactionV1Envelope {
+            tx: transaction(1_100, 1_000),
+            signatures: Default::default(),
+        });
+        let lookup = |_: &LedgerKey| Some(account(1, 25_000_000, Some(9)));
+
+        let report = fee_source(&envelope, lookup, 5_000_000, Some(300));
+        assert_eq!(report.account, account_id(1).to_string());
+        assert_eq!(report.fee_bump_of, None);
+        assert_eq!(
+            report
+                .charges
+                .iter()
+                .map(|c| (c.portion, c.stroops))
+                .collect::<Vec<_>>(),
+            vec![
+                ("inclusion_fee", 100),
+                ("resource_fee", 1_000),
+                ("rent", 300)
+            ]
+        );
+        // Two base reserves and three subentries, one of them sponsored.
+        assert_eq!(report.minimum_balance, Some(20_000_000));
+        assert_eq!(report.available, Some(4_999_000));
+        assert_eq!(report.reserve_sponsor, Some(account_id(9).to_string()));
+        assert_eq!(report.problem, None);
+    }
+
+    #[test]
+    fn test_underfunded_fee_bump_sponsor_is_flagged() {
+        let envelope = TransactionEnvelope::TxFeeBump(FeeBumpTransactionEnvelope {
+            tx: FeeBumpTransaction {
+                fee_source: MuxedAccount::Ed25519(Uint256([2; 32])),
+                fee: 10_000_000,
+                inner_tx: FeeBumpTransactionInnerTx::Tx(TransactionV1Envelope {
+                    tx: transaction(1_100, 1_000),
+                    signatures: Default::default(),
+                }),
+                ext: FeeBumpTransactionExt::V0,
+            },
+            signatures: Default::default(),
+        });
+        let lookup = |key: &LedgerKey| match key {
+            LedgerKey::Account(key) if key.account_id == account_id(2) => {
+                Some(account(2, 25_000_000, None))
+            }
+            _ => None,
+        };
+
+        let report = fee_source(&envelope, lookup, 5_000_000, None);
+        assert_eq!(report.account, account_id(2).to_string());
+        assert_eq!(report.fee_bump_of, Some(account_id(1).to_string()));
+        assert!(report
+            .charges
+            .iter()
+            .all(|charge| charge.account == report.account));
+        assert_eq!(report.charges[0].stroops, 9_999_000);
+        assert!(report.problem.unwrap().contains("underfunded"));
+
+        let report = fee_source(&envelope, |_| None, 5_000_000, None);
+        assert_eq!(report.balance, None);
+        assert!(report.problem.unwrap().contains("not in the ledger state"));
+    }
+}
@@ -0,0 +1,474 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Persistent local forks of a network.
+//!
+//! `erst-sim fork create` records a network's RPC endpoint and a ledger to
+//! fork it at; nothing is downloaded up front. `erst-sim fork simulate` runs
+//! requests against the fork: an entry the fork does not hold yet is fetched
+//! from the network the first time a simulation touches it and kept in the
+//! fork file, as is the fact that a key does not exist, so every later read
+//! of it is local. A successful transaction's ledger changes are written to
+//! the fork and the fork's ledger advances by one (five seconds of close
+//! time), so the next request sees them, much as with Anvil or Hardhat
+//! forking an EVM chain.
+//!
+//! soroban-rpc only serves current state, so an entry the network modified
+//! after the fork ledger cannot be fetched as it was at that ledger. Such a
+//! read fails rather than mixing the fork's state with later network state;
+//! forking again at a later ledger picks the change up.
+//!
+//! The fork file is a snapshot file (`ledgerSequence`, `ledgerEntries`) with
+//! a `fork` object describing the fork, so `repl` and `snapshot build` read
+//! it like any other snapshot.
+
+use crate::memory::MemoryLimits;
+use crate::scenario;
+use crate::simulation;
+use crate::source::cache::CachedSource;
+use crate::source::retry::{RetryPolicy, RetryingSource};
+use crate::source::rpc::RpcSource;
+use crate::source::{self, LedgerSource, SourceEntry, SourceError};
+use crate::types::{SimulationRequest, SimulationResponse};
+use serde::{Deserialize, Serialize};
+use soroban_env_host::xdr::{
+    LedgerEntry, LedgerEntryData, LedgerEntryExt, LedgerKey, Limits, TtlEntry, WriteXdr,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Seconds of close time per ledger the fork advances.
+pub const LEDGER_INTERVAL_SECS: u64 = 5;
+
+/// Entries the fetch cache of one simulation holds.
+const FETCH_CACHE_ENTRIES: usize = 4096;
+
+/// Where a fork came from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForkInfo {
+    /// soroban-rpc endpoint entries are fetched from.
+    pub rpc_url: String,
+    /// Network name or passphrase requests run on unless they give one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    /// Ledger of the network the fork was taken at.
+    pub at_ledger: u32,
+    /// Close time of the fork's current ledger.
+    pub ledger_close_time: u64,
+    /// Base64 `LedgerKey`s of entries that do not exist on the fork: absent
+    /// from the network at the fork ledger, or deleted since.
+    #[serde(default)]
+    pub absent: BTreeSet<String>,
+}
+
+/// A fork file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ForkFile {
+    fork: ForkInfo,
+    ledger_sequence: u32,
+    ledger_entries: Vec<(String, String)>,
+}
+
+/// A local fork of a network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fork {
+    pub info: ForkInfo,
+    /// The fork's current ledger.
+    pub ledger_sequence: u32,
+    /// Base64 `LedgerKey` to base64 `LedgerEntry` of the entries the fork
+    /// holds.
+    pub entries: HashMap<String, String>,
+}
+
+impl Fork {
+    /// A fork of the network behind `rpc_url` at `at_ledger`, the latest
+    /// ledger of `network_source` by default.
+    pub fn create(
+        rpc_url: &str,
+        network: Option<String>,
+        at_ledger: Option<u32>,
+        network_source: &dyn LedgerSource,
+    ) -> Result<Self, String> {
+        let latest = network_source
+            .latest_ledger()
+            .map_err(|e| format!("Failed to read the latest ledger: {e}"))?;
+        let at_ledger = at_ledger.unwrap_or(latest);
+        if at_ledger > latest {
+            return Err(format!(
+                "Ledger {at_ledger} is ahead of the network's latest ledger {latest}"
+            ));
+        }
+        Ok(Self {
+            info: ForkInfo {
+                rpc_url: rpc_url.to_string(),
+                network,
+                at_ledger,
+                ledger_close_time: simulation::ledger_timestamp(),
+                absent: BTreeSet::new(),
+            },
+            ledger_sequence: at_ledger,
+            entries: HashMap::new(),
+        })
+    }
+
+    /// Reads a fork file.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        let file: ForkFile = serde_json::from_str(&json)
+            .map_err(|e| format!("Invalid fork file {}: {e}", path.display()))?;
+        Ok(Self {
+            info: file.fork,
+            ledger_sequence: file.ledger_sequence,
+            entries: file.ledger_entries.into_iter().collect(),
+        })
+    }
+
+    /// Writes the fork file, entries sorted by key.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let sorted = self.entries.clone().into_iter().collect::<BTreeMap<_, _>>();
+        let file = ForkFile {
+            fork: self.info.clone(),
+            ledger_sequence: self.ledger_sequence,
+            ledger_entries: sorted.into_iter().collect(),
+        };
+        let json = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+        std::fs::write(path, json).map_err(|e| format!("Failed to write {}: {e}", path.display()))
+    }
+
+    /// Simulates `request` at the fork's next ledger, fetching the entries
+    /// it touches that the fork lacks from `network_source`. With `commit`,
+    /// a successful transaction's changes are applied and the fork's ledger
+    /// advances.
+    pub fn simulate(
+        &mut self,
+        request: &SimulationRequest,
+        limits: &MemoryLimits,
+        network_source: Arc<dyn LedgerSource>,
+        commit: bool,
+    ) -> Result<SimulationResponse, String> {
+        let sequence = self.ledger_sequence + 1;
+        let mut entries = self.entries.clone();
+        let expired = scenario::expire(&mut entries, sequence);
+        if let Some(own) = &request.ledger_entries {
+            entries.extend(own.clone());
+        }
+
+        let mut request = request.clone();
+        request.ledger_entries = Some(entries);
+        request.ledger_sequence = Some(sequence);
+        request.ledger_close_time = Some(self.info.ledger_close_time + LEDGER_INTERVAL_SECS);
+        if request.network.is_none() && request.network_passphrase.is_none() {
+            request.network.clone_from(&self.info.network);
+        }
+
+        let mut absent = self.info.absent.clone();
+        absent.extend(expired);
+        let source = Arc::new(ForkSource::new(
+            network_source,
+            self.info.at_ledger,
+            sequence,
+            absent,
+        ));
+        let remote = Arc::new(CachedSource::new(source.clone(), FETCH_CACHE_ENTRIES));
+        let response = simulation::run(&request, limits, None, Some(remote), None);
+        // What was fetched is the network's state at the fork ledger whether
+        // or not the run succeeded, so it is kept either way.
+        self.record(source.fetched())?;
+        let response = response.map_err(|e| e.to_string())?;
+
+        if commit && response.status == "success" {
+            if let Some(meta) = response.result_meta_xdr.as_deref() {
+                self.commit(sequence, meta)?;
+            }
+        }
+        Ok(response)
+    }
+
+    /// Keeps entries fetched from the network, with their TTL entries, and
+    /// the keys found absent.
+    fn record(&mut self, fetched: Vec<(LedgerKey, Option<SourceEntry>)>) -> Result<(), String> {
+        let encode = |xdr: Result<String, soroban_env_host::xdr::Error>| {
+            xdr.map_err(|e| format!("Failed to encode fetched entry: {e}"))
+        };
+        for (key, found) in fetched {
+            let key_xdr = encode(key.to_xdr_base64(Limits::none()))?;
+            let Some(found) = found else {
+                self.info.absent.insert(key_xdr);
+                continue;
+            };
+            if let Some(live_until) = found.live_until {
+                let LedgerKey::Ttl(ttl_key) = source::ttl_key(&key).map_err(|e| e.to_string())?
+                else {
+                    continue;
+                };
+                let ttl = LedgerEntry {
+                    last_modified_ledger_seq: found.entry.last_modified_ledger_seq,
+                    data: LedgerEntryData::Ttl(TtlEntry {
+                        key_hash: ttl_key.key_hash.clone(),
+                        live_until_ledger_seq: live_until,
+                    }),
+                    ext: LedgerEntryExt::V0,
+                };
+                self.entries
+                    .entry(encode(
+                        LedgerKey::Ttl(ttl_key).to_xdr_base64(Limits::none()),
+                    )?)
+                    .or_insert(encode(ttl.to_xdr_base64(Limits::none()))?);
+            }
+            self.entries
+                .entry(key_xdr)
+                .or_insert(encode(found.entry.to_xdr_base64(Limits::none()))?);
+        }
+        Ok(())
+    }
+
+    /// Closes ledger `sequence` with the changes of a base64
+    /// `TransactionMeta`.
+    fn commit(&mut self, sequence: u32, meta_xdr: &str) -> Result<(), String> {
+        self.info
+            .absent
+            .extend(scenario::expire(&mut self.entries, sequence));
+        let before = self.entries.keys().cloned().collect::<BTreeSet<_>>();
+        scenario::apply_changes(&mut self.entries, meta_xdr)?;
+        for key in before {
+            if !self.entries.contains_key(&key) {
+                self.info.absent.insert(key);
+            }
+        }
+        let entries = &self.entries;
+        self.info.absent.retain(|key| !entries.contains_key(key));
+        self.ledger_sequence = sequence;
+        self.info.ledger_close_time += LEDGER_INTERVAL_SECS;
+        Ok(())
+    }
+}
+
+/// The soroban-rpc endpoint at `url`, retried per the environment.
+pub fn rpc(url: &str) -> Arc<dyn LedgerSource> {
+    Arc::new(RetryingSource::new(
+        Arc::new(RpcSource::new(url)),
+        RetryPolicy::from_env(),
+    ))
+}
+
+/// The network as seen from a fork: keys the fork knows are absent are not
+/// looked up, entries modified after the fork ledger are refused, and every
+/// answer is recorded so the fork can keep it.
+struct ForkSource {
+    network: Arc<dyn LedgerSource>,
+    at_ledger: u32,
+    ledger_sequence: u32,
+    absent: BTreeSet<String>,
+    fetched: Mutex<Vec<(LedgerKey, Option<SourceEntry>)>>,
+}
+
+impl ForkSource {
+    fn new(
+        network: Arc<dyn LedgerSource>,
+        at_ledger: u32,
+        ledger_sequence: u32,
+        absent: BTreeSet<String>,
+    ) -> Self {
+        Self {
+            network,
+            at_ledger,
+            ledger_sequence,
+            absent,
+            fetched: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn fetched(&self) -> Vec<(LedgerKey, Option<SourceEntry>)> {
+        std::mem::take(&mut *self.fetched.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+impl LedgerSource for ForkSource {
+    fn name(&self) -> &str {
+        "fork"
+    }
+
+    fn latest_ledger(&self) -> Result<u32, SourceError> {
+        Ok(self.ledger_sequence)
+    }
+
+    fn fetch(&self, keys: &[LedgerKey]) -> Result<Vec<Option<SourceEntry>>, SourceError> {
+        let mut wanted = Vec::new();
+        let mut known_absent = Vec::with_capacity(keys.len());
+        for key in keys {
+            let key_xdr = key
+                .to_xdr_base64(Limits::none())
+                .map_err(|e| SourceError::InvalidResponse(e.to_string()))?;
+            let absent = self.absent.contains(&key_xdr);
+            if !absent {
+                wanted.push((key.clone(), key_xdr));
+            }
+            known_absent.push(absent);
+        }
+        let found = if wanted.is_empty() {
+            Vec::new()
+        } else {
+            let wanted_keys = wanted
+                .iter()
+                .map(|(key, _)| key.clone())
+                .collect::<Vec<_>>();
+            self.network.fetch(&wanted_keys)?
+        };
+
+        for ((_, key_xdr), entry) in wanted.iter().zip(&found) {
+            let modified = entry.as_ref().map(|e| e.entry.last_modified_ledger_seq);
+            if let Some(modified) = modified.filter(|&modified| modified > self.at_ledger) {
+                return Err(SourceError::Unavailable(format!(
+                    "entry {key_xdr} was modified on the network at ledger {modified}, after \
+                     the fork ledger {}; fork at a later ledger to see it",
+                    self.at_ledger
+                )));
+            }
+        }
+        self.fetched
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend(wanted.into_iter().map(|(key, _)| key).zip(found.clone()));
+        let mut found = found.into_iter();
+        Ok(known_absent
+            .into_iter()
+            .map(|absent| if absent { None } else { found.next().flatten() })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::xdr::{
+        ContractDataDurability, ContractDataEntry, ContractId, ExtensionPoint, Hash,
+        LedgerKeyContractData, ScAddress, ScVal,
+    };
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn data_key(n: u32) -> LedgerKey {
+        LedgerKey::ContractData(LedgerKeyContractData {
+            contract: ScAddress::Contract(ContractId(Hash([1; 32]))),
+            key: ScVal::U32(n),
+            durability: ContractDataDurability::Persistent,
+        })
+    }
+
+    fn data_entry(n: u32, last_modified: u32) -> SourceEntry {
+        SourceEntry {
+            entry: LedgerEntry {
+                last_modified_ledger_seq: last_modified,
+                data: LedgerEntryData::ContractData(ContractDataEntry {
+                    ext: ExtensionPoint::V0,
+                    contract: ScAddress::Contract(ContractId(Hash([1; 32]))),
+                    key: ScVal::U32(n),
+                    durability: ContractDataDurability::Persistent,
+                    val: ScVal::U32(n),
+                }),
+                ext: LedgerEntryExt::V0,
+            },
+            live_until: Some(1_000),
+        }
+    }
+
+    /// A network holding entries 1 and 2, counting the keys it is asked for.
+    struct Network {
+        modified: u32,
+        asked: AtomicUsize,
+    }
+
+    impl LedgerSource for Network {
+        fn name(&self) -> &str {
+            "network"
+        }
+
+        fn latest_ledger(&self) -> Result<u32, SourceError> {
+            Ok(200)
+        }
+
+        fn fetch(&self, keys: &[LedgerKey]) -> Result<Vec<Option<SourceEntry>>, SourceError> {
+            self.asked.fetch_add(keys.len(), Ordering::Relaxed);
+            Ok(keys
+                .iter()
+                .map(|key| match key {
+                    LedgerKey::ContractData(data) => match data.key {
+                        ScVal::U32(n @ (1 | 2)) => Some(data_entry(n, self.modified)),
+                        _ => None,
+                    },
+                    _ => None,
+                })
+                .collect())
+        }
+    }
+
+    fn network(modified: u32) -> Arc<Network> {
+        Arc::new(Network {
+            modified,
+            asked: AtomicUsize::new(0),
+        })
+    }
+
+    fn key_xdr(key: &LedgerKey) -> String {
+        key.to_xdr_base64(Limits::none()).unwrap()
+    }
+
+    #[test]
+    fn test_keeps_fetched_entries_and_absent_keys() {
+        let network = network(90);
+        let mut fork = Fork::create("http://rpc", None, Some(100), network.as_ref()).unwrap();
+        assert_eq!(fork.ledger_sequence, 100);
+        assert!(Fork::create("http://rpc", None, Some(300), network.as_ref()).is_err());
+
+        let source = ForkSource::new(network.clone(), 100, 101, fork.info.absent.clone());
+        let found = source.fetch(&[data_key(1), data_key(3)]).unwrap();
+        assert_eq!(found, vec![Some(data_entry(1, 90)), None]);
+        fork.record(source.fetched()).unwrap();
+
+        // The entry and its TTL entry are kept, and key 3 is known absent.
+        assert_eq!(fork.entries.len(), 2);
+        assert!(fork.entries.contains_key(&key_xdr(&data_key(1))));
+        assert!(fork
+            .entries
+            .contains_key(&key_xdr(&source::ttl_key(&data_key(1)).unwrap())));
+        assert_eq!(fork.info.absent, BTreeSet::from([key_xdr(&data_key(3))]));
+
+        // Known absent keys are not asked for again.
+        let source = ForkSource::new(network.clone(), 100, 101, fork.info.absent.clone());
+        let found = source.fetch(&[data_key(3), data_key(2)]).unwrap();
+        assert_eq!(found, vec![None, Some(data_entry(2, 90))]);
+        assert_eq!(network.asked.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn test_refuses_entries_modified_after_the_fork_ledger() {
+        let source = ForkSource::new(network(150), 100, 101, BTreeSet::new());
+        let error = source.fetch(&[data_key(1)]).unwrap_err().to_string();
+        assert!(
+            error.contains("modified on the network at ledger 150"),
+            "{error}"
+        );
+        assert!(source.fetched().is_empty());
+    }
+
+    #[test]
+    fn test_fork_file_round_trips_as_a_snapshot() {
+        let mut fork = Fork::create("http://rpc", None, Some(100), network(90).as_ref()).unwrap();
+        let source = ForkSource::new(network(90), 100, 101, BTreeSet::new());
+        source.fetch(&[data_key(1), data_key(3)]).unwrap();
+        fork.record(source.fetched()).unwrap();
+
+        let path = std::env::temp_dir().join(format!("erst-fork-{}.json", std::process::id()));
+        fork.save(&path).unwrap();
+        let loaded = Fork::load(&path);
+        let snapshot = crate::snapshot::file::SnapshotFile::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), fork);
+        let snapshot = snapshot.unwrap();
+        assert_eq!(snapshot.ledger_sequence, Some(100));
+        assert_eq!(snapshot.entries, fork.entries);
+    }
+}
@@ -1,6 +1,7 @@
 // Copyright 2025 Erst Users
 // SPDX-License-Identifier: Apache-2.0
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -15,7 +16,7 @@ pub struct BudgetMetrics {
     pub total_operations: usize,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct OptimizationTip {
     pub category: String,
     pub severity: String, // "high", "medium", "low"
@@ -24,7 +25,7 @@ pub struct OptimizationTip {
     pub code_location: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct OptimizationReport {
     pub overall_efficiency: f64, // 0-100 score
     pub tips: Vec<OptimizationTip>,
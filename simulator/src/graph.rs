@@ -0,0 +1,207 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Contract call graphs for docs and architecture reviews.
+//!
+//! `erst-sim --graph dot calls.dot` (or `mermaid calls.mmd`) writes the
+//! graph of the response's `call_tree` next to the usual response: one node
+//! per contract, and one edge per caller, callee and function, labelled with
+//! the function, the number of calls if more than one, and their inclusive
+//! CPU instructions when the request set `call_costs`. Top-level calls come
+//! from a `transaction` node.
+
+use crate::calls::Call;
+use crate::pretty;
+use std::fmt::Write as _;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// Graphviz DOT.
+    Dot,
+    /// A Mermaid flowchart.
+    Mermaid,
+}
+
+/// Calls from one node to a contract under one function.
+struct Edge {
+    /// Index of the calling contract; `None` for the transaction.
+    from: Option<usize>,
+    to: usize,
+    function: String,
+    calls: usize,
+    cpu_instructions: Option<u64>,
+}
+
+impl Edge {
+    fn label(&self) -> String {
+        let mut label = self.function.clone();
+        if self.calls > 1 {
+            let _ = write!(label, " ×{}", self.calls);
+        }
+        if let Some(cpu) = self.cpu_instructions {
+            let _ = write!(label, " · {cpu} CPU");
+        }
+        label
+    }
+}
+
+/// Contracts in the order they were first called, and the edges between
+/// them in the order they were first taken.
+fn graph(tree: &[Call]) -> (Vec<&str>, Vec<Edge>) {
+    fn walk<'a>(
+        from: Option<usize>,
+        calls: &'a [Call],
+        contracts: &mut Vec<&'a str>,
+        edges: &mut Vec<Edge>,
+    ) {
+        for call in calls {
+            let to = match contracts.iter().position(|c| *c == call.contract) {
+                Some(to) => to,
+                None => {
+                    contracts.push(&call.contract);
+                    contracts.len() - 1
+                }
+            };
+            let cpu = call.cost.map(|cost| cost.inclusive.cpu_instructions);
+            match edges
+                .iter_mut()
+                .find(|e| e.from == from && e.to == to && e.function == call.function)
+            {
+                Some(edge) => {
+                    edge.calls += 1;
+                    edge.cpu_instructions = edge.cpu_instructions.zip(cpu).map(|(a, b)| a + b);
+                }
+                None => edges.push(Edge {
+                    from,
+                    to,
+                    function: call.function.clone(),
+                    calls: 1,
+                    cpu_instructions: cpu,
+                }),
+            }
+            walk(Some(to), &call.calls, contracts, edges);
+        }
+    }
+    let mut contracts = Vec::new();
+    let mut edges = Vec::new();
+    walk(None, tree, &mut contracts, &mut edges);
+    (contracts, edges)
+}
+
+/// The call graph of `tree` in `format`.
+pub fn render(format: Format, tree: &[Call]) -> String {
+    let (contracts, edges) = graph(tree);
+    let node = |index: Option<usize>| index.map_or_else(|| "tx".to_string(), |i| format!("c{i}"));
+    let mut out = String::new();
+    match format {
+        Format::Dot => {
+            out.push_str("digraph calls {\n  rankdir=LR;\n  node [shape=box];\n");
+            out.push_str("  tx [label=\"transaction\", shape=ellipse];\n");
+            for (i, contract) in contracts.iter().enumerate() {
+                let _ = writeln!(
+                    out,
+                    "  c{i} [label=\"{}\", tooltip=\"{contract}\"];",
+                    dot_escape(&pretty::short(contract))
+                );
+            }
+            for edge in &edges {
+                let _ = writeln!(
+                    out,
+                    "  {} -> {} [label=\"{}\"];",
+                    node(edge.from),
+                    node(Some(edge.to)),
+                    dot_escape(&edge.label())
+                );
+            }
+            out.push_str("}\n");
+        }
+        Format::Mermaid => {
+            out.push_str("flowchart LR\n  tx([transaction])\n");
+            for (i, contract) in contracts.iter().enumerate() {
+                let _ = writeln!(
+                    out,
+                    "  c{i}[\"{}\"]",
+                    mermaid_escape(&pretty::short(contract))
+                );
+            }
+            for edge in &edges {
+                let _ = writeln!(
+                    out,
+                    "  {} -->|\"{}\"| {}",
+                    node(edge.from),
+                    mermaid_escape(&edge.label()),
+                    node(Some(edge.to))
+                );
+            }
+        }
+    }
+    out
+}
+
+/// Renders the call graph of `tree` in `format` and writes it to `path`.
+pub fn write(format: Format, path: &Path, tree: &[Call]) -> Result<(), String> {
+    std::fs::write(path, render(format, tree))
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn mermaid_escape(s: &str) -> String {
+    s.replace('"', "#quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calls::{CallCost, Charged};
+
+    fn call(contract: char, function: &str, cpu: u64, calls: Vec<Call>) -> Call {
+        let charged = Charged {
+            cpu_instructions: cpu,
+            memory_bytes: 0,
+        };
+        Call {
+            contract: format!("C{}", contract.to_string().repeat(55)),
+            function: function.to_string(),
+            args: vec![],
+            result: Some("()".to_string()),
+            error: None,
+            error_case: None,
+            calls,
+            cost: Some(CallCost {
+                inclusive: charged,
+                own: charged,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_calls_to_the_same_function_share_an_edge() {
+        let tree = [call(
+            'A',
+            "route",
+            100,
+            vec![
+                call('B', "swap", 30, vec![]),
+                call('C', "swap", 20, vec![]),
+                call('B', "swap", 10, vec![]),
+            ],
+        )];
+
+        let dot = render(Format::Dot, &tree);
+        assert!(dot.starts_with("digraph calls {\n"));
+        assert!(dot.contains("  c1 [label=\"CBBB…BBBB\", tooltip=\"CBBB"));
+        assert!(dot.contains("  tx -> c0 [label=\"route · 100 CPU\"];\n"));
+        assert!(dot.contains("  c0 -> c1 [label=\"swap ×2 · 40 CPU\"];\n"));
+        assert!(dot.contains("  c0 -> c2 [label=\"swap · 20 CPU\"];\n"));
+        assert!(dot.ends_with("}\n"));
+
+        let mermaid = render(Format::Mermaid, &tree);
+        assert!(mermaid.starts_with("flowchart LR\n  tx([transaction])\n"));
+        assert!(mermaid.contains("  c2[\"CCCC…CCCC\"]\n"));
+        assert!(mermaid.contains("  c0 -->|\"swap ×2 · 40 CPU\"| c1\n"));
+    }
+}
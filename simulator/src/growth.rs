@@ -0,0 +1,219 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Storage growth across the steps of a scenario.
+//!
+//! A contract whose storage grows with every call, one new key per deposit
+//! or a map entry that gains an element per call, ends up paying rent and
+//! write fees that grow without bound: a rent bomb. The [`Tracker`] follows
+//! each contract's data writes from step to step and [`Tracker::warnings`]
+//! reports two patterns:
+//!
+//! - `new_keys`: every call that wrote to the contract created entries, and
+//! - `growing_entry`: an entry got larger on every write.
+//!
+//! Both need [`MIN_WRITES`] writes to be reported, so setup steps do not.
+
+use crate::pretty;
+use crate::snapshot;
+use crate::storage_log::StorageAccess;
+use schemars::JsonSchema;
+use serde::Serialize;
+use soroban_env_host::xdr::LedgerKey;
+
+/// Writes a pattern must hold for before it is reported.
+pub const MIN_WRITES: usize = 3;
+
+/// A contract whose storage grows with calls.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct GrowthWarning {
+    /// Contract (`C...`).
+    pub contract: String,
+    /// `new_keys` or `growing_entry`.
+    pub pattern: &'static str,
+    /// Growing entry's key, for `growing_entry`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// Steps that wrote, in order.
+    pub steps: Vec<String>,
+    /// After each of `steps`: the entries the contract created so far for
+    /// `new_keys`, or the entry's size in bytes for `growing_entry`.
+    pub sizes: Vec<u32>,
+}
+
+impl GrowthWarning {
+    pub fn message(&self) -> String {
+        let sizes = self
+            .sizes
+            .iter()
+            .map(u32::to_string)
+            .collect::<Vec<_>>()
+            .join(" → ");
+        match &self.key {
+            Some(key) => format!(
+                "Entry {key} of {} grew on each of {} writes: {sizes} bytes",
+                self.contract,
+                self.steps.len()
+            ),
+            None => format!(
+                "{} created new entries on each of {} calls: {sizes} entries",
+                self.contract,
+                self.steps.len()
+            ),
+        }
+    }
+}
+
+/// Data writes of each contract, step by step.
+#[derive(Debug, Default)]
+pub struct Tracker {
+    contracts: Vec<History>,
+}
+
+#[derive(Debug)]
+struct History {
+    contract: String,
+    /// Step name and entries it created, for each step that wrote.
+    writes: Vec<(String, u32)>,
+    /// Key and the step name and size of each write to it.
+    entries: Vec<(String, Vec<(String, u32)>)>,
+}
+
+impl Tracker {
+    /// Records the storage accesses of a successful step.
+    pub fn record(&mut self, step: &str, accesses: &[StorageAccess]) {
+        let mut written = Vec::new();
+        for access in accesses.iter().filter(|access| access.operation != "read") {
+            let Ok(LedgerKey::ContractData(data)) = snapshot::decode_ledger_key(&access.key) else {
+                continue;
+            };
+            let contract = data.contract.to_string();
+            let index = match self.contracts.iter().position(|h| h.contract == contract) {
+                Some(index) => index,
+                None => {
+                    self.contracts.push(History {
+                        contract,
+                        writes: Vec::new(),
+                        entries: Vec::new(),
+                    });
+                    self.contracts.len() - 1
+                }
+            };
+            let history = &mut self.contracts[index];
+            if !written.contains(&index) {
+                written.push(index);
+                history.writes.push((step.to_string(), 0));
+            }
+            if access.operation == "create" {
+                if let Some((_, created)) = history.writes.last_mut() {
+                    *created += 1;
+                }
+            }
+            if access.operation == "delete" {
+                continue;
+            }
+            let key = pretty::value(&data.key);
+            let sample = (step.to_string(), access.entry_bytes);
+            match history.entries.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, samples)) => samples.push(sample),
+                None => history.entries.push((key, vec![sample])),
+            }
+        }
+    }
+
+    /// Contracts whose storage grew with every write, once there were
+    /// enough writes to tell.
+    pub fn warnings(&self) -> Vec<GrowthWarning> {
+        let mut warnings = Vec::new();
+        for history in &self.contracts {
+            if history.writes.len() >= MIN_WRITES
+                && history.writes.iter().all(|(_, created)| *created > 0)
+            {
+                let mut total = 0;
+                warnings.push(GrowthWarning {
+                    contract: history.contract.clone(),
+                    pattern: "new_keys",
+                    key: None,
+                    steps: history.writes.iter().map(|(s, _)| s.clone()).collect(),
+                    sizes: history
+                        .writes
+                        .iter()
+                        .map(|(_, created)| {
+                            total += created;
+                            total
+                        })
+                        .collect(),
+                });
+            }
+            for (key, samples) in &history.entries {
+                if samples.len() >= MIN_WRITES && samples.windows(2).all(|w| w[1].1 > w[0].1) {
+                    warnings.push(GrowthWarning {
+                        contract: history.contract.clone(),
+                        pattern: "growing_entry",
+                        key: Some(key.clone()),
+                        steps: samples.iter().map(|(s, _)| s.clone()).collect(),
+                        sizes: samples.iter().map(|(_, bytes)| *bytes).collect(),
+                    });
+                }
+            }
+        }
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::xdr::{
+        ContractDataDurability, ContractId, Hash, LedgerKeyContractData, Limits, ScAddress, ScVal,
+        WriteXdr,
+    };
+
+    fn access(contract: u8, key: u32, operation: &'static str, bytes: u32) -> StorageAccess {
+        let key = LedgerKey::ContractData(LedgerKeyContractData {
+            contract: ScAddress::Contract(ContractId(Hash([contract; 32]))),
+            key: ScVal::U32(key),
+            durability: ContractDataDurability::Persistent,
+        });
+        StorageAccess {
+            key: key.to_xdr_base64(Limits::none()).unwrap(),
+            entry_type: "ContractData".to_string(),
+            access: "read_write",
+            operation,
+            origin: "snapshot".to_string(),
+            entry_bytes: bytes,
+            live_until_before: None,
+            live_until_after: None,
+        }
+    }
+
+    #[test]
+    fn test_tracker_flags_new_keys_and_growing_entries() {
+        let mut tracker = Tracker::default();
+        for step in 0..3u32 {
+            tracker.record(
+                &format!("deposit {step}"),
+                &[
+                    // Contract 1 adds a key per call and grows entry 0.
+                    access(1, 0, "update", 100 + step * 10),
+                    access(1, step + 1, "create", 50),
+                    // Contract 2 rewrites the same entry at the same size.
+                    access(2, 0, "update", 80),
+                ],
+            );
+        }
+        tracker.record("read", &[access(1, 0, "read", 130)]);
+
+        let warnings = tracker.warnings();
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].pattern, "new_keys");
+        assert_eq!(warnings[0].sizes, vec![1, 2, 3]);
+        assert_eq!(warnings[1].pattern, "growing_entry");
+        assert_eq!(warnings[1].key.as_deref(), Some("0"));
+        assert_eq!(warnings[1].sizes, vec![100, 110, 120]);
+        assert_eq!(
+            warnings[1].steps,
+            vec!["deposit 0", "deposit 1", "deposit 2"]
+        );
+    }
+}
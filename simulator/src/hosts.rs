@@ -0,0 +1,37 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Versions of the simulator and of the Soroban host linked into it.
+//!
+//! The simulator links a single host, the `soroban-env-host` dependency,
+//! which only runs the ledger protocol it was built for.
+
+use crate::types::ResponseMeta;
+use soroban_env_host::meta;
+
+/// Versions of this build, reported in every response. The git hash is
+/// taken from `ERST_GIT_HASH` or the checkout at build time.
+pub fn response_meta() -> ResponseMeta {
+    let protocol = meta::INTERFACE_VERSION.protocol;
+    ResponseMeta {
+        simulator_version: env!("CARGO_PKG_VERSION"),
+        host_version: soroban_env_host::VERSION.pkg,
+        min_protocol: protocol,
+        max_protocol: protocol,
+        git_hash: option_env!("ERST_GIT_HASH").filter(|hash| !hash.is_empty()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_meta_names_the_linked_host() {
+        let meta = response_meta();
+        assert_eq!(meta.host_version, soroban_env_host::VERSION.pkg);
+        assert_eq!(meta.max_protocol, meta::INTERFACE_VERSION.protocol);
+        assert!(meta.min_protocol <= meta.max_protocol);
+        assert!(!meta.simulator_version.is_empty());
+    }
+}
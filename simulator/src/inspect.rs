@@ -0,0 +1,198 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Static summary of a contract module.
+//!
+//! `erst-sim inspect-wasm contract.wasm` lists what a contract needs from the
+//! host and what it offers, without running it: the host functions it
+//! imports with the protocol each first appeared in, its exported functions,
+//! its custom sections and its size. The highest of the import protocols and
+//! the `contractenvmetav0` interface version is the oldest protocol the
+//! contract can be deployed on. Use [`vm::validate`] for a full check.
+
+use crate::vm;
+use serde::Serialize;
+use std::path::Path;
+use wasmparser::{ExternalKind, FuncType, Parser, Payload, TypeRef};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WasmInspection {
+    pub code_size: usize,
+    /// From `contractenvmetav0`; absent if the section is missing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interface_version: Option<InterfaceVersion>,
+    /// Oldest protocol providing every imported host function and the
+    /// interface version.
+    pub min_protocol: u32,
+    pub imports: Vec<Import>,
+    pub exports: Vec<Export>,
+    pub custom_sections: Vec<CustomSection>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InterfaceVersion {
+    pub protocol: u32,
+    pub pre_release: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Import {
+    pub module: String,
+    pub name: String,
+    /// The host function's name; absent if the import is not one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host_function: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_protocol: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_protocol: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Export {
+    pub name: String,
+    pub params: usize,
+    pub results: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CustomSection {
+    pub name: String,
+    pub size: usize,
+}
+
+/// Reads and inspects the module at `path`.
+pub fn inspect_file(path: &Path) -> Result<WasmInspection, String> {
+    let wasm =
+        std::fs::read(path).map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    inspect(&wasm).map_err(|e| format!("Failed to parse {}: {e}", path.display()))
+}
+
+pub fn inspect(wasm: &[u8]) -> Result<WasmInspection, String> {
+    let mut types: Vec<FuncType> = Vec::new();
+    let mut functions: Vec<u32> = Vec::new();
+    let mut exports = Vec::new();
+    let mut inspection = WasmInspection {
+        code_size: wasm.len(),
+        interface_version: None,
+        min_protocol: 0,
+        imports: Vec::new(),
+        exports: Vec::new(),
+        custom_sections: Vec::new(),
+    };
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload.map_err(|e| e.to_string())? {
+            Payload::TypeSection(section) => {
+                for ty in section.into_iter_err_on_gc_types() {
+                    types.push(ty.map_err(|e| e.to_string())?);
+                }
+            }
+            Payload::ImportSection(section) => {
+                for import in section {
+                    let import = import.map_err(|e| e.to_string())?;
+                    if let TypeRef::Func(ty) = import.ty {
+                        functions.push(ty);
+                    }
+                    let host = vm::host_function(import.module, import.name);
+                    inspection.imports.push(Import {
+                        module: import.module.to_string(),
+                        name: import.name.to_string(),
+                        host_function: host.map(|f| f.function),
+                        min_protocol: host.and_then(|f| f.min_protocol),
+                        max_protocol: host.and_then(|f| f.max_protocol),
+                    });
+                }
+            }
+            Payload::FunctionSection(section) => {
+                for ty in section {
+                    functions.push(ty.map_err(|e| e.to_string())?);
+                }
+            }
+            Payload::ExportSection(section) => {
+                for export in section {
+                    let export = export.map_err(|e| e.to_string())?;
+                    if export.kind == ExternalKind::Func {
+                        exports.push((export.name.to_string(), export.index));
+                    }
+                }
+            }
+            Payload::CustomSection(section) => {
+                if section.name() == soroban_env_host::meta::ENV_META_V0_SECTION_NAME {
+                    inspection.interface_version =
+                        vm::interface_version(section.data()).map(|(protocol, pre_release)| {
+                            InterfaceVersion {
+                                protocol,
+                                pre_release,
+                            }
+                        });
+                }
+                inspection.custom_sections.push(CustomSection {
+                    name: section.name().to_string(),
+                    size: section.data().len(),
+                });
+            }
+            _ => {}
+        }
+    }
+    for (name, index) in exports {
+        let ty = functions
+            .get(index as usize)
+            .and_then(|&ty| types.get(ty as usize));
+        inspection.exports.push(Export {
+            name,
+            params: ty.map_or(0, |ty| ty.params().len()),
+            results: ty.map_or(0, |ty| ty.results().len()),
+        });
+    }
+    inspection.min_protocol = inspection
+        .imports
+        .iter()
+        .filter_map(|import| import.min_protocol)
+        .chain(inspection.interface_version.as_ref().map(|v| v.protocol))
+        .max()
+        .unwrap_or(0);
+    Ok(inspection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inspect_lists_imports_exports_and_sections() {
+        let mut wasm = wat::parse_str(
+            r#"(module
+                (import "x" "3" (func (result i64)))
+                (import "env" "abort" (func))
+                (func (export "hello") (param i64 i64) (result i64) i64.const 0)
+                (memory (export "memory") 1))"#,
+        )
+        .unwrap();
+        // Custom section "notes" holding "hi".
+        wasm.extend_from_slice(&[0, 8, 5, b'n', b'o', b't', b'e', b's', b'h', b'i']);
+
+        let inspection = inspect(&wasm).unwrap();
+        assert_eq!(inspection.code_size, wasm.len());
+        assert_eq!(
+            inspection.imports[0].host_function,
+            Some("get_ledger_sequence")
+        );
+        assert_eq!(inspection.imports[1].host_function, None);
+        assert_eq!(
+            inspection.exports,
+            vec![Export {
+                name: "hello".into(),
+                params: 2,
+                results: 1,
+            }]
+        );
+        assert_eq!(
+            inspection.custom_sections,
+            vec![CustomSection {
+                name: "notes".into(),
+                size: 2,
+            }]
+        );
+        assert_eq!(inspection.interface_version, None);
+    }
+}
@@ -0,0 +1,340 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Intent mode: building the transaction from what the caller wants done.
+//!
+//! A request with `intent` says what should happen, a token transfer or a
+//! swap, in addresses, asset names and amounts instead of XDR:
+//!
+//! ```json
+//! { "intent": { "type": "token_transfer", "from": "G...", "to": "G...",
+//!               "asset": "native", "amount": "12.5" } }
+//! ```
+//!
+//! [`expand`] turns it into the builder-mode call that does it, with
+//! `assemble` set, so the response carries the transaction ready to sign.
+//! Assets are `native`, `CODE:ISSUER` or a token contract (`C...`); the
+//! first two are called through their Stellar Asset Contract, whose address
+//! depends on the network. Amounts of Stellar assets may have up to seven
+//! decimals, and amounts of other tokens are in their smallest unit. The
+//! account moving the funds, when it is a `G...` account, is the
+//! transaction's source, so its authorization is the transaction signature.
+
+use crate::network::Network;
+use crate::simulation::{self, SimulationError};
+use crate::types::{InvokeSpec, SimulationRequest};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use soroban_env_host::xdr::{
+    AlphaNum12, AlphaNum4, Asset, AssetCode12, AssetCode4, ContractId, ContractIdPreimage, Hash,
+    HashIdPreimage, HashIdPreimageContractId, Int128Parts, Limits, ScAddress, ScVal, ScVec,
+    WriteXdr,
+};
+
+/// Decimals of every Stellar Asset Contract.
+const ASSET_DECIMALS: u32 = 7;
+
+/// Seconds a swap stays valid when the intent sets no deadline.
+const SWAP_VALIDITY: u64 = 300;
+
+/// Something to do, for which the simulator builds the transaction.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Intent {
+    /// Move `amount` of `asset` from `from` to `to` with the token's
+    /// `transfer`.
+    TokenTransfer {
+        /// Holder paying (`G...` or `C...`).
+        from: String,
+        /// Holder paid (`G...` or `C...`).
+        to: String,
+        /// `native`, `CODE:ISSUER` or a token contract (`C...`).
+        asset: String,
+        /// Decimal amount.
+        amount: String,
+    },
+    /// Sell exactly `amount_in` of `sell` for at least `min_amount_out` of
+    /// `buy` through a router with Soroswap's
+    /// `swap_exact_tokens_for_tokens`.
+    Swap {
+        /// Router contract (`C...`).
+        router: String,
+        /// Holder selling (`G...` or `C...`).
+        from: String,
+        /// Holder receiving what is bought. Defaults to `from`.
+        #[serde(default)]
+        to: Option<String>,
+        /// Asset sold, named as in `token_transfer`.
+        sell: String,
+        /// Asset bought.
+        buy: String,
+        /// Assets to route through between `sell` and `buy`.
+        #[serde(default)]
+        via: Vec<String>,
+        /// Decimal amount sold.
+        amount_in: String,
+        /// Least decimal amount of `buy` to accept.
+        min_amount_out: String,
+        /// Unix time after which the router refuses the swap. Defaults to
+        /// five minutes after the simulated ledger's close time.
+        #[serde(default)]
+        deadline: Option<u64>,
+    },
+}
+
+/// `request` with its intent replaced by the call that carries it out,
+/// assembled.
+pub fn expand(request: &SimulationRequest) -> Result<SimulationRequest, SimulationError> {
+    let Some(intent) = &request.intent else {
+        return Ok(request.clone());
+    };
+    if !request.envelope_xdr.is_empty() || request.invoke.is_some() {
+        return Err(SimulationError::InvalidRequest(
+            "intent cannot be given with envelope_xdr or invoke".to_string(),
+        ));
+    }
+    let network = Network::resolve(
+        request.network.as_deref(),
+        request.network_passphrase.as_deref(),
+    )
+    .map_err(SimulationError::InvalidRequest)?;
+    let close_time = request
+        .ledger_close_time
+        .unwrap_or_else(simulation::ledger_timestamp);
+    let invoke = call(intent, &network, close_time)
+        .map_err(|e| SimulationError::InvalidRequest(format!("Invalid intent: {e}")))?;
+    let mut expanded = request.clone();
+    expanded.intent = None;
+    expanded.invoke = Some(invoke);
+    expanded.assemble = true;
+    Ok(expanded)
+}
+
+/// The contract call that carries out `intent` on `network`.
+pub fn call(intent: &Intent, network: &Network, close_time: u64) -> Result<InvokeSpec, String> {
+    let (contract, function, args, payer) = match intent {
+        Intent::TokenTransfer {
+            from,
+            to,
+            asset,
+            amount,
+        } => {
+            let token = token(asset, network)?;
+            let amount = parse_amount(amount, asset)?;
+            let args = vec![address(from)?, address(to)?, i128_val(amount)];
+            (token, "transfer", args, from)
+        }
+        Intent::Swap {
+            router,
+            from,
+            to,
+            sell,
+            buy,
+            via,
+            amount_in,
+            min_amount_out,
+            deadline,
+        } => {
+            let router = router
+                .parse::<ScAddress>()
+                .ok()
+                .filter(|router| matches!(router, ScAddress::Contract(_)))
+                .ok_or_else(|| format!("router {router:?} is not a contract address"))?;
+            let path = std::iter::once(sell)
+                .chain(via)
+                .chain(std::iter::once(buy))
+                .map(|asset| token(asset, network).map(ScVal::Address))
+                .collect::<Result<Vec<_>, _>>()?;
+            let args = vec![
+                i128_val(parse_amount(amount_in, sell)?),
+                i128_val(parse_amount(min_amount_out, buy)?),
+                ScVal::Vec(Some(ScVec(path.try_into().map_err(|_| "path too long")?))),
+                address(to.as_ref().unwrap_or(from))?,
+                ScVal::U64(deadline.unwrap_or(close_time + SWAP_VALIDITY)),
+            ];
+            (router, "swap_exact_tokens_for_tokens", args, from)
+        }
+    };
+    let args = args
+        .iter()
+        .map(|arg| arg.to_xdr_base64(Limits::none()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to encode the arguments: {e}"))?;
+    Ok(InvokeSpec {
+        contract_id: contract.to_string(),
+        function: function.to_string(),
+        args,
+        source_account: payer.starts_with('G').then(|| payer.clone()),
+    })
+}
+
+/// The token contract of `asset`: the contract itself, or the Stellar Asset
+/// Contract of `native` or `CODE:ISSUER` on `network`.
+pub fn token(asset: &str, network: &Network) -> Result<ScAddress, String> {
+    if let Ok(contract @ ScAddress::Contract(_)) = asset.parse::<ScAddress>() {
+        return Ok(contract);
+    }
+    let preimage = HashIdPreimage::ContractId(HashIdPreimageContractId {
+        network_id: Hash(network.id()),
+        contract_id_preimage: ContractIdPreimage::Asset(stellar_asset(asset)?),
+    });
+    let xdr = preimage
+        .to_xdr(Limits::none())
+        .map_err(|e| format!("Failed to hash asset {asset:?}: {e}"))?;
+    Ok(ScAddress::Contract(ContractId(Hash(
+        Sha256::digest(xdr).into(),
+    ))))
+}
+
+/// The Stellar asset named `native` or `CODE:ISSUER`.
+fn stellar_asset(asset: &str) -> Result<Asset, String> {
+    if asset == "native" {
+        return Ok(Asset::Native);
+    }
+    let invalid = || format!("asset {asset:?} is not native, CODE:ISSUER or a contract address");
+    let (code, issuer) = asset.split_once(':').ok_or_else(invalid)?;
+    let issuer = issuer.parse().map_err(|_| invalid())?;
+    Ok(match code.len() {
+        1..=4 => Asset::CreditAlphanum4(AlphaNum4 {
+            asset_code: code.parse::<AssetCode4>().map_err(|_| invalid())?,
+            issuer,
+        }),
+        5..=12 => Asset::CreditAlphanum12(AlphaNum12 {
+            asset_code: code.parse::<AssetCode12>().map_err(|_| invalid())?,
+            issuer,
+        }),
+        _ => return Err(invalid()),
+    })
+}
+
+/// `amount` of `asset` in its smallest unit. Only Stellar assets, whose
+/// decimals are known, take a decimal amount.
+fn parse_amount(amount: &str, asset: &str) -> Result<i128, String> {
+    let invalid = || format!("amount {amount:?} is not a positive decimal number");
+    let decimals = if stellar_asset(asset).is_ok() {
+        ASSET_DECIMALS
+    } else {
+        0
+    };
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+    if whole.is_empty()
+        || !whole
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+    if fraction.len() > decimals as usize {
+        return Err(if decimals == 0 {
+            format!("amount {amount:?} of {asset} must be in the token's smallest unit")
+        } else {
+            format!("amount {amount:?} has more than {decimals} decimals")
+        });
+    }
+    let digits = format!("{whole}{fraction:0<width$}", width = decimals as usize);
+    digits.parse::<i128>().map_err(|_| invalid())
+}
+
+fn address(address: &str) -> Result<ScVal, String> {
+    address
+        .parse::<ScAddress>()
+        .map(ScVal::Address)
+        .map_err(|_| format!("{address:?} is not an account or contract address"))
+}
+
+fn i128_val(value: i128) -> ScVal {
+    ScVal::I128(Int128Parts {
+        hi: (value >> 64) as i64,
+        lo: value as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::xdr::{AccountId, PublicKey, ReadXdr, Uint256};
+
+    fn account(seed: u8) -> String {
+        let key = PublicKey::PublicKeyTypeEd25519(Uint256([seed; 32]));
+        ScAddress::Account(AccountId(key)).to_string()
+    }
+
+    fn decode(spec: &InvokeSpec) -> Vec<ScVal> {
+        spec.args
+            .iter()
+            .map(|arg| ScVal::from_xdr_base64(arg, Limits::none()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_transfer_calls_the_asset_contract() {
+        let network = Network::resolve(Some("testnet"), None).unwrap();
+        let intent: Intent = serde_json::from_value(serde_json::json!({
+            "type": "token_transfer",
+            "from": account(1),
+            "to": account(2),
+            "asset": "native",
+            "amount": "12.5",
+        }))
+        .unwrap();
+        let spec = call(&intent, &network, 0).unwrap();
+        // The testnet native asset contract.
+        assert_eq!(
+            spec.contract_id,
+            "CDLZFC3SYJYDZT7K67VZ75HPJVIEUVNIXF47ZG2FB2RMQQVU2HHGCYSC"
+        );
+        assert_eq!(spec.function, "transfer");
+        assert_eq!(spec.source_account.as_deref(), Some(account(1).as_str()));
+        assert_eq!(decode(&spec)[2], i128_val(125_000_000));
+
+        let contract_token = ScAddress::Contract(ContractId(Hash([5; 32]))).to_string();
+        assert_eq!(
+            token(&contract_token, &network).unwrap().to_string(),
+            contract_token
+        );
+        assert!(parse_amount("1.5", &contract_token)
+            .unwrap_err()
+            .contains("smallest unit"));
+        assert_eq!(parse_amount("15", &contract_token), Ok(15));
+        assert!(parse_amount("0.00000001", "native").is_err());
+        assert!(token("USDC", &network).is_err());
+    }
+
+    #[test]
+    fn test_swap_routes_through_the_router() {
+        let network = Network::resolve(Some("testnet"), None).unwrap();
+        let router = ScAddress::Contract(ContractId(Hash([6; 32]))).to_string();
+        let usdc = format!("USDC:{}", account(2));
+        let intent = Intent::Swap {
+            router: router.clone(),
+            from: account(1),
+            to: None,
+            sell: "native".to_string(),
+            buy: usdc.clone(),
+            via: Vec::new(),
+            amount_in: "100".to_string(),
+            min_amount_out: "9.5".to_string(),
+            deadline: None,
+        };
+        let spec = call(&intent, &network, 1_700_000_000).unwrap();
+        assert_eq!(spec.contract_id, router);
+        assert_eq!(spec.function, "swap_exact_tokens_for_tokens");
+        let args = decode(&spec);
+        assert_eq!(args[0], i128_val(1_000_000_000));
+        assert_eq!(args[1], i128_val(95_000_000));
+        let ScVal::Vec(Some(path)) = &args[2] else {
+            panic!("path is not a vector");
+        };
+        assert_eq!(
+            path.to_vec(),
+            [
+                ScVal::Address(token("native", &network).unwrap()),
+                ScVal::Address(token(&usdc, &network).unwrap()),
+            ]
+        );
+        assert_eq!(args[3], address(&account(1)).unwrap());
+        assert_eq!(args[4], ScVal::U64(1_700_000_300));
+    }
+}
@@ -0,0 +1,279 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Builder mode: simulating a contract call from a contract ID.
+//!
+//! A request with `invoke` names a contract, a function and its arguments
+//! instead of carrying a prepared envelope. [`envelope`] builds the
+//! `InvokeHostFunction` transaction, and [`resolve`] reads the contract's
+//! instance and the `ContractCode` entry it runs from the configured state,
+//! so callers need not know how either key is derived.
+
+use crate::network;
+use crate::simulation::SimulationError;
+use crate::source::LayeredSource;
+use crate::types::InvokeSpec;
+use crate::xdr_limits;
+use base64::Engine as _;
+use soroban_env_host::xdr::{
+    ContractDataDurability, ContractExecutable, ContractId, Hash, HostFunction, InvokeContractArgs,
+    InvokeHostFunctionOp, LedgerEntryData, LedgerKey, LedgerKeyContractCode, LedgerKeyContractData,
+    Memo, MuxedAccount, Operation, OperationBody, Preconditions, ScAddress, ScVal, SequenceNumber,
+    Transaction, TransactionEnvelope, TransactionExt, TransactionV1Envelope, Uint256,
+};
+use std::fmt;
+
+/// Fee of the built transaction; builder mode never submits it.
+const BUILDER_FEE: u32 = 100;
+
+/// Builds the envelope for `spec`: one `InvokeHostFunction` operation with
+/// no authorization entries, so the host records the ones the call needs.
+pub fn envelope(spec: &InvokeSpec) -> Result<TransactionEnvelope, SimulationError> {
+    let invalid = |what: &str, e: &dyn fmt::Display| {
+        SimulationError::InvalidRequest(format!("Invalid invoke.{what}: {e}"))
+    };
+    let contract: ContractId = spec
+        .contract_id
+        .parse()
+        .map_err(|e| invalid("contract_id", &e))?;
+    let source_account = match &spec.source_account {
+        Some(account) => account
+            .parse::<MuxedAccount>()
+            .map_err(|e| invalid("source_account", &e))?,
+        None => MuxedAccount::Ed25519(Uint256([0; 32])),
+    };
+    let args = spec
+        .args
+        .iter()
+        .enumerate()
+        .map(|(i, arg)| decode_arg(i, arg))
+        .collect::<Result<Vec<_>, _>>()?;
+    let invocation = InvokeContractArgs {
+        contract_address: ScAddress::Contract(contract),
+        function_name: spec
+            .function
+            .as_str()
+            .try_into()
+            .map_err(|()| invalid("function", &"not a symbol of at most 32 characters"))?,
+        args: args.try_into().map_err(|e| invalid("args", &e))?,
+    };
+    let operation = Operation {
+        source_account: None,
+        body: OperationBody::InvokeHostFunction(InvokeHostFunctionOp {
+            host_function: HostFunction::InvokeContract(invocation),
+            auth: Default::default(),
+        }),
+    };
+    Ok(TransactionEnvelope::Tx(TransactionV1Envelope {
+        tx: Transaction {
+            source_account,
+            fee: BUILDER_FEE,
+            seq_num: SequenceNumber(0),
+            cond: Preconditions::None,
+            memo: Memo::None,
+            operations: vec![operation].try_into().map_err(|e| {
+                SimulationError::InvalidRequest(format!("Failed to build envelope: {e}"))
+            })?,
+            ext: TransactionExt::V0,
+        },
+        signatures: Default::default(),
+    }))
+}
+
+fn decode_arg(index: usize, arg: &str) -> Result<ScVal, SimulationError> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(arg)
+        .map_err(|e| {
+            SimulationError::InvalidRequest(format!("Failed to decode invoke.args[{index}]: {e}"))
+        })?;
+    xdr_limits::read::<ScVal>(&bytes).map_err(|e| {
+        if xdr_limits::is_exceeded(&e) {
+            SimulationError::XdrLimitsExceeded(format!("invoke.args[{index}]: {e}"))
+        } else {
+            SimulationError::InvalidRequest(format!("Failed to parse invoke.args[{index}]: {e}"))
+        }
+    })
+}
+
+/// What a resolved contract runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedContract {
+    pub contract: ScAddress,
+    /// Hash of the contract's WASM; `None` for a Stellar Asset Contract.
+    pub wasm_hash: Option<Hash>,
+}
+
+impl fmt::Display for ResolvedContract {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.wasm_hash {
+            Some(hash) => write!(f, "{} runs WASM {}", self.contract, network::hex(&hash.0)),
+            None => write!(f, "{} is a Stellar Asset Contract", self.contract),
+        }
+    }
+}
+
+/// Reads the instance of `contract` and, for a WASM contract, its
+/// `ContractCode` entry through `state`, leaving both cached for the host.
+///
+/// A missing code entry is only an error without `code_supplied`, i.e.
+/// when the request does not upload the WASM itself.
+pub fn resolve(
+    contract: &ScAddress,
+    state: &LayeredSource,
+    code_supplied: bool,
+) -> Result<ResolvedContract, SimulationError> {
+    let instance_key = LedgerKey::ContractData(LedgerKeyContractData {
+        contract: contract.clone(),
+        key: ScVal::LedgerKeyContractInstance,
+        durability: ContractDataDurability::Persistent,
+    });
+    let instance = state
+        .read(&instance_key)
+        .map_err(SimulationError::Source)?
+        .ok_or_else(|| {
+            SimulationError::InvalidRequest(format!(
+                "Contract {contract} has no instance in the configured ledger state"
+            ))
+        })?;
+    let executable = match &instance.entry.data {
+        LedgerEntryData::ContractData(data) => match &data.val {
+            ScVal::ContractInstance(instance) => Some(instance.executable.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+    .ok_or_else(|| {
+        SimulationError::InvalidRequest(format!(
+            "Contract {contract} instance entry is not a contract instance"
+        ))
+    })?;
+    let wasm_hash = match executable {
+        ContractExecutable::Wasm(hash) => hash,
+        ContractExecutable::StellarAsset => {
+            return Ok(ResolvedContract {
+                contract: contract.clone(),
+                wasm_hash: None,
+            })
+        }
+    };
+    let code_key = LedgerKey::ContractCode(LedgerKeyContractCode {
+        hash: wasm_hash.clone(),
+    });
+    let code = state.read(&code_key).map_err(SimulationError::Source)?;
+    if code.is_none() && !code_supplied {
+        return Err(SimulationError::InvalidRequest(format!(
+            "Contract {contract} runs WASM {} which is not in the configured ledger state",
+            network::hex(&wasm_hash.0)
+        )));
+    }
+    Ok(ResolvedContract {
+        contract: contract.clone(),
+        wasm_hash: Some(wasm_hash),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::LedgerSnapshot;
+    use crate::source::SnapshotLedgerSource;
+    use soroban_env_host::xdr::{
+        ContractCodeEntry, ContractCodeEntryExt, ContractDataEntry, ExtensionPoint, LedgerEntry,
+        LedgerEntryExt, Limits, ScContractInstance, WriteXdr,
+    };
+
+    const CONTRACT: [u8; 32] = [7; 32];
+    const WASM_HASH: [u8; 32] = [9; 32];
+
+    fn contract() -> ScAddress {
+        ScAddress::Contract(ContractId(Hash(CONTRACT)))
+    }
+
+    fn entry(data: LedgerEntryData) -> LedgerEntry {
+        LedgerEntry {
+            last_modified_ledger_seq: 5,
+            data,
+            ext: LedgerEntryExt::V0,
+        }
+    }
+
+    fn instance_entry(executable: ContractExecutable) -> (LedgerKey, LedgerEntry) {
+        let key = LedgerKey::ContractData(LedgerKeyContractData {
+            contract: contract(),
+            key: ScVal::LedgerKeyContractInstance,
+            durability: ContractDataDurability::Persistent,
+        });
+        let data = LedgerEntryData::ContractData(ContractDataEntry {
+            ext: ExtensionPoint::V0,
+            contract: contract(),
+            key: ScVal::LedgerKeyContractInstance,
+            durability: ContractDataDurability::Persistent,
+            val: ScVal::ContractInstance(ScContractInstance {
+                executable,
+                storage: None,
+            }),
+        });
+        (key, entry(data))
+    }
+
+    fn code_entry() -> (LedgerKey, LedgerEntry) {
+        let key = LedgerKey::ContractCode(LedgerKeyContractCode {
+            hash: Hash(WASM_HASH),
+        });
+        let data = LedgerEntryData::ContractCode(ContractCodeEntry {
+            ext: ContractCodeEntryExt::V0,
+            hash: Hash(WASM_HASH),
+            code: vec![0, 0x61, 0x73, 0x6d].try_into().unwrap(),
+        });
+        (key, entry(data))
+    }
+
+    fn state(entries: &[(LedgerKey, LedgerEntry)]) -> LayeredSource {
+        let mut snapshot = LedgerSnapshot::new();
+        for (key, entry) in entries {
+            snapshot.insert_entry(key, entry.clone()).unwrap();
+        }
+        LayeredSource::new(SnapshotLedgerSource::new(snapshot), None, 5)
+    }
+
+    #[test]
+    fn test_envelope_invokes_the_named_function() {
+        let spec = InvokeSpec {
+            contract_id: contract().to_string(),
+            function: "transfer".into(),
+            args: vec![ScVal::U32(3).to_xdr_base64(Limits::none()).unwrap()],
+            source_account: None,
+        };
+        let built = envelope(&spec).unwrap();
+        let args = crate::trustline::invoked_contract(&built).unwrap();
+        assert_eq!(args.contract_address, contract());
+        assert_eq!(args.function_name.to_utf8_string_lossy(), "transfer");
+        assert_eq!(args.args.to_vec(), vec![ScVal::U32(3)]);
+
+        let bad = InvokeSpec {
+            contract_id: "GABC".into(),
+            ..spec
+        };
+        assert!(matches!(
+            envelope(&bad),
+            Err(SimulationError::InvalidRequest(msg)) if msg.starts_with("Invalid invoke.contract_id")
+        ));
+    }
+
+    #[test]
+    fn test_resolve_finds_instance_and_code() {
+        let wasm = ContractExecutable::Wasm(Hash(WASM_HASH));
+        let full = state(&[instance_entry(wasm.clone()), code_entry()]);
+        let resolved = resolve(&contract(), &full, false).unwrap();
+        assert_eq!(resolved.wasm_hash, Some(Hash(WASM_HASH)));
+        assert_eq!(full.reads().len(), 2);
+
+        let sac = state(&[instance_entry(ContractExecutable::StellarAsset)]);
+        assert_eq!(resolve(&contract(), &sac, false).unwrap().wasm_hash, None);
+
+        let missing_code = state(&[instance_entry(wasm)]);
+        assert!(resolve(&contract(), &missing_code, false).is_err());
+        assert!(resolve(&contract(), &missing_code, true).is_ok());
+        assert!(resolve(&contract(), &state(&[]), false).is_err());
+    }
+}
@@ -0,0 +1,132 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! JUnit XML for scenario runs, so CI systems show simulator regressions in
+//! their own test views. Each assertion of each step is one test case, named
+//! `<step> › <assertion>` and classed by the scenario; a step's time is
+//! reported on its first assertion.
+
+use crate::scenario::ScenarioOutcome;
+use std::fmt::Write as _;
+
+/// The JUnit XML of `outcome`.
+pub fn junit(outcome: &ScenarioOutcome) -> String {
+    let tests = outcome.assertions().count();
+    let failures = outcome.assertions().filter(|(_, a)| !a.passed).count();
+    let time = outcome
+        .steps
+        .iter()
+        .map(|step| step.duration.as_secs_f64())
+        .sum::<f64>();
+    let name = escape(&outcome.name);
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        out,
+        "<testsuites name=\"{name}\" tests=\"{tests}\" failures=\"{failures}\" time=\"{time:.3}\">"
+    );
+    let _ = writeln!(
+        out,
+        "  <testsuite name=\"{name}\" tests=\"{tests}\" failures=\"{failures}\" errors=\"0\" \
+         time=\"{time:.3}\">"
+    );
+    for step in &outcome.steps {
+        for (i, assertion) in step.assertions.iter().enumerate() {
+            let time = if i == 0 {
+                step.duration.as_secs_f64()
+            } else {
+                0.0
+            };
+            let _ = write!(
+                out,
+                "    <testcase classname=\"{name}\" name=\"{}\" time=\"{time:.3}\"",
+                escape(&format!("{} › {}", step.name, assertion.name))
+            );
+            if assertion.passed {
+                out.push_str("/>\n");
+                continue;
+            }
+            let message = assertion.message.as_deref().unwrap_or("failed");
+            let _ = writeln!(
+                out,
+                ">\n      <failure message=\"{}\">{}</failure>\n    </testcase>",
+                escape(message),
+                escape(step.error.as_deref().unwrap_or(message))
+            );
+        }
+    }
+    out.push_str("  </testsuite>\n</testsuites>\n");
+    out
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Not allowed in XML 1.0.
+            c if c.is_control() && !matches!(c, '\n' | '\r' | '\t') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenario::{Assertion, StepOutcome};
+    use std::time::Duration;
+
+    #[test]
+    fn test_junit_has_a_case_per_assertion() {
+        let outcome = ScenarioOutcome {
+            name: "vault".to_string(),
+            passed: false,
+            steps: vec![
+                StepOutcome {
+                    name: "deposit".to_string(),
+                    status: "success".to_string(),
+                    error: None,
+                    assertions: vec![Assertion {
+                        name: "status is success".to_string(),
+                        passed: true,
+                        message: None,
+                    }],
+                    expired: vec![],
+                    duration: Duration::from_millis(1500),
+                    cost: None,
+                    findings: vec![],
+                },
+                StepOutcome {
+                    name: "withdraw".to_string(),
+                    status: "error".to_string(),
+                    error: Some("Error(Contract, #1) <oops>".to_string()),
+                    assertions: vec![Assertion {
+                        name: "status is success".to_string(),
+                        passed: false,
+                        message: Some("status is error".to_string()),
+                    }],
+                    expired: vec![],
+                    duration: Duration::from_millis(250),
+                    cost: None,
+                    findings: vec![],
+                },
+            ],
+            storage_growth: vec![],
+        };
+
+        let xml = junit(&outcome);
+        assert!(xml.contains("tests=\"2\" failures=\"1\""));
+        assert!(xml.contains(
+            "<testcase classname=\"vault\" name=\"deposit › status is success\" time=\"1.500\"/>"
+        ));
+        assert!(xml.contains(
+            "<failure message=\"status is error\">Error(Contract, #1) &lt;oops&gt;</failure>"
+        ));
+        assert!(xml.ends_with("</testsuites>\n"));
+    }
+}
@@ -0,0 +1,518 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Preflight checks against the network's per-transaction limits.
+//!
+//! stellar-core rejects a transaction that exceeds any network limit, either
+//! at submission (size, footprint) or when it is applied (instructions,
+//! memory, bytes, events). [`check`] compares what the simulation used with
+//! each limit and reports the ones a transaction exceeds, as errors, or uses
+//! a soft threshold of, as warnings. The threshold is a request's
+//! `soft_limit_percent`, [`WARN_PERCENT`] by default; costs vary between
+//! ledgers, so a transaction close to a limit in simulation may exceed it on
+//! mainnet. Instruction and memory checks name the host cost types that
+//! charged the most, to show where to start optimizing.
+//!
+//! Like the rent parameters, the limits are read from the `ConfigSetting`
+//! entries of the simulated state, falling back to [`NetworkLimits::default`],
+//! which approximates pubnet.
+
+use crate::assemble;
+use crate::attribution::{self, CostTypeCharge};
+use crate::resources::{xdr_len, Resources};
+use crate::source::{self, SourceEntry};
+use schemars::JsonSchema;
+use serde::Serialize;
+use soroban_env_host::budget::Budget;
+use soroban_env_host::events::HostEvent;
+use soroban_env_host::storage::{AccessType, Storage};
+use soroban_env_host::xdr::{
+    ConfigSettingEntry, ConfigSettingId, ContractEventType, FeeBumpTransactionInnerTx,
+    LedgerEntryData, LedgerFootprint, LedgerKey, Limits, ReadXdr, ScVal, SorobanResources,
+    SorobanResourcesExtV0, SorobanTransactionData, SorobanTransactionDataExt, TransactionEnvelope,
+    TransactionExt, WriteXdr,
+};
+
+/// Usage at or above this share of a limit is reported as a warning,
+/// unless the request sets `soft_limit_percent`.
+pub const WARN_PERCENT: u64 = 90;
+
+/// Cost types listed in a check's `top_contributors`.
+pub const TOP_CONTRIBUTORS: usize = 3;
+
+/// Per-transaction limits of the network.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct NetworkLimits {
+    pub tx_max_instructions: u64,
+    pub tx_memory_limit: u64,
+    pub tx_max_size_bytes: u64,
+    pub tx_max_footprint_entries: u64,
+    pub tx_max_disk_read_entries: u64,
+    pub tx_max_disk_read_bytes: u64,
+    pub tx_max_write_ledger_entries: u64,
+    pub tx_max_write_bytes: u64,
+    pub tx_max_contract_events_size_bytes: u64,
+    pub contract_data_key_size_bytes: u64,
+    pub contract_data_entry_size_bytes: u64,
+    pub contract_max_size_bytes: u64,
+}
+
+impl Default for NetworkLimits {
+    fn default() -> Self {
+        Self {
+            tx_max_instructions: 100_000_000,
+            tx_memory_limit: 41_943_040,
+            tx_max_size_bytes: 132_096,
+            tx_max_footprint_entries: 100,
+            tx_max_disk_read_entries: 100,
+            tx_max_disk_read_bytes: 200_000,
+            tx_max_write_ledger_entries: 50,
+            tx_max_write_bytes: 132_096,
+            tx_max_contract_events_size_bytes: 16_384,
+            contract_data_key_size_bytes: 250,
+            contract_data_entry_size_bytes: 65_536,
+            contract_max_size_bytes: 65_536,
+        }
+    }
+}
+
+impl NetworkLimits {
+    /// Reads the limits from the network's `ConfigSetting` entries. Returns
+    /// whether every setting was found.
+    pub fn from_state(lookup: impl Fn(&LedgerKey) -> Option<SourceEntry>) -> (Self, bool) {
+        let setting = |id| source::config_setting(id, &lookup);
+        let mut limits = Self::default();
+        let mut found = 0;
+        if let Some(ConfigSettingEntry::ContractComputeV0(compute)) =
+            setting(ConfigSettingId::ContractComputeV0)
+        {
+            limits.tx_max_instructions = u64::try_from(compute.tx_max_instructions).unwrap_or(0);
+            limits.tx_memory_limit = compute.tx_memory_limit.into();
+            found += 1;
+        }
+        if let Some(ConfigSettingEntry::ContractBandwidthV0(bandwidth)) =
+            setting(ConfigSettingId::ContractBandwidthV0)
+        {
+            limits.tx_max_size_bytes = bandwidth.tx_max_size_bytes.into();
+            found += 1;
+        }
+        if let Some(ConfigSettingEntry::ContractLedgerCostV0(cost)) =
+            setting(ConfigSettingId::ContractLedgerCostV0)
+        {
+            limits.tx_max_disk_read_entries = cost.tx_max_disk_read_entries.into();
+            limits.tx_max_disk_read_bytes = cost.tx_max_disk_read_bytes.into();
+            limits.tx_max_write_ledger_entries = cost.tx_max_write_ledger_entries.into();
+            limits.tx_max_write_bytes = cost.tx_max_write_bytes.into();
+            found += 1;
+        }
+        if let Some(ConfigSettingEntry::ContractLedgerCostExtV0(ext)) =
+            setting(ConfigSettingId::ContractLedgerCostExtV0)
+        {
+            limits.tx_max_footprint_entries = ext.tx_max_footprint_entries.into();
+            found += 1;
+        }
+        if let Some(ConfigSettingEntry::ContractEventsV0(events)) =
+            setting(ConfigSettingId::ContractEventsV0)
+        {
+            limits.tx_max_contract_events_size_bytes =
+                events.tx_max_contract_events_size_bytes.into();
+            found += 1;
+        }
+        if let Some(ConfigSettingEntry::ContractDataKeySizeBytes(size)) =
+            setting(ConfigSettingId::ContractDataKeySizeBytes)
+        {
+            limits.contract_data_key_size_bytes = size.into();
+            found += 1;
+        }
+        if let Some(ConfigSettingEntry::ContractDataEntrySizeBytes(size)) =
+            setting(ConfigSettingId::ContractDataEntrySizeBytes)
+        {
+            limits.contract_data_entry_size_bytes = size.into();
+            found += 1;
+        }
+        if let Some(ConfigSettingEntry::ContractMaxSizeBytes(size)) =
+            setting(ConfigSettingId::ContractMaxSizeBytes)
+        {
+            limits.contract_max_size_bytes = size.into();
+            found += 1;
+        }
+        (limits, found == 8)
+    }
+}
+
+/// What a simulated transaction uses of the limited resources.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub instructions: u64,
+    pub memory_bytes: u64,
+    /// Size of the envelope, with the footprint it would be submitted with.
+    pub transaction_size_bytes: u64,
+    pub footprint_entries: u64,
+    pub disk_read_entries: u64,
+    pub disk_read_bytes: u64,
+    pub write_entries: u64,
+    pub write_bytes: u64,
+    /// Contract events of successful calls plus the return values.
+    pub events_and_return_bytes: u64,
+    /// Written entries: base64 key, the limit that applies and its size.
+    pub entries: Vec<(String, &'static str, u64)>,
+    /// The budget's charges per cost type.
+    pub cost_types: Vec<CostTypeCharge>,
+}
+
+/// Collects the usage of the execution that left `storage`.
+pub fn usage(
+    envelope: &TransactionEnvelope,
+    resources: &Resources,
+    storage: &Storage,
+    events: &[HostEvent],
+    return_values: &[ScVal],
+    budget: &Budget,
+) -> Result<Usage, String> {
+    let mut usage = Usage {
+        instructions: budget.get_cpu_insns_consumed().unwrap_or_default(),
+        memory_bytes: budget.get_mem_bytes_consumed().unwrap_or_default(),
+        transaction_size_bytes: transaction_size(envelope, resources, budget)?.into(),
+        footprint_entries: (resources.read_only.len() + resources.read_write.len()) as u64,
+        disk_read_entries: resources.disk_read_entries.into(),
+        disk_read_bytes: resources.disk_read_bytes.into(),
+        write_entries: resources.read_write.len() as u64,
+        write_bytes: resources.write_bytes.into(),
+        cost_types: attribution::cost_types(budget),
+        ..Usage::default()
+    };
+    for event in events
+        .iter()
+        .filter(|e| !e.failed_call && e.event.type_ != ContractEventType::Diagnostic)
+    {
+        usage.events_and_return_bytes += u64::from(xdr_len(&event.event)?);
+    }
+    for value in return_values {
+        usage.events_and_return_bytes += u64::from(xdr_len(value)?);
+    }
+
+    let metering = Budget::default();
+    let footprint = storage
+        .footprint
+        .0
+        .iter(&metering)
+        .map_err(|e| format!("{e:?}"))?;
+    for (key, access) in footprint {
+        if *access != AccessType::ReadWrite {
+            continue;
+        }
+        let Some((entry, _)) = storage
+            .map
+            .get::<LedgerKey>(key, &metering)
+            .map_err(|e| format!("{e:?}"))?
+            .and_then(Option::as_ref)
+        else {
+            continue;
+        };
+        let encoded = key
+            .to_xdr_base64(Limits::none())
+            .map_err(|e| e.to_string())?;
+        match &entry.data {
+            LedgerEntryData::ContractData(data) => {
+                usage.entries.push((
+                    encoded.clone(),
+                    "contract_data_key_size_bytes",
+                    xdr_len(&data.key)?.into(),
+                ));
+                usage.entries.push((
+                    encoded,
+                    "contract_data_entry_size_bytes",
+                    xdr_len(entry.as_ref())?.into(),
+                ));
+            }
+            LedgerEntryData::ContractCode(code) => {
+                usage
+                    .entries
+                    .push((encoded, "contract_max_size_bytes", code.code.len() as u64));
+            }
+            _ => {}
+        }
+    }
+    Ok(usage)
+}
+
+/// Size of `envelope` as submitted. Envelopes without `SorobanTransactionData`
+/// are sized with the data `resources` describe.
+fn transaction_size(
+    envelope: &TransactionEnvelope,
+    resources: &Resources,
+    budget: &Budget,
+) -> Result<u32, String> {
+    let ext = match envelope {
+        TransactionEnvelope::Tx(tx) => &tx.tx.ext,
+        TransactionEnvelope::TxFeeBump(bump) => match &bump.tx.inner_tx {
+            FeeBumpTransactionInnerTx::Tx(tx) => &tx.tx.ext,
+        },
+        TransactionEnvelope::TxV0(_) => &TransactionExt::V0,
+    };
+    let size = xdr_len(envelope)?;
+    if matches!(ext, TransactionExt::V1(_)) || matches!(envelope, TransactionEnvelope::TxV0(_)) {
+        return Ok(size);
+    }
+    let instructions = budget.get_cpu_insns_consumed().unwrap_or_default();
+    let data = assemble::transaction_data(resources, instructions)?;
+    Ok(size.saturating_add(xdr_len(&data)?))
+}
+
+/// A limit the transaction exceeds or comes close to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct LimitCheck {
+    /// Name of the network setting, e.g. `tx_max_write_bytes`.
+    pub limit: &'static str,
+    /// Base64 `LedgerKey` for per-entry limits.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    pub value: u64,
+    pub max: u64,
+    /// `error` if the network would reject the transaction, `warning` if it
+    /// uses at least the soft threshold of the limit.
+    pub severity: &'static str,
+    /// For `tx_max_instructions` and `tx_memory_limit`, the cost types that
+    /// charged the most of the resource, most expensive first.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub top_contributors: Vec<CostTypeCharge>,
+}
+
+impl LimitCheck {
+    /// The top contributors as `"; top costs: WasmInsnExec 61%, …"`, or an
+    /// empty string.
+    pub fn top_costs(&self) -> String {
+        if self.top_contributors.is_empty() {
+            return String::new();
+        }
+        let shares = self
+            .top_contributors
+            .iter()
+            .map(|charge| {
+                let spent = match self.limit {
+                    "tx_memory_limit" => charge.mem_bytes,
+                    _ => charge.cpu_insns,
+                };
+                let percent = u128::from(spent) * 100 / u128::from(self.value.max(1));
+                format!("{} {percent}%", charge.cost_type)
+            })
+            .collect::<Vec<_>>();
+        format!("; top costs: {}", shares.join(", "))
+    }
+}
+
+/// Compares `usage` with `limits`, warning at `warn_percent` of a limit.
+pub fn check(limits: &NetworkLimits, usage: &Usage, warn_percent: u64) -> Vec<LimitCheck> {
+    let totals = [
+        (
+            "tx_max_instructions",
+            usage.instructions,
+            limits.tx_max_instructions,
+        ),
+        (
+            "tx_memory_limit",
+            usage.memory_bytes,
+            limits.tx_memory_limit,
+        ),
+        (
+            "tx_max_size_bytes",
+            usage.transaction_size_bytes,
+            limits.tx_max_size_bytes,
+        ),
+        (
+            "tx_max_footprint_entries",
+            usage.footprint_entries,
+            limits.tx_max_footprint_entries,
+        ),
+        (
+            "tx_max_disk_read_entries",
+            usage.disk_read_entries,
+            limits.tx_max_disk_read_entries,
+        ),
+        (
+            "tx_max_disk_read_bytes",
+            usage.disk_read_bytes,
+            limits.tx_max_disk_read_bytes,
+        ),
+        (
+            "tx_max_write_ledger_entries",
+            usage.write_entries,
+            limits.tx_max_write_ledger_entries,
+        ),
+        (
+            "tx_max_write_bytes",
+            usage.write_bytes,
+            limits.tx_max_write_bytes,
+        ),
+        (
+            "tx_max_contract_events_size_bytes",
+            usage.events_and_return_bytes,
+            limits.tx_max_contract_events_size_bytes,
+        ),
+    ];
+    let entries = usage.entries.iter().map(|(key, limit, value)| {
+        let max = match *limit {
+            "contract_data_key_size_bytes" => limits.contract_data_key_size_bytes,
+            "contract_data_entry_size_bytes" => limits.contract_data_entry_size_bytes,
+            _ => limits.contract_max_size_bytes,
+        };
+        (*limit, Some(key.clone()), *value, max)
+    });
+    totals
+        .into_iter()
+        .map(|(limit, value, max)| (limit, None, value, max))
+        .chain(entries)
+        .filter_map(|(limit, key, value, max)| {
+            let severity = if value > max {
+                "error"
+            } else if u128::from(value) * 100 >= u128::from(max) * u128::from(warn_percent) {
+                "warning"
+            } else {
+                return None;
+            };
+            Some(LimitCheck {
+                limit,
+                key,
+                value,
+                max,
+                severity,
+                top_contributors: top_contributors(limit, &usage.cost_types),
+            })
+        })
+        .collect()
+}
+
+/// The cost types that charged the most of the resource `limit` caps, if
+/// it caps instructions or memory.
+fn top_contributors(limit: &str, cost_types: &[CostTypeCharge]) -> Vec<CostTypeCharge> {
+    let mut charges = cost_types.to_vec();
+    match limit {
+        "tx_max_instructions" => charges.sort_by_key(|c| std::cmp::Reverse(c.cpu_insns)),
+        "tx_memory_limit" => charges.sort_by_key(|c| std::cmp::Reverse(c.mem_bytes)),
+        _ => return Vec::new(),
+    }
+    charges.truncate(TOP_CONTRIBUTORS);
+    charges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::xdr::{LedgerEntry, LedgerEntryExt};
+
+    #[test]
+    fn test_check_reports_errors_and_warnings() {
+        let limits = NetworkLimits::default();
+        let usage = Usage {
+            instructions: 1_000,
+            write_bytes: limits.tx_max_write_bytes + 1,
+            footprint_entries: limits.tx_max_footprint_entries * 95 / 100,
+            entries: vec![
+                ("AAAA".into(), "contract_data_key_size_bytes", 40),
+                (
+                    "BBBB".into(),
+                    "contract_data_entry_size_bytes",
+                    limits.contract_data_entry_size_bytes * 2,
+                ),
+            ],
+            ..Usage::default()
+        };
+        let checks = check(&limits, &usage, WARN_PERCENT)
+            .into_iter()
+            .map(|c| (c.limit, c.key, c.severity))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            checks,
+            vec![
+                ("tx_max_footprint_entries", None, "warning"),
+                ("tx_max_write_bytes", None, "error"),
+                (
+                    "contract_data_entry_size_bytes",
+                    Some("BBBB".to_string()),
+                    "error"
+                ),
+            ]
+        );
+        assert!(check(&limits, &Usage::default(), WARN_PERCENT).is_empty());
+    }
+
+    #[test]
+    fn test_soft_threshold_warns_with_top_contributors() {
+        let limits = NetworkLimits::default();
+        let charge = |cost_type, cpu_insns, mem_bytes| CostTypeCharge {
+            cost_type,
+            iterations: 1,
+            cpu_insns,
+            mem_bytes,
+        };
+        let usage = Usage {
+            instructions: limits.tx_max_instructions * 85 / 100,
+            memory_bytes: 1_000,
+            cost_types: vec![
+                charge("WasmInsnExec", 60_000_000, 0),
+                charge("VmInstantiation", 20_000_000, 800),
+                charge("ComputeSha256Hash", 4_000_000, 0),
+                charge("MemCpy", 1_000_000, 200),
+            ],
+            ..Usage::default()
+        };
+        assert!(check(&limits, &usage, WARN_PERCENT).is_empty());
+
+        let checks = check(&limits, &usage, 80);
+        assert_eq!(checks.len(), 1);
+        let cpu = &checks[0];
+        assert_eq!(
+            (cpu.limit, cpu.severity),
+            ("tx_max_instructions", "warning")
+        );
+        assert_eq!(
+            cpu.top_contributors
+                .iter()
+                .map(|c| c.cost_type)
+                .collect::<Vec<_>>(),
+            vec!["WasmInsnExec", "VmInstantiation", "ComputeSha256Hash"]
+        );
+        assert_eq!(
+            cpu.top_costs(),
+            "; top costs: WasmInsnExec 70%, VmInstantiation 23%, ComputeSha256Hash 4%"
+        );
+
+        let memory = check(&limits, &usage, 0);
+        let memory = memory
+            .iter()
+            .find(|c| c.limit == "tx_memory_limit")
+            .unwrap();
+        assert_eq!(memory.top_contributors[0].cost_type, "VmInstantiation");
+        assert!(check(&limits, &usage, 0)
+            .iter()
+            .filter(|c| c.limit != "tx_max_instructions" && c.limit != "tx_memory_limit")
+            .all(|c| c.top_contributors.is_empty()));
+    }
+
+    #[test]
+    fn test_limits_read_from_config_settings() {
+        let (limits, complete) = NetworkLimits::from_state(|key| match key {
+            LedgerKey::ConfigSetting(setting)
+                if setting.config_setting_id == ConfigSettingId::ContractDataKeySizeBytes =>
+            {
+                Some(SourceEntry {
+                    entry: LedgerEntry {
+                        last_modified_ledger_seq: 1,
+                        data: LedgerEntryData::ConfigSetting(
+                            ConfigSettingEntry::ContractDataKeySizeBytes(300),
+                        ),
+                        ext: LedgerEntryExt::V0,
+                    },
+                    live_until: None,
+                })
+            }
+            _ => None,
+        });
+        assert_eq!(limits.contract_data_key_size_bytes, 300);
+        assert_eq!(
+            limits.tx_max_write_bytes,
+            NetworkLimits::default().tx_max_write_bytes
+        );
+        assert!(!complete);
+    }
+}
@@ -3,33 +3,456 @@
 
 #![allow(unused_imports, unused_variables, clippy::useless_format)]
 
+mod assemble;
+mod attribution;
+mod auth_lint;
+mod authorization;
+mod balances;
+mod baseline;
+mod bench;
+mod bundle;
+mod calls;
+mod check;
+mod codec;
+mod codegen;
+mod compression;
 mod config;
+mod custom_account;
+mod determinism;
+mod diff;
+mod explain;
+mod export;
+mod fault;
+mod fee_source;
+mod fork;
 mod gas_optimizer;
+mod graph;
+mod growth;
+mod hosts;
+mod inspect;
+mod intents;
+mod invoke;
+mod junit;
+mod limits;
+mod memory;
+mod multisig;
+mod network;
+mod oracle;
+mod ordering;
+mod pretty;
+mod protocol;
+mod rent;
+mod repl;
+mod replay;
+mod report;
+mod resources;
 mod runner;
+mod sarif;
+mod scenario;
+mod schema;
+mod server;
+mod simulation;
+mod snapshot;
+mod solver;
+mod source;
 mod source_map_cache;
 mod source_mapper;
+mod spec;
 mod stack_trace;
+mod storage_log;
+mod streaming;
+mod timeline;
 mod vm;
+mod trustline;
+mod truncation;
 mod types;
+mod warm;
 mod wasm;
-
-use crate::gas_optimizer::{BudgetMetrics, GasOptimizationAdvisor, CPU_LIMIT, MEMORY_LIMIT};
-use crate::source_mapper::SourceMapper;
-use crate::stack_trace::{decode_error, WasmStackTrace};
+mod watch;
+mod xdr_limits;
+
+use crate::config::file::ConfigFile;
+use crate::simulation::extract_wasm_instruction;
+use crate::source::cache;
+use crate::source::retry::RetryPolicy;
+use crate::streaming::ParseError;
 use crate::types::*;
-use base64::Engine as _;
-use serde::{Deserialize, Serialize};
-use soroban_env_host::xdr::ReadXdr;
-use soroban_env_host::{
-    xdr::{Operation, OperationBody},
-    Host, HostError,
-};
-use std::collections::HashMap;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
 use std::env;
-use std::io::{self, Read};
+use std::io;
+use std::time::Instant;
 use tracing_subscriber::{fmt, EnvFilter};
 
-// Use types::SimulationRequest directly
+const EXAMPLES: &str = "\
+Examples:
+  Simulate a request:
+    erst-sim < request.json > response.json
+  Check a request without simulating it:
+    erst-sim --check < request.json
+  Serve simulations over HTTP:
+    erst-sim serve --listen 0.0.0.0:8080 --network testnet
+  Describe what a transaction does:
+    erst-sim explain request.json
+  Install completions for bash:
+    erst-sim completions bash > ~/.local/share/bash-completion/completions/erst-sim
+
+Run `erst-sim help <COMMAND>` for the options of a command.";
+
+#[global_allocator]
+static ALLOCATOR: bench::CountingAlloc = bench::CountingAlloc;
+
+/// Soroban transaction simulator used by erst.
+///
+/// Without a subcommand, reads a `SimulationRequest` from stdin and
+/// writes a `SimulationResponse` to stdout.
+#[derive(Debug, Parser)]
+#[command(name = "erst-sim", version, after_long_help = EXAMPLES)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Settings file; defaults to `hintents.toml` in the working directory
+    /// when there is one. Flags, `HINTENTS_*` and `ERST_*` variables and
+    /// request fields override it.
+    #[arg(long, global = true, value_name = "PATH", env = "HINTENTS_CONFIG")]
+    config: Option<std::path::PathBuf>,
+    /// Encoding of the request read from stdin.
+    #[arg(long, value_enum, default_value_t = codec::Format::Json)]
+    input_format: codec::Format,
+    /// Encoding of the response written to stdout. Defaults to the input format.
+    #[arg(long, value_enum)]
+    output_format: Option<codec::Format>,
+    /// Compress the response written to stdout. Compressed requests are
+    /// detected automatically.
+    #[arg(long, value_enum)]
+    output_compression: Option<compression::Encoding>,
+    /// Shape of the response: the simulator's own, or soroban-rpc's
+    /// `getTransaction` result for transactions that ran.
+    #[arg(long, value_enum, default_value_t)]
+    output_shape: replay::OutputShape,
+    /// Also write a report of the simulation, `md` or `html`, e.g.
+    /// `--report md out.md`.
+    #[arg(long, num_args = 2, value_names = ["FORMAT", "PATH"])]
+    report: Option<Vec<String>>,
+    /// Also write the contract call graph, `dot` or `mermaid`, e.g.
+    /// `--graph dot calls.dot`.
+    #[arg(long, num_args = 2, value_names = ["FORMAT", "PATH"])]
+    graph: Option<Vec<String>>,
+    /// Also write footprint, authorization and network limit findings as
+    /// SARIF to this file.
+    #[arg(long, value_name = "PATH")]
+    sarif: Option<std::path::PathBuf>,
+    /// When the simulation fails, write a bundle that reproduces the failure
+    /// to this file: the request with the ledger entries it read, the
+    /// simulator and host versions, and the response.
+    #[arg(long, value_name = "PATH")]
+    bundle: Option<std::path::PathBuf>,
+    /// Write the ledger the transaction leaves behind to this file as a
+    /// snapshot, to start later simulations from.
+    #[arg(long, value_name = "PATH")]
+    export_state: Option<std::path::PathBuf>,
+    /// Entries `--export-state` writes: the whole ledger known to the
+    /// simulation, or only those the transaction read or wrote.
+    #[arg(long, value_enum, default_value_t, requires = "export_state")]
+    export_scope: export::Scope,
+    /// Validate the request without simulating it and print every problem
+    /// found.
+    ///
+    /// Exits with 0 when the request is valid, 1 when it is not and 2 when
+    /// it cannot be read.
+    #[arg(long, conflicts_with = "check_determinism")]
+    check: bool,
+    /// Simulate the request this many times and print the response fields
+    /// that differ between runs instead of the response.
+    ///
+    /// Exits with 0 when every run matches, 1 when one differs and 2 on
+    /// error.
+    #[arg(
+        long,
+        value_name = "RUNS",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(2..)
+    )]
+    check_determinism: Option<usize>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Serve simulations over HTTP.
+    Serve(Box<server::ServeArgs>),
+    /// Print the JSON Schema of the request or response.
+    Schema {
+        #[arg(value_enum, default_value_t = SchemaKind::Request)]
+        kind: SchemaKind,
+    },
+    /// Work with snapshot files.
+    #[command(subcommand)]
+    Snapshot(SnapshotCommand),
+    /// Work with settings.
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Generate code from a simulation.
+    #[command(subcommand)]
+    Codegen(CodegenCommand),
+    /// Work with a persistent local fork of a network, whose entries are
+    /// fetched from the network the first time they are read.
+    #[command(subcommand)]
+    Fork(ForkCommand),
+    /// Print a completion script for a shell.
+    ///
+    /// Write it where the shell loads completions from, e.g.
+    /// `~/.local/share/bash-completion/completions/erst-sim` for bash,
+    /// a directory on `$fpath` as `_erst-sim` for zsh, or
+    /// `~/.config/fish/completions/erst-sim.fish` for fish.
+    Completions {
+        /// Shell to complete for.
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+    /// Compare two saved simulation responses and print what changed.
+    ///
+    /// Exits with 0 when the simulations behaved the same, 1 when they
+    /// differ and 2 on error.
+    Diff {
+        /// Response of the baseline simulation.
+        before: std::path::PathBuf,
+        /// Response to compare against it.
+        after: std::path::PathBuf,
+    },
+    /// List a contract's imported host functions, exports, custom sections
+    /// and size, as JSON.
+    InspectWasm {
+        /// Contract WASM file.
+        path: std::path::PathBuf,
+    },
+    /// Print a contract's metadata, functions, types, errors and events, as
+    /// JSON.
+    Spec(SpecArgs),
+    /// Simulate a request and describe the transaction in plain English:
+    /// what it calls, who must authorize it, balance changes and fees.
+    Explain {
+        /// JSON request to simulate; read from stdin when omitted.
+        request: Option<std::path::PathBuf>,
+    },
+    /// Run a scenario file: simulation steps over shared ledger state, with
+    /// assertions about each step's outcome.
+    ///
+    /// Exits with 0 when every assertion holds, 1 when one fails and 2 on
+    /// error.
+    Scenario {
+        /// JSON scenario file.
+        file: std::path::PathBuf,
+        /// Also write the results as JUnit XML to this file.
+        #[arg(long)]
+        junit: Option<std::path::PathBuf>,
+        /// Also write failed assertions and the steps' findings as SARIF to
+        /// this file.
+        #[arg(long)]
+        sarif: Option<std::path::PathBuf>,
+    },
+    /// Run a batch of transactions in different orders and report how their
+    /// results and balance changes depend on the order.
+    ///
+    /// Exits with 0 when nothing depends on the order, 1 when something does
+    /// and 2 on error.
+    Ordering {
+        /// JSON batch file.
+        batch: std::path::PathBuf,
+    },
+    /// Run a scenario again whenever it or a contract's WASM changes, and
+    /// print which steps started failing or were fixed.
+    Watch {
+        /// JSON scenario file.
+        scenario: std::path::PathBuf,
+        /// Contract WASM to install into the scenario's state, e.g. the
+        /// output of `cargo build`.
+        #[arg(long)]
+        wasm: Option<std::path::PathBuf>,
+        /// Contract (`C...`) to run the WASM; defaults to the scenario's
+        /// only WASM contract. May be repeated.
+        #[arg(long, requires = "wasm")]
+        contract: Vec<String>,
+        /// Milliseconds between checks for changes.
+        #[arg(long, default_value_t = 500)]
+        interval_ms: u64,
+    },
+    /// Explore contracts interactively: call functions, inspect storage,
+    /// advance the ledger and roll back, over an in-memory ledger.
+    ///
+    /// Type `help` at the prompt for the commands.
+    Repl {
+        /// Snapshot file to start from; the ledger starts empty otherwise.
+        snapshot: Option<std::path::PathBuf>,
+        /// Source account (`G...`) of calls.
+        #[arg(long)]
+        source: Option<String>,
+    },
+    /// Record or check the CPU and memory costs of a scenario's steps.
+    #[command(subcommand)]
+    Baseline(BaselineCommand),
+    /// Simulate a request repeatedly and report latency percentiles,
+    /// throughput and allocations of the simulator itself.
+    Bench {
+        /// JSON request to simulate.
+        #[arg(long, required_unless_present = "snapshot")]
+        request: Option<std::path::PathBuf>,
+        /// Snapshot file whose entry decoding to time, instead of a request.
+        #[arg(long, conflicts_with_all = ["request", "jobs"])]
+        snapshot: Option<std::path::PathBuf>,
+        /// Simulations to run in total.
+        #[arg(long, default_value_t = 100)]
+        iterations: usize,
+        /// Threads running simulations concurrently.
+        #[arg(long, default_value_t = 1)]
+        jobs: usize,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum BaselineCommand {
+    /// Run a scenario and store each step's costs as a JSON baseline.
+    Record {
+        /// JSON scenario file.
+        scenario: std::path::PathBuf,
+        /// Baseline file to write.
+        #[arg(long, short)]
+        output: std::path::PathBuf,
+    },
+    /// Run a scenario and compare each step's costs with a baseline.
+    ///
+    /// Exits with 0 when no step regressed, 1 when one did or a baseline
+    /// step did not run, and 2 on error.
+    Check {
+        /// JSON scenario file.
+        scenario: std::path::PathBuf,
+        /// Baseline file written by `baseline record`.
+        #[arg(long)]
+        baseline: std::path::PathBuf,
+        /// Percentage a cost may grow before it counts as a regression.
+        #[arg(long, default_value_t = 5.0)]
+        threshold: f64,
+    },
+}
+
+#[derive(Debug, clap::Args)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct SpecArgs {
+    #[command(subcommand)]
+    command: Option<SpecCommand>,
+    /// Contract WASM file.
+    #[arg(long, conflicts_with = "contract_id", required_unless_present = "contract_id")]
+    wasm: Option<std::path::PathBuf>,
+    /// Contract (`C...`) whose WASM to fetch from the network.
+    #[arg(long)]
+    contract_id: Option<String>,
+    /// Network preset (`pubnet`, `testnet`, `futurenet`) to fetch from.
+    #[arg(long)]
+    network: Option<String>,
+    /// soroban-rpc endpoint to fetch from (defaults to the network's).
+    #[arg(long)]
+    rpc_url: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+enum SpecCommand {
+    /// Compare the interfaces of two builds of a contract.
+    ///
+    /// Exits with 0 when they match, 1 when they differ and 2 on error.
+    Diff {
+        /// Currently deployed build.
+        old: std::path::PathBuf,
+        /// Proposed build.
+        new: std::path::PathBuf,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum SnapshotCommand {
+    /// Convert a JSON snapshot into an indexed snapshot for `serve --snapshot-file`.
+    Build {
+        /// JSON snapshot to read.
+        input: std::path::PathBuf,
+        /// Indexed snapshot to write.
+        output: std::path::PathBuf,
+    },
+    /// Convert a snapshot into a soroban-sdk test snapshot, for
+    /// `Env::from_snapshot_file` in Rust unit tests.
+    ExportSdk {
+        /// Snapshot to read.
+        input: std::path::PathBuf,
+        /// Test snapshot to write.
+        output: std::path::PathBuf,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum CodegenCommand {
+    /// Simulate a request and print a soroban-sdk test making the same
+    /// call, writing the ledger entries it reads as a test snapshot.
+    RustTest {
+        /// Request to simulate; `jq .request` takes one out of a bundle.
+        request: std::path::PathBuf,
+        /// Name of the test function [default: test_<function>].
+        #[arg(long)]
+        name: Option<String>,
+        /// Test snapshot to write, relative to the crate the test goes in
+        /// [default: test_snapshots/erst/<name>.json].
+        #[arg(long)]
+        snapshot: Option<std::path::PathBuf>,
+    },
+    /// Simulate a request and print a TypeScript module exporting its
+    /// outcome, events, balance changes and fee as stellar-sdk values.
+    Ts {
+        /// Request to simulate; `jq .request` takes one out of a bundle.
+        request: std::path::PathBuf,
+        /// Name of the exported fixture [default: <function>Simulation].
+        #[arg(long)]
+        name: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ForkCommand {
+    /// Create a fork file forking the network at a ledger. No entries are
+    /// downloaded until a simulation reads them.
+    Create {
+        /// Fork file to create.
+        fork: std::path::PathBuf,
+        /// soroban-rpc endpoint to fork [default: the configured network's].
+        #[arg(long)]
+        rpc_url: Option<String>,
+        /// Ledger to fork at [default: the network's latest].
+        #[arg(long)]
+        at_ledger: Option<u32>,
+        /// Network name or passphrase requests run on [default: the
+        /// configured network].
+        #[arg(long)]
+        network: Option<String>,
+    },
+    /// Simulate a request at the fork's next ledger and, if it succeeds,
+    /// apply its ledger changes to the fork.
+    Simulate {
+        /// Fork file.
+        fork: std::path::PathBuf,
+        /// Request to simulate; read from stdin if omitted.
+        request: Option<std::path::PathBuf>,
+        /// Leave the fork's ledger unchanged. Entries fetched from the
+        /// network are kept either way.
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommand {
+    /// Print the settings in effect, merged from defaults, the settings
+    /// file and `HINTENTS_*` and `ERST_*` variables, as a settings file.
+    Show,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum SchemaKind {
+    Request,
+    Response,
+}
 
 fn init_logger() {
     // Check if the environment variable ERST_LOG_FORMAT is set to "json"
@@ -53,837 +476,936 @@ fn init_logger() {
     }
 }
 
-fn send_error(msg: String) {
-    let trace = WasmStackTrace::from_host_error(&msg);
-    let res = SimulationResponse {
-        status: "error".to_string(),
-        error: Some(msg),
-        events: vec![],
-        diagnostic_events: vec![],
-        categorized_events: vec![],
-        logs: vec![],
-        flamegraph: None,
-        optimization_report: None,
-        budget_usage: None,
-        source_location: None,
-        stack_trace: Some(trace),
-        wasm_offset: None,
-    };
-    if let Ok(json) = serde_json::to_string(&res) {
-        println!("{}", json);
-    } else {
-        eprintln!("Failed to serialize error response");
-        println!("{{\"status\": \"error\", \"error\": \"Internal serialization error\"}}");
-    }
-    std::process::exit(1);
+/// How the response is written to stdout.
+#[derive(Debug, Clone, Copy)]
+struct Output {
+    format: codec::Format,
+    compression: Option<compression::Encoding>,
 }
 
-fn execute_operations(host: &Host, operations: &[Operation]) -> Result<Vec<String>, HostError> {
-    let mut logs = Vec::new();
-    for op in operations {
-        match &op.body {
-            OperationBody::InvokeHostFunction(invoke_op) => {
-                logs.push("Executing InvokeHostFunction...".to_string());
-                let val = host.invoke_function(invoke_op.host_function.clone())?;
-                logs.push(format!("Result: {val:?}"));
-            }
-            _ => {
-                logs.push(format!(
-                    "Skipping non-Soroban operation: {:?}",
-                    op.body.name()
-                ));
-            }
+fn print_response(res: &impl serde::Serialize, output: Output) {
+    use std::io::Write as _;
+    if output.format == codec::Format::Json && output.compression.is_none() {
+        // Serialize straight to stdout rather than building the document in
+        // memory first; responses with thousands of events are large.
+        let mut stdout = io::BufWriter::new(io::stdout().lock());
+        let written = serde_json::to_writer(&mut stdout, res)
+            .map_err(io::Error::from)
+            .and_then(|()| stdout.write_all(b"\n"))
+            .and_then(|()| stdout.flush());
+        if written.is_err() {
+            eprintln!("Failed to write simulation response");
         }
+        return;
     }
-    Ok(logs)
-}
-
-fn transaction_fee_stroops(envelope: &soroban_env_host::xdr::TransactionEnvelope) -> u64 {
-    match envelope {
-        soroban_env_host::xdr::TransactionEnvelope::Tx(tx_v1) => tx_v1.tx.fee as u64,
-        soroban_env_host::xdr::TransactionEnvelope::TxV0(tx_v0) => tx_v0.tx.fee as u64,
-        soroban_env_host::xdr::TransactionEnvelope::TxFeeBump(bump) => bump.tx.fee as u64,
-    }
-}
-
-fn mocked_required_fee_stroops(
-    request: &SimulationRequest,
-    operations_count: usize,
-    cpu_insns: u64,
-    mem_bytes: u64,
-) -> Option<u64> {
-    let mut required_fee = 0u64;
-    let mut enabled = false;
-
-    if let Some(base_fee) = request.mock_base_fee {
-        enabled = true;
-        required_fee =
-            required_fee.saturating_add((base_fee as u64).saturating_mul(operations_count as u64));
-    }
-
-    if let Some(gas_price) = request.mock_gas_price {
-        enabled = true;
-        // Keep the unit small enough to be predictable in local replay while still driven by observed usage.
-        let cpu_units = cpu_insns.saturating_add(9_999) / 10_000;
-        let mem_units = mem_bytes.saturating_add(1_023) / 1_024;
-        let resource_units = cpu_units.saturating_add(mem_units).max(1);
-        required_fee = required_fee.saturating_add(gas_price.saturating_mul(resource_units));
-    }
-
-    if enabled {
-        Some(required_fee)
-    } else {
-        None
+    let encoded = codec::encode(output.format, res).and_then(|bytes| match output.compression {
+        Some(encoding) => compression::compress(encoding, &bytes).map_err(|e| e.to_string()),
+        None => Ok(bytes),
+    });
+    match encoded {
+        Ok(bytes) => {
+            let mut stdout = io::stdout().lock();
+            if stdout.write_all(&bytes).and_then(|()| stdout.flush()).is_err() {
+                eprintln!("Failed to write simulation response");
+            }
+        }
+        Err(_) => {
+            eprintln!("Failed to serialize simulation response");
+            println!("{{\"status\": \"error\", \"error\": \"Internal serialization error\"}}");
+        }
     }
 }
 
-fn categorize_events(events: &soroban_env_host::events::Events) -> Vec<CategorizedEvent> {
-    events
-        .0
-        .iter()
-        .map(|e| {
-            let category = match e.event.type_ {
-                soroban_env_host::xdr::ContractEventType::Contract => "Contract",
-                soroban_env_host::xdr::ContractEventType::System => "System",
-                soroban_env_host::xdr::ContractEventType::Diagnostic => "Diagnostic",
-            }
-            .to_string();
-
-            let contract_id = e.event.contract_id.as_ref().map(|id| format!("{id:?}"));
-            let topics = match &e.event.body {
-                soroban_env_host::xdr::ContractEventBody::V0(v0) => {
-                    v0.topics
-                        .iter()
-                        .map(|t| format!("{:?}", t))
-                        .collect::<Vec<String>>()
-                    v0.topics.iter().map(|t| format!("{t:?}")).collect()
-                }
-            };
-            let data = match &e.event.body {
-                soroban_env_host::xdr::ContractEventBody::V0(v0) => format!("{:?}", v0.data),
-            };
-
-            let wasm_instruction = extract_wasm_instruction(&topics, &data);
-            CategorizedEvent {
-                category,
-                event: DiagnosticEvent {
-                    event_type: match e.event.type_ {
-                        soroban_env_host::xdr::ContractEventType::Contract => {
-                            "contract".to_string()
-                        }
-                        soroban_env_host::xdr::ContractEventType::System => "system".to_string(),
-                        soroban_env_host::xdr::ContractEventType::Diagnostic => {
-                            "diagnostic".to_string()
-                        }
-                    },
-                    contract_id,
-                    topics,
-                    data,
-                    in_successful_contract_call: e.failed_call,
-                    wasm_instruction,
-                    // failed_call=true means the call that emitted this event
-                    // actually failed; so a successful call is the inverse.
-                    in_successful_contract_call: !e.failed_call,
-                },
-            }
-        })
-        .collect()
+fn send_error(msg: String, output: Output) {
+    print_response(&simulation::error_response(msg), output);
+    std::process::exit(1);
 }
 
 /// Main entry point for the erst simulator.
 ///
-/// Reads a JSON `SimulationRequest` from stdin, 
-/// initializes a Soroban host environment, and outputs a JSON
+/// Reads a `SimulationRequest` from stdin (JSON, CBOR or MessagePack),
+/// initializes a Soroban host environment, and outputs a
 /// `SimulationResponse` with simulation results or errors.
-///
-/// # Panics
-///
-/// May panic if JSON serialization of the response fails (should not happen
-/// with valid `SimulationResponse` structures).
+/// With `serve`, the same pipeline is exposed over HTTP instead.
 fn main() {
     // 1. Initialize the logger immediately
     init_logger();
 
-    // 2. Log that we started
-    tracing::info!(event = "simulator_started", "Simulator initializing...");
-
-    // Read JSON from Stdin
-    let mut buffer = String::new();
-    if let Err(e) = std::io::stdin().read_to_string(&mut buffer) {
-        let err_msg = format!("Failed to read stdin: {}", e);
-        let res = SimulationResponse {
-            status: "error".to_string(),
-            error: Some(err_msg.clone()),
-    if let Err(e) = io::stdin().read_to_string(&mut buffer) {
-        let res = SimulationResponse {
-            status: "error".to_string(),
-            error: Some(format!("Failed to read stdin: {e}")),
-            events: vec![],
-            diagnostic_events: vec![],
-            categorized_events: vec![],
-            logs: vec![],
-            flamegraph: None,
-            optimization_report: None,
-            budget_usage: None,
-            source_location: None,
-            stack_trace: None,
-        };
-        if let Ok(json) = serde_json::to_string(&res) {
-            println!("{}", json);
-        } else {
-            eprintln!("Failed to serialize error response");
-            println!("{{\"status\": \"error\", \"error\": \"Internal serialization error\"}}");
-        }
-        eprintln!("{}", err_msg);
-        eprintln!("Failed to read stdin: {e}");
+    let cli = Cli::parse();
+    if let Some(Command::Completions { shell }) = &cli.command {
+        clap_complete::generate(*shell, &mut Cli::command(), "erst-sim", &mut io::stdout());
         return;
     }
-
-    // Parse Request
-    let request: SimulationRequest = match serde_json::from_str(&buffer) {
-        Ok(req) => req,
+    let settings = ConfigFile::discover(cli.config.as_deref()).and_then(ConfigFile::with_env);
+    let settings = match settings {
+        Ok(settings) => settings,
         Err(e) => {
-            let res = SimulationResponse {
-                status: "error".to_string(),
-                error: Some(format!("Invalid JSON: {e}")),
-                events: vec![],
-                diagnostic_events: vec![],
-                categorized_events: vec![],
-                logs: vec![],
-                flamegraph: None,
-                optimization_report: None,
-                budget_usage: None,
-                source_location: None,
-                stack_trace: None,
-                wasm_offset: None,
-            };
-            println!("{}", serde_json::to_string(&res).expect("Failed to serialize error response"));
-            return;
+            eprintln!("{e}");
+            std::process::exit(2);
         }
     };
-
-    // Handle restore_preamble if present
-    if let Some(ref preamble) = request.restore_preamble {
-        eprintln!("[restore_preamble] Received: {}", preamble);
-        // If restore_preamble contains ledger keys/values, inject into host storage
-        if let Some(obj) = preamble.as_object() {
-            if let Some(entries) = obj.get("ledger_entries") {
-                if let Some(map) = entries.as_object() {
-                    for (key_xdr, entry_xdr_val) in map {
-                        if let Some(entry_xdr) = entry_xdr_val.as_str() {
-                            // Decode Key
-                            let key = match base64::engine::general_purpose::STANDARD.decode(key_xdr) {
-                                Ok(b) => match soroban_env_host::xdr::LedgerKey::from_xdr(
-                                    b,
-                                    soroban_env_host::xdr::Limits::none(),
-                                ) {
-                                    Ok(k) => k,
-                                    Err(e) => {
-                                        eprintln!("[restore_preamble] Failed to parse LedgerKey XDR: {}", e);
-                                        continue;
-                                    }
-                                },
-                                Err(e) => {
-                                    eprintln!("[restore_preamble] Failed to decode LedgerKey Base64: {}", e);
-                                    continue;
-                                }
-                            };
-                            // Decode Entry
-                            let entry = match base64::engine::general_purpose::STANDARD.decode(entry_xdr) {
-                                Ok(b) => match soroban_env_host::xdr::LedgerEntry::from_xdr(
-                                    b,
-                                    soroban_env_host::xdr::Limits::none(),
-                                ) {
-                                    Ok(e) => e,
-                                    Err(e) => {
-                                        eprintln!("[restore_preamble] Failed to parse LedgerEntry XDR: {}", e);
-                                        continue;
-                                    }
-                                },
-                                Err(e) => {
-                                    eprintln!("[restore_preamble] Failed to decode LedgerEntry Base64: {}", e);
-                                    continue;
-                                }
-                            };
-                            // Inject into host storage
-                            match host.put_ledger_entry(key.clone(), entry.clone()) {
-                                Ok(_) => eprintln!("[restore_preamble] Injected Ledger Entry: Key={:?}", key),
-                                Err(e) => eprintln!("[restore_preamble] Failed to inject entry: {:?}", e),
-                            }
-                        }
-                    }
+    let cli = match cli.command {
+        Some(Command::Serve(_)) => match parse_with_serve_defaults(&settings) {
+            Ok(cli) => cli,
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(2);
+            }
+        },
+        _ => cli,
+    };
+    let memory_limits = settings
+        .limits
+        .apply(memory::MemoryLimits::default())
+        .with_env();
+    memory::set_global_memory_bytes(memory_limits.global_memory_bytes);
+
+    if let Some(Command::Serve(args)) = &cli.command {
+        if let Err(e) = server::serve(args, memory_limits) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(Command::Config(ConfigCommand::Show)) = &cli.command {
+        let shown = effective_settings(&settings, &memory_limits)
+            .and_then(|effective| toml::to_string(&effective).map_err(|e| e.to_string()));
+        match shown {
+            Ok(toml) => print!("{toml}"),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(2);
+            }
+        }
+        return;
+    }
+    if let Some(Command::Repl { snapshot, source }) = &cli.command {
+        let mut session =
+            repl::Session::new(settings.network.name.clone(), source.clone(), memory_limits);
+        if let Some(path) = snapshot {
+            match session.load(path) {
+                Ok(loaded) => println!("{loaded}"),
+                Err(e) => {
+                    eprintln!("{e}");
+                    std::process::exit(2);
                 }
             }
         }
+        let prompt = io::IsTerminal::is_terminal(&io::stdin());
+        if let Err(e) = repl::run(&mut session, io::stdin().lock(), io::stdout(), prompt) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return;
     }
-
-    // Decode Envelope XDR
-    let envelope = match base64::engine::general_purpose::STANDARD.decode(&request.envelope_xdr) {
-        Ok(bytes) => match soroban_env_host::xdr::TransactionEnvelope::from_xdr(
-            bytes,
-            soroban_env_host::xdr::Limits::none(),
-        ) {
-            Ok(env) => env,
+    if let Some(Command::Snapshot(SnapshotCommand::Build { input, output })) = &cli.command {
+        let built = snapshot::file::SnapshotFile::load(input)
+            .and_then(|file| snapshot::indexed::build(&file, output));
+        match built {
+            Ok(entries) => println!("Wrote {entries} entries to {}", output.display()),
             Err(e) => {
-                send_error(format!("Failed to parse Envelope XDR: {}", e));
-                return;
+                eprintln!("{e}");
+                std::process::exit(1);
             }
-        },
-        Err(e) => {
-            send_error(format!("Failed to decode Envelope Base64: {}", e));
-            return;
         }
-    };
-
-    // Decode ResultMeta XDR
-    eprintln!(
-        "Debug: Received ResultMetaXdr len: {}",
-        request.result_meta_xdr.len()
-    );
-
-    let _result_meta = if request.result_meta_xdr.is_empty() {
-        eprintln!("Warning: ResultMetaXdr is empty. Host storage may be incomplete.");
-        None
-    } else {
-        match base64::engine::general_purpose::STANDARD.decode(&request.result_meta_xdr) {
-            Ok(bytes) => {
-                if bytes.is_empty() {
-                    eprintln!("Warning: ResultMetaXdr decoded to 0 bytes.");
-                    None
-                } else {
-                    match soroban_env_host::xdr::TransactionResultMeta::from_xdr(
-                        &bytes,
-                        soroban_env_host::xdr::Limits::none(),
-                    ) {
-                        Ok(meta) => Some(meta),
-                        Err(e) => {
-                            eprintln!("Warning: Failed to parse ResultMeta XDR: {}. Proceeding with empty storage.", e);
-                            None
-                        }
+        return;
+    }
+    if let Some(Command::Snapshot(SnapshotCommand::ExportSdk { input, output })) = &cli.command {
+        let exported = network::Network::resolve(settings.network.name.as_deref(), None)
+            .and_then(|network| {
+                let file = snapshot::file::SnapshotFile::load(input).map_err(|e| e.to_string())?;
+                let json = snapshot::sdk::to_json(&file, &network).map_err(|e| e.to_string())?;
+                std::fs::write(output, json)
+                    .map_err(|e| format!("Failed to write {}: {e}", output.display()))?;
+                Ok(file.entries.len())
+            });
+        match exported {
+            Ok(entries) => println!("Wrote {entries} entries to {}", output.display()),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(Command::Diff { before, after }) = &cli.command {
+        match diff::diff_files(before, after) {
+            Ok(delta) => {
+                match serde_json::to_string_pretty(&delta) {
+                    Ok(json) => println!("{json}"),
+                    Err(e) => {
+                        eprintln!("{e}");
+                        std::process::exit(2);
                     }
                 }
+                std::process::exit(i32::from(!delta.is_empty()));
             }
             Err(e) => {
-                eprintln!("Warning: Failed to decode ResultMeta Base64: {e}. Proceeding with empty storage.");
-                None
+                eprintln!("{e}");
+                std::process::exit(2);
             }
         }
-    };
-
-    // Initialize source mapper if WASM is provided
-    let _source_mapper = if let Some(wasm_base64) = &request.contract_wasm {
-        match base64::engine::general_purpose::STANDARD.decode(wasm_base64) {
-            Ok(wasm_bytes) => {
-                if let Err(e) = vm::enforce_soroban_compatibility(&wasm_bytes) {
-                    return send_error(format!("Strict VM enforcement failed: {}", e));
-                }
-                let mapper = SourceMapper::new(wasm_bytes);
-                if mapper.has_debug_symbols() {
-                    eprintln!("Debug symbols found in WASM");
-                    Some(mapper)
-                } else {
-                    eprintln!("No debug symbols found in WASM");
-                    None
+    }
+    if let Some(Command::InspectWasm { path }) = &cli.command {
+        match inspect::inspect_file(path).and_then(|inspection| {
+            serde_json::to_string_pretty(&inspection).map_err(|e| e.to_string())
+        }) {
+            Ok(json) => println!("{json}"),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(Command::Spec(SpecArgs {
+        command: Some(SpecCommand::Diff { old, new }),
+        ..
+    })) = &cli.command
+    {
+        match spec::diff_files(old, new) {
+            Ok(delta) => {
+                match serde_json::to_string_pretty(&delta) {
+                    Ok(json) => println!("{json}"),
+                    Err(e) => {
+                        eprintln!("{e}");
+                        std::process::exit(2);
+                    }
                 }
+                std::process::exit(i32::from(!delta.is_empty()));
             }
             Err(e) => {
-                eprintln!("Failed to decode WASM base64: {e}");
-                None
+                eprintln!("{e}");
+                std::process::exit(2);
             }
         }
-    } else {
-        None
-    };
-
-    // Initialize Host
-    let sim_host = runner::SimHost::new(None, request.resource_calibration.clone());
-    let host = sim_host.inner;
-
-    // --- START: Local WASM Loading Integration (Issue #70) ---
-    if let Some(path) = &request.wasm_path {
-        match wasm::load_wasm_from_path(path) {
-            Ok(wasm_bytes) => match host.upload_contract_wasm(wasm_bytes) {
-                Ok(hash) => eprintln!("Successfully loaded local WASM. Hash: {:?}", hash),
-                Err(e) => send_error(format!("Host failed to upload local WASM: {:?}", e)),
-            },
-            Err(e) => send_error(format!("Local WASM loading failed: {}", e)),
+    }
+    if let Some(Command::Spec(args)) = &cli.command {
+        let wasm = spec::read_wasm(
+            args.wasm.as_deref(),
+            args.contract_id.as_deref(),
+            args.network.as_deref(),
+            args.rpc_url.as_deref(),
+        );
+        match wasm
+            .and_then(|wasm| spec::from_wasm(&wasm))
+            .and_then(|spec| serde_json::to_string_pretty(&spec).map_err(|e| e.to_string()))
+        {
+            Ok(json) => println!("{json}"),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
         }
+        return;
     }
-    // --- END: Local WASM Loading Integration ---
-
-    let mut loaded_entries_count = 0;
-
-    // Populate Host Storage
-    if let Some(entries) = &request.ledger_entries {
-        for (key_xdr, entry_xdr) in entries {
-            // Decode Key
-            let _key = match base64::engine::general_purpose::STANDARD.decode(key_xdr) {
-                Ok(b) => match soroban_env_host::xdr::LedgerKey::from_xdr(
-                    b,
-                    soroban_env_host::xdr::Limits::none(),
-                ) {
-                    Ok(k) => k,
-                    Err(e) => {
-                        send_error(format!("Failed to parse LedgerKey XDR: {}", e));
-                        return;
+    if let Some(Command::Codegen(CodegenCommand::RustTest {
+        request,
+        name,
+        snapshot,
+    })) = &cli.command
+    {
+        match rust_test(request, name.as_deref(), snapshot.as_deref(), &memory_limits, &settings) {
+            Ok(code) => print!("{code}"),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(Command::Codegen(CodegenCommand::Ts { request, name })) = &cli.command {
+        match ts_fixture(request, name.as_deref(), &memory_limits, &settings) {
+            Ok(code) => print!("{code}"),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(Command::Fork(ForkCommand::Create {
+        fork,
+        rpc_url,
+        at_ledger,
+        network,
+    })) = &cli.command
+    {
+        if let Err(e) = create_fork(fork, rpc_url.as_deref(), *at_ledger, network, &settings) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(Command::Fork(ForkCommand::Simulate {
+        fork,
+        request,
+        dry_run,
+    })) = &cli.command
+    {
+        let response = simulate_on_fork(fork, request.as_deref(), !dry_run, &memory_limits)
+            .and_then(|response| serde_json::to_string_pretty(&response).map_err(|e| e.to_string()));
+        match response {
+            Ok(json) => println!("{json}"),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(Command::Explain { request }) = &cli.command {
+        let limit = memory_limits.max_request_bytes;
+        let parsed = match request {
+            Some(path) => std::fs::File::open(path)
+                .map_err(|e| format!("Failed to read {}: {e}", path.display()))
+                .and_then(|file| {
+                    streaming::parse_json(io::BufReader::new(file), limit)
+                        .map_err(|e| e.to_string())
+                }),
+            None => streaming::parse_json(io::stdin().lock(), limit).map_err(|e| e.to_string()),
+        };
+        let explained = parsed.and_then(|mut parsed| {
+            settings.network.apply(&mut parsed.request);
+            let remote = remote_source(&parsed.request, &settings);
+            simulation::run(&parsed.request, &memory_limits, None, remote, None)
+                .map_err(|e| e.to_string())
+                .and_then(|response| explain::explain(&parsed.request, &response))
+        });
+        match explained {
+            Ok(narrative) => println!("{narrative}"),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(Command::Scenario { file, junit, sarif }) = &cli.command {
+        let outcome = run_scenario(file, &memory_limits, &settings);
+        if let Some(path) = junit {
+            if let Err(e) = std::fs::write(path, junit::junit(&outcome)) {
+                eprintln!("Failed to write {}: {e}", path.display());
+                std::process::exit(2);
+            }
+        }
+        if let Some(path) = sarif {
+            let findings = sarif::scenario_findings(&outcome);
+            let artifact = file.to_string_lossy();
+            if let Err(e) = sarif::write(path, &findings, Some(&artifact)) {
+                eprintln!("{e}");
+                std::process::exit(2);
+            }
+        }
+        match serde_json::to_string_pretty(&outcome) {
+            Ok(json) => println!("{json}"),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(2);
+            }
+        }
+        std::process::exit(if outcome.passed { 0 } else { 1 });
+    }
+    if let Some(Command::Ordering { batch }) = &cli.command {
+        let default_name = batch
+            .file_stem()
+            .map_or_else(|| "batch".into(), |stem| stem.to_string_lossy());
+        let report = ordering::load(batch).and_then(|loaded| {
+            ordering::run(&loaded, &default_name, &memory_limits, |request| {
+                remote_source(request, &settings)
+            })
+        });
+        let report = match report {
+            Ok(report) => report,
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(2);
+            }
+        };
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{json}"),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(2);
+            }
+        }
+        std::process::exit(i32::from(report.order_dependent));
+    }
+    if let Some(Command::Watch {
+        scenario,
+        wasm,
+        contract,
+        interval_ms,
+    }) = &cli.command
+    {
+        let files = std::iter::once(scenario).chain(wasm).cloned().collect();
+        let mut watched = watch::Watched::new(files);
+        let mut previous = None;
+        loop {
+            match watch_scenario(scenario, wasm.as_deref(), contract, &memory_limits, &settings) {
+                Ok(outcome) => {
+                    let results = watch::results(&outcome);
+                    for line in watch::report(&outcome.name, previous.as_deref(), &results) {
+                        println!("{line}");
                     }
-                },
-                Err(e) => {
-                    send_error(format!("Failed to decode LedgerKey Base64: {}", e));
-                    return;
+                    previous = Some(results);
                 }
-            };
-
-            // Decode Entry
-            let _entry = match base64::engine::general_purpose::STANDARD.decode(entry_xdr) {
-                Ok(b) => match soroban_env_host::xdr::LedgerEntry::from_xdr(
-                    b,
-                    soroban_env_host::xdr::Limits::none(),
-                ) {
-                    Ok(e) => e,
+                Err(e) => eprintln!("{e}"),
+            }
+            watched.wait(std::time::Duration::from_millis(*interval_ms));
+        }
+    }
+    if let Some(Command::Baseline(command)) = &cli.command {
+        let (json, code) = match command {
+            BaselineCommand::Record { scenario, output } => {
+                let baseline = baseline::record(&run_scenario(scenario, &memory_limits, &settings));
+                if let Err(e) = baseline::save(output, &baseline) {
+                    eprintln!("{e}");
+                    std::process::exit(2);
+                }
+                (serde_json::to_string_pretty(&baseline), 0)
+            }
+            BaselineCommand::Check {
+                scenario,
+                baseline,
+                threshold,
+            } => {
+                let baseline = match baseline::load(baseline) {
+                    Ok(baseline) => baseline,
                     Err(e) => {
-                        send_error(format!("Failed to parse LedgerEntry XDR: {}", e));
-                        return;
+                        eprintln!("{e}");
+                        std::process::exit(2);
                     }
-                },
-                Err(e) => {
-                    send_error(format!("Failed to decode LedgerEntry Base64: {}", e));
-                    return;
-                }
-            };
-
-            // TODO: Inject into host storage.
-            // For MVP, we verify we can parse them.
-            eprintln!("Parsed Ledger Entry: Key={:?}, Entry={:?}", _key, _entry);
-            loaded_entries_count += 1;
+                };
+                let outcome = run_scenario(scenario, &memory_limits, &settings);
+                let check = baseline::check(&baseline, &outcome, *threshold);
+                (serde_json::to_string_pretty(&check), i32::from(!check.passed))
+            }
+        };
+        match json {
+            Ok(json) => println!("{json}"),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(2);
+            }
         }
-    } else {
-        snapshot::LedgerSnapshot::new()
-    };
+        std::process::exit(code);
+    }
+    if let Some(Command::Bench {
+        snapshot: Some(path),
+        iterations,
+        ..
+    }) = &cli.command
+    {
+        let report = snapshot::file::SnapshotFile::load(path)
+            .and_then(|file| bench::snapshot(&file.entries, *iterations))
+            .map_err(|e| e.to_string())
+            .and_then(|report| serde_json::to_string_pretty(&report).map_err(|e| e.to_string()));
+        match report {
+            Ok(json) => println!("{json}"),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(2);
+            }
+        }
+        return;
+    }
+    if let Some(Command::Bench {
+        request: Some(request),
+        iterations,
+        jobs,
+        ..
+    }) = &cli.command
+    {
+        let parsed = std::fs::File::open(request)
+            .map_err(|e| format!("Failed to read {}: {e}", request.display()))
+            .and_then(|file| {
+                streaming::parse_json(io::BufReader::new(file), memory_limits.max_request_bytes)
+                    .map_err(|e| e.to_string())
+            });
+        let mut parsed = match parsed {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(2);
+            }
+        };
+        settings.network.apply(&mut parsed.request);
+        let remote = remote_source(&parsed.request, &settings);
+        let report = bench::run(&parsed.request, &memory_limits, remote, *iterations, *jobs);
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{json}"),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(2);
+            }
+        }
+        return;
+    }
+    if let Some(Command::Schema { kind }) = cli.command {
+        let schema = match kind {
+            SchemaKind::Request => schema::request_schema(),
+            SchemaKind::Response => schema::response_schema(),
+        };
+        println!("{schema:#}");
+        return;
+    }
 
-    let loaded_entries_count = snapshot.len();
+    // 2. Log that we started
+    tracing::info!(event = "simulator_started", "Simulator initializing...");
 
-    // Extract Operations and Simulate
-    let operations = match &envelope {
-        soroban_env_host::xdr::TransactionEnvelope::Tx(tx_v1) => &tx_v1.tx.operations,
-        soroban_env_host::xdr::TransactionEnvelope::TxV0(tx_v0) => &tx_v0.tx.operations,
-        soroban_env_host::xdr::TransactionEnvelope::TxFeeBump(bump) => match &bump.tx.inner_tx {
-            soroban_env_host::xdr::FeeBumpTransactionInnerTx::Tx(tx_v1) => &tx_v1.tx.operations,
-        },
+    let output = Output {
+        format: cli.output_format.unwrap_or(cli.input_format),
+        compression: cli.output_compression,
     };
-
-    // Wrap the operation execution in panic protection
-    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-        execute_operations(&host, operations)
-    }));
-
-    // Budget and Reporting
-    let budget = host.budget_cloned();
-    let cpu_insns = budget.get_cpu_insns_consumed().unwrap_or(0);
-    let mem_bytes = budget.get_mem_bytes_consumed().unwrap_or(0);
-
-    let cpu_usage_percent = (cpu_insns as f64 / CPU_LIMIT as f64) * 100.0;
-    let memory_usage_percent = (mem_bytes as f64 / MEMORY_LIMIT as f64) * 100.0;
-
-    let budget_usage = BudgetUsage {
-        cpu_instructions: cpu_insns,
-        memory_bytes: mem_bytes,
-        operations_count: operations.len(),
-        cpu_limit: CPU_LIMIT,
-        memory_limit: MEMORY_LIMIT,
-        cpu_usage_percent,
-        memory_usage_percent,
+    let report = match cli.report.as_deref() {
+        Some([format, path]) => {
+            match <report::Format as clap::ValueEnum>::from_str(format, true) {
+                Ok(format) => Some((format, std::path::PathBuf::from(path))),
+                Err(_) => {
+                    eprintln!("Unknown report format {format:?}; expected md or html");
+                    std::process::exit(2);
+                }
+            }
+        }
+        _ => None,
     };
-
-    let optimization_report = if request.enable_optimization_advisor {
-        let advisor = GasOptimizationAdvisor::new();
-        let metrics = BudgetMetrics {
-            cpu_instructions: budget_usage.cpu_instructions,
-            memory_bytes: budget_usage.memory_bytes,
-            total_operations: budget_usage.operations_count,
-        };
-        Some(advisor.analyze(&metrics))
-    } else {
-        None
+    let graph = match cli.graph.as_deref() {
+        Some([format, path]) => match <graph::Format as clap::ValueEnum>::from_str(format, true) {
+            Ok(format) => Some((format, std::path::PathBuf::from(path))),
+            Err(_) => {
+                eprintln!("Unknown graph format {format:?}; expected dot or mermaid");
+                std::process::exit(2);
+            }
+        },
+        _ => None,
     };
 
-    let mut flamegraph_svg = None;
-    if request.profile.unwrap_or(false) {
-        // Simple simulated flamegraph for demonstration
-        let folded_data = format!("Total;CPU {}\nTotal;Memory {}\n", cpu_insns, mem_bytes);
-        let mut result_vec = Vec::new();
-        let mut options = inferno::flamegraph::Options::default();
-        options.title = "Soroban Resource Consumption".to_string();
-
-        if let Err(e) =
-            inferno::flamegraph::from_reader(&mut options, folded_data.as_bytes(), &mut result_vec)
-        {
-            eprintln!("Failed to generate flamegraph: {e}");
-        } else {
-            flamegraph_svg = Some(String::from_utf8_lossy(&result_vec).to_string());
+    if cli.check {
+        let document = compression::sniffing_decoder(io::stdin().lock())
+            .map_err(|e| e.to_string())
+            .and_then(|stdin| {
+                memory::read_to_end_limited(stdin, memory_limits.max_request_bytes)
+                    .map_err(|e| e.to_string())
+            })
+            .and_then(|buffer| codec::decode(cli.input_format, &buffer));
+        let report = match document {
+            Ok(document) => check::check(&document),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(2);
+            }
+        };
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{json}"),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(2);
+            }
         }
+        std::process::exit(i32::from(!report.valid));
     }
 
-    match result {
-        Ok(Ok(exec_logs)) => {
-            // Extract both raw event strings and structured diagnostic events
-            let (events, diagnostic_events): (Vec<String>, Vec<DiagnosticEvent>) =
-                match host.get_events() {
-                    Ok(evs) => {
-                        let raw_events: Vec<String> =
-                            (evs.0).iter().map(|e| format!("{:?}", e)).collect();
-                        let diag_events: Vec<DiagnosticEvent> = (evs.0)
-                            .iter()
-                            .map(|event| {
-                                let event_type = match &event.event.type_ {
-                                    soroban_env_host::xdr::ContractEventType::Contract => {
-                                        "contract".to_string()
-                                    }
-                                    soroban_env_host::xdr::ContractEventType::System => {
-                                        "system".to_string()
-                                    }
-                                    soroban_env_host::xdr::ContractEventType::Diagnostic => {
-                                        "diagnostic".to_string()
-                                    }
-                                };
-
-                                let contract_id = event
-                                    .event
-                                    .contract_id
-                                    .as_ref()
-                                    .map(|contract_id| format!("{:?}", contract_id));
-
-                                let (topics, data) = match &event.event.body {
-                                    soroban_env_host::xdr::ContractEventBody::V0(v0) => {
-                                        let topics: Vec<String> =
-                                            v0.topics.iter().map(|t| format!("{:?}", t)).collect();
-                                        let data = format!("{:?}", v0.data);
-                                        (topics, data)
-                                    }
-                                };
+    // Read stdin, refusing payloads above the request ceiling. JSON is
+    // parsed as it streams in; the binary formats are compact enough to
+    // buffer.
+    let limit = memory_limits.max_request_bytes;
+    let parse_started = Instant::now();
+    let parsed = compression::sniffing_decoder(io::stdin().lock())
+        .map_err(|e| ParseError::Memory(memory::MemoryError::Io(e.to_string())))
+        .and_then(|stdin| match cli.input_format {
+            codec::Format::Json => streaming::parse_json(stdin, limit)
+                .map(|parsed| (parsed.request, parsed.fields, parsed.reservation)),
+            format => read_binary_request(stdin, format, limit),
+        });
+    let (mut request, fields, _request_reservation) = match parsed {
+        Ok(parsed) => parsed,
+        Err(ParseError::Memory(e)) => {
+            eprintln!("{e}");
+            send_error(e.to_string(), output);
+            return;
+        }
+        Err(e) => {
+            print_response(&simulation::error_response(e.to_string()), output);
+            return;
+        }
+    };
+    let parse_elapsed = parse_started.elapsed();
 
-                                let wasm_instruction = extract_wasm_instruction(&topics, &data);
-                                DiagnosticEvent {
-                                    event_type,
-                                    contract_id,
-                                    topics,
-                                    data,
-                                    in_successful_contract_call: !event.failed_call,
-                                    wasm_instruction,
-                                }
-                            })
-                        .collect();
-                    (raw_events, diag_events)
+    settings.network.apply(&mut request);
+    if cli.export_state.is_some() {
+        request.record_reads = true;
+    }
+    let remote = remote_source(&request, &settings);
+    if let Some(runs) = cli.check_determinism {
+        let report = determinism::check(&request, &memory_limits, remote, runs);
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{json}"),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(2);
+            }
+        }
+        std::process::exit(i32::from(!report.deterministic));
+    }
+    let write_bundle = |response: &SimulationResponse| {
+        if let Some(path) = &cli.bundle {
+            if let Err(e) = bundle::write(path, &bundle::bundle(&fields, &request, response)) {
+                eprintln!("{e}");
+            }
+        }
+    };
+    match simulation::run(&request, &memory_limits, None, remote, None) {
+        Ok(mut response) => {
+            simulation::add_parse_time(&mut response, parse_elapsed);
+            if response.status != "success" {
+                write_bundle(&response);
+            }
+            if let Some(path) = &cli.export_state {
+                let written = export::state(&request, &response, cli.export_scope)
+                    .and_then(|state| state.save(path).map_err(|e| e.to_string()));
+                if let Err(e) = written {
+                    eprintln!("{e}");
                 }
-                Err(_) => (
-                    vec!["Failed to retrieve events".to_string()],
-                    Vec::<DiagnosticEvent>::new(),
-                ),
-            };
-
-            // Capture categorized events for analyzer
-            let categorized_events = match host.get_events() {
-                Ok(evs) => categorize_events(&evs),
-                Err(_) => vec![],
-            };
-
-            let mut final_logs = vec![
-                format!("Host Initialized with Budget: {:?}", budget),
-                format!("Loaded {} Ledger Entries", loaded_entries_count),
-                format!("Captured {} diagnostic events", diagnostic_events.len()),
-                format!("CPU Instructions Used: {}", cpu_insns),
-                format!("Memory Bytes Used: {}", mem_bytes),
-            ];
-            final_logs.extend(exec_logs);
-
-            if let Some(required_fee) = mocked_required_fee_stroops(
-                &request,
-                operations.as_slice().len(),
-                cpu_insns,
-                mem_bytes,
-            ) {
-                let declared_fee = transaction_fee_stroops(&envelope);
-                final_logs.push(format!(
-                    "Mock fee check: declared={} required={}",
-                    declared_fee, required_fee
-                ));
-
-                if declared_fee < required_fee {
-                    let response = SimulationResponse {
-                        status: "error".to_string(),
-                        error: Some(format!(
-                            "insufficient fee (mocked): declared {} stroops, required {} stroops",
-                            declared_fee, required_fee
-                        )),
-                        events,
-                        diagnostic_events,
-                        categorized_events,
-                        logs: final_logs,
-                        flamegraph: flamegraph_svg,
-                        optimization_report,
-                        budget_usage: Some(budget_usage),
-                        source_location: None,
-                    };
-
-                    if let Ok(json) = serde_json::to_string(&response) {
-                        println!("{}", json);
-                    } else {
-                        eprintln!("Failed to serialize simulation response");
-                        println!("{{\"status\": \"error\", \"error\": \"Internal serialization error\"}}");
-                    }
-                    return;
+            }
+            if let Some((format, path)) = &report {
+                if let Err(e) = report::write(*format, path, &request, &response) {
+                    eprintln!("{e}");
                 }
             }
-
-            let response = SimulationResponse {
-                status: "success".to_string(),
-                error: None,
-                events,
-                diagnostic_events,
-                categorized_events,
-                logs: final_logs,
-                flamegraph: flamegraph_svg,
-                optimization_report,
-                budget_usage: Some(budget_usage),
-                source_location: None,
-                stack_trace: None,
-                // If a WASM with debug symbols was provided, expose the first
-                // mappable source location so callers can correlate failures.
-                source_location: source_mapper
-                    .as_ref()
-                    .and_then(|m| m.map_wasm_offset_to_source(0))
-                    .and_then(|loc| serde_json::to_string(&loc).ok()),
-            };
-
-            if let Ok(json) = serde_json::to_string(&response) {
-                println!("{}", json);
-            } else {
-                eprintln!("Failed to serialize simulation response");
-                println!("{{\"status\": \"error\", \"error\": \"Internal serialization error\"}}");
+            if let Some((format, path)) = &graph {
+                if let Err(e) = graph::write(*format, path, &response.call_tree) {
+                    eprintln!("{e}");
+                }
+            }
+            if let Some(path) = &cli.sarif {
+                let findings = sarif::findings(&request, &response);
+                if let Err(e) = sarif::write(path, &findings, None) {
+                    eprintln!("{e}");
+                }
+            }
+            match cli.output_shape {
+                replay::OutputShape::GetTransaction => {
+                    match replay::get_transaction(&response, &request) {
+                        Some(shaped) => print_response(&shaped, output),
+                        None => print_response(&response, output),
+                    }
+                }
+                replay::OutputShape::Simulation => print_response(&response, output),
             }
         }
-        Ok(Err(host_error)) => {
-            // Host error during execution (e.g., contract trap, validation failure)
-            let error_msg = format!("{:?}", host_error);
-            let decoded_msg = decode_error(&error_msg);
-            
-            let structured_error = StructuredError {
-                error_type: "HostError".to_string(),
-                message: decoded_msg.clone(),
-                details: Some(format!(
-                    "Contract execution failed with host error: {}",
-                    decoded_msg
-                )),
-            let error_debug = format!("{:?}", host_error);
-            let wasm_trace = WasmStackTrace::from_host_error(&error_debug);
-
-            let structured_error = StructuredError {
-                error_type: "HostError".to_string(),
-                message: error_debug.clone(),
-                details: Some(format!(
-                    "Contract execution failed with host error: {}",
-                    error_debug
-                )),
-            };
+        Err(e) => {
+            write_bundle(&simulation::error_response(e.to_string()));
+            send_error(e.to_string(), output);
+        }
+    }
+}
 
-            let trace_display = wasm_trace.display();
+/// Runs the scenario in `file`, exiting with 2 if it cannot be read.
+fn run_scenario(
+    file: &std::path::Path,
+    limits: &memory::MemoryLimits,
+    settings: &ConfigFile,
+) -> scenario::ScenarioOutcome {
+    let scenario = match scenario::load(file) {
+        Ok(scenario) => scenario,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+    };
+    let default_name = file
+        .file_stem()
+        .map_or_else(|| "scenario".into(), |stem| stem.to_string_lossy());
+    scenario::run(&scenario, &default_name, limits, |request| {
+        remote_source(request, settings)
+    })
+}
 
-            // Extract both raw event strings and structured diagnostic events
-            let (events, diagnostic_events): (Vec<String>, Vec<DiagnosticEvent>) =
-                match host.get_events() {
-                    Ok(evs) => {
-                        let raw_events: Vec<String> =
-                            (evs.0).iter().map(|e| format!("{:?}", e)).collect();
-                        let diag_events: Vec<DiagnosticEvent> = (evs.0)
-                            .iter()
-                            .map(|event| {
-                                let event_type = match &event.event.type_ {
-                                    soroban_env_host::xdr::ContractEventType::Contract => {
-                                        "contract".to_string()
-                                    }
-                                    soroban_env_host::xdr::ContractEventType::System => {
-                                        "system".to_string()
-                                    }
-                                    soroban_env_host::xdr::ContractEventType::Diagnostic => {
-                                        "diagnostic".to_string()
-                                    }
-                                };
+/// Simulates `request` and returns a soroban-sdk test making its call,
+/// after writing the entries it read to `snapshot`.
+fn rust_test(
+    request: &std::path::Path,
+    name: Option<&str>,
+    snapshot: Option<&std::path::Path>,
+    limits: &memory::MemoryLimits,
+    settings: &ConfigFile,
+) -> Result<String, String> {
+    let mut request = codegen_request(request, limits, settings)?;
+    request.record_reads = true;
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => codegen::rust::default_name(&request)?,
+    };
+    let snapshot = snapshot.map_or_else(
+        || std::path::PathBuf::from(format!("test_snapshots/erst/{name}.json")),
+        std::path::Path::to_path_buf,
+    );
 
-                                let contract_id = event
-                                    .event
-                                    .contract_id
-                                    .as_ref()
-                                    .map(|contract_id| format!("{:?}", contract_id));
+    let remote = remote_source(&request, settings);
+    let response =
+        simulation::run(&request, limits, None, remote, None).map_err(|e| e.to_string())?;
+    let code = codegen::rust::rust_test(&request, &response, &name, &snapshot.to_string_lossy())?;
+    if let Some(dir) = snapshot.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+    }
+    std::fs::write(&snapshot, codegen::rust::snapshot(&request, &response)?)
+        .map_err(|e| format!("Failed to write {}: {e}", snapshot.display()))?;
+    eprintln!("Wrote the ledger entries to {}", snapshot.display());
+    Ok(code)
+}
 
-                                let (topics, data) = match &event.event.body {
-                                    soroban_env_host::xdr::ContractEventBody::V0(v0) => {
-                                        let topics: Vec<String> =
-                                            v0.topics.iter().map(|t| format!("{:?}", t)).collect();
-                                        let data = format!("{:?}", v0.data);
-                                        (topics, data)
-                                    }
-                                };
+/// Simulates `request`, assembled for its fee, and returns a TypeScript
+/// fixture of what it did.
+fn ts_fixture(
+    request: &std::path::Path,
+    name: Option<&str>,
+    limits: &memory::MemoryLimits,
+    settings: &ConfigFile,
+) -> Result<String, String> {
+    let mut request = codegen_request(request, limits, settings)?;
+    request.assemble = true;
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => codegen::typescript::default_name(&request)?,
+    };
+    let remote = remote_source(&request, settings);
+    let response =
+        simulation::run(&request, limits, None, remote, None).map_err(|e| e.to_string())?;
+    codegen::typescript::fixture(&request, &response, &name)
+}
 
-                                DiagnosticEvent {
-                                    event_type,
-                                    contract_id,
-                                    topics,
-                                    data,
-                                    in_successful_contract_call: event.failed_call,
-                                }
-                            })
-                            .collect();
-                        (raw_events, diag_events)
-                    }
-                    Err(_) => (
-                        vec!["Failed to retrieve events".to_string()],
-                        Vec::<DiagnosticEvent>::new(),
-                    ),
-                };
+/// The request in `path`, on the configured network.
+fn codegen_request(
+    path: &std::path::Path,
+    limits: &memory::MemoryLimits,
+    settings: &ConfigFile,
+) -> Result<SimulationRequest, String> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let mut parsed = streaming::parse_json(io::BufReader::new(file), limits.max_request_bytes)
+        .map_err(|e| e.to_string())?;
+    settings.network.apply(&mut parsed.request);
+    Ok(parsed.request)
+}
 
-            // Capture categorized events for analyzer
-            let categorized_events = match host.get_events() {
-                Ok(evs) => categorize_events(&evs),
-                Err(_) => vec![],
-            };
+/// Writes a fork of the network behind `rpc_url`, or the configured
+/// network, to `path`.
+fn create_fork(
+    path: &std::path::Path,
+    rpc_url: Option<&str>,
+    at_ledger: Option<u32>,
+    network: &Option<String>,
+    settings: &ConfigFile,
+) -> Result<(), String> {
+    if path.exists() {
+        return Err(format!(
+            "{} already exists; remove it to fork again",
+            path.display()
+        ));
+    }
+    let network = network.clone().or_else(|| settings.network.name.clone());
+    let rpc_url = rpc_url
+        .map(str::to_string)
+        .or_else(|| settings.network.rpc_url.clone())
+        .or_else(|| {
+            network
+                .as_deref()
+                .and_then(network::preset)
+                .map(|preset| preset.rpc_url.to_string())
+        })
+        .ok_or("No RPC endpoint: pass --rpc-url or a named network")?;
+    let fork = fork::Fork::create(&rpc_url, network, at_ledger, fork::rpc(&rpc_url).as_ref())?;
+    fork.save(path)?;
+    eprintln!(
+        "Forked {rpc_url} at ledger {} into {}",
+        fork.info.at_ledger,
+        path.display()
+    );
+    Ok(())
+}
 
-            // Heuristic to ignore Rust stdlib panic wrappers and find the actual source point
-            let mut user_panic_point = None;
-            for event in &diagnostic_events {
-                let mut combined_text = event.data.clone();
-                for topic in &event.topics {
-                    combined_text.push_str(" ");
-                    combined_text.push_str(topic);
-                }
+/// Simulates the request in `request`, or on stdin, on the fork in `path`,
+/// saving what it fetched and, with `commit`, the changes it made.
+fn simulate_on_fork(
+    path: &std::path::Path,
+    request: Option<&std::path::Path>,
+    commit: bool,
+    limits: &memory::MemoryLimits,
+) -> Result<SimulationResponse, String> {
+    let mut fork = fork::Fork::load(path)?;
+    let parsed = match request {
+        Some(request) => std::fs::File::open(request)
+            .map_err(|e| format!("Failed to read {}: {e}", request.display()))
+            .and_then(|file| {
+                streaming::parse_json(io::BufReader::new(file), limits.max_request_bytes)
+                    .map_err(|e| e.to_string())
+            }),
+        None => streaming::parse_json(io::stdin().lock(), limits.max_request_bytes)
+            .map_err(|e| e.to_string()),
+    }?;
+    let network = fork::rpc(&fork.info.rpc_url);
+    let response = fork.simulate(&parsed.request, limits, network, commit);
+    // Entries fetched before a failure are worth keeping too.
+    fork.save(path)?;
+    response
+}
 
-                if combined_text.contains("panicked")
-                    || combined_text.contains("Error")
-                    || combined_text.contains("Trap")
-                {
-                    // Ignore known Rust stdlib wrappers commonly seen in Backtrace/Diagnostic events
-                    if combined_text.contains("core/src/panicking.rs")
-                        || combined_text.contains("core::panicking")
-                        || combined_text.contains("rust_begin_unwind")
-                        || combined_text.contains("std::rt::lang_start")
-                        || combined_text.contains("compiler_builtins")
-                        || combined_text.contains("rustc_std_workspace")
-                    {
-                        continue;
-                    }
+/// Runs `file` with `wasm`, if any, installed as the code of `contracts`.
+fn watch_scenario(
+    file: &std::path::Path,
+    wasm: Option<&std::path::Path>,
+    contracts: &[String],
+    limits: &memory::MemoryLimits,
+    settings: &ConfigFile,
+) -> Result<scenario::ScenarioOutcome, String> {
+    let mut scenario = scenario::load(file)?;
+    if let Some(path) = wasm {
+        let code =
+            std::fs::read(path).map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        watch::install(&mut scenario.ledger_entries, &code, contracts)?;
+    }
+    let default_name = file
+        .file_stem()
+        .map_or_else(|| "scenario".into(), |stem| stem.to_string_lossy());
+    Ok(scenario::run(&scenario, &default_name, limits, |request| {
+        remote_source(request, settings)
+    }))
+}
 
-                    // Look for common user paths (like src/lib.rs, etc)
-                    if combined_text.contains(".rs") && !combined_text.contains("soroban-env-host")
-                    {
-                        user_panic_point = Some(combined_text.replace("\"", ""));
-                        // Break after finding the first valid panic point so we don't overwrite it with deeper arbitrary ones
-                        break;
-                    }
-                }
-            }
+/// Parses the command line again with the `[server]` settings of `settings`
+/// as the defaults of `serve`'s flags, so flags given on the command line
+/// override them.
+fn parse_with_serve_defaults(settings: &ConfigFile) -> Result<Cli, String> {
+    Cli::from_arg_matches(&serve_command(settings)?.get_matches()).map_err(|e| e.to_string())
+}
 
-            let details = if let Some(ref point) = user_panic_point {
-                format!(
-                    "Contract execution failed with host error: {:?}. Panic point: {}",
-                    host_error, point
-                )
-            } else {
+/// The command line definition, with the `[server]` settings of `settings`
+/// as the defaults of `serve`'s flags.
+fn serve_command(settings: &ConfigFile) -> Result<clap::Command, String> {
+    let command = Cli::command();
+    let Some(serve) = command.find_subcommand("serve") else {
+        return Ok(command);
+    };
+    let mut defaults = Vec::new();
+    for (flag, values) in settings.serve_defaults()? {
+        let id = serve
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(flag.as_str()))
+            .map(|arg| arg.get_id().clone())
+            .ok_or_else(|| {
                 format!(
-                    "Contract execution failed with host error: {:?}",
-                    host_error
+                    "[server] {flag} or {}{}: not a flag of serve",
+                    config::file::ENV_PREFIX,
+                    flag.replace('-', "_").to_uppercase()
                 )
-            };
-
-            let structured_error = StructuredError {
-                error_type: "HostError".to_string(),
-                message: format!("{:?}", host_error),
-                details: Some(details),
-            };
-
-            let error_msg = format!("{:?}", host_error);
-            let wasm_offset = extract_wasm_offset(&error_msg);
-            
-            let source_location = if let (Some(offset), Some(mapper)) = (wasm_offset, &source_mapper) {
-                mapper.map_wasm_offset_to_source(offset)
-            } else {
-                None
-            };
-
-            let error_msg = format!("{:?}", host_error);
-            let wasm_offset = extract_wasm_offset(&error_msg);
+            })?;
+        defaults.push((id, values));
+    }
+    Ok(command.mut_subcommand("serve", |serve| {
+        defaults.into_iter().fold(serve, |serve, (id, values)| {
+            serve.mut_arg(id, |arg| arg.default_values(values))
+        })
+    }))
+}
 
-            let response = SimulationResponse {
-                status: "error".to_string(),
-                error: serde_json::to_string(&structured_error).unwrap_or_else(|e| {
-                    eprintln!("Failed to serialize structured error: {}", e);
-                    format!("Internal error during error serialization: {}", e)
-                }),
-                events: vec![],
-                diagnostic_events: vec![],
-                categorized_events: vec![],
-                logs: vec![format!("Stack trace:\n{}", trace_display)],
-                flamegraph: None,
-                optimization_report: None,
-                budget_usage: None,
-                source_location: None,
-                stack_trace: Some(wasm_trace),
-                wasm_offset,
+/// The settings in effect under `settings` and `limits`, every `serve` flag
+/// with a value included.
+fn effective_settings(
+    settings: &ConfigFile,
+    limits: &memory::MemoryLimits,
+) -> Result<ConfigFile, String> {
+    let matches = serve_command(settings)?
+        .try_get_matches_from(["erst-sim", "serve"])
+        .map_err(|e| e.to_string())?;
+    let mut server = toml::Table::new();
+    if let Some(("serve", serve)) = matches.subcommand() {
+        for id in serve.ids().filter(|id| id.as_str() != "config") {
+            let Some(values) = serve.get_raw(id.as_str()) else {
+                continue;
             };
-            if let Ok(json) = serde_json::to_string(&response) {
-                println!("{}", json);
+            let mut values = values
+                .map(|value| toml::Value::from(value.to_string_lossy().into_owned()))
+                .collect::<Vec<_>>();
+            let value = if values.len() == 1 {
+                values.remove(0)
             } else {
-                eprintln!("Failed to serialize host error response");
-                println!("{{\"status\": \"error\", \"error\": \"Internal serialization error\"}}");
-            }
-        }
-        Err(panic_info) => {
-            let panic_msg = if let Some(s) = panic_info.downcast_ref::<&str>() {
-                s.to_string()
-            } else if let Some(s) = panic_info.downcast_ref::<String>() {
-                s.clone()
-            } else {
-                "Unknown panic".to_string()
+                toml::Value::Array(values)
             };
-
-            let wasm_trace = WasmStackTrace::from_panic(&panic_msg);
-
-            let response = SimulationResponse {
-                status: "error".to_string(),
-                error: Some(format!("Simulator panicked: {panic_msg}")),
-                events: vec![],
-                diagnostic_events: vec![],
-                categorized_events: vec![],
-                logs: vec![format!("PANIC: {}", panic_msg)],
-                flamegraph: None,
-                optimization_report: None,
-                budget_usage: None,
-                source_location: None,
-                stack_trace: Some(wasm_trace),
-                wasm_offset: None,
-            };
-            if let Ok(json) = serde_json::to_string(&response) {
-                println!("{}", json);
-            } else {
-                eprintln!("Failed to serialize panic response");
-                println!("{{\"status\": \"error\", \"error\": \"Internal serialization error\"}}");
-            }
+            server.insert(id.as_str().to_string(), value);
         }
     }
+    Ok(ConfigFile {
+        network: settings.network.clone(),
+        limits: (*limits).into(),
+        cache: config::file::CacheConfig {
+            entry_cache_size: Some(cache::capacity_from_env(
+                settings
+                    .cache
+                    .entry_cache_size
+                    .unwrap_or(cache::DEFAULT_CACHE_ENTRIES),
+            )),
+            entry_cache_dir: cache::dir_from_env(settings.cache.entry_cache_dir.clone()),
+        },
+        server,
+    })
 }
 
-fn extract_wasm_offset(error_msg: &str) -> Option<u64> {
-    // Look for patterns like "@ 0x[HEX]" in the error message
-    // Soroban/Wasmi errors often contain stack traces like:
-    // "  0: func[42] @ 0xa3c"
-    
-    for line in error_msg.lines() {
-        if let Some(pos) = line.find("@ 0x") {
-            let hex_part = &line[pos + 4..];
-            let end = hex_part.find(|c: char| !c.is_ascii_hexdigit()).unwrap_or(hex_part.len());
-            if let Ok(offset) = u64::from_str_radix(&hex_part[..end], 16) {
-                return Some(offset);
-            }
-        }
+/// The cached RPC source `request` reads missing state from, if any.
+fn remote_source(
+    request: &SimulationRequest,
+    settings: &ConfigFile,
+) -> Option<std::sync::Arc<cache::CachedSource>> {
+    let cache_dir = cache::dir_from_env(settings.cache.entry_cache_dir.clone());
+    // A named network supplies its public RPC endpoint unless one is given.
+    let rpc_url = request.rpc_url.as_deref().or_else(|| {
+        request
+            .network
+            .as_deref()
+            .and_then(network::preset)
+            .map(|preset| preset.rpc_url)
+    });
+    rpc_url.map(|url| {
+        source::rpc_cache(
+            url,
+            request.horizon_url.as_deref(),
+            cache::capacity_from_env(
+                settings
+                    .cache
+                    .entry_cache_size
+                    .unwrap_or(cache::DEFAULT_CACHE_ENTRIES),
+            ),
+            cache_dir.as_deref(),
+            RetryPolicy::from_env(),
+        )
+    })
+}
+
+/// Reads a CBOR or MessagePack request and validates it like JSON. Returns
+/// the request's fields but `ledger_entries` alongside, like
+/// [`streaming::parse_json`].
+fn read_binary_request(
+    reader: impl io::Read,
+    format: codec::Format,
+    limit: usize,
+) -> Result<(SimulationRequest, serde_json::Value, memory::MemoryReservation<'static>), ParseError>
+{
+    let buffer = memory::read_to_end_limited(reader, limit).map_err(ParseError::Memory)?;
+    let reservation = memory::global_accountant()
+        .reserve(buffer.len())
+        .map_err(ParseError::Memory)?;
+    let value = codec::decode(format, &buffer).map_err(ParseError::Syntax)?;
+    if let Err(errors) = schema::validate_request(&value) {
+        return Err(ParseError::Invalid(schema::describe(&errors)));
+    }
+    let request = serde::Deserialize::deserialize(&value)
+        .map_err(|e: serde_json::Error| ParseError::Invalid(e.to_string()))?;
+    let mut fields = value;
+    if let serde_json::Value::Object(fields) = &mut fields {
+        fields.remove("ledger_entries");
     }
+    Ok((request, fields, reservation))
 }
 
 /// Translate a raw soroban / WASM error string into a user-friendly description.
@@ -964,7 +1486,7 @@ mod tests {
     fn test_decode_unreachable() {
         let msg = decode_error("wasm trap: unreachable");
         assert!(msg.contains("VM Trap: Unreachable"));
-    fn test_enforce_soroban_compatibility_rejects_floats() {
+    fn test_validate_rejects_floats() {
         let wat = r#"
             (module
                 (func (export "f") (result f32)
@@ -974,95 +1496,10 @@ mod tests {
         "#;
 
         let wasm = wat::parse_str(wat).expect("failed to compile WAT");
-        let result = vm::enforce_soroban_compatibility(&wasm);
-        assert!(result.is_err());
-    }
-
-    // ── Protocol-21 host-trait correctness ─────────────────────────────────
-
-    /// `HostEvent.failed_call == true` means the call that emitted the event
-    /// *failed*.  `in_successful_contract_call` must therefore be the inverse.
-    /// This was silently backwards before the protocol-21 fix.
-    #[test]
-    fn test_in_successful_contract_call_is_negation_of_failed_call() {
-        use soroban_env_host::events::{Events, HostEvent};
-        use soroban_env_host::xdr::{
-            ContractEvent, ContractEventBody, ContractEventType, ContractEventV0,
-            ExtensionPoint, VecM,
-        };
-
-        let make_event = |failed: bool| -> HostEvent {
-            HostEvent {
-                failed_call: failed,
-                event: ContractEvent {
-                    ext: ExtensionPoint::V0,
-                    contract_id: None,
-                    type_: ContractEventType::Diagnostic,
-                    body: ContractEventBody::V0(ContractEventV0 {
-                        topics: VecM::default(),
-                        data: soroban_env_host::xdr::ScVal::Void,
-                    }),
-                },
-            }
-        };
-
-        // failed_call = true  →  in_successful_contract_call must be false
-        let evs_failed = Events(vec![make_event(true)]);
-        let categorized = categorize_events(&evs_failed);
-        assert_eq!(categorized.len(), 1);
-        assert!(
-            !categorized[0].event.in_successful_contract_call,
-            "a failed call should NOT be marked as a successful contract call"
-        );
-
-        // failed_call = false  →  in_successful_contract_call must be true
-        let evs_ok = Events(vec![make_event(false)]);
-        let categorized = categorize_events(&evs_ok);
-        assert_eq!(categorized.len(), 1);
-        assert!(
-            categorized[0].event.in_successful_contract_call,
-            "a successful call MUST be marked as a successful contract call"
-        );
-    }
-
-    /// categorize_events must correctly map ContractEventType variants to their
-    /// lowercase string representations.
-    #[test]
-    fn test_categorize_events_type_labels() {
-        use soroban_env_host::events::{Events, HostEvent};
-        use soroban_env_host::xdr::{
-            ContractEvent, ContractEventBody, ContractEventType, ContractEventV0,
-            ExtensionPoint, VecM,
-        };
-
-        let make_typed_event = |t: ContractEventType| HostEvent {
-            failed_call: false,
-            event: ContractEvent {
-                ext: ExtensionPoint::V0,
-                contract_id: None,
-                type_: t,
-                body: ContractEventBody::V0(ContractEventV0 {
-                    topics: VecM::default(),
-                    data: soroban_env_host::xdr::ScVal::Void,
-                }),
-            },
-        };
-
-        let evs = Events(vec![
-            make_typed_event(ContractEventType::Contract),
-            make_typed_event(ContractEventType::System),
-            make_typed_event(ContractEventType::Diagnostic),
-        ]);
-
-        let cats = categorize_events(&evs);
-        assert_eq!(cats[0].category, "Contract");
-        assert_eq!(cats[1].category, "System");
-        assert_eq!(cats[2].category, "Diagnostic");
-
-        // DiagnosticEvent.event_type should be lowercase
-        assert_eq!(cats[0].event.event_type, "contract");
-        assert_eq!(cats[1].event.event_type, "system");
-        assert_eq!(cats[2].event.event_type, "diagnostic");
+        let violations = vm::validate(&wasm, 25);
+        assert!(violations
+            .iter()
+            .any(|v| v.reason.contains("floating-point instruction F32Const")));
     }
 
     /// SourceMapper without debug symbols must return None for source locations,
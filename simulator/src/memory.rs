@@ -0,0 +1,377 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Memory ceilings for simulation requests.
+//!
+//! A single oversized request (for example a multi-gigabyte `ledger_entries`
+//! map) must not be able to exhaust the memory of a shared simulator. This
+//! module provides:
+//! - Per-request ceilings on the raw request size, the decoded snapshot size
+//!   and the memory consumed by the host during execution
+//! - A process-wide accountant that tracks how much memory all in-flight
+//!   requests have reserved and rejects new reservations past a global budget
+//! - Per-response ceilings on the events and logs returned, past which they
+//!   are truncated (see [`crate::truncation`])
+//!
+//! Ceilings are configured through `ERST_*` environment variables and fall
+//! back to conservative defaults.
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::io::Read;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+/// Default ceiling on the raw request payload (64 MiB).
+pub const DEFAULT_MAX_REQUEST_BYTES: usize = 64 * 1024 * 1024;
+/// Default ceiling on the estimated in-memory size of a decoded snapshot (256 MiB).
+pub const DEFAULT_MAX_SNAPSHOT_BYTES: usize = 256 * 1024 * 1024;
+/// Default ceiling on host memory consumption, matching the network budget.
+pub const DEFAULT_MAX_HOST_MEMORY_BYTES: u64 = crate::gas_optimizer::MEMORY_LIMIT;
+/// Default process-wide budget shared by all in-flight requests (1 GiB).
+pub const DEFAULT_GLOBAL_MEMORY_BYTES: usize = 1024 * 1024 * 1024;
+/// Default ceiling on the events returned in one response.
+pub const DEFAULT_MAX_EVENTS: usize = 10_000;
+/// Default ceiling on the rendered size of those events (16 MiB).
+pub const DEFAULT_MAX_EVENT_BYTES: usize = 16 * 1024 * 1024;
+/// Default ceiling on the log lines returned in one response.
+pub const DEFAULT_MAX_LOGS: usize = 10_000;
+/// Default ceiling on the size of those log lines (4 MiB).
+pub const DEFAULT_MAX_LOG_BYTES: usize = 4 * 1024 * 1024;
+
+/// Decoded XDR entries are held both as raw bytes and as parsed structures,
+/// so the in-memory footprint is roughly twice the decoded payload size.
+const DECODED_OVERHEAD_FACTOR: usize = 2;
+
+/// Configured memory ceilings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryLimits {
+    /// Maximum size of the raw JSON request in bytes.
+    pub max_request_bytes: usize,
+    /// Maximum estimated size of the decoded ledger snapshot in bytes.
+    pub max_snapshot_bytes: usize,
+    /// Maximum memory the host may consume while executing, in bytes.
+    pub max_host_memory_bytes: u64,
+    /// Maximum memory all concurrent requests may reserve together, in bytes.
+    pub global_memory_bytes: usize,
+    /// Maximum number of events a response returns.
+    pub max_events: usize,
+    /// Maximum rendered size of the events a response returns, in bytes.
+    pub max_event_bytes: usize,
+    /// Maximum number of log lines a response returns.
+    pub max_logs: usize,
+    /// Maximum size of the log lines a response returns, in bytes.
+    pub max_log_bytes: usize,
+}
+
+impl Default for MemoryLimits {
+    fn default() -> Self {
+        Self {
+            max_request_bytes: DEFAULT_MAX_REQUEST_BYTES,
+            max_snapshot_bytes: DEFAULT_MAX_SNAPSHOT_BYTES,
+            max_host_memory_bytes: DEFAULT_MAX_HOST_MEMORY_BYTES,
+            global_memory_bytes: DEFAULT_GLOBAL_MEMORY_BYTES,
+            max_events: DEFAULT_MAX_EVENTS,
+            max_event_bytes: DEFAULT_MAX_EVENT_BYTES,
+            max_logs: DEFAULT_MAX_LOGS,
+            max_log_bytes: DEFAULT_MAX_LOG_BYTES,
+        }
+    }
+}
+
+impl MemoryLimits {
+    /// Reads the ceilings from the environment, falling back to the defaults
+    /// for unset or unparsable values.
+    pub fn from_env() -> Self {
+        Self::default().with_env()
+    }
+
+    /// Overrides these ceilings with those set in the environment, keeping
+    /// the current value for unset or unparsable variables.
+    ///
+    /// Recognised variables:
+    /// - `ERST_MAX_REQUEST_BYTES`
+    /// - `ERST_MAX_SNAPSHOT_BYTES`
+    /// - `ERST_MAX_HOST_MEMORY_BYTES`
+    /// - `ERST_GLOBAL_MEMORY_BYTES`
+    /// - `ERST_MAX_EVENTS`, `ERST_MAX_EVENT_BYTES`
+    /// - `ERST_MAX_LOGS`, `ERST_MAX_LOG_BYTES`
+    pub fn with_env(self) -> Self {
+        Self {
+            max_request_bytes: env_or("ERST_MAX_REQUEST_BYTES", self.max_request_bytes),
+            max_snapshot_bytes: env_or("ERST_MAX_SNAPSHOT_BYTES", self.max_snapshot_bytes),
+            max_host_memory_bytes: env_or("ERST_MAX_HOST_MEMORY_BYTES", self.max_host_memory_bytes),
+            global_memory_bytes: env_or("ERST_GLOBAL_MEMORY_BYTES", self.global_memory_bytes),
+            max_events: env_or("ERST_MAX_EVENTS", self.max_events),
+            max_event_bytes: env_or("ERST_MAX_EVENT_BYTES", self.max_event_bytes),
+            max_logs: env_or("ERST_MAX_LOGS", self.max_logs),
+            max_log_bytes: env_or("ERST_MAX_LOG_BYTES", self.max_log_bytes),
+        }
+    }
+}
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(default)
+}
+
+/// Errors raised when a request would exceed a memory ceiling.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemoryError {
+    /// The raw request payload is larger than `max_request_bytes`.
+    RequestTooLarge { limit: usize },
+    /// The decoded snapshot would be larger than `max_snapshot_bytes`.
+    SnapshotTooLarge { estimated: usize, limit: usize },
+    /// Reserving memory would push the process past its global budget.
+    GlobalBudgetExhausted {
+        requested: usize,
+        in_use: usize,
+        limit: usize,
+    },
+    /// Reading the request failed.
+    Io(String),
+}
+
+impl fmt::Display for MemoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MemoryError::RequestTooLarge { limit } => {
+                write!(f, "request exceeds memory ceiling: payload larger than {limit} bytes")
+            }
+            MemoryError::SnapshotTooLarge { estimated, limit } => write!(
+                f,
+                "snapshot exceeds memory ceiling: ~{estimated} bytes decoded (limit {limit})"
+            ),
+            MemoryError::GlobalBudgetExhausted {
+                requested,
+                in_use,
+                limit,
+            } => write!(
+                f,
+                "simulator memory budget exhausted: requested {requested} bytes with {in_use} of {limit} bytes in use"
+            ),
+            MemoryError::Io(e) => write!(f, "failed to read request: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for MemoryError {}
+
+/// Tracks memory reserved by all in-flight requests against a global budget.
+#[derive(Debug)]
+pub struct MemoryAccountant {
+    in_use: AtomicUsize,
+    limit: usize,
+}
+
+impl MemoryAccountant {
+    /// Creates an accountant with the given global budget in bytes.
+    pub const fn new(limit: usize) -> Self {
+        Self {
+            in_use: AtomicUsize::new(0),
+            limit,
+        }
+    }
+
+    /// Returns the number of bytes currently reserved.
+    pub fn in_use(&self) -> usize {
+        self.in_use.load(Ordering::Acquire)
+    }
+
+    /// Returns the global budget in bytes.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Reserves `bytes` against the global budget.
+    ///
+    /// The reservation is released when the returned guard is dropped.
+    pub fn reserve(&self, bytes: usize) -> Result<MemoryReservation<'_>, MemoryError> {
+        self.try_add(bytes)?;
+        Ok(MemoryReservation {
+            accountant: self,
+            bytes,
+        })
+    }
+
+    fn try_add(&self, bytes: usize) -> Result<(), MemoryError> {
+        self.in_use
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |current| {
+                current
+                    .checked_add(bytes)
+                    .filter(|total| *total <= self.limit)
+            })
+            .map(|_| ())
+            .map_err(|current| MemoryError::GlobalBudgetExhausted {
+                requested: bytes,
+                in_use: current,
+                limit: self.limit,
+            })
+    }
+
+    fn release(&self, bytes: usize) {
+        self.in_use.fetch_sub(bytes, Ordering::AcqRel);
+    }
+}
+
+/// RAII guard for memory reserved through a [`MemoryAccountant`].
+#[derive(Debug)]
+pub struct MemoryReservation<'a> {
+    accountant: &'a MemoryAccountant,
+    bytes: usize,
+}
+
+impl MemoryReservation<'_> {
+    /// Extends the reservation by `extra` bytes.
+    pub fn grow(&mut self, extra: usize) -> Result<(), MemoryError> {
+        self.accountant.try_add(extra)?;
+        self.bytes += extra;
+        Ok(())
+    }
+
+    /// Returns the number of bytes held by this reservation.
+    #[cfg(test)]
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+impl Drop for MemoryReservation<'_> {
+    fn drop(&mut self) {
+        self.accountant.release(self.bytes);
+    }
+}
+
+/// Returns the process-wide accountant, sized from `ERST_GLOBAL_MEMORY_BYTES`.
+pub fn global_accountant() -> &'static MemoryAccountant {
+    GLOBAL.get_or_init(|| MemoryAccountant::new(MemoryLimits::from_env().global_memory_bytes))
+}
+
+/// Sizes the process-wide accountant at `limit` bytes instead of
+/// `ERST_GLOBAL_MEMORY_BYTES`. Has no effect once the accountant is in use.
+pub fn set_global_memory_bytes(limit: usize) {
+    // Already in use: keep the budget reservations were made against.
+    let _ = GLOBAL.set(MemoryAccountant::new(limit));
+}
+
+static GLOBAL: OnceLock<MemoryAccountant> = OnceLock::new();
+
+/// Reads `reader` to the end, failing once more than `limit` bytes arrive.
+///
+/// The payload is never buffered past `limit + 1` bytes, so an oversized
+/// request is rejected without being held in memory in full.
+pub fn read_to_end_limited<R: Read>(reader: R, limit: usize) -> Result<Vec<u8>, MemoryError> {
+    let mut buffer = Vec::new();
+    reader
+        .take(limit as u64 + 1)
+        .read_to_end(&mut buffer)
+        .map_err(|e| MemoryError::Io(e.to_string()))?;
+    if buffer.len() > limit {
+        return Err(MemoryError::RequestTooLarge { limit });
+    }
+    Ok(buffer)
+}
+
+/// Estimates the in-memory size of a snapshot decoded from base64 XDR pairs.
+pub fn estimate_snapshot_bytes(entries: &HashMap<String, String>) -> usize {
+    entries
+        .iter()
+        .map(|(k, v)| decoded_base64_len(k) + decoded_base64_len(v))
+        .fold(0usize, usize::saturating_add)
+        .saturating_mul(DECODED_OVERHEAD_FACTOR)
+}
+
+/// Estimates the in-memory size of a copy of base64 XDR pairs, before
+/// decoding.
+pub fn entries_bytes(entries: &HashMap<String, String>) -> usize {
+    entries
+        .iter()
+        .map(|(k, v)| k.len() + v.len() + 2 * std::mem::size_of::<String>())
+        .fold(0usize, usize::saturating_add)
+}
+
+fn decoded_base64_len(encoded: &str) -> usize {
+    encoded.len() / 4 * 3 + 3
+}
+
+/// Checks the estimated snapshot size against `limits` and reserves it
+/// against the global budget.
+pub fn reserve_snapshot<'a>(
+    accountant: &'a MemoryAccountant,
+    limits: &MemoryLimits,
+    entries: &HashMap<String, String>,
+) -> Result<MemoryReservation<'a>, MemoryError> {
+    let estimated = estimate_snapshot_bytes(entries);
+    if estimated > limits.max_snapshot_bytes {
+        return Err(MemoryError::SnapshotTooLarge {
+            estimated,
+            limit: limits.max_snapshot_bytes,
+        });
+    }
+    accountant.reserve(estimated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_limited_accepts_payload_at_limit() {
+        let body = "x".repeat(16);
+        let read = read_to_end_limited(body.as_bytes(), 16).expect("payload at limit");
+        assert_eq!(read.len(), 16);
+    }
+
+    #[test]
+    fn test_read_limited_rejects_oversized_payload() {
+        let body = "x".repeat(17);
+        let err = read_to_end_limited(body.as_bytes(), 16).unwrap_err();
+        assert_eq!(err, MemoryError::RequestTooLarge { limit: 16 });
+    }
+
+    #[test]
+    fn test_reservations_are_released_on_drop() {
+        let accountant = MemoryAccountant::new(100);
+        {
+            let _a = accountant.reserve(60).expect("first reservation");
+            assert_eq!(accountant.in_use(), 60);
+            assert!(matches!(
+                accountant.reserve(50),
+                Err(MemoryError::GlobalBudgetExhausted { in_use: 60, .. })
+            ));
+        }
+        assert_eq!(accountant.in_use(), 0);
+        assert!(accountant.reserve(100).is_ok());
+    }
+
+    #[test]
+    fn test_reservation_grow_respects_budget() {
+        let accountant = MemoryAccountant::new(100);
+        let mut reservation = accountant.reserve(40).expect("reservation");
+        reservation.grow(40).expect("grow within budget");
+        assert_eq!(reservation.bytes(), 80);
+        assert!(reservation.grow(40).is_err());
+        assert_eq!(accountant.in_use(), 80);
+    }
+
+    #[test]
+    fn test_snapshot_ceiling() {
+        let accountant = MemoryAccountant::new(usize::MAX);
+        let limits = MemoryLimits {
+            max_snapshot_bytes: 64,
+            ..MemoryLimits::default()
+        };
+        let mut entries = HashMap::new();
+        entries.insert("A".repeat(40), "B".repeat(40));
+
+        let err = reserve_snapshot(&accountant, &limits, &entries).unwrap_err();
+        assert!(matches!(
+            err,
+            MemoryError::SnapshotTooLarge { limit: 64, .. }
+        ));
+        assert_eq!(accountant.in_use(), 0);
+    }
+}
@@ -0,0 +1,518 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Classic multisig threshold evaluation.
+//!
+//! Before any operation runs, stellar-core checks the envelope's signatures
+//! against each source account's signers: the transaction source at the low
+//! threshold, and every operation's source at the threshold its type
+//! requires. [`evaluate`] repeats those checks against the simulated ledger
+//! state and reports how much signing weight each one is missing, so a
+//! wallet can tell which co-signers are still needed.
+
+use crate::authorization::{self, SourceAccount};
+use crate::network::Network;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use schemars::JsonSchema;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use soroban_env_host::storage::SnapshotSource;
+use soroban_env_host::xdr::{
+    self, AccountEntry, AccountId, DecoratedSignature, FeeBumpTransactionInnerTx, LedgerEntryData,
+    LedgerKey, LedgerKeyAccount, MuxedAccount, Operation, OperationBody, PublicKey, Signer,
+    SignerKey, SignerKeyEd25519SignedPayload, TransactionEnvelope, Uint256,
+};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Which of an account's thresholds an operation must meet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ThresholdLevel {
+    Low,
+    Medium,
+    High,
+}
+
+impl ThresholdLevel {
+    /// Lowercase name, as serialized.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+        }
+    }
+
+    /// The threshold level stellar-core requires for `op`.
+    pub fn for_operation(op: &OperationBody) -> Self {
+        match op {
+            OperationBody::AllowTrust(_)
+            | OperationBody::SetTrustLineFlags(_)
+            | OperationBody::BumpSequence(_)
+            | OperationBody::ClaimClaimableBalance(_)
+            | OperationBody::ExtendFootprintTtl(_)
+            | OperationBody::RestoreFootprint(_) => Self::Low,
+            OperationBody::AccountMerge(_) => Self::High,
+            OperationBody::SetOptions(set) => {
+                let changes_signing = set.master_weight.is_some()
+                    || set.low_threshold.is_some()
+                    || set.med_threshold.is_some()
+                    || set.high_threshold.is_some()
+                    || set.signer.is_some();
+                if changes_signing {
+                    Self::High
+                } else {
+                    Self::Medium
+                }
+            }
+            _ => Self::Medium,
+        }
+    }
+
+    fn threshold(self, account: &AccountEntry) -> u8 {
+        let [_, low, medium, high] = account.thresholds.0;
+        match self {
+            Self::Low => low,
+            Self::Medium => medium,
+            Self::High => high,
+        }
+    }
+}
+
+/// Outcome of one signature threshold check.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct SignatureCheck {
+    /// Source account (`G...`) whose signers were checked.
+    pub account: String,
+    /// Muxed ID, when the source was given as an `M...` address.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub muxed_id: Option<u64>,
+    /// Operation index, or `None` for the transaction (or fee bump) itself.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operation_index: Option<usize>,
+    pub threshold_level: ThresholdLevel,
+    /// Whether the account entry was found. Weights are zero when it wasn't.
+    pub account_found: bool,
+    /// Weight required: the threshold, and at least 1.
+    pub required_weight: u32,
+    /// Combined weight of the account's signers that signed.
+    pub signed_weight: u32,
+    pub missing_weight: u32,
+    pub satisfied: bool,
+}
+
+/// Evaluates the envelope's signatures against its source accounts.
+///
+/// Envelopes without signatures are not evaluated. For fee bumps the inner
+/// transaction is checked against the inner signatures, and the fee source
+/// against the outer ones.
+pub fn evaluate(
+    envelope: &TransactionEnvelope,
+    network: &Network,
+    state: &impl SnapshotSource,
+) -> Result<Vec<SignatureCheck>, xdr::Error> {
+    let mut evaluator = Evaluator {
+        state,
+        accounts: HashMap::new(),
+        checks: Vec::new(),
+    };
+    match envelope {
+        TransactionEnvelope::TxV0(env) => {
+            if !env.signatures.is_empty() {
+                evaluator.transaction(
+                    &MuxedAccount::Ed25519(env.tx.source_account_ed25519.clone()),
+                    &env.tx.operations,
+                    &network.transaction_hash(envelope)?,
+                    &env.signatures,
+                );
+            }
+        }
+        TransactionEnvelope::Tx(env) => {
+            if !env.signatures.is_empty() {
+                evaluator.transaction(
+                    &env.tx.source_account,
+                    &env.tx.operations,
+                    &network.transaction_hash(envelope)?,
+                    &env.signatures,
+                );
+            }
+        }
+        TransactionEnvelope::TxFeeBump(env) => {
+            let FeeBumpTransactionInnerTx::Tx(inner) = &env.tx.inner_tx;
+            if !inner.signatures.is_empty() {
+                let inner_hash =
+                    network.transaction_hash(&TransactionEnvelope::Tx(inner.clone()))?;
+                evaluator.transaction(
+                    &inner.tx.source_account,
+                    &inner.tx.operations,
+                    &inner_hash,
+                    &inner.signatures,
+                );
+            }
+            if !env.signatures.is_empty() {
+                evaluator.check(
+                    &env.tx.fee_source,
+                    None,
+                    ThresholdLevel::Low,
+                    &network.transaction_hash(envelope)?,
+                    &env.signatures,
+                );
+            }
+        }
+    }
+    Ok(evaluator.checks)
+}
+
+struct Evaluator<'a, S> {
+    state: &'a S,
+    accounts: HashMap<AccountId, Option<AccountEntry>>,
+    checks: Vec<SignatureCheck>,
+}
+
+impl<S: SnapshotSource> Evaluator<'_, S> {
+    fn transaction(
+        &mut self,
+        source: &MuxedAccount,
+        operations: &[Operation],
+        hash: &[u8; 32],
+        signatures: &[DecoratedSignature],
+    ) {
+        self.check(source, None, ThresholdLevel::Low, hash, signatures);
+        for (index, op) in operations.iter().enumerate() {
+            self.check(
+                op.source_account.as_ref().unwrap_or(source),
+                Some(index),
+                ThresholdLevel::for_operation(&op.body),
+                hash,
+                signatures,
+            );
+        }
+    }
+
+    fn check(
+        &mut self,
+        source: &MuxedAccount,
+        operation_index: Option<usize>,
+        threshold_level: ThresholdLevel,
+        hash: &[u8; 32],
+        signatures: &[DecoratedSignature],
+    ) {
+        let account_id = authorization::account_id(source.clone());
+        let source = SourceAccount::from(source);
+        let account = self.account(&account_id);
+        let account_found = account.is_some();
+        let (required_weight, signed_weight) = match account {
+            Some(account) => (
+                u32::from(threshold_level.threshold(account)).max(1),
+                signed_weight(account, hash, signatures),
+            ),
+            None => (0, 0),
+        };
+        self.checks.push(SignatureCheck {
+            account: source.account,
+            muxed_id: source.muxed_id,
+            operation_index,
+            threshold_level,
+            account_found,
+            required_weight,
+            signed_weight,
+            missing_weight: required_weight.saturating_sub(signed_weight),
+            satisfied: account_found && signed_weight >= required_weight,
+        });
+    }
+
+    fn account(&mut self, account_id: &AccountId) -> Option<&AccountEntry> {
+        let state = self.state;
+        self.accounts
+            .entry(account_id.clone())
+            .or_insert_with(|| {
+                let key = Rc::new(LedgerKey::Account(LedgerKeyAccount {
+                    account_id: account_id.clone(),
+                }));
+                match state.get(&key) {
+                    Ok(Some((entry, _))) => match &entry.data {
+                        LedgerEntryData::Account(account) => Some(account.clone()),
+                        _ => None,
+                    },
+                    _ => None,
+                }
+            })
+            .as_ref()
+    }
+}
+
+/// Combined weight of `account`'s signers (master key included) that
+/// signed `hash`.
+fn signed_weight(
+    account: &AccountEntry,
+    hash: &[u8; 32],
+    signatures: &[DecoratedSignature],
+) -> u32 {
+    let AccountId(PublicKey::PublicKeyTypeEd25519(master)) = &account.account_id;
+    let master = Signer {
+        key: SignerKey::Ed25519(master.clone()),
+        weight: u32::from(account.thresholds.0[0]),
+    };
+    std::iter::once(&master)
+        .chain(account.signers.iter())
+        .filter(|signer| signer.weight > 0 && has_signed(&signer.key, hash, signatures))
+        .map(|signer| signer.weight.min(u32::from(u8::MAX)))
+        .sum()
+}
+
+fn has_signed(key: &SignerKey, hash: &[u8; 32], signatures: &[DecoratedSignature]) -> bool {
+    match key {
+        SignerKey::Ed25519(Uint256(public_key)) => {
+            let Ok(verifying_key) = VerifyingKey::from_bytes(public_key) else {
+                return false;
+            };
+            matching_hint(public_key, signatures).any(|sig| {
+                Signature::from_slice(&sig.signature.0)
+                    .is_ok_and(|sig| verifying_key.verify(hash, &sig).is_ok())
+            })
+        }
+        SignerKey::PreAuthTx(Uint256(tx_hash)) => tx_hash == hash,
+        SignerKey::HashX(Uint256(x)) => {
+            matching_hint(x, signatures).any(|sig| Sha256::digest(&sig.signature.0)[..] == x[..])
+        }
+        // Signed over the signer's payload rather than the transaction hash.
+        SignerKey::Ed25519SignedPayload(SignerKeyEd25519SignedPayload {
+            ed25519: Uint256(public_key),
+            payload,
+        }) => {
+            let Ok(verifying_key) = VerifyingKey::from_bytes(public_key) else {
+                return false;
+            };
+            let hint = signed_payload_hint(public_key, payload);
+            signatures
+                .iter()
+                .filter(|sig| sig.hint.0 == hint)
+                .any(|sig| {
+                    Signature::from_slice(&sig.signature.0)
+                        .is_ok_and(|sig| verifying_key.verify(payload, &sig).is_ok())
+                })
+        }
+    }
+}
+
+/// Hint of a signed-payload signature: the key's last four bytes xor the
+/// payload's last four. Payloads shorter than that are zero-padded on the
+/// right.
+fn signed_payload_hint(key: &[u8; 32], payload: &[u8]) -> [u8; 4] {
+    let mut payload_hint = [0; 4];
+    if payload.len() >= 4 {
+        payload_hint.copy_from_slice(&payload[payload.len() - 4..]);
+    } else {
+        payload_hint[..payload.len()].copy_from_slice(payload);
+    }
+    std::array::from_fn(|i| key[28 + i] ^ payload_hint[i])
+}
+
+/// Signatures whose hint (the key's last four bytes) matches `key`.
+fn matching_hint<'a>(
+    key: &'a [u8; 32],
+    signatures: &'a [DecoratedSignature],
+) -> impl Iterator<Item = &'a DecoratedSignature> {
+    signatures.iter().filter(|sig| sig.hint.0 == key[28..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::LedgerSnapshot;
+    use crate::source::{LayeredSource, SnapshotLedgerSource};
+    use ed25519_dalek::{Signer as _, SigningKey};
+    use soroban_env_host::xdr::{
+        BumpSequenceOp, LedgerEntry, LedgerEntryExt, Memo, PaymentOp, Preconditions,
+        SequenceNumber, SignatureHint, Thresholds, Transaction, TransactionExt,
+        TransactionV1Envelope,
+    };
+
+    fn account_entry(
+        master: &SigningKey,
+        thresholds: [u8; 4],
+        signers: Vec<Signer>,
+    ) -> LedgerEntry {
+        LedgerEntry {
+            last_modified_ledger_seq: 1,
+            data: LedgerEntryData::Account(AccountEntry {
+                account_id: account_id(master),
+                balance: 100_000_000,
+                seq_num: SequenceNumber(1),
+                num_sub_entries: signers.len() as u32,
+                inflation_dest: None,
+                flags: 0,
+                home_domain: Default::default(),
+                thresholds: Thresholds(thresholds),
+                signers: signers.try_into().unwrap(),
+                ext: Default::default(),
+            }),
+            ext: LedgerEntryExt::V0,
+        }
+    }
+
+    fn account_id(key: &SigningKey) -> AccountId {
+        AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(
+            key.verifying_key().to_bytes(),
+        )))
+    }
+
+    fn sign(key: &SigningKey, hash: &[u8; 32]) -> DecoratedSignature {
+        let public_key = key.verifying_key().to_bytes();
+        DecoratedSignature {
+            hint: SignatureHint(public_key[28..].try_into().unwrap()),
+            signature: key.sign(hash).to_bytes().to_vec().try_into().unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_reports_missing_weight_per_operation() {
+        let master = SigningKey::from_bytes(&[1; 32]);
+        let cosigner = SigningKey::from_bytes(&[2; 32]);
+        let stranger = SigningKey::from_bytes(&[3; 32]);
+        let source = MuxedAccount::Ed25519(Uint256(master.verifying_key().to_bytes()));
+
+        let mut snapshot = LedgerSnapshot::new();
+        let key = LedgerKey::Account(LedgerKeyAccount {
+            account_id: account_id(&master),
+        });
+        // Master weight 1, thresholds low 1 / medium 2 / high 3.
+        let cosigner_signer = Signer {
+            key: SignerKey::Ed25519(Uint256(cosigner.verifying_key().to_bytes())),
+            weight: 1,
+        };
+        snapshot
+            .insert_entry(
+                &key,
+                account_entry(&master, [1, 1, 2, 3], vec![cosigner_signer]),
+            )
+            .unwrap();
+        let state = LayeredSource::new(SnapshotLedgerSource::new(snapshot), None, 1);
+
+        let operations = vec![
+            Operation {
+                source_account: None,
+                body: OperationBody::BumpSequence(BumpSequenceOp {
+                    bump_to: SequenceNumber(5),
+                }),
+            },
+            Operation {
+                source_account: None,
+                body: OperationBody::Payment(PaymentOp {
+                    destination: MuxedAccount::Ed25519(Uint256([9; 32])),
+                    asset: xdr::Asset::Native,
+                    amount: 10,
+                }),
+            },
+            Operation {
+                source_account: Some(MuxedAccount::Ed25519(Uint256(
+                    stranger.verifying_key().to_bytes(),
+                ))),
+                body: OperationBody::BumpSequence(BumpSequenceOp {
+                    bump_to: SequenceNumber(5),
+                }),
+            },
+        ];
+        let tx = Transaction {
+            source_account: source,
+            fee: 300,
+            seq_num: SequenceNumber(2),
+            cond: Preconditions::None,
+            memo: Memo::None,
+            operations: operations.try_into().unwrap(),
+            ext: TransactionExt::V0,
+        };
+        let network = Network::resolve(Some("testnet"), None).unwrap();
+        let unsigned = TransactionEnvelope::Tx(TransactionV1Envelope {
+            tx: tx.clone(),
+            signatures: Default::default(),
+        });
+        assert!(evaluate(&unsigned, &network, &state).unwrap().is_empty());
+
+        let hash = network.transaction_hash(&unsigned).unwrap();
+        let signed = |signers: &[&SigningKey]| {
+            TransactionEnvelope::Tx(TransactionV1Envelope {
+                tx: tx.clone(),
+                signatures: signers
+                    .iter()
+                    .map(|key| sign(key, &hash))
+                    .collect::<Vec<_>>()
+                    .try_into()
+                    .unwrap(),
+            })
+        };
+
+        let checks = evaluate(&signed(&[&master]), &network, &state).unwrap();
+        assert_eq!(checks.len(), 4);
+        assert!(checks[0].satisfied && checks[0].operation_index.is_none());
+        assert_eq!(checks[1].threshold_level, ThresholdLevel::Low);
+        assert!(checks[1].satisfied);
+        assert_eq!(checks[2].threshold_level, ThresholdLevel::Medium);
+        assert_eq!((checks[2].signed_weight, checks[2].missing_weight), (1, 1));
+        assert!(!checks[2].satisfied);
+        assert!(!checks[3].account_found && !checks[3].satisfied);
+
+        let checks = evaluate(&signed(&[&master, &cosigner]), &network, &state).unwrap();
+        assert!(checks[2].satisfied);
+        assert_eq!(checks[2].signed_weight, 2);
+
+        // A signature over another hash carries no weight.
+        let forged = TransactionEnvelope::Tx(TransactionV1Envelope {
+            tx: tx.clone(),
+            signatures: vec![sign(&master, &[0; 32])].try_into().unwrap(),
+        });
+        let checks = evaluate(&forged, &network, &state).unwrap();
+        assert_eq!(checks[0].signed_weight, 0);
+    }
+
+    #[test]
+    fn test_signed_payload_signer() {
+        let master = SigningKey::from_bytes(&[1; 32]);
+        let payload_key = SigningKey::from_bytes(&[2; 32]);
+        let public_key = payload_key.verifying_key().to_bytes();
+        let payload = vec![7, 8, 9, 10, 11];
+        let signer = Signer {
+            key: SignerKey::Ed25519SignedPayload(SignerKeyEd25519SignedPayload {
+                ed25519: Uint256(public_key),
+                payload: payload.clone().try_into().unwrap(),
+            }),
+            weight: 1,
+        };
+        let account = match account_entry(&master, [0, 1, 1, 1], vec![signer]).data {
+            LedgerEntryData::Account(account) => account,
+            _ => unreachable!(),
+        };
+        let hint = [
+            public_key[28] ^ 8,
+            public_key[29] ^ 9,
+            public_key[30] ^ 10,
+            public_key[31] ^ 11,
+        ];
+        let signature = |message: &[u8], hint: [u8; 4]| DecoratedSignature {
+            hint: SignatureHint(hint),
+            signature: payload_key
+                .sign(message)
+                .to_bytes()
+                .to_vec()
+                .try_into()
+                .unwrap(),
+        };
+        let hash = [5; 32];
+
+        assert_eq!(
+            signed_weight(&account, &hash, &[signature(&payload, hint)]),
+            1
+        );
+        // The transaction hash is not the payload.
+        assert_eq!(signed_weight(&account, &hash, &[signature(&hash, hint)]), 0);
+        // An ed25519 hint does not match a signed-payload signer.
+        let ed25519_hint = public_key[28..].try_into().unwrap();
+        assert_eq!(
+            signed_weight(&account, &hash, &[signature(&payload, ed25519_hint)]),
+            0
+        );
+        // Payloads shorter than four bytes are padded on the right.
+        assert_eq!(signed_payload_hint(&[0; 32], &[1, 2]), [1, 2, 0, 0]);
+    }
+}
@@ -0,0 +1,217 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Built-in network presets.
+//!
+//! A request (or `serve --network`) can name a network instead of spelling
+//! out its passphrase, RPC endpoint and fee defaults. The values match the
+//! network configurations used by the `erst` CLI (`internal/rpc`).
+//!
+//! The network ID (the SHA-256 of the passphrase) feeds every hash the host
+//! computes: auth payloads, contract IDs derived by `CreateContract`, and the
+//! transaction hash. A request may override the preset's passphrase with
+//! `network_passphrase`, e.g. for a standalone network.
+
+use sha2::{Digest, Sha256};
+use soroban_env_host::meta;
+use soroban_env_host::xdr::{
+    self, Hash, Limits, MuxedAccount, Preconditions, Transaction, TransactionEnvelope,
+    TransactionExt, TransactionSignaturePayload, TransactionSignaturePayloadTaggedTransaction,
+    TransactionV0, WriteXdr,
+};
+
+/// Network used when a request names none.
+pub const DEFAULT_NETWORK: &str = "pubnet";
+
+/// Defaults for a well-known Stellar network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NetworkPreset {
+    /// Canonical name.
+    pub name: &'static str,
+    /// Network passphrase; its SHA-256 is the network ID.
+    pub passphrase: &'static str,
+    /// Public soroban-rpc endpoint.
+    pub rpc_url: &'static str,
+    /// Minimum fee per operation, in stroops.
+    pub base_fee: u32,
+    /// Base reserve, in stroops.
+    pub base_reserve: u32,
+    /// Ledger protocol version simulated for the network.
+    pub protocol_version: u32,
+}
+
+/// The network a simulation runs on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Network {
+    /// Preset supplying the RPC endpoint, fees and protocol version.
+    pub preset: &'static NetworkPreset,
+    /// Passphrase used for hashing: the preset's unless overridden.
+    pub passphrase: String,
+}
+
+impl Network {
+    /// Resolves a request's `network` and `network_passphrase`.
+    pub fn resolve(name: Option<&str>, passphrase: Option<&str>) -> Result<Self, String> {
+        let preset = resolve(name)?;
+        Ok(Self {
+            preset,
+            passphrase: passphrase.unwrap_or(preset.passphrase).to_string(),
+        })
+    }
+
+    /// SHA-256 of the passphrase, as used in transaction and auth hashes.
+    pub fn id(&self) -> [u8; 32] {
+        Sha256::digest(&self.passphrase).into()
+    }
+
+    /// Hash of `envelope` on this network, as returned by `sendTransaction`
+    /// and accepted by `getTransaction`.
+    ///
+    /// Legacy `TxV0` envelopes hash as the equivalent `Tx`, like stellar-core.
+    pub fn transaction_hash(&self, envelope: &TransactionEnvelope) -> Result<[u8; 32], xdr::Error> {
+        let tagged_transaction = match envelope {
+            TransactionEnvelope::TxV0(env) => {
+                TransactionSignaturePayloadTaggedTransaction::Tx(v0_to_v1(&env.tx))
+            }
+            TransactionEnvelope::Tx(env) => {
+                TransactionSignaturePayloadTaggedTransaction::Tx(env.tx.clone())
+            }
+            TransactionEnvelope::TxFeeBump(env) => {
+                TransactionSignaturePayloadTaggedTransaction::TxFeeBump(env.tx.clone())
+            }
+        };
+        let payload = TransactionSignaturePayload {
+            network_id: Hash(self.id()),
+            tagged_transaction,
+        };
+        Ok(Sha256::digest(payload.to_xdr(Limits::none())?).into())
+    }
+}
+
+fn v0_to_v1(tx: &TransactionV0) -> Transaction {
+    Transaction {
+        source_account: MuxedAccount::Ed25519(tx.source_account_ed25519.clone()),
+        fee: tx.fee,
+        seq_num: tx.seq_num.clone(),
+        cond: match &tx.time_bounds {
+            Some(bounds) => Preconditions::Time(bounds.clone()),
+            None => Preconditions::None,
+        },
+        memo: tx.memo.clone(),
+        operations: tx.operations.clone(),
+        ext: TransactionExt::V0,
+    }
+}
+
+/// Lower-case hex encoding of a hash.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+const PUBNET: NetworkPreset = NetworkPreset {
+    name: "pubnet",
+    passphrase: "Public Global Stellar Network ; September 2015",
+    rpc_url: "https://mainnet.stellar.validationcloud.io/v1/soroban-rpc-demo",
+    base_fee: 100,
+    base_reserve: 5_000_000,
+    protocol_version: meta::INTERFACE_VERSION.protocol,
+};
+
+const TESTNET: NetworkPreset = NetworkPreset {
+    name: "testnet",
+    passphrase: "Test SDF Network ; September 2015",
+    rpc_url: "https://soroban-testnet.stellar.org",
+    base_fee: 100,
+    base_reserve: 5_000_000,
+    protocol_version: meta::INTERFACE_VERSION.protocol,
+};
+
+const FUTURENET: NetworkPreset = NetworkPreset {
+    name: "futurenet",
+    passphrase: "Test SDF Future Network ; October 2022",
+    rpc_url: "https://rpc-futurenet.stellar.org",
+    base_fee: 100,
+    base_reserve: 5_000_000,
+    protocol_version: meta::INTERFACE_VERSION.protocol,
+};
+
+/// Looks up a preset by name (case-insensitive). `mainnet` and `public` are
+/// accepted as aliases for `pubnet`.
+pub fn preset(name: &str) -> Option<&'static NetworkPreset> {
+    match name.trim().to_ascii_lowercase().as_str() {
+        "pubnet" | "mainnet" | "public" => Some(&PUBNET),
+        "testnet" => Some(&TESTNET),
+        "futurenet" => Some(&FUTURENET),
+        _ => None,
+    }
+}
+
+/// Resolves an optional network name, defaulting to [`DEFAULT_NETWORK`].
+pub fn resolve(name: Option<&str>) -> Result<&'static NetworkPreset, String> {
+    let name = name.unwrap_or(DEFAULT_NETWORK);
+    preset(name)
+        .ok_or_else(|| format!("unknown network {name:?} (expected pubnet, testnet or futurenet)"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preset_lookup_accepts_aliases() {
+        assert_eq!(preset("mainnet"), Some(&PUBNET));
+        assert_eq!(preset("Public"), Some(&PUBNET));
+        assert_eq!(preset("testnet").unwrap().rpc_url, TESTNET.rpc_url);
+        assert_eq!(preset("standalone"), None);
+        assert_eq!(resolve(None).unwrap().name, "pubnet");
+        assert!(resolve(Some("devnet")).is_err());
+    }
+
+    #[test]
+    fn test_network_id_hashes_passphrase() {
+        // Well-known testnet network ID: cee0302d...
+        let testnet = Network::resolve(Some("testnet"), None).unwrap();
+        assert_eq!(testnet.id()[..4], [0xce, 0xe0, 0x30, 0x2d]);
+
+        let standalone =
+            Network::resolve(Some("testnet"), Some("Standalone Network ; February 2017")).unwrap();
+        assert_eq!(standalone.preset.name, "testnet");
+        assert_ne!(standalone.id(), testnet.id());
+    }
+
+    #[test]
+    fn test_transaction_hash_treats_v0_as_v1() {
+        use soroban_env_host::xdr::{
+            Memo, SequenceNumber, TimeBounds, TimePoint, TransactionV0Envelope, TransactionV0Ext,
+            TransactionV1Envelope, Uint256,
+        };
+
+        let v0 = TransactionV0 {
+            source_account_ed25519: Uint256([5; 32]),
+            fee: 100,
+            seq_num: SequenceNumber(42),
+            time_bounds: Some(TimeBounds {
+                min_time: TimePoint(0),
+                max_time: TimePoint(1_700_000_000),
+            }),
+            memo: Memo::None,
+            operations: Default::default(),
+            ext: TransactionV0Ext::V0,
+        };
+        let v0_env = TransactionEnvelope::TxV0(TransactionV0Envelope {
+            tx: v0.clone(),
+            signatures: Default::default(),
+        });
+        let v1_env = TransactionEnvelope::Tx(TransactionV1Envelope {
+            tx: v0_to_v1(&v0),
+            signatures: Default::default(),
+        });
+
+        let testnet = Network::resolve(Some("testnet"), None).unwrap();
+        let pubnet = Network::resolve(Some("pubnet"), None).unwrap();
+        let hash = testnet.transaction_hash(&v0_env).unwrap();
+        assert_eq!(hash, testnet.transaction_hash(&v1_env).unwrap());
+        assert_ne!(hash, pubnet.transaction_hash(&v1_env).unwrap());
+        assert_eq!(hex(&hash).len(), 64);
+    }
+}
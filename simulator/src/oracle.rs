@@ -0,0 +1,371 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Built-in SEP-40 price oracles.
+//!
+//! A request's `mock_oracles` replace each named contract with a stub that
+//! answers the SEP-40 read interface from fixed prices, so DeFi flows that
+//! depend on a price feed simulate deterministically. The stub's instance
+//! storage holds the prices, and its code reads them back:
+//!
+//! - `lastprice(asset)` returns the asset's `PriceData`, or `None` for an
+//!   asset without a price;
+//! - `price(asset, timestamp)` does the same at the mocked timestamp, and
+//!   returns `None` at any other;
+//! - `prices(asset, records)` returns the one mocked record;
+//! - `base`, `assets`, `decimals`, `resolution` and `last_timestamp`
+//!   describe the feed.
+//!
+//! Assets are `Asset::Stellar(address)` when given as a contract address
+//! (`C...`), and `Asset::Other(symbol)` otherwise.
+
+use crate::simulation::SimulationError;
+use crate::snapshot::LedgerSnapshot;
+use crate::types::MockOracle;
+use sha2::{Digest, Sha256};
+use soroban_env_host::meta::ENV_META_V0_SECTION_NAME;
+use soroban_env_host::xdr::{
+    ContractCodeEntry, ContractCodeEntryExt, ContractDataDurability, ContractDataEntry,
+    ContractExecutable, ContractId, ExtensionPoint, Hash, Int128Parts, LedgerEntry,
+    LedgerEntryData, LedgerEntryExt, LedgerKey, LedgerKeyContractCode, LedgerKeyContractData,
+    Limits, ScAddress, ScContractInstance, ScEnvMetaEntry, ScEnvMetaEntryInterfaceVersion, ScMap,
+    ScMapEntry, ScSymbol, ScVal, WriteXdr,
+};
+use soroban_env_host::{StorageType, SymbolSmall, Val};
+use std::fmt::Write as _;
+use std::sync::OnceLock;
+
+/// Decimals of mocked prices unless the oracle sets its own, as on
+/// Reflector's feeds.
+const DEFAULT_DECIMALS: u32 = 14;
+/// Seconds between price records unless the oracle sets its own.
+const DEFAULT_RESOLUTION: u32 = 300;
+/// Oldest protocol with Soroban, so the stub runs under any the simulator
+/// supports.
+const STUB_PROTOCOL: u32 = 20;
+
+/// Instance storage keys of the stub.
+const BASE: &str = "base";
+const DECIMALS: &str = "decimals";
+const LAST_TIMESTAMP: &str = "last_ts";
+const PRICES: &str = "prices";
+const RESOLUTION: &str = "res";
+
+/// Replaces each of `oracles` in `ledger` with the stub. `now` is the
+/// timestamp of oracles that do not set one.
+pub fn install(
+    ledger: &mut LedgerSnapshot,
+    oracles: &[MockOracle],
+    now: u64,
+) -> Result<(), SimulationError> {
+    if oracles.is_empty() {
+        return Ok(());
+    }
+    let wasm = stub()?;
+    let hash = Hash(Sha256::digest(&wasm).into());
+    let code = wasm.clone().try_into().map_err(|e| {
+        SimulationError::InvalidRequest(format!("Failed to build the oracle stub: {e}"))
+    })?;
+    insert(
+        ledger,
+        LedgerKey::ContractCode(LedgerKeyContractCode { hash: hash.clone() }),
+        LedgerEntryData::ContractCode(ContractCodeEntry {
+            ext: ContractCodeEntryExt::V0,
+            hash: hash.clone(),
+            code,
+        }),
+    )?;
+    for (index, oracle) in oracles.iter().enumerate() {
+        let invalid = |what: &str, e: &dyn std::fmt::Display| {
+            SimulationError::InvalidRequest(format!("Invalid mock_oracles[{index}].{what}: {e}"))
+        };
+        let contract = ScAddress::Contract(
+            oracle
+                .contract_id
+                .parse::<ContractId>()
+                .map_err(|e| invalid("contract_id", &e))?,
+        );
+        let storage = instance_storage(oracle, now).map_err(|(what, e)| invalid(what, &e))?;
+        let key = ScVal::LedgerKeyContractInstance;
+        insert(
+            ledger,
+            LedgerKey::ContractData(LedgerKeyContractData {
+                contract: contract.clone(),
+                key: key.clone(),
+                durability: ContractDataDurability::Persistent,
+            }),
+            LedgerEntryData::ContractData(ContractDataEntry {
+                ext: ExtensionPoint::V0,
+                contract,
+                key,
+                durability: ContractDataDurability::Persistent,
+                val: ScVal::ContractInstance(ScContractInstance {
+                    executable: ContractExecutable::Wasm(hash.clone()),
+                    storage: Some(storage),
+                }),
+            }),
+        )?;
+    }
+    Ok(())
+}
+
+fn insert(
+    ledger: &mut LedgerSnapshot,
+    key: LedgerKey,
+    data: LedgerEntryData,
+) -> Result<(), SimulationError> {
+    let entry = LedgerEntry {
+        last_modified_ledger_seq: 0,
+        data,
+        ext: LedgerEntryExt::V0,
+    };
+    ledger
+        .insert_entry(&key, entry)
+        .map_err(|e| SimulationError::InvalidRequest(format!("Failed to mock oracle: {e}")))
+}
+
+/// The stub's instance storage for `oracle`, or the field that is invalid
+/// and why.
+fn instance_storage(oracle: &MockOracle, now: u64) -> Result<ScMap, (&'static str, String)> {
+    let decimals = oracle.decimals.unwrap_or(DEFAULT_DECIMALS);
+    let timestamp = oracle.timestamp.unwrap_or(now);
+    let mut prices = oracle
+        .prices
+        .iter()
+        .map(|(asset_name, price)| {
+            let price = parse_price(price, decimals).map_err(|e| ("prices", e))?;
+            Ok((
+                asset(asset_name).map_err(|e| ("prices", e))?,
+                map(vec![
+                    (symbol("price"), i128_val(price)),
+                    (symbol("timestamp"), ScVal::U64(timestamp)),
+                ]),
+            ))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    prices.sort_by(|a, b| a.0.cmp(&b.0));
+    let base = asset(oracle.base.as_deref().unwrap_or("USD")).map_err(|e| ("base", e))?;
+    match map(vec![
+        (symbol(BASE), base),
+        (symbol(DECIMALS), ScVal::U32(decimals)),
+        (symbol(LAST_TIMESTAMP), ScVal::U64(timestamp)),
+        (symbol(PRICES), map(prices)),
+        (
+            symbol(RESOLUTION),
+            ScVal::U32(oracle.resolution.unwrap_or(DEFAULT_RESOLUTION)),
+        ),
+    ]) {
+        ScVal::Map(Some(storage)) => Ok(storage),
+        _ => unreachable!("map() builds maps"),
+    }
+}
+
+/// The SEP-40 `Asset` named `name`: a contract address is a Stellar asset,
+/// anything else a symbol.
+fn asset(name: &str) -> Result<ScVal, String> {
+    let (variant, value) = match name.parse::<ScAddress>() {
+        Ok(address @ ScAddress::Contract(_)) => ("Stellar", ScVal::Address(address)),
+        _ => (
+            "Other",
+            ScVal::Symbol(
+                ScSymbol::try_from(name)
+                    .map_err(|()| format!("asset {name:?} is not an address or symbol"))?,
+            ),
+        ),
+    };
+    Ok(ScVal::Vec(Some(
+        vec![symbol(variant), value]
+            .try_into()
+            .expect("two elements fit"),
+    )))
+}
+
+/// `price` scaled by `decimals`: an integer, or a decimal such as `1.05`.
+fn parse_price(price: &str, decimals: u32) -> Result<i128, String> {
+    let invalid = || format!("price {price:?} is not a decimal number");
+    let (whole, fraction) = price.split_once('.').unwrap_or((price, ""));
+    if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    if fraction.len() > decimals as usize {
+        return Err(format!("price {price:?} has more than {decimals} decimals"));
+    }
+    let digits = format!("{whole}{fraction:0<width$}", width = decimals as usize);
+    digits.parse::<i128>().map_err(|_| invalid())
+}
+
+fn symbol(name: &str) -> ScVal {
+    ScVal::Symbol(name.try_into().expect("valid symbol"))
+}
+
+fn i128_val(value: i128) -> ScVal {
+    ScVal::I128(Int128Parts {
+        hi: (value >> 64) as i64,
+        lo: value as u64,
+    })
+}
+
+/// A map of `entries`, which must be sorted by key.
+fn map(entries: Vec<(ScVal, ScVal)>) -> ScVal {
+    let entries = entries
+        .into_iter()
+        .map(|(key, val)| ScMapEntry { key, val })
+        .collect::<Vec<_>>();
+    ScVal::Map(Some(ScMap(entries.try_into().expect("map fits"))))
+}
+
+/// The stub's Wasm, built once.
+fn stub() -> Result<Vec<u8>, SimulationError> {
+    static STUB: OnceLock<Result<Vec<u8>, String>> = OnceLock::new();
+    STUB.get_or_init(|| wat::parse_str(stub_wat()).map_err(|e| e.to_string()))
+        .clone()
+        .map_err(|e| {
+            SimulationError::InvalidRequest(format!("Failed to build the oracle stub: {e}"))
+        })
+}
+
+/// The stub's source. Timestamps compare as raw `Val`s, which is exact
+/// while they fit a small `U64`, as any Unix time in seconds does.
+fn stub_wat() -> String {
+    let key = |name: &str| {
+        SymbolSmall::try_from_str(name)
+            .expect("small symbol")
+            .to_val()
+            .get_payload() as i64
+    };
+    let void = Val::VOID.to_val().get_payload() as i64;
+    let true_ = Val::TRUE.to_val().get_payload() as i64;
+    let instance = StorageType::Instance as i64;
+    let meta = ScEnvMetaEntry::ScEnvMetaKindInterfaceVersion(ScEnvMetaEntryInterfaceVersion {
+        protocol: STUB_PROTOCOL,
+        pre_release: 0,
+    })
+    .to_xdr(Limits::none())
+    .expect("meta encodes");
+    let meta = meta.iter().fold(String::new(), |mut out, byte| {
+        let _ = write!(out, "\\{byte:02x}");
+        out
+    });
+    let (base, decimals, last_timestamp, prices, resolution) = (
+        key(BASE),
+        key(DECIMALS),
+        key(LAST_TIMESTAMP),
+        key(PRICES),
+        key(RESOLUTION),
+    );
+    format!(
+        r#"(module
+  (import "l" "1" (func $get_contract_data (param i64 i64) (result i64)))
+  (import "m" "1" (func $map_get (param i64 i64) (result i64)))
+  (import "m" "4" (func $map_has (param i64 i64) (result i64)))
+  (import "m" "7" (func $map_keys (param i64) (result i64)))
+  (import "v" "_" (func $vec_new (result i64)))
+  (import "v" "6" (func $vec_push_back (param i64 i64) (result i64)))
+  (func $field (param $key i64) (result i64)
+    (call $get_contract_data (local.get $key) (i64.const {instance})))
+  (func $lookup (param $asset i64) (result i64)
+    (local $prices i64)
+    (local.set $prices (call $field (i64.const {prices})))
+    (if (result i64)
+      (i64.eq (call $map_has (local.get $prices) (local.get $asset)) (i64.const {true_}))
+      (then (call $map_get (local.get $prices) (local.get $asset)))
+      (else (i64.const {void}))))
+  (func (export "lastprice") (param $asset i64) (result i64)
+    (call $lookup (local.get $asset)))
+  (func (export "price") (param $asset i64) (param $timestamp i64) (result i64)
+    (if (result i64)
+      (i64.eq (local.get $timestamp) (call $field (i64.const {last_timestamp})))
+      (then (call $lookup (local.get $asset)))
+      (else (i64.const {void}))))
+  (func (export "prices") (param $asset i64) (param $records i64) (result i64)
+    (local $price i64)
+    (local.set $price (call $lookup (local.get $asset)))
+    (if (result i64)
+      (i64.eq (local.get $price) (i64.const {void}))
+      (then (i64.const {void}))
+      (else (call $vec_push_back (call $vec_new) (local.get $price)))))
+  (func (export "base") (result i64) (call $field (i64.const {base})))
+  (func (export "assets") (result i64)
+    (call $map_keys (call $field (i64.const {prices}))))
+  (func (export "decimals") (result i64) (call $field (i64.const {decimals})))
+  (func (export "resolution") (result i64) (call $field (i64.const {resolution})))
+  (func (export "last_timestamp") (result i64) (call $field (i64.const {last_timestamp})))
+  (@custom "{ENV_META_V0_SECTION_NAME}" "{meta}"))
+"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prices_scale_by_decimals() {
+        assert_eq!(parse_price("1.05", 4), Ok(10_500));
+        assert_eq!(parse_price("42", 2), Ok(4_200));
+        assert!(parse_price("1.005", 2).is_err());
+        assert!(parse_price("abc", 2).is_err());
+    }
+
+    #[test]
+    fn test_install_stubs_the_oracle_instance() {
+        let token = ScAddress::Contract(ContractId(Hash([1; 32])));
+        let oracle = MockOracle {
+            contract_id: ScAddress::Contract(ContractId(Hash([7; 32]))).to_string(),
+            prices: [
+                ("XLM".to_string(), "0.1".to_string()),
+                ("BTC".to_string(), "60000".to_string()),
+                (token.to_string(), "1".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+            timestamp: Some(1_700_000_000),
+            decimals: Some(7),
+            resolution: None,
+            base: None,
+        };
+        let mut ledger = LedgerSnapshot::new();
+        install(&mut ledger, &[oracle], 0).unwrap();
+        assert_eq!(ledger.len(), 2);
+
+        let instance = ledger
+            .iter()
+            .find_map(|(_, entry)| match &entry.data {
+                LedgerEntryData::ContractData(data) => Some(data.val.clone()),
+                _ => None,
+            })
+            .unwrap();
+        let ScVal::ContractInstance(ScContractInstance {
+            storage: Some(storage),
+            ..
+        }) = instance
+        else {
+            panic!("not an instance: {instance:?}");
+        };
+        let prices = storage
+            .iter()
+            .find(|e| e.key == symbol(PRICES))
+            .map(|e| e.val.clone())
+            .unwrap();
+        let ScVal::Map(Some(prices)) = prices else {
+            panic!("prices are not a map");
+        };
+        // Sorted by asset, as the host requires.
+        assert_eq!(prices[0].key, asset("BTC").unwrap());
+        assert_eq!(
+            prices[2].key,
+            ScVal::Vec(Some(
+                vec![symbol("Stellar"), ScVal::Address(token)]
+                    .try_into()
+                    .unwrap()
+            ))
+        );
+        assert_eq!(
+            prices[1].val,
+            map(vec![
+                (symbol("price"), i128_val(1_000_000)),
+                (symbol("timestamp"), ScVal::U64(1_700_000_000)),
+            ])
+        );
+    }
+}
@@ -0,0 +1,476 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! `erst-sim ordering`: how a batch of transactions depends on their order.
+//!
+//! A batch file is a scenario whose `transactions` land in the same ledger,
+//! in an order nobody controls:
+//!
+//! ```json
+//! { "name": "auction", "ledger_entries": { "AAAABg...": "AAAAAA..." },
+//!   "defaults": { "network": "testnet" },
+//!   "transactions": [
+//!     { "name": "alice-bid", "request": { "envelope_xdr": "AAAAAg..." } },
+//!     { "name": "bob-bid", "request": { "envelope_xdr": "AAAAAg..." } },
+//!     { "name": "settle", "request": { "invoke": { "contract_id": "C...",
+//!         "function": "settle" } } } ] }
+//! ```
+//!
+//! [`run`] simulates the transactions in every order, or in the batch's own
+//! `orderings`, each order from the same initial state, applying the ledger
+//! changes of each successful transaction before the next. Every run is
+//! pinned to the ledger sequence and close time of the first. The report
+//! says, for each transaction, whether its status and return value, and for
+//! each holder, whether its net balance changes, differ from one order to
+//! another, with the orders giving each outcome: where a front-runner could
+//! profit or a transaction could be made to fail.
+//!
+//! Batches of up to [`EXHAUSTIVE`] transactions run in every order. Larger
+//! ones run in the given order, reversed, and with each transaction moved to
+//! the front.
+
+use crate::balances::BalanceChange;
+use crate::memory::MemoryLimits;
+use crate::pretty;
+use crate::scenario::{self, Advance, Expect, Scenario, Step};
+use crate::simulation;
+use crate::source::cache::CachedSource;
+use crate::types::SimulationRequest;
+use crate::xdr_limits;
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+use soroban_env_host::xdr::TransactionMeta;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Largest batch run in every order: 120 orders of 5 transactions.
+pub const EXHAUSTIVE: usize = 5;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Batch {
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Base64 `LedgerKey` to base64 `LedgerEntry`: the state every order
+    /// starts from.
+    #[serde(default)]
+    pub ledger_entries: HashMap<String, String>,
+    /// Request fields every transaction gets unless it sets them itself.
+    #[serde(default)]
+    pub defaults: serde_json::Map<String, serde_json::Value>,
+    pub transactions: Vec<Transaction>,
+    /// Orders to run, as lists of transaction names; every order, or a
+    /// sample of them for large batches, when empty.
+    #[serde(default)]
+    pub orderings: Vec<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Transaction {
+    pub name: String,
+    /// A `SimulationRequest`, without the fields `defaults` supplies.
+    pub request: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OrderingReport {
+    pub name: String,
+    /// Whether any outcome differs between orders.
+    pub order_dependent: bool,
+    /// The outcomes that differ, with the orders giving each value.
+    pub differences: Vec<Difference>,
+    pub orderings: Vec<OrderingOutcome>,
+}
+
+/// Everything one order produced.
+#[derive(Debug, Serialize)]
+pub struct OrderingOutcome {
+    /// Transaction names, in the order they ran.
+    pub order: Vec<String>,
+    pub transactions: Vec<TransactionOutcome>,
+    /// Net balance changes of the whole order, by holder and token.
+    pub balance_changes: Vec<BalanceChange>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TransactionOutcome {
+    pub name: String,
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The return value, rendered, when the transaction succeeded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub return_value: Option<String>,
+}
+
+/// An outcome that differs between orders.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Difference {
+    /// What differs, e.g. `bob-bid status` or `balance of GABC…WXYZ in
+    /// CDLZ…CYSC`.
+    pub subject: String,
+    pub variants: Vec<Variant>,
+}
+
+/// One value of a differing outcome.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Variant {
+    pub value: String,
+    /// Indexes into `orderings` of the orders giving it.
+    pub orderings: Vec<usize>,
+}
+
+pub fn load(path: &Path) -> Result<Batch, String> {
+    let file =
+        std::fs::read(path).map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    serde_json::from_slice(&file).map_err(|e| format!("Invalid batch {}: {e}", path.display()))
+}
+
+/// Runs `batch` in each of its orders. `remote` gives each request the state
+/// source it names, if any.
+pub fn run(
+    batch: &Batch,
+    default_name: &str,
+    limits: &MemoryLimits,
+    remote: impl Fn(&SimulationRequest) -> Option<Arc<CachedSource>>,
+) -> Result<OrderingReport, String> {
+    let names = batch
+        .transactions
+        .iter()
+        .map(|transaction| transaction.name.clone())
+        .collect::<Vec<_>>();
+    for (i, name) in names.iter().enumerate() {
+        if names[..i].contains(name) {
+            return Err(format!("Two transactions are named {name:?}"));
+        }
+    }
+    let orders = if batch.orderings.is_empty() {
+        orders(names.len())
+    } else {
+        batch
+            .orderings
+            .iter()
+            .map(|order| {
+                let indexes = order
+                    .iter()
+                    .map(|name| {
+                        names
+                            .iter()
+                            .position(|other| other == name)
+                            .ok_or_else(|| format!("No transaction is named {name:?}"))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let mut sorted = indexes.clone();
+                sorted.sort_unstable();
+                if !sorted.iter().copied().eq(0..names.len()) {
+                    return Err(format!(
+                        "Ordering {order:?} does not name every transaction once"
+                    ));
+                }
+                Ok(indexes)
+            })
+            .collect::<Result<Vec<Vec<usize>>, String>>()?
+    };
+
+    let scenario = Scenario {
+        name: batch.name.clone(),
+        ledger_entries: HashMap::new(),
+        defaults: batch.defaults.clone(),
+        advance: Advance::default(),
+        steps: batch
+            .transactions
+            .iter()
+            .map(|transaction| Step {
+                name: Some(transaction.name.clone()),
+                request: transaction.request.clone(),
+                expect: Expect::default(),
+                advance: None,
+            })
+            .collect(),
+    };
+    let mut ledger = (None, Some(simulation::ledger_timestamp()));
+    let mut orderings = Vec::with_capacity(orders.len());
+    for order in orders {
+        let mut state = batch.ledger_entries.clone();
+        let mut transactions = Vec::with_capacity(order.len());
+        let mut balances = BTreeMap::<(String, String), (Option<String>, i128)>::new();
+        for index in order {
+            let step = &scenario.steps[index];
+            let name = names[index].clone();
+            let result = scenario::request(&scenario, step, &state).and_then(|mut request| {
+                request.ledger_sequence = request.ledger_sequence.or(ledger.0);
+                request.ledger_close_time = request.ledger_close_time.or(ledger.1);
+                let remote = remote(&request);
+                simulation::run(&request, limits, None, remote, None).map_err(|e| e.to_string())
+            });
+            let response = match result {
+                Ok(response) => response,
+                Err(e) => {
+                    transactions.push(TransactionOutcome {
+                        name,
+                        status: "error".to_string(),
+                        error: Some(e),
+                        return_value: None,
+                    });
+                    continue;
+                }
+            };
+            if ledger.0.is_none() {
+                ledger = (response.ledger_sequence, response.ledger_close_time);
+            }
+            let mut outcome = TransactionOutcome {
+                name,
+                status: response.status.clone(),
+                error: response.error.clone(),
+                return_value: None,
+            };
+            if let Some(meta) = response.result_meta_xdr.as_deref() {
+                if response.status == "success" {
+                    let applied =
+                        scenario::apply_changes(&mut state, meta).and_then(|()| return_value(meta));
+                    match applied {
+                        Ok(value) => outcome.return_value = value,
+                        Err(e) => {
+                            outcome.status = "error".to_string();
+                            outcome.error = Some(e);
+                        }
+                    }
+                }
+            }
+            if outcome.status == "success" {
+                for change in &response.balance_changes {
+                    let amount = change.amount.parse::<i128>().unwrap_or_default();
+                    let net = balances
+                        .entry((change.address.clone(), change.token.clone()))
+                        .or_insert_with(|| (change.asset.clone(), 0));
+                    net.1 += amount;
+                }
+            }
+            transactions.push(outcome);
+        }
+        orderings.push(OrderingOutcome {
+            order: transactions.iter().map(|t| t.name.clone()).collect(),
+            transactions,
+            balance_changes: balances
+                .into_iter()
+                .filter(|(_, (_, amount))| *amount != 0)
+                .map(|((address, token), (asset, amount))| BalanceChange {
+                    address,
+                    token,
+                    asset,
+                    amount: amount.to_string(),
+                })
+                .collect(),
+        });
+    }
+    let differences = differences(&names, &orderings);
+    Ok(OrderingReport {
+        name: batch
+            .name
+            .clone()
+            .unwrap_or_else(|| default_name.to_string()),
+        order_dependent: !differences.is_empty(),
+        differences,
+        orderings,
+    })
+}
+
+/// The orders of `count` transactions to run, as indexes.
+fn orders(count: usize) -> Vec<Vec<usize>> {
+    let given = (0..count).collect::<Vec<_>>();
+    if count <= EXHAUSTIVE {
+        let mut all = Vec::new();
+        permutations(&given, 0, &mut all);
+        return all;
+    }
+    let mut sample = vec![given.clone(), given.iter().rev().copied().collect()];
+    for first in 1..count {
+        let mut order = vec![first];
+        order.extend(given.iter().filter(|&&i| i != first));
+        sample.push(order);
+    }
+    sample
+}
+
+/// Every order of `items[from..]` after `items[..from]`, in lexicographic
+/// order of the indexes.
+fn permutations(items: &[usize], from: usize, all: &mut Vec<Vec<usize>>) {
+    if from + 1 >= items.len() {
+        all.push(items.to_vec());
+        return;
+    }
+    for i in from..items.len() {
+        let mut next = items.to_vec();
+        let picked = next.remove(i);
+        next.insert(from, picked);
+        permutations(&next, from + 1, all);
+    }
+}
+
+/// The outcomes of `orderings` that are not the same in all of them.
+fn differences(names: &[String], orderings: &[OrderingOutcome]) -> Vec<Difference> {
+    let mut subjects = Vec::<(String, Vec<String>)>::new();
+    for name in names {
+        let outcomes = orderings
+            .iter()
+            .map(|ordering| ordering.transactions.iter().find(|t| &t.name == name))
+            .collect::<Vec<_>>();
+        subjects.push((
+            format!("{name} status"),
+            outcomes
+                .iter()
+                .map(|outcome| match outcome {
+                    Some(TransactionOutcome {
+                        status,
+                        error: Some(error),
+                        ..
+                    }) => format!("{status}: {}", error.lines().next().unwrap_or_default()),
+                    Some(outcome) => outcome.status.clone(),
+                    None => "not run".to_string(),
+                })
+                .collect(),
+        ));
+        subjects.push((
+            format!("{name} return value"),
+            outcomes
+                .iter()
+                .map(|outcome| {
+                    outcome
+                        .and_then(|outcome| outcome.return_value.clone())
+                        .unwrap_or_else(|| "none".to_string())
+                })
+                .collect(),
+        ));
+    }
+    let mut holders = orderings
+        .iter()
+        .flat_map(|ordering| &ordering.balance_changes)
+        .map(|change| (change.address.clone(), change.token.clone()))
+        .collect::<Vec<_>>();
+    holders.sort();
+    holders.dedup();
+    for (address, token) in holders {
+        subjects.push((
+            format!(
+                "balance of {} in {}",
+                pretty::short(&address),
+                pretty::short(&token)
+            ),
+            orderings
+                .iter()
+                .map(|ordering| {
+                    ordering
+                        .balance_changes
+                        .iter()
+                        .find(|change| change.address == address && change.token == token)
+                        .map_or_else(|| "0".to_string(), |change| change.amount.clone())
+                })
+                .collect(),
+        ));
+    }
+
+    subjects
+        .into_iter()
+        .filter_map(|(subject, values)| {
+            let mut variants = Vec::<Variant>::new();
+            for (index, value) in values.into_iter().enumerate() {
+                match variants.iter_mut().find(|variant| variant.value == value) {
+                    Some(variant) => variant.orderings.push(index),
+                    None => variants.push(Variant {
+                        value,
+                        orderings: vec![index],
+                    }),
+                }
+            }
+            (variants.len() > 1).then_some(Difference { subject, variants })
+        })
+        .collect()
+}
+
+/// The rendered return value recorded in a base64 `TransactionMeta`.
+fn return_value(meta_xdr: &str) -> Result<Option<String>, String> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(meta_xdr)
+        .map_err(|e| format!("Invalid result meta: {e}"))?;
+    let meta = xdr_limits::read::<TransactionMeta>(&bytes)
+        .map_err(|e| format!("Invalid result meta: {e}"))?;
+    Ok(match meta {
+        TransactionMeta::V4(meta) => meta.soroban_meta.and_then(|soroban| soroban.return_value),
+        TransactionMeta::V3(meta) => meta.soroban_meta.map(|soroban| soroban.return_value),
+        _ => None,
+    }
+    .map(|value| pretty::value(&value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ordering(order: &[&str], statuses: &[&str], balance: i128) -> OrderingOutcome {
+        OrderingOutcome {
+            order: order.iter().map(|name| name.to_string()).collect(),
+            transactions: order
+                .iter()
+                .zip(statuses)
+                .map(|(name, status)| TransactionOutcome {
+                    name: name.to_string(),
+                    status: status.to_string(),
+                    error: (*status == "error").then(|| "Error(Contract, #3)\nmore".to_string()),
+                    return_value: None,
+                })
+                .collect(),
+            balance_changes: vec![BalanceChange {
+                address: "GALICE".to_string(),
+                token: "CTOKEN".to_string(),
+                asset: None,
+                amount: balance.to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_orders_cover_small_batches_and_sample_large_ones() {
+        assert_eq!(orders(3).len(), 6);
+        assert_eq!(orders(3)[1], [0, 2, 1]);
+        assert_eq!(orders(EXHAUSTIVE).len(), 120);
+        let sample = orders(7);
+        assert_eq!(sample.len(), 8);
+        assert_eq!(sample[1], [6, 5, 4, 3, 2, 1, 0]);
+        assert_eq!(sample[7], [6, 0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_differences_group_orders_by_outcome() {
+        let names = ["buy".to_string(), "sell".to_string()];
+        let orderings = [
+            ordering(&["buy", "sell"], &["success", "success"], 5),
+            ordering(&["sell", "buy"], &["error", "success"], 5),
+        ];
+        assert_eq!(
+            differences(&names, &orderings),
+            [Difference {
+                subject: "sell status".to_string(),
+                variants: vec![
+                    Variant {
+                        value: "success".to_string(),
+                        orderings: vec![0]
+                    },
+                    Variant {
+                        value: "error: Error(Contract, #3)".to_string(),
+                        orderings: vec![1]
+                    },
+                ],
+            }]
+        );
+
+        let orderings = [
+            ordering(&["buy", "sell"], &["success", "success"], 5),
+            ordering(&["sell", "buy"], &["success", "success"], 7),
+        ];
+        let found = differences(&names, &orderings);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].subject, "balance of GALICE in CTOKEN");
+    }
+}
@@ -0,0 +1,287 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! One-line, human-readable rendering of host function calls.
+//!
+//! An invocation reads like the call it is:
+//! `CBXK…WXYZ::transfer(from=GABC…DEFG, to=GDEF…HIJK, amount=100_0000000)`.
+//! Addresses are strkeys shortened to their first and last four characters,
+//! 128-bit integers separate the 7 decimal places Stellar assets use, and
+//! arguments are named after the function's inputs in the contract spec
+//! when the contract's WASM is in the ledger state. Without it, arguments are
+//! positional.
+
+use crate::network;
+use crate::source::LayeredSource;
+use crate::spec;
+use soroban_env_host::xdr::{
+    ContractDataDurability, ContractExecutable, HostFunction, InvokeContractArgs, LedgerEntryData,
+    LedgerKey, LedgerKeyContractCode, LedgerKeyContractData, ScAddress, ScVal,
+};
+
+/// Decimal places of Stellar asset amounts.
+const AMOUNT_DECIMALS: usize = 7;
+/// Byte strings longer than this are shortened.
+const MAX_BYTES: usize = 32;
+
+/// Renders `function`, naming contract call arguments `input_names` when
+/// given one per argument.
+pub fn host_function(function: &HostFunction, input_names: Option<&[String]>) -> String {
+    match function {
+        HostFunction::InvokeContract(args) => invocation(args, input_names),
+        HostFunction::CreateContract(args) => create_contract(&args.executable, &[]),
+        HostFunction::CreateContractV2(args) => {
+            create_contract(&args.executable, &args.constructor_args)
+        }
+        HostFunction::UploadContractWasm(code) => {
+            format!("upload_contract_wasm({} bytes)", code.len())
+        }
+    }
+}
+
+/// Renders a contract call as `contract::function(name=value, ...)`.
+pub fn invocation(args: &InvokeContractArgs, input_names: Option<&[String]>) -> String {
+    let names = input_names.filter(|names| names.len() == args.args.len());
+    let rendered = args
+        .args
+        .iter()
+        .enumerate()
+        .map(|(i, arg)| match names {
+            Some(names) => format!("{}={}", names[i], value(arg)),
+            None => value(arg),
+        })
+        .collect::<Vec<_>>();
+    format!(
+        "{}::{}({})",
+        address(&args.contract_address),
+        args.function_name.to_utf8_string_lossy(),
+        rendered.join(", ")
+    )
+}
+
+fn create_contract(executable: &ContractExecutable, constructor_args: &[ScVal]) -> String {
+    let executable = match executable {
+        ContractExecutable::Wasm(hash) => format!("wasm={}", short(&network::hex(&hash.0))),
+        ContractExecutable::StellarAsset => "stellar_asset".to_string(),
+    };
+    if constructor_args.is_empty() {
+        return format!("create_contract({executable})");
+    }
+    let args = constructor_args.iter().map(value).collect::<Vec<_>>();
+    format!("create_contract({executable}, args=[{}])", args.join(", "))
+}
+
+/// Renders `val` compactly, e.g. `[1, "a", GABC…DEFG]`.
+pub fn value(val: &ScVal) -> String {
+    match val {
+        ScVal::Bool(b) => b.to_string(),
+        ScVal::Void => "()".to_string(),
+        ScVal::Error(e) => format!("Error({e:?})"),
+        ScVal::U32(n) => n.to_string(),
+        ScVal::I32(n) => n.to_string(),
+        ScVal::U64(n) => n.to_string(),
+        ScVal::I64(n) => n.to_string(),
+        ScVal::Timepoint(t) => format!("Timepoint({})", t.0),
+        ScVal::Duration(d) => format!("Duration({})", d.0),
+        ScVal::U128(parts) => {
+            amount(&((u128::from(parts.hi) << 64) | u128::from(parts.lo)).to_string())
+        }
+        ScVal::I128(parts) => {
+            amount(&((i128::from(parts.hi) << 64) | i128::from(parts.lo)).to_string())
+        }
+        ScVal::U256(parts) => format!(
+            "0x{:016x}{:016x}{:016x}{:016x}",
+            parts.hi_hi, parts.hi_lo, parts.lo_hi, parts.lo_lo
+        ),
+        ScVal::I256(parts) => format!(
+            "0x{:016x}{:016x}{:016x}{:016x}",
+            parts.hi_hi, parts.hi_lo, parts.lo_hi, parts.lo_lo
+        ),
+        ScVal::Bytes(bytes) => {
+            if bytes.len() <= MAX_BYTES {
+                format!("0x{}", network::hex(bytes.as_slice()))
+            } else {
+                format!(
+                    "0x{}… ({} bytes)",
+                    network::hex(&bytes[..MAX_BYTES / 2]),
+                    bytes.len()
+                )
+            }
+        }
+        ScVal::String(s) => format!("{:?}", s.0.to_utf8_string_lossy()),
+        ScVal::Symbol(s) => s.to_utf8_string_lossy(),
+        ScVal::Vec(items) => {
+            let items = items.iter().flat_map(|v| v.iter()).map(value);
+            format!("[{}]", items.collect::<Vec<_>>().join(", "))
+        }
+        ScVal::Map(entries) => {
+            let entries = entries
+                .iter()
+                .flat_map(|m| m.iter())
+                .map(|entry| format!("{}: {}", value(&entry.key), value(&entry.val)));
+            format!("{{{}}}", entries.collect::<Vec<_>>().join(", "))
+        }
+        ScVal::Address(a) => address(a),
+        ScVal::ContractInstance(_) => "ContractInstance".to_string(),
+        ScVal::LedgerKeyContractInstance => "LedgerKeyContractInstance".to_string(),
+        ScVal::LedgerKeyNonce(nonce) => format!("LedgerKeyNonce({})", nonce.nonce),
+    }
+}
+
+/// The strkey of `address`, shortened.
+pub fn address(address: &ScAddress) -> String {
+    short(&address.to_string())
+}
+
+/// `s` cut to its first and last four characters, e.g. a strkey.
+pub fn short(s: &str) -> String {
+    let chars = s.chars().collect::<Vec<_>>();
+    if chars.len() <= 12 {
+        return s.to_string();
+    }
+    let head = chars[..4].iter().collect::<String>();
+    let tail = chars[chars.len() - 4..].iter().collect::<String>();
+    format!("{head}…{tail}")
+}
+
+/// Separates the decimal places of an asset amount: `1000000000` is
+/// `100_0000000`.
+fn amount(digits: &str) -> String {
+    let (sign, digits) = match digits.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", digits),
+    };
+    if digits.len() <= AMOUNT_DECIMALS {
+        return format!("{sign}{digits}");
+    }
+    let (whole, fraction) = digits.split_at(digits.len() - AMOUNT_DECIMALS);
+    format!("{sign}{whole}_{fraction}")
+}
+
+/// The spec of the WASM of `contract` in `state`. Looks the entries up
+/// without recording them as reads of the simulation.
+pub fn contract_spec(contract: &ScAddress, state: &LayeredSource) -> Option<spec::ContractSpec> {
+    let instance = state
+        .peek(&LedgerKey::ContractData(LedgerKeyContractData {
+            contract: contract.clone(),
+            key: ScVal::LedgerKeyContractInstance,
+            durability: ContractDataDurability::Persistent,
+        }))
+        .ok()??;
+    let LedgerEntryData::ContractData(data) = instance.entry.data else {
+        return None;
+    };
+    let ScVal::ContractInstance(instance) = data.val else {
+        return None;
+    };
+    let ContractExecutable::Wasm(hash) = instance.executable else {
+        return None;
+    };
+    let code = state.peek_code(&hash).ok()??;
+    spec::from_wasm(&code.wasm).ok()
+}
+
+/// Names of the inputs of the function `args` calls, from the spec of the
+/// contract's WASM in `state`.
+pub fn input_names(args: &InvokeContractArgs, state: &LayeredSource) -> Option<Vec<String>> {
+    let function_name = args.function_name.to_utf8_string_lossy();
+    contract_spec(&args.contract_address, state)?
+        .functions
+        .into_iter()
+        .find(|function| function.name == function_name)
+        .map(|function| {
+            function
+                .inputs
+                .into_iter()
+                .map(|input| input.name)
+                .collect()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::xdr::{
+        AccountId, ContractId, Hash, Int128Parts, PublicKey, ScBytes, ScVec, Uint256,
+    };
+
+    fn contract() -> ScAddress {
+        ScAddress::Contract(ContractId(Hash([7; 32])))
+    }
+
+    fn account() -> ScAddress {
+        ScAddress::Account(AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([1; 32]))))
+    }
+
+    fn i128(n: i128) -> ScVal {
+        ScVal::I128(Int128Parts {
+            hi: (n >> 64) as i64,
+            lo: n as u64,
+        })
+    }
+
+    fn transfer() -> InvokeContractArgs {
+        InvokeContractArgs {
+            contract_address: contract(),
+            function_name: "transfer".try_into().unwrap(),
+            args: vec![ScVal::Address(account()), i128(1_000_000_000)]
+                .try_into()
+                .unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_invocation_names_arguments() {
+        let names = vec!["from".to_string(), "amount".to_string()];
+        let rendered = invocation(&transfer(), Some(&names));
+        let contract = contract().to_string();
+        let account = account().to_string();
+        assert_eq!(
+            rendered,
+            format!(
+                "{}…{}::transfer(from={}…{}, amount=100_0000000)",
+                &contract[..4],
+                &contract[contract.len() - 4..],
+                &account[..4],
+                &account[account.len() - 4..],
+            )
+        );
+    }
+
+    #[test]
+    fn test_invocation_without_matching_names_is_positional() {
+        let names = vec!["from".to_string()];
+        let rendered = invocation(&transfer(), Some(&names));
+        assert!(!rendered.contains('='));
+        assert!(rendered.ends_with(", 100_0000000)"));
+    }
+
+    #[test]
+    fn test_values() {
+        assert_eq!(value(&i128(-25)), "-25");
+        assert_eq!(value(&i128(-12_345_678_901)), "-1234_5678901");
+        assert_eq!(
+            value(&ScVal::Vec(Some(ScVec(
+                vec![ScVal::U32(1), ScVal::Symbol("hi".try_into().unwrap())]
+                    .try_into()
+                    .unwrap()
+            )))),
+            "[1, hi]"
+        );
+        assert_eq!(
+            value(&ScVal::String(soroban_env_host::xdr::ScString(
+                "a\"b".try_into().unwrap()
+            ))),
+            r#""a\"b""#
+        );
+        let long = ScVal::Bytes(ScBytes(vec![0xab; 40].try_into().unwrap()));
+        assert_eq!(value(&long), format!("0x{}… (40 bytes)", "ab".repeat(16)));
+        assert_eq!(
+            host_function(
+                &HostFunction::UploadContractWasm(vec![0; 10].try_into().unwrap()),
+                None
+            ),
+            "upload_contract_wasm(10 bytes)"
+        );
+    }
+}
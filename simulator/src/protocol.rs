@@ -0,0 +1,309 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Protocol configurations a request is simulated under.
+//!
+//! By default a simulation uses the network preset's protocol version and
+//! TTL settings and the host's built-in cost model. A request's `protocol`
+//! replaces any of them. With `compare_protocol`, the request runs a second
+//! time under another configuration, for example the cost parameters voted
+//! for an upcoming upgrade, and the response reports what changed.
+//!
+//! Stepped simulations (see `step_instructions`) run under the same costs
+//! with a lower CPU limit. The host only takes a limit together with cost
+//! parameters, so for its built-in model the parameters are read back by
+//! charging each cost type once.
+
+use crate::gas_optimizer::{CPU_LIMIT, MEMORY_LIMIT};
+use crate::hosts;
+use crate::network::Network;
+use crate::simulation::SimulationError;
+use crate::types::ProtocolConfig;
+use crate::xdr_limits;
+use base64::Engine as _;
+use soroban_env_host::budget::Budget;
+use soroban_env_host::xdr::{
+    ContractCostParamEntry, ContractCostParams, ContractCostType, ExtensionPoint,
+};
+use soroban_env_host::LedgerInfo;
+
+/// Protocol version simulated on `network` under `config`.
+pub fn version(network: &Network, config: Option<&ProtocolConfig>) -> u32 {
+    config
+        .and_then(|config| config.protocol_version)
+        .unwrap_or(network.preset.protocol_version)
+}
+
+/// Rejects a `version` the linked host cannot run. Only one host is linked,
+/// and it refuses ledgers of other protocols with a generic internal error.
+pub fn check_version(version: u32) -> Result<(), SimulationError> {
+    let meta = hosts::response_meta();
+    if (meta.min_protocol..=meta.max_protocol).contains(&version) {
+        return Ok(());
+    }
+    let supported = if meta.min_protocol == meta.max_protocol {
+        meta.max_protocol.to_string()
+    } else {
+        format!("{} to {}", meta.min_protocol, meta.max_protocol)
+    };
+    Err(SimulationError::InvalidRequest(format!(
+        "protocol_version {version} is not supported: soroban-env-host {} runs protocol {supported}",
+        meta.host_version
+    )))
+}
+
+/// Applies `config`'s ledger settings to `info`.
+pub fn ledger_info(config: Option<&ProtocolConfig>, info: LedgerInfo) -> LedgerInfo {
+    let Some(config) = config else {
+        return info;
+    };
+    LedgerInfo {
+        protocol_version: config.protocol_version.unwrap_or(info.protocol_version),
+        base_reserve: config.base_reserve.unwrap_or(info.base_reserve),
+        min_temp_entry_ttl: config.min_temp_entry_ttl.unwrap_or(info.min_temp_entry_ttl),
+        min_persistent_entry_ttl: config
+            .min_persistent_entry_ttl
+            .unwrap_or(info.min_persistent_entry_ttl),
+        max_entry_ttl: config.max_entry_ttl.unwrap_or(info.max_entry_ttl),
+        ..info
+    }
+}
+
+/// Budget charging `config`'s cost parameters, or the host's defaults.
+pub fn budget(config: Option<&ProtocolConfig>) -> Result<Budget, SimulationError> {
+    let params = config.map(|c| {
+        (
+            c.cpu_cost_params.as_deref(),
+            c.memory_cost_params.as_deref(),
+        )
+    });
+    match params {
+        None | Some((None, None)) => Ok(Budget::default()),
+        Some((Some(cpu), Some(memory))) => Budget::try_from_configs(
+            CPU_LIMIT,
+            MEMORY_LIMIT,
+            decode_params("cpu_cost_params", cpu)?,
+            decode_params("memory_cost_params", memory)?,
+        )
+        .map_err(|e| SimulationError::InvalidRequest(format!("Invalid cost parameters: {e:?}"))),
+        Some(_) => Err(SimulationError::InvalidRequest(
+            "cpu_cost_params and memory_cost_params must be given together".to_string(),
+        )),
+    }
+}
+
+/// Budget like [`budget`] that allows `cpu_limit` CPU instructions and
+/// `memory_limit` bytes. Limits left as `None` stay as [`budget`] has them.
+/// The shadow budget, which pays for the host's own work such as compiling
+/// contracts, keeps its usual limits so that only the transaction stops.
+pub fn budget_with_limits(
+    config: Option<&ProtocolConfig>,
+    cpu_limit: Option<u64>,
+    memory_limit: Option<u64>,
+) -> Result<Budget, SimulationError> {
+    let params = config.and_then(|c| {
+        c.cpu_cost_params
+            .as_deref()
+            .zip(c.memory_cost_params.as_deref())
+    });
+    let (cpu_params, memory_params, shadow_memory_limit) = match params {
+        Some((cpu, memory)) => (
+            decode_params("cpu_cost_params", cpu)?,
+            decode_params("memory_cost_params", memory)?,
+            MEMORY_LIMIT,
+        ),
+        None => {
+            // Rejects a lone cpu_cost_params or memory_cost_params.
+            budget(config)?;
+            let (cpu, memory) = default_cost_params();
+            let memory_limit = Budget::default()
+                .get_mem_bytes_remaining()
+                .unwrap_or(MEMORY_LIMIT);
+            (cpu, memory, memory_limit)
+        }
+    };
+    let shadow_cpu_limit = self::cpu_limit(config)?;
+    let cpu_limit = cpu_limit.unwrap_or(shadow_cpu_limit);
+    Budget::try_from_configs_with_shadow_limits(
+        cpu_limit,
+        memory_limit.unwrap_or(shadow_memory_limit),
+        cpu_limit.max(shadow_cpu_limit),
+        shadow_memory_limit,
+        cpu_params,
+        memory_params,
+    )
+    .map_err(|e| SimulationError::InvalidRequest(format!("Invalid cost parameters: {e:?}")))
+}
+
+/// CPU instructions a budget from [`budget`] allows.
+pub fn cpu_limit(config: Option<&ProtocolConfig>) -> Result<u64, SimulationError> {
+    Ok(budget(config)?
+        .get_cpu_insns_remaining()
+        .unwrap_or(CPU_LIMIT))
+}
+
+/// The host's built-in CPU and memory cost parameters. Each cost type is
+/// charged on a fresh budget at input 0 for its constant term, then at
+/// input 128, where the linear term's 7-bit scaling cancels out. Constant
+/// cost types reject an input and are charged without one.
+fn default_cost_params() -> (ContractCostParams, ContractCostParams) {
+    let measure = |ty: ContractCostType, input: Option<u64>| {
+        let budget = Budget::default();
+        budget.charge(ty, input).ok()?;
+        Some((
+            budget.get_cpu_insns_consumed().ok()?,
+            budget.get_mem_bytes_consumed().ok()?,
+        ))
+    };
+    let entry = |const_term: u64, linear_term: u64| ContractCostParamEntry {
+        ext: ExtensionPoint::V0,
+        const_term: i64::try_from(const_term).unwrap_or(i64::MAX),
+        linear_term: i64::try_from(linear_term).unwrap_or(i64::MAX),
+    };
+    let (cpu, memory): (Vec<_>, Vec<_>) = ContractCostType::VARIANTS
+        .iter()
+        .map(|&ty| {
+            let (base, scaled) = match (measure(ty, Some(0)), measure(ty, Some(128))) {
+                (Some(base), Some(scaled)) => (base, scaled),
+                _ => {
+                    let base = measure(ty, None).unwrap_or_default();
+                    (base, base)
+                }
+            };
+            (
+                entry(base.0, scaled.0 - base.0),
+                entry(base.1, scaled.1 - base.1),
+            )
+        })
+        .unzip();
+    (
+        ContractCostParams(cpu.try_into().unwrap_or_default()),
+        ContractCostParams(memory.try_into().unwrap_or_default()),
+    )
+}
+
+fn decode_params(field: &str, params: &str) -> Result<ContractCostParams, SimulationError> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(params)
+        .map_err(|e| SimulationError::InvalidRequest(format!("Failed to decode {field}: {e}")))?;
+    xdr_limits::read::<ContractCostParams>(&bytes).map_err(|e| {
+        if xdr_limits::is_exceeded(&e) {
+            SimulationError::XdrLimitsExceeded(format!("{field}: {e}"))
+        } else {
+            SimulationError::InvalidRequest(format!("Failed to parse {field}: {e}"))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::xdr::{Limits, WriteXdr};
+
+    fn info() -> LedgerInfo {
+        LedgerInfo {
+            protocol_version: 25,
+            sequence_number: 10,
+            timestamp: 0,
+            network_id: [0; 32],
+            base_reserve: 5_000_000,
+            min_temp_entry_ttl: 16,
+            min_persistent_entry_ttl: 120_960,
+            max_entry_ttl: 3_110_400,
+        }
+    }
+
+    #[test]
+    fn test_ledger_info_overrides_only_given_fields() {
+        let config = ProtocolConfig {
+            protocol_version: Some(24),
+            max_entry_ttl: Some(1_000),
+            ..ProtocolConfig::default()
+        };
+        let applied = ledger_info(Some(&config), info());
+        assert_eq!(applied.protocol_version, 24);
+        assert_eq!(applied.max_entry_ttl, 1_000);
+        assert_eq!(applied.min_temp_entry_ttl, 16);
+        assert_eq!(applied.sequence_number, 10);
+        assert_eq!(ledger_info(None, info()).protocol_version, 25);
+    }
+
+    #[test]
+    fn test_check_version_accepts_only_the_linked_host() {
+        assert!(check_version(25).is_ok());
+        for version in [24, 26] {
+            let Err(SimulationError::InvalidRequest(msg)) = check_version(version) else {
+                panic!("protocol {version} accepted");
+            };
+            assert!(
+                msg.contains(&format!("protocol_version {version}")),
+                "{msg}"
+            );
+            assert!(msg.ends_with("runs protocol 25"), "{msg}");
+        }
+    }
+
+    #[test]
+    fn test_budget_requires_both_cost_params() {
+        assert!(budget(None).is_ok());
+        let params = ContractCostParams(
+            vec![ContractCostParamEntry {
+                ext: ExtensionPoint::V0,
+                const_term: 4,
+                linear_term: 0,
+            }]
+            .try_into()
+            .unwrap(),
+        )
+        .to_xdr_base64(Limits::none())
+        .unwrap();
+        let cpu_only = ProtocolConfig {
+            cpu_cost_params: Some(params.clone()),
+            ..ProtocolConfig::default()
+        };
+        assert!(matches!(
+            budget(Some(&cpu_only)),
+            Err(SimulationError::InvalidRequest(_))
+        ));
+        let both = ProtocolConfig {
+            memory_cost_params: Some(params),
+            ..cpu_only
+        };
+        assert!(budget(Some(&both)).is_ok());
+        let corrupt = ProtocolConfig {
+            memory_cost_params: Some("AAAA".into()),
+            ..both
+        };
+        assert!(budget(Some(&corrupt)).is_err());
+    }
+
+    #[test]
+    fn test_cpu_limited_budget_charges_the_default_costs() {
+        let limited = budget_with_limits(None, Some(50_000_000), None).unwrap();
+        let default = Budget::default();
+        for (ty, input) in [
+            (ContractCostType::WasmInsnExec, None),
+            (ContractCostType::ComputeSha256Hash, Some(1_000)),
+            (ContractCostType::VmInstantiation, Some(50_000)),
+        ] {
+            limited.charge(ty, input).unwrap();
+            default.charge(ty, input).unwrap();
+        }
+        assert_eq!(
+            limited.get_cpu_insns_consumed().unwrap(),
+            default.get_cpu_insns_consumed().unwrap()
+        );
+        assert_eq!(
+            limited.get_mem_bytes_consumed().unwrap(),
+            default.get_mem_bytes_consumed().unwrap()
+        );
+        assert_eq!(
+            limited.get_cpu_insns_remaining().unwrap(),
+            50_000_000 - limited.get_cpu_insns_consumed().unwrap()
+        );
+        assert_eq!(
+            cpu_limit(None).unwrap(),
+            Budget::default().get_cpu_insns_remaining().unwrap()
+        );
+    }
+}
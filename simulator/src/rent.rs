@@ -0,0 +1,357 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Rent portion of the resource fee.
+//!
+//! Contract data and code pay rent for every ledger they are kept live. A
+//! transaction pays when it extends an entry's TTL, for the added ledgers at
+//! the entry's new size, and when it grows an entry, for the ledgers already
+//! paid for at the old size. Entries it creates or restores pay for their
+//! whole initial TTL. [`rent`] prices each entry with the host's own
+//! [`compute_rent_fee`], the way stellar-core charges it.
+//!
+//! The rent parameters are network settings. They are read from the
+//! `ConfigSetting` entries of the simulated state (request entries, base
+//! snapshot or remote source); settings found nowhere fall back to
+//! [`RentParams::default`], which approximates pubnet.
+
+use crate::resources::{self, xdr_len};
+use crate::source::{self, SourceEntry};
+use schemars::JsonSchema;
+use serde::Serialize;
+use soroban_env_host::budget::Budget;
+use soroban_env_host::e2e_invoke::entry_size_for_rent;
+use soroban_env_host::fees::{
+    compute_rent_fee, compute_rent_write_fee_per_1kb, LedgerEntryRentChange, RentFeeConfiguration,
+    RentWriteFeeConfiguration,
+};
+use soroban_env_host::storage::Storage;
+use soroban_env_host::xdr::{
+    ConfigSettingEntry, ConfigSettingId, LedgerEntry, LedgerKey, Limits, WriteXdr,
+};
+
+/// Network settings rent is computed from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct RentParams {
+    pub fee_per_write_1kb: i64,
+    pub fee_per_write_entry: i64,
+    pub persistent_rent_rate_denominator: i64,
+    pub temporary_rent_rate_denominator: i64,
+    pub soroban_state_target_size_bytes: i64,
+    pub rent_fee_1kb_state_size_low: i64,
+    pub rent_fee_1kb_state_size_high: i64,
+    pub state_size_rent_fee_growth_factor: u32,
+    /// Average of the live Soroban state size window.
+    pub soroban_state_size_bytes: i64,
+}
+
+impl Default for RentParams {
+    fn default() -> Self {
+        Self {
+            fee_per_write_1kb: 3_500,
+            fee_per_write_entry: 10_000,
+            persistent_rent_rate_denominator: 1_215,
+            temporary_rent_rate_denominator: 2_430,
+            soroban_state_target_size_bytes: 3_000_000_000,
+            rent_fee_1kb_state_size_low: -17_000,
+            rent_fee_1kb_state_size_high: 10_000,
+            state_size_rent_fee_growth_factor: 5_000,
+            soroban_state_size_bytes: 1_000_000_000,
+        }
+    }
+}
+
+impl RentParams {
+    /// Reads the parameters from the network's `ConfigSetting` entries.
+    /// Returns whether every setting was found.
+    pub fn from_state(lookup: impl Fn(&LedgerKey) -> Option<SourceEntry>) -> (Self, bool) {
+        let setting = |id| source::config_setting(id, &lookup);
+        let mut params = Self::default();
+        let mut found = 0;
+        if let Some(ConfigSettingEntry::ContractLedgerCostV0(cost)) =
+            setting(ConfigSettingId::ContractLedgerCostV0)
+        {
+            params.fee_per_write_entry = cost.fee_write_ledger_entry;
+            params.soroban_state_target_size_bytes = cost.soroban_state_target_size_bytes;
+            params.rent_fee_1kb_state_size_low = cost.rent_fee1_kb_soroban_state_size_low;
+            params.rent_fee_1kb_state_size_high = cost.rent_fee1_kb_soroban_state_size_high;
+            params.state_size_rent_fee_growth_factor = cost.soroban_state_rent_fee_growth_factor;
+            found += 1;
+        }
+        if let Some(ConfigSettingEntry::ContractLedgerCostExtV0(ext)) =
+            setting(ConfigSettingId::ContractLedgerCostExtV0)
+        {
+            params.fee_per_write_1kb = ext.fee_write1_kb;
+            found += 1;
+        }
+        if let Some(ConfigSettingEntry::StateArchival(archival)) =
+            setting(ConfigSettingId::StateArchival)
+        {
+            params.persistent_rent_rate_denominator = archival.persistent_rent_rate_denominator;
+            params.temporary_rent_rate_denominator = archival.temp_rent_rate_denominator;
+            found += 1;
+        }
+        if let Some(ConfigSettingEntry::LiveSorobanStateSizeWindow(window)) =
+            setting(ConfigSettingId::LiveSorobanStateSizeWindow)
+        {
+            if !window.is_empty() {
+                let total = window.iter().map(|&size| u128::from(size)).sum::<u128>();
+                let average = total / window.len() as u128;
+                params.soroban_state_size_bytes = i64::try_from(average).unwrap_or(i64::MAX);
+                found += 1;
+            }
+        }
+        (params, found == 4)
+    }
+
+    fn fee_configuration(&self) -> RentFeeConfiguration {
+        let fee_per_rent_1kb = compute_rent_write_fee_per_1kb(
+            self.soroban_state_size_bytes,
+            &RentWriteFeeConfiguration {
+                state_target_size_bytes: self.soroban_state_target_size_bytes,
+                rent_fee_1kb_state_size_low: self.rent_fee_1kb_state_size_low,
+                rent_fee_1kb_state_size_high: self.rent_fee_1kb_state_size_high,
+                state_size_rent_fee_growth_factor: self.state_size_rent_fee_growth_factor,
+            },
+        );
+        RentFeeConfiguration {
+            fee_per_write_1kb: self.fee_per_write_1kb,
+            fee_per_rent_1kb,
+            fee_per_write_entry: self.fee_per_write_entry,
+            persistent_rent_rate_denominator: self.persistent_rent_rate_denominator,
+            temporary_rent_rate_denominator: self.temporary_rent_rate_denominator,
+        }
+    }
+}
+
+/// Rent a simulated transaction pays.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct RentBreakdown {
+    /// Total rent fee in stroops, as charged for all entries together.
+    pub total_fee: i64,
+    /// Rent fee per 1 KB at the current Soroban state size.
+    pub fee_per_rent_1kb: i64,
+    /// Parameters the fee was computed with.
+    pub params: RentParams,
+    /// Whether every parameter was read from the ledger rather than
+    /// defaulted.
+    pub params_from_ledger: bool,
+    /// Entries that pay rent, in footprint order.
+    pub entries: Vec<EntryRent>,
+}
+
+/// Rent one entry pays.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct EntryRent {
+    /// Base64 `LedgerKey`.
+    pub key: String,
+    /// `ContractData` or `ContractCode`.
+    pub entry_type: String,
+    pub persistent: bool,
+    /// Size rent is charged for before the transaction (the in-memory module
+    /// size is added for contract code); 0 for new or restored entries.
+    pub old_size_bytes: u32,
+    pub new_size_bytes: u32,
+    /// 0 for new or restored entries.
+    pub old_live_until: u32,
+    pub new_live_until: u32,
+    /// Fee in stroops, including writing the extended TTL.
+    pub fee: i64,
+}
+
+/// Computes the rent of the execution that left `storage`, from the state
+/// `before` it at `ledger_sequence`, under `params`.
+pub fn rent(
+    storage: &Storage,
+    before: impl Fn(&LedgerKey) -> Option<SourceEntry>,
+    params: RentParams,
+    params_from_ledger: bool,
+    ledger_sequence: u32,
+) -> Result<RentBreakdown, String> {
+    let budget = Budget::default();
+    let size_for_rent = |entry: &LedgerEntry| {
+        entry_size_for_rent(&budget, entry, xdr_len(entry)?).map_err(|e| format!("{e:?}"))
+    };
+    let config = params.fee_configuration();
+    let entries = storage.map.iter(&budget).map_err(|e| format!("{e:?}"))?;
+    let mut changes = Vec::new();
+    let mut items = Vec::new();
+    for (key, after) in entries {
+        // Removed entries and entries without a TTL pay no rent.
+        let Some((entry, Some(new_live_until))) = after else {
+            continue;
+        };
+        let old = before(key).filter(|old| {
+            old.live_until
+                .is_some_and(|live_until| live_until >= ledger_sequence)
+        });
+        let (old_size_bytes, old_live_until) = match &old {
+            Some(old) => (size_for_rent(&old.entry)?, old.live_until.unwrap_or(0)),
+            None => (0, 0),
+        };
+        let new_size_bytes = size_for_rent(entry)?;
+        if *new_live_until <= old_live_until && new_size_bytes <= old_size_bytes {
+            continue;
+        }
+        let change = LedgerEntryRentChange {
+            is_persistent: resources::is_persistent(key),
+            is_code_entry: matches!(key.as_ref(), LedgerKey::ContractCode(_)),
+            old_size_bytes,
+            new_size_bytes,
+            old_live_until_ledger: old_live_until,
+            new_live_until_ledger: *new_live_until,
+        };
+        items.push(EntryRent {
+            key: key
+                .to_xdr_base64(Limits::none())
+                .map_err(|e| e.to_string())?,
+            entry_type: key.name().to_string(),
+            persistent: change.is_persistent,
+            old_size_bytes,
+            new_size_bytes,
+            old_live_until,
+            new_live_until: *new_live_until,
+            fee: compute_rent_fee(std::slice::from_ref(&change), &config, ledger_sequence),
+        });
+        changes.push(change);
+    }
+    Ok(RentBreakdown {
+        total_fee: compute_rent_fee(&changes, &config, ledger_sequence),
+        fee_per_rent_1kb: config.fee_per_rent_1kb,
+        params,
+        params_from_ledger,
+        entries: items,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::storage::{AccessType, Footprint, FootprintMap, StorageMap};
+    use soroban_env_host::xdr::{
+        ConfigSettingContractLedgerCostExtV0, ContractDataDurability, ContractDataEntry,
+        ContractId, ExtensionPoint, Hash, LedgerEntryData, LedgerEntryExt, LedgerKeyConfigSetting,
+        LedgerKeyContractData, ScAddress, ScVal,
+    };
+    use std::rc::Rc;
+
+    fn data_key(name: &str, durability: ContractDataDurability) -> LedgerKey {
+        LedgerKey::ContractData(LedgerKeyContractData {
+            contract: ScAddress::Contract(ContractId(Hash([7; 32]))),
+            key: ScVal::Symbol(name.try_into().unwrap()),
+            durability,
+        })
+    }
+
+    fn data_entry(key: &LedgerKey, bytes: usize) -> LedgerEntry {
+        let LedgerKey::ContractData(key) = key else {
+            unreachable!()
+        };
+        LedgerEntry {
+            last_modified_ledger_seq: 10,
+            data: LedgerEntryData::ContractData(ContractDataEntry {
+                ext: ExtensionPoint::V0,
+                contract: key.contract.clone(),
+                key: key.key.clone(),
+                durability: key.durability,
+                val: ScVal::Bytes(vec![0; bytes].try_into().unwrap()),
+            }),
+            ext: LedgerEntryExt::V0,
+        }
+    }
+
+    #[test]
+    fn test_rent_prices_extensions_growth_and_new_entries() {
+        let budget = Budget::default();
+        let persistent = ContractDataDurability::Persistent;
+        let extended = data_key("extended", persistent);
+        let grown = data_key("grown", persistent);
+        let created = data_key("created", ContractDataDurability::Temporary);
+        let untouched = data_key("untouched", persistent);
+        // (key, size and live_until before, size and live_until after)
+        let cases = [
+            (&extended, Some((100, 200)), (100, 1_200)),
+            (&grown, Some((100, 200)), (1_100, 200)),
+            (&created, None, (100, 115)),
+            (&untouched, Some((100, 200)), (100, 200)),
+        ];
+        let mut footprint = Vec::new();
+        let mut map = Vec::new();
+        for (key, _, (size, live_until)) in &cases {
+            let key = Rc::new(LedgerKey::clone(key));
+            footprint.push((key.clone(), AccessType::ReadWrite));
+            map.push((
+                key.clone(),
+                Some((Rc::new(data_entry(&key, *size)), Some(*live_until))),
+            ));
+        }
+        footprint.sort_by(|a, b| a.0.cmp(&b.0));
+        map.sort_by(|a, b| a.0.cmp(&b.0));
+        let storage = Storage::with_enforcing_footprint_and_map(
+            Footprint(FootprintMap::from_map(footprint, &budget).unwrap()),
+            StorageMap::from_map(map, &budget).unwrap(),
+        );
+        let before = |key: &LedgerKey| {
+            let (_, before, _) = cases.iter().find(|(k, ..)| *k == key)?;
+            before.map(|(size, live_until)| SourceEntry {
+                entry: data_entry(key, size),
+                live_until: Some(live_until),
+            })
+        };
+
+        let breakdown = rent(&storage, before, RentParams::default(), false, 100).unwrap();
+        let fee = |key: &LedgerKey| {
+            let key = key.to_xdr_base64(Limits::none()).unwrap();
+            breakdown
+                .entries
+                .iter()
+                .find(|e| e.key == key)
+                .map(|e| e.fee)
+        };
+        assert_eq!(breakdown.entries.len(), 3);
+        assert!(fee(&untouched).is_none());
+        assert!(fee(&extended).unwrap() > 0);
+        assert!(fee(&grown).unwrap() > 0);
+        let created_rent = breakdown.entries.iter().find(|e| !e.persistent).unwrap();
+        assert_eq!(
+            (created_rent.old_size_bytes, created_rent.old_live_until),
+            (0, 0)
+        );
+        let sum = breakdown.entries.iter().map(|e| e.fee).sum::<i64>();
+        // Writing the extended TTLs is charged for all entries together.
+        assert!(breakdown.total_fee > 0 && breakdown.total_fee <= sum);
+        assert_eq!(
+            breakdown.fee_per_rent_1kb,
+            RentParams::default().fee_configuration().fee_per_rent_1kb
+        );
+    }
+
+    #[test]
+    fn test_params_read_from_config_settings() {
+        let setting = LedgerEntry {
+            last_modified_ledger_seq: 1,
+            data: LedgerEntryData::ConfigSetting(ConfigSettingEntry::ContractLedgerCostExtV0(
+                ConfigSettingContractLedgerCostExtV0 {
+                    tx_max_footprint_entries: 100,
+                    fee_write1_kb: 42,
+                },
+            )),
+            ext: LedgerEntryExt::V0,
+        };
+        let (params, complete) = RentParams::from_state(|key| match key {
+            LedgerKey::ConfigSetting(LedgerKeyConfigSetting {
+                config_setting_id: ConfigSettingId::ContractLedgerCostExtV0,
+            }) => Some(SourceEntry {
+                entry: setting.clone(),
+                live_until: None,
+            }),
+            _ => None,
+        });
+        assert_eq!(params.fee_per_write_1kb, 42);
+        assert_eq!(
+            params.fee_per_write_entry,
+            RentParams::default().fee_per_write_entry
+        );
+        assert!(!complete);
+    }
+}
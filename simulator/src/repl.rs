@@ -0,0 +1,651 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! `erst-sim repl`: an interactive scratchpad over a local ledger.
+//!
+//! The REPL keeps ledger state in memory, empty or loaded from a snapshot
+//! file, and runs each command against it:
+//!
+//! ```text
+//! > load snapshot.json
+//! > call CA… transfer GA… GB… 100
+//! > storage CA…
+//! > advance 100
+//! > rollback
+//! ```
+//!
+//! `call` simulates a contract call in builder mode and, when it succeeds,
+//! applies its ledger changes to the state, as a scenario step does. The
+//! arguments are read as the types of the function's inputs in the contract
+//! spec when the contract's WASM is in the state, so `100` is an `i128`
+//! amount for a token's `transfer`. A `type:` prefix such as `u32:100` or
+//! `symbol:admin` sets the type explicitly, and `xdr:` passes a base64
+//! `ScVal` as is. Without a spec, strkeys are addresses, `true` and `false`
+//! booleans, numbers `i128`s and other words symbols.
+//!
+//! `advance` moves the ledger forward and deletes the temporary entries
+//! whose TTL runs out. `rollback` undoes the last commands that changed the
+//! state or ledger: `load`, `call` and `advance`.
+
+use crate::memory::MemoryLimits;
+use crate::pretty;
+use crate::scenario::{self, Advance};
+use crate::simulation;
+use crate::snapshot::{self, file::SnapshotFile, LedgerSnapshot};
+use crate::source::{self, LayeredSource, SnapshotLedgerSource};
+use crate::spec::Field;
+use crate::types::SimulationRequest;
+use soroban_env_host::xdr::{
+    ContractDataDurability, Duration, Int128Parts, LedgerEntry, LedgerEntryData, LedgerKey, Limits,
+    ScAddress, ScBytes, ScString, ScSymbol, ScVal, TimePoint, UInt128Parts, WriteXdr,
+};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+const HELP: &str = "\
+load <snapshot.json>              replace the state with a snapshot file
+call <contract> <function> [args] simulate a call and apply its changes
+storage <contract>                list a contract's storage
+advance [ledgers] [seconds]       move the ledger forward (default 1 ledger)
+rollback [commands]               undo load, call and advance (default 1)
+source <account>                  set the source account of calls
+ledger                            show the ledger sequence and close time
+quit                              leave";
+
+/// What a command asks of the loop.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Reply {
+    /// Print this and read the next command.
+    Print(String),
+    Quit,
+}
+
+/// The state commands run against.
+pub struct Session {
+    state: HashMap<String, String>,
+    /// Sequence and close time of the ledger calls run at.
+    ledger: (u32, u64),
+    history: Vec<Checkpoint>,
+    source: Option<String>,
+    network: Option<String>,
+    limits: MemoryLimits,
+}
+
+/// The state before a command changed it.
+struct Checkpoint {
+    command: String,
+    state: HashMap<String, String>,
+    ledger: (u32, u64),
+}
+
+impl Session {
+    /// An empty ledger on `network` (the default preset when `None`), with
+    /// calls sent by `source` (the all-zero account when `None`).
+    pub fn new(network: Option<String>, source: Option<String>, limits: MemoryLimits) -> Self {
+        Self {
+            state: HashMap::new(),
+            ledger: (0, simulation::ledger_timestamp()),
+            history: Vec::new(),
+            source,
+            network,
+            limits,
+        }
+    }
+
+    /// Runs one command line.
+    pub fn execute(&mut self, line: &str) -> Result<Reply, String> {
+        let words = words(line)?;
+        let Some((command, args)) = words.split_first() else {
+            return Ok(Reply::Print(String::new()));
+        };
+        let text = match (command.as_str(), args) {
+            ("help", []) => HELP.to_string(),
+            ("quit" | "exit", []) => return Ok(Reply::Quit),
+            ("load", [path]) => self.load(Path::new(path))?,
+            ("call", [contract, function, args @ ..]) => self.call(contract, function, args)?,
+            ("storage", [contract]) => self.storage(contract)?,
+            ("advance", by) if by.len() <= 2 => self.advance(by)?,
+            ("rollback", []) => self.rollback(1)?,
+            ("rollback", [count]) => self.rollback(parse(count)?)?,
+            ("source", [account]) => {
+                self.source = Some(account.clone());
+                format!("Calls are sent by {account}")
+            }
+            ("ledger", []) => format!(
+                "Ledger {}, closed at {}, {} entries",
+                self.ledger.0,
+                self.ledger.1,
+                self.state.len()
+            ),
+            _ => return Err(format!("Unknown command {line:?}; try `help`")),
+        };
+        Ok(Reply::Print(text))
+    }
+
+    fn checkpoint(&mut self, command: String) {
+        self.history.push(Checkpoint {
+            command,
+            state: self.state.clone(),
+            ledger: self.ledger,
+        });
+    }
+
+    /// Replaces the state with the snapshot file at `path`.
+    pub fn load(&mut self, path: &Path) -> Result<String, String> {
+        let file = SnapshotFile::load(path).map_err(|e| e.to_string())?;
+        self.checkpoint(format!("load {}", path.display()));
+        self.state = file.entries;
+        self.ledger.0 = file.ledger_sequence.unwrap_or(0);
+        Ok(format!(
+            "Loaded {} entries at ledger {}",
+            self.state.len(),
+            self.ledger.0
+        ))
+    }
+
+    fn call(&mut self, contract: &str, function: &str, args: &[String]) -> Result<String, String> {
+        let address = contract
+            .parse::<ScAddress>()
+            .map_err(|e| format!("Invalid contract {contract:?}: {e}"))?;
+        let snapshot = LedgerSnapshot::from_base64_map(&self.state).map_err(|e| e.to_string())?;
+        let local = LayeredSource::new(SnapshotLedgerSource::new(snapshot), None, self.ledger.0);
+        let inputs = pretty::contract_spec(&address, &local)
+            .and_then(|spec| spec.functions.into_iter().find(|f| f.name == function))
+            .map(|function| function.inputs);
+        if let Some(inputs) = inputs.as_ref().filter(|inputs| inputs.len() != args.len()) {
+            return Err(format!("{function} takes ({})", signature(inputs)));
+        }
+        let args = args
+            .iter()
+            .enumerate()
+            .map(|(index, arg)| {
+                let ty = inputs.as_ref().map(|inputs| inputs[index].type_.as_str());
+                argument(arg, ty).map_err(|e| format!("Argument {}: {e}", index + 1))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let request = serde_json::from_value::<SimulationRequest>(serde_json::json!({
+            "result_meta_xdr": "",
+            "enable_optimization_advisor": false,
+            "timestamp": "",
+            "network": self.network,
+            "ledger_entries": self.state,
+            "ledger_sequence": self.ledger.0,
+            "ledger_close_time": self.ledger.1,
+            "invoke": {
+                "contract_id": contract,
+                "function": function,
+                "args": args,
+                "source_account": self.source,
+            },
+        }))
+        .map_err(|e| format!("Invalid request: {e}"))?;
+        let response =
+            simulation::run(&request, &self.limits, None, None, None).map_err(|e| e.to_string())?;
+        if response.status != "success" {
+            return Err(response.error.unwrap_or(response.status));
+        }
+        let mut state = self.state.clone();
+        if let Some(meta) = &response.result_meta_xdr {
+            scenario::apply_changes(&mut state, meta)?;
+        }
+        self.checkpoint(format!("call {contract} {function}"));
+        self.state = state;
+
+        let mut lines = vec![response
+            .call_tree
+            .first()
+            .and_then(|call| call.result.clone())
+            .unwrap_or_else(|| "()".to_string())];
+        for event in &response.contract_events {
+            let topics = event
+                .topics()
+                .iter()
+                .map(|topic| format!("{topic:?}"))
+                .collect::<Vec<_>>();
+            lines.push(format!("event [{}] {:?}", topics.join(", "), event.data()));
+        }
+        if let Some(usage) = &response.budget_usage {
+            lines.push(format!(
+                "{} CPU instructions, {} bytes of memory",
+                usage.cpu_instructions, usage.memory_bytes
+            ));
+        }
+        Ok(lines.join("\n"))
+    }
+
+    fn storage(&self, contract: &str) -> Result<String, String> {
+        let address = contract
+            .parse::<ScAddress>()
+            .map_err(|e| format!("Invalid contract {contract:?}: {e}"))?;
+        let mut lines = Vec::new();
+        for (key_xdr, entry_xdr) in &self.state {
+            let Ok(key @ LedgerKey::ContractData(_)) = snapshot::decode_ledger_key(key_xdr) else {
+                continue;
+            };
+            let Ok(LedgerEntry {
+                data: LedgerEntryData::ContractData(data),
+                ..
+            }) = snapshot::decode_ledger_entry(entry_xdr)
+            else {
+                continue;
+            };
+            if data.contract != address {
+                continue;
+            }
+            if let ScVal::ContractInstance(instance) = &data.val {
+                for item in instance.storage.iter().flat_map(|storage| storage.iter()) {
+                    lines.push(format!(
+                        "instance {} = {}",
+                        pretty::value(&item.key),
+                        pretty::value(&item.val)
+                    ));
+                }
+                continue;
+            }
+            let durability = match data.durability {
+                ContractDataDurability::Persistent => "persistent",
+                ContractDataDurability::Temporary => "temporary",
+            };
+            let ttl = source::ttl_key(&key)
+                .ok()
+                .and_then(|ttl| ttl.to_xdr_base64(Limits::none()).ok())
+                .and_then(|ttl| self.state.get(&ttl))
+                .and_then(|ttl| snapshot::decode_ledger_entry(ttl).ok());
+            let live_until = source::live_until(&key, ttl.as_ref())
+                .filter(|&live_until| live_until != u32::MAX)
+                .map(|live_until| format!(" (live until {live_until})"))
+                .unwrap_or_default();
+            lines.push(format!(
+                "{durability} {} = {}{live_until}",
+                pretty::value(&data.key),
+                pretty::value(&data.val)
+            ));
+        }
+        if lines.is_empty() {
+            return Ok(format!("{contract} has no storage"));
+        }
+        lines.sort();
+        Ok(lines.join("\n"))
+    }
+
+    fn advance(&mut self, by: &[String]) -> Result<String, String> {
+        let ledgers = match by.first() {
+            Some(ledgers) => parse(ledgers)?,
+            None => Advance::between_steps().ledgers,
+        };
+        let seconds = match by.get(1) {
+            Some(seconds) => parse(seconds)?,
+            None => u64::from(ledgers) * Advance::between_steps().seconds,
+        };
+        self.checkpoint(format!("advance {ledgers} {seconds}"));
+        self.ledger = (
+            self.ledger.0.saturating_add(ledgers),
+            self.ledger.1.saturating_add(seconds),
+        );
+        let expired = scenario::expire(&mut self.state, self.ledger.0);
+        Ok(format!(
+            "Ledger {}, closed at {}; {} temporary entries expired",
+            self.ledger.0,
+            self.ledger.1,
+            expired.len()
+        ))
+    }
+
+    fn rollback(&mut self, count: usize) -> Result<String, String> {
+        if count > self.history.len() {
+            return Err(format!("Only {} commands to roll back", self.history.len()));
+        }
+        let undone = self.history.split_off(self.history.len() - count);
+        let mut commands = Vec::new();
+        for checkpoint in undone.into_iter().rev() {
+            commands.push(checkpoint.command);
+            self.state = checkpoint.state;
+            self.ledger = checkpoint.ledger;
+        }
+        Ok(format!("Rolled back {}", commands.join(", ")))
+    }
+}
+
+/// Reads commands from `input` until it ends or one quits, writing their
+/// output to `output`, after a prompt when `prompt` is set.
+pub fn run(
+    session: &mut Session,
+    input: impl BufRead,
+    mut output: impl Write,
+    prompt: bool,
+) -> io::Result<()> {
+    let mut lines = input.lines();
+    loop {
+        if prompt {
+            write!(output, "> ")?;
+            output.flush()?;
+        }
+        let Some(line) = lines.next().transpose()? else {
+            return Ok(());
+        };
+        match session.execute(&line) {
+            Ok(Reply::Print(text)) if text.is_empty() => {}
+            Ok(Reply::Print(text)) => writeln!(output, "{text}")?,
+            Ok(Reply::Quit) => return Ok(()),
+            Err(e) => writeln!(output, "error: {e}")?,
+        }
+    }
+}
+
+/// The words of `line`, split at whitespace outside double quotes. A
+/// backslash escapes the next character.
+fn words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = None::<String>;
+    let mut quoted = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let escaped = chars.next().ok_or("Trailing backslash")?;
+                word.get_or_insert_with(String::new).push(escaped);
+            }
+            '"' => {
+                quoted = !quoted;
+                word.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !quoted => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quoted {
+        return Err("Unterminated quote".to_string());
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// `text` as a base64 `ScVal` argument of type `ty`, the input's type in the
+/// contract spec when known.
+fn argument(text: &str, ty: Option<&str>) -> Result<String, String> {
+    if let Some(xdr) = text.strip_prefix("xdr:") {
+        return Ok(xdr.to_string());
+    }
+    let typed = text
+        .split_once(':')
+        .and_then(|(prefix, value)| scalar(value, prefix));
+    let value = match (typed, ty) {
+        (Some(value), _) => value?,
+        (None, Some(ty)) => scalar(text, ty).ok_or_else(|| {
+            format!("cannot read {ty} from text; pass it as xdr:<base64 ScVal>")
+        })??,
+        (None, None) => infer(text),
+    };
+    value
+        .to_xdr_base64(Limits::none())
+        .map_err(|e| e.to_string())
+}
+
+/// `text` as a value of the scalar type `ty`, or `None` when `ty` is not one
+/// that can be written as text.
+fn scalar(text: &str, ty: &str) -> Option<Result<ScVal, String>> {
+    let ty = ty.to_ascii_lowercase();
+    let value = match ty.as_str() {
+        "bool" => parse(text).map(ScVal::Bool),
+        "u32" => parse(text).map(ScVal::U32),
+        "i32" => parse(text).map(ScVal::I32),
+        "u64" => parse(text).map(ScVal::U64),
+        "i64" => parse(text).map(ScVal::I64),
+        "timepoint" => parse(text).map(|t| ScVal::Timepoint(TimePoint(t))),
+        "duration" => parse(text).map(|d| ScVal::Duration(Duration(d))),
+        "u128" => parse::<u128>(text).map(|n| {
+            ScVal::U128(UInt128Parts {
+                hi: (n >> 64) as u64,
+                lo: n as u64,
+            })
+        }),
+        "i128" => parse::<i128>(text).map(|n| {
+            ScVal::I128(Int128Parts {
+                hi: (n >> 64) as i64,
+                lo: n as u64,
+            })
+        }),
+        "address" => text
+            .parse::<ScAddress>()
+            .map(ScVal::Address)
+            .map_err(|e| format!("invalid address {text:?}: {e}")),
+        "symbol" => text
+            .try_into()
+            .map(|symbol| ScVal::Symbol(ScSymbol(symbol)))
+            .map_err(|e| format!("invalid symbol {text:?}: {e}")),
+        "string" => text
+            .try_into()
+            .map(|string| ScVal::String(ScString(string)))
+            .map_err(|e| format!("invalid string {text:?}: {e}")),
+        "bytes" => bytes(text),
+        ty if ty.starts_with("bytesn<") => bytes(text),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// `text` as the value it looks like.
+fn infer(text: &str) -> ScVal {
+    if let Ok(address) = text.parse::<ScAddress>() {
+        return ScVal::Address(address);
+    }
+    ["bool", "i128", "symbol", "string"]
+        .into_iter()
+        .find_map(|ty| scalar(text, ty)?.ok())
+        .unwrap_or(ScVal::Void)
+}
+
+fn bytes(hex: &str) -> Result<ScVal, String> {
+    let hex = hex.strip_prefix("0x").unwrap_or(hex);
+    if !hex.len().is_multiple_of(2) {
+        return Err(format!("odd number of hex digits in {hex:?}"));
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                .ok_or_else(|| format!("invalid hex {hex:?}"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    bytes
+        .try_into()
+        .map(|bytes| ScVal::Bytes(ScBytes(bytes)))
+        .map_err(|e| format!("invalid bytes: {e}"))
+}
+
+fn parse<T: FromStr>(text: &str) -> Result<T, String>
+where
+    T::Err: Display,
+{
+    text.parse()
+        .map_err(|e| format!("invalid value {text:?}: {e}"))
+}
+
+/// `inputs` as they are declared, e.g. `to: Address, amount: i128`.
+fn signature(inputs: &[Field]) -> String {
+    inputs
+        .iter()
+        .map(|input| format!("{}: {}", input.name, input.type_))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+    use soroban_env_host::meta::INTERFACE_VERSION;
+    use soroban_env_host::xdr::{
+        ContractCodeEntry, ContractCodeEntryExt, ContractDataEntry, ContractExecutable, ContractId,
+        ExtensionPoint, Hash, LedgerEntryExt, LedgerKeyContractCode, LedgerKeyContractData,
+        ScContractInstance, ScEnvMetaEntry, ScEnvMetaEntryInterfaceVersion, ScSpecEntry,
+        ScSpecFunctionInputV0, ScSpecFunctionV0, ScSpecTypeDef,
+    };
+
+    fn b64(xdr: &impl WriteXdr) -> String {
+        xdr.to_xdr_base64(Limits::none()).unwrap()
+    }
+
+    /// A contract whose `set(key: Symbol, value: u32) -> u32` stores `value`
+    /// under `key` in persistent storage.
+    fn contract() -> (ScAddress, HashMap<String, String>) {
+        let section = |bytes: Vec<u8>| {
+            bytes
+                .iter()
+                .map(|b| format!("\\{b:02x}"))
+                .collect::<String>()
+        };
+        let env_meta =
+            ScEnvMetaEntry::ScEnvMetaKindInterfaceVersion(ScEnvMetaEntryInterfaceVersion {
+                protocol: INTERFACE_VERSION.protocol,
+                pre_release: INTERFACE_VERSION.pre_release,
+            });
+        let input = |name: &str, type_| ScSpecFunctionInputV0 {
+            doc: "".try_into().unwrap(),
+            name: name.try_into().unwrap(),
+            type_,
+        };
+        let function = ScSpecEntry::FunctionV0(ScSpecFunctionV0 {
+            doc: "".try_into().unwrap(),
+            name: ScSymbol("set".try_into().unwrap()),
+            inputs: vec![
+                input("key", ScSpecTypeDef::Symbol),
+                input("value", ScSpecTypeDef::U32),
+            ]
+            .try_into()
+            .unwrap(),
+            outputs: vec![ScSpecTypeDef::U32].try_into().unwrap(),
+        });
+        let wasm = wat::parse_str(format!(
+            r#"(module
+                 (import "l" "_" (func $put (param i64 i64 i64) (result i64)))
+                 (func $set (param i64 i64) (result i64)
+                   (drop (call $put (local.get 0) (local.get 1) (i64.const 1)))
+                   (local.get 1))
+                 (memory 1)
+                 (export "set" (func $set))
+                 (export "memory" (memory 0))
+                 (@custom "contractenvmetav0" "{}")
+                 (@custom "contractspecv0" "{}"))"#,
+            section(env_meta.to_xdr(Limits::none()).unwrap()),
+            section(function.to_xdr(Limits::none()).unwrap()),
+        ))
+        .unwrap();
+        let hash = Hash(Sha256::digest(&wasm).into());
+        let contract = ScAddress::Contract(ContractId(Hash([4; 32])));
+        let entry = |data| LedgerEntry {
+            last_modified_ledger_seq: 5,
+            data,
+            ext: LedgerEntryExt::V0,
+        };
+        let instance = LedgerKey::ContractData(LedgerKeyContractData {
+            contract: contract.clone(),
+            key: ScVal::LedgerKeyContractInstance,
+            durability: ContractDataDurability::Persistent,
+        });
+        let code = LedgerKey::ContractCode(LedgerKeyContractCode { hash: hash.clone() });
+        let state = HashMap::from([
+            (
+                b64(&instance),
+                b64(&entry(LedgerEntryData::ContractData(ContractDataEntry {
+                    ext: ExtensionPoint::V0,
+                    contract: contract.clone(),
+                    key: ScVal::LedgerKeyContractInstance,
+                    durability: ContractDataDurability::Persistent,
+                    val: ScVal::ContractInstance(ScContractInstance {
+                        executable: ContractExecutable::Wasm(hash.clone()),
+                        storage: None,
+                    }),
+                }))),
+            ),
+            (
+                b64(&code),
+                b64(&entry(LedgerEntryData::ContractCode(ContractCodeEntry {
+                    ext: ContractCodeEntryExt::V0,
+                    hash,
+                    code: wasm.try_into().unwrap(),
+                }))),
+            ),
+        ]);
+        (contract, state)
+    }
+
+    #[test]
+    fn test_calls_change_the_state_until_rolled_back() {
+        let (contract, state) = contract();
+        let mut session = Session::new(None, None, MemoryLimits::default());
+        session.state = state;
+        session.ledger.0 = 10;
+        let closed = session.ledger.1;
+        let mut run = |line: &str| match session.execute(line) {
+            Ok(Reply::Print(text)) => Ok(text),
+            Ok(Reply::Quit) => Ok("quit".to_string()),
+            Err(e) => Err(e),
+        };
+
+        // `7` is read as the spec's u32, not an i128.
+        let called = run(&format!("call {contract} set counter 7")).unwrap();
+        assert!(called.starts_with('7'), "{called}");
+        let storage = run(&format!("storage {contract}")).unwrap();
+        assert!(storage.starts_with("persistent counter = 7"), "{storage}");
+        assert!(run(&format!("call {contract} set counter"))
+            .unwrap_err()
+            .contains("set takes (key: Symbol, value: u32)"));
+        // A failed call leaves nothing to roll back.
+        assert!(run(&format!("call {contract} get counter")).is_err());
+
+        assert_eq!(
+            run("advance 10").unwrap(),
+            format!(
+                "Ledger 20, closed at {}; 0 temporary entries expired",
+                closed + 50
+            )
+        );
+        assert_eq!(
+            run("rollback 2").unwrap(),
+            format!("Rolled back advance 10 50, call {contract} set")
+        );
+        assert_eq!(
+            run(&format!("storage {contract}")).unwrap(),
+            format!("{contract} has no storage")
+        );
+        assert!(run("rollback").is_err());
+        assert_eq!(run("quit").unwrap(), "quit");
+    }
+
+    #[test]
+    fn test_arguments_follow_the_spec_or_their_prefix() {
+        let decode = |b64: String| {
+            use soroban_env_host::xdr::ReadXdr;
+            ScVal::from_xdr_base64(b64, Limits::none()).unwrap()
+        };
+        assert_eq!(decode(argument("5", Some("u64")).unwrap()), ScVal::U64(5));
+        assert_eq!(
+            decode(argument("u32:5", Some("u64")).unwrap()),
+            ScVal::U32(5)
+        );
+        assert_eq!(
+            decode(argument("5", None).unwrap()),
+            ScVal::I128(Int128Parts { hi: 0, lo: 5 })
+        );
+        assert_eq!(
+            decode(argument("0xab01", Some("BytesN<2>")).unwrap()),
+            ScVal::Bytes(ScBytes(vec![0xab, 1].try_into().unwrap()))
+        );
+        assert!(matches!(
+            decode(argument("a:b", Some("String")).unwrap()),
+            ScVal::String(_)
+        ));
+        assert!(argument("x", Some("Vec<u32>"))
+            .unwrap_err()
+            .contains("xdr:<base64 ScVal>"));
+        assert_eq!(
+            words(r#"call C set "two words" a\"b"#).unwrap(),
+            ["call", "C", "set", "two words", "a\"b"]
+        );
+    }
+}
@@ -0,0 +1,619 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Transaction results and metadata of a simulated execution.
+//!
+//! [`transaction_result`] and [`transaction_meta`] rebuild what stellar-core
+//! would record for the simulated transaction: the `TransactionResult` with
+//! per-operation outcomes, and `TransactionMeta` v4 carrying the ledger
+//! changes, events and return value. [`get_transaction`] reshapes a response
+//! like soroban-rpc's `getTransaction`, so harnesses written against the RPC
+//! can consume simulator output unchanged.
+//!
+//! Fee and sequence number changes are not simulated, so
+//! `txChangesBefore`/`txChangesAfter` are always empty and the fee charged is
+//! the transaction's declared fee.
+
+use crate::invoke;
+use crate::network::Network;
+use crate::simulation::envelope_operations;
+use crate::source::{self, SourceEntry};
+use crate::types::{SimulationRequest, SimulationResponse};
+use crate::xdr_limits;
+use base64::Engine as _;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use soroban_env_host::budget::Budget;
+use soroban_env_host::events::HostEvent;
+use soroban_env_host::storage::{AccessType, Storage};
+use soroban_env_host::xdr::{
+    self, ContractEvent, ContractEventType, DiagnosticEvent, ExtensionPoint,
+    FeeBumpTransactionInnerTx, Hash, InnerTransactionResult, InnerTransactionResultExt,
+    InnerTransactionResultPair, InnerTransactionResultResult, InvokeHostFunctionResult,
+    InvokeHostFunctionSuccessPreImage, LedgerEntry, LedgerEntryChange, LedgerEntryChanges,
+    LedgerEntryData, LedgerEntryExt, LedgerKey, Limits, OperationBody, OperationMetaV2,
+    OperationResult, OperationResultTr, ScErrorType, ScVal, SorobanTransactionMetaExt,
+    SorobanTransactionMetaV2, TransactionEnvelope, TransactionMeta, TransactionMetaV4,
+    TransactionResult, TransactionResultExt, TransactionResultResult, TransactionV1Envelope,
+    TtlEntry, WriteXdr,
+};
+use soroban_env_host::Error as EnvError;
+
+/// How a simulated transaction ended.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// Every operation succeeded; the values returned by its host functions.
+    Succeeded(Vec<ScVal>),
+    /// The host failed with this error.
+    Failed(EnvError),
+    /// The declared fee is below the (mocked) required fee.
+    InsufficientFee,
+}
+
+/// Shape of the response written in stdin mode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputShape {
+    /// The simulator's own `SimulationResponse`.
+    #[default]
+    Simulation,
+    /// soroban-rpc's `getTransaction` result.
+    GetTransaction,
+}
+
+/// Builds the `TransactionResult` of `envelope` ending with `outcome`.
+///
+/// `events` are the host's events; the contract events of successful calls
+/// feed each `InvokeHostFunction` success hash.
+pub fn transaction_result(
+    envelope: &TransactionEnvelope,
+    network: &Network,
+    outcome: &Outcome,
+    events: &[HostEvent],
+) -> Result<TransactionResult, xdr::Error> {
+    let fee = declared_fee(envelope);
+    let Some(inner) = fee_bump_inner(envelope) else {
+        return Ok(TransactionResult {
+            fee_charged: fee,
+            result: match inner_result(envelope, outcome, events)? {
+                InnerTransactionResultResult::TxSuccess(ops) => {
+                    TransactionResultResult::TxSuccess(ops)
+                }
+                InnerTransactionResultResult::TxFailed(ops) => {
+                    TransactionResultResult::TxFailed(ops)
+                }
+                _ => TransactionResultResult::TxInsufficientFee,
+            },
+            ext: TransactionResultExt::V0,
+        });
+    };
+    if *outcome == Outcome::InsufficientFee {
+        return Ok(TransactionResult {
+            fee_charged: fee,
+            result: TransactionResultResult::TxInsufficientFee,
+            ext: TransactionResultExt::V0,
+        });
+    }
+    let result = inner_result(envelope, outcome, events)?;
+    let succeeded = matches!(result, InnerTransactionResultResult::TxSuccess(_));
+    let pair = InnerTransactionResultPair {
+        transaction_hash: Hash(network.transaction_hash(&inner)?),
+        result: InnerTransactionResult {
+            fee_charged: 0,
+            result,
+            ext: InnerTransactionResultExt::V0,
+        },
+    };
+    Ok(TransactionResult {
+        fee_charged: fee,
+        result: if succeeded {
+            TransactionResultResult::TxFeeBumpInnerSuccess(pair)
+        } else {
+            TransactionResultResult::TxFeeBumpInnerFailed(pair)
+        },
+        ext: TransactionResultExt::V0,
+    })
+}
+
+fn inner_result(
+    envelope: &TransactionEnvelope,
+    outcome: &Outcome,
+    events: &[HostEvent],
+) -> Result<InnerTransactionResultResult, xdr::Error> {
+    let operations = envelope_operations(envelope);
+    let invoke_result = |index: usize| -> Result<OperationResult, xdr::Error> {
+        let result = match outcome {
+            Outcome::Succeeded(values) => {
+                let preimage = InvokeHostFunctionSuccessPreImage {
+                    return_value: values.get(index).cloned().unwrap_or(ScVal::Void),
+                    events: contract_events(events).try_into()?,
+                };
+                let hash = Sha256::digest(preimage.to_xdr(Limits::none())?);
+                InvokeHostFunctionResult::Success(Hash(hash.into()))
+            }
+            Outcome::Failed(error) if error.is_type(ScErrorType::Budget) => {
+                InvokeHostFunctionResult::ResourceLimitExceeded
+            }
+            Outcome::Failed(_) | Outcome::InsufficientFee => InvokeHostFunctionResult::Trapped,
+        };
+        Ok(OperationResult::OpInner(
+            OperationResultTr::InvokeHostFunction(result),
+        ))
+    };
+    let mut invoked = 0;
+    let mut results = Vec::with_capacity(operations.len());
+    for op in operations {
+        results.push(match op.body {
+            OperationBody::InvokeHostFunction(_) => {
+                invoked += 1;
+                invoke_result(invoked - 1)?
+            }
+            _ => OperationResult::OpNotSupported,
+        });
+    }
+    let results = results.try_into()?;
+    Ok(match outcome {
+        Outcome::Succeeded(_) => InnerTransactionResultResult::TxSuccess(results),
+        Outcome::Failed(_) => InnerTransactionResultResult::TxFailed(results),
+        Outcome::InsufficientFee => InnerTransactionResultResult::TxInsufficientFee,
+    })
+}
+
+/// Builds the `TransactionMeta` v4 of a transaction ending with `outcome`.
+///
+/// `changes` are the ledger changes of a successful execution; failed
+/// transactions change nothing and carry only diagnostic events.
+pub fn transaction_meta(
+    envelope: &TransactionEnvelope,
+    outcome: &Outcome,
+    events: &[HostEvent],
+    changes: LedgerEntryChanges,
+) -> Result<TransactionMeta, xdr::Error> {
+    let succeeded = matches!(outcome, Outcome::Succeeded(_));
+    let mut changes = Some(changes);
+    let operations = envelope_operations(envelope)
+        .iter()
+        .map(|op| {
+            let invokes = matches!(op.body, OperationBody::InvokeHostFunction(_));
+            Ok(OperationMetaV2 {
+                ext: ExtensionPoint::V0,
+                // Soroban transactions have one operation, which made every change.
+                changes: changes
+                    .take()
+                    .filter(|_| succeeded && invokes)
+                    .unwrap_or_default(),
+                events: if succeeded && invokes {
+                    contract_events(events).try_into()?
+                } else {
+                    Default::default()
+                },
+            })
+        })
+        .collect::<Result<Vec<_>, xdr::Error>>()?;
+    let soroban_meta = match outcome {
+        Outcome::Succeeded(values) => Some(SorobanTransactionMetaV2 {
+            ext: SorobanTransactionMetaExt::V0,
+            return_value: values.last().cloned(),
+        }),
+        _ => None,
+    };
+    let diagnostic_events = events
+        .iter()
+        .map(|e| DiagnosticEvent {
+            in_successful_contract_call: !e.failed_call,
+            event: e.event.clone(),
+        })
+        .collect::<Vec<_>>();
+    Ok(TransactionMeta::V4(TransactionMetaV4 {
+        ext: ExtensionPoint::V0,
+        tx_changes_before: Default::default(),
+        operations: operations.try_into()?,
+        tx_changes_after: Default::default(),
+        soroban_meta,
+        events: Default::default(),
+        diagnostic_events: diagnostic_events.try_into()?,
+    }))
+}
+
+fn contract_events(events: &[HostEvent]) -> Vec<ContractEvent> {
+    events
+        .iter()
+        .filter(|e| e.event.type_ == ContractEventType::Contract && !e.failed_call)
+        .map(|e| e.event.clone())
+        .collect()
+}
+
+/// Diffs the host's storage after execution against the state it started
+/// from, as `STATE`/`CREATED`/`UPDATED`/`REMOVED` changes stamped with
+/// `ledger_sequence`. TTL extensions are reported as changes to the TTL
+/// entries, including for read-only keys.
+pub fn ledger_changes(
+    storage: &Storage,
+    before: impl Fn(&LedgerKey) -> Option<SourceEntry>,
+    ledger_sequence: u32,
+) -> Result<LedgerEntryChanges, String> {
+    let budget = Budget::default();
+    let entries = storage.map.iter(&budget).map_err(|e| format!("{e:?}"))?;
+    let mut changes = Vec::new();
+    for (key, after) in entries {
+        let access = storage
+            .footprint
+            .0
+            .get::<LedgerKey>(key, &budget)
+            .map_err(|e| format!("{e:?}"))?;
+        let old = before(key);
+        let new = after.as_ref().map(|(entry, live_until)| SourceEntry {
+            entry: LedgerEntry {
+                last_modified_ledger_seq: ledger_sequence,
+                ..LedgerEntry::clone(entry)
+            },
+            live_until: *live_until,
+        });
+        if access == Some(&AccessType::ReadWrite) {
+            entry_change(
+                &mut changes,
+                key,
+                old.as_ref().map(|o| &o.entry),
+                new.as_ref().map(|n| &n.entry),
+            );
+        }
+        if source::has_ttl(key) {
+            let ttl_key = source::ttl_key(key).map_err(|e| e.to_string())?;
+            let ttl = |entry: &SourceEntry, ledger: u32| {
+                entry
+                    .live_until
+                    .map(|live_until| ttl_entry(&ttl_key, live_until, ledger))
+            };
+            let old_ttl = old
+                .as_ref()
+                .and_then(|o| ttl(o, o.entry.last_modified_ledger_seq));
+            let new_ttl = new.as_ref().and_then(|n| ttl(n, ledger_sequence));
+            let live_until = |e: &Option<LedgerEntry>| match e.as_ref().map(|e| &e.data) {
+                Some(LedgerEntryData::Ttl(ttl)) => Some(ttl.live_until_ledger_seq),
+                _ => None,
+            };
+            if live_until(&old_ttl) != live_until(&new_ttl) {
+                entry_change(&mut changes, &ttl_key, old_ttl.as_ref(), new_ttl.as_ref());
+            }
+        }
+    }
+    changes.try_into().map_err(|e: xdr::Error| e.to_string())
+}
+
+fn entry_change(
+    changes: &mut Vec<LedgerEntryChange>,
+    key: &LedgerKey,
+    old: Option<&LedgerEntry>,
+    new: Option<&LedgerEntry>,
+) {
+    match (old, new) {
+        (None, Some(new)) => changes.push(LedgerEntryChange::Created(new.clone())),
+        (Some(old), None) => {
+            changes.push(LedgerEntryChange::State(old.clone()));
+            changes.push(LedgerEntryChange::Removed(key.clone()));
+        }
+        (Some(old), Some(new)) if old.data != new.data => {
+            changes.push(LedgerEntryChange::State(old.clone()));
+            changes.push(LedgerEntryChange::Updated(new.clone()));
+        }
+        _ => {}
+    }
+}
+
+fn ttl_entry(ttl_key: &LedgerKey, live_until: u32, ledger: u32) -> LedgerEntry {
+    let LedgerKey::Ttl(ttl) = ttl_key else {
+        unreachable!("source::ttl_key returns TTL keys")
+    };
+    LedgerEntry {
+        last_modified_ledger_seq: ledger,
+        data: LedgerEntryData::Ttl(TtlEntry {
+            key_hash: ttl.key_hash.clone(),
+            live_until_ledger_seq: live_until,
+        }),
+        ext: LedgerEntryExt::V0,
+    }
+}
+
+fn declared_fee(envelope: &TransactionEnvelope) -> i64 {
+    match envelope {
+        TransactionEnvelope::TxV0(env) => env.tx.fee.into(),
+        TransactionEnvelope::Tx(env) => env.tx.fee.into(),
+        TransactionEnvelope::TxFeeBump(env) => env.tx.fee,
+    }
+}
+
+fn fee_bump_inner(envelope: &TransactionEnvelope) -> Option<TransactionEnvelope> {
+    match envelope {
+        TransactionEnvelope::TxFeeBump(env) => match &env.tx.inner_tx {
+            FeeBumpTransactionInnerTx::Tx(inner) => {
+                Some(TransactionEnvelope::Tx(TransactionV1Envelope::clone(inner)))
+            }
+        },
+        _ => None,
+    }
+}
+
+/// A simulated transaction in the shape of soroban-rpc's `getTransaction`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTransactionResponse {
+    /// `SUCCESS` or `FAILED`.
+    pub status: &'static str,
+    pub tx_hash: String,
+    pub latest_ledger: u32,
+    pub ledger: u32,
+    pub application_order: u32,
+    pub fee_bump: bool,
+    pub envelope_xdr: String,
+    pub result_xdr: String,
+    pub result_meta_xdr: String,
+}
+
+/// Reshapes `response` to `request` like `getTransaction`, or returns
+/// `None` if the transaction never ran (for example because the request was
+/// invalid), which `getTransaction` has no way to express.
+pub fn get_transaction(
+    response: &SimulationResponse,
+    request: &SimulationRequest,
+) -> Option<GetTransactionResponse> {
+    let envelope = match &request.invoke {
+        Some(spec) => invoke::envelope(spec).ok()?,
+        None => {
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(&request.envelope_xdr)
+                .ok()?;
+            xdr_limits::read::<TransactionEnvelope>(&bytes).ok()?
+        }
+    };
+    let ledger = response.ledger_sequence?;
+    Some(GetTransactionResponse {
+        status: if response.status == "success" {
+            "SUCCESS"
+        } else {
+            "FAILED"
+        },
+        tx_hash: response.transaction_hash.clone()?,
+        latest_ledger: ledger,
+        ledger,
+        application_order: 1,
+        fee_bump: matches!(envelope, TransactionEnvelope::TxFeeBump(_)),
+        envelope_xdr: envelope.to_xdr_base64(Limits::none()).ok()?,
+        result_xdr: response.result_xdr.clone()?,
+        result_meta_xdr: response.result_meta_xdr.clone()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::InvokeSpec;
+    use soroban_env_host::storage::{Footprint, FootprintMap, StorageMap};
+    use soroban_env_host::xdr::{
+        ContractDataDurability, ContractDataEntry, ContractEventBody, ContractEventV0, ContractId,
+        FeeBumpTransaction, FeeBumpTransactionExt, LedgerKeyContractData, MuxedAccount, ScAddress,
+        Uint256,
+    };
+    use std::rc::Rc;
+
+    fn contract() -> ScAddress {
+        ScAddress::Contract(ContractId(Hash([7; 32])))
+    }
+
+    fn envelope() -> TransactionEnvelope {
+        invoke::envelope(&InvokeSpec {
+            contract_id: contract().to_string(),
+            function: "run".into(),
+            args: vec![],
+            source_account: None,
+        })
+        .unwrap()
+    }
+
+    fn event(type_: ContractEventType, failed_call: bool) -> HostEvent {
+        HostEvent {
+            event: ContractEvent {
+                ext: ExtensionPoint::V0,
+                contract_id: None,
+                type_,
+                body: ContractEventBody::V0(ContractEventV0 {
+                    topics: Default::default(),
+                    data: ScVal::U32(1),
+                }),
+            },
+            failed_call,
+        }
+    }
+
+    fn op_results(result: TransactionResultResult) -> Vec<OperationResult> {
+        match result {
+            TransactionResultResult::TxSuccess(ops) | TransactionResultResult::TxFailed(ops) => {
+                ops.to_vec()
+            }
+            other => panic!("unexpected result {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_success_hash_covers_return_value_and_contract_events() {
+        let network = Network::resolve(None, None).unwrap();
+        let events = [
+            event(ContractEventType::Contract, false),
+            event(ContractEventType::Diagnostic, false),
+            event(ContractEventType::Contract, true),
+        ];
+        let outcome = Outcome::Succeeded(vec![ScVal::U32(7)]);
+        let result = transaction_result(&envelope(), &network, &outcome, &events).unwrap();
+        assert_eq!(result.fee_charged, 100);
+
+        let preimage = InvokeHostFunctionSuccessPreImage {
+            return_value: ScVal::U32(7),
+            events: vec![events[0].event.clone()].try_into().unwrap(),
+        };
+        let hash = Hash(Sha256::digest(preimage.to_xdr(Limits::none()).unwrap()).into());
+        assert_eq!(
+            op_results(result.result),
+            vec![OperationResult::OpInner(
+                OperationResultTr::InvokeHostFunction(InvokeHostFunctionResult::Success(hash))
+            )]
+        );
+
+        let TransactionMeta::V4(meta) =
+            transaction_meta(&envelope(), &outcome, &events, Default::default()).unwrap()
+        else {
+            panic!("expected v4 meta");
+        };
+        assert_eq!(meta.operations[0].events.len(), 1);
+        assert_eq!(meta.diagnostic_events.len(), 3);
+        assert_eq!(meta.soroban_meta.unwrap().return_value, Some(ScVal::U32(7)));
+    }
+
+    #[test]
+    fn test_failures_and_fee_bumps() {
+        let network = Network::resolve(None, None).unwrap();
+        let budget = Outcome::Failed(EnvError::from_type_and_code(
+            ScErrorType::Budget,
+            xdr::ScErrorCode::ExceededLimit,
+        ));
+        let result = transaction_result(&envelope(), &network, &budget, &[]).unwrap();
+        assert_eq!(
+            op_results(result.result),
+            vec![OperationResult::OpInner(
+                OperationResultTr::InvokeHostFunction(
+                    InvokeHostFunctionResult::ResourceLimitExceeded
+                )
+            )]
+        );
+
+        let TransactionEnvelope::Tx(inner) = envelope() else {
+            unreachable!()
+        };
+        let bumped = TransactionEnvelope::TxFeeBump(xdr::FeeBumpTransactionEnvelope {
+            tx: FeeBumpTransaction {
+                fee_source: MuxedAccount::Ed25519(Uint256([1; 32])),
+                fee: 500,
+                inner_tx: FeeBumpTransactionInnerTx::Tx(inner.clone()),
+                ext: FeeBumpTransactionExt::V0,
+            },
+            signatures: Default::default(),
+        });
+        let outcome = Outcome::Succeeded(vec![ScVal::Void]);
+        let result = transaction_result(&bumped, &network, &outcome, &[]).unwrap();
+        assert_eq!(result.fee_charged, 500);
+        let TransactionResultResult::TxFeeBumpInnerSuccess(pair) = result.result else {
+            panic!("expected an inner success");
+        };
+        let inner_hash = network
+            .transaction_hash(&TransactionEnvelope::Tx(inner))
+            .unwrap();
+        assert_eq!(pair.transaction_hash, Hash(inner_hash));
+
+        let result = transaction_result(&bumped, &network, &Outcome::InsufficientFee, &[]).unwrap();
+        assert_eq!(result.result, TransactionResultResult::TxInsufficientFee);
+    }
+
+    fn data_key(name: &str) -> LedgerKey {
+        LedgerKey::ContractData(LedgerKeyContractData {
+            contract: contract(),
+            key: ScVal::Symbol(name.try_into().unwrap()),
+            durability: ContractDataDurability::Persistent,
+        })
+    }
+
+    fn data_entry(name: &str, value: u32, ledger: u32) -> LedgerEntry {
+        LedgerEntry {
+            last_modified_ledger_seq: ledger,
+            data: LedgerEntryData::ContractData(ContractDataEntry {
+                ext: ExtensionPoint::V0,
+                contract: contract(),
+                key: ScVal::Symbol(name.try_into().unwrap()),
+                durability: ContractDataDurability::Persistent,
+                val: ScVal::U32(value),
+            }),
+            ext: LedgerEntryExt::V0,
+        }
+    }
+
+    #[test]
+    fn test_ledger_changes_diff_storage_against_the_starting_state() {
+        let budget = Budget::default();
+        // (key, access, before, after): updated, created, removed, and a
+        // read-only entry whose TTL was extended.
+        let cases = [
+            (
+                "updated",
+                AccessType::ReadWrite,
+                Some((1, 100)),
+                Some((2, 100)),
+            ),
+            ("created", AccessType::ReadWrite, None, Some((3, 200))),
+            ("removed", AccessType::ReadWrite, Some((4, 100)), None),
+            (
+                "extended",
+                AccessType::ReadOnly,
+                Some((5, 100)),
+                Some((5, 300)),
+            ),
+        ];
+        let mut footprint = Vec::new();
+        let mut map = Vec::new();
+        for (name, access, _, after) in &cases {
+            let key = Rc::new(data_key(name));
+            footprint.push((key.clone(), *access));
+            let after = after.map(|(value, live_until)| {
+                (Rc::new(data_entry(name, value, 10)), Some(live_until))
+            });
+            map.push((key, after));
+        }
+        footprint.sort_by(|a, b| a.0.cmp(&b.0));
+        map.sort_by(|a, b| a.0.cmp(&b.0));
+        let storage = Storage::with_enforcing_footprint_and_map(
+            Footprint(FootprintMap::from_map(footprint, &budget).unwrap()),
+            StorageMap::from_map(map, &budget).unwrap(),
+        );
+        let before = |key: &LedgerKey| {
+            let (name, _, before, _) = cases.iter().find(|(name, ..)| data_key(name) == *key)?;
+            before.map(|(value, live_until)| SourceEntry {
+                entry: data_entry(name, value, 10),
+                live_until: Some(live_until),
+            })
+        };
+
+        let changes = ledger_changes(&storage, before, 50).unwrap();
+        let mut kinds = changes
+            .iter()
+            .map(|change| match change {
+                LedgerEntryChange::Created(e) => format!("created {}", describe(e)),
+                LedgerEntryChange::Updated(e) => format!("updated {}", describe(e)),
+                LedgerEntryChange::Removed(_) => "removed".to_string(),
+                LedgerEntryChange::State(e) => format!("state {}", describe(e)),
+                LedgerEntryChange::Restored(e) => format!("restored {}", describe(e)),
+            })
+            .collect::<Vec<_>>();
+        let mut expected = [
+            "created data 3@50",
+            "created ttl 200@50",
+            "state ttl 100@10",
+            "updated ttl 300@50",
+            "state data 4@10",
+            "removed",
+            "state ttl 100@10",
+            "removed",
+            "state data 1@10",
+            "updated data 2@50",
+        ];
+        kinds.sort();
+        expected.sort_unstable();
+        assert_eq!(kinds, expected);
+    }
+
+    fn describe(entry: &LedgerEntry) -> String {
+        let lms = entry.last_modified_ledger_seq;
+        match &entry.data {
+            LedgerEntryData::ContractData(data) => match data.val {
+                ScVal::U32(value) => format!("data {value}@{lms}"),
+                _ => unreachable!(),
+            },
+            LedgerEntryData::Ttl(ttl) => format!("ttl {}@{lms}", ttl.live_until_ledger_seq),
+            _ => unreachable!(),
+        }
+    }
+}
@@ -0,0 +1,353 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! HTML reports: one page with inline styles and no scripts, so it can be
+//! attached or mailed as is. The call tree folds with `<details>`.
+
+use super::{call_cost, call_error, fees, invocation, live_until, summary, warnings, writes};
+use crate::calls::Call;
+use crate::types::{SimulationRequest, SimulationResponse};
+use std::fmt::Write as _;
+
+/// Longest event topic or data string shown in the timeline.
+const MAX_EVENT_TEXT: usize = 300;
+/// Cost types shown in the cost chart.
+const MAX_COST_TYPES: usize = 10;
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;max-width:1100px;margin:2em auto;\
+padding:0 1em;color:#222}\
+table{border-collapse:collapse;margin:.5em 0}\
+td,th{border:1px solid #ccc;padding:.25em .5em;text-align:left;vertical-align:top}\
+code{font-size:.9em;word-break:break-all}\
+.status-success{color:#17692d}.status-error{color:#b00020}\
+details{margin-left:1.25em}summary{cursor:pointer}.leaf{margin-left:2.25em}\
+.failed{color:#b00020}.cost{color:#666}\
+.chart td:first-child{white-space:nowrap}.bar{background:#eee;width:400px;height:1em}\
+.bar span{display:block;height:100%;background:#3a6ea5}\
+tr.diagnostic{color:#777}tr.rolled-back{text-decoration:line-through}";
+
+/// The HTML report of `response`, the simulation of `request`.
+pub fn html(request: &SimulationRequest, response: &SimulationResponse) -> String {
+    let mut out = String::from("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n");
+    out.push_str("<meta charset=\"utf-8\">\n<title>Simulation Report</title>\n");
+    let _ = writeln!(out, "<style>{STYLE}</style>\n</head>\n<body>");
+    out.push_str("<h1>Simulation Report</h1>\n<table>\n");
+    for (label, value) in summary(request, response) {
+        let value = if label == "Status" {
+            format!(
+                "<span class=\"status-{}\">{}</span>",
+                escape(&value),
+                escape(&value)
+            )
+        } else {
+            escape(&value)
+        };
+        let _ = writeln!(out, "<tr><th>{label}</th><td>{value}</td></tr>");
+    }
+    out.push_str("</table>\n");
+    if let Some(error) = &response.error {
+        let _ = writeln!(
+            out,
+            "<h2>Error</h2>\n<pre>{}</pre>",
+            escape(error.trim_end())
+        );
+    }
+
+    if !response.pretty_invocations.is_empty() {
+        out.push_str("<h2>Invocations</h2>\n<ol>\n");
+        for invocation in &response.pretty_invocations {
+            let _ = writeln!(out, "<li><code>{}</code></li>", escape(invocation));
+        }
+        out.push_str("</ol>\n");
+    }
+    if !response.call_tree.is_empty() {
+        out.push_str("<h2>Call Tree</h2>\n");
+        for call in &response.call_tree {
+            call_item(&mut out, call);
+        }
+    }
+
+    costs(&mut out, response);
+    timeline(&mut out, response);
+
+    if !response.auth_payloads.is_empty() {
+        out.push_str("<h2>Authorizations</h2>\n");
+        table(
+            &mut out,
+            &["Address", "Nonce", "Valid until ledger"],
+            response.auth_payloads.iter().map(|payload| {
+                vec![
+                    code(&payload.address),
+                    payload.nonce.to_string(),
+                    payload.signature_expiration_ledger.to_string(),
+                ]
+            }),
+        );
+    }
+    if !response.balance_changes.is_empty() {
+        out.push_str("<h2>Balance Changes</h2>\n");
+        table(
+            &mut out,
+            &["Address", "Token", "Asset", "Amount"],
+            response.balance_changes.iter().map(|change| {
+                vec![
+                    code(&change.address),
+                    code(&change.token),
+                    escape(change.asset.as_deref().unwrap_or("")),
+                    escape(&change.amount),
+                ]
+            }),
+        );
+    }
+    let writes = writes(response);
+    if !response.storage_accesses.is_empty() {
+        out.push_str("<h2>State Changes</h2>\n");
+        if !writes.is_empty() {
+            table(
+                &mut out,
+                &["Entry", "Key", "Change", "Size (bytes)", "Live until"],
+                writes.iter().map(|access| {
+                    vec![
+                        escape(&access.entry_type),
+                        code(&access.key),
+                        access.operation.to_string(),
+                        access.entry_bytes.to_string(),
+                        escape(&live_until(access)),
+                    ]
+                }),
+            );
+        }
+        let _ = writeln!(
+            out,
+            "<p>{} entries read without changes.</p>",
+            response.storage_accesses.len() - writes.len()
+        );
+    }
+
+    out.push_str("<h2>Fees and Resources</h2>\n<table>\n");
+    for (label, value) in fees(request, response) {
+        let _ = writeln!(out, "<tr><th>{label}</th><td>{}</td></tr>", escape(&value));
+    }
+    out.push_str("</table>\n<h2>Warnings</h2>\n");
+    let warnings = warnings(response);
+    if warnings.is_empty() {
+        out.push_str("<p>None.</p>\n");
+    } else {
+        out.push_str("<ul>\n");
+        for warning in warnings {
+            let _ = writeln!(out, "<li>{}</li>", escape(&warning));
+        }
+        out.push_str("</ul>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// A call that made calls folds them under it, open; others are plain.
+fn call_item(out: &mut String, call: &Call) {
+    let result = match &call.result {
+        Some(result) => format!("→ {}", code(result)),
+        None => match call_error(call) {
+            Some(error) => format!(
+                "<span class=\"failed\">→ failed with</span> {}",
+                code(&error)
+            ),
+            None => "<span class=\"failed\">→ failed</span>".to_string(),
+        },
+    };
+    let cost = call_cost(call)
+        .map(|cost| format!(" <span class=\"cost\">{cost}</span>"))
+        .unwrap_or_default();
+    let line = format!("{} {result}{cost}", code(&invocation(call)));
+    if call.calls.is_empty() {
+        let _ = writeln!(out, "<div class=\"leaf\">{line}</div>");
+        return;
+    }
+    let _ = writeln!(out, "<details open><summary>{line}</summary>");
+    for inner in &call.calls {
+        call_item(out, inner);
+    }
+    out.push_str("</details>\n");
+}
+
+/// Budget use against the limits, and the most expensive cost types when
+/// the request was profiled.
+fn costs(out: &mut String, response: &SimulationResponse) {
+    let Some(usage) = &response.budget_usage else {
+        return;
+    };
+    out.push_str("<h2>Costs</h2>\n<table class=\"chart\">\n");
+    bar(
+        out,
+        "CPU instructions",
+        usage.cpu_usage_percent,
+        &format!("{:.1}%", usage.cpu_usage_percent),
+    );
+    bar(
+        out,
+        "Memory bytes",
+        usage.memory_usage_percent,
+        &format!("{:.1}%", usage.memory_usage_percent),
+    );
+    out.push_str("</table>\n");
+    let Some(attribution) = &response.cost_attribution else {
+        return;
+    };
+    let top = attribution
+        .cost_types
+        .iter()
+        .take(MAX_COST_TYPES)
+        .collect::<Vec<_>>();
+    let Some(max) = top.iter().map(|charge| charge.cpu_insns).max() else {
+        return;
+    };
+    out.push_str("<h3>CPU instructions by cost type</h3>\n<table class=\"chart\">\n");
+    for charge in top {
+        let percent = if max == 0 {
+            0.0
+        } else {
+            charge.cpu_insns as f64 * 100.0 / max as f64
+        };
+        bar(
+            out,
+            charge.cost_type,
+            percent,
+            &charge.cpu_insns.to_string(),
+        );
+    }
+    out.push_str("</table>\n");
+}
+
+fn bar(out: &mut String, label: &str, percent: f64, value: &str) {
+    let _ = writeln!(
+        out,
+        "<tr><td>{}</td><td><div class=\"bar\"><span style=\"width:{:.1}%\"></span></div></td>\
+         <td>{}</td></tr>",
+        escape(label),
+        percent.clamp(0.0, 100.0),
+        escape(value)
+    );
+}
+
+/// Every event in emission order. Diagnostic events are grey, and events of
+/// calls that failed, which the network discards, are struck through.
+fn timeline(out: &mut String, response: &SimulationResponse) {
+    if response.diagnostic_events.is_empty() {
+        return;
+    }
+    out.push_str("<h2>Event Timeline</h2>\n<table>\n");
+    out.push_str("<tr><th>#</th><th>Type</th><th>Contract</th><th>Topics</th><th>Data</th></tr>\n");
+    for (i, event) in response.diagnostic_events.iter().enumerate() {
+        let mut class = event.event_type().to_string();
+        if !event.in_successful_contract_call() {
+            class.push_str(" rolled-back");
+        }
+        let topics = event
+            .topics()
+            .iter()
+            .map(|topic| code(&truncate(&format!("{topic:?}"))))
+            .collect::<Vec<_>>();
+        let _ = writeln!(
+            out,
+            "<tr class=\"{}\"><td>{i}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape(&class),
+            escape(event.event_type()),
+            event
+                .contract_id()
+                .map(|id| code(&format!("{id:?}")))
+                .unwrap_or_default(),
+            topics.join("<br>"),
+            code(&truncate(&format!("{:?}", event.data()))),
+        );
+    }
+    out.push_str("</table>\n");
+}
+
+fn table(out: &mut String, headers: &[&str], rows: impl Iterator<Item = Vec<String>>) {
+    out.push_str("<table>\n<tr>");
+    for header in headers {
+        let _ = write!(out, "<th>{header}</th>");
+    }
+    out.push_str("</tr>\n");
+    for row in rows {
+        let _ = writeln!(out, "<tr><td>{}</td></tr>", row.join("</td><td>"));
+    }
+    out.push_str("</table>\n");
+}
+
+fn truncate(s: &str) -> String {
+    match s.char_indices().nth(MAX_EVENT_TEXT) {
+        Some((end, _)) => format!("{}…", &s[..end]),
+        None => s.to_string(),
+    }
+}
+
+fn code(s: &str) -> String {
+    format!("<code>{}</code>", escape(s))
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{test_request, test_response};
+    use super::*;
+    use crate::types::DiagnosticEvent;
+    use soroban_env_host::events::HostEvent;
+    use soroban_env_host::xdr::{
+        ContractEvent, ContractEventBody, ContractEventType, ContractEventV0, ExtensionPoint,
+        ScString, ScSymbol, ScVal,
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn test_html_renders_tree_timeline_and_escapes() {
+        let mut response = test_response();
+        let event = HostEvent {
+            event: ContractEvent {
+                ext: ExtensionPoint::V0,
+                contract_id: None,
+                type_: ContractEventType::Contract,
+                body: ContractEventBody::V0(ContractEventV0 {
+                    topics: vec![ScVal::Symbol(ScSymbol("<b>".try_into().unwrap()))]
+                        .try_into()
+                        .unwrap(),
+                    data: ScVal::String(ScString(
+                        "x".repeat(MAX_EVENT_TEXT + 5).try_into().unwrap(),
+                    )),
+                }),
+            },
+            failed_call: true,
+        };
+        response.diagnostic_events = vec![Arc::new(DiagnosticEvent::new(Arc::new([event]), 0))];
+
+        let report = html(&test_request(), &response);
+        assert!(report.starts_with("<!DOCTYPE html>"));
+        assert!(report.ends_with("</html>\n"));
+        assert!(report.contains(
+            "<details open><summary><code>CAAA…AAAA::swap(1)</code> → <code>true</code></summary>"
+        ));
+        assert!(report.contains(
+            "<div class=\"leaf\"><code>CBBB…BBBB::transfer()</code> \
+             <span class=\"failed\">→ failed</span></div>"
+        ));
+        assert!(report.contains("<tr class=\"contract rolled-back\">"));
+        assert!(report.contains("&lt;b&gt;"));
+        assert!(report.contains("x…</code>"));
+        assert!(!report.contains(&"x".repeat(MAX_EVENT_TEXT)));
+        assert!(!report.contains("<script"));
+    }
+}
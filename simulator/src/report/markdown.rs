@@ -0,0 +1,180 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Markdown reports.
+
+use super::{call_cost, call_error, fees, invocation, live_until, summary, warnings, writes};
+use crate::calls::Call;
+use crate::types::{SimulationRequest, SimulationResponse};
+use std::fmt::Write as _;
+
+/// The Markdown report of `response`, the simulation of `request`.
+pub fn markdown(request: &SimulationRequest, response: &SimulationResponse) -> String {
+    let mut out = String::from("# Simulation Report\n\n| | |\n|---|---|\n");
+    for (label, value) in summary(request, response) {
+        row(&mut out, &[label, &value]);
+    }
+    if let Some(error) = &response.error {
+        let _ = write!(out, "\n## Error\n\n```text\n{}\n```\n", error.trim_end());
+    }
+
+    if !response.pretty_invocations.is_empty() {
+        out.push_str("\n## Invocations\n\n");
+        for (i, invocation) in response.pretty_invocations.iter().enumerate() {
+            let _ = writeln!(out, "{}. {}", i + 1, code(invocation));
+        }
+    }
+    if !response.call_tree.is_empty() {
+        out.push_str("\n## Call Tree\n\n");
+        for call in &response.call_tree {
+            call_item(&mut out, call, 0);
+        }
+    }
+
+    if !response.auth_payloads.is_empty() {
+        out.push_str("\n## Authorizations\n\n");
+        out.push_str("| Address | Nonce | Valid until ledger |\n|---|---|---|\n");
+        for payload in &response.auth_payloads {
+            row(
+                &mut out,
+                &[
+                    &code(&payload.address),
+                    &payload.nonce.to_string(),
+                    &payload.signature_expiration_ledger.to_string(),
+                ],
+            );
+        }
+    }
+    if !response.balance_changes.is_empty() {
+        out.push_str("\n## Balance Changes\n\n");
+        out.push_str("| Address | Token | Asset | Amount |\n|---|---|---|---|\n");
+        for change in &response.balance_changes {
+            row(
+                &mut out,
+                &[
+                    &code(&change.address),
+                    &code(&change.token),
+                    change.asset.as_deref().unwrap_or(""),
+                    &change.amount,
+                ],
+            );
+        }
+    }
+    let writes = writes(response);
+    if !response.storage_accesses.is_empty() {
+        out.push_str("\n## State Changes\n\n");
+        if !writes.is_empty() {
+            out.push_str("| Entry | Key | Change | Size (bytes) | Live until |\n");
+            out.push_str("|---|---|---|---|---|\n");
+        }
+        for access in &writes {
+            row(
+                &mut out,
+                &[
+                    &access.entry_type,
+                    &code(&access.key),
+                    access.operation,
+                    &access.entry_bytes.to_string(),
+                    &live_until(access),
+                ],
+            );
+        }
+        let reads = response.storage_accesses.len() - writes.len();
+        let _ = writeln!(
+            out,
+            "{}{reads} entries read without changes.",
+            if writes.is_empty() { "" } else { "\n" }
+        );
+    }
+
+    out.push_str("\n## Fees and Resources\n\n| | |\n|---|---|\n");
+    for (label, value) in fees(request, response) {
+        row(&mut out, &[label, &value]);
+    }
+
+    out.push_str("\n## Warnings\n\n");
+    let warnings = warnings(response);
+    if warnings.is_empty() {
+        out.push_str("None.\n");
+    }
+    for warning in warnings {
+        let _ = writeln!(out, "- {warning}");
+    }
+    out
+}
+
+fn call_item(out: &mut String, call: &Call, depth: usize) {
+    let result = match &call.result {
+        Some(result) => format!("→ {}", code(result)),
+        None => match call_error(call) {
+            Some(error) => format!("→ failed with {}", code(&error)),
+            None => "→ failed".to_string(),
+        },
+    };
+    let cost = call_cost(call)
+        .map(|cost| format!(" · {cost}"))
+        .unwrap_or_default();
+    let _ = writeln!(
+        out,
+        "{}- {} {result}{cost}",
+        "  ".repeat(depth),
+        code(&invocation(call))
+    );
+    for inner in &call.calls {
+        call_item(out, inner, depth + 1);
+    }
+}
+
+fn row(out: &mut String, cells: &[&str]) {
+    let cells = cells
+        .iter()
+        .map(|cell| cell.replace('|', "\\|").replace('\n', " "))
+        .collect::<Vec<_>>();
+    let _ = writeln!(out, "| {} |", cells.join(" | "));
+}
+
+/// `s` as inline code, fenced with more backticks than it contains.
+fn code(s: &str) -> String {
+    let longest = s.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest + 1);
+    let pad = if longest > 0 { " " } else { "" };
+    format!("{fence}{pad}{s}{pad}{fence}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{test_request, test_response};
+    use super::*;
+    use crate::calls::{CallCost, Charged};
+
+    #[test]
+    fn test_markdown_renders_call_tree_and_warnings() {
+        let report = markdown(&test_request(), &test_response());
+        assert!(report.starts_with("# Simulation Report\n"));
+        assert!(report.contains("| Status | success |"));
+        assert!(report.contains("\n- `CAAA…AAAA::swap(1)` → `true`\n"));
+        assert!(report.contains("\n  - `CBBB…BBBB::transfer()` → failed\n"));
+        assert!(report.contains("## Warnings\n\nNone.\n"));
+        assert!(!report.contains("## Error"));
+
+        let mut response = test_response();
+        let charged = |cpu_instructions| Charged {
+            cpu_instructions,
+            memory_bytes: 0,
+        };
+        response.call_tree[0].cost = Some(CallCost {
+            inclusive: charged(1200),
+            own: charged(300),
+        });
+        let report = markdown(&test_request(), &response);
+        assert!(report.contains("`true` · 1200 CPU instructions (300 own)\n"));
+    }
+
+    #[test]
+    fn test_code_and_rows_escape_markdown() {
+        assert_eq!(code("a`b"), "`` a`b ``");
+        let mut out = String::new();
+        row(&mut out, &["a|b", "c"]);
+        assert_eq!(out, "| a\\|b | c |\n");
+    }
+}
@@ -0,0 +1,263 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Self-contained reports of a simulation, for attaching to governance
+//! proposals and audit PRs or sharing with people who will not read JSON.
+//!
+//! `erst-sim --report md out.md` (or `html out.html`) writes the report next
+//! to the usual response: a summary, the invocations and call tree,
+//! authorizations, balance and state changes, fees and resources, and
+//! warnings. The HTML report adds a collapsible call tree, the event
+//! timeline and cost charts.
+
+mod html;
+mod markdown;
+
+use crate::calls::Call;
+use crate::gas_optimizer::{CPU_LIMIT, MEMORY_LIMIT};
+use crate::pretty;
+use crate::simulation;
+use crate::storage_log::StorageAccess;
+use crate::types::{SimulationRequest, SimulationResponse};
+use std::path::Path;
+
+pub use html::html;
+pub use markdown::markdown;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// Markdown.
+    Md,
+    /// A single HTML page with inline styles.
+    Html,
+}
+
+/// Renders the report of `response` in `format` and writes it to `path`.
+pub fn write(
+    format: Format,
+    path: &Path,
+    request: &SimulationRequest,
+    response: &SimulationResponse,
+) -> Result<(), String> {
+    let report = match format {
+        Format::Md => markdown(request, response),
+        Format::Html => html(request, response),
+    };
+    std::fs::write(path, report).map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+/// Label and value of each summary row.
+fn summary(
+    request: &SimulationRequest,
+    response: &SimulationResponse,
+) -> Vec<(&'static str, String)> {
+    let mut rows = vec![("Status", response.status.clone())];
+    if let Some(hash) = &response.transaction_hash {
+        rows.push(("Transaction", hash.clone()));
+    }
+    if let Some(source) = &response.source_account {
+        rows.push(("Source account", source.account.clone()));
+    }
+    if let Some(sequence) = response.ledger_sequence {
+        rows.push(("Ledger", sequence.to_string()));
+    }
+    if let Some(network) = &request.network {
+        rows.push(("Network", network.clone()));
+    }
+    rows.push((
+        "Simulator",
+        format!(
+            "erst-sim {}, soroban-env-host {}",
+            response.meta.simulator_version, response.meta.host_version
+        ),
+    ));
+    rows
+}
+
+/// Label and value of each fee and resource row.
+fn fees(request: &SimulationRequest, response: &SimulationResponse) -> Vec<(&'static str, String)> {
+    let mut rows = Vec::new();
+    if let Ok(envelope) = simulation::envelope(request) {
+        let fee = simulation::transaction_fee_stroops(&envelope);
+        rows.push(("Declared fee", format!("{fee} stroops")));
+    }
+    if let Some(fee) = response.required_fee {
+        rows.push(("Required fee (mocked)", format!("{fee} stroops")));
+    }
+    if let Some(rent) = &response.rent {
+        rows.push(("Rent fee", format!("{} stroops", rent.total_fee)));
+    }
+    if let Some(usage) = &response.budget_usage {
+        rows.push((
+            "CPU instructions",
+            format!(
+                "{} ({:.1}% of {CPU_LIMIT})",
+                usage.cpu_instructions, usage.cpu_usage_percent
+            ),
+        ));
+        rows.push((
+            "Memory bytes",
+            format!(
+                "{} ({:.1}% of {MEMORY_LIMIT})",
+                usage.memory_bytes, usage.memory_usage_percent
+            ),
+        ));
+    }
+    if let Some(resources) = &response.resources {
+        rows.push((
+            "Footprint",
+            format!(
+                "{} read-only, {} read-write",
+                resources.read_only.len(),
+                resources.read_write.len()
+            ),
+        ));
+        rows.push((
+            "Disk reads",
+            format!(
+                "{} entries, {} bytes",
+                resources.disk_read_entries, resources.disk_read_bytes
+            ),
+        ));
+        rows.push(("Write bytes", resources.write_bytes.to_string()));
+    }
+    rows
+}
+
+fn warnings(response: &SimulationResponse) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for check in &response.limit_checks {
+        let entry = check
+            .key
+            .as_ref()
+            .map_or(String::new(), |key| format!(" for entry {key}"));
+        warnings.push(format!(
+            "{}: {} is {} of {}{entry}{}",
+            check.severity,
+            check.limit,
+            check.value,
+            check.max,
+            check.top_costs()
+        ));
+    }
+    for reentry in &response.reentrancy {
+        warnings.push(format!(
+            "Reentrancy: {} is re-entered via {}",
+            pretty::short(&reentry.contract),
+            reentry.path.join(" → ")
+        ));
+    }
+    for warning in &response.auth_warnings {
+        warnings.push(format!(
+            "Possible missing authorization: {} by {} without {}'s authorization",
+            warning.change, warning.contract, warning.address
+        ));
+    }
+    for check in response.signature_checks.iter().filter(|c| !c.satisfied) {
+        warnings.push(format!(
+            "{} has signatures worth {} of the {} required",
+            check.account, check.signed_weight, check.required_weight
+        ));
+    }
+    for trustline in &response.trustlines {
+        if let Some(problem) = &trustline.problem {
+            warnings.push(problem.clone());
+        }
+    }
+    if response
+        .rent
+        .as_ref()
+        .is_some_and(|rent| !rent.params_from_ledger)
+    {
+        warnings.push("Rent was priced with default network settings.".to_string());
+    }
+    warnings
+}
+
+/// Entries the transaction created, updated or deleted.
+fn writes(response: &SimulationResponse) -> Vec<&StorageAccess> {
+    response
+        .storage_accesses
+        .iter()
+        .filter(|access| access.operation != "read")
+        .collect()
+}
+
+/// `120 → 500` for an extended TTL.
+fn live_until(access: &StorageAccess) -> String {
+    match (access.live_until_before, access.live_until_after) {
+        (Some(before), Some(after)) if before != after => format!("{before} → {after}"),
+        (_, Some(after)) => after.to_string(),
+        (Some(before), None) => before.to_string(),
+        (None, None) => String::new(),
+    }
+}
+
+/// `CBXK…WXYZ::swap(1, 2)`.
+fn invocation(call: &Call) -> String {
+    format!(
+        "{}::{}({})",
+        pretty::short(&call.contract),
+        call.function,
+        call.args.join(", ")
+    )
+}
+
+/// The cost of `call`, when attributed, like `1200 CPU instructions (300
+/// own)`.
+fn call_cost(call: &Call) -> Option<String> {
+    call.cost.map(|cost| {
+        format!(
+            "{} CPU instructions ({} own)",
+            cost.inclusive.cpu_instructions, cost.own.cpu_instructions
+        )
+    })
+}
+
+/// The error `call` failed with, named when the contract's spec names it,
+/// like `OverLimit (Error(Contract(3)))`.
+fn call_error(call: &Call) -> Option<String> {
+    let error = call.error.as_deref()?;
+    Some(match &call.error_case {
+        Some(case) => format!("{case} ({error})"),
+        None => error.to_string(),
+    })
+}
+
+#[cfg(test)]
+fn test_request() -> SimulationRequest {
+    serde_json::from_value(serde_json::json!({
+        "envelope_xdr": "",
+        "result_meta_xdr": "",
+        "enable_optimization_advisor": false,
+        "timestamp": "0",
+    }))
+    .unwrap()
+}
+
+#[cfg(test)]
+fn test_response() -> SimulationResponse {
+    let mut response = simulation::error_response("boom".to_string());
+    response.status = "success".to_string();
+    response.error = None;
+    response.call_tree = vec![Call {
+        contract: "CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA".to_string(),
+        function: "swap".to_string(),
+        args: vec!["1".to_string()],
+        result: Some("true".to_string()),
+        error: None,
+        error_case: None,
+        calls: vec![Call {
+            contract: "CBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBB".to_string(),
+            function: "transfer".to_string(),
+            args: vec![],
+            result: None,
+            error: None,
+            error_case: None,
+            calls: vec![],
+            cost: None,
+        }],
+        cost: None,
+    }];
+    response
+}
@@ -0,0 +1,249 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Footprint and byte resources to declare in `SorobanResources`.
+//!
+//! The network rejects a transaction whose execution reads or writes more
+//! than it declared, so these are computed the way stellar-core meters
+//! them rather than estimated:
+//! - `disk_read_bytes` sums the XDR size of every classic entry in the
+//!   footprint and of every archived persistent entry that is restored.
+//!   Live contract data and code are held in memory and cost nothing to read.
+//! - `write_bytes` sums the XDR size of every read-write entry as written
+//!   back. Deleted entries count as 0.
+//!
+//! TTL entries are never part of the footprint; their size is covered by the
+//! entries they belong to.
+
+use crate::source::SourceEntry;
+use schemars::JsonSchema;
+use serde::Serialize;
+use soroban_env_host::budget::Budget;
+use soroban_env_host::storage::{AccessType, Storage};
+use soroban_env_host::xdr::{ContractDataDurability, LedgerKey, Limits, WriteXdr};
+
+/// What to declare in the transaction's `SorobanResources`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct Resources {
+    /// Base64 `LedgerKey`s of the read-only footprint.
+    pub read_only: Vec<String>,
+    /// Base64 `LedgerKey`s of the read-write footprint.
+    pub read_write: Vec<String>,
+    /// Indices into `read_write` of archived entries the transaction
+    /// restores (`archivedSorobanEntries`).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub archived_entries: Vec<u32>,
+    /// Footprint entries read from disk, whether or not they exist.
+    pub disk_read_entries: u32,
+    pub disk_read_bytes: u32,
+    pub write_bytes: u32,
+}
+
+/// Computes the resources of the execution that left `storage`, from the
+/// state `before` it at `ledger_sequence`.
+pub fn resources(
+    storage: &Storage,
+    before: impl Fn(&LedgerKey) -> Option<SourceEntry>,
+    ledger_sequence: u32,
+) -> Result<Resources, String> {
+    let budget = Budget::default();
+    let footprint = storage
+        .footprint
+        .0
+        .iter(&budget)
+        .map_err(|e| format!("{e:?}"))?;
+    let mut resources = Resources::default();
+    for (key, access) in footprint {
+        let old = before(key);
+        if is_disk_read(key, old.as_ref(), ledger_sequence) {
+            resources.disk_read_entries += 1;
+            if let Some(old) = &old {
+                resources.disk_read_bytes = resources
+                    .disk_read_bytes
+                    .saturating_add(xdr_len(&old.entry)?);
+            }
+            if is_persistent(key) && *access == AccessType::ReadWrite {
+                let index = u32::try_from(resources.read_write.len()).map_err(|e| e.to_string())?;
+                resources.archived_entries.push(index);
+            }
+        }
+        let encoded = key
+            .to_xdr_base64(Limits::none())
+            .map_err(|e| e.to_string())?;
+        match access {
+            AccessType::ReadOnly => resources.read_only.push(encoded),
+            AccessType::ReadWrite => {
+                let written = storage
+                    .map
+                    .get::<LedgerKey>(key, &budget)
+                    .map_err(|e| format!("{e:?}"))?
+                    .and_then(Option::as_ref)
+                    .map(|(entry, _)| xdr_len(entry.as_ref()))
+                    .transpose()?
+                    .unwrap_or(0);
+                resources.write_bytes = resources.write_bytes.saturating_add(written);
+                resources.read_write.push(encoded);
+            }
+        }
+    }
+    Ok(resources)
+}
+
+/// Whether reading `key` counts as a disk read: classic entries always do,
+/// persistent contract entries only when archived (`before` has expired).
+pub fn is_disk_read(key: &LedgerKey, before: Option<&SourceEntry>, ledger_sequence: u32) -> bool {
+    match key {
+        LedgerKey::ContractData(_) | LedgerKey::ContractCode(_) => {
+            is_persistent(key)
+                && before
+                    .and_then(|before| before.live_until)
+                    .is_some_and(|live_until| live_until < ledger_sequence)
+        }
+        _ => true,
+    }
+}
+
+/// Whether `key` is a persistent contract entry (data or code).
+pub fn is_persistent(key: &LedgerKey) -> bool {
+    match key {
+        LedgerKey::ContractData(data) => data.durability == ContractDataDurability::Persistent,
+        LedgerKey::ContractCode(_) => true,
+        _ => false,
+    }
+}
+
+/// XDR size of `value`, as metered by the network.
+pub fn xdr_len(value: &impl WriteXdr) -> Result<u32, String> {
+    let bytes = value.to_xdr(Limits::none()).map_err(|e| e.to_string())?;
+    u32::try_from(bytes.len()).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::storage::{Footprint, FootprintMap, StorageMap};
+    use soroban_env_host::xdr::{
+        AccountEntry, AccountEntryExt, AccountId, ContractDataEntry, ContractId, ExtensionPoint,
+        Hash, LedgerEntry, LedgerEntryData, LedgerEntryExt, LedgerKeyAccount,
+        LedgerKeyContractData, PublicKey, ScAddress, ScVal, SequenceNumber, String32, Thresholds,
+        Uint256,
+    };
+    use std::rc::Rc;
+
+    fn data_key(name: &str) -> LedgerKey {
+        LedgerKey::ContractData(LedgerKeyContractData {
+            contract: ScAddress::Contract(ContractId(Hash([7; 32]))),
+            key: ScVal::Symbol(name.try_into().unwrap()),
+            durability: ContractDataDurability::Persistent,
+        })
+    }
+
+    fn data_entry(name: &str, value: u32) -> LedgerEntry {
+        LedgerEntry {
+            last_modified_ledger_seq: 10,
+            data: LedgerEntryData::ContractData(ContractDataEntry {
+                ext: ExtensionPoint::V0,
+                contract: ScAddress::Contract(ContractId(Hash([7; 32]))),
+                key: ScVal::Symbol(name.try_into().unwrap()),
+                durability: ContractDataDurability::Persistent,
+                val: ScVal::U32(value),
+            }),
+            ext: LedgerEntryExt::V0,
+        }
+    }
+
+    fn account() -> (LedgerKey, LedgerEntry) {
+        let account_id = AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([1; 32])));
+        let key = LedgerKey::Account(LedgerKeyAccount {
+            account_id: account_id.clone(),
+        });
+        let entry = LedgerEntry {
+            last_modified_ledger_seq: 10,
+            data: LedgerEntryData::Account(AccountEntry {
+                account_id,
+                balance: 100,
+                seq_num: SequenceNumber(1),
+                num_sub_entries: 0,
+                inflation_dest: None,
+                flags: 0,
+                home_domain: String32::default(),
+                thresholds: Thresholds([1, 0, 0, 0]),
+                signers: Default::default(),
+                ext: AccountEntryExt::V0,
+            }),
+            ext: LedgerEntryExt::V0,
+        };
+        (key, entry)
+    }
+
+    #[test]
+    fn test_resources_meter_disk_reads_and_writes() {
+        let budget = Budget::default();
+        let (account_key, account_entry) = account();
+        let live = (data_key("live"), data_entry("live", 1));
+        let archived = (data_key("archived"), data_entry("archived", 2));
+        let deleted = (data_key("deleted"), data_entry("deleted", 3));
+        let mut footprint = vec![
+            (Rc::new(account_key.clone()), AccessType::ReadOnly),
+            (Rc::new(live.0.clone()), AccessType::ReadWrite),
+            (Rc::new(archived.0.clone()), AccessType::ReadWrite),
+            (Rc::new(deleted.0.clone()), AccessType::ReadWrite),
+        ];
+        let mut map = vec![
+            (
+                Rc::new(account_key.clone()),
+                Some((Rc::new(account_entry.clone()), None)),
+            ),
+            (
+                Rc::new(live.0.clone()),
+                Some((Rc::new(data_entry("live", 100_000)), Some(500))),
+            ),
+            (
+                Rc::new(archived.0.clone()),
+                Some((Rc::new(archived.1.clone()), Some(500))),
+            ),
+            (Rc::new(deleted.0.clone()), None),
+        ];
+        footprint.sort_by(|a, b| a.0.cmp(&b.0));
+        map.sort_by(|a, b| a.0.cmp(&b.0));
+        let storage = Storage::with_enforcing_footprint_and_map(
+            Footprint(FootprintMap::from_map(footprint, &budget).unwrap()),
+            StorageMap::from_map(map, &budget).unwrap(),
+        );
+        let before = |key: &LedgerKey| {
+            let (entry, live_until) = if *key == account_key {
+                (account_entry.clone(), None)
+            } else if *key == live.0 {
+                (live.1.clone(), Some(400))
+            } else if *key == archived.0 {
+                (archived.1.clone(), Some(20))
+            } else {
+                (deleted.1.clone(), Some(400))
+            };
+            Some(SourceEntry { entry, live_until })
+        };
+
+        let resources = resources(&storage, before, 50).unwrap();
+        let size = |entry: &LedgerEntry| xdr_len(entry).unwrap();
+        assert_eq!(
+            resources.disk_read_bytes,
+            size(&account_entry) + size(&archived.1)
+        );
+        assert_eq!(
+            resources.write_bytes,
+            size(&data_entry("live", 100_000)) + size(&archived.1)
+        );
+        assert_eq!(resources.disk_read_entries, 2);
+        assert_eq!(resources.read_only.len(), 1);
+        assert_eq!(resources.read_write.len(), 3);
+        let archived_key = archived.0.to_xdr_base64(Limits::none()).unwrap();
+        assert_eq!(
+            resources
+                .archived_entries
+                .iter()
+                .map(|&i| &resources.read_write[i as usize])
+                .collect::<Vec<_>>(),
+            vec![&archived_key]
+        );
+    }
+}
@@ -30,8 +30,30 @@ impl SimHost {
         calibration: Option<crate::types::ResourceCalibration>,
         memory_limit: Option<u64>
     ) -> Self {
-        let budget = Budget::default();
+        if let Some((_cpu, _mem)) = budget_limits {
+            // Budget customization requires testutils feature or extended API
+            // Using default mainnet budget settings
+        }
+        Self::with_storage(Storage::default(), calibration, memory_limit)
+    }
+
+    /// Initialize a Host over `storage` with optional resource calibration.
+    pub fn with_storage(
+        storage: Storage,
+        calibration: Option<crate::types::ResourceCalibration>,
+        memory_limit: Option<u64>
+    ) -> Self {
+        Self::with_storage_and_budget(storage, Budget::default(), calibration, memory_limit)
+    }
 
+    /// Initialize a Host over `storage` charging `budget`, with optional
+    /// resource calibration applied on top of its cost models.
+    pub fn with_storage_and_budget(
+        storage: Storage,
+        budget: Budget,
+        calibration: Option<crate::types::ResourceCalibration>,
+        memory_limit: Option<u64>
+    ) -> Self {
         if let Some(calib) = calibration {
             use soroban_env_host::budget::CostModel;
             use soroban_env_host::xdr::ContractCostType;
@@ -58,13 +80,8 @@ impl SimHost {
             let _ = budget.set_model(ContractCostType::VerifyEd25519Sig, ed25519_model);
         }
 
-        if let Some((_cpu, _mem)) = budget_limits {
-            // Budget customization requires testutils feature or extended API
-            // Using default mainnet budget settings
-        }
-
         // Host::with_storage_and_budget is available in recent versions
-        let host = Host::with_storage_and_budget(Storage::default(), budget);
+        let host = Host::with_storage_and_budget(storage, budget);
 
         // Enable debug mode for better diagnostics
         host.set_diagnostic_level(DiagnosticLevel::Debug).expect("failed to set diagnostic level");
@@ -0,0 +1,328 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Findings in SARIF 2.1.0, so code-review tools annotate them.
+//!
+//! A simulation yields findings for entries accessed outside the envelope's
+//! declared footprint, authorization failures and unmet signature
+//! thresholds, and network limits exceeded or nearly so. A scenario adds a
+//! finding for every failed assertion.
+
+use crate::scenario::ScenarioOutcome;
+use crate::simulation;
+use crate::source_mapper::SourceLocation;
+use crate::types::{SimulationRequest, SimulationResponse};
+use soroban_env_host::xdr::{Limits, WriteXdr};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Rule IDs and their descriptions.
+const RULES: &[(&str, &str)] = &[
+    (
+        "footprint-undeclared-read",
+        "The transaction reads a ledger entry its footprint does not declare.",
+    ),
+    (
+        "footprint-undeclared-write",
+        "The transaction writes a ledger entry its footprint does not declare read-write.",
+    ),
+    (
+        "auth-failure",
+        "The transaction failed an authorization check.",
+    ),
+    (
+        "auth-missing",
+        "A call changed an address's state without that address's authorization.",
+    ),
+    (
+        "auth-insufficient-signatures",
+        "The envelope's signatures do not meet an account's threshold.",
+    ),
+    (
+        "reentrancy",
+        "A contract was called while already on the call stack.",
+    ),
+    (
+        "network-limit",
+        "The transaction exceeds or comes close to a network limit.",
+    ),
+    (
+        "storage-growth",
+        "A contract's storage grew with every call across a scenario.",
+    ),
+    (
+        "assertion-failed",
+        "A scenario step did not produce the expected outcome.",
+    ),
+];
+
+#[derive(Debug, Clone)]
+pub struct Finding {
+    /// One of [`RULES`].
+    pub rule: &'static str,
+    /// `error`, `warning` or `note`.
+    pub level: &'static str,
+    pub message: String,
+    /// Contract source the finding points at, when debug info maps it.
+    pub location: Option<SourceLocation>,
+}
+
+impl Finding {
+    fn new(rule: &'static str, level: &'static str, message: String) -> Self {
+        Self {
+            rule,
+            level,
+            message,
+            location: None,
+        }
+    }
+}
+
+/// Findings of `response`, the simulation of `request`.
+pub fn findings(request: &SimulationRequest, response: &SimulationResponse) -> Vec<Finding> {
+    let mut findings = footprint(request, response);
+    if let Some(error) = response
+        .error
+        .as_deref()
+        .filter(|e| e.contains("Error(Auth,"))
+    {
+        let mut finding = Finding::new("auth-failure", "error", error.trim_end().to_string());
+        finding.location = response
+            .source_location
+            .as_deref()
+            .and_then(|loc| serde_json::from_str(loc).ok());
+        findings.push(finding);
+    }
+    for warning in &response.auth_warnings {
+        findings.push(Finding::new(
+            "auth-missing",
+            "warning",
+            format!(
+                "{} by {} without {}'s authorization",
+                warning.change, warning.contract, warning.address
+            ),
+        ));
+    }
+    for check in response.signature_checks.iter().filter(|c| !c.satisfied) {
+        findings.push(Finding::new(
+            "auth-insufficient-signatures",
+            "error",
+            format!(
+                "{} has signatures worth {} of the {} required ({} threshold)",
+                check.account,
+                check.signed_weight,
+                check.required_weight,
+                check.threshold_level.name()
+            ),
+        ));
+    }
+    for reentry in &response.reentrancy {
+        findings.push(Finding::new(
+            "reentrancy",
+            "warning",
+            format!(
+                "{} is re-entered via {}",
+                reentry.contract,
+                reentry.path.join(" → ")
+            ),
+        ));
+    }
+    for check in &response.limit_checks {
+        let entry = check
+            .key
+            .as_ref()
+            .map_or(String::new(), |key| format!(" for entry {key}"));
+        findings.push(Finding::new(
+            "network-limit",
+            check.severity,
+            format!(
+                "{} is {} of {}{entry}{}",
+                check.limit,
+                check.value,
+                check.max,
+                check.top_costs()
+            ),
+        ));
+    }
+    findings
+}
+
+/// Entries the host accessed beyond the envelope's declared footprint. An
+/// envelope without `SorobanTransactionData` declares nothing to check.
+fn footprint(request: &SimulationRequest, response: &SimulationResponse) -> Vec<Finding> {
+    let (Ok(envelope), Some(resources)) = (simulation::envelope(request), &response.resources)
+    else {
+        return Vec::new();
+    };
+    let Some(data) = simulation::soroban_data(&envelope) else {
+        return Vec::new();
+    };
+    let encode = |keys: &[soroban_env_host::xdr::LedgerKey]| {
+        keys.iter()
+            .filter_map(|key| key.to_xdr_base64(Limits::none()).ok())
+            .collect::<HashSet<_>>()
+    };
+    let read_only = encode(&data.resources.footprint.read_only);
+    let read_write = encode(&data.resources.footprint.read_write);
+    let mut findings = Vec::new();
+    for key in &resources.read_only {
+        if !read_only.contains(key) && !read_write.contains(key) {
+            findings.push(Finding::new(
+                "footprint-undeclared-read",
+                "error",
+                format!("Entry {key} is read but not in the declared footprint"),
+            ));
+        }
+    }
+    for key in &resources.read_write {
+        if !read_write.contains(key) {
+            let declared = if read_only.contains(key) {
+                "declared read-only"
+            } else {
+                "not in the declared footprint"
+            };
+            findings.push(Finding::new(
+                "footprint-undeclared-write",
+                "error",
+                format!("Entry {key} is written but {declared}"),
+            ));
+        }
+    }
+    findings
+}
+
+/// Findings of a scenario run: each step's, prefixed with its name, one
+/// per failed assertion and one per storage growth warning.
+pub fn scenario_findings(outcome: &ScenarioOutcome) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for step in &outcome.steps {
+        for finding in &step.findings {
+            findings.push(Finding {
+                message: format!("{}: {}", step.name, finding.message),
+                ..finding.clone()
+            });
+        }
+        for assertion in step.assertions.iter().filter(|a| !a.passed) {
+            let message = match &assertion.message {
+                Some(message) => {
+                    format!("{}: expected {}, but {message}", step.name, assertion.name)
+                }
+                None => format!("{}: expected {}", step.name, assertion.name),
+            };
+            findings.push(Finding::new("assertion-failed", "error", message));
+        }
+    }
+    for warning in &outcome.storage_growth {
+        findings.push(Finding::new("storage-growth", "warning", warning.message()));
+    }
+    findings
+}
+
+/// The SARIF log of `findings`. Findings without a source location point
+/// at `artifact`, the file that produced them, when there is one.
+pub fn sarif(findings: &[Finding], artifact: Option<&str>) -> serde_json::Value {
+    let rules = RULES
+        .iter()
+        .map(|(id, description)| {
+            serde_json::json!({
+                "id": id,
+                "shortDescription": { "text": description },
+            })
+        })
+        .collect::<Vec<_>>();
+    let results = findings
+        .iter()
+        .map(|finding| {
+            let mut result = serde_json::json!({
+                "ruleId": finding.rule,
+                "ruleIndex": RULES.iter().position(|(id, _)| *id == finding.rule),
+                "level": finding.level,
+                "message": { "text": finding.message },
+            });
+            let location = match (&finding.location, artifact) {
+                (Some(location), _) => Some(serde_json::json!({
+                    "artifactLocation": { "uri": location.file },
+                    "region": {
+                        "startLine": location.line.max(1),
+                        "startColumn": location.column.max(1),
+                    },
+                })),
+                (None, Some(artifact)) => Some(serde_json::json!({
+                    "artifactLocation": { "uri": artifact },
+                    "region": { "startLine": 1 },
+                })),
+                (None, None) => None,
+            };
+            if let Some(location) = location {
+                result["locations"] = serde_json::json!([{ "physicalLocation": location }]);
+            }
+            result
+        })
+        .collect::<Vec<_>>();
+    serde_json::json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": { "driver": {
+                "name": "erst-sim",
+                "version": env!("CARGO_PKG_VERSION"),
+                "rules": rules,
+            } },
+            "results": results,
+        }],
+    })
+}
+
+/// Writes the SARIF log of `findings` to `path`.
+pub fn write(path: &Path, findings: &[Finding], artifact: Option<&str>) -> Result<(), String> {
+    let log =
+        serde_json::to_string_pretty(&sarif(findings, artifact)).map_err(|e| e.to_string())?;
+    std::fs::write(path, log).map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::LimitCheck;
+
+    #[test]
+    fn test_sarif_reports_limit_and_auth_findings() {
+        let mut response =
+            simulation::error_response("HostError: Error(Auth, InvalidAction)\n".to_string());
+        response.limit_checks = vec![LimitCheck {
+            limit: "tx_max_write_bytes",
+            key: None,
+            value: 95,
+            max: 100,
+            severity: "warning",
+            top_contributors: vec![],
+        }];
+        let request = serde_json::from_value(serde_json::json!({
+            "envelope_xdr": "",
+            "result_meta_xdr": "",
+            "enable_optimization_advisor": false,
+            "timestamp": "0",
+        }))
+        .unwrap();
+
+        let findings = findings(&request, &response);
+        assert_eq!(
+            findings
+                .iter()
+                .map(|f| (f.rule, f.level))
+                .collect::<Vec<_>>(),
+            vec![("auth-failure", "error"), ("network-limit", "warning")]
+        );
+        assert_eq!(findings[1].message, "tx_max_write_bytes is 95 of 100");
+
+        let log = sarif(&findings, Some("scenario.json"));
+        let result = &log["runs"][0]["results"][1];
+        assert_eq!(result["ruleId"], "network-limit");
+        assert_eq!(result["ruleIndex"], 6);
+        assert_eq!(
+            result["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "scenario.json"
+        );
+        assert_eq!(log["version"], "2.1.0");
+    }
+}
@@ -0,0 +1,601 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Scenario files: sequences of simulations over shared ledger state.
+//!
+//! A scenario is a JSON file with initial `ledger_entries`, `defaults` for
+//! every request and a list of `steps`. Each step is a simulation request
+//! with assertions about its outcome. Steps run in order, and the ledger
+//! changes of each successful step are applied to the state the next one
+//! sees, so a deposit can be followed by a withdrawal. Between steps the
+//! ledger moves forward by the scenario's `advance`, one ledger and five
+//! seconds unless set, or by a step's own, e.g. past a time lock, and
+//! temporary entries whose TTL runs out are deleted:
+//!
+//! ```json
+//! { "name": "vault", "ledger_entries": { "AAAABg...": "AAAAAA..." },
+//!   "defaults": { "network": "testnet" },
+//!   "steps": [
+//!     { "name": "deposit", "request": { "invoke": { "contract_id": "C...",
+//!         "function": "deposit", "args": ["AAAACg..."] } } },
+//!     { "name": "overdraw", "request": { "envelope_xdr": "AAAAAg..." },
+//!       "expect": { "status": "error", "error_contains": "Error(Contract, #1)" } },
+//!     { "name": "unlock", "advance": { "ledgers": 100, "seconds": 500 },
+//!       "request": { "envelope_xdr": "AAAAAg..." } } ] }
+//! ```
+
+use crate::growth::{self, GrowthWarning};
+use crate::pretty;
+use crate::sarif::{self, Finding};
+use crate::simulation;
+use crate::snapshot::{self, LedgerSnapshot};
+use crate::source::cache::CachedSource;
+use crate::source::{self, SnapshotLedgerSource};
+use crate::types::SimulationRequest;
+use crate::xdr_limits;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use soroban_env_host::xdr::{
+    ContractDataDurability, LedgerEntryChange, LedgerKey, Limits, TransactionMeta, WriteXdr,
+};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Base64 `LedgerKey` to base64 `LedgerEntry`: the state before the
+    /// first step.
+    #[serde(default)]
+    pub ledger_entries: HashMap<String, String>,
+    /// Request fields every step gets unless it sets them itself.
+    #[serde(default)]
+    pub defaults: serde_json::Map<String, serde_json::Value>,
+    /// How far the ledger moves forward between steps.
+    #[serde(default = "Advance::between_steps")]
+    pub advance: Advance,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Step {
+    #[serde(default)]
+    pub name: Option<String>,
+    /// A `SimulationRequest`, without the fields `defaults` supplies. Its own
+    /// `ledger_entries` override the scenario's state for this step.
+    pub request: serde_json::Map<String, serde_json::Value>,
+    #[serde(default)]
+    pub expect: Expect,
+    /// How far to move the ledger forward before the step runs, instead of
+    /// the scenario's `advance`.
+    #[serde(default)]
+    pub advance: Option<Advance>,
+}
+
+/// Ledgers and seconds to advance the simulated ledger by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Advance {
+    /// Added to the ledger sequence.
+    #[serde(default)]
+    pub ledgers: u32,
+    /// Added to the ledger close time.
+    #[serde(default)]
+    pub seconds: u64,
+}
+
+impl Advance {
+    /// One ledger, about as long as the network takes to close one.
+    pub const fn between_steps() -> Self {
+        Self {
+            ledgers: 1,
+            seconds: 5,
+        }
+    }
+}
+
+/// What a step must produce.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expect {
+    /// `success` (the default) or `error`.
+    #[serde(default)]
+    pub status: Option<String>,
+    /// Text the error must contain.
+    #[serde(default)]
+    pub error_contains: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScenarioOutcome {
+    pub name: String,
+    pub passed: bool,
+    pub steps: Vec<StepOutcome>,
+    /// Contracts whose storage grew with every call.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub storage_growth: Vec<GrowthWarning>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StepOutcome {
+    pub name: String,
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub assertions: Vec<Assertion>,
+    /// Base64 `LedgerKey`s of the temporary entries whose TTL ran out before
+    /// the step, deleted from the state.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub expired: Vec<String>,
+    #[serde(rename = "duration_ms", serialize_with = "millis")]
+    pub duration: Duration,
+    /// Budget the step's execution used, when it ran.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost: Option<StepCost>,
+    /// Findings of the step's simulation, for SARIF output.
+    #[serde(skip)]
+    pub findings: Vec<Finding>,
+}
+
+/// What a step's execution cost.
+#[derive(Debug, Clone, Serialize)]
+pub struct StepCost {
+    /// Top-level call, e.g. `CBXK…WXYZ::deposit`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub function: Option<String>,
+    pub cpu_instructions: u64,
+    pub memory_bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Assertion {
+    /// What was checked, e.g. `status is success`.
+    pub name: String,
+    pub passed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl ScenarioOutcome {
+    pub fn assertions(&self) -> impl Iterator<Item = (&StepOutcome, &Assertion)> {
+        self.steps
+            .iter()
+            .flat_map(|step| step.assertions.iter().map(move |a| (step, a)))
+    }
+}
+
+fn millis<S: serde::Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u128(duration.as_millis())
+}
+
+pub fn load(path: &Path) -> Result<Scenario, String> {
+    let file =
+        std::fs::read(path).map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    serde_json::from_slice(&file).map_err(|e| format!("Invalid scenario {}: {e}", path.display()))
+}
+
+/// Runs the steps of `scenario` in order. `remote` gives each request the
+/// state source it names, if any. A step whose request cannot be simulated
+/// at all fails its assertions rather than the scenario.
+pub fn run(
+    scenario: &Scenario,
+    default_name: &str,
+    limits: &crate::memory::MemoryLimits,
+    remote: impl Fn(&SimulationRequest) -> Option<Arc<CachedSource>>,
+) -> ScenarioOutcome {
+    let mut state = scenario.ledger_entries.clone();
+    // Sequence and close time of the ledger the last step ran at.
+    let mut ledger = None;
+    let mut steps = Vec::new();
+    let mut growth = growth::Tracker::default();
+    for (index, step) in scenario.steps.iter().enumerate() {
+        let name = step
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("step {}", index + 1));
+        let by = match (step.advance, ledger) {
+            (Some(by), _) => by,
+            (None, Some(_)) => scenario.advance,
+            (None, None) => Advance::default(),
+        };
+        let started = Instant::now();
+        let mut sequence = None;
+        let result = request(scenario, step, &state).and_then(|mut request| {
+            let remote = remote(&request);
+            advance(&mut request, by, ledger, remote.as_deref())?;
+            sequence = request.ledger_sequence;
+            if let (Some(entries), Some(sequence)) = (&mut request.ledger_entries, sequence) {
+                expire(entries, sequence);
+            }
+            let response =
+                simulation::run(&request, limits, None, remote, None).map_err(|e| e.to_string())?;
+            Ok((sarif::findings(&request, &response), response))
+        });
+        let duration = started.elapsed();
+        let expired = sequence.map_or_else(Vec::new, |sequence| expire(&mut state, sequence));
+        let (status, error, meta, findings, cost) = match result {
+            Ok((findings, response)) => {
+                if let Some(at) = response.ledger_sequence.zip(response.ledger_close_time) {
+                    ledger = Some(at);
+                }
+                if response.status == "success" {
+                    growth.record(&name, &response.storage_accesses);
+                }
+                let cost = response.budget_usage.as_ref().map(|usage| StepCost {
+                    function: response.call_tree.first().map(|call| {
+                        format!("{}::{}", pretty::short(&call.contract), call.function)
+                    }),
+                    cpu_instructions: usage.cpu_instructions,
+                    memory_bytes: usage.memory_bytes,
+                });
+                (
+                    response.status,
+                    response.error,
+                    response.result_meta_xdr,
+                    findings,
+                    cost,
+                )
+            }
+            Err(e) => ("error".to_string(), Some(e), None, Vec::new(), None),
+        };
+        if let Some(meta) = meta.filter(|_| status == "success") {
+            if let Err(e) = apply_changes(&mut state, &meta) {
+                steps.push(StepOutcome {
+                    name,
+                    status,
+                    error: Some(e.clone()),
+                    assertions: vec![Assertion {
+                        name: "ledger changes apply".to_string(),
+                        passed: false,
+                        message: Some(e),
+                    }],
+                    expired,
+                    duration,
+                    cost,
+                    findings,
+                });
+                continue;
+            }
+        }
+        let assertions = check(&step.expect, &status, error.as_deref());
+        steps.push(StepOutcome {
+            name,
+            status,
+            error,
+            assertions,
+            expired,
+            duration,
+            cost,
+            findings,
+        });
+    }
+    let passed = steps
+        .iter()
+        .all(|step| step.assertions.iter().all(|a| a.passed));
+    ScenarioOutcome {
+        name: scenario
+            .name
+            .clone()
+            .unwrap_or_else(|| default_name.to_string()),
+        passed,
+        steps,
+        storage_growth: growth.warnings(),
+    }
+}
+
+/// The request of `step`: its own fields, then the scenario's defaults,
+/// with the current state under the step's own entries.
+pub fn request(
+    scenario: &Scenario,
+    step: &Step,
+    state: &HashMap<String, String>,
+) -> Result<SimulationRequest, String> {
+    let mut request = step.request.clone();
+    for (field, value) in &scenario.defaults {
+        request
+            .entry(field.clone())
+            .or_insert_with(|| value.clone());
+    }
+    let mut entries = state.clone();
+    if let Some(own) = request.remove("ledger_entries") {
+        let own: HashMap<String, String> =
+            serde_json::from_value(own).map_err(|e| format!("Invalid ledger_entries: {e}"))?;
+        entries.extend(own);
+    }
+    request.insert(
+        "ledger_entries".to_string(),
+        serde_json::to_value(entries).map_err(|e| e.to_string())?,
+    );
+    for (field, value) in [
+        ("result_meta_xdr", serde_json::json!("")),
+        ("enable_optimization_advisor", serde_json::json!(false)),
+        ("timestamp", serde_json::json!("")),
+    ] {
+        request.entry(field).or_insert(value);
+    }
+    serde_json::from_value(serde_json::Value::Object(request))
+        .map_err(|e| format!("Invalid request: {e}"))
+}
+
+/// Runs `request` at `previous`, the ledger the last step ran at, moved
+/// forward by `advance`, unless the request sets its own ledger. The first
+/// step advances from the ledger it would otherwise run at.
+fn advance(
+    request: &mut SimulationRequest,
+    advance: Advance,
+    previous: Option<(u32, u64)>,
+    remote: Option<&CachedSource>,
+) -> Result<(), String> {
+    let (sequence, close_time) = match previous {
+        Some(previous) => previous,
+        None if advance == Advance::default() => return Ok(()),
+        None => {
+            let sequence = match remote {
+                Some(remote) => remote.latest_ledger().map_err(|e| e.to_string())?,
+                None => {
+                    let entries = request.ledger_entries.clone().unwrap_or_default();
+                    let snapshot = LedgerSnapshot::from_base64_map(&entries)
+                        .map_err(|e| format!("Invalid ledger_entries: {e}"))?;
+                    SnapshotLedgerSource::new(snapshot)
+                        .max_last_modified()
+                        .unwrap_or(0)
+                }
+            };
+            (sequence, simulation::ledger_timestamp())
+        }
+    };
+    request
+        .ledger_sequence
+        .get_or_insert(sequence.saturating_add(advance.ledgers));
+    request
+        .ledger_close_time
+        .get_or_insert(close_time.saturating_add(advance.seconds));
+    Ok(())
+}
+
+/// Deletes the temporary entries of `entries` whose TTL ran out before
+/// ledger `sequence`, and their TTL entries, as the network does. Returns
+/// the keys of the deleted entries.
+pub fn expire(entries: &mut HashMap<String, String>, sequence: u32) -> Vec<String> {
+    let mut expired = entries
+        .keys()
+        .filter_map(|key_xdr| {
+            let key = snapshot::decode_ledger_key(key_xdr).ok()?;
+            if !matches!(&key, LedgerKey::ContractData(data)
+                if data.durability == ContractDataDurability::Temporary)
+            {
+                return None;
+            }
+            let ttl_xdr = source::ttl_key(&key)
+                .ok()?
+                .to_xdr_base64(Limits::none())
+                .ok()?;
+            let ttl = entries
+                .get(&ttl_xdr)
+                .and_then(|ttl| snapshot::decode_ledger_entry(ttl).ok());
+            source::live_until(&key, ttl.as_ref())
+                .filter(|&live_until| live_until < sequence)
+                .map(|_| (key_xdr.clone(), ttl_xdr))
+        })
+        .collect::<Vec<_>>();
+    expired.sort();
+    expired
+        .into_iter()
+        .map(|(key_xdr, ttl_xdr)| {
+            entries.remove(&key_xdr);
+            entries.remove(&ttl_xdr);
+            key_xdr
+        })
+        .collect()
+}
+
+fn check(expect: &Expect, status: &str, error: Option<&str>) -> Vec<Assertion> {
+    let expected = expect.status.as_deref().unwrap_or("success");
+    let mut assertions = vec![Assertion {
+        name: format!("status is {expected}"),
+        passed: status == expected,
+        message: (status != expected).then(|| match error {
+            Some(error) => format!("status is {status}: {error}"),
+            None => format!("status is {status}"),
+        }),
+    }];
+    if let Some(text) = &expect.error_contains {
+        let passed = error.is_some_and(|error| error.contains(text.as_str()));
+        assertions.push(Assertion {
+            name: format!("error contains {text:?}"),
+            passed,
+            message: (!passed).then(|| match error {
+                Some(error) => format!("error is {error:?}"),
+                None => "no error".to_string(),
+            }),
+        });
+    }
+    assertions
+}
+
+/// Applies the ledger changes of a base64 `TransactionMeta` to `state`.
+pub fn apply_changes(state: &mut HashMap<String, String>, meta_xdr: &str) -> Result<(), String> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(meta_xdr)
+        .map_err(|e| format!("Invalid result meta: {e}"))?;
+    let meta = xdr_limits::read::<TransactionMeta>(&bytes)
+        .map_err(|e| format!("Invalid result meta: {e}"))?;
+    let changes = match &meta {
+        TransactionMeta::V4(meta) => meta
+            .operations
+            .iter()
+            .flat_map(|op| op.changes.iter())
+            .collect::<Vec<_>>(),
+        TransactionMeta::V3(meta) => meta
+            .operations
+            .iter()
+            .flat_map(|op| op.changes.iter())
+            .collect(),
+        _ => Vec::new(),
+    };
+    let encode = |xdr: &dyn Fn() -> Result<String, soroban_env_host::xdr::Error>| {
+        xdr().map_err(|e| format!("Failed to encode ledger change: {e}"))
+    };
+    for change in changes {
+        match change {
+            LedgerEntryChange::Created(entry)
+            | LedgerEntryChange::Updated(entry)
+            | LedgerEntryChange::Restored(entry) => {
+                let key = crate::snapshot::entry_key(entry);
+                state.insert(
+                    encode(&|| key.to_xdr_base64(Limits::none()))?,
+                    encode(&|| entry.to_xdr_base64(Limits::none()))?,
+                );
+            }
+            LedgerEntryChange::Removed(key) => {
+                state.remove(&encode(&|| key.to_xdr_base64(Limits::none()))?);
+            }
+            LedgerEntryChange::State(_) => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scenario(json: serde_json::Value) -> Scenario {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_step_request_merges_defaults_and_state() {
+        let scenario = scenario(serde_json::json!({
+            "ledger_entries": { "a": "1", "b": "2" },
+            "defaults": { "network": "testnet", "mock_base_fee": 100 },
+            "steps": [{ "request": {
+                "mock_base_fee": 200,
+                "ledger_entries": { "b": "3" },
+            } }],
+        }));
+        let request = request(&scenario, &scenario.steps[0], &scenario.ledger_entries).unwrap();
+        assert_eq!(request.network.as_deref(), Some("testnet"));
+        assert_eq!(request.mock_base_fee, Some(200));
+        let entries = request.ledger_entries.unwrap();
+        assert_eq!(entries["a"], "1");
+        assert_eq!(entries["b"], "3");
+    }
+
+    #[test]
+    fn test_advance_moves_the_previous_ledger_forward() {
+        let scenario = scenario(serde_json::json!({
+            "steps": [
+                { "request": {} },
+                { "advance": { "ledgers": 10 }, "request": { "ledger_sequence": 7 } },
+            ],
+        }));
+        assert_eq!(scenario.advance, Advance::between_steps());
+        assert_eq!(
+            scenario.steps[1].advance,
+            Some(Advance {
+                ledgers: 10,
+                seconds: 0
+            })
+        );
+        let at = |index: usize, by, previous| {
+            let mut request = request(&scenario, &scenario.steps[index], &HashMap::new()).unwrap();
+            advance(&mut request, by, previous, None).unwrap();
+            (request.ledger_sequence, request.ledger_close_time)
+        };
+        let by = Advance {
+            ledgers: 10,
+            seconds: 50,
+        };
+        assert_eq!(at(0, Advance::default(), None), (None, None));
+        assert_eq!(at(0, by, Some((100, 1000))), (Some(110), Some(1050)));
+        assert_eq!(at(1, by, Some((100, 1000))), (Some(7), Some(1050)));
+        // The first step advances from the newest entry, at 0 here.
+        assert_eq!(at(0, by, None).0, Some(10));
+    }
+
+    #[test]
+    fn test_expired_temporary_entries_are_deleted() {
+        use soroban_env_host::xdr::{
+            ContractDataEntry, ContractId, ExtensionPoint, Hash, LedgerEntry, LedgerEntryData,
+            LedgerEntryExt, LedgerKeyContractData, ScAddress, ScVal, TtlEntry,
+        };
+        let entry = |index: u32, durability, live_until: u32| {
+            let contract = ScAddress::Contract(ContractId(Hash([1; 32])));
+            let key = LedgerKey::ContractData(LedgerKeyContractData {
+                contract: contract.clone(),
+                key: ScVal::U32(index),
+                durability,
+            });
+            let data = LedgerEntry {
+                last_modified_ledger_seq: 0,
+                data: LedgerEntryData::ContractData(ContractDataEntry {
+                    ext: ExtensionPoint::V0,
+                    contract,
+                    key: ScVal::U32(index),
+                    durability,
+                    val: ScVal::Void,
+                }),
+                ext: LedgerEntryExt::V0,
+            };
+            let ttl_key = source::ttl_key(&key).unwrap();
+            let LedgerKey::Ttl(ttl) = &ttl_key else {
+                unreachable!()
+            };
+            let ttl = LedgerEntry {
+                last_modified_ledger_seq: 0,
+                data: LedgerEntryData::Ttl(TtlEntry {
+                    key_hash: ttl.key_hash.clone(),
+                    live_until_ledger_seq: live_until,
+                }),
+                ext: LedgerEntryExt::V0,
+            };
+            let b64 = |xdr: Result<String, _>| xdr.unwrap();
+            [
+                (
+                    b64(key.to_xdr_base64(Limits::none())),
+                    b64(data.to_xdr_base64(Limits::none())),
+                ),
+                (
+                    b64(ttl_key.to_xdr_base64(Limits::none())),
+                    b64(ttl.to_xdr_base64(Limits::none())),
+                ),
+            ]
+        };
+        let expiring = entry(1, ContractDataDurability::Temporary, 99);
+        let mut state = expiring
+            .iter()
+            .chain(&entry(2, ContractDataDurability::Temporary, 100))
+            .chain(&entry(3, ContractDataDurability::Persistent, 10))
+            .cloned()
+            .collect::<HashMap<_, _>>();
+
+        assert_eq!(expire(&mut state, 100), vec![expiring[0].0.clone()]);
+        assert_eq!(state.len(), 4);
+        assert!(!state.contains_key(&expiring[1].0));
+        assert_eq!(expire(&mut state, 100), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_check_expectations() {
+        let expect = Expect {
+            status: Some("error".to_string()),
+            error_contains: Some("#1".to_string()),
+        };
+        let assertions = check(&expect, "error", Some("Error(Contract, #1)"));
+        assert!(assertions.iter().all(|a| a.passed));
+
+        let assertions = check(&Expect::default(), "error", Some("boom"));
+        assert_eq!(assertions.len(), 1);
+        assert_eq!(assertions[0].name, "status is success");
+        assert_eq!(
+            assertions[0].message.as_deref(),
+            Some("status is error: boom")
+        );
+    }
+}
@@ -0,0 +1,265 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! JSON Schemas of the simulation protocol, and request validation.
+//!
+//! The schemas are derived from [`SimulationRequest`] and
+//! [`SimulationResponse`], so they cannot drift from what the simulator
+//! actually accepts and returns. `erst-sim schema` prints them and server
+//! mode serves them at `/schema/request` and `/schema/response`.
+//!
+//! [`validate_request`] checks a request against the request schema and then
+//! checks that every base64 field decodes, reporting each problem with the
+//! path of the offending field (`auth_entries[3]: invalid base64`) instead of
+//! serde's first-error-only message.
+
+use crate::types::{SimulationRequest, SimulationResponse};
+use base64::Engine as _;
+use jsonschema::error::{TypeKind, ValidationErrorKind};
+use jsonschema::paths::LocationSegment;
+use jsonschema::Validator;
+use schemars::generate::SchemaSettings;
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::OnceLock;
+
+/// Maximum number of problems reported for one request.
+pub const MAX_ERRORS: usize = 20;
+
+/// A problem with one field of a request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldError {
+    /// Path of the field, e.g. `ledger_entries["AAAA..."]` or `auth_entries[3]`.
+    pub path: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for FieldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// The JSON Schema of `SimulationRequest`.
+pub fn request_schema() -> Value {
+    schemars::schema_for!(SimulationRequest).to_value()
+}
+
+/// The JSON Schema of `SimulationResponse`, describing what the simulator
+/// emits: fields skipped when empty are not required.
+pub fn response_schema() -> Value {
+    SchemaSettings::draft2020_12()
+        .for_serialize()
+        .into_generator()
+        .into_root_schema_for::<SimulationResponse>()
+        .to_value()
+}
+
+fn request_validator() -> &'static Validator {
+    static VALIDATOR: OnceLock<Validator> = OnceLock::new();
+    VALIDATOR.get_or_init(|| {
+        jsonschema::validator_for(&request_schema()).expect("derived request schema is valid")
+    })
+}
+
+/// Validates `request` against the request schema and checks its base64
+/// fields, returning every problem found (up to a limit).
+pub fn validate_request(request: &Value) -> Result<(), Vec<FieldError>> {
+    let mut errors: Vec<FieldError> = request_validator()
+        .iter_errors(request)
+        .map(|e| {
+            let mut path = render_path(e.instance_path());
+            let message = match e.kind() {
+                ValidationErrorKind::Required { property } => {
+                    path = join(&path, &property_segment(property.as_str().unwrap_or("?")));
+                    "is required".to_string()
+                }
+                ValidationErrorKind::Type { kind } => format!("expected {}", type_name(kind)),
+                _ => e.to_string(),
+            };
+            FieldError { path, message }
+        })
+        .take(MAX_ERRORS)
+        .collect();
+    // Content checks only make sense once the shape is right.
+    if errors.is_empty() {
+        check_base64(request, &mut errors);
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        errors.truncate(MAX_ERRORS);
+        Err(errors)
+    }
+}
+
+/// Joins field errors into one message.
+pub fn describe(errors: &[FieldError]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+fn check_base64(request: &Value, errors: &mut Vec<FieldError>) {
+    for field in ["envelope_xdr", "result_meta_xdr", "contract_wasm"] {
+        if let Some(value) = request.get(field).and_then(Value::as_str) {
+            check(errors, field.to_string(), value, "");
+        }
+    }
+    if let Some(entries) = request.get("ledger_entries").and_then(Value::as_object) {
+        for (key, value) in entries {
+            if let Some(value) = value.as_str() {
+                check_ledger_entry(key, value, errors);
+            }
+        }
+    }
+    if let Some(entries) = request.get("auth_entries").and_then(Value::as_array) {
+        for (i, entry) in entries.iter().enumerate() {
+            if let Some(entry) = entry.as_str() {
+                check(errors, format!("auth_entries[{i}]"), entry, "");
+            }
+        }
+    }
+    for config in ["protocol", "compare_protocol"] {
+        for field in ["cpu_cost_params", "memory_cost_params"] {
+            if let Some(value) = request
+                .get(config)
+                .and_then(|c| c.get(field))
+                .and_then(Value::as_str)
+            {
+                check(errors, format!("{config}.{field}"), value, "");
+            }
+        }
+    }
+    if let Some(args) = request.pointer("/invoke/args").and_then(Value::as_array) {
+        for (i, arg) in args.iter().enumerate() {
+            if let Some(arg) = arg.as_str() {
+                check(errors, format!("invoke.args[{i}]"), arg, "");
+            }
+        }
+    }
+}
+
+/// Checks that a `ledger_entries` key and value are base64.
+pub fn check_ledger_entry(key: &str, value: &str, errors: &mut Vec<FieldError>) {
+    let path = format!("ledger_entries[{}]", Value::from(key));
+    check(errors, path.clone(), key, " in key");
+    check(errors, path, value, "");
+}
+
+fn check(errors: &mut Vec<FieldError>, path: String, value: &str, what: &str) {
+    if base64::engine::general_purpose::STANDARD
+        .decode(value)
+        .is_err()
+    {
+        errors.push(FieldError {
+            path,
+            message: format!("invalid base64{what}"),
+        });
+    }
+}
+
+/// Renders a JSON pointer as `a.b[3]["odd key"]`.
+fn render_path(location: &jsonschema::paths::Location) -> String {
+    location
+        .into_iter()
+        .fold(String::new(), |path, segment| match segment {
+            LocationSegment::Index(i) => format!("{path}[{i}]"),
+            LocationSegment::Property(name) => join(&path, &property_segment(&name)),
+        })
+}
+
+fn property_segment(name: &str) -> String {
+    let plain = !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if plain {
+        name.to_string()
+    } else {
+        format!("[{}]", Value::from(name))
+    }
+}
+
+fn join(path: &str, segment: &str) -> String {
+    if path.is_empty() || segment.starts_with('[') {
+        format!("{path}{segment}")
+    } else {
+        format!("{path}.{segment}")
+    }
+}
+
+fn type_name(kind: &TypeKind) -> String {
+    match kind {
+        TypeKind::Single(ty) => ty.to_string(),
+        TypeKind::Multiple(types) => types
+            .iter()
+            .map(|ty| ty.to_string())
+            .collect::<Vec<_>>()
+            .join(" or "),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(extra: Value) -> Value {
+        let mut request = json!({
+            "envelope_xdr": "AAAA",
+            "result_meta_xdr": "",
+            "enable_optimization_advisor": false,
+            "timestamp": "",
+        });
+        request
+            .as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        request
+    }
+
+    #[test]
+    fn test_valid_request_passes() {
+        assert_eq!(validate_request(&request(json!({}))), Ok(()));
+    }
+
+    #[test]
+    fn test_errors_name_the_field() {
+        let errors = validate_request(&json!({
+            "envelope_xdr": 5,
+            "result_meta_xdr": "",
+            "enable_optimization_advisor": false,
+        }))
+        .unwrap_err();
+        let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert!(messages.contains(&"timestamp: is required".to_string()));
+        assert!(messages.contains(&"envelope_xdr: expected string".to_string()));
+
+        let errors = validate_request(&request(json!({
+            "ledger_entries": { "AAAA": "not base64!" },
+            "auth_entries": ["AAAA", "%%%"],
+        })))
+        .unwrap_err();
+        assert_eq!(
+            describe(&errors),
+            "ledger_entries[\"AAAA\"]: invalid base64; auth_entries[1]: invalid base64"
+        );
+    }
+
+    #[test]
+    fn test_schemas_describe_fields() {
+        let schema = request_schema();
+        assert!(schema["properties"]["auth_entries"].is_object());
+        let schema = response_schema();
+        assert!(schema["properties"]["signature_checks"].is_object());
+        let required = schema["required"].as_array().unwrap();
+        assert!(required.contains(&json!("status")));
+        assert!(!required.contains(&json!("signature_checks")));
+    }
+}
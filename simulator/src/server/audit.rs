@@ -0,0 +1,268 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Append-only audit log of simulation requests.
+//!
+//! With `--audit-log audit.jsonl` the server appends one JSON line for each
+//! `POST /simulate` request it decoded, once it is answered: when, a hash of
+//! the request (its [`response_cache::fingerprint`]), the API key and address
+//! that sent it, the outcome and HTTP status, and for simulations that ran
+//! the transaction hash and required fee. The file is opened for appending
+//! and never rewritten or rotated by the server.
+//!
+//! `--audit-redact` sets how much of the transaction is recorded. `envelope`,
+//! the default, records the source account and the contract and function
+//! invoked but not the envelope; `none` also records the envelope XDR; `all`
+//! records none of the three, nor error messages, which can quote arguments.
+//!
+//! [`response_cache::fingerprint`]: super::response_cache::fingerprint
+
+use crate::authorization::{self, SourceAccount};
+use crate::network;
+use crate::simulation;
+use crate::trustline;
+use crate::types::{SimulationRequest, SimulationResponse};
+use serde::Serialize;
+use soroban_env_host::xdr::{Limits, WriteXdr};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How much of the transaction audit records carry.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Redaction {
+    /// Record the envelope XDR too.
+    None,
+    /// Record the source account, contract and function, not the envelope.
+    #[default]
+    Envelope,
+    /// Record no part of the transaction.
+    All,
+}
+
+/// How the server answered a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// Simulated, and the transaction succeeded.
+    Success,
+    /// Simulated, and the transaction failed.
+    Failed,
+    /// Answered from the response cache.
+    Cached,
+    /// Answered with the response to an earlier request with the same
+    /// idempotency key.
+    Replayed,
+    /// Refused or not simulated, e.g. for another network or a bad envelope.
+    Rejected,
+}
+
+/// One line of the audit log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuditRecord {
+    /// Milliseconds since the Unix epoch at which the request was answered.
+    pub time_ms: u64,
+    /// Hex SHA-256 fingerprint of the request.
+    pub request_hash: String,
+    /// Name of the API key that presented the request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_addr: Option<IpAddr>,
+    pub outcome: Outcome,
+    pub http_status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required_fee: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_account: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contract: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub function: Option<String>,
+    /// Base64 `TransactionEnvelope` simulated, built from `invoke` for
+    /// builder-mode requests.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub envelope_xdr: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A decoded request, as the audit log sees it.
+#[derive(Debug, Clone, Copy)]
+pub struct Audited<'a> {
+    pub fingerprint: &'a [u8; 32],
+    pub client: Option<&'a str>,
+    pub remote_addr: Option<IpAddr>,
+    pub request: &'a SimulationRequest,
+}
+
+/// How a request was answered.
+#[derive(Debug, Clone, Copy)]
+pub struct Answer<'a> {
+    pub outcome: Outcome,
+    pub http_status: u16,
+    /// The simulation's response, if one was made for this request.
+    pub response: Option<&'a SimulationResponse>,
+    pub error: Option<&'a str>,
+}
+
+impl<'a> Answer<'a> {
+    /// A request answered with an earlier response, cached or replayed.
+    pub fn reused(outcome: Outcome) -> Self {
+        Self {
+            outcome,
+            http_status: 200,
+            response: None,
+            error: None,
+        }
+    }
+
+    /// A request refused with `http_status` because of `error`.
+    pub fn rejected(http_status: u16, error: &'a str) -> Self {
+        Self {
+            outcome: Outcome::Rejected,
+            http_status,
+            response: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// The audit log file.
+pub struct AuditLog {
+    file: Mutex<File>,
+    redaction: Redaction,
+}
+
+impl AuditLog {
+    /// Opens `path` for appending, creating it if needed.
+    pub fn open(path: &Path, redaction: Redaction) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("failed to open audit log {}: {e}", path.display()))?;
+        Ok(Self {
+            file: Mutex::new(file),
+            redaction,
+        })
+    }
+
+    /// Appends the record of `audited` answered with `answer`. Failures are
+    /// logged rather than failing the request.
+    pub fn record(&self, audited: &Audited, answer: &Answer) {
+        let record = record(audited, answer, self.redaction);
+        let mut line = match serde_json::to_vec(&record) {
+            Ok(line) => line,
+            Err(e) => {
+                tracing::warn!(event = "audit_log_failed", error = %e);
+                return;
+            }
+        };
+        line.push(b'\n');
+        // One write per line, so lines from concurrent requests do not
+        // interleave.
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = file.write_all(&line) {
+            tracing::warn!(event = "audit_log_failed", error = %e);
+        }
+    }
+}
+
+/// The record of `audited` answered with `answer`, under `redaction`.
+pub fn record(audited: &Audited, answer: &Answer, redaction: Redaction) -> AuditRecord {
+    let mut record = AuditRecord {
+        time_ms: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64),
+        request_hash: network::hex(audited.fingerprint),
+        client: audited.client.map(str::to_string),
+        remote_addr: audited.remote_addr,
+        outcome: answer.outcome,
+        http_status: answer.http_status,
+        transaction_hash: answer.response.and_then(|r| r.transaction_hash.clone()),
+        required_fee: answer.response.and_then(|r| r.required_fee),
+        source_account: None,
+        contract: None,
+        function: None,
+        envelope_xdr: None,
+        error: answer.error.map(str::to_string),
+    };
+    if redaction == Redaction::All {
+        record.error = None;
+        return record;
+    }
+    // Rejected requests may not have an envelope to describe.
+    let Ok(envelope) = simulation::envelope(audited.request) else {
+        return record;
+    };
+    let source = SourceAccount::from(&authorization::transaction_source(&envelope));
+    record.source_account = Some(source.muxed_address.unwrap_or(source.account));
+    if let Some(args) = trustline::invoked_contract(&envelope) {
+        record.contract = Some(args.contract_address.to_string());
+        record.function = Some(args.function_name.to_utf8_string_lossy());
+    }
+    if redaction == Redaction::None {
+        record.envelope_xdr = envelope.to_xdr_base64(Limits::none()).ok();
+    }
+    record
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use soroban_env_host::xdr::{AccountId, ContractId, Hash, PublicKey, ScAddress, Uint256};
+
+    #[test]
+    fn test_redaction_controls_what_is_recorded() {
+        let contract = ScAddress::Contract(ContractId(Hash([1; 32])));
+        let account =
+            ScAddress::Account(AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([2; 32]))));
+        let request = serde_json::from_value::<SimulationRequest>(json!({
+            "envelope_xdr": "",
+            "result_meta_xdr": "",
+            "enable_optimization_advisor": false,
+            "timestamp": "",
+            "invoke": {
+                "contract_id": contract.to_string(),
+                "function": "hello",
+                "source_account": account.to_string(),
+            }
+        }))
+        .unwrap();
+        let audited = Audited {
+            fingerprint: &[0xab; 32],
+            client: Some("ci"),
+            remote_addr: Some(IpAddr::from([127, 0, 0, 1])),
+            request: &request,
+        };
+        let answer = Answer::rejected(400, "missing contract");
+
+        let full = record(&audited, &answer, Redaction::None);
+        assert_eq!(full.request_hash, "ab".repeat(32));
+        assert_eq!(full.client.as_deref(), Some("ci"));
+        assert_eq!(full.function.as_deref(), Some("hello"));
+        assert_eq!(full.contract, Some(contract.to_string()));
+        assert_eq!(full.source_account, Some(account.to_string()));
+        assert!(full.envelope_xdr.is_some());
+
+        let summary = record(&audited, &answer, Redaction::Envelope);
+        assert_eq!(summary.source_account, full.source_account);
+        assert!(summary.envelope_xdr.is_none());
+        assert_eq!(summary.error.as_deref(), Some("missing contract"));
+
+        let redacted = record(&audited, &answer, Redaction::All);
+        assert!(redacted.source_account.is_none() && redacted.contract.is_none());
+        assert!(redacted.error.is_none());
+        assert_eq!(
+            serde_json::to_value(&redacted).unwrap()["outcome"],
+            "rejected"
+        );
+    }
+}
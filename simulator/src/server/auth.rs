@@ -0,0 +1,269 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! API key authentication for server mode.
+//!
+//! Keys are loaded from a JSON file passed with `--api-keys-file`:
+//!
+//! ```json
+//! [
+//!   { "name": "wallet-team", "key": "s3cr3t", "scopes": ["simulate"], "rate_limit": 5, "rate_limit_burst": 10 },
+//!   { "name": "ci", "key": "other", "scopes": ["simulate", "session"] }
+//! ]
+//! ```
+//!
+//! Clients present a key with either `X-API-Key: <key>` or
+//! `Authorization: Bearer <key>`. When no keys file is configured the server
+//! stays open, matching the behaviour of earlier releases.
+
+use super::limits::TokenBucket;
+use serde::Deserialize;
+use std::path::Path;
+use std::time::Duration;
+
+/// Header carrying the API key.
+pub const API_KEY_HEADER: &str = "X-API-Key";
+
+/// What a key is allowed to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Run one-shot simulations (`POST /simulate`).
+    Simulate,
+    /// Create and drive stateful simulation sessions.
+    Session,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiKeyConfig {
+    name: String,
+    key: String,
+    scopes: Vec<Scope>,
+    #[serde(default)]
+    rate_limit: Option<f64>,
+    #[serde(default)]
+    rate_limit_burst: Option<u32>,
+}
+
+/// A configured API key.
+#[derive(Debug)]
+pub struct ApiKey {
+    pub name: String,
+    key: String,
+    scopes: Vec<Scope>,
+    limiter: Option<TokenBucket>,
+}
+
+impl ApiKey {
+    fn from_config(config: ApiKeyConfig) -> Self {
+        let limiter = config.rate_limit.filter(|rate| *rate > 0.0).map(|rate| {
+            let burst = config
+                .rate_limit_burst
+                .unwrap_or_else(|| rate.ceil() as u32);
+            TokenBucket::new(rate, burst)
+        });
+        Self {
+            name: config.name,
+            key: config.key,
+            scopes: config.scopes,
+            limiter,
+        }
+    }
+
+    /// Returns true if the key grants `scope`.
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
+}
+
+/// Why a request was refused by [`ApiKeys::authorize`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthError {
+    /// No key was presented.
+    Missing,
+    /// The presented key is not configured.
+    Invalid,
+    /// The key does not grant the scope the route needs.
+    Forbidden { name: String, scope: Scope },
+    /// The key exhausted its own rate limit.
+    RateLimited { name: String, retry_after: Duration },
+}
+
+impl AuthError {
+    /// HTTP status code for this error.
+    pub fn status(&self) -> u16 {
+        match self {
+            AuthError::Missing | AuthError::Invalid => 401,
+            AuthError::Forbidden { .. } => 403,
+            AuthError::RateLimited { .. } => 429,
+        }
+    }
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::Missing => write!(f, "missing API key"),
+            AuthError::Invalid => write!(f, "invalid API key"),
+            AuthError::Forbidden { name, scope } => {
+                write!(f, "API key '{name}' is not allowed the {scope:?} scope")
+            }
+            AuthError::RateLimited { name, .. } => {
+                write!(f, "rate limit exceeded for API key '{name}'")
+            }
+        }
+    }
+}
+
+/// The set of keys accepted by the server.
+#[derive(Debug, Default)]
+pub struct ApiKeys {
+    keys: Vec<ApiKey>,
+}
+
+impl ApiKeys {
+    /// Parses a keys file.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let configs: Vec<ApiKeyConfig> =
+            serde_json::from_str(json).map_err(|e| format!("invalid API keys file: {e}"))?;
+        for (i, config) in configs.iter().enumerate() {
+            if config.key.is_empty() {
+                return Err(format!("API key '{}' has an empty key", config.name));
+            }
+            if configs[..i].iter().any(|other| other.key == config.key) {
+                return Err(format!("API key '{}' duplicates another key", config.name));
+            }
+        }
+        Ok(Self {
+            keys: configs.into_iter().map(ApiKey::from_config).collect(),
+        })
+    }
+
+    /// Loads a keys file from disk.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read API keys file {}: {e}", path.display()))?;
+        Self::from_json(&json)
+    }
+
+    /// Number of configured keys.
+    pub fn count(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Authenticates `presented` and checks it grants `scope`, taking one
+    /// token from the key's rate limit on success.
+    pub fn authorize(&self, presented: Option<&str>, scope: Scope) -> Result<&ApiKey, AuthError> {
+        let presented = presented.ok_or(AuthError::Missing)?;
+        let key = self
+            .keys
+            .iter()
+            .find(|k| constant_time_eq(k.key.as_bytes(), presented.as_bytes()))
+            .ok_or(AuthError::Invalid)?;
+
+        if !key.allows(scope) {
+            return Err(AuthError::Forbidden {
+                name: key.name.clone(),
+                scope,
+            });
+        }
+        if let Some(limiter) = &key.limiter {
+            limiter
+                .try_take()
+                .map_err(|retry_after| AuthError::RateLimited {
+                    name: key.name.clone(),
+                    retry_after,
+                })?;
+        }
+        Ok(key)
+    }
+}
+
+/// Extracts the API key from `X-API-Key` or an `Authorization: Bearer` header.
+pub fn presented_key(headers: &[tiny_http::Header]) -> Option<&str> {
+    let mut bearer = None;
+    for h in headers {
+        if h.field.equiv(API_KEY_HEADER) {
+            return Some(h.value.as_str().trim());
+        }
+        if h.field.equiv("Authorization") {
+            bearer = h.value.as_str().strip_prefix("Bearer ").map(str::trim);
+        }
+    }
+    bearer
+}
+
+/// Compares two byte strings without short-circuiting on the first mismatch.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEYS: &str = r#"[
+        { "name": "wallet", "key": "wallet-key", "scopes": ["simulate"] },
+        { "name": "ci", "key": "ci-key", "scopes": ["simulate", "session"], "rate_limit": 1, "rate_limit_burst": 1 }
+    ]"#;
+
+    #[test]
+    fn test_authorize_checks_key_and_scope() {
+        let keys = ApiKeys::from_json(KEYS).unwrap();
+        assert_eq!(keys.count(), 2);
+
+        assert_eq!(
+            keys.authorize(Some("wallet-key"), Scope::Simulate)
+                .unwrap()
+                .name,
+            "wallet"
+        );
+        assert_eq!(
+            keys.authorize(None, Scope::Simulate).unwrap_err(),
+            AuthError::Missing
+        );
+        assert_eq!(
+            keys.authorize(Some("nope"), Scope::Simulate).unwrap_err(),
+            AuthError::Invalid
+        );
+
+        let err = keys
+            .authorize(Some("wallet-key"), Scope::Session)
+            .unwrap_err();
+        assert_eq!(err.status(), 403);
+    }
+
+    #[test]
+    fn test_authorize_applies_per_key_rate_limit() {
+        let keys = ApiKeys::from_json(KEYS).unwrap();
+        assert!(keys.authorize(Some("ci-key"), Scope::Session).is_ok());
+        let err = keys.authorize(Some("ci-key"), Scope::Simulate).unwrap_err();
+        assert_eq!(err.status(), 429);
+        // Other keys are unaffected.
+        assert!(keys.authorize(Some("wallet-key"), Scope::Simulate).is_ok());
+    }
+
+    #[test]
+    fn test_from_json_rejects_duplicate_keys() {
+        let json = r#"[
+            { "name": "a", "key": "same", "scopes": ["simulate"] },
+            { "name": "b", "key": "same", "scopes": ["session"] }
+        ]"#;
+        assert!(ApiKeys::from_json(json).is_err());
+        assert!(ApiKeys::from_json(r#"[{ "name": "a", "key": "", "scopes": [] }]"#).is_err());
+    }
+
+    #[test]
+    fn test_presented_key_reads_both_headers() {
+        let api_key = tiny_http::Header::from_bytes("x-api-key", "k1").unwrap();
+        let bearer = tiny_http::Header::from_bytes("Authorization", "Bearer k2").unwrap();
+        let basic = tiny_http::Header::from_bytes("Authorization", "Basic abc").unwrap();
+
+        assert_eq!(presented_key(&[bearer.clone(), api_key]), Some("k1"));
+        assert_eq!(presented_key(&[bearer]), Some("k2"));
+        assert_eq!(presented_key(&[basic]), None);
+    }
+}
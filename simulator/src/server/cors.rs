@@ -0,0 +1,157 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! CORS support so browser-based clients (web IDEs, wallet frontends) can
+//! call the server directly.
+//!
+//! CORS is off unless at least one `--cors-origin` is given. `*` allows any
+//! origin; otherwise the request's `Origin` must match a configured origin
+//! exactly and is echoed back.
+
+use tiny_http::Header;
+
+/// Request headers always allowed in addition to the configured ones.
+const DEFAULT_ALLOWED_HEADERS: &[&str] = &["Content-Type", "Authorization", "X-API-Key"];
+
+/// Methods the server exposes.
+const ALLOWED_METHODS: &str = "GET, POST, OPTIONS";
+
+/// Response headers browsers may read (`Retry-After` on 429/503).
+const EXPOSED_HEADERS: &str = "Retry-After";
+
+/// CORS policy for the server.
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    origins: Vec<String>,
+    allowed_headers: String,
+    max_age_secs: u64,
+}
+
+impl CorsConfig {
+    /// Builds a policy, or `None` when no origins are configured.
+    pub fn new(origins: &[String], extra_headers: &[String], max_age_secs: u64) -> Option<Self> {
+        if origins.is_empty() {
+            return None;
+        }
+        let mut headers: Vec<&str> = DEFAULT_ALLOWED_HEADERS.to_vec();
+        for h in extra_headers {
+            if !headers
+                .iter()
+                .any(|existing| existing.eq_ignore_ascii_case(h))
+            {
+                headers.push(h);
+            }
+        }
+        Some(Self {
+            origins: origins
+                .iter()
+                .map(|o| o.trim_end_matches('/').to_string())
+                .collect(),
+            allowed_headers: headers.join(", "),
+            max_age_secs,
+        })
+    }
+
+    /// Returns the `Access-Control-Allow-Origin` value for `origin`, if allowed.
+    pub fn allow_origin<'a>(&self, origin: &'a str) -> Option<&'a str> {
+        if self.origins.iter().any(|o| o == "*") {
+            Some("*")
+        } else if self.origins.iter().any(|o| o == origin) {
+            Some(origin)
+        } else {
+            None
+        }
+    }
+
+    /// Headers added to every response for a request from `origin`.
+    pub fn response_headers(&self, origin: Option<&str>) -> Vec<Header> {
+        let Some(allowed) = origin.and_then(|o| self.allow_origin(o)) else {
+            return Vec::new();
+        };
+        let mut headers = vec![
+            ("Access-Control-Allow-Origin", allowed.to_string()),
+            ("Access-Control-Expose-Headers", EXPOSED_HEADERS.to_string()),
+        ];
+        if allowed != "*" {
+            headers.push(("Vary", "Origin".to_string()));
+        }
+        to_headers(headers)
+    }
+
+    /// Headers for a preflight (`OPTIONS`) response from `origin`.
+    pub fn preflight_headers(&self, origin: Option<&str>) -> Vec<Header> {
+        let mut headers = self.response_headers(origin);
+        if headers.is_empty() {
+            return headers;
+        }
+        headers.extend(to_headers(vec![
+            ("Access-Control-Allow-Methods", ALLOWED_METHODS.to_string()),
+            ("Access-Control-Allow-Headers", self.allowed_headers.clone()),
+            ("Access-Control-Max-Age", self.max_age_secs.to_string()),
+        ]));
+        headers
+    }
+}
+
+/// Returns the request's `Origin` header.
+pub fn request_origin(headers: &[Header]) -> Option<&str> {
+    headers
+        .iter()
+        .find(|h| h.field.equiv("Origin"))
+        .map(|h| h.value.as_str())
+}
+
+fn to_headers(pairs: Vec<(&str, String)>) -> Vec<Header> {
+    pairs
+        .into_iter()
+        .filter_map(|(name, value)| Header::from_bytes(name.as_bytes(), value.as_bytes()).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value<'a>(headers: &'a [Header], name: &'static str) -> Option<&'a str> {
+        headers
+            .iter()
+            .find(|h| h.field.equiv(name))
+            .map(|h| h.value.as_str())
+    }
+
+    #[test]
+    fn test_disabled_without_origins() {
+        assert!(CorsConfig::new(&[], &[], 600).is_none());
+    }
+
+    #[test]
+    fn test_echoes_allowed_origin_only() {
+        let cors = CorsConfig::new(&["https://ide.example.com/".to_string()], &[], 600).unwrap();
+
+        let headers = cors.response_headers(Some("https://ide.example.com"));
+        assert_eq!(
+            value(&headers, "Access-Control-Allow-Origin"),
+            Some("https://ide.example.com")
+        );
+        assert_eq!(value(&headers, "Vary"), Some("Origin"));
+
+        assert!(cors
+            .response_headers(Some("https://evil.example.com"))
+            .is_empty());
+        assert!(cors.response_headers(None).is_empty());
+    }
+
+    #[test]
+    fn test_wildcard_and_preflight_headers() {
+        let cors = CorsConfig::new(&["*".to_string()], &["X-Request-Id".to_string()], 60).unwrap();
+        let headers = cors.preflight_headers(Some("https://wallet.example.com"));
+
+        assert_eq!(value(&headers, "Access-Control-Allow-Origin"), Some("*"));
+        assert_eq!(value(&headers, "Vary"), None);
+        assert_eq!(value(&headers, "Access-Control-Max-Age"), Some("60"));
+        assert_eq!(
+            value(&headers, "Access-Control-Allow-Headers"),
+            Some("Content-Type, Authorization, X-API-Key, X-Request-Id")
+        );
+    }
+}
@@ -0,0 +1,298 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Idempotency keys for `POST /simulate`.
+//!
+//! Callers that retry around the HTTP API send an `Idempotency-Key` header
+//! (or an `idempotency_key` request field). The first request with a key
+//! claims it; once it completes with a `200`, duplicates within the window
+//! get the stored response instead of a new simulation:
+//! - a duplicate arriving while the first is still running is rejected with
+//!   409
+//! - a key reused with a different request body is rejected with 422
+//! - a request that did not complete with a `200` releases its key, so the
+//!   caller can retry with the same key
+//!
+//! Keys are scoped to the API key that presented them.
+
+use super::response_cache::CachedBody;
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Header carrying the idempotency key.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// Header set on responses replayed from a previous request.
+pub const REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
+/// Default number of remembered keys.
+pub const DEFAULT_IDEMPOTENCY_KEYS: usize = 10_000;
+
+/// Longest accepted key.
+const MAX_KEY_LEN: usize = 255;
+
+/// Why a request's idempotency key was not accepted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IdempotencyError {
+    /// The key is empty, too long or not printable ASCII.
+    InvalidKey,
+    /// The header and the request field carry different keys.
+    Conflicting,
+    /// A request with the same key is still being simulated.
+    InProgress,
+    /// The key was already used with a different request body.
+    Mismatch,
+}
+
+impl IdempotencyError {
+    /// HTTP status code for this error.
+    pub fn status(&self) -> u16 {
+        match self {
+            IdempotencyError::InvalidKey | IdempotencyError::Conflicting => 400,
+            IdempotencyError::InProgress => 409,
+            IdempotencyError::Mismatch => 422,
+        }
+    }
+}
+
+impl std::fmt::Display for IdempotencyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IdempotencyError::InvalidKey => write!(
+                f,
+                "idempotency key must be 1 to {MAX_KEY_LEN} printable ASCII characters"
+            ),
+            IdempotencyError::Conflicting => write!(
+                f,
+                "{IDEMPOTENCY_KEY_HEADER} header and idempotency_key field differ"
+            ),
+            IdempotencyError::InProgress => {
+                write!(
+                    f,
+                    "a request with this idempotency key is still in progress"
+                )
+            }
+            IdempotencyError::Mismatch => {
+                write!(
+                    f,
+                    "idempotency key was already used with a different request"
+                )
+            }
+        }
+    }
+}
+
+/// Picks the request's idempotency key from the header or the request field.
+pub fn request_key<'a>(
+    header: Option<&'a str>,
+    field: Option<&'a str>,
+) -> Result<Option<&'a str>, IdempotencyError> {
+    let key = match (header, field) {
+        (Some(header), Some(field)) if header != field => {
+            return Err(IdempotencyError::Conflicting)
+        }
+        (header, field) => header.or(field),
+    };
+    match key {
+        Some(key)
+            if key.is_empty()
+                || key.len() > MAX_KEY_LEN
+                || !key.bytes().all(|b| b.is_ascii_graphic() || b == b' ') =>
+        {
+            Err(IdempotencyError::InvalidKey)
+        }
+        key => Ok(key),
+    }
+}
+
+/// Extracts the `Idempotency-Key` header.
+pub fn presented_key(headers: &[tiny_http::Header]) -> Option<&str> {
+    headers
+        .iter()
+        .find(|h| h.field.equiv(IDEMPOTENCY_KEY_HEADER))
+        .map(|h| h.value.as_str().trim())
+}
+
+#[derive(Debug)]
+struct Entry {
+    claim_id: u64,
+    created: Instant,
+    fingerprint: [u8; 32],
+    /// Body of the completed `200` response.
+    response: Option<CachedBody>,
+}
+
+/// Remembers idempotency keys and their responses for a window.
+#[derive(Debug)]
+pub struct IdempotencyStore {
+    window: Duration,
+    entries: Mutex<LruCache<String, Entry>>,
+    next_claim: AtomicU64,
+}
+
+/// The outcome of presenting a key.
+#[derive(Debug)]
+pub enum Claim<'a> {
+    /// The key is new; the request should run and complete the claim.
+    New(PendingKey<'a>),
+    /// The key was already used for this request; send the stored response.
+    Replay(CachedBody),
+}
+
+impl IdempotencyStore {
+    /// Remembers up to `capacity` keys for `window`. Returns `None` when
+    /// either is zero.
+    pub fn new(window: Duration, capacity: usize) -> Option<Self> {
+        let capacity = NonZeroUsize::new(capacity)?;
+        (!window.is_zero()).then(|| Self {
+            window,
+            entries: Mutex::new(LruCache::new(capacity)),
+            next_claim: AtomicU64::new(0),
+        })
+    }
+
+    /// Claims `key` for a request whose body hashes to `fingerprint`.
+    pub fn claim(&self, key: String, fingerprint: [u8; 32]) -> Result<Claim<'_>, IdempotencyError> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(entry) = entries.get(&key) {
+            if entry.created.elapsed() < self.window {
+                if entry.fingerprint != fingerprint {
+                    return Err(IdempotencyError::Mismatch);
+                }
+                return match &entry.response {
+                    Some(response) => Ok(Claim::Replay(response.clone())),
+                    None => Err(IdempotencyError::InProgress),
+                };
+            }
+        }
+        let claim_id = self.next_claim.fetch_add(1, Ordering::Relaxed);
+        entries.put(
+            key.clone(),
+            Entry {
+                claim_id,
+                created: Instant::now(),
+                fingerprint,
+                response: None,
+            },
+        );
+        Ok(Claim::New(PendingKey {
+            store: self,
+            key,
+            claim_id,
+            completed: false,
+        }))
+    }
+
+    /// Number of remembered keys, including expired ones not yet evicted.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+}
+
+/// A claimed key. Dropping it without [`PendingKey::complete`] releases
+/// the key.
+#[derive(Debug)]
+pub struct PendingKey<'a> {
+    store: &'a IdempotencyStore,
+    key: String,
+    claim_id: u64,
+    completed: bool,
+}
+
+impl PendingKey<'_> {
+    /// Stores `body` as the response to replay for this key.
+    pub fn complete(mut self, body: CachedBody) {
+        let mut entries = self.store.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(entry) = entries.get_mut(&self.key) {
+            if entry.claim_id == self.claim_id {
+                entry.response = Some(body);
+                self.completed = true;
+            }
+        }
+    }
+}
+
+impl Drop for PendingKey<'_> {
+    fn drop(&mut self) {
+        if self.completed {
+            return;
+        }
+        let mut entries = self.store.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries
+            .peek(&self.key)
+            .is_some_and(|entry| entry.claim_id == self.claim_id)
+        {
+            entries.pop(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn store() -> IdempotencyStore {
+        IdempotencyStore::new(Duration::from_secs(60), 10).unwrap()
+    }
+
+    #[test]
+    fn test_completed_key_replays_response() {
+        let store = store();
+        let Ok(Claim::New(pending)) = store.claim("k".into(), [1; 32]) else {
+            panic!("expected a new claim");
+        };
+        assert_eq!(
+            store.claim("k".into(), [1; 32]).unwrap_err(),
+            IdempotencyError::InProgress
+        );
+        let body = CachedBody {
+            content_type: "application/json",
+            bytes: Arc::from(&b"{}"[..]),
+        };
+        pending.complete(body.clone());
+
+        match store.claim("k".into(), [1; 32]) {
+            Ok(Claim::Replay(replayed)) => assert_eq!(replayed, body),
+            other => panic!("expected a replay, got {other:?}"),
+        }
+        assert_eq!(
+            store.claim("k".into(), [2; 32]).unwrap_err(),
+            IdempotencyError::Mismatch
+        );
+    }
+
+    #[test]
+    fn test_dropped_claim_releases_key() {
+        let store = store();
+        drop(store.claim("k".into(), [1; 32]).unwrap());
+        assert_eq!(store.len(), 0);
+        assert!(matches!(
+            store.claim("k".into(), [2; 32]),
+            Ok(Claim::New(_))
+        ));
+    }
+
+    #[test]
+    fn test_request_key_validation() {
+        assert_eq!(request_key(None, None), Ok(None));
+        assert_eq!(request_key(Some("a"), Some("a")), Ok(Some("a")));
+        assert_eq!(request_key(None, Some("b")), Ok(Some("b")));
+        assert_eq!(
+            request_key(Some("a"), Some("b")),
+            Err(IdempotencyError::Conflicting)
+        );
+        assert_eq!(
+            request_key(Some(""), None),
+            Err(IdempotencyError::InvalidKey)
+        );
+        let long = "x".repeat(MAX_KEY_LEN + 1);
+        assert_eq!(
+            request_key(Some(&long), None),
+            Err(IdempotencyError::InvalidKey)
+        );
+    }
+}
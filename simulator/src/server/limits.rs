@@ -0,0 +1,360 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Admission control for server mode.
+//!
+//! Two independent guards protect a publicly exposed simulator:
+//! - [`RateLimiter`]: a token bucket per client IP, rejecting with 429
+//!   ([`TokenBucket`] applies the same policy to a single caller)
+//! - [`ConcurrencyLimiter`]: a cap on simultaneous simulations with a bounded
+//!   wait queue, rejecting with 503 once the queue is full or a queued request
+//!   waits too long
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Buckets idle long enough to have refilled are pruned once the table grows
+/// past this many clients.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Per-client token bucket rate limiter.
+#[derive(Debug)]
+pub struct RateLimiter {
+    rate_per_sec: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl Bucket {
+    fn full(burst: f64, now: Instant) -> Self {
+        Self {
+            tokens: burst,
+            last_refill: now,
+        }
+    }
+
+    /// Refills the bucket for the time elapsed since the last refill and
+    /// takes one token, or returns how long until a token is available.
+    fn take(&mut self, rate_per_sec: f64, burst: f64, now: Instant) -> Result<(), Duration> {
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate_per_sec).min(burst);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            let missing = 1.0 - self.tokens;
+            Err(Duration::from_secs_f64(missing / rate_per_sec))
+        }
+    }
+}
+
+/// A single token bucket, used where one limit applies to one caller
+/// (for example an API key).
+#[derive(Debug)]
+pub struct TokenBucket {
+    rate_per_sec: f64,
+    burst: f64,
+    bucket: Mutex<Bucket>,
+}
+
+impl TokenBucket {
+    /// Creates a bucket refilling at `rate_per_sec` and holding up to `burst` tokens.
+    pub fn new(rate_per_sec: f64, burst: u32) -> Self {
+        let burst = f64::from(burst.max(1));
+        Self {
+            rate_per_sec,
+            burst,
+            bucket: Mutex::new(Bucket::full(burst, Instant::now())),
+        }
+    }
+
+    /// Takes one token, or returns `Err(retry_after)` if the bucket is empty.
+    pub fn try_take(&self) -> Result<(), Duration> {
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        bucket.take(self.rate_per_sec, self.burst, Instant::now())
+    }
+}
+
+impl RateLimiter {
+    /// Creates a limiter allowing `rate_per_sec` sustained requests per client
+    /// with bursts of up to `burst` requests.
+    pub fn new(rate_per_sec: f64, burst: u32) -> Self {
+        Self {
+            rate_per_sec,
+            burst: f64::from(burst.max(1)),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes one token for `client`.
+    ///
+    /// Returns `Err(retry_after)` when the client has exhausted its bucket.
+    pub fn check(&self, client: IpAddr) -> Result<(), Duration> {
+        self.check_at(client, Instant::now())
+    }
+
+    fn check_at(&self, client: IpAddr, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() > PRUNE_THRESHOLD {
+            let full_after = Duration::from_secs_f64(self.burst / self.rate_per_sec);
+            buckets.retain(|_, b| now.duration_since(b.last_refill) < full_after);
+        }
+
+        buckets
+            .entry(client)
+            .or_insert_with(|| Bucket::full(self.burst, now))
+            .take(self.rate_per_sec, self.burst, now)
+    }
+}
+
+/// Why a request was not admitted by the [`ConcurrencyLimiter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// All simulation slots are busy and the wait queue is full.
+    QueueFull,
+    /// The request waited in the queue longer than the queue timeout.
+    QueueTimeout,
+}
+
+#[derive(Debug, Default)]
+struct SlotState {
+    running: usize,
+    waiting: usize,
+}
+
+/// Caps the number of simultaneous simulations.
+#[derive(Debug)]
+pub struct ConcurrencyLimiter {
+    max_running: usize,
+    max_waiting: usize,
+    queue_timeout: Duration,
+    state: Mutex<SlotState>,
+    freed: Condvar,
+}
+
+impl ConcurrencyLimiter {
+    /// Creates a limiter running at most `max_running` simulations, with up to
+    /// `max_waiting` more queued for at most `queue_timeout`.
+    pub fn new(max_running: usize, max_waiting: usize, queue_timeout: Duration) -> Self {
+        Self {
+            max_running: max_running.max(1),
+            max_waiting,
+            queue_timeout,
+            state: Mutex::new(SlotState::default()),
+            freed: Condvar::new(),
+        }
+    }
+
+    /// Takes a free simulation slot, or a place in the queue for one, without
+    /// waiting.
+    ///
+    /// A queued permit must [`SlotPermit::wait`] for its slot. Either is given
+    /// back when the permit is dropped.
+    pub fn admit(self: &Arc<Self>) -> Result<SlotPermit, Rejection> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let queued = state.running >= self.max_running;
+        if !queued {
+            state.running += 1;
+        } else if state.waiting < self.max_waiting {
+            state.waiting += 1;
+        } else {
+            return Err(Rejection::QueueFull);
+        }
+        Ok(SlotPermit {
+            limiter: Arc::clone(self),
+            queued,
+        })
+    }
+
+    /// Waits for a free simulation slot.
+    ///
+    /// The slot is released when the returned permit is dropped.
+    #[cfg(test)]
+    pub fn acquire(self: &Arc<Self>) -> Result<SlotPermit, Rejection> {
+        let mut permit = self.admit()?;
+        permit.wait()?;
+        Ok(permit)
+    }
+
+    /// Returns the number of simulations currently running.
+    pub fn running(&self) -> usize {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).running
+    }
+
+    /// Returns the number of requests waiting for a slot.
+    pub fn waiting(&self) -> usize {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).waiting
+    }
+
+    /// A conservative hint for how long a rejected client should back off.
+    pub fn retry_after(&self) -> Duration {
+        self.queue_timeout.max(Duration::from_secs(1))
+    }
+
+    fn release(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.running -= 1;
+        drop(state);
+        self.freed.notify_one();
+    }
+}
+
+/// RAII permit for one simulation slot, or for a place in the queue for one.
+#[derive(Debug)]
+pub struct SlotPermit {
+    limiter: Arc<ConcurrencyLimiter>,
+    queued: bool,
+}
+
+impl SlotPermit {
+    /// Waits, if the permit holds a place in the queue, until a slot is free
+    /// and takes it.
+    pub fn wait(&mut self) -> Result<(), Rejection> {
+        if !self.queued {
+            return Ok(());
+        }
+        let limiter = &*self.limiter;
+        let deadline = Instant::now() + limiter.queue_timeout;
+        let mut state = limiter.state.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            if state.running < limiter.max_running {
+                state.waiting -= 1;
+                state.running += 1;
+                self.queued = false;
+                return Ok(());
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Rejection::QueueTimeout);
+            }
+            state = limiter
+                .freed
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+}
+
+impl Drop for SlotPermit {
+    fn drop(&mut self) {
+        if self.queued {
+            let mut state = self.limiter.state.lock().unwrap_or_else(|e| e.into_inner());
+            state.waiting -= 1;
+        } else {
+            self.limiter.release();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+    #[test]
+    fn test_rate_limiter_allows_burst_then_rejects() {
+        let limiter = RateLimiter::new(1.0, 3);
+        let now = Instant::now();
+        for _ in 0..3 {
+            assert!(limiter.check_at(CLIENT, now).is_ok());
+        }
+        let retry_after = limiter.check_at(CLIENT, now).unwrap_err();
+        assert!(retry_after <= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_rate_limiter_refills_over_time() {
+        let limiter = RateLimiter::new(2.0, 1);
+        let now = Instant::now();
+        assert!(limiter.check_at(CLIENT, now).is_ok());
+        assert!(limiter.check_at(CLIENT, now).is_err());
+        assert!(limiter
+            .check_at(CLIENT, now + Duration::from_millis(500))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_rate_limiter_is_per_client() {
+        let limiter = RateLimiter::new(1.0, 1);
+        let other = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let now = Instant::now();
+        assert!(limiter.check_at(CLIENT, now).is_ok());
+        assert!(limiter.check_at(other, now).is_ok());
+        assert!(limiter.check_at(CLIENT, now).is_err());
+    }
+
+    #[test]
+    fn test_token_bucket_rejects_when_empty() {
+        let bucket = TokenBucket::new(0.5, 2);
+        assert!(bucket.try_take().is_ok());
+        assert!(bucket.try_take().is_ok());
+        let retry_after = bucket.try_take().unwrap_err();
+        assert!(retry_after > Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_concurrency_limiter_rejects_when_queue_full() {
+        let limiter = Arc::new(ConcurrencyLimiter::new(1, 0, Duration::from_millis(10)));
+        let permit = limiter.acquire().expect("first slot");
+        assert_eq!(limiter.running(), 1);
+        assert_eq!(limiter.acquire().unwrap_err(), Rejection::QueueFull);
+        drop(permit);
+        assert_eq!(limiter.running(), 0);
+        assert!(limiter.acquire().is_ok());
+    }
+
+    #[test]
+    fn test_concurrency_limiter_queue_timeout() {
+        let limiter = Arc::new(ConcurrencyLimiter::new(1, 1, Duration::from_millis(10)));
+        let _permit = limiter.acquire().expect("first slot");
+        assert_eq!(limiter.acquire().unwrap_err(), Rejection::QueueTimeout);
+        assert_eq!(limiter.waiting(), 0);
+    }
+
+    #[test]
+    fn test_concurrency_limiter_hands_slot_to_waiter() {
+        let limiter = Arc::new(ConcurrencyLimiter::new(1, 1, Duration::from_secs(5)));
+        std::thread::scope(|s| {
+            let permit = limiter.acquire().expect("first slot");
+            let waiter = s.spawn(|| limiter.acquire().map(|_| ()));
+            while limiter.waiting() == 0 {
+                std::thread::yield_now();
+            }
+            drop(permit);
+            assert!(waiter.join().expect("waiter thread").is_ok());
+        });
+    }
+
+    #[test]
+    fn test_concurrency_limiter_admits_without_waiting() {
+        let limiter = Arc::new(ConcurrencyLimiter::new(1, 1, Duration::from_millis(10)));
+        let running = limiter.admit().expect("first slot");
+        let mut queued = limiter.admit().expect("queue place");
+        assert_eq!((limiter.running(), limiter.waiting()), (1, 1));
+        assert_eq!(limiter.admit().unwrap_err(), Rejection::QueueFull);
+
+        drop(running);
+        assert!(queued.wait().is_ok());
+        assert_eq!((limiter.running(), limiter.waiting()), (1, 0));
+        drop(queued);
+        assert_eq!(limiter.running(), 0);
+
+        // A queued permit dropped without waiting gives its place back.
+        let _running = limiter.admit().expect("first slot");
+        drop(limiter.admit().expect("queue place"));
+        assert_eq!(limiter.waiting(), 0);
+    }
+}
@@ -0,0 +1,1102 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! HTTP server mode.
+//!
+//! `erst-sim serve` exposes the same simulation pipeline as the stdin
+//! protocol over HTTP:
+//! - `POST /simulate` accepts a `SimulationRequest` and returns a `SimulationResponse`,
+//!   as JSON or protobuf (see [`proto`])
+//! - `GET /health` reports liveness and current load
+//! - `GET /schema/request` and `GET /schema/response` return the JSON Schemas
+//! - `GET /openapi.json` returns the OpenAPI document and `GET /docs` a
+//!   Swagger UI for it (see [`openapi`])
+//!
+//! With `--tls-cert` and `--tls-key` the server terminates TLS itself.
+//! SIGINT and SIGTERM trigger a graceful shutdown (see [`shutdown`]). The
+//! snapshot file and API keys file are reloaded when they change (see
+//! [`reload`]). With `--rpc-url`, entries missing from a request are fetched
+//! from soroban-rpc through an LRU cache shared by all requests; with
+//! `--core-data-dir` or `--archive-url`, they are read from stellar-core's
+//! bucket files or a history archive checkpoint instead. With
+//! `--response-cache-ttl-secs`, identical requests against the same ledger
+//! state are answered from a response cache (see [`response_cache`]).
+//! Retried submissions carrying an `Idempotency-Key` replay the first
+//! response (see [`idempotency`]). Contract modules compiled for one
+//! simulation are kept in a pool and reused by later ones (see
+//! [`crate::warm`]). Request bodies may be gzip or zstd
+//! compressed (`Content-Encoding`); with `--compress-min-bytes`, large
+//! responses are compressed for clients that send `Accept-Encoding`.
+//!
+//! Every simulation request passes admission control (see [`limits`]) and,
+//! when API keys are configured, authentication (see [`auth`]) before it is
+//! executed on its own worker thread. With `--audit-log`, each decoded
+//! simulation request and its outcome is appended to an audit log (see
+//! [`audit`]).
+
+pub mod audit;
+pub mod auth;
+pub mod cors;
+pub mod idempotency;
+pub mod limits;
+pub mod openapi;
+pub mod proto;
+pub mod reload;
+pub mod response_cache;
+pub mod shutdown;
+
+use crate::compression::{self, Encoding};
+use crate::memory::{self, MemoryError, MemoryLimits, MemoryReservation};
+use crate::network::{self, NetworkPreset};
+use crate::schema;
+use crate::simulation::{self, SimulationError};
+use crate::snapshot::file::SnapshotFile;
+use crate::snapshot::indexed::{self, IndexedSnapshot};
+use crate::snapshot::SnapshotError;
+use crate::source;
+use crate::source::cache::{CachedSource, DEFAULT_CACHE_ENTRIES};
+use crate::source::retry::{self, RetryPolicy};
+use crate::streaming::{self, ParseError};
+use crate::types::{SimulationRequest, SimulationResponse, Timings};
+use crate::warm::{self, HostPool};
+use audit::{Answer, AuditLog, Audited, Outcome};
+use auth::{ApiKeys, AuthError, Scope};
+use cors::CorsConfig;
+use idempotency::{Claim, IdempotencyStore, DEFAULT_IDEMPOTENCY_KEYS};
+use limits::{ConcurrencyLimiter, RateLimiter, Rejection, SlotPermit};
+use reload::{Reloadable, WatchedFile};
+use response_cache::{CachedBody, ResponseCache, DEFAULT_RESPONSE_CACHE_ENTRIES};
+use shutdown::{InFlight, ServerStats};
+use std::io::{Cursor, Read};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tiny_http::{Header, Method, Request, Response, Server, SslConfig};
+
+/// Command-line options for `serve`.
+#[derive(Debug, Clone, clap::Args)]
+pub struct ServeArgs {
+    /// Address to listen on.
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    pub listen: String,
+
+    /// Maximum number of simulations executing at the same time.
+    #[arg(long, default_value_t = 4)]
+    pub max_concurrency: usize,
+
+    /// Maximum number of requests waiting for a free simulation slot.
+    #[arg(long, default_value_t = 32)]
+    pub max_queue: usize,
+
+    /// Seconds a queued request may wait for a slot before being rejected.
+    #[arg(long, default_value_t = 30)]
+    pub queue_timeout_secs: u64,
+
+    /// Sustained simulation requests per second allowed per client IP
+    /// (0 disables rate limiting).
+    #[arg(long, default_value_t = 10.0)]
+    pub rate_limit: f64,
+
+    /// Burst of requests a client IP may send before being rate limited.
+    #[arg(long, default_value_t = 20)]
+    pub rate_limit_burst: u32,
+
+    /// JSON file of API keys; when set, every simulation request must
+    /// present a key with the required scope.
+    #[arg(long)]
+    pub api_keys_file: Option<String>,
+
+    /// PEM certificate chain; serves HTTPS when given with `--tls-key`.
+    #[arg(long, requires = "tls_key")]
+    pub tls_cert: Option<String>,
+
+    /// PEM private key for `--tls-cert`.
+    #[arg(long, requires = "tls_cert")]
+    pub tls_key: Option<String>,
+
+    /// Origin allowed to call the server from a browser (repeatable; `*`
+    /// allows any origin). CORS is disabled when none is given.
+    #[arg(long = "cors-origin")]
+    pub cors_origins: Vec<String>,
+
+    /// Extra request header browsers may send (repeatable).
+    #[arg(long = "cors-allow-header")]
+    pub cors_allow_headers: Vec<String>,
+
+    /// Seconds browsers may cache a preflight response.
+    #[arg(long, default_value_t = 600)]
+    pub cors_max_age_secs: u64,
+
+    /// Seconds to wait for in-flight simulations after SIGINT/SIGTERM.
+    #[arg(long, default_value_t = 30)]
+    pub drain_timeout_secs: u64,
+
+    /// Snapshot file providing base ledger state for every request; entries
+    /// in a request override it. Either `ledgerEntries` JSON, or an indexed
+    /// snapshot from `erst-sim snapshot build`, which is searched in place
+    /// instead of being loaded into memory.
+    #[arg(long)]
+    pub snapshot_file: Option<String>,
+
+    /// Seconds between checks of the snapshot and API keys files for changes
+    /// (0 disables hot reload).
+    #[arg(long, default_value_t = 5)]
+    pub reload_interval_secs: u64,
+
+    /// Network preset (`pubnet`, `testnet`, `futurenet`) for every request.
+    /// Requests naming a different network are rejected.
+    #[arg(long)]
+    pub network: Option<String>,
+
+    /// soroban-rpc endpoint to fetch entries missing from a request
+    /// (defaults to the `--network` preset's endpoint). The `rpc_url` field
+    /// of requests is ignored in server mode.
+    #[arg(long)]
+    pub rpc_url: Option<String>,
+
+    /// Horizon endpoint to fill in accounts and trustlines the RPC endpoint
+    /// does not serve.
+    #[arg(long, conflicts_with_all = ["core_data_dir", "archive_url"])]
+    pub horizon_url: Option<String>,
+
+    /// Maximum number of fetched entries kept in the shared cache
+    /// (0 disables caching).
+    #[arg(long, default_value_t = DEFAULT_CACHE_ENTRIES)]
+    pub entry_cache_size: usize,
+
+    /// Directory for a persistent entry cache that survives restarts.
+    #[arg(long)]
+    pub entry_cache_dir: Option<String>,
+
+    /// stellar-core data directory whose bucket files serve entries missing
+    /// from a request, instead of soroban-rpc.
+    #[arg(long, requires = "core_state", conflicts_with = "rpc_url")]
+    pub core_data_dir: Option<String>,
+
+    /// History archive state JSON naming the current bucket list in
+    /// `--core-data-dir`.
+    #[arg(long, requires = "core_data_dir")]
+    pub core_state: Option<String>,
+
+    /// History archive to read entries missing from a request from, as of
+    /// `--archive-checkpoint`, instead of soroban-rpc.
+    #[arg(
+        long,
+        requires = "archive_checkpoint",
+        conflicts_with_all = ["rpc_url", "core_data_dir"]
+    )]
+    pub archive_url: Option<String>,
+
+    /// Checkpoint ledger of `--archive-url` to simulate against.
+    #[arg(long, requires = "archive_url")]
+    pub archive_checkpoint: Option<u32>,
+
+    /// Directory downloaded archive buckets are kept in (defaults to
+    /// `erst-archive-buckets` in the system temporary directory).
+    #[arg(long, requires = "archive_url")]
+    pub archive_bucket_dir: Option<String>,
+
+    /// Retries after a failed remote state lookup (0 disables retrying).
+    #[arg(long, default_value_t = retry::DEFAULT_MAX_RETRIES)]
+    pub source_max_retries: u32,
+
+    /// Milliseconds before the first retry; doubled for each further retry.
+    #[arg(long, default_value_t = retry::DEFAULT_BACKOFF_MS)]
+    pub source_backoff_ms: u64,
+
+    /// Ceiling on the delay between retries, in milliseconds.
+    #[arg(long, default_value_t = retry::DEFAULT_MAX_BACKOFF_MS)]
+    pub source_max_backoff_ms: u64,
+
+    /// Milliseconds after which a remote lookup stops retrying
+    /// (0 for no limit).
+    #[arg(long, default_value_t = retry::DEFAULT_DEADLINE_MS)]
+    pub source_deadline_ms: u64,
+
+    /// Seconds a simulation response is reused for identical requests
+    /// against the same ledger state (0 disables the response cache).
+    #[arg(long, default_value_t = 0)]
+    pub response_cache_ttl_secs: u64,
+
+    /// Maximum number of cached simulation responses.
+    #[arg(long, default_value_t = DEFAULT_RESPONSE_CACHE_ENTRIES)]
+    pub response_cache_size: usize,
+
+    /// Seconds an idempotency key and its response are remembered
+    /// (0 disables idempotency keys).
+    #[arg(long, default_value_t = 300)]
+    pub idempotency_window_secs: u64,
+
+    /// Maximum number of remembered idempotency keys.
+    #[arg(long, default_value_t = DEFAULT_IDEMPOTENCY_KEYS)]
+    pub idempotency_max_keys: usize,
+
+    /// Compress simulation responses of at least this many bytes for
+    /// clients that accept gzip or zstd (unset disables compression).
+    #[arg(long)]
+    pub compress_min_bytes: Option<usize>,
+
+    /// Idle module caches kept for reuse by later simulations, so contracts
+    /// are compiled once rather than on every request (0 disables reuse).
+    #[arg(long, default_value_t = warm::DEFAULT_POOL_SIZE)]
+    pub host_pool_size: usize,
+
+    /// Share of each network limit, in percent, from which responses warn,
+    /// for requests without `soft_limit_percent` (defaults to 90).
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..=100))]
+    pub soft_limit_percent: Option<u64>,
+
+    /// File to append a JSON line to for each simulation request answered:
+    /// request hash, caller, outcome, transaction hash and fee.
+    #[arg(long)]
+    pub audit_log: Option<String>,
+
+    /// How much of the transaction audit log lines record.
+    #[arg(long, value_enum, default_value_t, requires = "audit_log")]
+    pub audit_redact: audit::Redaction,
+}
+
+impl ServeArgs {
+    /// Reads the TLS certificate and key, if configured.
+    fn tls_config(&self) -> Result<Option<SslConfig>, String> {
+        let (Some(cert), Some(key)) = (&self.tls_cert, &self.tls_key) else {
+            return Ok(None);
+        };
+        let read = |path: &str, what: &str| {
+            std::fs::read(path).map_err(|e| format!("failed to read TLS {what} {path}: {e}"))
+        };
+        Ok(Some(SslConfig {
+            certificate: read(cert, "certificate")?,
+            private_key: read(key, "private key")?,
+        }))
+    }
+}
+
+/// The `--snapshot-file` contents.
+enum BaseSnapshot {
+    /// Decoded JSON, merged into each request's `ledger_entries`.
+    Json(SnapshotFile),
+    /// An indexed file, searched in place for entries a request lacks.
+    Indexed(Arc<IndexedSnapshot>),
+}
+
+impl BaseSnapshot {
+    /// Loads `path`, telling the formats apart by the indexed file's magic.
+    fn load(path: &Path) -> Result<Self, SnapshotError> {
+        if indexed::is_indexed(path)? {
+            IndexedSnapshot::open(path).map(|snapshot| Self::Indexed(Arc::new(snapshot)))
+        } else {
+            SnapshotFile::load(path).map(Self::Json)
+        }
+    }
+
+    fn format(&self) -> &'static str {
+        match self {
+            BaseSnapshot::Json(_) => "json",
+            BaseSnapshot::Indexed(_) => "indexed",
+        }
+    }
+
+    fn ledger_sequence(&self) -> Option<u32> {
+        match self {
+            BaseSnapshot::Json(file) => file.ledger_sequence,
+            BaseSnapshot::Indexed(snapshot) => snapshot.ledger_sequence(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            BaseSnapshot::Json(file) => file.entries.len(),
+            BaseSnapshot::Indexed(snapshot) => snapshot.len(),
+        }
+    }
+}
+
+struct ServerState {
+    memory_limits: MemoryLimits,
+    rate_limiter: Option<RateLimiter>,
+    concurrency: Arc<ConcurrencyLimiter>,
+    api_keys: Option<Reloadable<ApiKeys>>,
+    snapshot: Option<Reloadable<BaseSnapshot>>,
+    network: Option<&'static NetworkPreset>,
+    remote: Option<Arc<CachedSource>>,
+    response_cache: Option<ResponseCache>,
+    idempotency: Option<IdempotencyStore>,
+    compress_min_bytes: Option<usize>,
+    cors: Option<CorsConfig>,
+    in_flight: Arc<InFlight>,
+    stats: ServerStats,
+    host_pool: Option<HostPool>,
+    soft_limit_percent: Option<u64>,
+    audit_log: Option<AuditLog>,
+}
+
+/// Runs the HTTP server until it receives SIGINT or SIGTERM, then drains
+/// in-flight simulations before returning.
+pub fn serve(args: &ServeArgs, memory_limits: MemoryLimits) -> Result<(), String> {
+    let tls = args.tls_config()?;
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    let server = match tls {
+        Some(config) => Server::https(&args.listen, config),
+        None => Server::http(&args.listen),
+    }
+    .map_err(|e| format!("failed to listen on {}: {e}", args.listen))?;
+    let server = Arc::new(server);
+    shutdown::install_handler(Arc::clone(&server))?;
+    let api_keys = args
+        .api_keys_file
+        .as_deref()
+        .map(|path| ApiKeys::load(Path::new(path)))
+        .transpose()?
+        .map(Reloadable::new);
+    let network = args
+        .network
+        .as_deref()
+        .map(|name| network::resolve(Some(name)))
+        .transpose()?;
+    // Bucket files and archives take the place of the network's RPC endpoint.
+    let rpc_url = args
+        .rpc_url
+        .as_deref()
+        .or_else(|| network.map(|preset| preset.rpc_url))
+        .filter(|_| args.core_data_dir.is_none() && args.archive_url.is_none());
+    let retry = RetryPolicy::new(
+        args.source_max_retries,
+        args.source_backoff_ms,
+        args.source_max_backoff_ms,
+        args.source_deadline_ms,
+    );
+    let remote = match (&args.core_data_dir, &args.core_state, &args.archive_url) {
+        (Some(dir), Some(state), _) => Some(
+            source::bucket_cache(Path::new(dir), Path::new(state), args.entry_cache_size)
+                .map_err(|e| e.to_string())?,
+        ),
+        (_, _, Some(url)) => {
+            let bucket_dir = args.archive_bucket_dir.as_ref().map_or_else(
+                || std::env::temp_dir().join("erst-archive-buckets"),
+                std::path::PathBuf::from,
+            );
+            Some(
+                source::archive_cache(
+                    url,
+                    args.archive_checkpoint.unwrap_or_default(),
+                    &bucket_dir,
+                    args.entry_cache_size,
+                    retry,
+                )
+                .map_err(|e| e.to_string())?,
+            )
+        }
+        _ => rpc_url.map(|url| {
+            source::rpc_cache(
+                url,
+                args.horizon_url.as_deref(),
+                args.entry_cache_size,
+                args.entry_cache_dir.as_deref().map(Path::new),
+                retry,
+            )
+        }),
+    };
+    let audit_log = args
+        .audit_log
+        .as_deref()
+        .map(|path| AuditLog::open(Path::new(path), args.audit_redact))
+        .transpose()?;
+    let snapshot = match &args.snapshot_file {
+        Some(path) => {
+            let snapshot = BaseSnapshot::load(Path::new(path)).map_err(|e| e.to_string())?;
+            log_snapshot_loaded("snapshot_loaded", &snapshot);
+            Some(Reloadable::new(snapshot))
+        }
+        None => None,
+    };
+
+    let state = Arc::new(ServerState {
+        memory_limits,
+        rate_limiter: (args.rate_limit > 0.0)
+            .then(|| RateLimiter::new(args.rate_limit, args.rate_limit_burst)),
+        concurrency: Arc::new(ConcurrencyLimiter::new(
+            args.max_concurrency,
+            args.max_queue,
+            Duration::from_secs(args.queue_timeout_secs),
+        )),
+        api_keys,
+        snapshot,
+        network,
+        remote,
+        response_cache: ResponseCache::new(
+            Duration::from_secs(args.response_cache_ttl_secs),
+            args.response_cache_size,
+        ),
+        idempotency: IdempotencyStore::new(
+            Duration::from_secs(args.idempotency_window_secs),
+            args.idempotency_max_keys,
+        ),
+        compress_min_bytes: args.compress_min_bytes,
+        cors: CorsConfig::new(
+            &args.cors_origins,
+            &args.cors_allow_headers,
+            args.cors_max_age_secs,
+        ),
+        in_flight: Arc::new(InFlight::default()),
+        stats: ServerStats::default(),
+        host_pool: HostPool::new(args.host_pool_size),
+        soft_limit_percent: args.soft_limit_percent,
+        audit_log,
+    });
+
+    tracing::info!(
+        event = "server_started",
+        listen = %args.listen,
+        scheme,
+        max_concurrency = args.max_concurrency,
+        max_queue = args.max_queue,
+        api_keys = state.api_keys.as_ref().map_or(0, |keys| keys.get().count()),
+        network = network.map_or("any", |preset| preset.name),
+        rpc_url = rpc_url.unwrap_or("none"),
+        horizon_url = args.horizon_url.as_deref().unwrap_or("none"),
+        core_data_dir = args.core_data_dir.as_deref().unwrap_or("none"),
+        archive_url = args.archive_url.as_deref().unwrap_or("none"),
+        "Simulator server listening"
+    );
+
+    let mut watched = Vec::new();
+    if let Some(path) = &args.snapshot_file {
+        let state = Arc::clone(&state);
+        watched.push(WatchedFile::new(path, move |path| {
+            reload_snapshot(&state, path)
+        }));
+    }
+    if let Some(path) = &args.api_keys_file {
+        let state = Arc::clone(&state);
+        watched.push(WatchedFile::new(path, move |path| {
+            reload_api_keys(&state, path)
+        }));
+    }
+    reload::spawn_watcher(watched, Duration::from_secs(args.reload_interval_secs));
+
+    for request in server.incoming_requests() {
+        ServerStats::incr(&state.stats.received);
+        match (request.method(), request.url()) {
+            (Method::Get, "/health") => {
+                let body = serde_json::json!({
+                    "status": "ok",
+                    "running": state.concurrency.running(),
+                    "waiting": state.concurrency.waiting(),
+                    "snapshot_ledger_sequence": state
+                        .snapshot
+                        .as_ref()
+                        .and_then(|snapshot| snapshot.get().ledger_sequence()),
+                    "entry_cache": state.remote.as_ref().map(|remote| remote.stats()),
+                    "response_cache": state.response_cache.as_ref().map(ResponseCache::stats),
+                    "idempotency_keys": state.idempotency.as_ref().map(IdempotencyStore::len),
+                    "host_pool": state.host_pool.as_ref().map(HostPool::stats),
+                    "memory": {
+                        "in_use": memory::global_accountant().in_use(),
+                        "limit": memory::global_accountant().limit(),
+                    },
+                });
+                respond(&state, request, json_response(200, body.to_string()));
+            }
+            (Method::Post, "/simulate") => {
+                if let (Some(limiter), Some(addr)) = (&state.rate_limiter, request.remote_addr()) {
+                    if let Err(retry_after) = limiter.check(addr.ip()) {
+                        ServerStats::incr(&state.stats.rejected);
+                        respond(
+                            &state,
+                            request,
+                            rejection_response(429, "rate limit exceeded", retry_after),
+                        );
+                        continue;
+                    }
+                }
+                let client = match authorize(&state, &request, Scope::Simulate) {
+                    Ok(client) => client,
+                    Err(e) => {
+                        ServerStats::incr(&state.stats.rejected);
+                        respond(&state, request, auth_error_response(&e));
+                        continue;
+                    }
+                };
+                // Only requests holding a slot or a queue place get a thread.
+                let permit = match state.concurrency.admit() {
+                    Ok(permit) => permit,
+                    Err(rejection) => {
+                        reject_for_slot(&state, request, rejection);
+                        continue;
+                    }
+                };
+                let guard = state.in_flight.start();
+                let state = Arc::clone(&state);
+                std::thread::spawn(move || {
+                    handle_simulate(&state, request, client.as_deref(), permit);
+                    drop(guard);
+                });
+            }
+            (Method::Get, "/schema/request") => {
+                let body = schema::request_schema().to_string();
+                respond(&state, request, json_response(200, body));
+            }
+            (Method::Get, "/schema/response") => {
+                let body = schema::response_schema().to_string();
+                respond(&state, request, json_response(200, body));
+            }
+            (Method::Get, "/openapi.json") => {
+                let body = openapi::document().to_string();
+                respond(&state, request, json_response(200, body));
+            }
+            (Method::Get, "/docs") => {
+                let response = Response::from_string(openapi::SWAGGER_UI)
+                    .with_header(header("Content-Type", "text/html; charset=utf-8"));
+                respond(&state, request, response);
+            }
+            (
+                Method::Options,
+                "/health" | "/simulate" | "/schema/request" | "/schema/response" | "/openapi.json",
+            ) => {
+                let preflight = match &state.cors {
+                    Some(cors) => cors.preflight_headers(cors::request_origin(request.headers())),
+                    None => Vec::new(),
+                };
+                let mut response = Response::from_data(Vec::new()).with_status_code(204);
+                for h in preflight {
+                    response.add_header(h);
+                }
+                // Preflight headers are already set; bypass `respond`.
+                send(request, response);
+            }
+            _ => respond(&state, request, error_json(404, "not found")),
+        }
+    }
+
+    let drain_timeout = Duration::from_secs(args.drain_timeout_secs);
+    tracing::info!(
+        event = "server_draining",
+        in_flight = state.in_flight.count(),
+        drain_timeout_secs = args.drain_timeout_secs,
+        "Waiting for in-flight simulations"
+    );
+    let remaining = state.in_flight.wait_idle(drain_timeout);
+    if remaining > 0 {
+        tracing::warn!(
+            event = "drain_timeout",
+            remaining,
+            "Drain timeout elapsed with simulations still running"
+        );
+    }
+    state.stats.flush();
+    tracing::info!(event = "server_stopped", "Simulator server stopped");
+
+    Ok(())
+}
+
+/// Answers a request the [`ConcurrencyLimiter`] turned away with 503.
+fn reject_for_slot(state: &ServerState, request: Request, rejection: Rejection) {
+    let reason = match rejection {
+        Rejection::QueueFull => "simulation queue is full",
+        Rejection::QueueTimeout => "timed out waiting for a simulation slot",
+    };
+    let retry_after = state.concurrency.retry_after();
+    ServerStats::incr(&state.stats.rejected);
+    respond(state, request, rejection_response(503, reason, retry_after));
+}
+
+/// Runs a simulation for `request` once `permit` has a slot. `client` is the
+/// name of the API key that presented it, if keys are configured.
+fn handle_simulate(
+    state: &ServerState,
+    mut request: Request,
+    client: Option<&str>,
+    mut permit: SlotPermit,
+) {
+    if let Err(rejection) = permit.wait() {
+        reject_for_slot(state, request, rejection);
+        return;
+    }
+
+    let limits = &state.memory_limits;
+    let encoding = match content_encoding(request.headers()) {
+        Ok(encoding) => encoding,
+        Err(response) => {
+            respond(state, request, response);
+            return;
+        }
+    };
+    let compression = response_compression(state, request.headers());
+    let protobuf_request = proto::is_protobuf_request(request.headers());
+    let protobuf_response = proto::wants_protobuf_response(request.headers(), protobuf_request);
+    let parse_started = Instant::now();
+    let decoded = match compression::decoder(encoding, request.as_reader()) {
+        Ok(body) if protobuf_request => decode_protobuf(body, limits.max_request_bytes),
+        Ok(body) => decode_json(body, limits.max_request_bytes),
+        Err(e) => Err(error_json(400, &format!("invalid request body: {e}"))),
+    };
+    let DecodedRequest {
+        request: mut sim_request,
+        fingerprint,
+        reservation: mut body_reservation,
+    } = match decoded {
+        Ok(decoded) => decoded,
+        Err(response) => {
+            respond(state, request, response);
+            return;
+        }
+    };
+    let parse_elapsed = parse_started.elapsed();
+    let remote_addr = request.remote_addr().map(|addr| addr.ip());
+    let audit = |sim_request: &SimulationRequest, answer: Answer| {
+        if let Some(log) = &state.audit_log {
+            let audited = Audited {
+                fingerprint: &fingerprint,
+                client,
+                remote_addr,
+                request: sim_request,
+            };
+            log.record(&audited, &answer);
+        }
+    };
+    sim_request.rpc_url = None;
+    sim_request.soft_limit_percent = sim_request.soft_limit_percent.or(state.soft_limit_percent);
+
+    if let Some(network) = state.network {
+        let same_preset = sim_request
+            .network
+            .as_deref()
+            .is_none_or(|name| network::preset(name).is_some_and(|p| p.name == network.name));
+        let same_passphrase = sim_request
+            .network_passphrase
+            .as_deref()
+            .is_none_or(|p| p == network.passphrase);
+        if !(same_preset && same_passphrase) {
+            let msg = format!("this server simulates {} only", network.name);
+            audit(&sim_request, Answer::rejected(400, &msg));
+            respond(state, request, simulation_error_json(400, msg));
+            return;
+        }
+        sim_request.network = Some(network.name.to_string());
+    }
+
+    let pending = match claim_idempotency_key(state, &request, client, &sim_request, &fingerprint) {
+        Ok(Some(Claim::Replay(body))) => {
+            audit(&sim_request, Answer::reused(Outcome::Replayed));
+            let response = body_response(state, body, compression)
+                .with_header(header(idempotency::REPLAYED_HEADER, "true"));
+            respond(state, request, response);
+            return;
+        }
+        Ok(Some(Claim::New(pending))) => Some(pending),
+        Ok(None) => None,
+        Err(e) => {
+            audit(&sim_request, Answer::rejected(e.status(), &e.to_string()));
+            respond(state, request, error_json(e.status(), &e.to_string()));
+            return;
+        }
+    };
+
+    let cache_key = state
+        .response_cache
+        .as_ref()
+        .and_then(|_| ledger_state(state))
+        .map(|ledger_state| {
+            let state = format!("{ledger_state};protobuf={protobuf_response}");
+            response_cache::cache_key(&fingerprint, &state)
+        });
+    if let (Some(cache), Some(key)) = (&state.response_cache, &cache_key) {
+        if let Some(body) = cache.get(key) {
+            if let Some(pending) = pending {
+                pending.complete(body.clone());
+            }
+            audit(&sim_request, Answer::reused(Outcome::Cached));
+            let response =
+                body_response(state, body, compression).with_header(header("X-Cache", "hit"));
+            respond(state, request, response);
+            return;
+        }
+    }
+
+    let base = state.snapshot.as_ref().map(Reloadable::get);
+    let indexed = match base.as_deref() {
+        Some(BaseSnapshot::Json(file)) => {
+            // The copy lives as long as the request body, so it is held by
+            // the same reservation.
+            if let Err(e) = body_reservation.grow(memory::entries_bytes(&file.entries)) {
+                let response = memory_error_response(&e);
+                let status = response.status_code().0;
+                audit(&sim_request, Answer::rejected(status, &e.to_string()));
+                respond(state, request, response);
+                return;
+            }
+            let mut entries = file.entries.clone();
+            entries.extend(sim_request.ledger_entries.take().unwrap_or_default());
+            sim_request.ledger_entries = Some(entries);
+            None
+        }
+        Some(BaseSnapshot::Indexed(snapshot)) => Some(Arc::clone(snapshot)),
+        None => None,
+    };
+    drop(base);
+
+    let result = simulation::run(
+        &sim_request,
+        limits,
+        indexed,
+        state.remote.clone(),
+        state.host_pool.as_ref(),
+    );
+    ServerStats::incr(&state.stats.simulated);
+    let result = result.map(|mut response| {
+        simulation::add_parse_time(&mut response, parse_elapsed);
+        response
+    });
+    let response = match &result {
+        Ok(response) => {
+            let encode_started = Instant::now();
+            match encode_response(response, protobuf_response) {
+                Ok(body) => {
+                    let timing = server_timing(response.timings.as_ref(), encode_started.elapsed());
+                    if let Some(pending) = pending {
+                        pending.complete(body.clone());
+                    }
+                    match (&state.response_cache, cache_key) {
+                        (Some(cache), Some(key)) => {
+                            cache.put(key, body.clone());
+                            body_response(state, body, compression)
+                                .with_header(header("X-Cache", "miss"))
+                        }
+                        _ => body_response(state, body, compression),
+                    }
+                    .with_header(timing)
+                }
+                Err(e) => error_json(500, &format!("failed to serialize response: {e}")),
+            }
+        }
+        Err(SimulationError::InvalidRequest(msg)) => simulation_error_json(400, msg.clone()),
+        Err(SimulationError::Memory(e)) => memory_error_response(e),
+        Err(e @ SimulationError::XdrLimitsExceeded(_)) => error_json(413, &e.to_string()),
+        Err(e @ SimulationError::Source(_)) => error_json(502, &e.to_string()),
+    };
+    let error = match &result {
+        Ok(response) => response.error.clone(),
+        Err(e) => Some(e.to_string()),
+    };
+    audit(
+        &sim_request,
+        Answer {
+            outcome: match &result {
+                Ok(response) if response.status == "success" => Outcome::Success,
+                Ok(_) => Outcome::Failed,
+                Err(_) => Outcome::Rejected,
+            },
+            http_status: response.status_code().0,
+            response: result.as_ref().ok(),
+            error: error.as_deref(),
+        },
+    );
+    respond(state, request, response);
+}
+
+/// The request body's `Content-Encoding`.
+fn content_encoding(headers: &[Header]) -> Result<Option<Encoding>, HttpResponse> {
+    let Some(value) = headers.iter().find(|h| h.field.equiv("Content-Encoding")) else {
+        return Ok(None);
+    };
+    Encoding::from_header(value.value.as_str()).map_err(|unsupported| {
+        error_json(
+            415,
+            &format!("unsupported Content-Encoding {unsupported:?}; use gzip or zstd"),
+        )
+    })
+}
+
+/// The encoding to compress simulation responses with, if compression is
+/// enabled and the client accepts one.
+fn response_compression(state: &ServerState, headers: &[Header]) -> Option<Encoding> {
+    state.compress_min_bytes?;
+    headers
+        .iter()
+        .find(|h| h.field.equiv("Accept-Encoding"))
+        .and_then(|h| Encoding::negotiate(h.value.as_str()))
+}
+
+/// A decoded simulation request.
+struct DecodedRequest {
+    request: SimulationRequest,
+    fingerprint: [u8; 32],
+    /// Memory charged for the request body.
+    reservation: MemoryReservation<'static>,
+}
+
+/// Parses and validates a JSON request as it streams in.
+fn decode_json(body: impl Read, limit: usize) -> Result<DecodedRequest, HttpResponse> {
+    let parsed = streaming::parse_json(body, limit).map_err(|e| match e {
+        ParseError::Memory(e) => memory_error_response(&e),
+        e => simulation_error_json(400, e.to_string()),
+    })?;
+    let fingerprint =
+        response_cache::fingerprint(&parsed.fields, parsed.request.ledger_entries.as_ref());
+    Ok(DecodedRequest {
+        request: parsed.request,
+        fingerprint,
+        reservation: parsed.reservation,
+    })
+}
+
+/// Reads and decodes a protobuf request.
+fn decode_protobuf(body: impl Read, limit: usize) -> Result<DecodedRequest, HttpResponse> {
+    let body = memory::read_to_end_limited(body, limit).map_err(|e| memory_error_response(&e))?;
+    let reservation = memory::global_accountant()
+        .reserve(body.len())
+        .map_err(|e| memory_error_response(&e))?;
+    let request = proto::decode_request(&body)
+        .map_err(|e| simulation_error_json(400, format!("Invalid protobuf: {e}")))?;
+    Ok(DecodedRequest {
+        request,
+        fingerprint: response_cache::raw_fingerprint(&body),
+        reservation,
+    })
+}
+
+/// Encodes a simulation response as protobuf or JSON.
+fn encode_response(
+    response: &SimulationResponse,
+    protobuf: bool,
+) -> Result<CachedBody, serde_json::Error> {
+    if protobuf {
+        return Ok(CachedBody {
+            content_type: proto::CONTENT_TYPE,
+            bytes: proto::encode_response(response).into(),
+        });
+    }
+    Ok(CachedBody {
+        content_type: "application/json",
+        bytes: serde_json::to_vec(response)?.into(),
+    })
+}
+
+/// Describes the ledger state a request would run against, for response
+/// cache keys: the network, the base snapshot version and, with a remote
+/// source, the ledger it serves. `None` when that ledger is unknown.
+fn ledger_state(state: &ServerState) -> Option<String> {
+    let latest_ledger = match &state.remote {
+        Some(remote) => Some(remote.latest_ledger().ok()?),
+        None => None,
+    };
+    Some(format!(
+        "network={};snapshot={};ledger={}",
+        state.network.map_or("any", |preset| preset.name),
+        state.snapshot.as_ref().map_or(0, Reloadable::generation),
+        latest_ledger.map_or_else(|| "none".to_string(), |seq| seq.to_string()),
+    ))
+}
+
+/// Checks the request's API key when keys are configured, returning the
+/// key's name.
+fn authorize(
+    state: &ServerState,
+    request: &Request,
+    scope: Scope,
+) -> Result<Option<String>, AuthError> {
+    let Some(keys) = &state.api_keys else {
+        return Ok(None);
+    };
+    let keys = keys.get();
+    let key = keys.authorize(auth::presented_key(request.headers()), scope)?;
+    tracing::debug!(event = "request_authorized", api_key = %key.name, ?scope);
+    Ok(Some(key.name.clone()))
+}
+
+/// Claims the request's idempotency key, if it has one, scoped to `client`.
+fn claim_idempotency_key<'a>(
+    state: &'a ServerState,
+    request: &Request,
+    client: Option<&str>,
+    sim_request: &SimulationRequest,
+    fingerprint: &[u8; 32],
+) -> Result<Option<Claim<'a>>, idempotency::IdempotencyError> {
+    let Some(store) = &state.idempotency else {
+        return Ok(None);
+    };
+    let Some(key) = idempotency::request_key(
+        idempotency::presented_key(request.headers()),
+        sim_request.idempotency_key.as_deref(),
+    )?
+    else {
+        return Ok(None);
+    };
+    let scoped = format!("{}:{key}", client.unwrap_or_default());
+    store.claim(scoped, *fingerprint).map(Some)
+}
+
+fn log_snapshot_loaded(event: &str, snapshot: &BaseSnapshot) {
+    tracing::info!(
+        event,
+        format = snapshot.format(),
+        ledger_sequence = ?snapshot.ledger_sequence(),
+        entries = snapshot.len(),
+        "Snapshot loaded"
+    );
+}
+
+fn reload_snapshot(state: &ServerState, path: &Path) {
+    let Some(snapshot) = &state.snapshot else {
+        return;
+    };
+    match BaseSnapshot::load(path) {
+        Ok(loaded) => {
+            log_snapshot_loaded("snapshot_reloaded", &loaded);
+            snapshot.swap(loaded);
+        }
+        Err(e) => tracing::warn!(
+            event = "snapshot_reload_failed",
+            path = %path.display(),
+            error = %e,
+            "Keeping previous snapshot"
+        ),
+    }
+}
+
+fn reload_api_keys(state: &ServerState, path: &Path) {
+    let Some(keys) = &state.api_keys else {
+        return;
+    };
+    match ApiKeys::load(path) {
+        Ok(new_keys) => {
+            tracing::info!(
+                event = "api_keys_reloaded",
+                api_keys = new_keys.count(),
+                "API keys reloaded"
+            );
+            keys.swap(new_keys);
+        }
+        Err(e) => tracing::warn!(
+            event = "api_keys_reload_failed",
+            error = %e,
+            "Keeping previous API keys"
+        ),
+    }
+}
+
+type HttpResponse = Response<Cursor<Vec<u8>>>;
+
+/// Sends `response`, adding CORS headers when the request's origin is allowed.
+fn respond(state: &ServerState, request: Request, mut response: HttpResponse) {
+    if let Some(cors) = &state.cors {
+        for h in cors.response_headers(cors::request_origin(request.headers())) {
+            response.add_header(h);
+        }
+    }
+    send(request, response);
+}
+
+fn send(request: Request, response: HttpResponse) {
+    if let Err(e) = request.respond(response) {
+        tracing::warn!(event = "response_failed", error = %e, "Failed to send response");
+    }
+}
+
+/// A `Server-Timing` header with the phases of `timings` and the time
+/// spent encoding the response body.
+fn server_timing(timings: Option<&Timings>, encode: Duration) -> Header {
+    let mut phases = timings
+        .map(|t| {
+            vec![
+                ("parse", t.parse_ms),
+                ("snapshot", t.snapshot_ms),
+                ("execution", t.execution_ms),
+                ("post-processing", t.post_processing_ms),
+            ]
+        })
+        .unwrap_or_default();
+    phases.push(("encode", encode.as_secs_f64() * 1000.0));
+    let value = phases
+        .iter()
+        .map(|(name, ms)| format!("{name};dur={ms:.3}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    header("Server-Timing", &value)
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("static header is valid")
+}
+
+/// A `200` response carrying an encoded simulation response, compressed
+/// with `compression` if it is large enough.
+fn body_response(
+    state: &ServerState,
+    body: CachedBody,
+    compression: Option<Encoding>,
+) -> HttpResponse {
+    let content_type = header("Content-Type", body.content_type);
+    let large = state
+        .compress_min_bytes
+        .is_some_and(|min| body.bytes.len() >= min);
+    if let Some(encoding) = compression.filter(|_| large) {
+        match compression::compress(encoding, &body.bytes) {
+            Ok(compressed) => {
+                return Response::from_data(compressed)
+                    .with_header(content_type)
+                    .with_header(header("Content-Encoding", encoding.name()))
+                    .with_header(header("Vary", "Accept-Encoding"));
+            }
+            Err(e) => {
+                tracing::warn!(event = "compression_failed", error = %e, "Sending response uncompressed");
+            }
+        }
+    }
+    let response = Response::from_data(body.bytes.to_vec()).with_header(content_type);
+    if state.compress_min_bytes.is_some() {
+        response.with_header(header("Vary", "Accept-Encoding"))
+    } else {
+        response
+    }
+}
+
+fn json_response(status: u16, body: String) -> HttpResponse {
+    Response::from_string(body)
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"))
+}
+
+fn error_json(status: u16, msg: &str) -> HttpResponse {
+    json_response(
+        status,
+        serde_json::json!({ "status": "error", "error": msg }).to_string(),
+    )
+}
+
+fn simulation_error_json(status: u16, msg: String) -> HttpResponse {
+    match serde_json::to_string(&simulation::error_response(msg)) {
+        Ok(json) => json_response(status, json),
+        Err(e) => error_json(500, &format!("failed to serialize response: {e}")),
+    }
+}
+
+fn rejection_response(status: u16, msg: &str, retry_after: Duration) -> HttpResponse {
+    let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    error_json(status, msg).with_header(header("Retry-After", &secs.max(1).to_string()))
+}
+
+fn auth_error_response(e: &AuthError) -> HttpResponse {
+    match e {
+        AuthError::RateLimited { retry_after, .. } => {
+            rejection_response(429, &e.to_string(), *retry_after)
+        }
+        AuthError::Missing | AuthError::Invalid => {
+            error_json(e.status(), &e.to_string()).with_header(header("WWW-Authenticate", "Bearer"))
+        }
+        AuthError::Forbidden { .. } => error_json(e.status(), &e.to_string()),
+    }
+}
+
+fn memory_error_response(e: &MemoryError) -> HttpResponse {
+    match e {
+        MemoryError::RequestTooLarge { .. } | MemoryError::SnapshotTooLarge { .. } => {
+            error_json(413, &e.to_string())
+        }
+        MemoryError::GlobalBudgetExhausted { .. } => {
+            rejection_response(503, &e.to_string(), Duration::from_secs(1))
+        }
+        MemoryError::Io(_) => error_json(400, &e.to_string()),
+    }
+}
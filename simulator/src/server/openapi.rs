@@ -0,0 +1,250 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! OpenAPI document and Swagger UI for server mode.
+//!
+//! The document is assembled from the same schemars-derived schemas as
+//! [`crate::schema`], so generated clients always match the server they were
+//! generated from. It is served at `/openapi.json`; `/docs` serves a Swagger
+//! UI page pointing at it.
+
+use crate::types::{SimulationRequest, SimulationResponse};
+use schemars::generate::{SchemaGenerator, SchemaSettings};
+use schemars::JsonSchema;
+use serde_json::{json, Map, Value};
+
+/// Swagger UI page for `/docs`. The browser loads the UI assets from unpkg,
+/// pinned to one swagger-ui-dist release so the page cannot change under
+/// the server; bump the version in both URLs together.
+pub const SWAGGER_UI: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>erst-sim API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5.17.14/swagger-ui.css" crossorigin="anonymous" referrerpolicy="no-referrer">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5.17.14/swagger-ui-bundle.js" crossorigin="anonymous" referrerpolicy="no-referrer"></script>
+  <script>
+    window.ui = SwaggerUIBundle({ url: "openapi.json", dom_id: "#swagger-ui" });
+  </script>
+</body>
+</html>
+"##;
+
+/// Builds the OpenAPI 3.1 document describing the server's endpoints.
+pub fn document() -> Value {
+    let mut schemas = Map::new();
+    let request = component::<SimulationRequest>(SchemaSettings::draft2020_12(), &mut schemas);
+    let response = component::<SimulationResponse>(
+        SchemaSettings::draft2020_12().for_serialize(),
+        &mut schemas,
+    );
+    schemas.insert(
+        "Error".to_string(),
+        json!({
+            "type": "object",
+            "required": ["status", "error"],
+            "properties": {
+                "status": { "const": "error" },
+                "error": { "type": "string" }
+            }
+        }),
+    );
+    schemas.insert("Health".to_string(), health_schema());
+
+    let error = |description: &str| {
+        json!({
+            "description": description,
+            "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Error" } } }
+        })
+    };
+    // Protobuf bodies are described by proto/simulator.proto.
+    let binary = json!({ "schema": { "type": "string", "format": "binary" } });
+    let json_schema = |description: &str| {
+        json!({
+            "description": description,
+            "content": { "application/json": { "schema": { "type": "object" } } }
+        })
+    };
+
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "erst-sim",
+            "description": "Soroban transaction simulator",
+            "version": env!("CARGO_PKG_VERSION")
+        },
+        "paths": {
+            "/simulate": {
+                "post": {
+                    "operationId": "simulate",
+                    "summary": "Simulate a transaction",
+                    "security": [{ "apiKey": [] }, { "bearer": [] }, {}],
+                    "parameters": [{
+                        "name": "Idempotency-Key",
+                        "in": "header",
+                        "required": false,
+                        "schema": { "type": "string", "maxLength": 255 }
+                    }],
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "application/json": { "schema": request },
+                            (super::proto::CONTENT_TYPE): binary.clone()
+                        }
+                    },
+                    "responses": {
+                        "200": {
+                            "description": "Simulated; `status` is `error` if the host failed",
+                            "content": {
+                                "application/json": { "schema": response },
+                                (super::proto::CONTENT_TYPE): binary
+                            }
+                        },
+                        "400": error("Invalid JSON, schema violation, base64 or XDR"),
+                        "401": error("Missing or unknown API key"),
+                        "403": error("API key lacks the `simulate` scope"),
+                        "409": error("Same idempotency key still in progress"),
+                        "413": error("Request exceeds a memory or XDR limit"),
+                        "415": error("Unsupported `Content-Encoding`"),
+                        "422": error("Idempotency key reused with a different request"),
+                        "429": error("Rate limited; see `Retry-After`"),
+                        "502": error("soroban-rpc unreachable"),
+                        "503": error("No simulation slot available; see `Retry-After`")
+                    }
+                }
+            },
+            "/health": {
+                "get": {
+                    "operationId": "health",
+                    "summary": "Liveness and load",
+                    "responses": {
+                        "200": {
+                            "description": "Server is up",
+                            "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Health" } } }
+                        }
+                    }
+                }
+            },
+            "/schema/request": {
+                "get": {
+                    "operationId": "requestSchema",
+                    "summary": "JSON Schema of SimulationRequest",
+                    "responses": { "200": json_schema("JSON Schema") }
+                }
+            },
+            "/schema/response": {
+                "get": {
+                    "operationId": "responseSchema",
+                    "summary": "JSON Schema of SimulationResponse",
+                    "responses": { "200": json_schema("JSON Schema") }
+                }
+            },
+            "/openapi.json": {
+                "get": {
+                    "operationId": "openapi",
+                    "summary": "This document",
+                    "responses": { "200": json_schema("OpenAPI document") }
+                }
+            }
+        },
+        "components": {
+            "schemas": schemas,
+            "securitySchemes": {
+                "apiKey": { "type": "apiKey", "in": "header", "name": super::auth::API_KEY_HEADER },
+                "bearer": { "type": "http", "scheme": "bearer" }
+            }
+        }
+    })
+}
+
+/// Adds `T` and the types it refers to under `#/components/schemas`,
+/// returning a reference to `T`.
+fn component<T: JsonSchema>(settings: SchemaSettings, schemas: &mut Map<String, Value>) -> Value {
+    let mut generator: SchemaGenerator = settings
+        .with(|s| s.definitions_path = "/components/schemas".into())
+        .into_generator();
+    let reference = generator.subschema_for::<T>().to_value();
+    schemas.extend(generator.take_definitions(true));
+    reference
+}
+
+fn health_schema() -> Value {
+    let counter = json!({ "type": "integer", "minimum": 0 });
+    let nullable_object = json!({ "type": ["object", "null"] });
+    json!({
+        "type": "object",
+        "required": ["status", "running", "waiting"],
+        "properties": {
+            "status": { "const": "ok" },
+            "running": counter,
+            "waiting": counter,
+            "snapshot_ledger_sequence": { "type": ["integer", "null"] },
+            "entry_cache": nullable_object,
+            "response_cache": nullable_object,
+            "idempotency_keys": { "type": ["integer", "null"] },
+            "memory": {
+                "type": "object",
+                "required": ["in_use", "limit"],
+                "properties": { "in_use": counter, "limit": counter }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_references_resolve_to_components() {
+        let doc = document();
+        let schemas = doc["components"]["schemas"].as_object().unwrap();
+        for name in [
+            "SimulationRequest",
+            "SimulationResponse",
+            "AuthPayload",
+            "Error",
+        ] {
+            assert!(schemas.contains_key(name), "missing {name}");
+        }
+
+        // Every $ref in the document points at a component that exists.
+        fn refs(value: &Value, out: &mut Vec<String>) {
+            match value {
+                Value::Object(map) => {
+                    if let Some(Value::String(r)) = map.get("$ref") {
+                        out.push(r.clone());
+                    }
+                    map.values().for_each(|v| refs(v, out));
+                }
+                Value::Array(items) => items.iter().for_each(|v| refs(v, out)),
+                _ => {}
+            }
+        }
+        let mut found = Vec::new();
+        refs(&doc, &mut found);
+        assert!(!found.is_empty());
+        for r in found {
+            let name = r.strip_prefix("#/components/schemas/").unwrap();
+            assert!(schemas.contains_key(name), "dangling {r}");
+        }
+    }
+
+    #[test]
+    fn test_swagger_ui_assets_are_pinned() {
+        let urls: Vec<&str> = SWAGGER_UI
+            .split('"')
+            .filter(|part| part.starts_with("https://"))
+            .collect();
+        assert_eq!(urls.len(), 2);
+        for url in urls {
+            assert!(
+                url.starts_with("https://unpkg.com/swagger-ui-dist@5.17.14/"),
+                "{url}"
+            );
+        }
+    }
+}
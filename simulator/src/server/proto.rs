@@ -0,0 +1,656 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Protobuf encoding of `/simulate` requests and responses.
+//!
+//! Large `ledger_entries` maps are much smaller as raw XDR bytes than as
+//! base64 inside JSON, and faster to parse. The messages mirror
+//! `proto/simulator.proto`; requests are converted into the regular
+//! [`types::SimulationRequest`] so both encodings share one pipeline.
+//!
+//! A request is decoded as protobuf when its `Content-Type` is
+//! `application/x-protobuf`. A `200` response is encoded as protobuf when
+//! `Accept` asks for it, or when the request was protobuf and `Accept` does
+//! not ask for JSON. Error statuses are always JSON.
+
+use crate::types;
+use base64::Engine as _;
+use prost::Message;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Media type of protobuf bodies.
+pub const CONTENT_TYPE: &str = "application/x-protobuf";
+
+#[derive(Clone, PartialEq, Message)]
+pub struct LedgerEntryPair {
+    #[prost(bytes = "vec", tag = "1")]
+    pub key: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub entry: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct SimulationRequest {
+    #[prost(bytes = "vec", tag = "1")]
+    pub envelope_xdr: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub result_meta_xdr: Vec<u8>,
+    #[prost(message, repeated, tag = "3")]
+    pub ledger_entries: Vec<LedgerEntryPair>,
+    #[prost(bytes = "vec", optional, tag = "4")]
+    pub contract_wasm: Option<Vec<u8>>,
+    #[prost(string, optional, tag = "5")]
+    pub wasm_path: Option<String>,
+    #[prost(bool, tag = "6")]
+    pub enable_optimization_advisor: bool,
+    #[prost(bool, optional, tag = "7")]
+    pub profile: Option<bool>,
+    #[prost(string, tag = "8")]
+    pub timestamp: String,
+    #[prost(uint32, optional, tag = "9")]
+    pub mock_base_fee: Option<u32>,
+    #[prost(uint64, optional, tag = "10")]
+    pub mock_gas_price: Option<u64>,
+    #[prost(string, optional, tag = "11")]
+    pub network: Option<String>,
+    #[prost(string, optional, tag = "12")]
+    pub network_passphrase: Option<String>,
+    #[prost(bytes = "vec", repeated, tag = "13")]
+    pub auth_entries: Vec<Vec<u8>>,
+    #[prost(string, optional, tag = "14")]
+    pub idempotency_key: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct DiagnosticEvent {
+    #[prost(string, tag = "1")]
+    pub event_type: String,
+    #[prost(string, optional, tag = "2")]
+    pub contract_id: Option<String>,
+    #[prost(string, repeated, tag = "3")]
+    pub topics: Vec<String>,
+    #[prost(string, tag = "4")]
+    pub data: String,
+    #[prost(bool, tag = "5")]
+    pub in_successful_contract_call: bool,
+    #[prost(string, optional, tag = "6")]
+    pub wasm_instruction: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct BudgetUsage {
+    #[prost(uint64, tag = "1")]
+    pub cpu_instructions: u64,
+    #[prost(uint64, tag = "2")]
+    pub memory_bytes: u64,
+    #[prost(uint64, tag = "3")]
+    pub operations_count: u64,
+    #[prost(uint64, tag = "4")]
+    pub cpu_limit: u64,
+    #[prost(uint64, tag = "5")]
+    pub memory_limit: u64,
+    #[prost(double, tag = "6")]
+    pub cpu_usage_percent: f64,
+    #[prost(double, tag = "7")]
+    pub memory_usage_percent: f64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct AuthPayload {
+    #[prost(string, tag = "1")]
+    pub address: String,
+    #[prost(int64, tag = "2")]
+    pub nonce: i64,
+    #[prost(uint32, tag = "3")]
+    pub signature_expiration_ledger: u32,
+    #[prost(bytes = "vec", tag = "4")]
+    pub entry_xdr: Vec<u8>,
+    #[prost(string, tag = "5")]
+    pub payload_hash: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ConsumedNonce {
+    #[prost(string, tag = "1")]
+    pub address: String,
+    #[prost(int64, tag = "2")]
+    pub nonce: i64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct SignatureCheck {
+    #[prost(string, tag = "1")]
+    pub account: String,
+    #[prost(uint64, optional, tag = "2")]
+    pub muxed_id: Option<u64>,
+    #[prost(uint32, optional, tag = "3")]
+    pub operation_index: Option<u32>,
+    #[prost(string, tag = "4")]
+    pub threshold_level: String,
+    #[prost(bool, tag = "5")]
+    pub account_found: bool,
+    #[prost(uint32, tag = "6")]
+    pub required_weight: u32,
+    #[prost(uint32, tag = "7")]
+    pub signed_weight: u32,
+    #[prost(uint32, tag = "8")]
+    pub missing_weight: u32,
+    #[prost(bool, tag = "9")]
+    pub satisfied: bool,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct SourceAccount {
+    #[prost(string, tag = "1")]
+    pub account: String,
+    #[prost(uint64, optional, tag = "2")]
+    pub muxed_id: Option<u64>,
+    #[prost(string, optional, tag = "3")]
+    pub muxed_address: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct TrustlineState {
+    #[prost(string, tag = "1")]
+    pub account: String,
+    #[prost(string, tag = "2")]
+    pub asset: String,
+    #[prost(bool, tag = "3")]
+    pub exists: bool,
+    #[prost(bool, tag = "4")]
+    pub authorized: bool,
+    #[prost(bool, tag = "5")]
+    pub authorized_to_maintain_liabilities: bool,
+    #[prost(bool, tag = "6")]
+    pub clawback_enabled: bool,
+    #[prost(string, optional, tag = "7")]
+    pub problem: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Resources {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub read_only: Vec<Vec<u8>>,
+    #[prost(bytes = "vec", repeated, tag = "2")]
+    pub read_write: Vec<Vec<u8>>,
+    #[prost(uint32, repeated, tag = "3")]
+    pub archived_entries: Vec<u32>,
+    #[prost(uint32, tag = "4")]
+    pub disk_read_bytes: u32,
+    #[prost(uint32, tag = "5")]
+    pub write_bytes: u32,
+    #[prost(uint32, tag = "6")]
+    pub disk_read_entries: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct LimitCheck {
+    #[prost(string, tag = "1")]
+    pub limit: String,
+    #[prost(bytes = "vec", optional, tag = "2")]
+    pub key: Option<Vec<u8>>,
+    #[prost(uint64, tag = "3")]
+    pub value: u64,
+    #[prost(uint64, tag = "4")]
+    pub max: u64,
+    #[prost(string, tag = "5")]
+    pub severity: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct BalanceChange {
+    #[prost(string, tag = "1")]
+    pub address: String,
+    #[prost(string, tag = "2")]
+    pub token: String,
+    #[prost(string, optional, tag = "3")]
+    pub asset: Option<String>,
+    #[prost(string, tag = "4")]
+    pub amount: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct AuthWarning {
+    #[prost(string, tag = "1")]
+    pub address: String,
+    #[prost(string, tag = "2")]
+    pub contract: String,
+    #[prost(string, optional, tag = "3")]
+    pub function: Option<String>,
+    #[prost(string, tag = "4")]
+    pub change: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Reentrancy {
+    #[prost(string, tag = "1")]
+    pub contract: String,
+    #[prost(string, repeated, tag = "2")]
+    pub path: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Call {
+    #[prost(string, tag = "1")]
+    pub contract: String,
+    #[prost(string, tag = "2")]
+    pub function: String,
+    #[prost(string, repeated, tag = "3")]
+    pub args: Vec<String>,
+    #[prost(string, optional, tag = "4")]
+    pub result: Option<String>,
+    #[prost(message, repeated, tag = "5")]
+    pub calls: Vec<Call>,
+    #[prost(string, optional, tag = "6")]
+    pub error: Option<String>,
+    #[prost(string, optional, tag = "7")]
+    pub error_case: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct StorageAccess {
+    #[prost(bytes = "vec", tag = "1")]
+    pub key: Vec<u8>,
+    #[prost(string, tag = "2")]
+    pub entry_type: String,
+    #[prost(string, tag = "3")]
+    pub access: String,
+    #[prost(string, tag = "4")]
+    pub operation: String,
+    #[prost(string, tag = "5")]
+    pub origin: String,
+    #[prost(uint32, tag = "6")]
+    pub entry_bytes: u32,
+    #[prost(uint32, optional, tag = "7")]
+    pub live_until_before: Option<u32>,
+    #[prost(uint32, optional, tag = "8")]
+    pub live_until_after: Option<u32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ResponseMeta {
+    #[prost(string, tag = "1")]
+    pub simulator_version: String,
+    #[prost(string, tag = "2")]
+    pub host_version: String,
+    #[prost(uint32, tag = "3")]
+    pub min_protocol: u32,
+    #[prost(uint32, tag = "4")]
+    pub max_protocol: u32,
+    #[prost(string, optional, tag = "5")]
+    pub git_hash: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct SimulationResponse {
+    #[prost(string, tag = "1")]
+    pub status: String,
+    #[prost(string, optional, tag = "2")]
+    pub error: Option<String>,
+    #[prost(string, repeated, tag = "3")]
+    pub events: Vec<String>,
+    #[prost(message, repeated, tag = "4")]
+    pub diagnostic_events: Vec<DiagnosticEvent>,
+    #[prost(string, repeated, tag = "5")]
+    pub logs: Vec<String>,
+    #[prost(message, optional, tag = "6")]
+    pub budget_usage: Option<BudgetUsage>,
+    #[prost(uint64, optional, tag = "7")]
+    pub wasm_offset: Option<u64>,
+    #[prost(string, optional, tag = "8")]
+    pub source_location: Option<String>,
+    #[prost(string, optional, tag = "9")]
+    pub transaction_hash: Option<String>,
+    #[prost(message, repeated, tag = "10")]
+    pub auth_payloads: Vec<AuthPayload>,
+    #[prost(message, repeated, tag = "11")]
+    pub consumed_nonces: Vec<ConsumedNonce>,
+    #[prost(message, repeated, tag = "12")]
+    pub signature_checks: Vec<SignatureCheck>,
+    #[prost(message, optional, tag = "13")]
+    pub source_account: Option<SourceAccount>,
+    #[prost(message, repeated, tag = "14")]
+    pub trustlines: Vec<TrustlineState>,
+    #[prost(string, optional, tag = "15")]
+    pub sac_admin: Option<String>,
+    #[prost(uint32, optional, tag = "16")]
+    pub ledger_sequence: Option<u32>,
+    #[prost(bytes = "vec", optional, tag = "17")]
+    pub result_xdr: Option<Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "18")]
+    pub result_meta_xdr: Option<Vec<u8>>,
+    #[prost(uint64, optional, tag = "19")]
+    pub required_fee: Option<u64>,
+    #[prost(message, optional, tag = "20")]
+    pub meta: Option<ResponseMeta>,
+    #[prost(message, repeated, tag = "21")]
+    pub storage_accesses: Vec<StorageAccess>,
+    #[prost(message, optional, tag = "22")]
+    pub resources: Option<Resources>,
+    #[prost(message, repeated, tag = "23")]
+    pub limit_checks: Vec<LimitCheck>,
+    #[prost(string, repeated, tag = "24")]
+    pub pretty_invocations: Vec<String>,
+    #[prost(message, repeated, tag = "25")]
+    pub balance_changes: Vec<BalanceChange>,
+    #[prost(message, repeated, tag = "26")]
+    pub call_tree: Vec<Call>,
+    #[prost(message, repeated, tag = "27")]
+    pub auth_warnings: Vec<AuthWarning>,
+    #[prost(message, repeated, tag = "28")]
+    pub reentrancy: Vec<Reentrancy>,
+    #[prost(message, repeated, tag = "29")]
+    pub contract_events: Vec<DiagnosticEvent>,
+    #[prost(message, repeated, tag = "30")]
+    pub system_events: Vec<DiagnosticEvent>,
+    #[prost(uint64, optional, tag = "31")]
+    pub ledger_close_time: Option<u64>,
+}
+
+/// Whether the header `name` names the protobuf media type.
+fn names_protobuf(headers: &[tiny_http::Header], name: &'static str) -> Option<bool> {
+    headers
+        .iter()
+        .find(|h| h.field.equiv(name))
+        .map(|h| h.value.as_str().contains(CONTENT_TYPE))
+}
+
+/// Whether the request body is protobuf.
+pub fn is_protobuf_request(headers: &[tiny_http::Header]) -> bool {
+    names_protobuf(headers, "Content-Type").unwrap_or(false)
+}
+
+/// Whether a `200` response should be protobuf.
+pub fn wants_protobuf_response(headers: &[tiny_http::Header], protobuf_request: bool) -> bool {
+    let accepts_json = headers
+        .iter()
+        .find(|h| h.field.equiv("Accept"))
+        .is_some_and(|h| h.value.as_str().contains("application/json"));
+    names_protobuf(headers, "Accept").unwrap_or(false) || (protobuf_request && !accepts_json)
+}
+
+/// Decodes a protobuf request into the regular request type.
+pub fn decode_request(body: &[u8]) -> Result<types::SimulationRequest, prost::DecodeError> {
+    let request = SimulationRequest::decode(body)?;
+    let b64 = |bytes: &[u8]| base64::engine::general_purpose::STANDARD.encode(bytes);
+    let ledger_entries: HashMap<String, String> = request
+        .ledger_entries
+        .iter()
+        .map(|pair| (b64(&pair.key), b64(&pair.entry)))
+        .collect();
+    Ok(types::SimulationRequest {
+        envelope_xdr: b64(&request.envelope_xdr),
+        result_meta_xdr: b64(&request.result_meta_xdr),
+        ledger_entries: (!ledger_entries.is_empty()).then_some(ledger_entries),
+        contract_wasm: request.contract_wasm.as_deref().map(b64),
+        wasm_path: request.wasm_path,
+        enable_optimization_advisor: request.enable_optimization_advisor,
+        profile: request.profile,
+        timestamp: request.timestamp,
+        mock_base_fee: request.mock_base_fee,
+        mock_gas_price: request.mock_gas_price,
+        restore_preamble: None,
+        resource_calibration: None,
+        rpc_url: None,
+        horizon_url: None,
+        network: request.network,
+        network_passphrase: request.network_passphrase,
+        auth_entries: (!request.auth_entries.is_empty())
+            .then(|| request.auth_entries.iter().map(|e| b64(e)).collect()),
+        idempotency_key: request.idempotency_key,
+        invoke: None,
+        intent: None,
+        candidates: vec![],
+        candidate_output: None,
+        protocol: None,
+        compare_protocol: None,
+        step_instructions: None,
+        stop_at_instructions: None,
+        soft_limit_percent: None,
+        call_costs: false,
+        mock_oracles: vec![],
+        ledger_sequence: None,
+        ledger_close_time: None,
+        assemble: false,
+        resource_padding: types::ResourcePadding::default(),
+        fault_injection: types::FaultInjection::default(),
+        include: None,
+        record_reads: false,
+    })
+}
+
+/// Encodes a response as protobuf.
+pub fn encode_response(response: &types::SimulationResponse) -> Vec<u8> {
+    let unb64 = |text: &str| {
+        base64::engine::general_purpose::STANDARD
+            .decode(text)
+            .unwrap_or_default()
+    };
+    let message = SimulationResponse {
+        status: response.status.clone(),
+        error: response.error.clone(),
+        events: response.events.iter().map(ToString::to_string).collect(),
+        diagnostic_events: response.diagnostic_events.iter().map(event).collect(),
+        logs: response.logs.clone(),
+        budget_usage: response.budget_usage.as_ref().map(|b| BudgetUsage {
+            cpu_instructions: b.cpu_instructions,
+            memory_bytes: b.memory_bytes,
+            operations_count: b.operations_count as u64,
+            cpu_limit: b.cpu_limit,
+            memory_limit: b.memory_limit,
+            cpu_usage_percent: b.cpu_usage_percent,
+            memory_usage_percent: b.memory_usage_percent,
+        }),
+        wasm_offset: response.wasm_offset,
+        source_location: response.source_location.clone(),
+        transaction_hash: response.transaction_hash.clone(),
+        auth_payloads: response
+            .auth_payloads
+            .iter()
+            .map(|p| AuthPayload {
+                address: p.address.clone(),
+                nonce: p.nonce,
+                signature_expiration_ledger: p.signature_expiration_ledger,
+                entry_xdr: base64::engine::general_purpose::STANDARD
+                    .decode(&p.entry_xdr)
+                    .unwrap_or_default(),
+                payload_hash: p.payload_hash.clone(),
+            })
+            .collect(),
+        consumed_nonces: response
+            .consumed_nonces
+            .iter()
+            .map(|n| ConsumedNonce {
+                address: n.address.clone(),
+                nonce: n.nonce,
+            })
+            .collect(),
+        signature_checks: response
+            .signature_checks
+            .iter()
+            .map(|c| SignatureCheck {
+                account: c.account.clone(),
+                muxed_id: c.muxed_id,
+                operation_index: c.operation_index.and_then(|i| u32::try_from(i).ok()),
+                threshold_level: c.threshold_level.name().to_string(),
+                account_found: c.account_found,
+                required_weight: c.required_weight,
+                signed_weight: c.signed_weight,
+                missing_weight: c.missing_weight,
+                satisfied: c.satisfied,
+            })
+            .collect(),
+        source_account: response.source_account.as_ref().map(|s| SourceAccount {
+            account: s.account.clone(),
+            muxed_id: s.muxed_id,
+            muxed_address: s.muxed_address.clone(),
+        }),
+        trustlines: response
+            .trustlines
+            .iter()
+            .map(|t| TrustlineState {
+                account: t.account.clone(),
+                asset: t.asset.clone(),
+                exists: t.exists,
+                authorized: t.authorized,
+                authorized_to_maintain_liabilities: t.authorized_to_maintain_liabilities,
+                clawback_enabled: t.clawback_enabled,
+                problem: t.problem.clone(),
+            })
+            .collect(),
+        sac_admin: response.sac_admin.clone(),
+        ledger_sequence: response.ledger_sequence,
+        ledger_close_time: response.ledger_close_time,
+        result_xdr: response.result_xdr.as_deref().map(unb64),
+        result_meta_xdr: response.result_meta_xdr.as_deref().map(unb64),
+        required_fee: response.required_fee,
+        meta: Some(ResponseMeta {
+            simulator_version: response.meta.simulator_version.to_string(),
+            host_version: response.meta.host_version.to_string(),
+            min_protocol: response.meta.min_protocol,
+            max_protocol: response.meta.max_protocol,
+            git_hash: response.meta.git_hash.map(str::to_string),
+        }),
+        storage_accesses: response
+            .storage_accesses
+            .iter()
+            .map(|a| StorageAccess {
+                key: unb64(&a.key),
+                entry_type: a.entry_type.clone(),
+                access: a.access.to_string(),
+                operation: a.operation.to_string(),
+                origin: a.origin.clone(),
+                entry_bytes: a.entry_bytes,
+                live_until_before: a.live_until_before,
+                live_until_after: a.live_until_after,
+            })
+            .collect(),
+        resources: response.resources.as_ref().map(|r| Resources {
+            read_only: r.read_only.iter().map(|k| unb64(k)).collect(),
+            read_write: r.read_write.iter().map(|k| unb64(k)).collect(),
+            archived_entries: r.archived_entries.clone(),
+            disk_read_bytes: r.disk_read_bytes,
+            write_bytes: r.write_bytes,
+            disk_read_entries: r.disk_read_entries,
+        }),
+        limit_checks: response
+            .limit_checks
+            .iter()
+            .map(|c| LimitCheck {
+                limit: c.limit.to_string(),
+                key: c.key.as_deref().map(unb64),
+                value: c.value,
+                max: c.max,
+                severity: c.severity.to_string(),
+            })
+            .collect(),
+        pretty_invocations: response.pretty_invocations.clone(),
+        balance_changes: response
+            .balance_changes
+            .iter()
+            .map(|c| BalanceChange {
+                address: c.address.clone(),
+                token: c.token.clone(),
+                asset: c.asset.clone(),
+                amount: c.amount.clone(),
+            })
+            .collect(),
+        call_tree: response.call_tree.iter().map(call).collect(),
+        auth_warnings: response
+            .auth_warnings
+            .iter()
+            .map(|w| AuthWarning {
+                address: w.address.clone(),
+                contract: w.contract.clone(),
+                function: w.function.clone(),
+                change: w.change.clone(),
+            })
+            .collect(),
+        reentrancy: response
+            .reentrancy
+            .iter()
+            .map(|r| Reentrancy {
+                contract: r.contract.clone(),
+                path: r.path.clone(),
+            })
+            .collect(),
+        contract_events: response.contract_events.iter().map(event).collect(),
+        system_events: response.system_events.iter().map(event).collect(),
+    };
+    message.encode_to_vec()
+}
+
+fn event(event: &Arc<types::DiagnosticEvent>) -> DiagnosticEvent {
+    DiagnosticEvent {
+        event_type: event.event_type().to_string(),
+        contract_id: event.contract_id().map(|id| format!("{id:?}")),
+        topics: event.topics().iter().map(|t| format!("{t:?}")).collect(),
+        data: format!("{:?}", event.data()),
+        in_successful_contract_call: event.in_successful_contract_call(),
+        wasm_instruction: event.wasm_instruction.clone(),
+    }
+}
+
+fn call(call: &crate::calls::Call) -> Call {
+    Call {
+        contract: call.contract.clone(),
+        function: call.function.clone(),
+        args: call.args.clone(),
+        result: call.result.clone(),
+        calls: call.calls.iter().map(self::call).collect(),
+        error: call.error.clone(),
+        error_case: call.error_case.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_bytes_become_base64() {
+        let message = SimulationRequest {
+            envelope_xdr: vec![1, 2, 3],
+            ledger_entries: vec![LedgerEntryPair {
+                key: vec![4],
+                entry: vec![5, 6],
+            }],
+            auth_entries: vec![vec![7]],
+            timestamp: "now".to_string(),
+            ..Default::default()
+        };
+        let request = decode_request(&message.encode_to_vec()).unwrap();
+        assert_eq!(request.envelope_xdr, "AQID");
+        assert_eq!(request.result_meta_xdr, "");
+        assert_eq!(
+            request
+                .ledger_entries
+                .unwrap()
+                .get("BA==")
+                .map(String::as_str),
+            Some("BQY=")
+        );
+        assert_eq!(request.auth_entries, Some(vec!["Bw==".to_string()]));
+        assert_eq!(request.contract_wasm, None);
+        assert_eq!(request.timestamp, "now");
+
+        assert!(decode_request(&[0xff, 0xff]).is_err());
+    }
+
+    #[test]
+    fn test_response_format_negotiation() {
+        let h = |name: &str, value: &str| tiny_http::Header::from_bytes(name, value).unwrap();
+        let proto = [h("Content-Type", CONTENT_TYPE)];
+        assert!(is_protobuf_request(&proto));
+        assert!(wants_protobuf_response(&proto, true));
+        let proto_json = [
+            h("Content-Type", CONTENT_TYPE),
+            h("Accept", "application/json"),
+        ];
+        assert!(!wants_protobuf_response(&proto_json, true));
+        let json_proto = [h("Accept", CONTENT_TYPE)];
+        assert!(!is_protobuf_request(&json_proto));
+        assert!(wants_protobuf_response(&json_proto, false));
+    }
+}
@@ -0,0 +1,138 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Hot reload of the server's snapshot file and API keys file.
+//!
+//! A background thread polls the modification time of each watched file.
+//! When a file changes it is reloaded and validated; only a successful load
+//! is swapped in, so a half-written or corrupt file leaves the previous
+//! version serving. Requests already running keep the version they started
+//! with.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+/// A value that can be replaced atomically while readers hold the old one.
+#[derive(Debug)]
+pub struct Reloadable<T> {
+    current: RwLock<Arc<T>>,
+    generation: AtomicU64,
+}
+
+impl<T> Reloadable<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(value)),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the current value.
+    pub fn get(&self) -> Arc<T> {
+        Arc::clone(&self.current.read().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Replaces the current value.
+    pub fn swap(&self, value: T) {
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(value);
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Number of times the value has been replaced.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+}
+
+type ReloadFn = Box<dyn FnMut(&Path) + Send>;
+
+/// A file whose changes trigger a reload.
+pub struct WatchedFile {
+    path: PathBuf,
+    last_modified: Option<SystemTime>,
+    on_change: ReloadFn,
+}
+
+impl WatchedFile {
+    /// Watches `path`, calling `on_change` each time its modification time changes.
+    pub fn new(path: impl Into<PathBuf>, on_change: impl FnMut(&Path) + Send + 'static) -> Self {
+        let path = path.into();
+        let last_modified = modified(&path);
+        Self {
+            path,
+            last_modified,
+            on_change: Box::new(on_change),
+        }
+    }
+
+    /// Calls the reload callback if the file changed since the last poll.
+    fn poll(&mut self) {
+        let modified = modified(&self.path);
+        if modified.is_some() && modified != self.last_modified {
+            self.last_modified = modified;
+            (self.on_change)(&self.path);
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Polls `files` every `interval` on a background thread.
+pub fn spawn_watcher(mut files: Vec<WatchedFile>, interval: Duration) {
+    if files.is_empty() || interval.is_zero() {
+        return;
+    }
+    std::thread::spawn(move || loop {
+        std::thread::sleep(interval);
+        for file in &mut files {
+            file.poll();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn test_reloadable_swap_keeps_old_readers() {
+        let value = Reloadable::new(1);
+        let old = value.get();
+        value.swap(2);
+        assert_eq!(*old, 1);
+        assert_eq!(*value.get(), 2);
+        assert_eq!(value.generation(), 1);
+    }
+
+    #[test]
+    fn test_watched_file_fires_on_modification() {
+        let path = std::env::temp_dir().join(format!("erst-reload-{}.json", std::process::id()));
+        std::fs::write(&path, "1").unwrap();
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let mut file = WatchedFile::new(&path, move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        file.poll();
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        let later = SystemTime::now() + Duration::from_secs(5);
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|f| f.set_modified(later))
+            .unwrap();
+        file.poll();
+        file.poll();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,225 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Cache of simulation responses.
+//!
+//! Dashboards re-preflight the same pending transaction every few seconds.
+//! Serialized responses are cached under a hash of the request's
+//! [`fingerprint`] (for JSON, the canonical request with object keys and
+//! ledger entries sorted, so field order and whitespace do not matter), the response encoding and
+//! the state it ran against: network, base snapshot version and remote ledger
+//! sequence. Entries are served until they are older than the TTL.
+
+use lru::LruCache;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Default number of cached responses.
+pub const DEFAULT_RESPONSE_CACHE_ENTRIES: usize = 1_000;
+
+/// Request fields that do not affect the response in server mode.
+const IGNORED_FIELDS: &[&str] = &["rpc_url", "horizon_url", "idempotency_key"];
+
+/// An encoded response body and its content type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedBody {
+    pub content_type: &'static str,
+    pub bytes: Arc<[u8]>,
+}
+
+/// When a response was stored, and its body.
+type CachedResponse = (Instant, CachedBody);
+
+/// Response cache statistics reported by `/health`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ResponseCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+/// A TTL-bounded LRU cache of serialized responses.
+pub struct ResponseCache {
+    ttl: Duration,
+    entries: Mutex<LruCache<[u8; 32], CachedResponse>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ResponseCache {
+    /// Caches up to `capacity` responses for `ttl`. Returns `None` when
+    /// either is zero.
+    pub fn new(ttl: Duration, capacity: usize) -> Option<Self> {
+        let capacity = NonZeroUsize::new(capacity)?;
+        (!ttl.is_zero()).then(|| Self {
+            ttl,
+            entries: Mutex::new(LruCache::new(capacity)),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        })
+    }
+
+    /// Returns the cached response for `key` unless it has expired.
+    pub fn get(&self, key: &[u8; 32]) -> Option<CachedBody> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let found = match entries.get(key) {
+            Some((stored_at, body)) if stored_at.elapsed() < self.ttl => Some(body.clone()),
+            Some(_) => {
+                entries.pop(key);
+                None
+            }
+            None => None,
+        };
+        let counter = if found.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        found
+    }
+
+    /// Stores the response for `key`.
+    pub fn put(&self, key: [u8; 32], body: CachedBody) {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .put(key, (Instant::now(), body));
+    }
+
+    pub fn stats(&self) -> ResponseCacheStats {
+        ResponseCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.entries.lock().unwrap_or_else(|e| e.into_inner()).len(),
+        }
+    }
+}
+
+/// Hashes the canonical form of a JSON request, given as its `fields` other
+/// than `ledger_entries` plus the entries, ignoring fields that do not
+/// affect the response.
+pub fn fingerprint(fields: &Value, ledger_entries: Option<&HashMap<String, String>>) -> [u8; 32] {
+    let mut fields = canonical(fields);
+    if let Value::Object(fields) = &mut fields {
+        for field in IGNORED_FIELDS {
+            fields.remove(*field);
+        }
+    }
+    let mut hasher = Sha256::new()
+        .chain_update(b"json\0")
+        .chain_update(fields.to_string().as_bytes());
+    if let Some(entries) = ledger_entries {
+        let mut sorted: Vec<_> = entries.iter().collect();
+        sorted.sort_unstable();
+        for (key, entry) in sorted {
+            hasher.update(key.as_bytes());
+            hasher.update(b"\0");
+            hasher.update(entry.as_bytes());
+            hasher.update(b"\0");
+        }
+    }
+    hasher.finalize().into()
+}
+
+/// Hashes a binary request body as sent.
+pub fn raw_fingerprint(body: &[u8]) -> [u8; 32] {
+    Sha256::new()
+        .chain_update(b"raw\0")
+        .chain_update(body)
+        .finalize()
+        .into()
+}
+
+/// Combines a request fingerprint with `state`, a description of the ledger
+/// state the request runs against and how its response is encoded.
+pub fn cache_key(fingerprint: &[u8; 32], state: &str) -> [u8; 32] {
+    Sha256::new()
+        .chain_update(fingerprint)
+        .chain_update(state.as_bytes())
+        .finalize()
+        .into()
+}
+
+/// Rebuilds `value` with object keys in sorted order.
+fn canonical(value: &Value) -> Value {
+    match value {
+        Value::Object(fields) => {
+            let mut sorted: Vec<_> = fields.iter().collect();
+            sorted.sort_by(|a, b| a.0.cmp(b.0));
+            Value::Object(
+                sorted
+                    .into_iter()
+                    .map(|(k, v)| (k.clone(), canonical(v)))
+                    .collect::<Map<_, _>>(),
+            )
+        }
+        Value::Array(items) => Value::Array(items.iter().map(canonical).collect()),
+        other => other.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_key_ignores_field_order_and_rpc_url() {
+        let a = json!({ "envelope_xdr": "AAA", "timestamp": "" });
+        let b: Value =
+            serde_json::from_str(r#"{"timestamp":"","rpc_url":"http://x","envelope_xdr":"AAA"}"#)
+                .unwrap();
+        let entries: HashMap<String, String> = [("k1", "v1"), ("k2", "v2")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        assert_eq!(
+            fingerprint(&a, Some(&entries)),
+            fingerprint(&b, Some(&entries.clone()))
+        );
+        assert_ne!(fingerprint(&a, Some(&entries)), fingerprint(&a, None));
+        assert_ne!(
+            fingerprint(&a, None),
+            fingerprint(&json!({ "envelope_xdr": "BBB" }), None)
+        );
+        assert_ne!(
+            fingerprint(&a, None),
+            raw_fingerprint(a.to_string().as_bytes())
+        );
+        assert_ne!(
+            cache_key(&fingerprint(&a, None), "ledger=5"),
+            cache_key(&fingerprint(&a, None), "ledger=6")
+        );
+    }
+
+    #[test]
+    fn test_entries_expire_after_ttl() {
+        let cache = ResponseCache::new(Duration::from_millis(50), 10).unwrap();
+        let body = CachedBody {
+            content_type: "application/json",
+            bytes: Arc::from(&b"{}"[..]),
+        };
+        cache.put([1; 32], body.clone());
+        assert_eq!(cache.get(&[1; 32]), Some(body));
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(cache.get(&[1; 32]), None);
+        assert_eq!(
+            cache.stats(),
+            ResponseCacheStats {
+                hits: 1,
+                misses: 1,
+                entries: 0
+            }
+        );
+
+        assert!(ResponseCache::new(Duration::ZERO, 10).is_none());
+        assert!(ResponseCache::new(Duration::from_secs(1), 0).is_none());
+    }
+}
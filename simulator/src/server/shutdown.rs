@@ -0,0 +1,154 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Graceful shutdown for server mode.
+//!
+//! On SIGINT or SIGTERM the server stops taking new requests, waits for
+//! in-flight simulations to finish (bounded by `--drain-timeout-secs`) and
+//! logs its final request counters before exiting, so rolling deploys do not
+//! cut responses off mid-stream.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tiny_http::Server;
+
+/// Tracks requests that have been accepted but not yet answered.
+#[derive(Debug, Default)]
+pub struct InFlight {
+    count: Mutex<usize>,
+    idle: Condvar,
+}
+
+impl InFlight {
+    /// Marks a request as in flight until the returned guard is dropped.
+    pub fn start(self: &Arc<Self>) -> InFlightGuard {
+        *self.count.lock().unwrap_or_else(|e| e.into_inner()) += 1;
+        InFlightGuard {
+            in_flight: Arc::clone(self),
+        }
+    }
+
+    /// Returns the number of requests in flight.
+    pub fn count(&self) -> usize {
+        *self.count.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Waits until no requests are in flight or `timeout` elapses.
+    ///
+    /// Returns the number of requests still in flight.
+    pub fn wait_idle(&self, timeout: Duration) -> usize {
+        let deadline = Instant::now() + timeout;
+        let mut count = self.count.lock().unwrap_or_else(|e| e.into_inner());
+        while *count > 0 {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            count = self
+                .idle
+                .wait_timeout(count, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+        *count
+    }
+
+    fn finish(&self) {
+        let mut count = self.count.lock().unwrap_or_else(|e| e.into_inner());
+        *count -= 1;
+        if *count == 0 {
+            self.idle.notify_all();
+        }
+    }
+}
+
+/// RAII marker for one in-flight request.
+#[derive(Debug)]
+pub struct InFlightGuard {
+    in_flight: Arc<InFlight>,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.in_flight.finish();
+    }
+}
+
+/// Request counters reported when the server stops.
+#[derive(Debug, Default)]
+pub struct ServerStats {
+    /// Requests received, including rejected ones.
+    pub received: AtomicU64,
+    /// Simulations that ran to completion (successfully or not).
+    pub simulated: AtomicU64,
+    /// Requests rejected by rate limiting, authentication or admission control.
+    pub rejected: AtomicU64,
+}
+
+impl ServerStats {
+    /// Increments `counter` by one.
+    pub fn incr(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Logs the final counters.
+    pub fn flush(&self) {
+        tracing::info!(
+            event = "server_stats",
+            received = self.received.load(Ordering::Relaxed),
+            simulated = self.simulated.load(Ordering::Relaxed),
+            rejected = self.rejected.load(Ordering::Relaxed),
+            "Final request counters"
+        );
+    }
+}
+
+/// Installs a SIGINT/SIGTERM handler that stops `server` from yielding new
+/// requests.
+pub fn install_handler(server: Arc<Server>) -> Result<(), String> {
+    let stopping = AtomicBool::new(false);
+    ctrlc::set_handler(move || {
+        if !stopping.swap(true, Ordering::SeqCst) {
+            tracing::info!(event = "shutdown_requested", "Stopping intake and draining");
+            server.unblock();
+        }
+    })
+    .map_err(|e| format!("failed to install signal handler: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_in_flight_counts_guards() {
+        let in_flight = Arc::new(InFlight::default());
+        let a = in_flight.start();
+        let b = in_flight.start();
+        assert_eq!(in_flight.count(), 2);
+        drop(a);
+        assert_eq!(in_flight.count(), 1);
+        drop(b);
+        assert_eq!(in_flight.wait_idle(Duration::ZERO), 0);
+    }
+
+    #[test]
+    fn test_wait_idle_times_out_with_remaining_count() {
+        let in_flight = Arc::new(InFlight::default());
+        let _guard = in_flight.start();
+        assert_eq!(in_flight.wait_idle(Duration::from_millis(10)), 1);
+    }
+
+    #[test]
+    fn test_wait_idle_wakes_when_last_request_finishes() {
+        let in_flight = Arc::new(InFlight::default());
+        let guard = in_flight.start();
+        let worker = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            drop(guard);
+        });
+        assert_eq!(in_flight.wait_idle(Duration::from_secs(5)), 0);
+        worker.join().expect("worker thread");
+    }
+}
@@ -0,0 +1,2118 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Simulation pipeline shared by the stdin protocol and server mode.
+//!
+//! [`run`] takes a parsed `SimulationRequest`, builds a Soroban host, executes
+//! the envelope's operations and assembles a `SimulationResponse`. Failures
+//! that happen before execution (malformed XDR, XDR or memory limits) are
+//! returned as a [`SimulationError`] so each front end can decide how to
+//! report them.
+//!
+//! The host runs with a recording footprint over a [`LayeredSource`]: the
+//! request's entries first, then the optional indexed base snapshot, then
+//! the optional shared remote source. Keys declared in the envelope's
+//! footprint are prefetched from the remote source before execution.
+
+use crate::assemble::{self, FeeParams};
+use crate::attribution;
+use crate::auth_lint;
+use crate::authorization;
+use crate::balances;
+use crate::bundle;
+use crate::calls;
+use crate::custom_account;
+use crate::diff;
+use crate::fault;
+use crate::fee_source;
+use crate::gas_optimizer::{BudgetMetrics, GasOptimizationAdvisor, CPU_LIMIT, MEMORY_LIMIT};
+use crate::hosts;
+use crate::intents;
+use crate::invoke;
+use crate::limits::{self, NetworkLimits};
+use crate::memory::{self, MemoryError, MemoryLimits};
+use crate::multisig;
+use crate::network::{self, Network};
+use crate::oracle;
+use crate::pretty;
+use crate::protocol;
+use crate::rent::{self, RentParams};
+use crate::replay;
+use crate::resources;
+use crate::runner::SimHost;
+use crate::snapshot::indexed::IndexedSnapshot;
+use crate::snapshot::{self, LedgerSnapshot, SnapshotError};
+use crate::solver;
+use crate::source::cache::CachedSource;
+use crate::source::{LayeredSource, SnapshotLedgerSource, SourceError};
+use crate::source_mapper::SourceMapper;
+use crate::stack_trace::{decode_error, WasmStackTrace};
+use crate::storage_log;
+use crate::timeline;
+use crate::truncation;
+use crate::trustline;
+use crate::types::*;
+use crate::warm::HostPool;
+use crate::xdr_limits;
+use base64::Engine as _;
+use soroban_env_host::auth::RecordedAuthPayload;
+use soroban_env_host::budget::Budget;
+use soroban_env_host::events::{Events, HostEvent};
+use soroban_env_host::storage::Storage;
+use soroban_env_host::xdr::{
+    ContractEventBody, ContractEventType, FeeBumpTransactionInnerTx, HostFunction,
+    InvokeHostFunctionOp, LedgerKey, Limits, Operation, OperationBody, ScAddress, ScErrorCode,
+    ScErrorType, ScVal, SorobanAuthorizationEntry, SorobanTransactionData, TransactionEnvelope,
+    TransactionExt, TransactionResultMeta, WriteXdr,
+};
+use soroban_env_host::{Host, HostError, LedgerInfo};
+use std::collections::HashSet;
+use std::fmt::{self, Write as _};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Errors that prevent a simulation from running at all.
+#[derive(Debug)]
+pub enum SimulationError {
+    /// The request is malformed (bad base64, bad XDR, incompatible WASM).
+    InvalidRequest(String),
+    /// The request would exceed a configured memory ceiling.
+    Memory(MemoryError),
+    /// Decoding the request's XDR hit the configured depth or length limits.
+    XdrLimitsExceeded(String),
+    /// The remote state source could not be queried.
+    Source(SourceError),
+}
+
+impl fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimulationError::InvalidRequest(msg) => write!(f, "{msg}"),
+            SimulationError::Memory(e) => write!(f, "{e}"),
+            SimulationError::XdrLimitsExceeded(msg) => write!(f, "XDR limits exceeded: {msg}"),
+            SimulationError::Source(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<MemoryError> for SimulationError {
+    fn from(e: MemoryError) -> Self {
+        SimulationError::Memory(e)
+    }
+}
+
+/// Builds the response returned for requests that could not be simulated.
+pub fn error_response(msg: String) -> SimulationResponse {
+    let trace = WasmStackTrace::from_host_error(&msg);
+    SimulationResponse {
+        error: Some(msg),
+        stack_trace: Some(trace),
+        ..empty_response()
+    }
+}
+
+/// A failed response that reports nothing.
+fn empty_response() -> SimulationResponse {
+    SimulationResponse {
+        status: "error".to_string(),
+        error: None,
+        events: vec![],
+        contract_events: vec![],
+        system_events: vec![],
+        diagnostic_events: vec![],
+        categorized_events: vec![],
+        logs: vec![],
+        flamegraph: None,
+        cost_attribution: None,
+        optimization_report: None,
+        budget_usage: None,
+        timings: None,
+        source_location: None,
+        stack_trace: None,
+        wasm_offset: None,
+        transaction_hash: None,
+        cache_stats: None,
+        auth_payloads: vec![],
+        consumed_nonces: vec![],
+        signature_checks: vec![],
+        source_account: None,
+        trustlines: vec![],
+        resources: None,
+        rent: None,
+        limit_checks: vec![],
+        pretty_invocations: vec![],
+        balance_changes: vec![],
+        token_events: vec![],
+        call_tree: vec![],
+        auth_warnings: vec![],
+        custom_accounts: vec![],
+        reentrancy: vec![],
+        storage_accesses: vec![],
+        sac_admin: None,
+        ledger_sequence: None,
+        ledger_close_time: None,
+        result_xdr: None,
+        result_meta_xdr: None,
+        required_fee: None,
+        fee_source: None,
+        assembled: None,
+        truncated: None,
+        read_entries: Default::default(),
+        protocol_comparison: None,
+        solver: None,
+        checkpoints: vec![],
+        timeline: vec![],
+        meta: hosts::response_meta(),
+    }
+}
+
+/// Runs a single simulation request to completion.
+///
+/// Entries missing from the request are looked up in `base`, then fetched
+/// from `remote`, if given, as of its latest ledger. With
+/// `compare_protocol`, the request runs again under that configuration at
+/// the same ledger, and the differences are reported in
+/// `protocol_comparison`. With `pool`, contract modules compiled by earlier
+/// runs are reused (see [`crate::warm`]). With `step_instructions`, each
+/// run is stepped (see [`run_stepped`]).
+pub fn run(
+    request: &SimulationRequest,
+    limits: &MemoryLimits,
+    base: Option<Arc<IndexedSnapshot>>,
+    remote: Option<Arc<CachedSource>>,
+    pool: Option<&HostPool>,
+) -> Result<SimulationResponse, SimulationError> {
+    if !request.candidates.is_empty() {
+        let mut response = solver::run(request, limits, base, remote, pool)?;
+        if let Some(include) = &request.include {
+            select_sections(&mut response, include);
+        }
+        return Ok(response);
+    }
+    if request.intent.is_some() {
+        return run(&intents::expand(request)?, limits, base, remote, pool);
+    }
+    let baseline = request.protocol.as_ref();
+    let Some(candidate) = &request.compare_protocol else {
+        let mut response = run_under(
+            request,
+            baseline,
+            request.ledger_sequence,
+            limits,
+            base,
+            remote,
+            pool,
+        )?;
+        finish(request, limits, &mut response);
+        return Ok(response);
+    };
+    let mut response = run_under(
+        request,
+        baseline,
+        request.ledger_sequence,
+        limits,
+        base.clone(),
+        remote.clone(),
+        pool,
+    )?;
+    let compared = run_under(
+        request,
+        Some(candidate),
+        response.ledger_sequence,
+        limits,
+        base,
+        remote,
+        pool,
+    )?;
+    let network = Network::resolve(
+        request.network.as_deref(),
+        request.network_passphrase.as_deref(),
+    )
+    .map_err(SimulationError::InvalidRequest)?;
+    match diff::compare(&response, &compared) {
+        Ok(differences) => {
+            response.protocol_comparison = Some(ProtocolComparison {
+                baseline_protocol: protocol::version(&network, baseline),
+                candidate_protocol: protocol::version(&network, Some(candidate)),
+                differences,
+            });
+        }
+        Err(e) => response
+            .logs
+            .push(format!("Failed to compare protocol runs: {e}")),
+    }
+    finish(request, limits, &mut response);
+    Ok(response)
+}
+
+/// Cuts `response` down to the sections `request` asked for and the event
+/// and log ceilings of `limits`.
+fn finish(request: &SimulationRequest, limits: &MemoryLimits, response: &mut SimulationResponse) {
+    if let Some(include) = &request.include {
+        select_sections(response, include);
+    }
+    response.truncated = truncation::truncate(response, limits);
+}
+
+/// Empties the sections of `response` that `include` leaves out, so they
+/// cost nothing to serialize.
+fn select_sections(response: &mut SimulationResponse, include: &[ResponseSection]) {
+    use std::mem::take;
+    let excluded = |section| !include.contains(&section);
+    if excluded(ResponseSection::Events) {
+        take(&mut response.events);
+        take(&mut response.contract_events);
+        take(&mut response.system_events);
+        take(&mut response.diagnostic_events);
+        take(&mut response.categorized_events);
+    }
+    if excluded(ResponseSection::Logs) {
+        take(&mut response.logs);
+        take(&mut response.stack_trace);
+    }
+    if excluded(ResponseSection::Budget) {
+        take(&mut response.budget_usage);
+        take(&mut response.optimization_report);
+        take(&mut response.cost_attribution);
+        take(&mut response.flamegraph);
+    }
+    if excluded(ResponseSection::Footprint) {
+        take(&mut response.resources);
+        take(&mut response.limit_checks);
+    }
+    if excluded(ResponseSection::Fees) {
+        take(&mut response.required_fee);
+        take(&mut response.fee_source);
+        take(&mut response.rent);
+    }
+    if excluded(ResponseSection::Auth) {
+        take(&mut response.auth_payloads);
+        take(&mut response.consumed_nonces);
+        take(&mut response.signature_checks);
+        take(&mut response.auth_warnings);
+        take(&mut response.custom_accounts);
+    }
+    if excluded(ResponseSection::Calls) {
+        take(&mut response.call_tree);
+        take(&mut response.pretty_invocations);
+        take(&mut response.reentrancy);
+    }
+    if excluded(ResponseSection::State) {
+        take(&mut response.result_meta_xdr);
+        take(&mut response.storage_accesses);
+    }
+    if excluded(ResponseSection::Balances) {
+        take(&mut response.balance_changes);
+        take(&mut response.token_events);
+        take(&mut response.trustlines);
+    }
+    if excluded(ResponseSection::Timeline) {
+        take(&mut response.timeline);
+    }
+}
+
+/// Runs `request` under `config`, at `ledger_sequence` if given, stepped
+/// and with call costs if it asks to be.
+fn run_under(
+    request: &SimulationRequest,
+    config: Option<&ProtocolConfig>,
+    ledger_sequence: Option<u32>,
+    limits: &MemoryLimits,
+    base: Option<Arc<IndexedSnapshot>>,
+    remote: Option<Arc<CachedSource>>,
+    pool: Option<&HostPool>,
+) -> Result<SimulationResponse, SimulationError> {
+    let mut response = match request.step_instructions.filter(|&step| step > 0) {
+        Some(step) => run_stepped(
+            request,
+            step,
+            config,
+            ledger_sequence,
+            limits,
+            base.clone(),
+            remote.clone(),
+            pool,
+        )?,
+        None => run_once(
+            request,
+            config,
+            ledger_sequence,
+            limits,
+            base.clone(),
+            remote.clone(),
+            pool,
+        )?,
+    };
+    if request.call_costs {
+        attribute_calls(request, config, limits, base, remote, pool, &mut response)?;
+    }
+    custom_account::link(&mut response.custom_accounts, &response.call_tree);
+    Ok(response)
+}
+
+/// Parts of the total CPU cost within which [`attribute_calls`] places each
+/// call boundary.
+const CALL_COST_RESOLUTION: u64 = 1000;
+
+/// Sets the cost of every call in the `call_tree` of `response`, a finished
+/// run of `request`.
+///
+/// Like stepping, this reruns the transaction under CPU limits, here
+/// bisecting for the limit at which each call boundary is first reached
+/// (see [`calls::attribute`]). Boundaries between the same two limits share
+/// their reruns, so a tree of `n` calls takes at most about
+/// `2n * log2(CALL_COST_RESOLUTION)` of them.
+fn attribute_calls(
+    request: &SimulationRequest,
+    config: Option<&ProtocolConfig>,
+    limits: &MemoryLimits,
+    base: Option<Arc<IndexedSnapshot>>,
+    remote: Option<Arc<CachedSource>>,
+    pool: Option<&HostPool>,
+    response: &mut SimulationResponse,
+) -> Result<(), SimulationError> {
+    let (Some(usage), Some(ledger_sequence)) = (&response.budget_usage, response.ledger_sequence)
+    else {
+        response
+            .logs
+            .push("Call costs are only available for successful runs".to_string());
+        return Ok(());
+    };
+    let total = calls::Charged {
+        cpu_instructions: usage.cpu_instructions,
+        memory_bytes: usage.memory_bytes,
+    };
+    let count = calls::boundaries(&response.call_tree);
+    let resolution = (total.cpu_instructions / CALL_COST_RESOLUTION).max(1);
+    // Boundaries reached, and what was charged, when stopped at `limit`.
+    let reach = |limit: u64| -> Result<Option<(usize, calls::Charged)>, SimulationError> {
+        let slice = SimulationRequest {
+            step_instructions: None,
+            stop_at_instructions: Some(limit),
+            call_costs: false,
+            ..request.clone()
+        };
+        let stopped = run_once(
+            &slice,
+            config,
+            Some(ledger_sequence),
+            limits,
+            base.clone(),
+            remote.clone(),
+            pool,
+        )?;
+        Ok(stopped.checkpoints.first().map(|checkpoint| {
+            let charged = calls::Charged {
+                cpu_instructions: checkpoint.instructions,
+                memory_bytes: checkpoint.memory_bytes,
+            };
+            (calls::boundaries(&stopped.call_tree), charged)
+        }))
+    };
+
+    let mut marks = vec![total; count + 1];
+    // Limits `lo` and `hi` with the boundaries reached under each, and
+    // what was charged under `hi`.
+    let mut pending = vec![(0, 0, total.cpu_instructions, count, total)];
+    while let Some((lo, reached_lo, hi, reached_hi, charged_hi)) = pending.pop() {
+        if reached_lo >= reached_hi {
+            continue;
+        }
+        if hi - lo <= resolution {
+            // The charge that broke the limit is not part of the boundary.
+            let mark = calls::Charged {
+                cpu_instructions: charged_hi.cpu_instructions.min(hi),
+                ..charged_hi
+            };
+            marks[reached_lo..reached_hi].fill(mark);
+            continue;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let Some((reached, charged)) = reach(mid)? else {
+            response.logs.push(format!(
+                "Call costs unavailable: the run limited to {mid} instructions did not stop"
+            ));
+            return Ok(());
+        };
+        let reached = reached.clamp(reached_lo, reached_hi);
+        pending.push((lo, reached_lo, mid, reached, charged));
+        pending.push((mid, reached, hi, reached_hi, charged_hi));
+    }
+    calls::attribute(&mut response.call_tree, &marks);
+    Ok(())
+}
+
+/// Most slices a stepped simulation runs. Each slice reruns the
+/// transaction from the start, so the total cost grows with the square of
+/// the number of slices.
+pub const MAX_STEP_SLICES: u64 = 100;
+
+/// Runs `request` in slices of `step` CPU instructions, logging and
+/// collecting a checkpoint at the end of each. Steps that would take more
+/// than [`MAX_STEP_SLICES`] slices are refused.
+///
+/// The host cannot pause and resume, so each slice reruns the transaction
+/// from the start under a CPU limit `step` higher than the last, and
+/// stepping costs grow with the square of the number of slices. Execution
+/// is deterministic, so every rerun retraces the one before it, and a slice
+/// ends exactly when the Wasm runs out of fuel. The response is that of the
+/// first run to finish, or to reach `stop_at_instructions`.
+#[allow(clippy::too_many_arguments)]
+fn run_stepped(
+    request: &SimulationRequest,
+    step: u64,
+    config: Option<&ProtocolConfig>,
+    ledger_sequence: Option<u32>,
+    limits: &MemoryLimits,
+    base: Option<Arc<IndexedSnapshot>>,
+    remote: Option<Arc<CachedSource>>,
+    pool: Option<&HostPool>,
+) -> Result<SimulationResponse, SimulationError> {
+    // Every slice must see the same ledger.
+    let ledger_sequence = match (ledger_sequence, &remote) {
+        (None, Some(remote)) => Some(remote.latest_ledger().map_err(SimulationError::Source)?),
+        (sequence, _) => sequence,
+    };
+    let end = request
+        .stop_at_instructions
+        .unwrap_or(protocol::cpu_limit(config)?);
+    let slices = end.div_ceil(step);
+    if slices > MAX_STEP_SLICES {
+        return Err(SimulationError::InvalidRequest(format!(
+            "step_instructions {step} would take {slices} slices to run {end} instructions; \
+             at most {MAX_STEP_SLICES} are allowed, so step by at least {}",
+            end.div_ceil(MAX_STEP_SLICES)
+        )));
+    }
+    let mut checkpoints = Vec::new();
+    let mut limit = step;
+    loop {
+        let last = limit >= end;
+        let slice = SimulationRequest {
+            step_instructions: None,
+            stop_at_instructions: if last {
+                request.stop_at_instructions
+            } else {
+                Some(limit)
+            },
+            ..request.clone()
+        };
+        let mut response = run_once(
+            &slice,
+            config,
+            ledger_sequence,
+            limits,
+            base.clone(),
+            remote.clone(),
+            pool,
+        )?;
+        for checkpoint in &response.checkpoints {
+            tracing::info!(
+                event = "simulation_checkpoint",
+                instructions = checkpoint.instructions,
+                memory_bytes = checkpoint.memory_bytes,
+                frames = %checkpoint.frames.join(" > "),
+            );
+        }
+        let stopped = !response.checkpoints.is_empty();
+        checkpoints.append(&mut response.checkpoints);
+        if last || !stopped {
+            response.checkpoints = checkpoints;
+            return Ok(response);
+        }
+        limit = limit.saturating_add(step);
+    }
+}
+
+/// What the stages of one run share once the ledger is loaded.
+struct Run<'a> {
+    request: &'a SimulationRequest,
+    config: Option<&'a ProtocolConfig>,
+    network: &'a Network,
+    envelope: &'a TransactionEnvelope,
+    state: &'a LayeredSource,
+    ledger_sequence: u32,
+    /// Lookups the simulator made before execution, ahead of the host's.
+    preloaded_reads: usize,
+    /// The `soft_limit_percent` network limits warn at.
+    warn_percent: u64,
+    source_mapper: Option<SourceMapper>,
+    /// One log line per injected fault.
+    fault_logs: Vec<String>,
+}
+
+/// How the host was told to treat authorization.
+struct AuthSetup {
+    /// Whether the supplied entries are enforced, rather than the host
+    /// recording the ones the transaction needs.
+    enforcing: bool,
+    supplied: usize,
+    consumed_nonces: Vec<authorization::ConsumedNonce>,
+    reused_nonces: Vec<authorization::ConsumedNonce>,
+    enforced_addresses: HashSet<ScAddress>,
+}
+
+/// Runs `request` under `config` once, stopping at `stop_at_instructions`
+/// if given.
+fn run_once(
+    request: &SimulationRequest,
+    config: Option<&ProtocolConfig>,
+    ledger_sequence: Option<u32>,
+    limits: &MemoryLimits,
+    base: Option<Arc<IndexedSnapshot>>,
+    remote: Option<Arc<CachedSource>>,
+    pool: Option<&HostPool>,
+) -> Result<SimulationResponse, SimulationError> {
+    let started = Instant::now();
+    let warn_percent = match request.soft_limit_percent {
+        Some(percent @ 1..=100) => percent,
+        Some(percent) => {
+            return Err(SimulationError::InvalidRequest(format!(
+                "soft_limit_percent must be between 1 and 100, got {percent}"
+            )))
+        }
+        None => limits::WARN_PERCENT,
+    };
+    let network = Network::resolve(
+        request.network.as_deref(),
+        request.network_passphrase.as_deref(),
+    )
+    .map_err(SimulationError::InvalidRequest)?;
+    protocol::check_version(protocol::version(&network, config))?;
+    let envelope = envelope(request)?;
+    let transaction_hash = network
+        .transaction_hash(&envelope)
+        .map(|hash| network::hex(&hash))
+        .map_err(|e| SimulationError::InvalidRequest(format!("Failed to hash transaction: {e}")))?;
+    let _result_meta = decode_result_meta(&request.result_meta_xdr);
+    let parsed = Instant::now();
+
+    let source_mapper = source_mapper(request);
+    let close_time = request.ledger_close_time.unwrap_or_else(ledger_timestamp);
+    let (ledger, _snapshot_reservation) = load_ledger(request, limits, close_time)?;
+    let loaded_entries_count = ledger.len();
+    let loaded_entry_types = ledger
+        .type_counts()
+        .into_iter()
+        .map(|(name, count)| format!("{name}: {count}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let local = SnapshotLedgerSource::new(ledger);
+    let ledger_sequence = match (ledger_sequence, &remote) {
+        (Some(sequence), _) => sequence,
+        (None, Some(remote)) => remote.latest_ledger().map_err(SimulationError::Source)?,
+        (None, None) => local
+            .max_last_modified()
+            .max(base.as_ref().and_then(|base| base.ledger_sequence()))
+            .unwrap_or(0),
+    };
+    let mut state = LayeredSource::new(local, remote, ledger_sequence).with_base(base);
+    let injected_faults = fault::inject(&mut state, &request.fault_injection)?;
+    let state = Rc::new(state);
+    state.prefetch(&declared_footprint(&envelope));
+    // Builder mode: fail early, and by name, if the contract is not there.
+    let resolved_contract = match (&request.invoke, trustline::invoked_contract(&envelope)) {
+        (Some(_), Some(args)) => Some(invoke::resolve(
+            &args.contract_address,
+            &state,
+            request.wasm_path.is_some(),
+        )?),
+        _ => None,
+    };
+    let source_account =
+        authorization::SourceAccount::from(&authorization::transaction_source(&envelope));
+    let signature_checks = multisig::evaluate(&envelope, &network, state.as_ref())
+        .map_err(|e| SimulationError::InvalidRequest(format!("Failed to hash transaction: {e}")))?;
+
+    let run = Run {
+        request,
+        config,
+        network: &network,
+        envelope: &envelope,
+        state: &state,
+        ledger_sequence,
+        // Lookups made so far are the simulator's own, not the host's.
+        preloaded_reads: state.read_origins().len(),
+        warn_percent,
+        source_mapper,
+        fault_logs: injected_faults
+            .iter()
+            .map(|f| format!("Injected fault: {f}"))
+            .collect(),
+    };
+    let loaded = Instant::now();
+
+    // Initialize Host
+    let sim_host = SimHost::with_storage_and_budget(
+        Storage::with_recording_footprint(state.clone()),
+        host_budget(request, config)?,
+        request.resource_calibration.clone(),
+        Some(limits.max_host_memory_bytes),
+    );
+    let host = &sim_host.inner;
+    let ledger = protocol::ledger_info(config, ledger_info(&network, ledger_sequence, close_time));
+    let protocol_version = ledger.protocol_version;
+    host.set_ledger_info(ledger).map_err(|e| {
+        SimulationError::InvalidRequest(format!(
+            "Failed to set ledger info for protocol {protocol_version}: {e:?}"
+        ))
+    })?;
+    let (auth, custom_accounts) = configure_auth(&run, host, protocol_version)?;
+
+    validate_modules(request, &envelope, protocol_version)?;
+    if let Some(path) = &request.wasm_path {
+        upload_local_wasm(host, path, protocol_version)?;
+    }
+
+    let operations = envelope_operations(&envelope);
+    let pretty_invocations = pretty_invocations(operations, &state);
+
+    // Custom cost parameters change the wasmi fuel costs modules are
+    // compiled with, so those runs compile their own.
+    let custom_costs = config.is_some_and(|config| {
+        config.cpu_cost_params.is_some() || config.memory_cost_params.is_some()
+    });
+    let mut warm = pool
+        .filter(|_| !custom_costs)
+        .and_then(|pool| pool.checkout(protocol_version));
+    let mut cold_start = None;
+    if let Some(warm) = &mut warm {
+        warm.retain_live(|hash| state.peek_code(hash).ok().flatten(), ledger_sequence);
+        if let Err(e) = host.set_module_cache(warm.cache()) {
+            tracing::warn!(
+                event = "module_cache_install_failed",
+                error = ?e,
+                "Failed to install module cache; running cold"
+            );
+            cold_start = Some(format!(
+                "Module cache: failed to install ({e:?}); contracts were compiled cold"
+            ));
+        }
+    }
+
+    // Wrap the operation execution in panic protection
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        execute_operations(&sim_host, operations, &pretty_invocations)
+    }));
+    let executed = Instant::now();
+
+    let outcome = match &result {
+        Ok(Ok((_, return_values))) => Some(replay::Outcome::Succeeded(return_values.clone())),
+        Ok(Err(host_error)) => Some(replay::Outcome::Failed(host_error.error)),
+        Err(_) => None,
+    };
+    let recorded_events: Option<Arc<[HostEvent]>> =
+        host.get_events().ok().map(|Events(events)| events.into());
+    let host_events = recorded_events.as_deref().unwrap_or_default();
+    if let (Some(pool), Some(mut warm)) = (pool, warm) {
+        warm.learn(
+            &state.reads(),
+            |hash| state.peek_code(hash).ok().flatten(),
+            ledger_sequence,
+        );
+        pool.checkin(warm);
+    }
+
+    // Budget and Reporting
+    let budget = host.budget_cloned();
+    let invoked = trustline::invoked_contract(&envelope);
+    let sac_admin =
+        invoked.and_then(|args| trustline::sac_admin(&args.contract_address, state.as_ref()));
+    let sac_function = invoked
+        .filter(|_| sac_admin.is_some())
+        .map(|args| args.function_name.0.to_utf8_string_lossy());
+    let trustlines = trustline::trustlines(&state.reads(), state.as_ref(), sac_function.as_deref());
+
+    let source_account_log = format!("Source Account: {source_account}");
+    let mut response = SimulationResponse {
+        transaction_hash: Some(transaction_hash),
+        cache_stats: state.cache_stats(),
+        signature_checks,
+        source_account: Some(source_account),
+        trustlines,
+        pretty_invocations,
+        custom_accounts,
+        sac_admin,
+        ..empty_response()
+    };
+    match result {
+        Ok(Ok((exec_logs, _))) => {
+            let cpu_insns = budget.get_cpu_insns_consumed().unwrap_or(0);
+            let mem_bytes = budget.get_mem_bytes_consumed().unwrap_or(0);
+            let mut logs = vec![
+                format!("Host Initialized with Budget: {budget:?}"),
+                source_account_log,
+                format!("Loaded {loaded_entries_count} Ledger Entries ({loaded_entry_types})"),
+                format!("Ledger Sequence: {ledger_sequence}"),
+                format!("Captured {} diagnostic events", host_events.len()),
+                format!("CPU Instructions Used: {cpu_insns}"),
+                format!("Memory Bytes Used: {mem_bytes}"),
+            ];
+            logs.extend(exec_logs);
+            record_success(
+                &mut response,
+                &run,
+                host,
+                &auth,
+                recorded_events.as_ref(),
+                resolved_contract.as_ref(),
+                logs,
+            );
+        }
+        Ok(Err(host_error)) => record_host_error(
+            &mut response,
+            &run,
+            &host_error,
+            &auth,
+            recorded_events.as_ref(),
+            &budget,
+        ),
+        Err(panic_info) => record_panic(&mut response, panic_info.as_ref()),
+    }
+
+    // Only a mocked fee check turns a successful execution into an error.
+    let outcome = outcome.map(|outcome| match outcome {
+        replay::Outcome::Succeeded(_) if response.status != "success" => {
+            replay::Outcome::InsufficientFee
+        }
+        outcome => outcome,
+    });
+    record_calls(&mut response, host_events, &state);
+    if let Some(outcome) = outcome {
+        let enforced = auth.enforcing.then_some(auth.enforced_addresses);
+        record_outcome(
+            &mut response,
+            &run,
+            sim_host.inner,
+            enforced,
+            &outcome,
+            host_events,
+            &budget,
+        )?;
+    }
+    // Writes only reach the ledger when the transaction succeeds.
+    let writes = match response.status.as_str() {
+        "success" => response.storage_accesses.as_slice(),
+        _ => &[],
+    };
+    response.fee_source = Some(fee_source::fee_source(
+        &envelope,
+        |key| state.peek(key).ok().flatten(),
+        network.preset.base_reserve,
+        response.rent.as_ref().map(|rent| rent.total_fee),
+    ));
+    if let Some(problem) = response
+        .fee_source
+        .as_ref()
+        .and_then(|f| f.problem.as_ref())
+    {
+        response.logs.push(format!("Fee source: {problem}"));
+    }
+    response.logs.extend(cold_start);
+    response.timeline = timeline::timeline(operations, host_events, writes);
+    if response.status != "success" || request.record_reads {
+        response.read_entries = bundle::read_entries(&state);
+    }
+    response.ledger_sequence = Some(ledger_sequence);
+    response.ledger_close_time = Some(close_time);
+    response.timings = Some(Timings {
+        parse_ms: millis(parsed - started),
+        snapshot_ms: millis(loaded - parsed),
+        execution_ms: millis(executed - loaded),
+        post_processing_ms: millis(executed.elapsed()),
+    });
+
+    Ok(response)
+}
+
+/// A source mapper for the request's `contract_wasm`, if it has debug
+/// symbols.
+fn source_mapper(request: &SimulationRequest) -> Option<SourceMapper> {
+    let wasm_base64 = request.contract_wasm.as_ref()?;
+    match base64::engine::general_purpose::STANDARD.decode(wasm_base64) {
+        Ok(wasm_bytes) => {
+            let mapper = SourceMapper::new(wasm_bytes);
+            if mapper.has_debug_symbols() {
+                eprintln!("Debug symbols found in WASM");
+                Some(mapper)
+            } else {
+                eprintln!("No debug symbols found in WASM");
+                None
+            }
+        }
+        Err(e) => {
+            eprintln!("Failed to decode WASM base64: {e}");
+            None
+        }
+    }
+}
+
+/// Decodes the request's `ledger_entries`, with its restore preamble and
+/// mock oracles applied, reserving the decoded size up front.
+fn load_ledger(
+    request: &SimulationRequest,
+    limits: &MemoryLimits,
+    close_time: u64,
+) -> Result<(LedgerSnapshot, Option<memory::MemoryReservation<'static>>), SimulationError> {
+    let reservation = match &request.ledger_entries {
+        Some(entries) => Some(memory::reserve_snapshot(
+            memory::global_accountant(),
+            limits,
+            entries,
+        )?),
+        None => None,
+    };
+    let mut ledger = match &request.ledger_entries {
+        Some(entries) => LedgerSnapshot::from_base64_map(entries).map_err(|e| match e {
+            SnapshotError::XdrLimitsExceeded(msg) => SimulationError::XdrLimitsExceeded(msg),
+            e => SimulationError::InvalidRequest(format!("Failed to load ledger snapshot: {e}")),
+        })?,
+        None => LedgerSnapshot::new(),
+    };
+    if let Some(preamble) = &request.restore_preamble {
+        apply_restore_preamble(&mut ledger, preamble);
+    }
+    oracle::install(&mut ledger, &request.mock_oracles, close_time)?;
+    Ok((ledger, reservation))
+}
+
+/// The budget the host runs under: the protocol's, cut short by
+/// `stop_at_instructions` and by injected CPU and memory limits.
+fn host_budget(
+    request: &SimulationRequest,
+    config: Option<&ProtocolConfig>,
+) -> Result<Budget, SimulationError> {
+    let cpu_limit = request
+        .stop_at_instructions
+        .into_iter()
+        .chain(request.fault_injection.cpu_limit)
+        .min();
+    match (cpu_limit, request.fault_injection.memory_limit) {
+        (None, None) => protocol::budget(config),
+        (cpu_limit, memory_limit) => protocol::budget_with_limits(config, cpu_limit, memory_limit),
+    }
+}
+
+/// Points the host at the transaction's invoker and hands it the supplied
+/// authorization entries, or has it record them when none are supplied.
+fn configure_auth(
+    run: &Run,
+    host: &Host,
+    protocol_version: u32,
+) -> Result<(AuthSetup, Vec<custom_account::CustomAccountAuth>), SimulationError> {
+    let supplied_auth = match &run.request.auth_entries {
+        Some(entries) => decode_auth_entries(entries)?,
+        None => authorization::envelope_entries(run.envelope),
+    };
+    let custom_accounts =
+        custom_account::accounts(&supplied_auth, run.network.id(), protocol_version);
+    let invoker = authorization::invoker(run.envelope);
+    let auth = AuthSetup {
+        enforcing: !supplied_auth.is_empty(),
+        supplied: supplied_auth.len(),
+        consumed_nonces: authorization::consumed_nonces(&supplied_auth),
+        reused_nonces: authorization::reused_nonces(&supplied_auth, run.state, run.ledger_sequence),
+        enforced_addresses: authorization::credential_addresses(&supplied_auth, &invoker),
+    };
+    host.set_source_account(invoker)
+        .and_then(|()| {
+            if auth.enforcing {
+                host.set_authorization_entries(supplied_auth)
+            } else {
+                host.switch_to_recording_auth(true)
+            }
+        })
+        .map_err(|e| {
+            SimulationError::InvalidRequest(format!("Failed to configure authorization: {e:?}"))
+        })?;
+    Ok((auth, custom_accounts))
+}
+
+/// Rejects modules the host would refuse before running anything, with
+/// the reasons the host itself does not give.
+fn validate_modules(
+    request: &SimulationRequest,
+    envelope: &TransactionEnvelope,
+    protocol_version: u32,
+) -> Result<(), SimulationError> {
+    if let Some(Ok(wasm_bytes)) = request
+        .contract_wasm
+        .as_ref()
+        .map(|wasm| base64::engine::general_purpose::STANDARD.decode(wasm))
+    {
+        validate_wasm("contract_wasm", &wasm_bytes, protocol_version)?;
+    }
+    for (index, operation) in envelope_operations(envelope).iter().enumerate() {
+        if let OperationBody::InvokeHostFunction(InvokeHostFunctionOp {
+            host_function: HostFunction::UploadContractWasm(code),
+            ..
+        }) = &operation.body
+        {
+            validate_wasm(&format!("operation {index}"), code, protocol_version)?;
+        }
+    }
+    Ok(())
+}
+
+/// Uploads the module at `path` ahead of the transaction.
+fn upload_local_wasm(
+    host: &Host,
+    path: &str,
+    protocol_version: u32,
+) -> Result<(), SimulationError> {
+    let wasm_bytes = crate::wasm::load_wasm_from_path(path)
+        .map_err(|e| SimulationError::InvalidRequest(format!("Local WASM loading failed: {e}")))?;
+    validate_wasm(path, &wasm_bytes, protocol_version)?;
+    let code = wasm_bytes.try_into().map_err(|e| {
+        SimulationError::InvalidRequest(format!("Local WASM is not valid XDR bytes: {e}"))
+    })?;
+    let hash = host
+        .invoke_function(HostFunction::UploadContractWasm(code))
+        .map_err(|e| {
+            SimulationError::InvalidRequest(format!("Host failed to upload local WASM: {e:?}"))
+        })?;
+    eprintln!("Successfully loaded local WASM. Hash: {hash:?}");
+    Ok(())
+}
+
+/// The host functions of `operations`, as calls with named arguments where
+/// the contract's spec names them.
+fn pretty_invocations(operations: &[Operation], state: &LayeredSource) -> Vec<String> {
+    operations
+        .iter()
+        .filter_map(|op| match &op.body {
+            OperationBody::InvokeHostFunction(invoke_op) => Some(&invoke_op.host_function),
+            _ => None,
+        })
+        .map(|function| {
+            let names = match function {
+                HostFunction::InvokeContract(args) => pretty::input_names(args, state),
+                _ => None,
+            };
+            pretty::host_function(function, names.as_deref())
+        })
+        .collect()
+}
+
+/// Reports an execution the host completed: its events, budget and
+/// authorizations, failing it when a mocked fee check says the declared
+/// fee falls short. `logs` come first.
+fn record_success(
+    response: &mut SimulationResponse,
+    run: &Run,
+    host: &Host,
+    auth: &AuthSetup,
+    events: Option<&Arc<[HostEvent]>>,
+    resolved_contract: Option<&invoke::ResolvedContract>,
+    mut logs: Vec<String>,
+) {
+    let captured = capture_events(events);
+    let budget = host.budget_cloned();
+    let cpu_insns = budget.get_cpu_insns_consumed().unwrap_or(0);
+    let mem_bytes = budget.get_mem_bytes_consumed().unwrap_or(0);
+    let operations_count = envelope_operations(run.envelope).len();
+    let budget_usage = BudgetUsage {
+        cpu_instructions: cpu_insns,
+        memory_bytes: mem_bytes,
+        operations_count,
+        cpu_limit: CPU_LIMIT,
+        memory_limit: MEMORY_LIMIT,
+        cpu_usage_percent: (cpu_insns as f64 / CPU_LIMIT as f64) * 100.0,
+        memory_usage_percent: (mem_bytes as f64 / MEMORY_LIMIT as f64) * 100.0,
+    };
+    if run.request.enable_optimization_advisor {
+        let metrics = BudgetMetrics {
+            cpu_instructions: budget_usage.cpu_instructions,
+            memory_bytes: budget_usage.memory_bytes,
+            total_operations: budget_usage.operations_count,
+        };
+        response.optimization_report = Some(GasOptimizationAdvisor::new().analyze(&metrics));
+    }
+    if run.request.profile.unwrap_or(false) {
+        response.flamegraph = render_flamegraph(&budget);
+    }
+    response.budget_usage = Some(budget_usage);
+
+    if let Some(stats) = &response.cache_stats {
+        logs.push(format!(
+            "Entry cache: {} hits, {} misses",
+            stats.hits, stats.misses
+        ));
+    }
+    if auth.enforcing {
+        logs.push(format!(
+            "Authorization: enforced {} supplied entries",
+            auth.supplied
+        ));
+        logs.extend(auth.consumed_nonces.iter().map(|n| format!("Consumed {n}")));
+        response.consumed_nonces = auth.consumed_nonces.clone();
+    } else {
+        response.auth_payloads = host
+            .get_recorded_auth_payloads()
+            .map_err(|e| format!("{e:?}"))
+            .and_then(|recorded| {
+                authorization::signature_payloads(
+                    recorded,
+                    run.network.id(),
+                    run.ledger_sequence
+                        .saturating_add(authorization::SIGNATURE_EXPIRATION_LEDGERS),
+                )
+                .map_err(|e| e.to_string())
+            })
+            .unwrap_or_else(|e| {
+                logs.push(format!("Failed to build auth payloads: {e}"));
+                vec![]
+            });
+    }
+    logs.extend(run.fault_logs.iter().cloned());
+    if let Some(resolved) = resolved_contract {
+        logs.push(format!("Resolved contract: {resolved}"));
+    }
+    if let Some(admin) = &response.sac_admin {
+        logs.push(format!("SAC Admin: {admin}"));
+    }
+    logs.extend(
+        response
+            .signature_checks
+            .iter()
+            .filter(|check| !check.satisfied)
+            .map(|check| {
+                format!(
+                    "Signatures for {} ({:?} threshold) short by {} weight",
+                    check.account, check.threshold_level, check.missing_weight
+                )
+            }),
+    );
+
+    let mut status = "success".to_string();
+    let mut error = None;
+    let required_fee = mocked_required_fee_stroops(
+        run.request,
+        run.config,
+        run.network,
+        operations_count,
+        cpu_insns,
+        mem_bytes,
+    );
+    if let Some(required_fee) = required_fee {
+        let declared_fee = transaction_fee_stroops(run.envelope);
+        logs.push(format!(
+            "Mock fee check: declared={declared_fee} required={required_fee}"
+        ));
+        if declared_fee < required_fee {
+            status = "error".to_string();
+            error = Some(format!(
+                "insufficient fee (mocked): declared {declared_fee} stroops, required {required_fee} stroops"
+            ));
+        }
+    }
+    // An insufficient fee fails the transaction, events and all.
+    if error.is_none() {
+        response.contract_events = captured.contract_events;
+        response.system_events = captured.system_events;
+    }
+
+    response.status = status;
+    response.error = error;
+    response.events = captured.events;
+    response.diagnostic_events = captured.diagnostic_events;
+    response.categorized_events = captured.categorized_events;
+    response.logs = logs;
+    // If a WASM with debug symbols was provided, expose the first
+    // mappable source location so callers can correlate failures.
+    response.source_location = run
+        .source_mapper
+        .as_ref()
+        .and_then(|m| m.map_wasm_offset_to_source(0))
+        .and_then(|loc| serde_json::to_string(&loc).ok());
+    response.required_fee = required_fee;
+}
+
+/// Reports an execution the host failed (e.g. a contract trap or a
+/// validation failure), naming the cause as precisely as the run allows.
+fn record_host_error(
+    response: &mut SimulationResponse,
+    run: &Run,
+    host_error: &HostError,
+    auth: &AuthSetup,
+    events: Option<&Arc<[HostEvent]>>,
+    budget: &Budget,
+) {
+    let error_debug = format!("{host_error:?}");
+    let wasm_trace = WasmStackTrace::from_host_error(&error_debug);
+    let captured = capture_events(events);
+
+    let details = match find_user_panic_point(&captured.diagnostic_events) {
+        Some(point) => format!(
+            "Contract execution failed with host error: {error_debug}. Panic point: {point}"
+        ),
+        None => format!("Contract execution failed with host error: {error_debug}"),
+    };
+    let source_errors = run.state.errors();
+    let (error_type, message) =
+        classify_host_error(response, run, host_error, auth, &source_errors);
+    let structured_error = StructuredError {
+        error_type: error_type.to_string(),
+        message,
+        details: Some(details),
+    };
+
+    let wasm_offset = extract_wasm_offset(&error_debug);
+    response.source_location = wasm_offset
+        .zip(run.source_mapper.as_ref())
+        .and_then(|(offset, mapper)| mapper.map_wasm_offset_to_source(offset))
+        .and_then(|loc| serde_json::to_string(&loc).ok());
+
+    let mut logs = vec![format!("Stack trace:\n{}", wasm_trace.display())];
+    logs.extend(run.fault_logs.iter().cloned());
+    logs.extend(
+        source_errors
+            .iter()
+            .map(|e| format!("State fetch failed: {e}")),
+    );
+    let host_events = events.map_or(&[][..], |events| &events[..]);
+
+    response.error = Some(
+        serde_json::to_string(&structured_error).unwrap_or_else(|e| {
+            eprintln!("Failed to serialize structured error: {e}");
+            format!("Internal error during error serialization: {e}")
+        }),
+    );
+    // A failed transaction's events stay diagnostic.
+    response.events = captured.events;
+    response.diagnostic_events = captured.diagnostic_events;
+    response.categorized_events = captured.categorized_events;
+    response.logs = logs;
+    response.stack_trace = Some(wasm_trace);
+    response.wasm_offset = wasm_offset;
+    response.checkpoints = stop_checkpoint(run.request, host_error, budget, host_events)
+        .into_iter()
+        .collect();
+}
+
+/// Names the cause of `host_error`: its error type and message.
+fn classify_host_error(
+    response: &SimulationResponse,
+    run: &Run,
+    host_error: &HostError,
+    auth: &AuthSetup,
+    source_errors: &[String],
+) -> (&'static str, String) {
+    let trustline_problems: Vec<&str> = response
+        .trustlines
+        .iter()
+        .filter_map(|t| t.problem.as_deref())
+        .collect();
+    let absent_keys = run.state.absent();
+    // A backend that could not be reached surfaces in the host as a
+    // storage error too; report it ahead of anything it may have
+    // caused, and keep it apart from entries that do not exist.
+    if !source_errors.is_empty() {
+        ("StateSourceUnavailable", source_errors.join("; "))
+    } else if !auth.reused_nonces.is_empty() {
+        // A replayed nonce makes the host fail with a generic auth
+        // error; name the nonce instead.
+        let reused: Vec<String> = auth.reused_nonces.iter().map(|n| n.to_string()).collect();
+        (
+            "NonceAlreadyUsed",
+            format!("{} already used", reused.join(", ")),
+        )
+    } else if !trustline_problems.is_empty() {
+        // The SAC reports these as a generic contract error.
+        ("TrustlineNotAuthorized", trustline_problems.join("; "))
+    } else if is_missing_entry(host_error) && !absent_keys.is_empty() {
+        let keys: Vec<String> = absent_keys
+            .iter()
+            .filter_map(|key| key.to_xdr_base64(Limits::none()).ok())
+            .collect();
+        (
+            "LedgerEntryNotFound",
+            format!("ledger entries not found: {}", keys.join(", ")),
+        )
+    } else {
+        ("HostError", decode_error(&format!("{host_error:?}")))
+    }
+}
+
+/// Where a stepped run stopped: set when the host ran out of CPU under
+/// `stop_at_instructions`, as opposed to out of memory.
+fn stop_checkpoint(
+    request: &SimulationRequest,
+    host_error: &HostError,
+    budget: &Budget,
+    host_events: &[HostEvent],
+) -> Option<Checkpoint> {
+    let stopped = request.stop_at_instructions.is_some()
+        && host_error.error.is_type(ScErrorType::Budget)
+        && host_error.error.is_code(ScErrorCode::ExceededLimit)
+        && budget.get_mem_bytes_remaining().is_ok_and(|left| left > 0);
+    stopped.then(|| Checkpoint {
+        instructions: budget.get_cpu_insns_consumed().unwrap_or(0),
+        memory_bytes: budget.get_mem_bytes_consumed().unwrap_or(0),
+        frames: calls::open_frames(&calls::call_tree(host_events)),
+    })
+}
+
+/// Reports a panic out of the host.
+fn record_panic(response: &mut SimulationResponse, panic_info: &(dyn std::any::Any + Send)) {
+    let panic_msg = if let Some(s) = panic_info.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = panic_info.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "Unknown panic".to_string()
+    };
+    response.error = Some(format!("Simulator panicked: {panic_msg}"));
+    response.logs = vec![format!("PANIC: {panic_msg}")];
+    response.stack_trace = Some(WasmStackTrace::from_panic(&panic_msg));
+    response.custom_accounts = vec![];
+}
+
+/// Adds the call tree of `host_events`, with contract errors named from
+/// the contracts' specs, and any reentrancy in it.
+fn record_calls(
+    response: &mut SimulationResponse,
+    host_events: &[HostEvent],
+    state: &LayeredSource,
+) {
+    response.call_tree = calls::call_tree(host_events);
+    let mut specs = std::collections::HashMap::new();
+    calls::name_errors(&mut response.call_tree, &mut |contract, code| {
+        let spec = specs.entry(contract.to_string()).or_insert_with(|| {
+            let address = contract.parse().ok()?;
+            pretty::contract_spec(&address, state)
+        });
+        let case = spec
+            .as_ref()?
+            .error_enums
+            .iter()
+            .flat_map(|error_enum| &error_enum.cases)
+            .find(|case| case.value == code)?;
+        Some(case.name.clone())
+    });
+    response.reentrancy = calls::reentrancy(&response.call_tree);
+    for reentry in &response.reentrancy {
+        response.logs.push(format!(
+            "Reentrancy: {} re-entered via {}",
+            reentry.contract,
+            reentry.path.join(" → ")
+        ));
+    }
+}
+
+/// Reports what the transaction did to the ledger and what it costs, once
+/// its `outcome` is known, finishing `host`. `enforced` holds the addresses
+/// of the supplied authorization entries, if they were enforced.
+fn record_outcome(
+    response: &mut SimulationResponse,
+    run: &Run,
+    host: Host,
+    enforced: Option<HashSet<ScAddress>>,
+    outcome: &replay::Outcome,
+    host_events: &[HostEvent],
+    budget: &Budget,
+) -> Result<(), SimulationError> {
+    let enforcing = enforced.is_some();
+    // Changes only persist when the transaction succeeds.
+    let authorized = match (outcome, enforced) {
+        (replay::Outcome::Succeeded(_), Some(enforced)) => Some(enforced),
+        (replay::Outcome::Succeeded(_), None) => {
+            let invoker = authorization::invoker(run.envelope);
+            match host.get_recorded_auth_payloads() {
+                Ok(recorded) => Some(authorization::recorded_addresses(&recorded, &invoker)),
+                Err(e) => {
+                    response
+                        .logs
+                        .push(format!("Failed to read recorded authorizations: {e:?}"));
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+    if let replay::Outcome::Succeeded(_) = outcome {
+        response.balance_changes = balances::changes(host_events);
+        response.token_events = balances::token_events(host_events);
+    }
+    // Recorded authorizations go with the host.
+    let recorded_auth = match (run.request.assemble, enforcing) {
+        (true, false) => host.get_recorded_auth_payloads().ok(),
+        _ => None,
+    };
+    let state = run.state;
+    let finished = host.try_finish().map_err(|e| format!("{e:?}"));
+    let changes = match (outcome, &finished) {
+        (replay::Outcome::Succeeded(_), finished) => finished
+            .as_ref()
+            .map_err(Clone::clone)
+            .and_then(|(storage, _)| {
+                replay::ledger_changes(
+                    storage,
+                    |key| state.peek(key).ok().flatten(),
+                    run.ledger_sequence,
+                )
+            }),
+        _ => Ok(Default::default()),
+    };
+    match &finished {
+        Ok((storage, _)) => {
+            let reads = state.read_origins().split_off(run.preloaded_reads);
+            match storage_log::storage_accesses(storage, &reads, |key| {
+                state.peek(key).ok().flatten()
+            }) {
+                Ok(accesses) => response.storage_accesses = accesses,
+                Err(e) => response
+                    .logs
+                    .push(format!("Failed to log storage accesses: {e}")),
+            }
+        }
+        Err(e) => response
+            .logs
+            .push(format!("Failed to finish the host: {e}")),
+    }
+    if let Some(authorized) = &authorized {
+        lint_auth(response, host_events, authorized);
+    }
+    if let (false, Ok((storage, _))) = (matches!(outcome, replay::Outcome::Failed(_)), &finished) {
+        check_resources(
+            response,
+            run,
+            storage,
+            outcome,
+            host_events,
+            budget,
+            recorded_auth,
+        )?;
+    }
+    if let (true, Ok((storage, _))) = (run.request.profile.unwrap_or(false), &finished) {
+        match attribution::attribute(
+            storage,
+            |key| state.peek(key).ok().flatten(),
+            host_events,
+            budget,
+            || protocol::budget(run.config).unwrap_or_default(),
+            run.ledger_sequence,
+        ) {
+            Ok(attribution) => response.cost_attribution = Some(attribution),
+            Err(e) => response
+                .logs
+                .push(format!("Failed to attribute costs: {e}")),
+        }
+    }
+    match changes {
+        Ok(changes) => {
+            let result =
+                replay::transaction_result(run.envelope, run.network, outcome, host_events);
+            let meta = replay::transaction_meta(run.envelope, outcome, host_events, changes);
+            response.result_xdr = result
+                .and_then(|result| result.to_xdr_base64(Limits::none()))
+                .ok();
+            response.result_meta_xdr = meta
+                .and_then(|meta| meta.to_xdr_base64(Limits::none()))
+                .ok();
+        }
+        Err(e) => response
+            .logs
+            .push(format!("Failed to collect ledger changes: {e}")),
+    }
+    Ok(())
+}
+
+/// Warns of writes `host_events` show were made without the authorization
+/// of the addresses they belong to.
+fn lint_auth(
+    response: &mut SimulationResponse,
+    host_events: &[HostEvent],
+    authorized: &HashSet<ScAddress>,
+) {
+    let keys = response
+        .storage_accesses
+        .iter()
+        .filter(|access| access.operation != "read")
+        .filter_map(|access| {
+            let key = snapshot::decode_ledger_key(&access.key).ok()?;
+            Some((key, access.operation))
+        })
+        .collect::<Vec<_>>();
+    let writes = keys
+        .iter()
+        .map(|(key, operation)| (key, *operation))
+        .collect::<Vec<_>>();
+    response.auth_warnings = auth_lint::missing_auth(host_events, &writes, authorized);
+    for warning in &response.auth_warnings {
+        response.logs.push(format!(
+            "Possible missing authorization: {} by {} without {}'s authorization",
+            warning.change, warning.contract, warning.address
+        ));
+    }
+}
+
+/// Meters the resources and rent of a transaction that did not fail,
+/// checks them against the network's limits, and assembles it if asked.
+fn check_resources(
+    response: &mut SimulationResponse,
+    run: &Run,
+    storage: &Storage,
+    outcome: &replay::Outcome,
+    host_events: &[HostEvent],
+    budget: &Budget,
+    recorded_auth: Option<Vec<RecordedAuthPayload>>,
+) -> Result<(), SimulationError> {
+    let state = run.state;
+    let mut events_bytes = None;
+    match resources::resources(
+        storage,
+        |key| state.peek(key).ok().flatten(),
+        run.ledger_sequence,
+    ) {
+        Ok(resources) => {
+            let (limits, _) = NetworkLimits::from_state(|key| state.peek(key).ok().flatten());
+            let return_values = match outcome {
+                replay::Outcome::Succeeded(values) => values.as_slice(),
+                _ => &[],
+            };
+            match limits::usage(
+                run.envelope,
+                &resources,
+                storage,
+                host_events,
+                return_values,
+                budget,
+            ) {
+                Ok(usage) => {
+                    let checks = limits::check(&limits, &usage, run.warn_percent);
+                    for check in &checks {
+                        response.logs.push(format!(
+                            "Network limit {}: {} is {} of {}{}{}",
+                            check.severity,
+                            check.limit,
+                            check.value,
+                            check.max,
+                            check
+                                .key
+                                .as_ref()
+                                .map_or(String::new(), |key| format!(" (entry {key})")),
+                            check.top_costs(),
+                        ));
+                    }
+                    response.limit_checks = checks;
+                    events_bytes = Some(usage.events_and_return_bytes);
+                }
+                Err(e) => response
+                    .logs
+                    .push(format!("Failed to check network limits: {e}")),
+            }
+            response.resources = Some(resources);
+        }
+        Err(e) => response
+            .logs
+            .push(format!("Failed to meter resources: {e}")),
+    }
+    let (params, from_ledger) = RentParams::from_state(|key| state.peek(key).ok().flatten());
+    match rent::rent(
+        storage,
+        |key| state.peek(key).ok().flatten(),
+        params,
+        from_ledger,
+        run.ledger_sequence,
+    ) {
+        Ok(rent) if !rent.entries.is_empty() => response.rent = Some(rent),
+        Ok(_) => {}
+        Err(e) => response.logs.push(format!("Failed to compute rent: {e}")),
+    }
+    if let (true, Some(resources), Some(events_bytes)) = (
+        run.request.assemble && response.status == "success",
+        &response.resources,
+        events_bytes,
+    ) {
+        let entries = match (recorded_auth, &run.request.auth_entries) {
+            (Some(recorded), _) => authorization::recorded_entries(
+                recorded,
+                run.ledger_sequence
+                    .saturating_add(authorization::SIGNATURE_EXPIRATION_LEDGERS),
+            ),
+            (None, Some(entries)) => decode_auth_entries(entries)?,
+            (None, None) => authorization::envelope_entries(run.envelope),
+        };
+        let metered = assemble::Metered {
+            instructions: budget.get_cpu_insns_consumed().unwrap_or(0),
+            events_bytes,
+            rent_fee: response.rent.as_ref().map_or(0, |rent| rent.total_fee),
+        };
+        let params = FeeParams::from_state(|key| state.peek(key).ok().flatten());
+        match assemble::assemble(
+            run.envelope,
+            resources,
+            &metered,
+            entries,
+            &run.request.resource_padding,
+            params,
+        ) {
+            Ok(assembled) => response.assembled = Some(assembled),
+            Err(e) => response
+                .logs
+                .push(format!("Failed to assemble the transaction: {e}")),
+        }
+    }
+    Ok(())
+}
+
+/// Adds `elapsed`, spent decoding the request before [`run`], to the
+/// response's parse time.
+pub fn add_parse_time(response: &mut SimulationResponse, elapsed: Duration) {
+    if let Some(timings) = &mut response.timings {
+        timings.parse_ms += millis(elapsed);
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Ledger context for the simulated transaction, at `sequence` on `network`
+/// with the network's current TTL settings.
+pub fn ledger_info(network: &Network, sequence: u32, close_time: u64) -> LedgerInfo {
+    LedgerInfo {
+        protocol_version: network.preset.protocol_version,
+        sequence_number: sequence,
+        timestamp: close_time,
+        network_id: network.id(),
+        base_reserve: network.preset.base_reserve,
+        min_temp_entry_ttl: 16,
+        min_persistent_entry_ttl: 120_960,
+        max_entry_ttl: 3_110_400,
+    }
+}
+
+/// Close time of a simulated ledger unless the request sets one: now.
+pub fn ledger_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// The transaction `request` simulates: its `envelope_xdr`, or the one
+/// built from `invoke`.
+pub fn envelope(request: &SimulationRequest) -> Result<TransactionEnvelope, SimulationError> {
+    match &request.invoke {
+        Some(_) if !request.envelope_xdr.is_empty() => Err(SimulationError::InvalidRequest(
+            "envelope_xdr and invoke cannot both be given".to_string(),
+        )),
+        Some(spec) => invoke::envelope(spec),
+        None => decode_envelope(&request.envelope_xdr),
+    }
+}
+
+fn decode_envelope(envelope_xdr: &str) -> Result<TransactionEnvelope, SimulationError> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(envelope_xdr)
+        .map_err(|e| {
+            SimulationError::InvalidRequest(format!("Failed to decode Envelope Base64: {e}"))
+        })?;
+    xdr_limits::read::<TransactionEnvelope>(&bytes).map_err(|e| {
+        if xdr_limits::is_exceeded(&e) {
+            SimulationError::XdrLimitsExceeded(format!("Envelope: {e}"))
+        } else {
+            SimulationError::InvalidRequest(format!("Failed to parse Envelope XDR: {e}"))
+        }
+    })
+}
+
+/// Decodes the request's signed authorization entries.
+fn decode_auth_entries(
+    entries: &[String],
+) -> Result<Vec<SorobanAuthorizationEntry>, SimulationError> {
+    entries
+        .iter()
+        .map(|entry| {
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(entry)
+                .map_err(|e| {
+                    SimulationError::InvalidRequest(format!(
+                        "Failed to decode auth entry Base64: {e}"
+                    ))
+                })?;
+            xdr_limits::read::<SorobanAuthorizationEntry>(&bytes).map_err(|e| {
+                if xdr_limits::is_exceeded(&e) {
+                    SimulationError::XdrLimitsExceeded(format!("Auth entry: {e}"))
+                } else {
+                    SimulationError::InvalidRequest(format!("Failed to parse auth entry XDR: {e}"))
+                }
+            })
+        })
+        .collect()
+}
+
+/// Decodes the optional result meta. Failures only degrade the simulation,
+/// so they are logged rather than returned.
+fn decode_result_meta(result_meta_xdr: &str) -> Option<TransactionResultMeta> {
+    if result_meta_xdr.is_empty() {
+        eprintln!("Warning: ResultMetaXdr is empty. Host storage may be incomplete.");
+        return None;
+    }
+    match base64::engine::general_purpose::STANDARD.decode(result_meta_xdr) {
+        Ok(bytes) if bytes.is_empty() => {
+            eprintln!("Warning: ResultMetaXdr decoded to 0 bytes.");
+            None
+        }
+        Ok(bytes) => match xdr_limits::read::<TransactionResultMeta>(&bytes) {
+            Ok(meta) => Some(meta),
+            Err(e) => {
+                eprintln!(
+                    "Warning: Failed to parse ResultMeta XDR: {e}. Proceeding with empty storage."
+                );
+                None
+            }
+        },
+        Err(e) => {
+            eprintln!(
+                "Warning: Failed to decode ResultMeta Base64: {e}. Proceeding with empty storage."
+            );
+            None
+        }
+    }
+}
+
+/// Merges the `ledger_entries` of a restore preamble into the snapshot.
+/// Malformed entries are skipped with a warning.
+fn apply_restore_preamble(ledger: &mut LedgerSnapshot, preamble: &serde_json::Value) {
+    let Some(map) = preamble.get("ledger_entries").and_then(|e| e.as_object()) else {
+        return;
+    };
+    for (key_xdr, entry_xdr_val) in map {
+        let Some(entry_xdr) = entry_xdr_val.as_str() else {
+            continue;
+        };
+        let decoded = snapshot::decode_ledger_key(key_xdr).and_then(|key| {
+            let entry = snapshot::decode_ledger_entry(entry_xdr)?;
+            Ok((key, entry))
+        });
+        match decoded {
+            Ok((key, entry)) => match ledger.insert_entry(&key, entry) {
+                Ok(()) => eprintln!("[restore_preamble] Injected Ledger Entry: Key={key:?}"),
+                Err(e) => eprintln!("[restore_preamble] Failed to inject entry: {e}"),
+            },
+            Err(e) => eprintln!("[restore_preamble] Skipping entry: {e}"),
+        }
+    }
+}
+
+/// Returns the operations of the (inner) transaction.
+pub fn envelope_operations(envelope: &TransactionEnvelope) -> &[Operation] {
+    match envelope {
+        TransactionEnvelope::Tx(tx_v1) => &tx_v1.tx.operations,
+        TransactionEnvelope::TxV0(tx_v0) => &tx_v0.tx.operations,
+        TransactionEnvelope::TxFeeBump(bump) => match &bump.tx.inner_tx {
+            FeeBumpTransactionInnerTx::Tx(tx_v1) => &tx_v1.tx.operations,
+        },
+    }
+}
+
+/// Fails the request with every rule `wasm`, named `label` in the error,
+/// breaks at `protocol`.
+fn validate_wasm(label: &str, wasm: &[u8], protocol: u32) -> Result<(), SimulationError> {
+    let violations = crate::vm::validate(wasm, protocol);
+    if violations.is_empty() {
+        return Ok(());
+    }
+    let reasons = violations
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ");
+    Err(SimulationError::InvalidRequest(format!(
+        "Contract WASM validation failed for {label}: {reasons}"
+    )))
+}
+
+/// Returns true if the host failed because a ledger entry it needed was
+/// absent.
+fn is_missing_entry(error: &HostError) -> bool {
+    error.error.is_type(ScErrorType::Storage) && error.error.is_code(ScErrorCode::MissingValue)
+}
+
+/// Keys in the footprint of the envelope's `SorobanTransactionData`, if any.
+fn declared_footprint(envelope: &TransactionEnvelope) -> Vec<LedgerKey> {
+    match soroban_data(envelope) {
+        Some(data) => {
+            let footprint = &data.resources.footprint;
+            footprint
+                .read_only
+                .iter()
+                .chain(footprint.read_write.iter())
+                .cloned()
+                .collect()
+        }
+        None => Vec::new(),
+    }
+}
+
+/// The (inner) transaction's `SorobanTransactionData`, if it declares one.
+pub fn soroban_data(envelope: &TransactionEnvelope) -> Option<&SorobanTransactionData> {
+    let ext = match envelope {
+        TransactionEnvelope::Tx(tx_v1) => &tx_v1.tx.ext,
+        TransactionEnvelope::TxV0(_) => return None,
+        TransactionEnvelope::TxFeeBump(bump) => match &bump.tx.inner_tx {
+            FeeBumpTransactionInnerTx::Tx(tx_v1) => &tx_v1.tx.ext,
+        },
+    };
+    match ext {
+        TransactionExt::V1(data) => Some(data),
+        TransactionExt::V0 => None,
+    }
+}
+
+/// `invocations` renders each `InvokeHostFunction` operation, in order, for
+/// the log.
+fn execute_operations(
+    sim_host: &SimHost,
+    operations: &[Operation],
+    invocations: &[String],
+) -> Result<(Vec<String>, Vec<ScVal>), HostError> {
+    let mut logs = Vec::new();
+    let mut return_values = Vec::new();
+    let mut invocations = invocations.iter();
+    for op in operations {
+        match &op.body {
+            OperationBody::InvokeHostFunction(invoke_op) => {
+                match invocations.next() {
+                    Some(invocation) => logs.push(format!("Executing {invocation}")),
+                    None => logs.push("Executing InvokeHostFunction...".to_string()),
+                }
+                let val = sim_host
+                    .inner
+                    .invoke_function(invoke_op.host_function.clone())?;
+                // Abort before the next operation if this one pushed the host
+                // past its memory ceiling.
+                sim_host.check_memory_limit();
+                logs.push(format!("Result: {val:?}"));
+                return_values.push(val);
+            }
+            _ => {
+                logs.push(format!(
+                    "Skipping non-Soroban operation: {:?}",
+                    op.body.name()
+                ));
+            }
+        }
+    }
+    Ok((logs, return_values))
+}
+
+pub fn transaction_fee_stroops(envelope: &TransactionEnvelope) -> u64 {
+    match envelope {
+        TransactionEnvelope::Tx(tx_v1) => u64::from(tx_v1.tx.fee),
+        TransactionEnvelope::TxV0(tx_v0) => u64::from(tx_v0.tx.fee),
+        TransactionEnvelope::TxFeeBump(bump) => bump.tx.fee as u64,
+    }
+}
+
+/// An explicitly named network supplies its base fee when the request sets
+/// no `mock_base_fee`.
+fn mocked_required_fee_stroops(
+    request: &SimulationRequest,
+    config: Option<&ProtocolConfig>,
+    network: &Network,
+    operations_count: usize,
+    cpu_insns: u64,
+    mem_bytes: u64,
+) -> Option<u64> {
+    let mut required_fee = 0u64;
+    let mut enabled = false;
+
+    let network_base_fee = request.network.as_ref().map(|_| network.preset.base_fee);
+    let base_fee = config.and_then(|c| c.base_fee).or(request.mock_base_fee);
+    if let Some(base_fee) = base_fee.or(network_base_fee) {
+        enabled = true;
+        required_fee = required_fee
+            .saturating_add(u64::from(base_fee).saturating_mul(operations_count as u64));
+    }
+
+    if let Some(gas_price) = config.and_then(|c| c.gas_price).or(request.mock_gas_price) {
+        enabled = true;
+        // Keep the unit small enough to be predictable in local replay while still driven by observed usage.
+        let cpu_units = cpu_insns.saturating_add(9_999) / 10_000;
+        let mem_units = mem_bytes.saturating_add(1_023) / 1_024;
+        let resource_units = cpu_units.saturating_add(mem_units).max(1);
+        required_fee = required_fee.saturating_add(gas_price.saturating_mul(resource_units));
+    }
+
+    enabled.then_some(required_fee)
+}
+
+fn render_flamegraph(budget: &Budget) -> Option<String> {
+    // One frame per host cost type under each of the CPU and memory totals.
+    let mut folded_data = String::new();
+    for charge in attribution::cost_types(budget) {
+        folded_data.push_str(&format!(
+            "Total;CPU;{} {}\n",
+            charge.cost_type, charge.cpu_insns
+        ));
+        if charge.mem_bytes > 0 {
+            folded_data.push_str(&format!(
+                "Total;Memory;{} {}\n",
+                charge.cost_type, charge.mem_bytes
+            ));
+        }
+    }
+    if folded_data.is_empty() {
+        return None;
+    }
+    let mut result_vec = Vec::new();
+    let mut options = inferno::flamegraph::Options::default();
+    options.title = "Soroban Resource Consumption".to_string();
+
+    match inferno::flamegraph::from_reader(&mut options, folded_data.as_bytes(), &mut result_vec) {
+        Ok(()) => Some(String::from_utf8_lossy(&result_vec).to_string()),
+        Err(e) => {
+            eprintln!("Failed to generate flamegraph: {e}");
+            None
+        }
+    }
+}
+
+/// Events captured from the host after execution.
+struct CapturedEvents {
+    events: Vec<EventLine>,
+    contract_events: Vec<Arc<DiagnosticEvent>>,
+    system_events: Vec<Arc<DiagnosticEvent>>,
+    diagnostic_events: Vec<Arc<DiagnosticEvent>>,
+    categorized_events: Vec<CategorizedEvent>,
+}
+
+/// Lists `events`, the host's events if they could be retrieved. The lists
+/// share the events, which are only rendered as the response is serialized.
+fn capture_events(events: Option<&Arc<[HostEvent]>>) -> CapturedEvents {
+    match events {
+        Some(events) => {
+            let diagnostic_events = (0..events.len())
+                .map(|index| Arc::new(DiagnosticEvent::new(Arc::clone(events), index)))
+                .collect::<Vec<_>>();
+            // Events of failed calls do not make it into the meta.
+            let of_type = |type_: ContractEventType| {
+                diagnostic_events
+                    .iter()
+                    .filter(|event| {
+                        event.event().event.type_ == type_ && event.in_successful_contract_call()
+                    })
+                    .map(Arc::clone)
+                    .collect::<Vec<_>>()
+            };
+            CapturedEvents {
+                events: diagnostic_events
+                    .iter()
+                    .map(|event| EventLine::Event(Arc::clone(event)))
+                    .collect(),
+                contract_events: of_type(ContractEventType::Contract),
+                system_events: of_type(ContractEventType::System),
+                categorized_events: diagnostic_events
+                    .iter()
+                    .map(|event| CategorizedEvent {
+                        category: category(event.event()),
+                        event: Arc::clone(event),
+                    })
+                    .collect(),
+                diagnostic_events,
+            }
+        }
+        None => CapturedEvents {
+            events: vec![EventLine::Note("Failed to retrieve events")],
+            contract_events: vec![],
+            system_events: vec![],
+            diagnostic_events: vec![],
+            categorized_events: vec![],
+        },
+    }
+}
+
+fn category(e: &HostEvent) -> &'static str {
+    match e.event.type_ {
+        ContractEventType::Contract => "Contract",
+        ContractEventType::System => "System",
+        ContractEventType::Diagnostic => "Diagnostic",
+    }
+}
+
+/// The WASM instruction a budget `tick` diagnostic event reports, when
+/// `event` is one. Only those events are rendered to find it.
+pub fn wasm_instruction(event: &HostEvent) -> Option<String> {
+    let ContractEventBody::V0(body) = &event.event.body;
+    let mentions_tick = |topic: &ScVal| {
+        let bytes: &[u8] = match topic {
+            ScVal::Symbol(symbol) => symbol.0.as_ref(),
+            ScVal::String(string) => string.0.as_ref(),
+            _ => return false,
+        };
+        bytes.windows(4).any(|window| window == b"tick")
+    };
+    if !body.topics.iter().any(mentions_tick) {
+        return None;
+    }
+    let topics = body
+        .topics
+        .iter()
+        .map(|t| format!("{t:?}"))
+        .collect::<Vec<_>>();
+    extract_wasm_instruction(&topics, &format!("{:?}", body.data))
+}
+
+/// Extracts the WASM instruction from a budget `tick` diagnostic event, whose
+/// data carries a string of the form `"Instruction: <op>"`.
+pub fn extract_wasm_instruction(topics: &[String], data: &str) -> Option<String> {
+    if !topics.iter().any(|t| t.contains("tick")) {
+        return None;
+    }
+    let (_, rest) = data.split_once("Instruction: ")?;
+    let instruction = rest.trim_end_matches(['"', ')']).trim();
+    (!instruction.is_empty()).then(|| instruction.to_string())
+}
+
+/// Heuristic to ignore Rust stdlib panic wrappers and find the actual source point
+fn find_user_panic_point(diagnostic_events: &[Arc<DiagnosticEvent>]) -> Option<String> {
+    for event in diagnostic_events {
+        let mut combined_text = format!("{:?}", event.data());
+        for topic in event.topics() {
+            let _ = write!(combined_text, " {topic:?}");
+        }
+
+        if combined_text.contains("panicked")
+            || combined_text.contains("Error")
+            || combined_text.contains("Trap")
+        {
+            // Ignore known Rust stdlib wrappers commonly seen in Backtrace/Diagnostic events
+            if combined_text.contains("core/src/panicking.rs")
+                || combined_text.contains("core::panicking")
+                || combined_text.contains("rust_begin_unwind")
+                || combined_text.contains("std::rt::lang_start")
+                || combined_text.contains("compiler_builtins")
+                || combined_text.contains("rustc_std_workspace")
+            {
+                continue;
+            }
+
+            // Look for common user paths (like src/lib.rs, etc); the first
+            // match wins so deeper frames don't overwrite it.
+            if combined_text.contains(".rs") && !combined_text.contains("soroban-env-host") {
+                return Some(combined_text.replace('"', ""));
+            }
+        }
+    }
+    None
+}
+
+pub fn extract_wasm_offset(error_msg: &str) -> Option<u64> {
+    // Look for patterns like "@ 0x[HEX]" in the error message
+    // Soroban/Wasmi errors often contain stack traces like:
+    // "  0: func[42] @ 0xa3c"
+    for line in error_msg.lines() {
+        if let Some(pos) = line.find("@ 0x") {
+            let hex_part = &line[pos + 4..];
+            let end = hex_part
+                .find(|c: char| !c.is_ascii_hexdigit())
+                .unwrap_or(hex_part.len());
+            if let Ok(offset) = u64::from_str_radix(&hex_part[..end], 16) {
+                return Some(offset);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::xdr::{ContractEvent, ContractEventV0, ExtensionPoint, VecM};
+
+    fn host_event(type_: ContractEventType, failed_call: bool) -> HostEvent {
+        HostEvent {
+            failed_call,
+            event: ContractEvent {
+                ext: ExtensionPoint::V0,
+                contract_id: None,
+                type_,
+                body: ContractEventBody::V0(ContractEventV0 {
+                    topics: VecM::default(),
+                    data: ScVal::Void,
+                }),
+            },
+        }
+    }
+
+    /// `HostEvent.failed_call == true` means the call that emitted the event
+    /// *failed*. `in_successful_contract_call` must therefore be the inverse.
+    #[test]
+    fn test_in_successful_contract_call_is_negation_of_failed_call() {
+        let events: Arc<[HostEvent]> = Arc::new([
+            host_event(ContractEventType::Contract, true),
+            host_event(ContractEventType::Contract, false),
+        ]);
+        let captured = capture_events(Some(&events));
+        assert!(!captured.diagnostic_events[0].in_successful_contract_call());
+        assert!(captured.diagnostic_events[1].in_successful_contract_call());
+        // Only the successful call's event makes it into the meta.
+        assert_eq!(captured.contract_events.len(), 1);
+        assert!(Arc::ptr_eq(
+            &captured.contract_events[0],
+            &captured.diagnostic_events[1]
+        ));
+
+        let json = serde_json::to_value(&captured.diagnostic_events[0]).unwrap();
+        assert_eq!(json["in_successful_contract_call"], false);
+        assert_eq!(json["data"], "Void");
+        assert!(json.get("wasm_instruction").is_none());
+    }
+
+    #[test]
+    fn test_stepping_caps_the_number_of_slices() {
+        let request = |step: u64| {
+            serde_json::from_value::<SimulationRequest>(serde_json::json!({
+                "envelope_xdr": "AAAA",
+                "result_meta_xdr": "",
+                "enable_optimization_advisor": false,
+                "timestamp": "",
+                "step_instructions": step,
+                "stop_at_instructions": 1_000_000,
+            }))
+            .unwrap()
+        };
+        let limits = MemoryLimits::default();
+        match run(&request(1_000), &limits, None, None, None) {
+            Err(SimulationError::InvalidRequest(msg)) => {
+                assert!(msg.contains("1000 slices"), "{msg}");
+                assert!(msg.contains("at least 10000"), "{msg}");
+            }
+            other => panic!("expected InvalidRequest, got {other:?}"),
+        }
+        // Within the cap, stepping gets as far as decoding the envelope.
+        assert!(!matches!(
+            run(&request(10_000), &limits, None, None, None),
+            Err(SimulationError::InvalidRequest(msg)) if msg.contains("slices")
+        ));
+    }
+
+    #[test]
+    fn test_categorized_event_type_labels() {
+        let events: Arc<[HostEvent]> = Arc::new([
+            host_event(ContractEventType::Contract, false),
+            host_event(ContractEventType::System, false),
+            host_event(ContractEventType::Diagnostic, false),
+        ]);
+        let captured = capture_events(Some(&events));
+        let labels = captured
+            .categorized_events
+            .iter()
+            .map(|c| (c.category, c.event.event_type()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            labels,
+            [
+                ("Contract", "contract"),
+                ("System", "system"),
+                ("Diagnostic", "diagnostic")
+            ]
+        );
+        assert_eq!(captured.events[1].to_string(), format!("{:?}", events[1]));
+    }
+}
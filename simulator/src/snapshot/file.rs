@@ -0,0 +1,177 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Snapshot files on disk.
+//!
+//! The format matches the snapshots written by `erst` (and soroban-cli): a
+//! JSON object whose `ledgerEntries` is a list of `[key, entry]` pairs of
+//! base64 XDR. An optional `ledgerSequence` records the ledger the snapshot
+//! was taken at. Snapshots written by soroban-sdk's testutils are read as
+//! well (see [`super::sdk`]).
+
+use super::{sdk, LedgerSnapshot, SnapshotError};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawSnapshotFile {
+    #[serde(default)]
+    ledger_sequence: Option<u32>,
+    #[serde(default)]
+    ledger_entries: Vec<(String, String)>,
+    /// The ledger of a soroban-sdk test snapshot.
+    #[serde(default)]
+    ledger: Option<sdk::LedgerSnapshot>,
+    /// The entries of a bare soroban-sdk ledger snapshot, whose keys are
+    /// snake case.
+    #[serde(default, rename = "ledger_entries")]
+    sdk_ledger_entries: Option<Vec<sdk::Entry>>,
+    #[serde(default, rename = "sequence_number")]
+    sdk_sequence_number: Option<u32>,
+}
+
+/// A validated snapshot file.
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotFile {
+    /// Ledger sequence of the snapshot: the recorded `ledgerSequence`, or the
+    /// highest `last_modified_ledger_seq` among its entries.
+    pub ledger_sequence: Option<u32>,
+    /// Base64 `LedgerKey` to base64 `LedgerEntry`, as used by `SimulationRequest`.
+    pub entries: HashMap<String, String>,
+}
+
+impl SnapshotFile {
+    /// Parses and validates snapshot JSON. Every entry is decoded once so a
+    /// corrupt file is rejected as a whole.
+    pub fn from_json(json: &str) -> Result<Self, SnapshotError> {
+        let raw: RawSnapshotFile = serde_json::from_str(json)
+            .map_err(|e| SnapshotError::File(format!("invalid JSON: {e}")))?;
+        let sdk = raw.ledger.or_else(|| {
+            raw.sdk_ledger_entries
+                .map(|ledger_entries| sdk::LedgerSnapshot {
+                    sequence_number: raw.sdk_sequence_number.unwrap_or_default(),
+                    ledger_entries,
+                    ..Default::default()
+                })
+        });
+        let (recorded_sequence, entries) = match sdk {
+            Some(ledger) => (Some(ledger.sequence_number), ledger.base64_entries()?),
+            None => (
+                raw.ledger_sequence,
+                raw.ledger_entries.into_iter().collect(),
+            ),
+        };
+
+        let decoded = LedgerSnapshot::from_base64_map(&entries)?;
+        let ledger_sequence = recorded_sequence.or_else(|| {
+            decoded
+                .iter()
+                .map(|(_, entry)| entry.last_modified_ledger_seq)
+                .max()
+        });
+
+        Ok(Self {
+            ledger_sequence,
+            entries,
+        })
+    }
+
+    /// Loads and validates a snapshot file from disk.
+    pub fn load(path: &Path) -> Result<Self, SnapshotError> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| SnapshotError::File(format!("failed to read {}: {e}", path.display())))?;
+        Self::from_json(&json)
+    }
+
+    /// The snapshot as JSON in the `erst` format, entries sorted by key.
+    pub fn to_json(&self) -> Result<String, SnapshotError> {
+        let entries = self.entries.iter().collect::<BTreeMap<_, _>>();
+        let json = serde_json::json!({
+            "ledgerSequence": self.ledger_sequence,
+            "ledgerEntries": entries.into_iter().collect::<Vec<_>>(),
+        });
+        serde_json::to_string_pretty(&json).map_err(|e| SnapshotError::File(e.to_string()))
+    }
+
+    /// Writes the snapshot to `path` in the `erst` format.
+    pub fn save(&self, path: &Path) -> Result<(), SnapshotError> {
+        std::fs::write(path, self.to_json()?)
+            .map_err(|e| SnapshotError::File(format!("failed to write {}: {e}", path.display())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+    use soroban_env_host::xdr::{
+        AccountEntry, AccountId, LedgerEntry, LedgerEntryData, LedgerKey, LedgerKeyAccount, Limits,
+        PublicKey, SequenceNumber, Thresholds, Uint256, WriteXdr,
+    };
+
+    fn account_pair(seed: u8, last_modified: u32) -> (String, String) {
+        let account_id = AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([seed; 32])));
+        let key = LedgerKey::Account(LedgerKeyAccount {
+            account_id: account_id.clone(),
+        });
+        let entry = LedgerEntry {
+            last_modified_ledger_seq: last_modified,
+            data: LedgerEntryData::Account(AccountEntry {
+                account_id,
+                balance: 1000,
+                seq_num: SequenceNumber(1),
+                num_sub_entries: 0,
+                inflation_dest: None,
+                flags: 0,
+                home_domain: Default::default(),
+                thresholds: Thresholds([1, 0, 0, 0]),
+                signers: Default::default(),
+                ext: Default::default(),
+            }),
+            ext: Default::default(),
+        };
+        let b64 = base64::engine::general_purpose::STANDARD;
+        (
+            b64.encode(key.to_xdr(Limits::none()).unwrap()),
+            b64.encode(entry.to_xdr(Limits::none()).unwrap()),
+        )
+    }
+
+    #[test]
+    fn test_from_json_reads_entries_and_sequence() {
+        let (k1, v1) = account_pair(1, 10);
+        let (k2, v2) = account_pair(2, 42);
+        let json = serde_json::json!({ "ledgerEntries": [[k1, v1], [k2, v2]] }).to_string();
+
+        let file = SnapshotFile::from_json(&json).unwrap();
+        assert_eq!(file.entries.len(), 2);
+        assert_eq!(file.ledger_sequence, Some(42));
+
+        let json = serde_json::json!({ "ledgerSequence": 100, "ledgerEntries": [] }).to_string();
+        assert_eq!(
+            SnapshotFile::from_json(&json).unwrap().ledger_sequence,
+            Some(100)
+        );
+    }
+
+    #[test]
+    fn test_to_json_round_trips() {
+        let (k1, v1) = account_pair(1, 10);
+        let file = SnapshotFile {
+            ledger_sequence: Some(12),
+            entries: HashMap::from([(k1, v1)]),
+        };
+        let read = SnapshotFile::from_json(&file.to_json().unwrap()).unwrap();
+        assert_eq!(read.ledger_sequence, Some(12));
+        assert_eq!(read.entries, file.entries);
+    }
+
+    #[test]
+    fn test_from_json_rejects_corrupt_entries() {
+        let json = r#"{ "ledgerEntries": [["AAAA", "not base64!"]] }"#;
+        assert!(SnapshotFile::from_json(json).is_err());
+        assert!(SnapshotFile::from_json("{").is_err());
+    }
+}
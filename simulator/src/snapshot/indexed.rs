@@ -0,0 +1,474 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Indexed binary snapshot files.
+//!
+//! A JSON snapshot is decoded into memory as a whole, which does not scale
+//! to near-full-network state. `erst-sim snapshot build` converts one into an
+//! indexed file that is memory-mapped and searched in place, so only the
+//! entries a simulation actually reads are ever decoded.
+//!
+//! Layout, with integers little-endian:
+//!
+//! | Section | Size                | Contents                                                       |
+//! |---------|---------------------|----------------------------------------------------------------|
+//! | header  | 32 bytes            | magic `ERSTSNAP`, version, flags, ledger sequence, reserved, entry count (u64) |
+//! | fanout  | 65536 × 8 bytes     | number of entries whose key hash has a 16-bit prefix ≤ i       |
+//! | index   | entry count × 24    | key hash (u64), data offset (u64), key length, entry length    |
+//! | data    | rest of the file    | key XDR followed by entry XDR, for each entry                  |
+//!
+//! The key hash is the first eight bytes of SHA-256 of the key XDR, and the
+//! index is sorted by hash. A lookup reads one fanout bucket, a handful of
+//! index records on average, and compares key bytes only on a hash match.
+//!
+//! Contract code can be megabytes of Wasm. [`IndexedSnapshot::contract_code`]
+//! returns it as a slice of the mapped file, so only the host's own copy is
+//! made per request.
+
+use super::file::SnapshotFile;
+use super::SnapshotError;
+use crate::source::{self, ContractCode, LedgerSource, SourceEntry, SourceError};
+use crate::xdr_limits;
+use base64::Engine;
+use memmap2::Mmap;
+use sha2::{Digest, Sha256};
+use soroban_env_host::xdr::{
+    ContractCodeEntryExt, Hash, LedgerEntry, LedgerEntryType, LedgerKey, LedgerKeyContractCode,
+    Limited, Limits, ReadXdr,
+};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufWriter, Cursor, Read, Write};
+use std::path::Path;
+
+/// First bytes of every indexed snapshot file.
+pub const MAGIC: &[u8; 8] = b"ERSTSNAP";
+const VERSION: u32 = 1;
+/// Flag set when the header records a ledger sequence.
+const FLAG_LEDGER_SEQUENCE: u32 = 1;
+
+const HEADER_LEN: usize = 32;
+const FANOUT_BUCKETS: usize = 1 << 16;
+const FANOUT_LEN: usize = FANOUT_BUCKETS * 8;
+const RECORD_LEN: usize = 24;
+
+/// Returns true if the file at `path` starts with [`MAGIC`].
+pub fn is_indexed(path: &Path) -> Result<bool, SnapshotError> {
+    let mut magic = [0u8; MAGIC.len()];
+    let read = File::open(path)
+        .and_then(|file| file.take(MAGIC.len() as u64).read(&mut magic))
+        .map_err(|e| file_error(path, "read", &e))?;
+    Ok(read == MAGIC.len() && &magic == MAGIC)
+}
+
+/// Writes `snapshot` to `out` in the indexed format, returning the number of
+/// entries written. The file is written next to `out` and renamed into
+/// place, so a server mapping the old file never sees a partial one.
+pub fn build(snapshot: &SnapshotFile, out: &Path) -> Result<usize, SnapshotError> {
+    let b64 = base64::engine::general_purpose::STANDARD;
+    let decode = |what: &str, value: &str| {
+        b64.decode(value)
+            .map_err(|e| SnapshotError::Base64Decode(format!("{what}: {e}")))
+    };
+    let mut entries = snapshot
+        .entries
+        .iter()
+        .map(|(key, entry)| {
+            let key = decode("LedgerKey", key)?;
+            Ok((key_hash(&key), key, decode("LedgerEntry", entry)?))
+        })
+        .collect::<Result<Vec<_>, SnapshotError>>()?;
+    entries.sort_unstable();
+
+    let mut fanout = vec![0u64; FANOUT_BUCKETS];
+    for (hash, _, _) in &entries {
+        fanout[bucket(*hash)] += 1;
+    }
+    for i in 1..FANOUT_BUCKETS {
+        fanout[i] += fanout[i - 1];
+    }
+
+    let tmp = out.with_extension("tmp");
+    let write = || -> std::io::Result<()> {
+        let mut w = BufWriter::new(File::create(&tmp)?);
+        let (flags, ledger_sequence) = match snapshot.ledger_sequence {
+            Some(seq) => (FLAG_LEDGER_SEQUENCE, seq),
+            None => (0, 0),
+        };
+        w.write_all(MAGIC)?;
+        for word in [VERSION, flags, ledger_sequence, 0] {
+            w.write_all(&word.to_le_bytes())?;
+        }
+        w.write_all(&(entries.len() as u64).to_le_bytes())?;
+        for count in &fanout {
+            w.write_all(&count.to_le_bytes())?;
+        }
+        let mut offset = (HEADER_LEN + FANOUT_LEN + entries.len() * RECORD_LEN) as u64;
+        for (hash, key, entry) in &entries {
+            w.write_all(&hash.to_le_bytes())?;
+            w.write_all(&offset.to_le_bytes())?;
+            w.write_all(&len_u32(key.len())?.to_le_bytes())?;
+            w.write_all(&len_u32(entry.len())?.to_le_bytes())?;
+            offset += (key.len() + entry.len()) as u64;
+        }
+        for (_, key, entry) in &entries {
+            w.write_all(key)?;
+            w.write_all(entry)?;
+        }
+        w.into_inner()?.sync_all()
+    };
+    write()
+        .and_then(|()| std::fs::rename(&tmp, out))
+        .map_err(|e| {
+            let _ = std::fs::remove_file(&tmp);
+            file_error(out, "write", &e)
+        })?;
+    Ok(entries.len())
+}
+
+/// A memory-mapped indexed snapshot.
+#[derive(Debug)]
+pub struct IndexedSnapshot {
+    map: Mmap,
+    len: usize,
+    ledger_sequence: Option<u32>,
+}
+
+impl IndexedSnapshot {
+    /// Maps the indexed snapshot at `path`, checking its header and sizes.
+    pub fn open(path: &Path) -> Result<Self, SnapshotError> {
+        let file = File::open(path).map_err(|e| file_error(path, "open", &e))?;
+        // SAFETY: the map is read-only, and `build` replaces files by rename
+        // rather than rewriting them, so the mapped bytes do not change
+        // underneath us unless another program truncates the file in place.
+        let map = unsafe { Mmap::map(&file) }.map_err(|e| file_error(path, "map", &e))?;
+        let invalid = |msg: &str| SnapshotError::File(format!("{}: {msg}", path.display()));
+
+        if map.len() < HEADER_LEN || &map[..MAGIC.len()] != MAGIC {
+            return Err(invalid("not an indexed snapshot"));
+        }
+        let version = read_u32(&map, 8);
+        if version != VERSION {
+            return Err(invalid(&format!("unsupported version {version}")));
+        }
+        let flags = read_u32(&map, 12);
+        let ledger_sequence = (flags & FLAG_LEDGER_SEQUENCE != 0).then(|| read_u32(&map, 16));
+        let len = usize::try_from(read_u64(&map, 24)).map_err(|_| invalid("too many entries"))?;
+        len.checked_mul(RECORD_LEN)
+            .and_then(|index| index.checked_add(HEADER_LEN + FANOUT_LEN))
+            .filter(|end| *end <= map.len())
+            .ok_or_else(|| invalid("truncated index"))?;
+        let snapshot = Self {
+            map,
+            len,
+            ledger_sequence,
+        };
+        if snapshot.fanout(FANOUT_BUCKETS - 1) != len as u64 {
+            return Err(invalid("fanout table does not match entry count"));
+        }
+        Ok(snapshot)
+    }
+
+    /// Number of entries.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Ledger sequence the snapshot was taken at, if recorded.
+    pub fn ledger_sequence(&self) -> Option<u32> {
+        self.ledger_sequence
+    }
+
+    /// Returns the entry XDR stored under `key` XDR.
+    pub fn get_raw(&self, key: &[u8]) -> Result<Option<&[u8]>, SourceError> {
+        let hash = key_hash(key);
+        let b = bucket(hash);
+        let clamp = |count: u64| usize::try_from(count).map_or(self.len, |c| c.min(self.len));
+        let start = if b == 0 { 0 } else { clamp(self.fanout(b - 1)) };
+        let end = clamp(self.fanout(b));
+        if start > end {
+            return Err(corrupt("fanout table is not sorted"));
+        }
+        // First record in the bucket whose hash is not below `hash`.
+        let (mut lo, mut hi) = (start, end);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self.record_hash(mid) < hash {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        for i in lo..end {
+            if self.record_hash(i) != hash {
+                break;
+            }
+            let (stored_key, entry) = self.record_data(i)?;
+            if stored_key == key {
+                return Ok(Some(entry));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Looks up `key`, with its TTL for contract entries.
+    pub fn get(&self, key: &LedgerKey) -> Result<Option<SourceEntry>, SourceError> {
+        let Some(entry) = self.get_raw(&source::encode_key(key)?)? else {
+            return Ok(None);
+        };
+        let entry = decode_entry(entry)?;
+        let ttl = if source::has_ttl(key) {
+            self.get_raw(&source::encode_key(&source::ttl_key(key)?)?)?
+                .map(decode_entry)
+                .transpose()?
+        } else {
+            None
+        };
+        Ok(Some(SourceEntry {
+            live_until: source::live_until(key, ttl.as_ref()),
+            entry,
+        }))
+    }
+
+    /// Looks up contract code `hash`, borrowing its Wasm from the file
+    /// rather than decoding the entry.
+    pub fn contract_code(&self, hash: &Hash) -> Result<Option<ContractCode<'_>>, SourceError> {
+        let key = LedgerKey::ContractCode(LedgerKeyContractCode { hash: hash.clone() });
+        let Some(entry) = self.get_raw(&source::encode_key(&key)?)? else {
+            return Ok(None);
+        };
+        let wasm = code_slice(entry)?;
+        let ttl = self
+            .get_raw(&source::encode_key(&source::ttl_key(&key)?)?)?
+            .map(decode_entry)
+            .transpose()?;
+        Ok(Some(ContractCode {
+            wasm: Cow::Borrowed(wasm),
+            live_until: source::live_until(&key, ttl.as_ref()),
+        }))
+    }
+
+    fn fanout(&self, bucket: usize) -> u64 {
+        read_u64(&self.map, HEADER_LEN + bucket * 8)
+    }
+
+    fn record(&self, i: usize) -> usize {
+        HEADER_LEN + FANOUT_LEN + i * RECORD_LEN
+    }
+
+    fn record_hash(&self, i: usize) -> u64 {
+        read_u64(&self.map, self.record(i))
+    }
+
+    fn record_data(&self, i: usize) -> Result<(&[u8], &[u8]), SourceError> {
+        let record = self.record(i);
+        let offset = usize::try_from(read_u64(&self.map, record + 8))
+            .map_err(|_| corrupt("data offset out of range"))?;
+        let key_len = read_u32(&self.map, record + 16) as usize;
+        let entry_len = read_u32(&self.map, record + 20) as usize;
+        let key_end = offset.saturating_add(key_len);
+        let key = self
+            .map
+            .get(offset..key_end)
+            .ok_or_else(|| corrupt("data offset out of range"))?;
+        let entry = self
+            .map
+            .get(key_end..key_end.saturating_add(entry_len))
+            .ok_or_else(|| corrupt("data offset out of range"))?;
+        Ok((key, entry))
+    }
+}
+
+impl LedgerSource for IndexedSnapshot {
+    fn name(&self) -> &str {
+        "indexed-snapshot"
+    }
+
+    fn latest_ledger(&self) -> Result<u32, SourceError> {
+        Ok(self.ledger_sequence.unwrap_or(0))
+    }
+
+    fn fetch(&self, keys: &[LedgerKey]) -> Result<Vec<Option<SourceEntry>>, SourceError> {
+        keys.iter().map(|key| self.get(key)).collect()
+    }
+}
+
+fn key_hash(key: &[u8]) -> u64 {
+    let digest = Sha256::digest(key);
+    u64::from_be_bytes(digest[..8].try_into().expect("digest has 32 bytes"))
+}
+
+fn bucket(hash: u64) -> usize {
+    (hash >> 48) as usize
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().expect("4 bytes"))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().expect("8 bytes"))
+}
+
+fn len_u32(len: usize) -> std::io::Result<u32> {
+    u32::try_from(len).map_err(|_| std::io::Error::other("entry larger than 4 GiB"))
+}
+
+fn decode_entry(bytes: &[u8]) -> Result<LedgerEntry, SourceError> {
+    xdr_limits::read::<LedgerEntry>(bytes)
+        .map_err(|e| corrupt(&format!("invalid LedgerEntry XDR: {e}")))
+}
+
+/// The Wasm inside a `ContractCode` entry's XDR, found by reading the
+/// fields before it.
+fn code_slice(entry: &[u8]) -> Result<&[u8], SourceError> {
+    let mut cursor = Limited::new(Cursor::new(entry), Limits::len(entry.len()));
+    let len = (|| {
+        u32::read_xdr(&mut cursor)?; // last_modified_ledger_seq
+        if LedgerEntryType::read_xdr(&mut cursor)? != LedgerEntryType::ContractCode {
+            return Ok(None);
+        }
+        ContractCodeEntryExt::read_xdr(&mut cursor)?;
+        Hash::read_xdr(&mut cursor)?;
+        u32::read_xdr(&mut cursor).map(Some)
+    })()
+    .map_err(|e: soroban_env_host::xdr::Error| corrupt(&format!("invalid LedgerEntry XDR: {e}")))?
+    .ok_or_else(|| corrupt("contract code key holds another entry type"))?;
+    let start = usize::try_from(cursor.inner.position())
+        .map_err(|_| corrupt("contract code out of range"))?;
+    entry
+        .get(start..start.saturating_add(len as usize))
+        .ok_or_else(|| corrupt("contract code out of range"))
+}
+
+fn corrupt(msg: &str) -> SourceError {
+    SourceError::InvalidResponse(format!("corrupt indexed snapshot: {msg}"))
+}
+
+fn file_error(path: &Path, action: &str, e: &std::io::Error) -> SnapshotError {
+    SnapshotError::File(format!("failed to {action} {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::fixtures::account;
+    use soroban_env_host::xdr::{
+        ContractCodeEntry, ContractCodeEntryV1, ExtensionPoint, LedgerEntryData, LedgerKeyTtl,
+        TtlEntry, WriteXdr,
+    };
+
+    fn snapshot_file(seeds: impl Iterator<Item = u8>) -> SnapshotFile {
+        let b64 = base64::engine::general_purpose::STANDARD;
+        SnapshotFile {
+            ledger_sequence: Some(77),
+            entries: seeds
+                .map(|seed| {
+                    let (key, entry) = account(seed, i64::from(seed) * 100, u32::from(seed));
+                    (
+                        b64.encode(key.to_xdr(Limits::none()).unwrap()),
+                        b64.encode(entry.to_xdr(Limits::none()).unwrap()),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_build_and_look_up_entries() {
+        let dir = std::env::temp_dir().join(format!("erst-indexed-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.snap");
+
+        assert_eq!(build(&snapshot_file(1..=200), &path).unwrap(), 200);
+        assert!(is_indexed(&path).unwrap());
+        let snapshot = IndexedSnapshot::open(&path).unwrap();
+        assert_eq!(snapshot.len(), 200);
+        assert_eq!(snapshot.ledger_sequence(), Some(77));
+
+        let (present, expected) = account(150, 15_000, 150);
+        let (missing, _) = account(250, 25_000, 250);
+        let found = snapshot.fetch(&[present, missing]).unwrap();
+        let entry = found[0].as_ref().unwrap();
+        assert_eq!(entry.entry, expected);
+        assert_eq!(entry.live_until, None);
+        assert!(found[1].is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_contract_code_is_borrowed_from_the_file() {
+        let dir = std::env::temp_dir().join(format!("erst-indexed-code-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.snap");
+
+        let hash = Hash([7; 32]);
+        let wasm = b"\0asm\x01\0\0\0 not really a contract".to_vec();
+        let key = LedgerKey::ContractCode(LedgerKeyContractCode { hash: hash.clone() });
+        let code = LedgerEntry {
+            last_modified_ledger_seq: 5,
+            data: LedgerEntryData::ContractCode(ContractCodeEntry {
+                ext: ContractCodeEntryExt::V1(ContractCodeEntryV1 {
+                    ext: ExtensionPoint::V0,
+                    cost_inputs: Default::default(),
+                }),
+                hash: hash.clone(),
+                code: wasm.clone().try_into().unwrap(),
+            }),
+            ext: Default::default(),
+        };
+        let ttl_key = source::ttl_key(&key).unwrap();
+        let LedgerKey::Ttl(LedgerKeyTtl { key_hash }) = ttl_key.clone() else {
+            unreachable!();
+        };
+        let ttl = LedgerEntry {
+            last_modified_ledger_seq: 5,
+            data: LedgerEntryData::Ttl(TtlEntry {
+                key_hash,
+                live_until_ledger_seq: 900,
+            }),
+            ext: Default::default(),
+        };
+        let b64 = base64::engine::general_purpose::STANDARD;
+        let mut file = snapshot_file(1..=3);
+        for (key, entry) in [(&key, &code), (&ttl_key, &ttl)] {
+            file.entries.insert(
+                b64.encode(key.to_xdr(Limits::none()).unwrap()),
+                b64.encode(entry.to_xdr(Limits::none()).unwrap()),
+            );
+        }
+        build(&file, &path).unwrap();
+        let snapshot = IndexedSnapshot::open(&path).unwrap();
+
+        let found = snapshot.contract_code(&hash).unwrap().unwrap();
+        assert_eq!(found.live_until, Some(900));
+        let Cow::Borrowed(found) = found.wasm else {
+            panic!("contract code was copied");
+        };
+        assert_eq!(found, wasm.as_slice());
+        assert!(snapshot.map.as_ptr_range().contains(&found.as_ptr()));
+        assert!(snapshot.contract_code(&Hash([8; 32])).unwrap().is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_open_rejects_other_files() {
+        let dir = std::env::temp_dir().join(format!("erst-indexed-bad-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.json");
+        std::fs::write(&path, br#"{"ledgerEntries":[]}"#).unwrap();
+        assert!(!is_indexed(&path).unwrap());
+        assert!(IndexedSnapshot::open(&path).is_err());
+
+        // A header claiming more entries than the file holds.
+        let mut truncated = MAGIC.to_vec();
+        truncated.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        truncated.extend_from_slice(&5u64.to_le_bytes());
+        std::fs::write(&path, truncated).unwrap();
+        assert!(IndexedSnapshot::open(&path).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
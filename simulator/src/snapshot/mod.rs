@@ -8,12 +8,27 @@
 //! - Loading ledger state into Soroban Host storage
 //! - Managing ledger snapshots for transaction replay
 //!
+//! Snapshots hold entries of every ledger entry type. The host itself only
+//! reads accounts, trustlines and contract entries; classic entries such as
+//! offers, liquidity pools, claimable balances and data entries are kept for
+//! mixed classic and Soroban scenarios and for diagnostics.
+//!
 //! These utilities can be shared across different Soroban tools that need
 //! to reconstruct ledger state for simulation or analysis purposes.
 
+pub mod file;
+pub mod indexed;
+pub mod sdk;
+pub mod simd;
+
+use crate::xdr_limits;
 use base64::Engine;
-use soroban_env_host::xdr::{LedgerEntry, LedgerKey, Limits, ReadXdr, WriteXdr};
-use std::collections::HashMap;
+use soroban_env_host::xdr::{
+    self, LedgerEntry, LedgerEntryData, LedgerKey, LedgerKeyAccount, LedgerKeyClaimableBalance,
+    LedgerKeyConfigSetting, LedgerKeyContractCode, LedgerKeyContractData, LedgerKeyData,
+    LedgerKeyLiquidityPool, LedgerKeyOffer, LedgerKeyTrustLine, LedgerKeyTtl, Limits, WriteXdr,
+};
+use std::collections::{BTreeMap, HashMap};
 
 /// Represents a decoded ledger snapshot containing key-value pairs
 /// of ledger entries ready for loading into Host storage.
@@ -48,20 +63,41 @@ impl LedgerSnapshot {
     /// let snapshot = LedgerSnapshot::from_base64_map(&entries)?;
     /// ```
     pub fn from_base64_map(entries: &HashMap<String, String>) -> Result<Self, SnapshotError> {
-        let mut decoded_entries = HashMap::new();
-
-        for (key_xdr, entry_xdr) in entries {
-            let key = decode_ledger_key(key_xdr)?;
-            let entry = decode_ledger_entry(entry_xdr)?;
+        Self::from_base64_map_on(entries, decode_threads(entries.len()), Decoder::best())
+    }
 
-            // Use the XDR-encoded key bytes as the map key for consistency
-            let key_bytes = key
-                .to_xdr(Limits::none())
-                .map_err(|e| SnapshotError::XdrEncoding(format!("Failed to encode key: {e}")))?;
+    /// Like [`LedgerSnapshot::from_base64_map`], decoding the entries on
+    /// `threads` threads with `decoder`.
+    pub fn from_base64_map_on(
+        entries: &HashMap<String, String>,
+        threads: usize,
+        decoder: Decoder,
+    ) -> Result<Self, SnapshotError> {
+        let pairs = entries.iter().collect::<Vec<_>>();
+        let threads = threads.clamp(1, pairs.len().max(1));
+        let chunks = if threads == 1 {
+            vec![decode_pairs(&pairs, decoder)?]
+        } else {
+            let chunk_len = pairs.len().div_ceil(threads);
+            std::thread::scope(|scope| {
+                pairs
+                    .chunks(chunk_len)
+                    .map(|chunk| scope.spawn(move || decode_pairs(chunk, decoder)))
+                    .collect::<Vec<_>>()
+                    .into_iter()
+                    .map(|handle| {
+                        handle
+                            .join()
+                            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })?
+        };
 
-            decoded_entries.insert(key_bytes, entry);
+        let mut decoded_entries = HashMap::with_capacity(pairs.len());
+        for chunk in chunks {
+            decoded_entries.extend(chunk);
         }
-
         Ok(Self {
             entries: decoded_entries,
         })
@@ -78,8 +114,17 @@ impl LedgerSnapshot {
         self.entries.is_empty()
     }
 
+    /// Returns the number of entries of each type, keyed by XDR type name
+    /// (`Account`, `Trustline`, `Offer`, ...).
+    pub fn type_counts(&self) -> BTreeMap<&'static str, usize> {
+        let mut counts = BTreeMap::new();
+        for entry in self.entries.values() {
+            *counts.entry(entry.data.name()).or_insert(0) += 1;
+        }
+        counts
+    }
+
     /// Returns an iterator over the entries in the snapshot.
-    #[allow(dead_code)]
     pub fn iter(&self) -> impl Iterator<Item = (&Vec<u8>, &LedgerEntry)> {
         self.entries.iter()
     }
@@ -94,8 +139,24 @@ impl LedgerSnapshot {
         self.entries.insert(key, entry);
     }
 
+    /// Inserts an entry under its decoded ledger key.
+    ///
+    /// # Arguments
+    /// * `key` - The ledger key
+    /// * `entry` - The ledger entry
+    pub fn insert_entry(
+        &mut self,
+        key: &LedgerKey,
+        entry: LedgerEntry,
+    ) -> Result<(), SnapshotError> {
+        let key_bytes = key
+            .to_xdr(Limits::none())
+            .map_err(|e| SnapshotError::XdrEncoding(format!("Failed to encode key: {e}")))?;
+        self.entries.insert(key_bytes, entry);
+        Ok(())
+    }
+
     /// Gets an entry from the snapshot by key.
-    #[allow(dead_code)]
     pub fn get(&self, key: &[u8]) -> Option<&LedgerEntry> {
         self.entries.get(key)
     }
@@ -119,11 +180,92 @@ pub enum SnapshotError {
     #[error("Failed to encode XDR: {0}")]
     XdrEncoding(String),
 
+    #[error("XDR limits exceeded: {0}")]
+    XdrLimitsExceeded(String),
+
+    #[error("Ledger key does not match entry: {0}")]
+    KeyMismatch(String),
+
+    #[error("Snapshot file error: {0}")]
+    File(String),
+
     #[error("Storage operation failed: {0}")]
     #[allow(dead_code)]
     StorageError(String),
 }
 
+/// Snapshots with fewer entries are decoded on the calling thread.
+pub const PARALLEL_DECODE_MIN_ENTRIES: usize = 4096;
+
+/// How snapshot base64 is decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decoder {
+    /// The `base64` crate's engine.
+    Scalar,
+    /// [`simd::decode`], which is the scalar engine where SIMD is not
+    /// [`simd::available`].
+    Simd,
+}
+
+impl Decoder {
+    /// The decoder snapshots are loaded with: SIMD where available.
+    pub fn best() -> Self {
+        if simd::available() {
+            Self::Simd
+        } else {
+            Self::Scalar
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Scalar => "scalar",
+            Self::Simd => "simd",
+        }
+    }
+
+    fn decode(self, input: &str) -> Result<Vec<u8>, base64::DecodeError> {
+        match self {
+            Self::Scalar => base64::engine::general_purpose::STANDARD.decode(input),
+            Self::Simd => simd::decode(input),
+        }
+    }
+}
+
+/// Threads to decode `entries` snapshot entries on.
+fn decode_threads(entries: usize) -> usize {
+    if entries < PARALLEL_DECODE_MIN_ENTRIES {
+        return 1;
+    }
+    std::thread::available_parallelism().map_or(1, usize::from)
+}
+
+/// Decodes and checks key and entry pairs, keyed by the key's XDR bytes.
+fn decode_pairs(
+    pairs: &[(&String, &String)],
+    decoder: Decoder,
+) -> Result<Vec<(Vec<u8>, LedgerEntry)>, SnapshotError> {
+    let mut decoded = Vec::with_capacity(pairs.len());
+    for (key_xdr, entry_xdr) in pairs {
+        let key = decode_key_with(key_xdr, decoder)?;
+        let entry = decode_entry_with(entry_xdr, decoder)?;
+        if entry_key(&entry) != key {
+            return Err(SnapshotError::KeyMismatch(format!(
+                "{} key {key_xdr} does not match its {} entry",
+                key.name(),
+                entry.data.name()
+            )));
+        }
+
+        // Use the XDR-encoded key bytes as the map key for consistency
+        let key_bytes = key
+            .to_xdr(Limits::none())
+            .map_err(|e| SnapshotError::XdrEncoding(format!("Failed to encode key: {e}")))?;
+        decoded.push((key_bytes, entry));
+    }
+    Ok(decoded)
+}
+
 /// Decodes a base64-encoded LedgerKey XDR string.
 ///
 /// # Arguments
@@ -133,13 +275,17 @@ pub enum SnapshotError {
 /// * `Ok(LedgerKey)` - Successfully decoded key
 /// * `Err(SnapshotError)` - Decoding or parsing failed
 pub fn decode_ledger_key(key_xdr: &str) -> Result<LedgerKey, SnapshotError> {
+    decode_key_with(key_xdr, Decoder::best())
+}
+
+fn decode_key_with(key_xdr: &str, decoder: Decoder) -> Result<LedgerKey, SnapshotError> {
     if key_xdr.is_empty() {
         return Err(SnapshotError::Base64Decode(
             "LedgerKey: empty payload".to_string(),
         ));
     }
 
-    let bytes = base64::engine::general_purpose::STANDARD
+    let bytes = decoder
         .decode(key_xdr)
         .map_err(|e| SnapshotError::Base64Decode(format!("LedgerKey: {e}")))?;
 
@@ -149,8 +295,7 @@ pub fn decode_ledger_key(key_xdr: &str) -> Result<LedgerKey, SnapshotError> {
         ));
     }
 
-    LedgerKey::from_xdr(bytes, Limits::none())
-        .map_err(|e| SnapshotError::XdrParse(format!("LedgerKey: {e}")))
+    xdr_limits::read::<LedgerKey>(&bytes).map_err(|e| xdr_error("LedgerKey", &e))
 }
 
 /// Decodes a base64-encoded LedgerEntry XDR string.
@@ -162,13 +307,17 @@ pub fn decode_ledger_key(key_xdr: &str) -> Result<LedgerKey, SnapshotError> {
 /// * `Ok(LedgerEntry)` - Successfully decoded entry
 /// * `Err(SnapshotError)` - Decoding or parsing failed
 pub fn decode_ledger_entry(entry_xdr: &str) -> Result<LedgerEntry, SnapshotError> {
+    decode_entry_with(entry_xdr, Decoder::best())
+}
+
+fn decode_entry_with(entry_xdr: &str, decoder: Decoder) -> Result<LedgerEntry, SnapshotError> {
     if entry_xdr.is_empty() {
         return Err(SnapshotError::Base64Decode(
             "LedgerEntry: empty payload".to_string(),
         ));
     }
 
-    let bytes = base64::engine::general_purpose::STANDARD
+    let bytes = decoder
         .decode(entry_xdr)
         .map_err(|e| SnapshotError::Base64Decode(format!("LedgerEntry: {e}")))?;
 
@@ -178,8 +327,59 @@ pub fn decode_ledger_entry(entry_xdr: &str) -> Result<LedgerEntry, SnapshotError
         ));
     }
 
-    LedgerEntry::from_xdr(bytes, Limits::none())
-        .map_err(|e| SnapshotError::XdrParse(format!("LedgerEntry: {e}")))
+    xdr_limits::read::<LedgerEntry>(&bytes).map_err(|e| xdr_error("LedgerEntry", &e))
+}
+
+/// Derives the ledger key identifying `entry`.
+pub fn entry_key(entry: &LedgerEntry) -> LedgerKey {
+    match &entry.data {
+        LedgerEntryData::Account(e) => LedgerKey::Account(LedgerKeyAccount {
+            account_id: e.account_id.clone(),
+        }),
+        LedgerEntryData::Trustline(e) => LedgerKey::Trustline(LedgerKeyTrustLine {
+            account_id: e.account_id.clone(),
+            asset: e.asset.clone(),
+        }),
+        LedgerEntryData::Offer(e) => LedgerKey::Offer(LedgerKeyOffer {
+            seller_id: e.seller_id.clone(),
+            offer_id: e.offer_id,
+        }),
+        LedgerEntryData::Data(e) => LedgerKey::Data(LedgerKeyData {
+            account_id: e.account_id.clone(),
+            data_name: e.data_name.clone(),
+        }),
+        LedgerEntryData::ClaimableBalance(e) => {
+            LedgerKey::ClaimableBalance(LedgerKeyClaimableBalance {
+                balance_id: e.balance_id.clone(),
+            })
+        }
+        LedgerEntryData::LiquidityPool(e) => LedgerKey::LiquidityPool(LedgerKeyLiquidityPool {
+            liquidity_pool_id: e.liquidity_pool_id.clone(),
+        }),
+        LedgerEntryData::ContractData(e) => LedgerKey::ContractData(LedgerKeyContractData {
+            contract: e.contract.clone(),
+            key: e.key.clone(),
+            durability: e.durability,
+        }),
+        LedgerEntryData::ContractCode(e) => LedgerKey::ContractCode(LedgerKeyContractCode {
+            hash: e.hash.clone(),
+        }),
+        LedgerEntryData::ConfigSetting(e) => LedgerKey::ConfigSetting(LedgerKeyConfigSetting {
+            config_setting_id: e.discriminant(),
+        }),
+        LedgerEntryData::Ttl(e) => LedgerKey::Ttl(LedgerKeyTtl {
+            key_hash: e.key_hash.clone(),
+        }),
+    }
+}
+
+/// Distinguishes limit violations from malformed XDR.
+fn xdr_error(what: &str, e: &xdr::Error) -> SnapshotError {
+    if xdr_limits::is_exceeded(e) {
+        SnapshotError::XdrLimitsExceeded(format!("{what}: {e}"))
+    } else {
+        SnapshotError::XdrParse(format!("{what}: {e}"))
+    }
 }
 
 /// Statistics about a loaded snapshot.
@@ -238,7 +438,8 @@ mod tests {
     #[test]
     fn test_snapshot_from_empty_map() {
         let entries = HashMap::new();
-        let snapshot = LedgerSnapshot::from_base64_map(&entries).expect("Failed to create snapshot from empty map");
+        let snapshot = LedgerSnapshot::from_base64_map(&entries)
+            .expect("Failed to create snapshot from empty map");
         assert!(snapshot.is_empty());
     }
 
@@ -276,7 +477,103 @@ mod tests {
 
         let result = LedgerSnapshot::from_base64_map(&entries);
         assert!(result.is_err());
-        assert!(matches!(result.unwrap_err(), SnapshotError::Base64Decode(_)));
+        assert!(matches!(
+            result.unwrap_err(),
+            SnapshotError::Base64Decode(_)
+        ));
+    }
+
+    #[test]
+    fn test_from_base64_map_accepts_classic_entries() {
+        use soroban_env_host::xdr::{
+            AccountId, DataEntry, DataEntryExt, LedgerEntryExt, OfferEntry, OfferEntryExt, Price,
+            PublicKey, String64, Uint256,
+        };
+
+        let seller = AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([3u8; 32])));
+        let offer = LedgerEntry {
+            last_modified_ledger_seq: 7,
+            data: LedgerEntryData::Offer(OfferEntry {
+                seller_id: seller.clone(),
+                offer_id: 42,
+                selling: xdr::Asset::Native,
+                buying: xdr::Asset::Native,
+                amount: 10,
+                price: Price { n: 1, d: 2 },
+                flags: 0,
+                ext: OfferEntryExt::V0,
+            }),
+            ext: LedgerEntryExt::V0,
+        };
+        let data = LedgerEntry {
+            last_modified_ledger_seq: 7,
+            data: LedgerEntryData::Data(DataEntry {
+                account_id: seller,
+                data_name: String64("config".try_into().unwrap()),
+                data_value: vec![1, 2, 3].try_into().unwrap(),
+                ext: DataEntryExt::V0,
+            }),
+            ext: LedgerEntryExt::V0,
+        };
+        let account = create_dummy_ledger_entry();
+        let b64 = |bytes: Vec<u8>| base64::engine::general_purpose::STANDARD.encode(bytes);
+        let pair = |entry: &LedgerEntry| {
+            (
+                b64(entry_key(entry).to_xdr(Limits::none()).unwrap()),
+                b64(entry.to_xdr(Limits::none()).unwrap()),
+            )
+        };
+
+        let entries: HashMap<String, String> = [pair(&offer), pair(&data), pair(&account)]
+            .into_iter()
+            .collect();
+        let snapshot = LedgerSnapshot::from_base64_map(&entries).unwrap();
+        let counts = snapshot.type_counts();
+        assert_eq!(counts.get("Offer"), Some(&1));
+        assert_eq!(counts.get("Data"), Some(&1));
+        assert_eq!(counts.get("Account"), Some(&1));
+
+        // An entry filed under another entry's key is rejected.
+        let mismatched = HashMap::from([(pair(&offer).0, pair(&data).1)]);
+        assert!(matches!(
+            LedgerSnapshot::from_base64_map(&mismatched),
+            Err(SnapshotError::KeyMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_parallel_decode_matches_sequential() {
+        use soroban_env_host::xdr::{AccountId, PublicKey, Uint256};
+
+        let b64 = |bytes: Vec<u8>| base64::engine::general_purpose::STANDARD.encode(bytes);
+        let mut entries = (0..100u8)
+            .map(|i| {
+                let mut entry = create_dummy_ledger_entry();
+                if let LedgerEntryData::Account(account) = &mut entry.data {
+                    account.account_id =
+                        AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([i; 32])));
+                }
+                (
+                    b64(entry_key(&entry).to_xdr(Limits::none()).unwrap()),
+                    b64(entry.to_xdr(Limits::none()).unwrap()),
+                )
+            })
+            .collect::<HashMap<_, _>>();
+
+        let sequential = LedgerSnapshot::from_base64_map_on(&entries, 1, Decoder::Scalar).unwrap();
+        let parallel = LedgerSnapshot::from_base64_map_on(&entries, 4, Decoder::Scalar).unwrap();
+        let simd = LedgerSnapshot::from_base64_map_on(&entries, 4, Decoder::Simd).unwrap();
+        assert_eq!(parallel.len(), 100);
+        assert_eq!(parallel.entries, sequential.entries);
+        assert_eq!(simd.entries, sequential.entries);
+
+        entries.insert("!".to_string(), "!".to_string());
+        for decoder in [Decoder::Scalar, Decoder::Simd] {
+            assert!(matches!(
+                LedgerSnapshot::from_base64_map_on(&entries, 4, decoder),
+                Err(SnapshotError::Base64Decode(_))
+            ));
+        }
     }
 
     #[test]
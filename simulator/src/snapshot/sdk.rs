@@ -0,0 +1,265 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Snapshots in the format of soroban-sdk's testutils.
+//!
+//! A Rust unit test writes its state to `test_snapshots/` as a JSON object
+//! whose `ledger` holds the ledger info and the entries, each with its
+//! `live_until` ledger, in the JSON form of the XDR types. The
+//! `ledger` object alone is a ledger snapshot, the form that
+//! `Env::from_ledger_snapshot_file` reads and `stellar snapshot create`
+//! writes.
+//!
+//! [`SnapshotFile`] reads either form next to its own, turning each
+//! `live_until` into a TTL entry. [`to_json`] goes the other way, so state
+//! from a simulation can be loaded with `Env::from_snapshot_file`.
+
+use super::{decode_ledger_entry, decode_ledger_key, entry_key, file::SnapshotFile, SnapshotError};
+use crate::network::{self, Network};
+use crate::{simulation, source};
+use serde::{Deserialize, Serialize};
+use soroban_env_host::xdr::{
+    LedgerEntry, LedgerEntryData, LedgerEntryExt, LedgerKey, Limits, TtlEntry, WriteXdr,
+};
+use std::collections::HashMap;
+
+/// A ledger entry with its `live_until` ledger, if it has a TTL.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Entry {
+    /// As current soroban-sdk releases write it.
+    Compact {
+        entry: LedgerEntry,
+        live_until: Option<u32>,
+    },
+    /// `[key, [entry, live_until]]`, as older releases write it.
+    Pair(LedgerKey, (LedgerEntry, Option<u32>)),
+}
+
+/// The ledger of a soroban-sdk snapshot.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LedgerSnapshot {
+    pub protocol_version: u32,
+    pub sequence_number: u32,
+    pub timestamp: u64,
+    /// Hex.
+    pub network_id: String,
+    pub base_reserve: u32,
+    pub min_persistent_entry_ttl: u32,
+    pub min_temp_entry_ttl: u32,
+    pub max_entry_ttl: u32,
+    pub ledger_entries: Vec<Entry>,
+}
+
+impl LedgerSnapshot {
+    /// The entries as base64 XDR, with a TTL entry for each `live_until`.
+    pub fn base64_entries(&self) -> Result<HashMap<String, String>, SnapshotError> {
+        let encode = |xdr: Result<String, soroban_env_host::xdr::Error>| {
+            xdr.map_err(|e| SnapshotError::XdrEncoding(e.to_string()))
+        };
+        let mut entries = HashMap::with_capacity(self.ledger_entries.len());
+        for ledger_entry in &self.ledger_entries {
+            let (key, entry, live_until) = match ledger_entry {
+                Entry::Compact { entry, live_until } => (entry_key(entry), entry, live_until),
+                Entry::Pair(key, (entry, live_until)) => (key.clone(), entry, live_until),
+            };
+            entries.insert(
+                encode(key.to_xdr_base64(Limits::none()))?,
+                encode(entry.to_xdr_base64(Limits::none()))?,
+            );
+            let Some(live_until) = live_until else {
+                continue;
+            };
+            let ttl_key =
+                source::ttl_key(&key).map_err(|e| SnapshotError::XdrEncoding(e.to_string()))?;
+            let LedgerKey::Ttl(ttl) = &ttl_key else {
+                unreachable!("ttl_key returns a TTL key");
+            };
+            let ttl = LedgerEntry {
+                last_modified_ledger_seq: entry.last_modified_ledger_seq,
+                data: LedgerEntryData::Ttl(TtlEntry {
+                    key_hash: ttl.key_hash.clone(),
+                    live_until_ledger_seq: *live_until,
+                }),
+                ext: LedgerEntryExt::V0,
+            };
+            entries.insert(
+                encode(ttl_key.to_xdr_base64(Limits::none()))?,
+                encode(ttl.to_xdr_base64(Limits::none()))?,
+            );
+        }
+        Ok(entries)
+    }
+}
+
+/// `file` as a soroban-sdk test snapshot on `network`, at the snapshot's
+/// ledger sequence. TTL entries become the `live_until` of their entries, and
+/// contract entries without one live forever, as they do in a simulation.
+pub fn to_json(file: &SnapshotFile, network: &Network) -> Result<String, SnapshotError> {
+    let mut ledger_entries = Vec::with_capacity(file.entries.len());
+    for (key_xdr, entry_xdr) in &file.entries {
+        let key = decode_ledger_key(key_xdr)?;
+        if matches!(key, LedgerKey::Ttl(_)) {
+            continue;
+        }
+        let ttl = source::ttl_key(&key)
+            .ok()
+            .and_then(|ttl_key| ttl_key.to_xdr_base64(Limits::none()).ok())
+            .and_then(|ttl_key| file.entries.get(&ttl_key))
+            .map(|ttl| decode_ledger_entry(ttl))
+            .transpose()?;
+        let live_until = source::live_until(&key, ttl.as_ref());
+        let entry = decode_ledger_entry(entry_xdr)?;
+        ledger_entries.push((key_xdr, Entry::Compact { entry, live_until }));
+    }
+    ledger_entries.sort_by(|a, b| a.0.cmp(b.0));
+
+    let info = simulation::ledger_info(network, file.ledger_sequence.unwrap_or(0), 0);
+    let ledger = LedgerSnapshot {
+        protocol_version: info.protocol_version,
+        sequence_number: info.sequence_number,
+        timestamp: info.timestamp,
+        network_id: network::hex(&info.network_id),
+        base_reserve: info.base_reserve,
+        min_persistent_entry_ttl: info.min_persistent_entry_ttl,
+        min_temp_entry_ttl: info.min_temp_entry_ttl,
+        max_entry_ttl: info.max_entry_ttl,
+        ledger_entries: ledger_entries.into_iter().map(|(_, entry)| entry).collect(),
+    };
+    let snapshot = serde_json::json!({
+        "generators": { "address": 0, "nonce": 0, "mux_id": 0 },
+        "auth": [],
+        "ledger": ledger,
+        "events": [],
+    });
+    serde_json::to_string_pretty(&snapshot)
+        .map_err(|e| SnapshotError::File(format!("failed to write JSON: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::xdr::{
+        ContractDataDurability, ContractDataEntry, ContractExecutable, ContractId, ExtensionPoint,
+        Hash, LedgerKeyContractData, ScAddress, ScContractInstance, ScVal,
+    };
+
+    fn instance(contract: &ScAddress) -> (LedgerKey, LedgerEntry) {
+        let key = LedgerKey::ContractData(LedgerKeyContractData {
+            contract: contract.clone(),
+            key: ScVal::LedgerKeyContractInstance,
+            durability: ContractDataDurability::Persistent,
+        });
+        let entry = LedgerEntry {
+            last_modified_ledger_seq: 0,
+            data: LedgerEntryData::ContractData(ContractDataEntry {
+                ext: ExtensionPoint::V0,
+                contract: contract.clone(),
+                key: ScVal::LedgerKeyContractInstance,
+                durability: ContractDataDurability::Persistent,
+                val: ScVal::ContractInstance(ScContractInstance {
+                    executable: ContractExecutable::StellarAsset,
+                    storage: None,
+                }),
+            }),
+            ext: LedgerEntryExt::V0,
+        };
+        (key, entry)
+    }
+
+    #[test]
+    fn test_reads_sdk_test_snapshot() {
+        let contract = ScAddress::Contract(ContractId(Hash([0; 32]))).to_string();
+        let key = serde_json::json!({ "contract_data": {
+            "contract": contract,
+            "key": "ledger_key_contract_instance",
+            "durability": "persistent",
+        }});
+        let entry = serde_json::json!({
+            "last_modified_ledger_seq": 0,
+            "data": { "contract_data": {
+                "ext": "v0",
+                "contract": contract,
+                "key": "ledger_key_contract_instance",
+                "durability": "persistent",
+                "val": { "contract_instance": { "executable": "stellar_asset", "storage": null } },
+            }},
+            "ext": "v0",
+        });
+        let ledger = serde_json::json!({
+            "protocol_version": 22,
+            "sequence_number": 7,
+            "timestamp": 0,
+            "network_id": "00".repeat(32),
+            "base_reserve": 0,
+            "min_persistent_entry_ttl": 4096,
+            "min_temp_entry_ttl": 16,
+            "max_entry_ttl": 6_312_000,
+            "ledger_entries": [[key, [entry, 4095]]],
+        });
+        let mut compact = ledger.clone();
+        compact["ledger_entries"] = serde_json::json!([{ "entry": entry, "live_until": 4095 }]);
+        let test_snapshot = serde_json::json!({
+            "generators": { "address": 1, "nonce": 0 },
+            "auth": [[]],
+            "ledger": ledger,
+            "events": [],
+        });
+
+        for json in [test_snapshot, ledger, compact] {
+            let file = SnapshotFile::from_json(&json.to_string()).unwrap();
+            assert_eq!(file.ledger_sequence, Some(7));
+            // The instance and its TTL.
+            assert_eq!(file.entries.len(), 2);
+            let (key, _) = instance(&contract.parse().unwrap());
+            let ttl = source::ttl_key(&key).unwrap();
+            let ttl = &file.entries[&ttl.to_xdr_base64(Limits::none()).unwrap()];
+            assert!(matches!(
+                decode_ledger_entry(ttl).unwrap().data,
+                LedgerEntryData::Ttl(TtlEntry {
+                    live_until_ledger_seq: 4095,
+                    ..
+                })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_to_json_round_trips() {
+        let mut entries = HashMap::new();
+        for seed in [1, 2] {
+            let (key, entry) = instance(&ScAddress::Contract(ContractId(Hash([seed; 32]))));
+            entries.insert(
+                key.to_xdr_base64(Limits::none()).unwrap(),
+                entry.to_xdr_base64(Limits::none()).unwrap(),
+            );
+        }
+        let file = SnapshotFile {
+            ledger_sequence: Some(100),
+            entries,
+        };
+        let network = Network::resolve(Some("testnet"), None).unwrap();
+        let json = to_json(&file, &network).unwrap();
+
+        let snapshot: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(snapshot["ledger"]["sequence_number"], 100);
+        assert_eq!(
+            snapshot["ledger"]["network_id"],
+            network::hex(&network.id())
+        );
+        assert_eq!(
+            snapshot["ledger"]["ledger_entries"][0]["live_until"],
+            u64::from(u32::MAX)
+        );
+
+        // Read back, each instance gains the TTL it was given.
+        let read = SnapshotFile::from_json(&json).unwrap();
+        assert_eq!(read.ledger_sequence, Some(100));
+        assert_eq!(read.entries.len(), 4);
+        assert!(file
+            .entries
+            .iter()
+            .all(|(key, entry)| read.entries.get(key) == Some(entry)));
+    }
+}
@@ -0,0 +1,174 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! SIMD base64 decoding of snapshot entries.
+//!
+//! Built with the `simd-base64` feature on x86-64, [`decode`] turns 32
+//! characters into 24 bytes per step with AVX2 instructions when the CPU
+//! has them, after Muła and Lemire, "Faster Base64 Encoding and Decoding
+//! using AVX2 Instructions" (2018). The last block, which holds any padding,
+//! goes through the scalar `base64` engine, and so does any input with a
+//! character outside the alphabet, so the result and the errors are always
+//! the scalar engine's.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::{DecodeError, Engine as _};
+
+/// Whether [`decode`] uses SIMD instructions in this build on this CPU.
+pub fn available() -> bool {
+    #[cfg(all(feature = "simd-base64", target_arch = "x86_64"))]
+    {
+        std::is_x86_feature_detected!("avx2")
+    }
+    #[cfg(not(all(feature = "simd-base64", target_arch = "x86_64")))]
+    {
+        false
+    }
+}
+
+/// Decodes standard, padded base64, with SIMD instructions where
+/// [`available`].
+pub fn decode(input: &str) -> Result<Vec<u8>, DecodeError> {
+    #[cfg(all(feature = "simd-base64", target_arch = "x86_64"))]
+    if available() {
+        // SAFETY: the CPU has AVX2.
+        return unsafe { avx2::decode(input.as_bytes()) };
+    }
+    STANDARD.decode(input)
+}
+
+#[cfg(all(feature = "simd-base64", target_arch = "x86_64"))]
+mod avx2 {
+    use super::STANDARD;
+    use base64::{DecodeError, Engine as _};
+    use std::arch::x86_64::*;
+
+    /// Characters decoded per step.
+    const BLOCK: usize = 32;
+    /// Bytes they decode to.
+    const DECODED: usize = 24;
+
+    /// Decodes `input` a block at a time, leaving the last block, which
+    /// may be padded, to the scalar engine.
+    ///
+    /// # Safety
+    ///
+    /// The CPU must have AVX2.
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn decode(input: &[u8]) -> Result<Vec<u8>, DecodeError> {
+        let mut output = Vec::with_capacity(input.len() / 4 * 3);
+        let mut position = 0;
+        let mut decoded = [0u8; BLOCK];
+        while input.len() - position > BLOCK {
+            let block = _mm256_loadu_si256(input.as_ptr().add(position).cast());
+            let Some(bytes) = decode_block(block) else {
+                // The scalar engine names the offending character.
+                return STANDARD.decode(input);
+            };
+            _mm256_storeu_si256(decoded.as_mut_ptr().cast(), bytes);
+            output.extend_from_slice(&decoded[..DECODED]);
+            position += BLOCK;
+        }
+        if STANDARD
+            .decode_vec(&input[position..], &mut output)
+            .is_err()
+        {
+            return STANDARD.decode(input);
+        }
+        Ok(output)
+    }
+
+    /// Decodes 32 characters into the low 24 bytes of the result, or
+    /// returns `None` if one of them is outside the alphabet.
+    #[target_feature(enable = "avx2")]
+    unsafe fn decode_block(input: __m256i) -> Option<__m256i> {
+        // Each character is valid when the bits its low nibble selects from
+        // `lut_lo` and its high nibble from `lut_hi` do not overlap.
+        let lut_lo = _mm256_setr_epi8(
+            0x15, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x13, 0x1A, 0x1B, 0x1B,
+            0x1B, 0x1A, 0x15, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x13, 0x1A,
+            0x1B, 0x1B, 0x1B, 0x1A,
+        );
+        let lut_hi = _mm256_setr_epi8(
+            0x10, 0x10, 0x01, 0x02, 0x04, 0x08, 0x04, 0x08, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10,
+            0x10, 0x10, 0x10, 0x10, 0x01, 0x02, 0x04, 0x08, 0x04, 0x08, 0x10, 0x10, 0x10, 0x10,
+            0x10, 0x10, 0x10, 0x10,
+        );
+        // What to add to a character to get its value, by high nibble, with
+        // `/` moved to its own slot.
+        let lut_roll = _mm256_setr_epi8(
+            0, 16, 19, 4, -65, -65, -71, -71, 0, 0, 0, 0, 0, 0, 0, 0, 0, 16, 19, 4, -65, -65, -71,
+            -71, 0, 0, 0, 0, 0, 0, 0, 0,
+        );
+        let mask_2f = _mm256_set1_epi8(0x2F);
+
+        let hi_nibbles = _mm256_and_si256(_mm256_srli_epi32(input, 4), mask_2f);
+        let lo_nibbles = _mm256_and_si256(input, mask_2f);
+        let lo = _mm256_shuffle_epi8(lut_lo, lo_nibbles);
+        let hi = _mm256_shuffle_epi8(lut_hi, hi_nibbles);
+        if _mm256_testz_si256(lo, hi) == 0 {
+            return None;
+        }
+        let eq_2f = _mm256_cmpeq_epi8(input, mask_2f);
+        let roll = _mm256_shuffle_epi8(lut_roll, _mm256_add_epi8(eq_2f, hi_nibbles));
+        let values = _mm256_add_epi8(input, roll);
+
+        // Pack each four 6-bit values into three bytes, then the twelve
+        // bytes of each lane into the low 24.
+        let pairs = _mm256_maddubs_epi16(values, _mm256_set1_epi32(0x0140_0140));
+        let words = _mm256_madd_epi16(pairs, _mm256_set1_epi32(0x0001_1000));
+        let bytes = _mm256_shuffle_epi8(
+            words,
+            _mm256_setr_epi8(
+                2, 1, 0, 6, 5, 4, 10, 9, 8, 14, 13, 12, -1, -1, -1, -1, 2, 1, 0, 6, 5, 4, 10, 9, 8,
+                14, 13, 12, -1, -1, -1, -1,
+            ),
+        );
+        Some(_mm256_permutevar8x32_epi32(
+            bytes,
+            _mm256_setr_epi32(0, 1, 2, 4, 5, 6, -1, -1),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic bytes of every value.
+    fn bytes(len: usize, seed: u32) -> Vec<u8> {
+        (0..len)
+            .map(|i| (i as u32).wrapping_mul(2_654_435_761).wrapping_add(seed) as u8)
+            .collect()
+    }
+
+    #[test]
+    fn test_decode_matches_the_scalar_engine() {
+        for len in (0..200).chain([1_000, 4_099]) {
+            let encoded = STANDARD.encode(bytes(len, len as u32));
+            assert_eq!(
+                decode(&encoded).unwrap(),
+                STANDARD.decode(&encoded).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode_fails_like_the_scalar_engine() {
+        let encoded = STANDARD.encode(bytes(120, 7));
+        for (at, bad) in [(0, '*'), (5, ' '), (31, '='), (40, '\u{e9}'), (159, '-')] {
+            let mut broken = encoded.clone();
+            broken.replace_range(at..at + 1, &bad.to_string());
+            assert_eq!(
+                decode(&broken).unwrap_err(),
+                STANDARD.decode(&broken).unwrap_err(),
+                "{bad:?} at {at}"
+            );
+        }
+        let unpadded = &encoded[..encoded.len() - 2];
+        assert_eq!(
+            decode(unpadded).unwrap_err(),
+            STANDARD.decode(unpadded).unwrap_err()
+        );
+    }
+}
@@ -0,0 +1,284 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Solver mode: ranking transactions that reach the same goal.
+//!
+//! A request with `candidates` lists other ways of doing what its own
+//! transaction does, such as a swap along different routes, each as an
+//! envelope, an `invoke` call or an [`Intent`]. [`run`] simulates the request
+//! and then every candidate at the same ledger sequence and close time, all
+//! assembled, and reports in `solver` how each did, best first:
+//!
+//! 1. candidates that succeed before those that fail,
+//! 2. with `candidate_output`, the most of its asset received by its
+//!    address,
+//! 3. the lowest total fee,
+//! 4. the order they were given in.
+//!
+//! The recommendation is the best candidate that succeeds. The response is
+//! otherwise that of the request's own transaction, named `request` in the
+//! ranking.
+
+use crate::intents::{self, Intent};
+use crate::memory::MemoryLimits;
+use crate::network::Network;
+use crate::simulation::{self, SimulationError};
+use crate::snapshot::indexed::IndexedSnapshot;
+use crate::source::cache::CachedSource;
+use crate::types::{InvokeSpec, SimulationRequest, SimulationResponse};
+use crate::warm::HostPool;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Another transaction to rank against the request's own. Exactly one of
+/// `envelope_xdr`, `invoke` and `intent` is given.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct Candidate {
+    /// Name in the ranking. Defaults to `candidate <n>`, counting from 1.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Base64 `TransactionEnvelope`.
+    #[serde(default)]
+    pub envelope_xdr: String,
+    #[serde(default)]
+    pub invoke: Option<InvokeSpec>,
+    #[serde(default)]
+    pub intent: Option<Intent>,
+}
+
+/// The amount candidates are ranked by: how much of `asset` `address`
+/// receives.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct CandidateOutput {
+    /// Holder (`G...` or `C...`).
+    pub address: String,
+    /// `native`, `CODE:ISSUER` or a token contract (`C...`).
+    pub asset: String,
+}
+
+/// The candidates ranked, best first.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct SolverReport {
+    /// Name of the best candidate that succeeds, if any does.
+    pub recommended: Option<String>,
+    pub candidates: Vec<CandidateOutcome>,
+}
+
+/// How a candidate did.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct CandidateOutcome {
+    pub name: String,
+    /// `success`, `error`, or `invalid` for a candidate that could not be
+    /// simulated.
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Net amount of `candidate_output`'s asset its address received, in
+    /// the token's smallest unit, as a decimal string.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    /// Total fee of the assembled transaction, in stroops.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee: Option<i64>,
+    /// The resource fee within `fee`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource_fee: Option<i64>,
+    /// CPU instructions the run used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<u64>,
+    /// Base64 assembled `TransactionEnvelope`, unsigned.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub envelope_xdr: Option<String>,
+}
+
+/// Simulates `request` and its candidates at the same ledger, returning the
+/// request's response with the ranking in `solver`.
+pub fn run(
+    request: &SimulationRequest,
+    limits: &MemoryLimits,
+    base: Option<Arc<IndexedSnapshot>>,
+    remote: Option<Arc<CachedSource>>,
+    pool: Option<&HostPool>,
+) -> Result<SimulationResponse, SimulationError> {
+    let network = Network::resolve(
+        request.network.as_deref(),
+        request.network_passphrase.as_deref(),
+    )
+    .map_err(SimulationError::InvalidRequest)?;
+    let output = request
+        .candidate_output
+        .as_ref()
+        .map(|output| {
+            intents::token(&output.asset, &network)
+                .map(|token| (output.address.clone(), token.to_string()))
+                .map_err(|e| SimulationError::InvalidRequest(format!("candidate_output: {e}")))
+        })
+        .transpose()?;
+
+    let mut pinned = request.clone();
+    pinned.candidates = Vec::new();
+    pinned.include = None;
+    pinned.assemble = true;
+    pinned.ledger_close_time = Some(
+        request
+            .ledger_close_time
+            .unwrap_or_else(simulation::ledger_timestamp),
+    );
+    let mut response = simulation::run(&pinned, limits, base.clone(), remote.clone(), pool)?;
+    pinned.ledger_sequence = response.ledger_sequence;
+    pinned.compare_protocol = None;
+
+    let mut outcomes = vec![outcome("request", &response, output.as_ref())];
+    for (i, candidate) in request.candidates.iter().enumerate() {
+        let name = candidate
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("candidate {}", i + 1));
+        let mut alternative = pinned.clone();
+        alternative.envelope_xdr = candidate.envelope_xdr.clone();
+        alternative.invoke = candidate.invoke.clone();
+        alternative.intent = candidate.intent.clone();
+        let given = [
+            !alternative.envelope_xdr.is_empty(),
+            alternative.invoke.is_some(),
+            alternative.intent.is_some(),
+        ];
+        let result = if given.iter().filter(|&&given| given).count() == 1 {
+            simulation::run(&alternative, limits, base.clone(), remote.clone(), pool)
+        } else {
+            Err(SimulationError::InvalidRequest(
+                "give exactly one of envelope_xdr, invoke and intent".to_string(),
+            ))
+        };
+        outcomes.push(match result {
+            Ok(candidate_response) => outcome(&name, &candidate_response, output.as_ref()),
+            Err(e) => CandidateOutcome {
+                name,
+                status: "invalid".to_string(),
+                error: Some(e.to_string()),
+                output: None,
+                fee: None,
+                resource_fee: None,
+                instructions: None,
+                envelope_xdr: None,
+            },
+        });
+    }
+    response.solver = Some(rank(outcomes));
+    Ok(response)
+}
+
+/// How the run that produced `response` did, with what `output`, an
+/// address and token contract, received.
+fn outcome(
+    name: &str,
+    response: &SimulationResponse,
+    output: Option<&(String, String)>,
+) -> CandidateOutcome {
+    let succeeded = response.status == "success";
+    let received = output.filter(|_| succeeded).map(|(address, token)| {
+        response
+            .balance_changes
+            .iter()
+            .filter(|change| &change.address == address && &change.token == token)
+            .filter_map(|change| change.amount.parse::<i128>().ok())
+            .sum::<i128>()
+            .to_string()
+    });
+    CandidateOutcome {
+        name: name.to_string(),
+        status: response.status.clone(),
+        error: response.error.clone(),
+        output: received,
+        fee: response.assembled.as_ref().map(|assembled| assembled.fee),
+        resource_fee: response
+            .assembled
+            .as_ref()
+            .map(|assembled| assembled.resource_fee),
+        instructions: response
+            .budget_usage
+            .as_ref()
+            .map(|budget| budget.cpu_instructions),
+        envelope_xdr: response
+            .assembled
+            .as_ref()
+            .map(|assembled| assembled.envelope_xdr.clone()),
+    }
+}
+
+/// `outcomes` best first, with the recommendation.
+pub fn rank(mut outcomes: Vec<CandidateOutcome>) -> SolverReport {
+    let output = |outcome: &CandidateOutcome| {
+        outcome
+            .output
+            .as_deref()
+            .and_then(|output| output.parse::<i128>().ok())
+    };
+    // A stable sort keeps the given order among equals.
+    outcomes.sort_by(|a, b| {
+        (b.status == "success")
+            .cmp(&(a.status == "success"))
+            .then_with(|| output(b).cmp(&output(a)))
+            .then_with(|| a.fee.unwrap_or(i64::MAX).cmp(&b.fee.unwrap_or(i64::MAX)))
+    });
+    SolverReport {
+        recommended: outcomes
+            .first()
+            .filter(|best| best.status == "success")
+            .map(|best| best.name.clone()),
+        candidates: outcomes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(name: &str, status: &str, output: Option<i128>, fee: i64) -> CandidateOutcome {
+        CandidateOutcome {
+            name: name.to_string(),
+            status: status.to_string(),
+            error: None,
+            output: output.map(|output| output.to_string()),
+            fee: (status == "success").then_some(fee),
+            resource_fee: None,
+            instructions: None,
+            envelope_xdr: None,
+        }
+    }
+
+    fn names(report: &SolverReport) -> Vec<&str> {
+        report.candidates.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_ranks_by_success_output_then_fee() {
+        let report = rank(vec![
+            candidate("request", "success", Some(90), 100),
+            candidate("failing", "error", None, 0),
+            candidate("cheap", "success", Some(95), 50),
+            candidate("rich", "success", Some(95), 80),
+            candidate("best", "success", Some(100), 500),
+        ]);
+        assert_eq!(report.recommended.as_deref(), Some("best"));
+        assert_eq!(
+            names(&report),
+            ["best", "cheap", "rich", "request", "failing"]
+        );
+
+        // Without an output, the cheapest wins, ties keeping their order.
+        let report = rank(vec![
+            candidate("request", "success", None, 300),
+            candidate("a", "success", None, 200),
+            candidate("b", "success", None, 200),
+        ]);
+        assert_eq!(names(&report), ["a", "b", "request"]);
+
+        let report = rank(vec![
+            candidate("request", "error", None, 0),
+            candidate("broken", "invalid", None, 0),
+        ]);
+        assert_eq!(report.recommended, None);
+    }
+}
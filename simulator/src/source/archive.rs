@@ -0,0 +1,304 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Ledger state read from a history archive checkpoint.
+//!
+//! A history archive publishes, every 64 ledgers, a history archive state
+//! (HAS) naming the bucket list as of that checkpoint, and the buckets
+//! themselves as gzipped files. [`ArchiveSource`] reads the HAS for one
+//! checkpoint and resolves lookups against its buckets newest first, like
+//! [`super::bucket::BucketListSource`], so transactions can be replayed
+//! against historical state without running a node.
+//!
+//! Buckets are downloaded only when a lookup reaches them: a key found in
+//! (or deleted by) a shallow level never pulls the deeper, larger buckets.
+//! Downloads are checked against the bucket's SHA-256 hash and kept in a
+//! cache directory, where they are indexed and searched in place.
+
+use super::bucket::{Bucket, HistoryArchiveState};
+use super::{LedgerSource, SourceEntry, SourceError};
+use sha2::{Digest, Sha256};
+use soroban_env_host::xdr::{LedgerEntry, LedgerKey};
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Ledgers between checkpoints.
+const CHECKPOINT_FREQUENCY: u32 = 64;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Deep buckets run to gigabytes, so only stalls are timed out.
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A bucket named by the HAS, downloaded on first use.
+struct LazyBucket {
+    hash: String,
+    bucket: Mutex<Option<Arc<Bucket>>>,
+}
+
+/// Serves entries from a history archive checkpoint.
+pub struct ArchiveSource {
+    url: String,
+    cache_dir: PathBuf,
+    agent: ureq::Agent,
+    checkpoint: u32,
+    /// Buckets newest first.
+    buckets: Vec<LazyBucket>,
+}
+
+impl ArchiveSource {
+    /// Reads the HAS for `checkpoint` from the archive at `url`, keeping
+    /// downloaded buckets in `cache_dir`.
+    pub fn open(url: &str, checkpoint: u32, cache_dir: &Path) -> Result<Self, SourceError> {
+        check_checkpoint(checkpoint)?;
+        std::fs::create_dir_all(cache_dir).map_err(|e| {
+            SourceError::Unavailable(format!("failed to create {}: {e}", cache_dir.display()))
+        })?;
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(CONNECT_TIMEOUT)
+            .timeout_read(READ_TIMEOUT)
+            .build();
+        let url = url.trim_end_matches('/').to_string();
+        let hex = format!("{checkpoint:08x}");
+        let has_url = format!("{url}/history/{}/history-{hex}.json", fan_out(&hex));
+        let mut bytes = Vec::new();
+        agent
+            .get(&has_url)
+            .call()
+            .map_err(|e| SourceError::Unavailable(format!("{has_url}: {e}")))?
+            .into_reader()
+            .read_to_end(&mut bytes)
+            .map_err(|e| SourceError::Unavailable(format!("{has_url}: {e}")))?;
+        let has = HistoryArchiveState::parse(&bytes, &has_url)?;
+        if has.current_ledger != checkpoint {
+            return Err(SourceError::InvalidResponse(format!(
+                "{has_url} describes ledger {}, not {checkpoint}",
+                has.current_ledger
+            )));
+        }
+        let buckets = has
+            .bucket_hashes()
+            .map(|hash| {
+                if hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    Ok(LazyBucket {
+                        hash: hash.to_ascii_lowercase(),
+                        bucket: Mutex::new(None),
+                    })
+                } else {
+                    Err(SourceError::InvalidResponse(format!(
+                        "{has_url} names an invalid bucket hash {hash:?}"
+                    )))
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        tracing::info!(
+            event = "archive_checkpoint_opened",
+            url = %url,
+            checkpoint,
+            buckets = buckets.len(),
+        );
+        Ok(Self {
+            url,
+            cache_dir: cache_dir.to_path_buf(),
+            agent,
+            checkpoint,
+            buckets,
+        })
+    }
+
+    /// Looks up `key`, with its TTL for contract entries.
+    pub fn get(&self, key: &LedgerKey) -> Result<Option<SourceEntry>, SourceError> {
+        let Some(entry) = self.resolve(&super::encode_key(key)?)? else {
+            return Ok(None);
+        };
+        let ttl = if super::has_ttl(key) {
+            self.resolve(&super::encode_key(&super::ttl_key(key)?)?)?
+        } else {
+            None
+        };
+        Ok(Some(SourceEntry {
+            live_until: super::live_until(key, ttl.as_ref()),
+            entry,
+        }))
+    }
+
+    /// Returns the newest live entry stored under `key` XDR, downloading
+    /// buckets until one mentions it.
+    fn resolve(&self, key: &[u8]) -> Result<Option<LedgerEntry>, SourceError> {
+        for lazy in &self.buckets {
+            if let Some(found) = self.bucket(lazy)?.lookup(key)? {
+                return Ok(found);
+            }
+        }
+        Ok(None)
+    }
+
+    fn bucket(&self, lazy: &LazyBucket) -> Result<Arc<Bucket>, SourceError> {
+        let mut slot = lazy.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(bucket) = slot.as_ref() {
+            return Ok(Arc::clone(bucket));
+        }
+        let path = self.cache_dir.join(format!("bucket-{}.xdr", lazy.hash));
+        if !path.exists() {
+            self.download(&lazy.hash, &path)?;
+        }
+        let bucket = Arc::new(Bucket::open(&path)?);
+        tracing::info!(
+            event = "archive_bucket_loaded",
+            hash = %lazy.hash,
+            records = bucket.len(),
+        );
+        *slot = Some(Arc::clone(&bucket));
+        Ok(bucket)
+    }
+
+    /// Downloads and unzips bucket `hash` to `path`, verifying its hash.
+    fn download(&self, hash: &str, path: &Path) -> Result<(), SourceError> {
+        let url = format!("{}/bucket/{}/bucket-{hash}.xdr.gz", self.url, fan_out(hash));
+        let response = self
+            .agent
+            .get(&url)
+            .call()
+            .map_err(|e| SourceError::Unavailable(format!("{url}: {e}")))?;
+        let tmp = path.with_extension("tmp");
+        let copy = || -> std::io::Result<String> {
+            let mut reader = flate2::read::GzDecoder::new(response.into_reader());
+            let mut writer = BufWriter::new(File::create(&tmp)?);
+            let mut hasher = Sha256::new();
+            let mut buffer = vec![0u8; 64 * 1024];
+            loop {
+                let n = reader.read(&mut buffer)?;
+                if n == 0 {
+                    break;
+                }
+                hasher.update(&buffer[..n]);
+                writer.write_all(&buffer[..n])?;
+            }
+            writer.into_inner()?.sync_all()?;
+            Ok(hasher
+                .finalize()
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect())
+        };
+        let result = match copy() {
+            Ok(actual) if actual == hash => std::fs::rename(&tmp, path)
+                .map_err(|e| SourceError::Unavailable(format!("{}: {e}", path.display()))),
+            Ok(actual) => Err(SourceError::InvalidResponse(format!(
+                "{url} hashes to {actual}"
+            ))),
+            Err(e) => Err(SourceError::Unavailable(format!("{url}: {e}"))),
+        };
+        if result.is_err() {
+            let _ = std::fs::remove_file(&tmp);
+        }
+        result
+    }
+}
+
+impl LedgerSource for ArchiveSource {
+    fn name(&self) -> &str {
+        "history-archive"
+    }
+
+    fn latest_ledger(&self) -> Result<u32, SourceError> {
+        Ok(self.checkpoint)
+    }
+
+    fn fetch(&self, keys: &[LedgerKey]) -> Result<Vec<Option<SourceEntry>>, SourceError> {
+        keys.iter().map(|key| self.get(key)).collect()
+    }
+}
+
+/// Rejects ledgers that are not checkpoints, naming the next one.
+fn check_checkpoint(ledger: u32) -> Result<(), SourceError> {
+    if ledger % CHECKPOINT_FREQUENCY == CHECKPOINT_FREQUENCY - 1 {
+        return Ok(());
+    }
+    Err(SourceError::Unavailable(format!(
+        "ledger {ledger} is not a checkpoint; the next checkpoint is {}",
+        ledger | (CHECKPOINT_FREQUENCY - 1)
+    )))
+}
+
+/// The `aa/bb/cc` directory prefix archives file a hex name under.
+fn fan_out(hex: &str) -> String {
+    format!("{}/{}/{}", &hex[0..2], &hex[2..4], &hex[4..6])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::fixtures::{account, gzipped_bucket};
+    use soroban_env_host::xdr::BucketEntry;
+    use std::collections::HashMap;
+
+    /// Serves `files` from a background thread, returning the base URL.
+    /// Other paths get 404.
+    fn serve(files: HashMap<String, Vec<u8>>) -> String {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", server.server_addr().to_ip().unwrap());
+        std::thread::spawn(move || {
+            for request in server.incoming_requests() {
+                let response = match files.get(request.url()) {
+                    Some(body) => tiny_http::Response::from_data(body.clone()),
+                    None => tiny_http::Response::from_data(Vec::new()).with_status_code(404),
+                };
+                let _ = request.respond(response);
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_buckets_are_downloaded_only_when_reached() {
+        let (key1, entry1) = account(1, 1, 100);
+        let (key2, entry2) = account(2, 2, 100);
+        let (newest, newest_gz) = gzipped_bucket(&[BucketEntry::Liveentry(entry1.clone())]);
+        let (oldest, oldest_gz) = gzipped_bucket(&[
+            BucketEntry::Liveentry(account(1, 1, 100).1),
+            BucketEntry::Liveentry(entry2.clone()),
+        ]);
+        let has = format!(
+            r#"{{"currentLedger":127,"currentBuckets":[{{"curr":"{newest}","snap":"{oldest}"}}]}}"#
+        );
+        let mut files = HashMap::new();
+        files.insert(
+            "/history/00/00/00/history-0000007f.json".to_string(),
+            has.into_bytes(),
+        );
+        files.insert(
+            format!("/bucket/{}/bucket-{newest}.xdr.gz", fan_out(&newest)),
+            newest_gz,
+        );
+        files.insert(
+            format!("/bucket/{}/bucket-{oldest}.xdr.gz", fan_out(&oldest)),
+            oldest_gz,
+        );
+        let url = serve(files);
+
+        let cache = std::env::temp_dir().join(format!("erst-archive-{}", std::process::id()));
+        let source = ArchiveSource::open(&url, 127, &cache).unwrap();
+        assert_eq!(source.latest_ledger().unwrap(), 127);
+
+        let found = source.fetch(&[key1]).unwrap();
+        assert_eq!(found[0].as_ref().unwrap().entry, entry1);
+        assert!(cache.join(format!("bucket-{newest}.xdr")).exists());
+        assert!(!cache.join(format!("bucket-{oldest}.xdr")).exists());
+
+        let found = source.fetch(&[key2]).unwrap();
+        assert_eq!(found[0].as_ref().unwrap().entry, entry2);
+        assert!(cache.join(format!("bucket-{oldest}.xdr")).exists());
+
+        std::fs::remove_dir_all(&cache).unwrap();
+    }
+
+    #[test]
+    fn test_checkpoints_are_validated() {
+        assert!(check_checkpoint(63).is_ok());
+        assert!(check_checkpoint(127).is_ok());
+        let err = check_checkpoint(100).unwrap_err().to_string();
+        assert!(err.contains("next checkpoint is 127"), "{err}");
+    }
+}
@@ -0,0 +1,344 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Ledger state read directly from stellar-core bucket files.
+//!
+//! An operator running stellar-core (or captive-core) already has the full
+//! ledger on disk as the bucket list: `bucket-<hash>.xdr` files, each a
+//! sorted stream of `BucketEntry` records. [`BucketListSource`] maps the
+//! buckets named by a history archive state (HAS) and resolves every lookup
+//! against them newest first, so no export step is needed.
+//!
+//! The HAS lists, for each level, a `curr` and a `snap` bucket; level 0
+//! `curr` holds the most recent changes. The first bucket holding a key
+//! decides it: a `LIVEENTRY` or `INITENTRY` is the current entry and a
+//! `DEADENTRY` means it was deleted. Buckets are indexed by key hash when
+//! opened and searched in place afterwards.
+//!
+//! Only the live bucket list is read. Entries evicted to the hot archive are
+//! reported as absent.
+
+use super::{LedgerSource, SourceEntry, SourceError};
+use crate::snapshot;
+use crate::xdr_limits;
+use memmap2::Mmap;
+use serde::Deserialize;
+use soroban_env_host::xdr::{BucketEntry, LedgerEntry, LedgerKey};
+use std::collections::hash_map::DefaultHasher;
+use std::fs::File;
+use std::hash::{Hash as _, Hasher};
+use std::path::{Path, PathBuf};
+
+/// High bit of an XDR record mark, set on the last fragment of a record.
+pub(super) const LAST_FRAGMENT: u32 = 0x8000_0000;
+/// Hash stellar-core uses for an empty bucket.
+const EMPTY_BUCKET: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// The parts of a history archive state the source needs.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct HistoryArchiveState {
+    pub current_ledger: u32,
+    current_buckets: Vec<BucketLevel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BucketLevel {
+    curr: String,
+    snap: String,
+}
+
+impl HistoryArchiveState {
+    /// Parses HAS JSON read from `origin`.
+    pub fn parse(bytes: &[u8], origin: &str) -> Result<Self, SourceError> {
+        serde_json::from_slice(bytes).map_err(|e| {
+            SourceError::InvalidResponse(format!("invalid history archive state {origin}: {e}"))
+        })
+    }
+
+    /// Hashes of the non-empty buckets, newest first.
+    pub fn bucket_hashes(&self) -> impl Iterator<Item = &str> {
+        self.current_buckets
+            .iter()
+            .flat_map(|level| [level.curr.as_str(), level.snap.as_str()])
+            .filter(|hash| *hash != EMPTY_BUCKET)
+    }
+}
+
+/// One mapped bucket file with its key index.
+pub(super) struct Bucket {
+    path: PathBuf,
+    map: Mmap,
+    /// `(key hash, record offset)`, sorted by hash.
+    index: Vec<(u64, usize)>,
+}
+
+/// Serves entries from a stellar-core bucket list.
+pub struct BucketListSource {
+    /// Buckets newest first.
+    buckets: Vec<Bucket>,
+    ledger_sequence: u32,
+}
+
+impl BucketListSource {
+    /// Opens the bucket list described by the HAS JSON at `state`, reading
+    /// bucket files from `dir` (or its `buckets` subdirectory, where
+    /// stellar-core keeps them by default).
+    pub fn open(dir: &Path, state: &Path) -> Result<Self, SourceError> {
+        let bytes = std::fs::read(state).map_err(|e| unavailable(state, &e.to_string()))?;
+        let has = HistoryArchiveState::parse(&bytes, &state.display().to_string())?;
+        let bucket_dir = if dir.join("buckets").is_dir() {
+            dir.join("buckets")
+        } else {
+            dir.to_path_buf()
+        };
+        let buckets = has
+            .bucket_hashes()
+            .map(|hash| Bucket::open(&bucket_dir.join(format!("bucket-{hash}.xdr"))))
+            .collect::<Result<Vec<_>, _>>()?;
+        tracing::info!(
+            event = "bucket_list_opened",
+            path = %bucket_dir.display(),
+            ledger_sequence = has.current_ledger,
+            buckets = buckets.len(),
+            records = buckets.iter().map(Bucket::len).sum::<usize>(),
+        );
+        Ok(Self {
+            buckets,
+            ledger_sequence: has.current_ledger,
+        })
+    }
+
+    /// Looks up `key`, with its TTL for contract entries.
+    pub fn get(&self, key: &LedgerKey) -> Result<Option<SourceEntry>, SourceError> {
+        let Some(entry) = self.resolve(&super::encode_key(key)?)? else {
+            return Ok(None);
+        };
+        let ttl = if super::has_ttl(key) {
+            self.resolve(&super::encode_key(&super::ttl_key(key)?)?)?
+        } else {
+            None
+        };
+        Ok(Some(SourceEntry {
+            live_until: super::live_until(key, ttl.as_ref()),
+            entry,
+        }))
+    }
+
+    /// Returns the newest live entry stored under `key` XDR.
+    fn resolve(&self, key: &[u8]) -> Result<Option<LedgerEntry>, SourceError> {
+        for bucket in &self.buckets {
+            if let Some(found) = bucket.lookup(key)? {
+                return Ok(found);
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl LedgerSource for BucketListSource {
+    fn name(&self) -> &str {
+        "bucket-list"
+    }
+
+    fn latest_ledger(&self) -> Result<u32, SourceError> {
+        Ok(self.ledger_sequence)
+    }
+
+    fn fetch(&self, keys: &[LedgerKey]) -> Result<Vec<Option<SourceEntry>>, SourceError> {
+        keys.iter().map(|key| self.get(key)).collect()
+    }
+}
+
+impl Bucket {
+    /// Maps the bucket at `path` and indexes its records.
+    pub fn open(path: &Path) -> Result<Self, SourceError> {
+        let file = File::open(path).map_err(|e| unavailable(path, &e.to_string()))?;
+        // SAFETY: the map is read-only, and stellar-core never rewrites a
+        // bucket file in place: buckets are named by their content hash and
+        // only ever created or deleted as a whole.
+        let map = unsafe { Mmap::map(&file) }.map_err(|e| unavailable(path, &e.to_string()))?;
+        let mut bucket = Self {
+            path: path.to_path_buf(),
+            map,
+            index: Vec::new(),
+        };
+        let mut offset = 0;
+        while offset < bucket.map.len() {
+            let (next, record) = bucket.record(offset)?;
+            let entry = bucket.decode(record)?;
+            if let Some(key) = record_key(record, &entry)? {
+                bucket.index.push((key_hash(&key), offset));
+            }
+            offset = next;
+        }
+        bucket.index.sort_unstable();
+        Ok(bucket)
+    }
+
+    /// Looks up `key` XDR. The outer `Option` is `None` when the bucket does
+    /// not mention the key; the inner one is `None` when it deletes it.
+    pub fn lookup(&self, key: &[u8]) -> Result<Option<Option<LedgerEntry>>, SourceError> {
+        let hash = key_hash(key);
+        let start = self.index.partition_point(|(h, _)| *h < hash);
+        for &(h, offset) in &self.index[start..] {
+            if h != hash {
+                break;
+            }
+            let (_, record) = self.record(offset)?;
+            let entry = self.decode(record)?;
+            if record_key(record, &entry)?.as_deref() != Some(key) {
+                continue;
+            }
+            return Ok(Some(match entry {
+                BucketEntry::Liveentry(entry) | BucketEntry::Initentry(entry) => Some(entry),
+                _ => None,
+            }));
+        }
+        Ok(None)
+    }
+
+    /// Returns the offset of the following record and the XDR of the
+    /// record at `offset`.
+    fn record(&self, offset: usize) -> Result<(usize, &[u8]), SourceError> {
+        let mark = self
+            .map
+            .get(offset..offset + 4)
+            .map(|b| u32::from_be_bytes(b.try_into().expect("4 bytes")))
+            .ok_or_else(|| self.corrupt("truncated record mark"))?;
+        if mark & LAST_FRAGMENT == 0 {
+            return Err(self.corrupt("fragmented records are not supported"));
+        }
+        let start = offset + 4;
+        let end = start + (mark & !LAST_FRAGMENT) as usize;
+        let record = self
+            .map
+            .get(start..end)
+            .ok_or_else(|| self.corrupt("truncated record"))?;
+        Ok((end, record))
+    }
+
+    fn decode(&self, record: &[u8]) -> Result<BucketEntry, SourceError> {
+        xdr_limits::read::<BucketEntry>(record)
+            .map_err(|e| self.corrupt(&format!("invalid BucketEntry XDR: {e}")))
+    }
+
+    /// Number of records, excluding metadata.
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    fn corrupt(&self, msg: &str) -> SourceError {
+        SourceError::InvalidResponse(format!("corrupt bucket {}: {msg}", self.path.display()))
+    }
+}
+
+/// The key XDR of a bucket record; `None` for the metadata record.
+fn record_key(record: &[u8], entry: &BucketEntry) -> Result<Option<Vec<u8>>, SourceError> {
+    match entry {
+        BucketEntry::Liveentry(entry) | BucketEntry::Initentry(entry) => {
+            super::encode_key(&snapshot::entry_key(entry)).map(Some)
+        }
+        // The record is the 4-byte discriminant followed by the key XDR.
+        BucketEntry::Deadentry(_) => Ok(Some(record[4..].to_vec())),
+        BucketEntry::Metaentry(_) => Ok(None),
+    }
+}
+
+fn key_hash(key: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn unavailable(path: &Path, msg: &str) -> SourceError {
+    SourceError::Unavailable(format!("failed to read {}: {msg}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::fixtures::{account, bucket};
+    use soroban_env_host::xdr::{BucketMetadata, BucketMetadataExt};
+
+    fn write_bucket(dir: &Path, hash: &str, entries: &[BucketEntry]) {
+        std::fs::write(dir.join(format!("bucket-{hash}.xdr")), bucket(entries)).unwrap();
+    }
+
+    #[test]
+    fn test_newest_bucket_decides_each_key() {
+        let dir = std::env::temp_dir().join(format!("erst-buckets-{}", std::process::id()));
+        let buckets = dir.join("buckets");
+        std::fs::create_dir_all(&buckets).unwrap();
+        let meta = BucketEntry::Metaentry(BucketMetadata {
+            ledger_version: 23,
+            ext: BucketMetadataExt::V0,
+        });
+
+        let (key1, old1) = account(1, 100, 10);
+        let (key2, old2) = account(2, 200, 10);
+        let (key3, old3) = account(3, 300, 10);
+        let (_, new2) = account(2, 250, 10);
+        write_bucket(
+            &buckets,
+            &"b".repeat(64),
+            &[
+                meta.clone(),
+                BucketEntry::Liveentry(old1),
+                BucketEntry::Liveentry(old2),
+                BucketEntry::Liveentry(old3.clone()),
+            ],
+        );
+        write_bucket(
+            &buckets,
+            &"a".repeat(64),
+            &[
+                meta,
+                BucketEntry::Deadentry(key1.clone()),
+                BucketEntry::Initentry(new2.clone()),
+            ],
+        );
+        let state = dir.join("has.json");
+        std::fs::write(
+            &state,
+            format!(
+                r#"{{"version":2,"currentLedger":64,"currentBuckets":[
+                    {{"curr":"{}","next":{{"state":0}},"snap":"{EMPTY_BUCKET}"}},
+                    {{"curr":"{}","next":{{"state":0}},"snap":"{EMPTY_BUCKET}"}}]}}"#,
+                "a".repeat(64),
+                "b".repeat(64)
+            ),
+        )
+        .unwrap();
+
+        let source = BucketListSource::open(&dir, &state).unwrap();
+        assert_eq!(source.latest_ledger().unwrap(), 64);
+        let (missing, _) = account(4, 0, 10);
+        let found = source.fetch(&[key1, key2, key3, missing]).unwrap();
+        assert!(found[0].is_none(), "deleted in the newer bucket");
+        assert_eq!(found[1].as_ref().unwrap().entry, new2);
+        assert_eq!(found[2].as_ref().unwrap().entry, old3);
+        assert!(found[3].is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_missing_bucket_is_reported() {
+        let dir = std::env::temp_dir().join(format!("erst-buckets-missing-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let state = dir.join("has.json");
+        std::fs::write(
+            &state,
+            format!(
+                r#"{{"currentLedger":1,"currentBuckets":[{{"curr":"{}","snap":"{EMPTY_BUCKET}"}}]}}"#,
+                "c".repeat(64)
+            ),
+        )
+        .unwrap();
+        let err = BucketListSource::open(&dir, &state).err().unwrap();
+        assert!(err.to_string().contains("bucket-ccc"), "{err}");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,439 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Bounded LRU cache in front of a remote [`LedgerSource`].
+//!
+//! Entries are keyed by ledger key and the ledger sequence they were read
+//! at, so a simulation pinned to one ledger never sees state from another.
+//! Absent entries are cached too: a contract probing for a missing key is as
+//! common as one reading an existing key.
+//!
+//! An optional [`DiskCache`] sits between the in-memory tier and the backend
+//! so entries survive restarts.
+//!
+//! [`CachedSource::prefetch`] loads a batch of keys (a transaction's declared
+//! footprint) concurrently before execution, so the host's reads are served
+//! from the cache instead of reaching the backend one at a time.
+
+use super::disk::DiskCache;
+use super::{encode_key, LedgerSource, SourceEntry, SourceError};
+use lru::LruCache;
+use schemars::JsonSchema;
+use serde::Serialize;
+use soroban_env_host::xdr::LedgerKey;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Default number of cached entries.
+pub const DEFAULT_CACHE_ENTRIES: usize = 10_000;
+/// Maximum number of concurrent backend calls while prefetching.
+const PREFETCH_CONCURRENCY: usize = 8;
+
+/// Reads the cache size from `ERST_ENTRY_CACHE_SIZE`, falling back to
+/// `default` when unset or unparsable.
+pub fn capacity_from_env(default: usize) -> usize {
+    std::env::var("ERST_ENTRY_CACHE_SIZE")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(default)
+}
+
+/// Reads the on-disk cache directory from `ERST_ENTRY_CACHE_DIR`, falling
+/// back to `default` when unset.
+pub fn dir_from_env(default: Option<PathBuf>) -> Option<PathBuf> {
+    std::env::var_os("ERST_ENTRY_CACHE_DIR")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or(default)
+}
+
+/// Cache statistics reported with a simulation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct CacheStats {
+    /// Lookups answered from the cache (either tier).
+    pub hits: u64,
+    /// Lookups answered from the on-disk tier.
+    pub disk_hits: u64,
+    /// Lookups that went to the backend.
+    pub misses: u64,
+    /// Entries currently cached.
+    pub entries: usize,
+    /// Maximum number of cached entries.
+    pub capacity: usize,
+}
+
+/// Whether a lookup was served from the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheOutcome {
+    Hit,
+    DiskHit,
+    Miss,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    key_xdr: Vec<u8>,
+    ledger_sequence: u32,
+}
+
+/// A [`LedgerSource`] with an LRU cache shared by all simulations.
+pub struct CachedSource {
+    source: Arc<dyn LedgerSource>,
+    entries: Option<Mutex<LruCache<CacheKey, Option<SourceEntry>>>>,
+    capacity: usize,
+    disk: Option<DiskCache>,
+    hits: AtomicU64,
+    disk_hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl CachedSource {
+    /// Caches up to `capacity` entries from `source` (0 disables caching).
+    pub fn new(source: Arc<dyn LedgerSource>, capacity: usize) -> Self {
+        Self {
+            source,
+            entries: NonZeroUsize::new(capacity).map(|cap| Mutex::new(LruCache::new(cap))),
+            capacity,
+            disk: None,
+            hits: AtomicU64::new(0),
+            disk_hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Adds a persistent tier consulted before the backend.
+    pub fn with_disk(mut self, disk: DiskCache) -> Self {
+        self.disk = Some(disk);
+        self
+    }
+
+    /// Name of the underlying source.
+    pub fn name(&self) -> &str {
+        self.source.name()
+    }
+
+    /// The ledger sequence the underlying source currently reflects.
+    pub fn latest_ledger(&self) -> Result<u32, SourceError> {
+        self.source.latest_ledger()
+    }
+
+    /// Looks up `key` as of `ledger_sequence`.
+    pub fn fetch(
+        &self,
+        key: &LedgerKey,
+        ledger_sequence: u32,
+    ) -> Result<(Option<SourceEntry>, CacheOutcome), SourceError> {
+        let cache_key = CacheKey {
+            key_xdr: encode_key(key)?,
+            ledger_sequence,
+        };
+
+        if let Some(entries) = &self.entries {
+            let mut entries = entries.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(found) = entries.get(&cache_key) {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok((found.clone(), CacheOutcome::Hit));
+            }
+        }
+
+        if let Some(found) = self
+            .disk
+            .as_ref()
+            .and_then(|disk| disk.get(&cache_key.key_xdr, ledger_sequence))
+        {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            self.disk_hits.fetch_add(1, Ordering::Relaxed);
+            self.remember(cache_key, found.clone());
+            return Ok((found, CacheOutcome::DiskHit));
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let found = self
+            .source
+            .fetch(std::slice::from_ref(key))?
+            .into_iter()
+            .next()
+            .flatten();
+        if let Some(disk) = &self.disk {
+            disk.put(&cache_key.key_xdr, ledger_sequence, found.as_ref());
+        }
+        self.remember(cache_key, found.clone());
+        Ok((found, CacheOutcome::Miss))
+    }
+
+    /// Fetches the uncached `keys` as of `ledger_sequence`, splitting them
+    /// across up to [`PREFETCH_CONCURRENCY`] concurrent backend calls, and
+    /// caches the results. Returns the number of keys fetched.
+    ///
+    /// Does nothing when caching is disabled. Batches that fail are not
+    /// cached; the first failure is returned after the others are stored.
+    pub fn prefetch(&self, keys: &[LedgerKey], ledger_sequence: u32) -> Result<usize, SourceError> {
+        if self.entries.is_none() && self.disk.is_none() {
+            return Ok(0);
+        }
+        let mut missing = Vec::new();
+        for key in keys {
+            let cache_key = CacheKey {
+                key_xdr: encode_key(key)?,
+                ledger_sequence,
+            };
+            if !self.is_cached(&cache_key) && !missing.iter().any(|(_, k)| *k == cache_key) {
+                missing.push((key.clone(), cache_key));
+            }
+        }
+        if missing.is_empty() {
+            return Ok(0);
+        }
+
+        let chunk_size = missing.len().div_ceil(PREFETCH_CONCURRENCY);
+        let results = std::thread::scope(|scope| {
+            let batches = missing
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    let keys = chunk.iter().map(|(key, _)| key.clone()).collect::<Vec<_>>();
+                    scope.spawn(move || self.source.fetch(&keys))
+                })
+                .collect::<Vec<_>>();
+            batches
+                .into_iter()
+                .map(|batch| {
+                    batch.join().unwrap_or_else(|_| {
+                        Err(SourceError::Unavailable("prefetch worker panicked".into()))
+                    })
+                })
+                .collect::<Vec<_>>()
+        });
+
+        let mut first_error = None;
+        for (chunk, result) in missing.chunks(chunk_size).zip(results) {
+            match result {
+                Ok(found) => {
+                    for ((_, cache_key), found) in chunk.iter().zip(found) {
+                        if let Some(disk) = &self.disk {
+                            disk.put(&cache_key.key_xdr, ledger_sequence, found.as_ref());
+                        }
+                        self.remember(cache_key.clone(), found);
+                    }
+                }
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        self.misses
+            .fetch_add(missing.len() as u64, Ordering::Relaxed);
+        first_error.map_or(Ok(missing.len()), Err)
+    }
+
+    /// Whether `cache_key` is in either tier, promoting disk records into
+    /// memory.
+    fn is_cached(&self, cache_key: &CacheKey) -> bool {
+        if let Some(entries) = &self.entries {
+            let entries = entries.lock().unwrap_or_else(|e| e.into_inner());
+            if entries.contains(cache_key) {
+                return true;
+            }
+        }
+        let on_disk = self
+            .disk
+            .as_ref()
+            .and_then(|disk| disk.get(&cache_key.key_xdr, cache_key.ledger_sequence));
+        match on_disk {
+            Some(found) => {
+                self.remember(cache_key.clone(), found);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remember(&self, cache_key: CacheKey, found: Option<SourceEntry>) {
+        if let Some(entries) = &self.entries {
+            entries
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .put(cache_key, found);
+        }
+    }
+
+    /// Number of cached entries.
+    pub fn len(&self) -> usize {
+        self.entries
+            .as_ref()
+            .map_or(0, |e| e.lock().unwrap_or_else(|e| e.into_inner()).len())
+    }
+
+    /// Maximum number of cached entries.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Statistics accumulated over the cache's lifetime.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            disk_hits: self.disk_hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.len(),
+            capacity: self.capacity,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::xdr::{
+        AccountEntry, AccountId, LedgerEntry, LedgerEntryData, LedgerKeyAccount, PublicKey,
+        SequenceNumber, Thresholds, Uint256,
+    };
+    use std::sync::atomic::AtomicUsize;
+
+    /// Source that answers every key with an account entry and counts calls.
+    #[derive(Default)]
+    struct CountingSource {
+        calls: AtomicUsize,
+    }
+
+    impl LedgerSource for CountingSource {
+        fn name(&self) -> &str {
+            "counting"
+        }
+
+        fn latest_ledger(&self) -> Result<u32, SourceError> {
+            Ok(1)
+        }
+
+        fn fetch(&self, keys: &[LedgerKey]) -> Result<Vec<Option<SourceEntry>>, SourceError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(keys
+                .iter()
+                .map(|key| {
+                    let LedgerKey::Account(LedgerKeyAccount { account_id }) = key else {
+                        return None;
+                    };
+                    Some(SourceEntry {
+                        entry: LedgerEntry {
+                            last_modified_ledger_seq: 1,
+                            data: LedgerEntryData::Account(AccountEntry {
+                                account_id: account_id.clone(),
+                                balance: 10,
+                                seq_num: SequenceNumber(1),
+                                num_sub_entries: 0,
+                                inflation_dest: None,
+                                flags: 0,
+                                home_domain: Default::default(),
+                                thresholds: Thresholds([1, 0, 0, 0]),
+                                signers: Default::default(),
+                                ext: Default::default(),
+                            }),
+                            ext: Default::default(),
+                        },
+                        live_until: None,
+                    })
+                })
+                .collect())
+        }
+    }
+
+    fn account_key(seed: u8) -> LedgerKey {
+        LedgerKey::Account(LedgerKeyAccount {
+            account_id: AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([seed; 32]))),
+        })
+    }
+
+    #[test]
+    fn test_hits_after_first_fetch_at_same_sequence() {
+        let source = Arc::new(CountingSource::default());
+        let cache = CachedSource::new(source.clone(), 10);
+
+        let (first, outcome) = cache.fetch(&account_key(1), 5).unwrap();
+        assert!(first.is_some());
+        assert_eq!(outcome, CacheOutcome::Miss);
+        let (_, outcome) = cache.fetch(&account_key(1), 5).unwrap();
+        assert_eq!(outcome, CacheOutcome::Hit);
+
+        // A different ledger sequence is a different cache entry.
+        let (_, outcome) = cache.fetch(&account_key(1), 6).unwrap();
+        assert_eq!(outcome, CacheOutcome::Miss);
+
+        assert_eq!(source.calls.load(Ordering::SeqCst), 2);
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 2, 2));
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let cache = CachedSource::new(Arc::new(CountingSource::default()), 2);
+        cache.fetch(&account_key(1), 1).unwrap();
+        cache.fetch(&account_key(2), 1).unwrap();
+        cache.fetch(&account_key(1), 1).unwrap();
+        cache.fetch(&account_key(3), 1).unwrap();
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(
+            cache.fetch(&account_key(1), 1).unwrap().1,
+            CacheOutcome::Hit
+        );
+        assert_eq!(
+            cache.fetch(&account_key(2), 1).unwrap().1,
+            CacheOutcome::Miss
+        );
+    }
+
+    #[test]
+    fn test_disk_tier_answers_after_restart() {
+        let dir = std::env::temp_dir().join(format!("erst-cached-source-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let source = Arc::new(CountingSource::default());
+        let cache = CachedSource::new(source.clone(), 10).with_disk(DiskCache::open(&dir).unwrap());
+        cache.fetch(&account_key(1), 1).unwrap();
+        drop(cache);
+
+        let cache = CachedSource::new(source.clone(), 10).with_disk(DiskCache::open(&dir).unwrap());
+        let (found, outcome) = cache.fetch(&account_key(1), 1).unwrap();
+        assert!(found.is_some());
+        assert_eq!(outcome, CacheOutcome::DiskHit);
+        assert_eq!(
+            cache.fetch(&account_key(1), 1).unwrap().1,
+            CacheOutcome::Hit
+        );
+        assert_eq!(source.calls.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.stats().disk_hits, 1);
+
+        drop(cache);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_prefetch_fills_the_cache_concurrently() {
+        let source = Arc::new(CountingSource::default());
+        let cache = CachedSource::new(source.clone(), 100);
+        cache.fetch(&account_key(1), 1).unwrap();
+
+        let keys = (1..=20).map(account_key).collect::<Vec<_>>();
+        assert_eq!(cache.prefetch(&keys, 1).unwrap(), 19);
+        // 19 uncached keys in batches of 3: seven concurrent calls.
+        assert_eq!(source.calls.load(Ordering::SeqCst), 1 + 7);
+        for key in &keys {
+            assert_eq!(cache.fetch(key, 1).unwrap().1, CacheOutcome::Hit);
+        }
+        assert_eq!(cache.prefetch(&keys, 1).unwrap(), 0);
+
+        let uncached = CachedSource::new(source, 0);
+        assert_eq!(uncached.prefetch(&keys, 1).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_zero_capacity_disables_caching() {
+        let source = Arc::new(CountingSource::default());
+        let cache = CachedSource::new(source.clone(), 0);
+        cache.fetch(&account_key(1), 1).unwrap();
+        cache.fetch(&account_key(1), 1).unwrap();
+        assert_eq!(source.calls.load(Ordering::SeqCst), 2);
+        assert_eq!(cache.len(), 0);
+    }
+}
@@ -0,0 +1,194 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Persistent on-disk tier of the entry cache.
+//!
+//! Fetched entries are written to a sled database under the cache directory
+//! so a restarted daemon (or the next stdin invocation) does not fetch them
+//! again. Records are keyed by ledger sequence and ledger key, exactly like
+//! the in-memory tier, so a record is only ever served for the ledger it was
+//! read at.
+
+use super::{SourceEntry, SourceError};
+use crate::xdr_limits;
+use soroban_env_host::xdr::{LedgerEntry, Limits, WriteXdr};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+const ABSENT: u8 = 0;
+const PRESENT: u8 = 1;
+const PRESENT_WITH_TTL: u8 = 2;
+
+/// A sled database of fetched entries.
+///
+/// Dropping the cache flushes the database and returns once sled has closed
+/// its files, so the directory can be opened again straight away.
+pub struct DiskCache {
+    db: sled::Db,
+    /// Declared after `db`, so it is dropped after the database handle.
+    _closed: Closed,
+}
+
+/// Blocks, when dropped, until the lock sled holds on the database file is
+/// released. sled's IO threads keep the file open for a moment after the
+/// last handle is gone.
+struct Closed(PathBuf);
+
+impl Drop for Closed {
+    fn drop(&mut self) {
+        if let Ok(file) = File::open(self.0.join("db")) {
+            let _ = file.lock();
+        }
+    }
+}
+
+impl DiskCache {
+    /// Opens (or creates) the cache database in `dir`.
+    ///
+    /// Fails if another process holds the database open.
+    pub fn open(dir: &Path) -> Result<Self, SourceError> {
+        let db = sled::open(dir).map_err(|e| {
+            SourceError::Unavailable(format!("failed to open entry cache {}: {e}", dir.display()))
+        })?;
+        tracing::info!(
+            event = "entry_cache_opened",
+            path = %dir.display(),
+            records = db.len(),
+        );
+        Ok(Self {
+            db,
+            _closed: Closed(dir.to_path_buf()),
+        })
+    }
+
+    /// Looks up the record for `key_xdr` at `ledger_sequence`.
+    ///
+    /// The outer `Option` is `None` when nothing is recorded; the inner one
+    /// is `None` when the entry was recorded as absent. Unreadable records
+    /// are treated as missing.
+    pub fn get(&self, key_xdr: &[u8], ledger_sequence: u32) -> Option<Option<SourceEntry>> {
+        let record = match self.db.get(record_key(key_xdr, ledger_sequence)) {
+            Ok(record) => record?,
+            Err(e) => {
+                tracing::warn!(event = "entry_cache_read_failed", error = %e);
+                return None;
+            }
+        };
+        decode_record(&record)
+    }
+
+    /// Records `entry` for `key_xdr` at `ledger_sequence`. Write failures
+    /// are logged and otherwise ignored.
+    pub fn put(&self, key_xdr: &[u8], ledger_sequence: u32, entry: Option<&SourceEntry>) {
+        let result = encode_record(entry).and_then(|record| {
+            self.db
+                .insert(record_key(key_xdr, ledger_sequence), record)
+                .map_err(|e| e.to_string())
+        });
+        if let Err(e) = result {
+            tracing::warn!(event = "entry_cache_write_failed", error = %e);
+        }
+    }
+}
+
+impl Drop for DiskCache {
+    fn drop(&mut self) {
+        if let Err(e) = self.db.flush() {
+            tracing::warn!(event = "entry_cache_flush_failed", error = %e);
+        }
+    }
+}
+
+fn record_key(key_xdr: &[u8], ledger_sequence: u32) -> Vec<u8> {
+    let mut key = Vec::with_capacity(4 + key_xdr.len());
+    key.extend_from_slice(&ledger_sequence.to_be_bytes());
+    key.extend_from_slice(key_xdr);
+    key
+}
+
+fn encode_record(entry: Option<&SourceEntry>) -> Result<Vec<u8>, String> {
+    let Some(entry) = entry else {
+        return Ok(vec![ABSENT]);
+    };
+    let mut record = match entry.live_until {
+        Some(live_until) => {
+            let mut record = vec![PRESENT_WITH_TTL];
+            record.extend_from_slice(&live_until.to_be_bytes());
+            record
+        }
+        None => vec![PRESENT],
+    };
+    let xdr = entry
+        .entry
+        .to_xdr(Limits::none())
+        .map_err(|e| format!("failed to encode entry: {e}"))?;
+    record.extend_from_slice(&xdr);
+    Ok(record)
+}
+
+fn decode_record(record: &[u8]) -> Option<Option<SourceEntry>> {
+    let (&tag, rest) = record.split_first()?;
+    let (live_until, xdr) = match tag {
+        ABSENT => return Some(None),
+        PRESENT => (None, rest),
+        PRESENT_WITH_TTL if rest.len() >= 4 => {
+            let (ttl, xdr) = rest.split_at(4);
+            (Some(u32::from_be_bytes(ttl.try_into().ok()?)), xdr)
+        }
+        _ => return None,
+    };
+    let entry: LedgerEntry = xdr_limits::read(xdr).ok()?;
+    Some(Some(SourceEntry { entry, live_until }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::xdr::{
+        ContractCodeEntry, ContractCodeEntryExt, Hash, LedgerEntryData, LedgerEntryExt,
+    };
+
+    fn code_entry() -> SourceEntry {
+        SourceEntry {
+            entry: LedgerEntry {
+                last_modified_ledger_seq: 9,
+                data: LedgerEntryData::ContractCode(ContractCodeEntry {
+                    ext: ContractCodeEntryExt::V0,
+                    hash: Hash([3; 32]),
+                    code: vec![0, 97, 115, 109].try_into().unwrap(),
+                }),
+                ext: LedgerEntryExt::V0,
+            },
+            live_until: Some(4096),
+        }
+    }
+
+    #[test]
+    fn test_records_survive_reopen() {
+        let dir = std::env::temp_dir().join(format!("erst-entry-cache-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        {
+            let cache = DiskCache::open(&dir).unwrap();
+            cache.put(b"code", 10, Some(&code_entry()));
+            cache.put(b"missing", 10, None);
+        }
+
+        let cache = DiskCache::open(&dir).unwrap();
+        assert_eq!(cache.db.len(), 2);
+        assert_eq!(cache.get(b"code", 10), Some(Some(code_entry())));
+        assert_eq!(cache.get(b"missing", 10), Some(None));
+        // Records are never served for another ledger.
+        assert_eq!(cache.get(b"code", 11), None);
+
+        drop(cache);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_corrupt_records_are_ignored() {
+        assert_eq!(decode_record(&[]), None);
+        assert_eq!(decode_record(&[PRESENT_WITH_TTL, 0]), None);
+        assert_eq!(decode_record(&[PRESENT, 0xff]), None);
+    }
+}
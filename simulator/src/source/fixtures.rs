@@ -0,0 +1,62 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Ledger entries and bucket files shared by the source and snapshot tests.
+
+use super::bucket::LAST_FRAGMENT;
+use sha2::{Digest, Sha256};
+use soroban_env_host::xdr::{
+    AccountEntry, AccountId, BucketEntry, LedgerEntry, LedgerEntryData, LedgerKey,
+    LedgerKeyAccount, Limits, PublicKey, SequenceNumber, Thresholds, Uint256, WriteXdr,
+};
+use std::io::Write;
+
+/// An account whose ID is `seed` repeated, with `balance` stroops, last
+/// modified at `last_modified`.
+pub fn account(seed: u8, balance: i64, last_modified: u32) -> (LedgerKey, LedgerEntry) {
+    let account_id = AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([seed; 32])));
+    let key = LedgerKey::Account(LedgerKeyAccount {
+        account_id: account_id.clone(),
+    });
+    let entry = LedgerEntry {
+        last_modified_ledger_seq: last_modified,
+        data: LedgerEntryData::Account(AccountEntry {
+            account_id,
+            balance,
+            seq_num: SequenceNumber(1),
+            num_sub_entries: 0,
+            inflation_dest: None,
+            flags: 0,
+            home_domain: Default::default(),
+            thresholds: Thresholds([1, 0, 0, 0]),
+            signers: Default::default(),
+            ext: Default::default(),
+        }),
+        ext: Default::default(),
+    };
+    (key, entry)
+}
+
+/// `entries` as a bucket file: record-marked XDR, as stellar-core writes it.
+pub fn bucket(entries: &[BucketEntry]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for entry in entries {
+        let record = entry.to_xdr(Limits::none()).unwrap();
+        bytes.extend_from_slice(&(record.len() as u32 | LAST_FRAGMENT).to_be_bytes());
+        bytes.extend_from_slice(&record);
+    }
+    bytes
+}
+
+/// `entries` as a gzipped bucket file, as history archives publish it, and
+/// the bucket's hash.
+pub fn gzipped_bucket(entries: &[BucketEntry]) -> (String, Vec<u8>) {
+    let bytes = bucket(entries);
+    let hash = Sha256::digest(&bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+    let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gz.write_all(&bytes).unwrap();
+    (hash, gz.finish().unwrap())
+}
@@ -0,0 +1,551 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Account and trustline entries synthesized from Horizon.
+//!
+//! Some soroban-rpc deployments do not serve classic state, so a preflight
+//! that touches an account or a Stellar Asset Contract balance sees those
+//! entries as missing. [`HorizonFallback`] wraps such a source: keys it
+//! reports as absent are looked up through Horizon's `/accounts/{id}`
+//! endpoint, and the `AccountEntry` or `TrustLineEntry` is rebuilt from the
+//! account record (balances, flags, thresholds, signers, sponsorships and
+//! liabilities).
+//!
+//! Horizon may lag or lead the RPC's ledger by a few ledgers, so synthesized
+//! entries are a best effort, not a consistent snapshot.
+
+use super::{LedgerSource, SourceEntry, SourceError};
+use serde::Deserialize;
+use soroban_env_host::xdr::{
+    AccountEntry, AccountEntryExt, AccountEntryExtensionV1, AccountEntryExtensionV1Ext,
+    AccountEntryExtensionV2, AccountEntryExtensionV2Ext, AccountEntryExtensionV3, AccountFlags,
+    AccountId, AlphaNum12, AlphaNum4, AssetCode12, AssetCode4, ExtensionPoint, Hash, LedgerEntry,
+    LedgerEntryData, LedgerEntryExt, LedgerEntryExtensionV1, LedgerEntryExtensionV1Ext, LedgerKey,
+    Liabilities, PoolId, SequenceNumber, Signer, SignerKey, SponsorshipDescriptor, String32,
+    Thresholds, TimePoint, TrustLineAsset, TrustLineEntry, TrustLineEntryExt, TrustLineEntryV1,
+    TrustLineEntryV1Ext, TrustLineFlags, VecM,
+};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Digits after the decimal point in Horizon amounts.
+const AMOUNT_DECIMALS: usize = 7;
+
+#[derive(Debug, Deserialize)]
+struct HorizonAccount {
+    sequence: String,
+    subentry_count: u32,
+    #[serde(default)]
+    inflation_destination: Option<String>,
+    #[serde(default)]
+    home_domain: Option<String>,
+    last_modified_ledger: u32,
+    thresholds: HorizonThresholds,
+    flags: HorizonFlags,
+    balances: Vec<HorizonBalance>,
+    signers: Vec<HorizonSigner>,
+    #[serde(default)]
+    num_sponsoring: u32,
+    #[serde(default)]
+    num_sponsored: u32,
+    #[serde(default)]
+    sponsor: Option<String>,
+    #[serde(default)]
+    sequence_ledger: Option<u32>,
+    #[serde(default)]
+    sequence_time: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HorizonThresholds {
+    low_threshold: u8,
+    med_threshold: u8,
+    high_threshold: u8,
+}
+
+#[derive(Debug, Deserialize)]
+struct HorizonFlags {
+    auth_required: bool,
+    auth_revocable: bool,
+    auth_immutable: bool,
+    #[serde(default)]
+    auth_clawback_enabled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct HorizonBalance {
+    balance: String,
+    asset_type: String,
+    #[serde(default)]
+    asset_code: Option<String>,
+    #[serde(default)]
+    asset_issuer: Option<String>,
+    #[serde(default)]
+    liquidity_pool_id: Option<String>,
+    #[serde(default)]
+    limit: Option<String>,
+    #[serde(default)]
+    buying_liabilities: Option<String>,
+    #[serde(default)]
+    selling_liabilities: Option<String>,
+    #[serde(default)]
+    last_modified_ledger: Option<u32>,
+    #[serde(default)]
+    is_authorized: bool,
+    #[serde(default)]
+    is_authorized_to_maintain_liabilities: bool,
+    #[serde(default)]
+    is_clawback_enabled: bool,
+    #[serde(default)]
+    sponsor: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HorizonSigner {
+    key: String,
+    weight: u32,
+    #[serde(default)]
+    sponsor: Option<String>,
+}
+
+/// A Horizon server.
+pub struct HorizonSource {
+    url: String,
+    agent: ureq::Agent,
+}
+
+impl HorizonSource {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            agent: ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build(),
+        }
+    }
+
+    /// Fills the account and trustline entries missing from `found`,
+    /// fetching each account once.
+    pub fn fill(
+        &self,
+        keys: &[LedgerKey],
+        found: &mut [Option<SourceEntry>],
+    ) -> Result<(), SourceError> {
+        let mut accounts = HashMap::new();
+        for (key, slot) in keys.iter().zip(found.iter_mut()) {
+            let account_id = match key {
+                LedgerKey::Account(k) => &k.account_id,
+                LedgerKey::Trustline(k) => &k.account_id,
+                _ => continue,
+            };
+            if slot.is_some() {
+                continue;
+            }
+            let id = account_id.to_string();
+            if !accounts.contains_key(&id) {
+                let account = self.account(&id)?;
+                accounts.insert(id.clone(), account);
+            }
+            let Some(account) = &accounts[&id] else {
+                continue;
+            };
+            let entry = match key {
+                LedgerKey::Trustline(k) => trustline_entry(account_id, &k.asset, account)?,
+                _ => Some(account_entry(account_id, account)?),
+            };
+            *slot = entry.map(|entry| SourceEntry {
+                entry,
+                live_until: None,
+            });
+        }
+        Ok(())
+    }
+
+    /// Fetches the account `id`; `None` if Horizon does not know it.
+    fn account(&self, id: &str) -> Result<Option<HorizonAccount>, SourceError> {
+        let url = format!("{}/accounts/{id}", self.url);
+        match self.agent.get(&url).call() {
+            Ok(response) => response
+                .into_json()
+                .map(Some)
+                .map_err(|e| SourceError::InvalidResponse(format!("{url}: {e}"))),
+            Err(ureq::Error::Status(404, _)) => Ok(None),
+            Err(e) => Err(SourceError::Unavailable(format!("{url}: {e}"))),
+        }
+    }
+}
+
+/// A source whose missing classic entries are filled in from Horizon.
+pub struct HorizonFallback {
+    name: String,
+    primary: Arc<dyn LedgerSource>,
+    horizon: HorizonSource,
+}
+
+impl HorizonFallback {
+    pub fn new(primary: Arc<dyn LedgerSource>, horizon: HorizonSource) -> Self {
+        Self {
+            name: format!("{}+horizon", primary.name()),
+            primary,
+            horizon,
+        }
+    }
+}
+
+impl LedgerSource for HorizonFallback {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn latest_ledger(&self) -> Result<u32, SourceError> {
+        self.primary.latest_ledger()
+    }
+
+    fn fetch(&self, keys: &[LedgerKey]) -> Result<Vec<Option<SourceEntry>>, SourceError> {
+        let mut found = self.primary.fetch(keys)?;
+        self.horizon.fill(keys, &mut found)?;
+        Ok(found)
+    }
+}
+
+fn invalid(what: &str, value: &str) -> SourceError {
+    SourceError::InvalidResponse(format!("Horizon returned an invalid {what}: {value:?}"))
+}
+
+fn parse<T: FromStr>(what: &str, value: &str) -> Result<T, SourceError> {
+    value.parse().map_err(|_| invalid(what, value))
+}
+
+/// Parses a Horizon amount such as `"12.3400000"` into stroops.
+fn amount(value: &str) -> Result<i64, SourceError> {
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+    if fraction.len() > AMOUNT_DECIMALS || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid("amount", value));
+    }
+    let whole: i64 = parse("amount", whole)?;
+    let fraction: i64 = format!("{fraction:0<AMOUNT_DECIMALS$}")
+        .parse()
+        .map_err(|_| invalid("amount", value))?;
+    whole
+        .checked_mul(10_i64.pow(AMOUNT_DECIMALS as u32))
+        .and_then(|stroops| stroops.checked_add(fraction))
+        .ok_or_else(|| invalid("amount", value))
+}
+
+fn optional_amount(value: Option<&String>) -> Result<i64, SourceError> {
+    value.map_or(Ok(0), |v| amount(v))
+}
+
+fn sponsor(value: Option<&String>) -> Result<SponsorshipDescriptor, SourceError> {
+    Ok(SponsorshipDescriptor(
+        value.map(|id| parse("sponsor", id)).transpose()?,
+    ))
+}
+
+/// The entry extension recording `sponsor`, if any.
+fn entry_ext(sponsor_id: Option<&String>) -> Result<LedgerEntryExt, SourceError> {
+    Ok(match sponsor_id {
+        Some(_) => LedgerEntryExt::V1(LedgerEntryExtensionV1 {
+            sponsoring_id: sponsor(sponsor_id)?,
+            ext: LedgerEntryExtensionV1Ext::V0,
+        }),
+        None => LedgerEntryExt::V0,
+    })
+}
+
+fn account_entry(
+    account_id: &AccountId,
+    account: &HorizonAccount,
+) -> Result<LedgerEntry, SourceError> {
+    let native = account
+        .balances
+        .iter()
+        .find(|b| b.asset_type == "native")
+        .ok_or_else(|| invalid("account", "no native balance"))?;
+    let id = account_id.to_string();
+    let master_weight = account
+        .signers
+        .iter()
+        .find(|s| s.key == id)
+        .map_or(0, |s| s.weight);
+    let mut signers = account
+        .signers
+        .iter()
+        .filter(|s| s.key != id)
+        .map(|s| {
+            Ok((
+                Signer {
+                    key: parse::<SignerKey>("signer", &s.key)?,
+                    weight: s.weight,
+                },
+                sponsor(s.sponsor.as_ref())?,
+            ))
+        })
+        .collect::<Result<Vec<_>, SourceError>>()?;
+    // stellar-core keeps signers ordered by key.
+    signers.sort_by(|a, b| a.0.key.cmp(&b.0.key));
+    let (signers, signer_sponsors): (Vec<_>, Vec<_>) = signers.into_iter().unzip();
+
+    let flags = [
+        (account.flags.auth_required, AccountFlags::RequiredFlag),
+        (account.flags.auth_revocable, AccountFlags::RevocableFlag),
+        (account.flags.auth_immutable, AccountFlags::ImmutableFlag),
+        (
+            account.flags.auth_clawback_enabled,
+            AccountFlags::ClawbackEnabledFlag,
+        ),
+    ]
+    .into_iter()
+    .filter(|(set, _)| *set)
+    .fold(0, |flags, (_, flag)| flags | flag as u32);
+
+    let liabilities = Liabilities {
+        buying: optional_amount(native.buying_liabilities.as_ref())?,
+        selling: optional_amount(native.selling_liabilities.as_ref())?,
+    };
+    let v3 = match (account.sequence_ledger, &account.sequence_time) {
+        (Some(seq_ledger), Some(seq_time)) => Some(AccountEntryExtensionV3 {
+            ext: ExtensionPoint::V0,
+            seq_ledger,
+            seq_time: TimePoint(parse("sequence_time", seq_time)?),
+        }),
+        _ => None,
+    };
+    let has_sponsorship = account.num_sponsoring > 0
+        || account.num_sponsored > 0
+        || signer_sponsors.iter().any(|s| s.0.is_some());
+    let signers: VecM<Signer, 20> = signers
+        .try_into()
+        .map_err(|_| invalid("signer list", &id))?;
+    let signer_sponsors = signer_sponsors
+        .try_into()
+        .map_err(|_| invalid("signer list", &id))?;
+    let v2 = (has_sponsorship || v3.is_some()).then(|| AccountEntryExtensionV2 {
+        num_sponsored: account.num_sponsored,
+        num_sponsoring: account.num_sponsoring,
+        signer_sponsoring_i_ds: signer_sponsors,
+        ext: v3.map_or(
+            AccountEntryExtensionV2Ext::V0,
+            AccountEntryExtensionV2Ext::V3,
+        ),
+    });
+    let ext = if v2.is_some() || liabilities.buying != 0 || liabilities.selling != 0 {
+        AccountEntryExt::V1(AccountEntryExtensionV1 {
+            liabilities,
+            ext: v2.map_or(
+                AccountEntryExtensionV1Ext::V0,
+                AccountEntryExtensionV1Ext::V2,
+            ),
+        })
+    } else {
+        AccountEntryExt::V0
+    };
+
+    let home_domain = account.home_domain.as_deref().unwrap_or_default();
+    Ok(LedgerEntry {
+        last_modified_ledger_seq: account.last_modified_ledger,
+        data: LedgerEntryData::Account(AccountEntry {
+            account_id: account_id.clone(),
+            balance: amount(&native.balance)?,
+            seq_num: SequenceNumber(parse("sequence", &account.sequence)?),
+            num_sub_entries: account.subentry_count,
+            inflation_dest: account
+                .inflation_destination
+                .as_deref()
+                .map(|id| parse("inflation_destination", id))
+                .transpose()?,
+            flags,
+            home_domain: String32(
+                home_domain
+                    .try_into()
+                    .map_err(|_| invalid("home_domain", home_domain))?,
+            ),
+            thresholds: Thresholds([
+                u8::try_from(master_weight).map_err(|_| invalid("signer weight", &id))?,
+                account.thresholds.low_threshold,
+                account.thresholds.med_threshold,
+                account.thresholds.high_threshold,
+            ]),
+            signers,
+            ext,
+        }),
+        ext: entry_ext(account.sponsor.as_ref())?,
+    })
+}
+
+/// The asset of a Horizon balance, `None` for the native balance.
+fn balance_asset(balance: &HorizonBalance) -> Result<Option<TrustLineAsset>, SourceError> {
+    let code = balance.asset_code.as_deref().unwrap_or_default();
+    let issuer = || {
+        parse::<AccountId>(
+            "asset_issuer",
+            balance.asset_issuer.as_deref().unwrap_or_default(),
+        )
+    };
+    Ok(Some(match balance.asset_type.as_str() {
+        "credit_alphanum4" => TrustLineAsset::CreditAlphanum4(AlphaNum4 {
+            asset_code: parse::<AssetCode4>("asset_code", code)?,
+            issuer: issuer()?,
+        }),
+        "credit_alphanum12" => TrustLineAsset::CreditAlphanum12(AlphaNum12 {
+            asset_code: parse::<AssetCode12>("asset_code", code)?,
+            issuer: issuer()?,
+        }),
+        "liquidity_pool_shares" => {
+            let id = balance.liquidity_pool_id.as_deref().unwrap_or_default();
+            TrustLineAsset::PoolShare(PoolId(Hash(
+                hex32(id).ok_or_else(|| invalid("liquidity_pool_id", id))?,
+            )))
+        }
+        _ => return Ok(None),
+    }))
+}
+
+fn hex32(value: &str) -> Option<[u8; 32]> {
+    if value.len() != 64 {
+        return None;
+    }
+    let mut bytes = [0u8; 32];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(value.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(bytes)
+}
+
+fn trustline_entry(
+    account_id: &AccountId,
+    asset: &TrustLineAsset,
+    account: &HorizonAccount,
+) -> Result<Option<LedgerEntry>, SourceError> {
+    for balance in &account.balances {
+        if balance_asset(balance)?.as_ref() != Some(asset) {
+            continue;
+        }
+        let flags = [
+            (balance.is_authorized, TrustLineFlags::AuthorizedFlag),
+            (
+                balance.is_authorized_to_maintain_liabilities && !balance.is_authorized,
+                TrustLineFlags::AuthorizedToMaintainLiabilitiesFlag,
+            ),
+            (
+                balance.is_clawback_enabled,
+                TrustLineFlags::TrustlineClawbackEnabledFlag,
+            ),
+        ]
+        .into_iter()
+        .filter(|(set, _)| *set)
+        .fold(0, |flags, (_, flag)| flags | flag as u32);
+        let liabilities = Liabilities {
+            buying: optional_amount(balance.buying_liabilities.as_ref())?,
+            selling: optional_amount(balance.selling_liabilities.as_ref())?,
+        };
+        let ext = if liabilities.buying != 0 || liabilities.selling != 0 {
+            TrustLineEntryExt::V1(TrustLineEntryV1 {
+                liabilities,
+                ext: TrustLineEntryV1Ext::V0,
+            })
+        } else {
+            TrustLineEntryExt::V0
+        };
+        return Ok(Some(LedgerEntry {
+            last_modified_ledger_seq: balance
+                .last_modified_ledger
+                .unwrap_or(account.last_modified_ledger),
+            data: LedgerEntryData::Trustline(TrustLineEntry {
+                account_id: account_id.clone(),
+                asset: asset.clone(),
+                balance: amount(&balance.balance)?,
+                limit: optional_amount(balance.limit.as_ref())?,
+                flags,
+                ext,
+            }),
+            ext: entry_ext(balance.sponsor.as_ref())?,
+        }));
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::xdr::{PublicKey, Uint256};
+
+    fn account_id(seed: u8) -> AccountId {
+        AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([seed; 32])))
+    }
+
+    fn usdc(code: &str) -> TrustLineAsset {
+        TrustLineAsset::CreditAlphanum4(AlphaNum4 {
+            asset_code: code.parse().unwrap(),
+            issuer: account_id(2),
+        })
+    }
+
+    fn account() -> HorizonAccount {
+        serde_json::from_value(serde_json::json!({
+            "id": account_id(1).to_string(),
+            "sequence": "4294967301",
+            "subentry_count": 1,
+            "last_modified_ledger": 900,
+            "thresholds": { "low_threshold": 1, "med_threshold": 2, "high_threshold": 3 },
+            "flags": { "auth_required": false, "auth_revocable": true, "auth_immutable": false },
+            "balances": [
+                {
+                    "balance": "5.0000000", "limit": "100.0000000",
+                    "buying_liabilities": "0.0000000", "selling_liabilities": "1.5000000",
+                    "last_modified_ledger": 850, "is_authorized": true,
+                    "is_authorized_to_maintain_liabilities": true,
+                    "asset_type": "credit_alphanum4", "asset_code": "USDC",
+                    "asset_issuer": account_id(2).to_string()
+                },
+                { "balance": "12.3400000", "asset_type": "native" }
+            ],
+            "signers": [
+                { "key": account_id(3).to_string(), "weight": 1, "type": "ed25519_public_key" },
+                { "key": account_id(1).to_string(), "weight": 5, "type": "ed25519_public_key" }
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_amounts_are_parsed_as_stroops() {
+        assert_eq!(amount("12.34").unwrap(), 123_400_000);
+        assert_eq!(amount("0.0000001").unwrap(), 1);
+        assert_eq!(amount("7").unwrap(), 70_000_000);
+        assert!(amount("1.00000001").is_err());
+        assert!(amount("abc").is_err());
+    }
+
+    #[test]
+    fn test_entries_are_synthesized_from_the_account() {
+        let entry = account_entry(&account_id(1), &account()).unwrap();
+        let LedgerEntryData::Account(data) = entry.data else {
+            panic!("expected an account entry");
+        };
+        assert_eq!(data.balance, 123_400_000);
+        assert_eq!(data.seq_num, SequenceNumber(4_294_967_301));
+        assert_eq!(data.thresholds, Thresholds([5, 1, 2, 3]));
+        assert_eq!(data.flags, AccountFlags::RevocableFlag as u32);
+        assert_eq!(data.signers.len(), 1);
+        assert_eq!(data.ext, AccountEntryExt::V0);
+
+        let entry = trustline_entry(&account_id(1), &usdc("USDC"), &account())
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.last_modified_ledger_seq, 850);
+        let LedgerEntryData::Trustline(data) = entry.data else {
+            panic!("expected a trustline entry");
+        };
+        assert_eq!((data.balance, data.limit), (50_000_000, 1_000_000_000));
+        assert_eq!(data.flags, TrustLineFlags::AuthorizedFlag as u32);
+        assert!(
+            matches!(data.ext, TrustLineEntryExt::V1(ref v1) if v1.liabilities.selling == 15_000_000)
+        );
+
+        assert!(trustline_entry(&account_id(1), &usdc("EURC"), &account())
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,630 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Ledger state sources for the Soroban host.
+//!
+//! The host runs with a recording footprint and pulls entries on demand
+//! through [`LayeredSource`], which consults, in order:
+//! - the entries supplied with the request (`ledger_entries`, a server-side
+//!   JSON snapshot file, the restore preamble)
+//! - an optional server-side [`IndexedSnapshot`], searched in place
+//! - an optional remote [`LedgerSource`] such as soroban-rpc (optionally
+//!   backed by Horizon for classic entries, [`horizon`]), a stellar-core
+//!   bucket list ([`bucket`]) or a history archive checkpoint ([`archive`]),
+//!   behind a shared [`cache::CachedSource`] with an optional on-disk tier
+//!   ([`disk`]), with network backends retried per [`retry::RetryPolicy`]
+//!
+//! Entries found nowhere are reported to the host as absent.
+
+pub mod archive;
+pub mod bucket;
+pub mod cache;
+pub mod disk;
+#[cfg(test)]
+pub(crate) mod fixtures;
+pub mod horizon;
+pub mod retry;
+pub mod rpc;
+
+use crate::snapshot::indexed::IndexedSnapshot;
+use crate::snapshot::LedgerSnapshot;
+use archive::ArchiveSource;
+use bucket::BucketListSource;
+use cache::{CacheOutcome, CacheStats, CachedSource};
+use disk::DiskCache;
+use horizon::{HorizonFallback, HorizonSource};
+use retry::{RetryPolicy, RetryingSource};
+use rpc::RpcSource;
+use sha2::{Digest, Sha256};
+use soroban_env_host::storage::{EntryWithLiveUntil, SnapshotSource};
+use soroban_env_host::xdr::{
+    ConfigSettingEntry, ConfigSettingId, Hash, LedgerEntry, LedgerEntryData, LedgerKey,
+    LedgerKeyConfigSetting, LedgerKeyContractCode, LedgerKeyTtl, Limits, ScErrorCode, ScErrorType,
+    WriteXdr,
+};
+use soroban_env_host::{Error as EnvError, HostError};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// A ledger entry together with its TTL, if it has one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceEntry {
+    pub entry: LedgerEntry,
+    /// `live_until_ledger_seq` for contract data and code entries.
+    pub live_until: Option<u32>,
+}
+
+/// The Wasm of a contract code entry, borrowed from the base snapshot when
+/// it has the entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractCode<'a> {
+    pub wasm: Cow<'a, [u8]>,
+    pub live_until: Option<u32>,
+}
+
+/// The layer of a [`LayeredSource`] that answered a read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    /// The entries supplied with the request.
+    Snapshot,
+    /// The server-side base snapshot.
+    Base,
+    /// The remote source, by name.
+    Remote(String),
+    /// No layer has the entry.
+    Missing,
+    /// The remote source could not be queried.
+    Failed,
+}
+
+impl Origin {
+    pub fn label(&self) -> &str {
+        match self {
+            Self::Snapshot => "snapshot",
+            Self::Base => "base_snapshot",
+            Self::Remote(name) => name,
+            Self::Missing => "missing",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// Errors raised while fetching ledger state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SourceError {
+    /// The backend could not be reached or returned an error.
+    Unavailable(String),
+    /// The backend answered with something that could not be decoded.
+    InvalidResponse(String),
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceError::Unavailable(msg) => write!(f, "state source unavailable: {msg}"),
+            SourceError::InvalidResponse(msg) => {
+                write!(f, "invalid response from state source: {msg}")
+            }
+        }
+    }
+}
+
+/// A backend that can look up ledger entries.
+pub trait LedgerSource: Send + Sync {
+    /// Short name used in logs.
+    fn name(&self) -> &str;
+
+    /// The ledger sequence the source currently reflects.
+    fn latest_ledger(&self) -> Result<u32, SourceError>;
+
+    /// Looks up `keys`, returning one result per key in the same order.
+    fn fetch(&self, keys: &[LedgerKey]) -> Result<Vec<Option<SourceEntry>>, SourceError>;
+}
+
+/// Builds the shared cache in front of the soroban-rpc endpoint at `url`,
+/// filling accounts and trustlines it lacks from `horizon_url` if given and
+/// retrying unanswered lookups per `retry`.
+///
+/// If the on-disk tier in `disk_dir` cannot be opened (for example because
+/// another process holds it), caching continues in memory only.
+pub fn rpc_cache(
+    url: &str,
+    horizon_url: Option<&str>,
+    capacity: usize,
+    disk_dir: Option<&Path>,
+    retry: RetryPolicy,
+) -> Arc<CachedSource> {
+    let rpc: Arc<dyn LedgerSource> = Arc::new(RpcSource::new(url));
+    let source: Arc<dyn LedgerSource> = match horizon_url {
+        Some(horizon_url) => Arc::new(HorizonFallback::new(rpc, HorizonSource::new(horizon_url))),
+        None => rpc,
+    };
+    let cache = CachedSource::new(Arc::new(RetryingSource::new(source, retry)), capacity);
+    let cache = match disk_dir.map(DiskCache::open) {
+        Some(Ok(disk)) => cache.with_disk(disk),
+        Some(Err(e)) => {
+            tracing::warn!(event = "entry_cache_unavailable", error = %e);
+            cache
+        }
+        None => cache,
+    };
+    Arc::new(cache)
+}
+
+/// Builds the shared cache in front of the stellar-core bucket list in
+/// `dir`, as described by the history archive state at `state`.
+///
+/// Bucket files are already on local disk, so there is no on-disk tier.
+pub fn bucket_cache(
+    dir: &Path,
+    state: &Path,
+    capacity: usize,
+) -> Result<Arc<CachedSource>, SourceError> {
+    let source = BucketListSource::open(dir, state)?;
+    Ok(Arc::new(CachedSource::new(Arc::new(source), capacity)))
+}
+
+/// Builds the shared cache in front of `checkpoint` of the history archive
+/// at `url`, downloading buckets into `bucket_dir` and retrying failed
+/// downloads per `retry`.
+pub fn archive_cache(
+    url: &str,
+    checkpoint: u32,
+    bucket_dir: &Path,
+    capacity: usize,
+    retry: RetryPolicy,
+) -> Result<Arc<CachedSource>, SourceError> {
+    let source = Arc::new(ArchiveSource::open(url, checkpoint, bucket_dir)?);
+    let source = RetryingSource::new(source, retry);
+    Ok(Arc::new(CachedSource::new(Arc::new(source), capacity)))
+}
+
+/// Returns true if entries under `key` carry a TTL.
+pub fn has_ttl(key: &LedgerKey) -> bool {
+    matches!(key, LedgerKey::ContractData(_) | LedgerKey::ContractCode(_))
+}
+
+/// Looks up the network's `ConfigSetting` entry `id` through `lookup`.
+pub fn config_setting(
+    id: ConfigSettingId,
+    lookup: impl Fn(&LedgerKey) -> Option<SourceEntry>,
+) -> Option<ConfigSettingEntry> {
+    let key = LedgerKey::ConfigSetting(LedgerKeyConfigSetting {
+        config_setting_id: id,
+    });
+    match lookup(&key)?.entry.data {
+        LedgerEntryData::ConfigSetting(setting) => Some(setting),
+        _ => None,
+    }
+}
+
+/// Returns the key of the TTL entry belonging to `key`.
+pub fn ttl_key(key: &LedgerKey) -> Result<LedgerKey, SourceError> {
+    Ok(LedgerKey::Ttl(LedgerKeyTtl {
+        key_hash: Hash(Sha256::digest(encode_key(key)?).into()),
+    }))
+}
+
+/// The `live_until` of an entry under `key`, given the snapshot's TTL entry
+/// for it. Contract entries without one are treated as live forever.
+pub fn live_until(key: &LedgerKey, ttl: Option<&LedgerEntry>) -> Option<u32> {
+    has_ttl(key).then(|| match ttl.map(|t| &t.data) {
+        Some(LedgerEntryData::Ttl(ttl)) => ttl.live_until_ledger_seq,
+        _ => u32::MAX,
+    })
+}
+
+/// Serves entries from a decoded request snapshot.
+///
+/// Contract entries take their TTL from the matching `Ttl` entry in the
+/// snapshot; when the snapshot has none they are treated as live forever.
+#[derive(Debug, Default)]
+pub struct SnapshotLedgerSource {
+    snapshot: LedgerSnapshot,
+}
+
+impl SnapshotLedgerSource {
+    pub fn new(snapshot: LedgerSnapshot) -> Self {
+        Self { snapshot }
+    }
+
+    /// Highest `last_modified_ledger_seq` in the snapshot.
+    pub fn max_last_modified(&self) -> Option<u32> {
+        self.snapshot
+            .iter()
+            .map(|(_, entry)| entry.last_modified_ledger_seq)
+            .max()
+    }
+
+    /// Serves `entry` for `key`, replacing any entry the snapshot had.
+    pub fn insert(&mut self, key: &LedgerKey, entry: LedgerEntry) -> Result<(), SourceError> {
+        self.snapshot.insert(encode_key(key)?, entry);
+        Ok(())
+    }
+
+    fn get(&self, key: &LedgerKey) -> Result<Option<SourceEntry>, SourceError> {
+        let Some(entry) = self.snapshot.get(&encode_key(key)?).cloned() else {
+            return Ok(None);
+        };
+        let ttl = if has_ttl(key) {
+            self.snapshot.get(&encode_key(&ttl_key(key)?)?)
+        } else {
+            None
+        };
+        Ok(Some(SourceEntry {
+            live_until: live_until(key, ttl),
+            entry,
+        }))
+    }
+}
+
+impl LedgerSource for SnapshotLedgerSource {
+    fn name(&self) -> &str {
+        "snapshot"
+    }
+
+    fn latest_ledger(&self) -> Result<u32, SourceError> {
+        Ok(self.max_last_modified().unwrap_or(0))
+    }
+
+    fn fetch(&self, keys: &[LedgerKey]) -> Result<Vec<Option<SourceEntry>>, SourceError> {
+        keys.iter().map(|key| self.get(key)).collect()
+    }
+}
+
+/// Encodes `key` as XDR.
+pub fn encode_key(key: &LedgerKey) -> Result<Vec<u8>, SourceError> {
+    key.to_xdr(Limits::none())
+        .map_err(|e| SourceError::InvalidResponse(format!("failed to encode key: {e}")))
+}
+
+/// The host's view of ledger state for one simulation.
+pub struct LayeredSource {
+    local: SnapshotLedgerSource,
+    base: Option<Arc<IndexedSnapshot>>,
+    remote: Option<Arc<CachedSource>>,
+    ledger_sequence: u32,
+    hits: Cell<u64>,
+    disk_hits: Cell<u64>,
+    misses: Cell<u64>,
+    errors: RefCell<Vec<String>>,
+    reads: RefCell<Vec<(LedgerKey, Origin)>>,
+    absent: RefCell<Vec<LedgerKey>>,
+    dropped: Vec<LedgerKey>,
+}
+
+impl LayeredSource {
+    /// Layers `local` over `remote`, reading remote entries as of
+    /// `ledger_sequence`.
+    pub fn new(
+        local: SnapshotLedgerSource,
+        remote: Option<Arc<CachedSource>>,
+        ledger_sequence: u32,
+    ) -> Self {
+        Self {
+            local,
+            base: None,
+            remote,
+            ledger_sequence,
+            hits: Cell::new(0),
+            disk_hits: Cell::new(0),
+            misses: Cell::new(0),
+            errors: RefCell::new(Vec::new()),
+            reads: RefCell::new(Vec::new()),
+            absent: RefCell::new(Vec::new()),
+            dropped: Vec::new(),
+        }
+    }
+
+    /// Consults `base` after the local entries and before the remote source.
+    pub fn with_base(mut self, base: Option<Arc<IndexedSnapshot>>) -> Self {
+        self.base = base;
+        self
+    }
+
+    /// Serves `entry` for `key` ahead of every other layer.
+    pub fn insert_local(&mut self, key: &LedgerKey, entry: LedgerEntry) -> Result<(), SourceError> {
+        self.dropped.retain(|dropped| dropped != key);
+        self.local.insert(key, entry)
+    }
+
+    /// Answers `key` as not existing, whichever layer holds it.
+    pub fn drop_entry(&mut self, key: LedgerKey) {
+        self.dropped.push(key);
+    }
+
+    /// Fetches the remote entries among `keys` ahead of execution, so the
+    /// host's reads are answered from the cache. Keys supplied locally are
+    /// skipped. Failures are logged and left for the reads to report.
+    pub fn prefetch(&self, keys: &[LedgerKey]) {
+        let Some(remote) = &self.remote else {
+            return;
+        };
+        let remote_keys = keys
+            .iter()
+            .filter(|key| !self.is_local(key))
+            .cloned()
+            .collect::<Vec<_>>();
+        match remote.prefetch(&remote_keys, self.ledger_sequence) {
+            Ok(fetched) => {
+                self.misses.set(self.misses.get() + fetched as u64);
+                tracing::debug!(
+                    event = "footprint_prefetched",
+                    keys = remote_keys.len(),
+                    fetched,
+                );
+            }
+            Err(e) => tracing::warn!(event = "footprint_prefetch_failed", error = %e),
+        }
+    }
+
+    fn is_local(&self, key: &LedgerKey) -> bool {
+        matches!(self.local.get(key), Ok(Some(_)))
+            || self
+                .base
+                .as_ref()
+                .is_some_and(|base| matches!(base.get(key), Ok(Some(_))))
+    }
+
+    /// Cache statistics for this simulation, if a remote source is configured.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.remote.as_ref().map(|remote| CacheStats {
+            hits: self.hits.get(),
+            disk_hits: self.disk_hits.get(),
+            misses: self.misses.get(),
+            entries: remote.len(),
+            capacity: remote.capacity(),
+        })
+    }
+
+    /// Errors raised by the remote source during this simulation.
+    pub fn errors(&self) -> Vec<String> {
+        self.errors.borrow().clone()
+    }
+
+    /// Keys looked up so far, in order, including those not found.
+    pub fn reads(&self) -> Vec<LedgerKey> {
+        self.reads
+            .borrow()
+            .iter()
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    /// Keys looked up so far, in order, with the layer that answered each.
+    pub fn read_origins(&self) -> Vec<(LedgerKey, Origin)> {
+        self.reads.borrow().clone()
+    }
+
+    /// Keys every source answered as not existing, as opposed to lookups
+    /// that failed (see [`LayeredSource::errors`]).
+    pub fn absent(&self) -> Vec<LedgerKey> {
+        self.absent.borrow().clone()
+    }
+
+    /// Looks up `key` as the host would, recording it in [`LayeredSource::reads`]
+    /// and, depending on the outcome, [`LayeredSource::absent`] or
+    /// [`LayeredSource::errors`].
+    pub fn read(&self, key: &LedgerKey) -> Result<Option<SourceEntry>, SourceError> {
+        let found = self.lookup(key);
+        let origin = match &found {
+            Ok((None, _)) => {
+                self.absent.borrow_mut().push(key.clone());
+                Origin::Missing
+            }
+            Ok((Some(_), origin)) => origin.clone(),
+            Err(e) => {
+                let source = self.remote.as_ref().map_or("snapshot", |r| r.name());
+                tracing::warn!(event = "state_fetch_failed", source, error = %e);
+                self.errors.borrow_mut().push(e.to_string());
+                Origin::Failed
+            }
+        };
+        self.reads.borrow_mut().push((key.clone(), origin));
+        found.map(|(found, _)| found)
+    }
+
+    /// Looks up `key` without recording the read, e.g. to compare the state
+    /// a simulation started from with what it wrote.
+    pub fn peek(&self, key: &LedgerKey) -> Result<Option<SourceEntry>, SourceError> {
+        self.lookup(key).map(|(found, _)| found)
+    }
+
+    /// Looks up contract code `hash` like [`LayeredSource::peek`], without
+    /// copying the Wasm out of the base snapshot.
+    pub fn peek_code(&self, hash: &Hash) -> Result<Option<ContractCode<'_>>, SourceError> {
+        let key = LedgerKey::ContractCode(LedgerKeyContractCode { hash: hash.clone() });
+        if self.local.get(&key)?.is_none() && !self.dropped.contains(&key) {
+            if let Some(base) = &self.base {
+                if let Some(code) = base.contract_code(hash)? {
+                    return Ok(Some(code));
+                }
+            }
+        }
+        Ok(self.peek(&key)?.and_then(|found| match found.entry.data {
+            LedgerEntryData::ContractCode(code) => Some(ContractCode {
+                wasm: Cow::Owned(code.code.into()),
+                live_until: found.live_until,
+            }),
+            _ => None,
+        }))
+    }
+
+    fn lookup(&self, key: &LedgerKey) -> Result<(Option<SourceEntry>, Origin), SourceError> {
+        if self.dropped.contains(key) {
+            return Ok((None, Origin::Missing));
+        }
+        if let Some(found) = self.local.get(key)? {
+            return Ok((Some(found), Origin::Snapshot));
+        }
+        if let Some(base) = &self.base {
+            if let Some(found) = base.get(key)? {
+                return Ok((Some(found), Origin::Base));
+            }
+        }
+        let Some(remote) = &self.remote else {
+            return Ok((None, Origin::Missing));
+        };
+        let (found, outcome) = remote.fetch(key, self.ledger_sequence)?;
+        match outcome {
+            CacheOutcome::Hit => self.hits.set(self.hits.get() + 1),
+            CacheOutcome::DiskHit => {
+                self.hits.set(self.hits.get() + 1);
+                self.disk_hits.set(self.disk_hits.get() + 1);
+            }
+            CacheOutcome::Miss => self.misses.set(self.misses.get() + 1),
+        }
+        Ok((found, Origin::Remote(remote.name().to_string())))
+    }
+}
+
+impl SnapshotSource for LayeredSource {
+    fn get(&self, key: &Rc<LedgerKey>) -> Result<Option<EntryWithLiveUntil>, HostError> {
+        match self.read(key) {
+            Ok(found) => Ok(found.map(|e| (Rc::new(e.entry), e.live_until))),
+            Err(_) => Err(EnvError::from_type_and_code(
+                ScErrorType::Storage,
+                ScErrorCode::InternalError,
+            )
+            .into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::xdr::{
+        ContractDataDurability, ContractDataEntry, ContractId, ExtensionPoint, LedgerEntryExt,
+        LedgerKeyContractData, ScAddress, ScVal, TtlEntry,
+    };
+
+    fn contract_data_key() -> LedgerKey {
+        LedgerKey::ContractData(LedgerKeyContractData {
+            contract: ScAddress::Contract(ContractId(Hash([7; 32]))),
+            key: ScVal::Symbol("counter".try_into().unwrap()),
+            durability: ContractDataDurability::Persistent,
+        })
+    }
+
+    fn contract_data_entry() -> LedgerEntry {
+        LedgerEntry {
+            last_modified_ledger_seq: 12,
+            data: LedgerEntryData::ContractData(ContractDataEntry {
+                ext: ExtensionPoint::V0,
+                contract: ScAddress::Contract(ContractId(Hash([7; 32]))),
+                key: ScVal::Symbol("counter".try_into().unwrap()),
+                durability: ContractDataDurability::Persistent,
+                val: ScVal::U32(3),
+            }),
+            ext: LedgerEntryExt::V0,
+        }
+    }
+
+    fn ttl_entry(key: &LedgerKey, live_until: u32) -> (LedgerKey, LedgerEntry) {
+        let LedgerKey::Ttl(ttl) = ttl_key(key).unwrap() else {
+            unreachable!()
+        };
+        let entry = LedgerEntry {
+            last_modified_ledger_seq: 12,
+            data: LedgerEntryData::Ttl(TtlEntry {
+                key_hash: ttl.key_hash.clone(),
+                live_until_ledger_seq: live_until,
+            }),
+            ext: LedgerEntryExt::V0,
+        };
+        (LedgerKey::Ttl(ttl), entry)
+    }
+
+    #[test]
+    fn test_snapshot_source_reads_ttl_entries() {
+        let key = contract_data_key();
+        let mut snapshot = LedgerSnapshot::new();
+        snapshot.insert_entry(&key, contract_data_entry()).unwrap();
+
+        let source = SnapshotLedgerSource::new(snapshot.clone());
+        let found = source
+            .fetch(std::slice::from_ref(&key))
+            .unwrap()
+            .remove(0)
+            .unwrap();
+        assert_eq!(found.live_until, Some(u32::MAX));
+
+        let (ttl_key, ttl_entry) = ttl_entry(&key, 500);
+        snapshot.insert_entry(&ttl_key, ttl_entry).unwrap();
+        let source = SnapshotLedgerSource::new(snapshot);
+        let found = source.fetch(&[key]).unwrap().remove(0).unwrap();
+        assert_eq!(found.live_until, Some(500));
+        assert_eq!(source.latest_ledger().unwrap(), 12);
+    }
+
+    #[test]
+    fn test_layered_source_prefers_local_entries() {
+        let key = contract_data_key();
+        let mut snapshot = LedgerSnapshot::new();
+        snapshot.insert_entry(&key, contract_data_entry()).unwrap();
+
+        let layered = LayeredSource::new(SnapshotLedgerSource::new(snapshot), None, 12);
+        let found = layered.get(&Rc::new(key.clone())).unwrap().unwrap();
+        assert_eq!(found.0.last_modified_ledger_seq, 12);
+        assert!(layered.cache_stats().is_none());
+        assert_eq!(layered.read_origins(), vec![(key, Origin::Snapshot)]);
+    }
+
+    #[test]
+    fn test_layered_source_drops_and_inserts_entries() {
+        let key = contract_data_key();
+        let mut snapshot = LedgerSnapshot::new();
+        snapshot.insert_entry(&key, contract_data_entry()).unwrap();
+
+        let mut layered = LayeredSource::new(SnapshotLedgerSource::new(snapshot), None, 12);
+        layered.drop_entry(key.clone());
+        assert!(layered.get(&Rc::new(key.clone())).unwrap().is_none());
+        assert_eq!(layered.absent(), vec![key.clone()]);
+
+        let mut entry = contract_data_entry();
+        entry.last_modified_ledger_seq = 13;
+        layered.insert_local(&key, entry).unwrap();
+        let found = layered.peek(&key).unwrap().unwrap();
+        assert_eq!(found.entry.last_modified_ledger_seq, 13);
+    }
+
+    struct DownSource;
+
+    impl LedgerSource for DownSource {
+        fn name(&self) -> &str {
+            "down"
+        }
+
+        fn latest_ledger(&self) -> Result<u32, SourceError> {
+            Err(SourceError::Unavailable("connection refused".into()))
+        }
+
+        fn fetch(&self, _: &[LedgerKey]) -> Result<Vec<Option<SourceEntry>>, SourceError> {
+            Err(SourceError::Unavailable("connection refused".into()))
+        }
+    }
+
+    #[test]
+    fn test_layered_source_separates_absent_entries_from_failures() {
+        let key = Rc::new(contract_data_key());
+        let empty = || SnapshotLedgerSource::new(LedgerSnapshot::new());
+
+        let layered = LayeredSource::new(empty(), None, 12);
+        assert!(layered.get(&key).unwrap().is_none());
+        assert_eq!(layered.absent(), vec![LedgerKey::clone(&key)]);
+        assert!(layered.errors().is_empty());
+        assert_eq!(layered.read_origins()[0].1, Origin::Missing);
+
+        let remote = Arc::new(CachedSource::new(Arc::new(DownSource), 8));
+        let layered = LayeredSource::new(empty(), Some(remote), 12);
+        assert!(layered.get(&key).is_err());
+        assert!(layered.absent().is_empty());
+        assert_eq!(layered.errors().len(), 1);
+        assert_eq!(layered.read_origins()[0].1, Origin::Failed);
+    }
+}
@@ -0,0 +1,239 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Retries for remote state backends.
+//!
+//! Public soroban-rpc, Horizon and archive endpoints drop the occasional
+//! request or rate limit bursts. [`RetryingSource`] retries lookups that
+//! failed with [`SourceError::Unavailable`], backing off exponentially, until
+//! the retry count or the lookup's deadline runs out. Malformed responses are
+//! not retried: asking again would return the same thing.
+
+use super::{LedgerSource, SourceEntry, SourceError};
+use soroban_env_host::xdr::LedgerKey;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Default number of retries after the first attempt.
+pub const DEFAULT_MAX_RETRIES: u32 = 2;
+/// Default delay before the first retry, in milliseconds.
+pub const DEFAULT_BACKOFF_MS: u64 = 200;
+/// Default ceiling on the delay between retries, in milliseconds.
+pub const DEFAULT_MAX_BACKOFF_MS: u64 = 2_000;
+/// Default time budget for one lookup including retries, in milliseconds.
+pub const DEFAULT_DEADLINE_MS: u64 = 30_000;
+
+/// How failed lookups are retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt (0 disables retrying).
+    pub max_retries: u32,
+    /// Delay before the first retry; doubled for each further retry.
+    pub backoff: Duration,
+    /// Ceiling on the delay between retries.
+    pub max_backoff: Duration,
+    /// Time after which no further retry is started; `None` for no limit.
+    pub deadline: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_MAX_RETRIES,
+            DEFAULT_BACKOFF_MS,
+            DEFAULT_MAX_BACKOFF_MS,
+            DEFAULT_DEADLINE_MS,
+        )
+    }
+}
+
+impl RetryPolicy {
+    /// Builds a policy from millisecond values; a `deadline_ms` of 0 means
+    /// no deadline.
+    pub fn new(max_retries: u32, backoff_ms: u64, max_backoff_ms: u64, deadline_ms: u64) -> Self {
+        Self {
+            max_retries,
+            backoff: Duration::from_millis(backoff_ms),
+            max_backoff: Duration::from_millis(max_backoff_ms),
+            deadline: (deadline_ms > 0).then(|| Duration::from_millis(deadline_ms)),
+        }
+    }
+
+    /// Reads the policy from the environment, falling back to the defaults
+    /// for unset or unparsable values.
+    ///
+    /// Recognised variables:
+    /// - `ERST_SOURCE_MAX_RETRIES`
+    /// - `ERST_SOURCE_BACKOFF_MS`
+    /// - `ERST_SOURCE_MAX_BACKOFF_MS`
+    /// - `ERST_SOURCE_DEADLINE_MS`
+    pub fn from_env() -> Self {
+        fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(default)
+        }
+        Self::new(
+            env_or("ERST_SOURCE_MAX_RETRIES", DEFAULT_MAX_RETRIES),
+            env_or("ERST_SOURCE_BACKOFF_MS", DEFAULT_BACKOFF_MS),
+            env_or("ERST_SOURCE_MAX_BACKOFF_MS", DEFAULT_MAX_BACKOFF_MS),
+            env_or("ERST_SOURCE_DEADLINE_MS", DEFAULT_DEADLINE_MS),
+        )
+    }
+
+    /// Delay before retry number `retry` (starting at 1).
+    fn delay(&self, retry: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(retry - 1))
+            .min(self.max_backoff)
+    }
+}
+
+/// A [`LedgerSource`] that retries lookups the backend could not answer.
+pub struct RetryingSource {
+    inner: Arc<dyn LedgerSource>,
+    policy: RetryPolicy,
+}
+
+impl RetryingSource {
+    pub fn new(inner: Arc<dyn LedgerSource>, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    fn retry<T>(
+        &self,
+        operation: &str,
+        mut attempt: impl FnMut() -> Result<T, SourceError>,
+    ) -> Result<T, SourceError> {
+        let started = Instant::now();
+        let mut retries = 0;
+        loop {
+            let message = match attempt() {
+                Err(SourceError::Unavailable(message)) => message,
+                result => return result,
+            };
+            let delay = self.policy.delay(retries + 1);
+            let out_of_time = self
+                .policy
+                .deadline
+                .is_some_and(|deadline| started.elapsed() + delay >= deadline);
+            if retries >= self.policy.max_retries || out_of_time {
+                return Err(SourceError::Unavailable(if retries == 0 {
+                    message
+                } else {
+                    format!("{message} (gave up after {} attempts)", retries + 1)
+                }));
+            }
+            retries += 1;
+            tracing::debug!(
+                event = "state_fetch_retry",
+                source = self.inner.name(),
+                operation,
+                retry = retries,
+                delay_ms = delay.as_millis() as u64,
+                error = %message,
+            );
+            std::thread::sleep(delay);
+        }
+    }
+}
+
+impl LedgerSource for RetryingSource {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn latest_ledger(&self) -> Result<u32, SourceError> {
+        self.retry("latest_ledger", || self.inner.latest_ledger())
+    }
+
+    fn fetch(&self, keys: &[LedgerKey]) -> Result<Vec<Option<SourceEntry>>, SourceError> {
+        self.retry("fetch", || self.inner.fetch(keys))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Fails the first `failures` calls with `error`, then finds nothing.
+    struct FlakySource {
+        failures: u32,
+        error: SourceError,
+        calls: AtomicU32,
+    }
+
+    impl LedgerSource for FlakySource {
+        fn name(&self) -> &str {
+            "flaky"
+        }
+
+        fn latest_ledger(&self) -> Result<u32, SourceError> {
+            Ok(1)
+        }
+
+        fn fetch(&self, keys: &[LedgerKey]) -> Result<Vec<Option<SourceEntry>>, SourceError> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err(self.error.clone());
+            }
+            Ok(vec![None; keys.len()])
+        }
+    }
+
+    fn flaky(failures: u32, error: SourceError) -> Arc<FlakySource> {
+        Arc::new(FlakySource {
+            failures,
+            error,
+            calls: AtomicU32::new(0),
+        })
+    }
+
+    #[test]
+    fn test_unavailable_backends_are_retried() {
+        let policy = RetryPolicy::new(2, 1, 1, 0);
+        let source = flaky(2, SourceError::Unavailable("timeout".into()));
+        assert!(RetryingSource::new(source.clone(), policy)
+            .fetch(&[])
+            .is_ok());
+        assert_eq!(source.calls.load(Ordering::SeqCst), 3);
+
+        let source = flaky(5, SourceError::Unavailable("timeout".into()));
+        let err = RetryingSource::new(source.clone(), policy)
+            .fetch(&[])
+            .unwrap_err();
+        assert_eq!(
+            err,
+            SourceError::Unavailable("timeout (gave up after 3 attempts)".into())
+        );
+        assert_eq!(source.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_invalid_responses_and_deadlines_stop_retrying() {
+        let source = flaky(1, SourceError::InvalidResponse("bad xdr".into()));
+        let policy = RetryPolicy::new(5, 1, 1, 0);
+        assert!(RetryingSource::new(source.clone(), policy)
+            .fetch(&[])
+            .is_err());
+        assert_eq!(source.calls.load(Ordering::SeqCst), 1);
+
+        // The first backoff already overruns the deadline.
+        let source = flaky(5, SourceError::Unavailable("timeout".into()));
+        let policy = RetryPolicy::new(5, 50, 50, 10);
+        assert!(RetryingSource::new(source.clone(), policy)
+            .fetch(&[])
+            .is_err());
+        assert_eq!(source.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_ceiling() {
+        let policy = RetryPolicy::new(10, 100, 350, 0);
+        let delays: Vec<u64> = (1..=4)
+            .map(|r| policy.delay(r).as_millis() as u64)
+            .collect();
+        assert_eq!(delays, [100, 200, 350, 350]);
+    }
+}
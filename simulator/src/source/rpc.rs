@@ -0,0 +1,196 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Ledger state fetched from a soroban-rpc endpoint.
+//!
+//! Uses the JSON-RPC `getLatestLedger` and `getLedgerEntries` methods. Each
+//! `getLedgerEntries` result carries the entry's data rather than the full
+//! `LedgerEntry`, so the entry is reassembled from the data and
+//! `lastModifiedLedgerSeq`.
+
+use super::{encode_key, LedgerSource, SourceEntry, SourceError};
+use crate::xdr_limits;
+use base64::Engine;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use soroban_env_host::xdr::{LedgerEntry, LedgerEntryData, LedgerEntryExt, LedgerKey};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Maximum number of keys per `getLedgerEntries` call accepted by soroban-rpc.
+const MAX_KEYS_PER_REQUEST: usize = 200;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RpcLedgerEntry {
+    key: String,
+    xdr: String,
+    last_modified_ledger_seq: u32,
+    #[serde(default)]
+    live_until_ledger_seq: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LedgerEntriesResult {
+    #[serde(default)]
+    entries: Option<Vec<RpcLedgerEntry>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LatestLedgerResult {
+    sequence: u32,
+}
+
+/// A soroban-rpc endpoint.
+pub struct RpcSource {
+    url: String,
+    agent: ureq::Agent,
+}
+
+impl RpcSource {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            agent: ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build(),
+        }
+    }
+
+    fn call<T: serde::de::DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<T, SourceError> {
+        let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let response: Value = self
+            .agent
+            .post(&self.url)
+            .send_json(body)
+            .map_err(|e| SourceError::Unavailable(format!("{method}: {e}")))?
+            .into_json()
+            .map_err(|e| SourceError::InvalidResponse(format!("{method}: {e}")))?;
+        parse_rpc_result(method, response)
+    }
+}
+
+impl LedgerSource for RpcSource {
+    fn name(&self) -> &str {
+        "rpc"
+    }
+
+    fn latest_ledger(&self) -> Result<u32, SourceError> {
+        let result: LatestLedgerResult = self.call("getLatestLedger", json!({}))?;
+        Ok(result.sequence)
+    }
+
+    fn fetch(&self, keys: &[LedgerKey]) -> Result<Vec<Option<SourceEntry>>, SourceError> {
+        let b64 = base64::engine::general_purpose::STANDARD;
+        let encoded = keys
+            .iter()
+            .map(|key| encode_key(key).map(|xdr| b64.encode(xdr)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut found = HashMap::new();
+        for chunk in encoded.chunks(MAX_KEYS_PER_REQUEST) {
+            let result: LedgerEntriesResult =
+                self.call("getLedgerEntries", json!({ "keys": chunk }))?;
+            for entry in result.entries.unwrap_or_default() {
+                let key = entry.key.clone();
+                found.insert(key, to_source_entry(entry)?);
+            }
+        }
+        Ok(encoded.iter().map(|key| found.remove(key)).collect())
+    }
+}
+
+/// Extracts `result` from a JSON-RPC response, surfacing `error` if set.
+fn parse_rpc_result<T: serde::de::DeserializeOwned>(
+    method: &str,
+    mut response: Value,
+) -> Result<T, SourceError> {
+    if let Some(error) = response.get("error").filter(|e| !e.is_null()) {
+        let message = error
+            .get("message")
+            .and_then(Value::as_str)
+            .unwrap_or("unknown error");
+        return Err(SourceError::Unavailable(format!("{method}: {message}")));
+    }
+    let result = response
+        .get_mut("result")
+        .map(Value::take)
+        .ok_or_else(|| SourceError::InvalidResponse(format!("{method}: missing result")))?;
+    serde_json::from_value(result)
+        .map_err(|e| SourceError::InvalidResponse(format!("{method}: {e}")))
+}
+
+fn to_source_entry(entry: RpcLedgerEntry) -> Result<SourceEntry, SourceError> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(&entry.xdr)
+        .map_err(|e| SourceError::InvalidResponse(format!("invalid base64 entry: {e}")))?;
+    let data: LedgerEntryData = xdr_limits::read(&bytes)
+        .map_err(|e| SourceError::InvalidResponse(format!("invalid entry XDR: {e}")))?;
+    let live_until = match data {
+        LedgerEntryData::ContractData(_) | LedgerEntryData::ContractCode(_) => {
+            Some(entry.live_until_ledger_seq.unwrap_or(u32::MAX))
+        }
+        _ => None,
+    };
+    Ok(SourceEntry {
+        entry: LedgerEntry {
+            last_modified_ledger_seq: entry.last_modified_ledger_seq,
+            data,
+            ext: LedgerEntryExt::V0,
+        },
+        live_until,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::xdr::{ContractCodeEntry, ContractCodeEntryExt, Hash, Limits, WriteXdr};
+
+    #[test]
+    fn test_parses_ledger_entries_result() {
+        let data = LedgerEntryData::ContractCode(ContractCodeEntry {
+            ext: ContractCodeEntryExt::V0,
+            hash: Hash([1; 32]),
+            code: vec![0, 97, 115, 109].try_into().unwrap(),
+        });
+        let xdr =
+            base64::engine::general_purpose::STANDARD.encode(data.to_xdr(Limits::none()).unwrap());
+        let response = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": {
+                "entries": [{
+                    "key": "AAAABw==",
+                    "xdr": xdr,
+                    "lastModifiedLedgerSeq": 77,
+                    "liveUntilLedgerSeq": 1000
+                }],
+                "latestLedger": 80
+            }
+        });
+
+        let result: LedgerEntriesResult = parse_rpc_result("getLedgerEntries", response).unwrap();
+        let entry = to_source_entry(result.entries.unwrap().remove(0)).unwrap();
+        assert_eq!(entry.entry.last_modified_ledger_seq, 77);
+        assert_eq!(entry.live_until, Some(1000));
+        assert_eq!(entry.entry.data, data);
+    }
+
+    #[test]
+    fn test_surfaces_rpc_errors() {
+        let response = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "error": { "code": -32602, "message": "invalid keys" }
+        });
+        let err = parse_rpc_result::<LatestLedgerResult>("getLedgerEntries", response).unwrap_err();
+        assert_eq!(
+            err,
+            SourceError::Unavailable("getLedgerEntries: invalid keys".to_string())
+        );
+    }
+}
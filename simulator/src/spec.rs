@@ -0,0 +1,798 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Contract metadata and interface, read from the module's custom sections.
+//!
+//! `erst-sim spec` decodes the three sections the Soroban SDK embeds:
+//! `contractenvmetav0` (the interface version), `contractmetav0` (free-form
+//! key/value pairs such as the SDK and compiler versions) and
+//! `contractspecv0` (the functions, user-defined types, error enums and
+//! events). Types are rendered as they are written in the SDK, e.g.
+//! `Vec<Address>` or `Result<u32, Error>`, and the dump is the same whether
+//! the module comes from a file or, by contract ID, from the network.
+
+use crate::inspect::InterfaceVersion;
+use crate::invoke;
+use crate::network;
+use crate::snapshot::LedgerSnapshot;
+use crate::source::cache::{self, CachedSource};
+use crate::source::retry::RetryPolicy;
+use crate::source::{self, LayeredSource, SnapshotLedgerSource};
+use serde::Serialize;
+use soroban_env_host::meta::ENV_META_V0_SECTION_NAME;
+use soroban_env_host::xdr::{
+    ContractId, LedgerEntryData, LedgerKey, LedgerKeyContractCode, Limited, Limits, ReadXdr,
+    ScAddress, ScMetaEntry, ScSpecEntry, ScSpecEventDataFormat, ScSpecEventParamLocationV0,
+    ScSpecTypeDef, ScSpecUdtUnionCaseV0,
+};
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::Arc;
+use wasmparser::{Parser, Payload};
+
+const META_SECTION_NAME: &str = "contractmetav0";
+const SPEC_SECTION_NAME: &str = "contractspecv0";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ContractSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interface_version: Option<InterfaceVersion>,
+    pub meta: Vec<MetaEntry>,
+    pub functions: Vec<Function>,
+    pub structs: Vec<Struct>,
+    pub unions: Vec<Union>,
+    pub enums: Vec<Enum>,
+    pub error_enums: Vec<Enum>,
+    pub events: Vec<Event>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MetaEntry {
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Function {
+    pub name: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub doc: String,
+    pub inputs: Vec<Field>,
+    /// Return type; absent for functions returning nothing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+}
+
+/// A function input or struct field.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Field {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub doc: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Struct {
+    pub name: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub doc: String,
+    pub fields: Vec<Field>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Union {
+    pub name: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub doc: String,
+    pub cases: Vec<UnionCase>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnionCase {
+    pub name: String,
+    /// Types of the case's values; empty for a case without any.
+    pub types: Vec<String>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub doc: String,
+}
+
+/// An integer enum or a contract error enum.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Enum {
+    pub name: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub doc: String,
+    pub cases: Vec<EnumCase>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EnumCase {
+    pub name: String,
+    pub value: u32,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub doc: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Event {
+    pub name: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub doc: String,
+    /// Fixed topics preceding the parameters published as topics.
+    pub prefix_topics: Vec<String>,
+    pub params: Vec<EventParam>,
+    /// How the data parameters are published: `single_value`, `vec` or `map`.
+    pub data_format: &'static str,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EventParam {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    /// `topic` or `data`.
+    pub location: &'static str,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub doc: String,
+}
+
+/// Decodes the metadata and spec sections of `wasm`.
+pub fn from_wasm(wasm: &[u8]) -> Result<ContractSpec, String> {
+    let mut spec = ContractSpec::default();
+    for payload in Parser::new(0).parse_all(wasm) {
+        let Payload::CustomSection(section) = payload.map_err(|e| e.to_string())? else {
+            continue;
+        };
+        match section.name() {
+            ENV_META_V0_SECTION_NAME => {
+                spec.interface_version =
+                    crate::vm::interface_version(section.data()).map(|(protocol, pre_release)| {
+                        InterfaceVersion {
+                            protocol,
+                            pre_release,
+                        }
+                    });
+            }
+            META_SECTION_NAME => {
+                for entry in read_entries::<ScMetaEntry>(section.data(), META_SECTION_NAME)? {
+                    let ScMetaEntry::ScMetaV0(meta) = entry;
+                    spec.meta.push(MetaEntry {
+                        key: meta.key.to_utf8_string_lossy(),
+                        value: meta.val.to_utf8_string_lossy(),
+                    });
+                }
+            }
+            SPEC_SECTION_NAME => {
+                for entry in read_entries::<ScSpecEntry>(section.data(), SPEC_SECTION_NAME)? {
+                    add_entry(&mut spec, entry);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(spec)
+}
+
+/// Reads the module at `path`, or else the WASM `contract_id` runs from
+/// `rpc_url` or the `network` preset's endpoint.
+pub fn read_wasm(
+    path: Option<&Path>,
+    contract_id: Option<&str>,
+    network: Option<&str>,
+    rpc_url: Option<&str>,
+) -> Result<Vec<u8>, String> {
+    match (path, contract_id) {
+        (Some(path), _) => {
+            std::fs::read(path).map_err(|e| format!("Failed to read {}: {e}", path.display()))
+        }
+        (None, Some(contract_id)) => {
+            let url = match rpc_url {
+                Some(url) => url,
+                None => network::resolve(network)?.rpc_url,
+            };
+            let remote = source::rpc_cache(
+                url,
+                None,
+                cache::capacity_from_env(cache::DEFAULT_CACHE_ENTRIES),
+                cache::dir_from_env(None).as_deref(),
+                RetryPolicy::from_env(),
+            );
+            fetch_wasm(contract_id, remote)
+        }
+        (None, None) => Err("Either a WASM file or a contract ID is required".to_string()),
+    }
+}
+
+/// Reads the WASM that `contract_id` runs from `remote`.
+pub fn fetch_wasm(contract_id: &str, remote: Arc<CachedSource>) -> Result<Vec<u8>, String> {
+    let contract: ContractId = contract_id
+        .parse()
+        .map_err(|e| format!("Invalid contract ID {contract_id:?}: {e}"))?;
+    let ledger_sequence = remote.latest_ledger().map_err(|e| e.to_string())?;
+    let state = LayeredSource::new(
+        SnapshotLedgerSource::new(LedgerSnapshot::new()),
+        Some(remote),
+        ledger_sequence,
+    );
+    let resolved = invoke::resolve(&ScAddress::Contract(contract), &state, false)
+        .map_err(|e| e.to_string())?;
+    let Some(hash) = resolved.wasm_hash else {
+        return Err(format!(
+            "{contract_id} is a Stellar Asset Contract and has no WASM"
+        ));
+    };
+    let code = state
+        .read(&LedgerKey::ContractCode(LedgerKeyContractCode {
+            hash: hash.clone(),
+        }))
+        .map_err(|e| e.to_string())?;
+    match code.map(|code| code.entry.data) {
+        Some(LedgerEntryData::ContractCode(code)) => Ok(code.code.to_vec()),
+        _ => Err(format!("WASM {} not found", network::hex(&hash.0))),
+    }
+}
+
+fn read_entries<T: ReadXdr>(data: &[u8], section: &str) -> Result<Vec<T>, String> {
+    let mut cursor = Limited::new(Cursor::new(data), Limits::len(data.len()));
+    T::read_xdr_iter(&mut cursor)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to decode {section}: {e}"))
+}
+
+fn add_entry(spec: &mut ContractSpec, entry: ScSpecEntry) {
+    match entry {
+        ScSpecEntry::FunctionV0(function) => spec.functions.push(Function {
+            name: function.name.to_utf8_string_lossy(),
+            doc: function.doc.to_utf8_string_lossy(),
+            inputs: function
+                .inputs
+                .iter()
+                .map(|input| Field {
+                    name: input.name.to_utf8_string_lossy(),
+                    type_: type_name(&input.type_),
+                    doc: input.doc.to_utf8_string_lossy(),
+                })
+                .collect(),
+            output: function.outputs.first().map(type_name),
+        }),
+        ScSpecEntry::UdtStructV0(udt) => spec.structs.push(Struct {
+            name: udt.name.to_utf8_string_lossy(),
+            doc: udt.doc.to_utf8_string_lossy(),
+            fields: udt
+                .fields
+                .iter()
+                .map(|field| Field {
+                    name: field.name.to_utf8_string_lossy(),
+                    type_: type_name(&field.type_),
+                    doc: field.doc.to_utf8_string_lossy(),
+                })
+                .collect(),
+        }),
+        ScSpecEntry::UdtUnionV0(udt) => spec.unions.push(Union {
+            name: udt.name.to_utf8_string_lossy(),
+            doc: udt.doc.to_utf8_string_lossy(),
+            cases: udt
+                .cases
+                .iter()
+                .map(|case| match case {
+                    ScSpecUdtUnionCaseV0::VoidV0(case) => UnionCase {
+                        name: case.name.to_utf8_string_lossy(),
+                        types: Vec::new(),
+                        doc: case.doc.to_utf8_string_lossy(),
+                    },
+                    ScSpecUdtUnionCaseV0::TupleV0(case) => UnionCase {
+                        name: case.name.to_utf8_string_lossy(),
+                        types: case.type_.iter().map(type_name).collect(),
+                        doc: case.doc.to_utf8_string_lossy(),
+                    },
+                })
+                .collect(),
+        }),
+        ScSpecEntry::UdtEnumV0(udt) => spec.enums.push(Enum {
+            name: udt.name.to_utf8_string_lossy(),
+            doc: udt.doc.to_utf8_string_lossy(),
+            cases: udt
+                .cases
+                .iter()
+                .map(|case| EnumCase {
+                    name: case.name.to_utf8_string_lossy(),
+                    value: case.value,
+                    doc: case.doc.to_utf8_string_lossy(),
+                })
+                .collect(),
+        }),
+        ScSpecEntry::UdtErrorEnumV0(udt) => spec.error_enums.push(Enum {
+            name: udt.name.to_utf8_string_lossy(),
+            doc: udt.doc.to_utf8_string_lossy(),
+            cases: udt
+                .cases
+                .iter()
+                .map(|case| EnumCase {
+                    name: case.name.to_utf8_string_lossy(),
+                    value: case.value,
+                    doc: case.doc.to_utf8_string_lossy(),
+                })
+                .collect(),
+        }),
+        ScSpecEntry::EventV0(event) => spec.events.push(Event {
+            name: event.name.to_utf8_string_lossy(),
+            doc: event.doc.to_utf8_string_lossy(),
+            prefix_topics: event
+                .prefix_topics
+                .iter()
+                .map(|topic| topic.to_utf8_string_lossy())
+                .collect(),
+            params: event
+                .params
+                .iter()
+                .map(|param| EventParam {
+                    name: param.name.to_utf8_string_lossy(),
+                    type_: type_name(&param.type_),
+                    location: match param.location {
+                        ScSpecEventParamLocationV0::TopicList => "topic",
+                        ScSpecEventParamLocationV0::Data => "data",
+                    },
+                    doc: param.doc.to_utf8_string_lossy(),
+                })
+                .collect(),
+            data_format: match event.data_format {
+                ScSpecEventDataFormat::SingleValue => "single_value",
+                ScSpecEventDataFormat::Vec => "vec",
+                ScSpecEventDataFormat::Map => "map",
+            },
+        }),
+    }
+}
+
+/// Interface changes between two builds of a contract.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SpecDiff {
+    /// Whether any change can break existing callers or event consumers.
+    pub breaking: bool,
+    pub changes: Vec<SpecChange>,
+}
+
+impl SpecDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SpecChange {
+    /// `function`, `struct`, `union`, `enum`, `error_enum` or `event`.
+    pub item: &'static str,
+    pub name: String,
+    /// `added`, `removed` or `changed`.
+    pub change: &'static str,
+    pub breaking: bool,
+    /// What changed, e.g. `+ field memo: String` or
+    /// `output: u32 -> Result<u32, Error>`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<String>,
+}
+
+/// One item of a spec, flattened for comparison.
+struct Item {
+    kind: &'static str,
+    name: String,
+    /// Fields, cases or parameters, e.g. `field to: Address`.
+    members: Vec<String>,
+    /// Input and output types, compared as a whole.
+    signature: Option<(String, String)>,
+}
+
+fn items(spec: &ContractSpec) -> Vec<Item> {
+    let field = |prefix: &str, f: &Field| format!("{prefix} {}: {}", f.name, f.type_);
+    let case = |c: &EnumCase| format!("case {} = {}", c.name, c.value);
+    let mut items = Vec::new();
+    for function in &spec.functions {
+        let inputs = function
+            .inputs
+            .iter()
+            .map(|input| format!("{}: {}", input.name, input.type_))
+            .collect::<Vec<_>>();
+        items.push(Item {
+            kind: "function",
+            name: function.name.clone(),
+            members: Vec::new(),
+            signature: Some((
+                format!("({})", inputs.join(", ")),
+                function.output.clone().unwrap_or_else(|| "()".to_string()),
+            )),
+        });
+    }
+    for udt in &spec.structs {
+        items.push(Item {
+            kind: "struct",
+            name: udt.name.clone(),
+            members: udt.fields.iter().map(|f| field("field", f)).collect(),
+            signature: None,
+        });
+    }
+    for udt in &spec.unions {
+        let cases = udt.cases.iter().map(|c| match c.types.as_slice() {
+            [] => format!("case {}", c.name),
+            types => format!("case {}({})", c.name, types.join(", ")),
+        });
+        items.push(Item {
+            kind: "union",
+            name: udt.name.clone(),
+            members: cases.collect(),
+            signature: None,
+        });
+    }
+    for (kind, enums) in [("enum", &spec.enums), ("error_enum", &spec.error_enums)] {
+        for udt in enums {
+            items.push(Item {
+                kind,
+                name: udt.name.clone(),
+                members: udt.cases.iter().map(case).collect(),
+                signature: None,
+            });
+        }
+    }
+    for event in &spec.events {
+        let mut members = event
+            .prefix_topics
+            .iter()
+            .map(|topic| format!("topic {topic}"))
+            .collect::<Vec<_>>();
+        members.extend(
+            event
+                .params
+                .iter()
+                .map(|p| format!("{} param {}: {}", p.location, p.name, p.type_)),
+        );
+        members.push(format!("data format {}", event.data_format));
+        items.push(Item {
+            kind: "event",
+            name: event.name.clone(),
+            members,
+            signature: None,
+        });
+    }
+    items
+}
+
+/// Compares the interface of `old` with `new`.
+///
+/// Removing anything is breaking. Changing a function's signature, a
+/// struct's fields or an event's shape is breaking; adding functions,
+/// types, events, or cases to an enum or union is not.
+pub fn diff(old: &ContractSpec, new: &ContractSpec) -> SpecDiff {
+    let old_items = items(old);
+    let new_items = items(new);
+    let find = |items: &'_ [Item], item: &Item| {
+        items
+            .iter()
+            .position(|other| other.kind == item.kind && other.name == item.name)
+    };
+    let mut diff = SpecDiff::default();
+    for item in &old_items {
+        let Some(index) = find(&new_items, item) else {
+            diff.changes.push(SpecChange {
+                item: item.kind,
+                name: item.name.clone(),
+                change: "removed",
+                breaking: true,
+                details: Vec::new(),
+            });
+            continue;
+        };
+        let other = &new_items[index];
+        let mut details = Vec::new();
+        let mut breaking = false;
+        if let (Some((old_in, old_out)), Some((new_in, new_out))) =
+            (&item.signature, &other.signature)
+        {
+            if old_in != new_in {
+                details.push(format!("inputs: {old_in} -> {new_in}"));
+            }
+            if old_out != new_out {
+                details.push(format!("output: {old_out} -> {new_out}"));
+            }
+            breaking = !details.is_empty();
+        }
+        for member in &item.members {
+            if !other.members.contains(member) {
+                details.push(format!("- {member}"));
+                breaking = true;
+            }
+        }
+        for member in &other.members {
+            if !item.members.contains(member) {
+                details.push(format!("+ {member}"));
+                breaking |= !matches!(item.kind, "enum" | "error_enum" | "union");
+            }
+        }
+        if !details.is_empty() {
+            diff.changes.push(SpecChange {
+                item: item.kind,
+                name: item.name.clone(),
+                change: "changed",
+                breaking,
+                details,
+            });
+        }
+    }
+    for item in &new_items {
+        if find(&old_items, item).is_none() {
+            diff.changes.push(SpecChange {
+                item: item.kind,
+                name: item.name.clone(),
+                change: "added",
+                breaking: false,
+                details: Vec::new(),
+            });
+        }
+    }
+    diff.breaking = diff.changes.iter().any(|change| change.breaking);
+    diff
+}
+
+/// Compares the specs of the modules at `old` and `new`.
+pub fn diff_files(old: &Path, new: &Path) -> Result<SpecDiff, String> {
+    let load = |path: &Path| {
+        let wasm =
+            std::fs::read(path).map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        from_wasm(&wasm).map_err(|e| format!("{}: {e}", path.display()))
+    };
+    Ok(diff(&load(old)?, &load(new)?))
+}
+
+/// `ty` as written in the SDK, e.g. `Map<Symbol, Vec<i128>>`.
+pub fn type_name(ty: &ScSpecTypeDef) -> String {
+    match ty {
+        ScSpecTypeDef::Val => "Val".to_string(),
+        ScSpecTypeDef::Bool => "bool".to_string(),
+        ScSpecTypeDef::Void => "()".to_string(),
+        ScSpecTypeDef::Error => "Error".to_string(),
+        ScSpecTypeDef::U32 => "u32".to_string(),
+        ScSpecTypeDef::I32 => "i32".to_string(),
+        ScSpecTypeDef::U64 => "u64".to_string(),
+        ScSpecTypeDef::I64 => "i64".to_string(),
+        ScSpecTypeDef::Timepoint => "Timepoint".to_string(),
+        ScSpecTypeDef::Duration => "Duration".to_string(),
+        ScSpecTypeDef::U128 => "u128".to_string(),
+        ScSpecTypeDef::I128 => "i128".to_string(),
+        ScSpecTypeDef::U256 => "U256".to_string(),
+        ScSpecTypeDef::I256 => "I256".to_string(),
+        ScSpecTypeDef::Bytes => "Bytes".to_string(),
+        ScSpecTypeDef::String => "String".to_string(),
+        ScSpecTypeDef::Symbol => "Symbol".to_string(),
+        ScSpecTypeDef::Address => "Address".to_string(),
+        ScSpecTypeDef::MuxedAddress => "MuxedAddress".to_string(),
+        ScSpecTypeDef::Option(option) => format!("Option<{}>", type_name(&option.value_type)),
+        ScSpecTypeDef::Result(result) => format!(
+            "Result<{}, {}>",
+            type_name(&result.ok_type),
+            type_name(&result.error_type)
+        ),
+        ScSpecTypeDef::Vec(vec) => format!("Vec<{}>", type_name(&vec.element_type)),
+        ScSpecTypeDef::Map(map) => format!(
+            "Map<{}, {}>",
+            type_name(&map.key_type),
+            type_name(&map.value_type)
+        ),
+        ScSpecTypeDef::Tuple(tuple) => format!(
+            "({})",
+            tuple
+                .value_types
+                .iter()
+                .map(type_name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        ScSpecTypeDef::BytesN(bytes) => format!("BytesN<{}>", bytes.n),
+        ScSpecTypeDef::Udt(udt) => udt.name.to_utf8_string_lossy(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::xdr::{
+        ScMetaV0, ScSpecFunctionInputV0, ScSpecFunctionV0, ScSpecTypeBytesN, ScSpecTypeMap,
+        ScSpecTypeOption, ScSpecTypeResult, ScSpecTypeUdt, ScSpecTypeVec, ScSpecUdtErrorEnumCaseV0,
+        ScSpecUdtErrorEnumV0, WriteXdr,
+    };
+
+    fn custom_section(name: &str, data: &[u8]) -> Vec<u8> {
+        let mut body = vec![u8::try_from(name.len()).unwrap()];
+        body.extend_from_slice(name.as_bytes());
+        body.extend_from_slice(data);
+        let mut section = vec![0];
+        // Unsigned LEB128 section size.
+        let mut size = body.len();
+        loop {
+            let byte = u8::try_from(size & 0x7f).unwrap();
+            size >>= 7;
+            if size == 0 {
+                section.push(byte);
+                break;
+            }
+            section.push(byte | 0x80);
+        }
+        section.extend_from_slice(&body);
+        section
+    }
+
+    fn encode(entries: &[impl WriteXdr]) -> Vec<u8> {
+        entries
+            .iter()
+            .flat_map(|entry| entry.to_xdr(Limits::none()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_reads_meta_functions_and_errors() {
+        let function = ScSpecEntry::FunctionV0(ScSpecFunctionV0 {
+            doc: "Pays out".try_into().unwrap(),
+            name: "pay".try_into().unwrap(),
+            inputs: vec![ScSpecFunctionInputV0 {
+                doc: Default::default(),
+                name: "to".try_into().unwrap(),
+                type_: ScSpecTypeDef::Vec(Box::new(ScSpecTypeVec {
+                    element_type: Box::new(ScSpecTypeDef::Address),
+                })),
+            }]
+            .try_into()
+            .unwrap(),
+            outputs: vec![ScSpecTypeDef::Result(Box::new(ScSpecTypeResult {
+                ok_type: Box::new(ScSpecTypeDef::U32),
+                error_type: Box::new(ScSpecTypeDef::Udt(ScSpecTypeUdt {
+                    name: "Error".try_into().unwrap(),
+                })),
+            }))]
+            .try_into()
+            .unwrap(),
+        });
+        let errors = ScSpecEntry::UdtErrorEnumV0(ScSpecUdtErrorEnumV0 {
+            doc: Default::default(),
+            lib: Default::default(),
+            name: "Error".try_into().unwrap(),
+            cases: vec![ScSpecUdtErrorEnumCaseV0 {
+                doc: Default::default(),
+                name: "Broke".try_into().unwrap(),
+                value: 3,
+            }]
+            .try_into()
+            .unwrap(),
+        });
+        let meta = ScMetaEntry::ScMetaV0(ScMetaV0 {
+            key: "rsver".try_into().unwrap(),
+            val: "1.84.0".try_into().unwrap(),
+        });
+        let mut wasm = wat::parse_str("(module)").unwrap();
+        wasm.extend(custom_section(META_SECTION_NAME, &encode(&[meta])));
+        wasm.extend(custom_section(
+            SPEC_SECTION_NAME,
+            &encode(&[function, errors]),
+        ));
+
+        let spec = from_wasm(&wasm).unwrap();
+        assert_eq!(
+            spec.meta,
+            vec![MetaEntry {
+                key: "rsver".into(),
+                value: "1.84.0".into(),
+            }]
+        );
+        assert_eq!(spec.functions[0].name, "pay");
+        assert_eq!(spec.functions[0].doc, "Pays out");
+        assert_eq!(spec.functions[0].inputs[0].type_, "Vec<Address>");
+        assert_eq!(
+            spec.functions[0].output.as_deref(),
+            Some("Result<u32, Error>")
+        );
+        assert_eq!(spec.error_enums[0].cases[0].name, "Broke");
+        assert_eq!(spec.error_enums[0].cases[0].value, 3);
+        assert_eq!(spec.interface_version, None);
+    }
+
+    fn function(name: &str, inputs: &[(&str, &str)], output: Option<&str>) -> Function {
+        Function {
+            name: name.into(),
+            doc: String::new(),
+            inputs: inputs
+                .iter()
+                .map(|(name, type_)| Field {
+                    name: (*name).into(),
+                    type_: (*type_).into(),
+                    doc: String::new(),
+                })
+                .collect(),
+            output: output.map(Into::into),
+        }
+    }
+
+    fn error_enum(cases: &[(&str, u32)]) -> Enum {
+        Enum {
+            name: "Error".into(),
+            doc: String::new(),
+            cases: cases
+                .iter()
+                .map(|(name, value)| EnumCase {
+                    name: (*name).into(),
+                    value: *value,
+                    doc: String::new(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_diff_classifies_breaking_changes() {
+        let old = ContractSpec {
+            functions: vec![
+                function("pay", &[("to", "Address")], Some("u32")),
+                function("close", &[], None),
+                function("peek", &[], Some("u32")),
+            ],
+            error_enums: vec![error_enum(&[("Broke", 1)])],
+            ..ContractSpec::default()
+        };
+        let new = ContractSpec {
+            functions: vec![
+                function("pay", &[("to", "Address"), ("memo", "String")], Some("u32")),
+                function("peek", &[], Some("u32")),
+                function("open", &[], None),
+            ],
+            error_enums: vec![error_enum(&[("Broke", 1), ("Locked", 2)])],
+            ..ContractSpec::default()
+        };
+
+        let diff = diff(&old, &new);
+        let summary = diff
+            .changes
+            .iter()
+            .map(|c| (c.item, c.name.as_str(), c.change, c.breaking))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("function", "pay", "changed", true),
+                ("function", "close", "removed", true),
+                ("error_enum", "Error", "changed", false),
+                ("function", "open", "added", false),
+            ]
+        );
+        assert_eq!(
+            diff.changes[0].details,
+            vec!["inputs: (to: Address) -> (to: Address, memo: String)"]
+        );
+        assert_eq!(diff.changes[2].details, vec!["+ case Locked = 2"]);
+        assert!(diff.breaking);
+    }
+
+    #[test]
+    fn test_diff_of_same_spec_is_empty() {
+        let spec = ContractSpec {
+            functions: vec![function("pay", &[("to", "Address")], Some("u32"))],
+            ..ContractSpec::default()
+        };
+        assert!(diff(&spec, &spec).is_empty());
+    }
+
+    #[test]
+    fn test_type_names_nest() {
+        let ty = ScSpecTypeDef::Map(Box::new(ScSpecTypeMap {
+            key_type: Box::new(ScSpecTypeDef::Symbol),
+            value_type: Box::new(ScSpecTypeDef::Option(Box::new(ScSpecTypeOption {
+                value_type: Box::new(ScSpecTypeDef::BytesN(ScSpecTypeBytesN { n: 32 })),
+            }))),
+        }));
+        assert_eq!(type_name(&ty), "Map<Symbol, Option<BytesN<32>>>");
+    }
+
+    #[test]
+    fn test_rejects_truncated_spec() {
+        let mut wasm = wat::parse_str("(module)").unwrap();
+        wasm.extend(custom_section(SPEC_SECTION_NAME, &[0, 0, 0]));
+        let error = from_wasm(&wasm).unwrap_err();
+        assert!(error.contains("Failed to decode contractspecv0"), "{error}");
+    }
+}
@@ -6,10 +6,11 @@
 //! Exposes the Wasmi internal call stack directly on traps,
 //! bypassing Soroban Host abstractions for low-level debugging.
 
+use schemars::JsonSchema;
 use serde::Serialize;
 
 /// A single frame in a WASM call stack.
-#[derive(Debug, Clone, Serialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, PartialEq, JsonSchema)]
 pub struct StackFrame {
     /// Index within the call stack (0 = innermost/trap site).
     pub index: usize,
@@ -24,7 +25,7 @@ pub struct StackFrame {
 }
 
 /// Categorised trap reason extracted from a raw error string.
-#[derive(Debug, Clone, Serialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, PartialEq, JsonSchema)]
 pub enum TrapKind {
     OutOfBoundsMemoryAccess,
     OutOfBoundsTableAccess,
@@ -40,7 +41,7 @@ pub enum TrapKind {
 }
 
 /// Structured stack trace emitted on a WASM trap.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, JsonSchema)]
 pub struct WasmStackTrace {
     /// Categorised trap reason.
     pub trap_kind: TrapKind,
@@ -0,0 +1,216 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Ordered log of the ledger entries a simulation accessed.
+//!
+//! The host loads an entry from the [`LayeredSource`](crate::source::LayeredSource)
+//! the first time a contract gets, puts, checks or deletes it, and from then
+//! on serves it from its own storage map. Individual storage calls are not
+//! observable outside the host, so the log has one record per entry, in the
+//! order the entries were first accessed. Each record carries the layer that
+//! supplied the entry and what the transaction did to it overall, which is
+//! what the footprint and rent are computed from.
+
+use crate::resources::xdr_len;
+use crate::source::{Origin, SourceEntry};
+use schemars::JsonSchema;
+use serde::Serialize;
+use soroban_env_host::budget::Budget;
+use soroban_env_host::storage::{AccessType, Storage};
+use soroban_env_host::xdr::{LedgerKey, Limits, WriteXdr};
+
+/// One ledger entry the host accessed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct StorageAccess {
+    /// Base64 `LedgerKey`.
+    pub key: String,
+    /// Entry type, e.g. `ContractData`.
+    pub entry_type: String,
+    /// Footprint access: `read_only` or `read_write`.
+    pub access: &'static str,
+    /// Net effect: `read`, `create`, `update` or `delete`.
+    pub operation: &'static str,
+    /// Where the entry came from: `snapshot`, `base_snapshot`, the remote
+    /// source's name (e.g. `rpc`), `missing` if it did not exist, `created`
+    /// if the transaction created it, or `failed` if the lookup failed.
+    pub origin: String,
+    /// XDR size of the entry after the transaction, or before it for
+    /// deleted entries; 0 if it never existed.
+    pub entry_bytes: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub live_until_before: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub live_until_after: Option<u32>,
+}
+
+/// Lists the footprint of `storage` in the order of `reads`, the host's
+/// lookups with their origins. `before` is the state the simulation started
+/// from.
+pub fn storage_accesses(
+    storage: &Storage,
+    reads: &[(LedgerKey, Origin)],
+    before: impl Fn(&LedgerKey) -> Option<SourceEntry>,
+) -> Result<Vec<StorageAccess>, String> {
+    let budget = Budget::default();
+    let footprint = storage
+        .footprint
+        .0
+        .iter(&budget)
+        .map_err(|e| format!("{e:?}"))?;
+    let mut accesses = Vec::new();
+    for (key, access) in footprint {
+        let first_read = reads.iter().position(|(read, _)| read == key.as_ref());
+        let old = before(key);
+        let new = storage
+            .map
+            .get::<LedgerKey>(key, &budget)
+            .map_err(|e| format!("{e:?}"))?
+            .and_then(Option::as_ref);
+        let operation = match (*access, &old, new) {
+            (AccessType::ReadWrite, None, Some(_)) => "create",
+            (AccessType::ReadWrite, Some(_), None) => "delete",
+            (AccessType::ReadWrite, Some(old), Some((new, _))) if old.entry.data != new.data => {
+                "update"
+            }
+            _ => "read",
+        };
+        let origin = match (operation, first_read) {
+            ("create", _) => "created".to_string(),
+            (_, Some(index)) => reads[index].1.label().to_string(),
+            (_, None) => Origin::Missing.label().to_string(),
+        };
+        let entry_bytes = match (new, &old) {
+            (Some((entry, _)), _) => xdr_len(entry.as_ref())?,
+            (None, Some(old)) => xdr_len(&old.entry)?,
+            (None, None) => 0,
+        };
+        accesses.push((
+            first_read.unwrap_or(usize::MAX),
+            StorageAccess {
+                key: key
+                    .to_xdr_base64(Limits::none())
+                    .map_err(|e| e.to_string())?,
+                entry_type: key.name().to_string(),
+                access: match access {
+                    AccessType::ReadOnly => "read_only",
+                    AccessType::ReadWrite => "read_write",
+                },
+                operation,
+                origin,
+                entry_bytes,
+                live_until_before: old.and_then(|old| old.live_until),
+                live_until_after: new.and_then(|(_, live_until)| *live_until),
+            },
+        ));
+    }
+    // Stable, so entries the host never looked up keep footprint order.
+    accesses.sort_by_key(|(first_read, _)| *first_read);
+    Ok(accesses.into_iter().map(|(_, access)| access).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::storage::{Footprint, FootprintMap, StorageMap};
+    use soroban_env_host::xdr::{
+        ContractDataDurability, ContractDataEntry, ContractId, ExtensionPoint, Hash, LedgerEntry,
+        LedgerEntryData, LedgerEntryExt, LedgerKeyContractData, ScAddress, ScVal,
+    };
+    use std::rc::Rc;
+
+    fn contract() -> ScAddress {
+        ScAddress::Contract(ContractId(Hash([7; 32])))
+    }
+
+    fn data_key(name: &str) -> LedgerKey {
+        LedgerKey::ContractData(LedgerKeyContractData {
+            contract: contract(),
+            key: ScVal::Symbol(name.try_into().unwrap()),
+            durability: ContractDataDurability::Persistent,
+        })
+    }
+
+    fn data_entry(name: &str, value: u32) -> LedgerEntry {
+        LedgerEntry {
+            last_modified_ledger_seq: 10,
+            data: LedgerEntryData::ContractData(ContractDataEntry {
+                ext: ExtensionPoint::V0,
+                contract: contract(),
+                key: ScVal::Symbol(name.try_into().unwrap()),
+                durability: ContractDataDurability::Persistent,
+                val: ScVal::U32(value),
+            }),
+            ext: LedgerEntryExt::V0,
+        }
+    }
+
+    #[test]
+    fn test_accesses_follow_read_order_with_origins() {
+        let budget = Budget::default();
+        // (key, access, before, after), in the order the host read them.
+        let cases = [
+            ("remote", AccessType::ReadOnly, Some(1), Some(1)),
+            ("created", AccessType::ReadWrite, None, Some(2)),
+            ("updated", AccessType::ReadWrite, Some(3), Some(4)),
+            ("deleted", AccessType::ReadWrite, Some(5), None),
+            ("probed", AccessType::ReadOnly, None, None),
+        ];
+        let mut footprint = Vec::new();
+        let mut map = Vec::new();
+        for (name, access, _, after) in &cases {
+            let key = Rc::new(data_key(name));
+            footprint.push((key.clone(), *access));
+            let after = after.map(|value| (Rc::new(data_entry(name, value)), Some(300)));
+            map.push((key, after));
+        }
+        footprint.sort_by(|a, b| a.0.cmp(&b.0));
+        map.sort_by(|a, b| a.0.cmp(&b.0));
+        let storage = Storage::with_enforcing_footprint_and_map(
+            Footprint(FootprintMap::from_map(footprint, &budget).unwrap()),
+            StorageMap::from_map(map, &budget).unwrap(),
+        );
+        let reads = cases
+            .iter()
+            .map(|(name, _, before, _)| {
+                let origin = match (name, before) {
+                    (&"remote", _) => Origin::Remote("rpc".into()),
+                    (_, Some(_)) => Origin::Snapshot,
+                    (_, None) => Origin::Missing,
+                };
+                (data_key(name), origin)
+            })
+            .collect::<Vec<_>>();
+        let before = |key: &LedgerKey| {
+            let (name, _, before, _) = cases.iter().find(|(name, ..)| data_key(name) == *key)?;
+            before.map(|value| SourceEntry {
+                entry: data_entry(name, value),
+                live_until: Some(100),
+            })
+        };
+
+        let accesses = storage_accesses(&storage, &reads, before).unwrap();
+        let summary = accesses
+            .iter()
+            .map(|a| (a.access, a.operation, a.origin.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("read_only", "read", "rpc"),
+                ("read_write", "create", "created"),
+                ("read_write", "update", "snapshot"),
+                ("read_write", "delete", "snapshot"),
+                ("read_only", "read", "missing"),
+            ]
+        );
+        assert_eq!(
+            accesses[0].key,
+            data_key("remote").to_xdr_base64(Limits::none()).unwrap()
+        );
+        assert_eq!(accesses[1].live_until_before, None);
+        assert_eq!(accesses[1].live_until_after, Some(300));
+        assert!(accesses[3].entry_bytes > 0);
+        assert_eq!(accesses[3].live_until_after, None);
+        assert_eq!(accesses[4].entry_bytes, 0);
+    }
+}
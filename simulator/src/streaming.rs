@@ -0,0 +1,272 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Incremental parsing of JSON simulation requests.
+//!
+//! `ledger_entries` can hold hundreds of megabytes of base64 XDR. Reading the
+//! whole body, parsing it into a `serde_json::Value` and then converting that
+//! into a [`SimulationRequest`] holds the payload two to three times over
+//! before simulation even starts. [`parse_json`] instead parses straight from
+//! the reader: each ledger entry is checked and moved into the final map as
+//! it arrives, and only the remaining (small) fields pass through a `Value`
+//! for schema validation.
+//!
+//! The bytes read are charged to the global memory budget as they arrive and
+//! held to the request ceiling, so an oversized body is rejected part way
+//! through rather than after it has been buffered.
+
+use crate::memory::{self, MemoryError, MemoryReservation};
+use crate::schema::{self, FieldError};
+use crate::types::SimulationRequest;
+use serde::de::{DeserializeSeed, Deserializer, MapAccess, Visitor};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufReader, Read};
+
+/// Why a request could not be parsed.
+#[derive(Debug)]
+pub enum ParseError {
+    /// The body is not well-formed; the message names the format.
+    Syntax(String),
+    /// The body is JSON but not a valid request.
+    Invalid(String),
+    /// The body exceeds a memory ceiling or could not be read.
+    Memory(MemoryError),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Syntax(msg) => write!(f, "{msg}"),
+            ParseError::Invalid(msg) => write!(f, "Invalid request: {msg}"),
+            ParseError::Memory(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A parsed request.
+#[derive(Debug)]
+pub struct ParsedRequest {
+    pub request: SimulationRequest,
+    /// Every field of the request except `ledger_entries`.
+    pub fields: Value,
+    /// Memory charged for the body; hold it until the request is done.
+    pub reservation: MemoryReservation<'static>,
+}
+
+/// Parses and validates a JSON request from `reader`, reading at most
+/// `limit` bytes.
+pub fn parse_json<R: Read>(reader: R, limit: usize) -> Result<ParsedRequest, ParseError> {
+    let mut metered = MeteredReader {
+        inner: reader,
+        limit,
+        read: 0,
+        reservation: memory::global_accountant()
+            .reserve(0)
+            .map_err(ParseError::Memory)?,
+        error: None,
+    };
+    let streamed = {
+        let mut de = serde_json::Deserializer::from_reader(BufReader::new(&mut metered));
+        Streamed::deserialize(&mut de).and_then(|streamed| de.end().map(|()| streamed))
+    };
+    let streamed = match streamed {
+        Ok(streamed) => streamed,
+        Err(e) => {
+            return Err(match metered.error.take() {
+                Some(memory_error) => ParseError::Memory(memory_error),
+                None if e.is_io() => ParseError::Memory(MemoryError::Io(e.to_string())),
+                None if e.is_data() => ParseError::Invalid(e.to_string()),
+                None => ParseError::Syntax(format!("Invalid JSON: {e}")),
+            })
+        }
+    };
+
+    let fields = Value::Object(streamed.fields);
+    let mut errors = schema::validate_request(&fields).err().unwrap_or_default();
+    errors.extend(streamed.entry_errors);
+    if !errors.is_empty() {
+        errors.truncate(schema::MAX_ERRORS);
+        return Err(ParseError::Invalid(schema::describe(&errors)));
+    }
+    let mut request =
+        SimulationRequest::deserialize(&fields).map_err(|e| ParseError::Invalid(e.to_string()))?;
+    request.ledger_entries = streamed.ledger_entries;
+    Ok(ParsedRequest {
+        request,
+        fields,
+        reservation: metered.reservation,
+    })
+}
+
+/// Counts bytes against the request ceiling and the global memory budget.
+struct MeteredReader<R> {
+    inner: R,
+    limit: usize,
+    read: usize,
+    reservation: MemoryReservation<'static>,
+    /// The ceiling that stopped the read, if any.
+    error: Option<MemoryError>,
+}
+
+impl<R: Read> Read for MeteredReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read += n;
+        let exceeded = if self.read > self.limit {
+            Err(MemoryError::RequestTooLarge { limit: self.limit })
+        } else {
+            self.reservation.grow(n)
+        };
+        match exceeded {
+            Ok(()) => Ok(n),
+            Err(e) => {
+                let message = e.to_string();
+                self.error = Some(e);
+                Err(io::Error::other(message))
+            }
+        }
+    }
+}
+
+/// A request split into `ledger_entries` and everything else.
+struct Streamed {
+    fields: Map<String, Value>,
+    ledger_entries: Option<HashMap<String, String>>,
+    entry_errors: Vec<FieldError>,
+}
+
+impl<'de> Deserialize<'de> for Streamed {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(RequestVisitor)
+    }
+}
+
+struct RequestVisitor;
+
+impl<'de> Visitor<'de> for RequestVisitor {
+    type Value = Streamed;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a request object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Streamed, A::Error> {
+        let mut streamed = Streamed {
+            fields: Map::new(),
+            ledger_entries: None,
+            entry_errors: Vec::new(),
+        };
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "ledger_entries" {
+                streamed.ledger_entries = map.next_value_seed(EntriesSeed {
+                    errors: &mut streamed.entry_errors,
+                })?;
+            } else {
+                let value = map.next_value()?;
+                streamed.fields.insert(key, value);
+            }
+        }
+        Ok(streamed)
+    }
+}
+
+/// Reads `ledger_entries` one pair at a time, checking each as it arrives.
+struct EntriesSeed<'a> {
+    errors: &'a mut Vec<FieldError>,
+}
+
+impl<'de> DeserializeSeed<'de> for EntriesSeed<'_> {
+    type Value = Option<HashMap<String, String>>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_option(self)
+    }
+}
+
+impl<'de> Visitor<'de> for EntriesSeed<'_> {
+    type Value = Option<HashMap<String, String>>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a map of base64 ledger keys to base64 ledger entries")
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut entries = HashMap::with_capacity(map.size_hint().unwrap_or(0));
+        while let Some((key, value)) = map.next_entry::<String, String>()? {
+            if self.errors.len() < schema::MAX_ERRORS {
+                schema::check_ledger_entry(&key, &value, self.errors);
+            }
+            entries.insert(key, value);
+        }
+        Ok(Some(entries))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIELDS: &str = r#""envelope_xdr":"AAAA","result_meta_xdr":"","enable_optimization_advisor":false,"timestamp":"""#;
+
+    fn parse(body: &str) -> Result<ParsedRequest, ParseError> {
+        parse_json(body.as_bytes(), 1 << 20)
+    }
+
+    #[test]
+    fn test_entries_are_split_from_fields() {
+        let body = format!(r#"{{"ledger_entries":{{"AAAA":"AAAB"}},{FIELDS}}}"#);
+        let parsed = parse(&body).unwrap();
+        assert_eq!(
+            parsed.request.ledger_entries.unwrap()["AAAA"],
+            "AAAB".to_string()
+        );
+        assert!(parsed.fields.get("ledger_entries").is_none());
+        assert_eq!(parsed.fields["envelope_xdr"], "AAAA");
+        assert_eq!(parsed.reservation.bytes(), body.len());
+
+        let parsed = parse(&format!(r#"{{"ledger_entries":null,{FIELDS}}}"#)).unwrap();
+        assert!(parsed.request.ledger_entries.is_none());
+    }
+
+    #[test]
+    fn test_errors_are_reported_like_buffered_parsing() {
+        let body = format!(r#"{{"ledger_entries":{{"AAAA":"%%"}},{FIELDS}}}"#);
+        assert_eq!(
+            parse(&body).unwrap_err().to_string(),
+            "Invalid request: ledger_entries[\"AAAA\"]: invalid base64"
+        );
+        let err = parse(r#"{"envelope_xdr":"AAAA""#).unwrap_err();
+        assert!(matches!(err, ParseError::Syntax(_)), "{err}");
+        let err = parse(r#"{"ledger_entries":{"AAAA":5}}"#).unwrap_err();
+        assert!(matches!(err, ParseError::Invalid(_)), "{err}");
+    }
+
+    #[test]
+    fn test_oversized_body_is_rejected_while_reading() {
+        let body = format!(
+            r#"{{"ledger_entries":{{"AAAA":"{}"}},{FIELDS}}}"#,
+            "A".repeat(4096)
+        );
+        match parse_json(body.as_bytes(), 1024) {
+            Err(ParseError::Memory(MemoryError::RequestTooLarge { limit: 1024 })) => {}
+            other => panic!("expected RequestTooLarge, got {other:?}"),
+        }
+    }
+}
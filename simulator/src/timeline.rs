@@ -0,0 +1,272 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Ordered timeline of what a transaction did.
+//!
+//! Operation boundaries, contract frames entering and exiting, events and
+//! storage writes, interleaved in the order they happened and numbered by
+//! `seq`, for reasoning about the exact ordering of effects as event-driven
+//! indexers see it. Frames come from the `fn_call` and `fn_return`
+//! diagnostic events (see [`crate::calls`]).
+//!
+//! A Soroban transaction has a single `InvokeHostFunction` operation, and
+//! everything the host did falls inside it. The host buffers storage writes
+//! until the transaction commits, and individual storage calls are not
+//! observable outside it, so writes come last in their operation, as the
+//! ledger sees them, and only when the transaction succeeded.
+
+use crate::calls::{self, Boundary};
+use crate::storage_log::StorageAccess;
+use schemars::JsonSchema;
+use serde::Serialize;
+use soroban_env_host::events::HostEvent;
+use soroban_env_host::xdr::{Operation, OperationBody, ScAddress};
+
+/// One step of the timeline.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct TimelineEntry {
+    /// Position in the timeline, from 0.
+    pub seq: usize,
+    /// `operation_start`, `operation_end`, `frame_enter`, `frame_exit`,
+    /// `event` or `storage_write`.
+    pub kind: &'static str,
+    /// Index of the operation in the transaction.
+    pub operation: usize,
+    /// Contract frames open around the entry; a frame enters and exits at
+    /// its caller's depth.
+    pub depth: usize,
+    /// The frame's contract, or the contract that emitted the event
+    /// (`C...`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contract: Option<String>,
+    /// The frame's function.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub function: Option<String>,
+    /// Index in `diagnostic_events` of the event, or of the `fn_call` or
+    /// `fn_return` event of the frame.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_index: Option<usize>,
+    /// Base64 `LedgerKey` of the written entry.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// What the write did: `create`, `update` or `delete`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub change: Option<&'static str>,
+    /// The frame exited without returning.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub failed: bool,
+}
+
+/// The timeline of running `operations`, which emitted `events`. `writes`
+/// are the storage accesses of a transaction that succeeded; reads among
+/// them are skipped.
+pub fn timeline(
+    operations: &[Operation],
+    events: &[HostEvent],
+    writes: &[StorageAccess],
+) -> Vec<TimelineEntry> {
+    let soroban = operations
+        .iter()
+        .position(|op| matches!(op.body, OperationBody::InvokeHostFunction(_)));
+    let mut entries = Vec::new();
+    let mut push = |entry: TimelineEntry| {
+        entries.push(TimelineEntry {
+            seq: entries.len(),
+            ..entry
+        })
+    };
+    for operation in 0..operations.len() {
+        push(TimelineEntry {
+            kind: "operation_start",
+            operation,
+            ..Default::default()
+        });
+        if soroban == Some(operation) {
+            let mut frames: Vec<(String, String)> = Vec::new();
+            for (index, event) in events.iter().enumerate() {
+                match calls::boundary(event) {
+                    Some(Boundary::Call {
+                        contract, function, ..
+                    }) => {
+                        push(TimelineEntry {
+                            kind: "frame_enter",
+                            operation,
+                            depth: frames.len(),
+                            contract: Some(contract.clone()),
+                            function: Some(function.clone()),
+                            event_index: Some(index),
+                            ..Default::default()
+                        });
+                        frames.push((contract, function));
+                    }
+                    Some(Boundary::Return { function, .. }) => {
+                        // Frames above the returning one failed.
+                        while let Some((contract, open)) = frames.pop() {
+                            let returned = open == function;
+                            push(TimelineEntry {
+                                kind: "frame_exit",
+                                operation,
+                                depth: frames.len(),
+                                contract: Some(contract),
+                                function: Some(open),
+                                event_index: returned.then_some(index),
+                                failed: !returned,
+                                ..Default::default()
+                            });
+                            if returned {
+                                break;
+                            }
+                        }
+                    }
+                    None => push(TimelineEntry {
+                        kind: "event",
+                        operation,
+                        depth: frames.len(),
+                        contract: event
+                            .event
+                            .contract_id
+                            .clone()
+                            .map(|id| ScAddress::Contract(id).to_string()),
+                        event_index: Some(index),
+                        ..Default::default()
+                    }),
+                }
+            }
+            while let Some((contract, function)) = frames.pop() {
+                push(TimelineEntry {
+                    kind: "frame_exit",
+                    operation,
+                    depth: frames.len(),
+                    contract: Some(contract),
+                    function: Some(function),
+                    failed: true,
+                    ..Default::default()
+                });
+            }
+            for write in writes.iter().filter(|access| access.operation != "read") {
+                push(TimelineEntry {
+                    kind: "storage_write",
+                    operation,
+                    key: Some(write.key.clone()),
+                    change: Some(write.operation),
+                    ..Default::default()
+                });
+            }
+        }
+        push(TimelineEntry {
+            kind: "operation_end",
+            operation,
+            ..Default::default()
+        });
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::xdr::{
+        ContractEvent, ContractEventBody, ContractEventType, ContractEventV0, ContractId,
+        ExtensionPoint, Hash, HostFunction, InvokeHostFunctionOp, ScBytes, ScVal,
+    };
+
+    fn event(type_: ContractEventType, topics: Vec<ScVal>) -> HostEvent {
+        HostEvent {
+            event: ContractEvent {
+                ext: ExtensionPoint::V0,
+                contract_id: (type_ == ContractEventType::Contract)
+                    .then_some(ContractId(Hash([2; 32]))),
+                type_,
+                body: ContractEventBody::V0(ContractEventV0 {
+                    topics: topics.try_into().unwrap(),
+                    data: ScVal::Void,
+                }),
+            },
+            failed_call: false,
+        }
+    }
+
+    fn symbol(s: &str) -> ScVal {
+        ScVal::Symbol(s.try_into().unwrap())
+    }
+
+    fn fn_call(id: u8, function: &str) -> HostEvent {
+        let id = ScVal::Bytes(ScBytes(vec![id; 32].try_into().unwrap()));
+        event(
+            ContractEventType::Diagnostic,
+            vec![symbol("fn_call"), id, symbol(function)],
+        )
+    }
+
+    fn fn_return(function: &str) -> HostEvent {
+        event(
+            ContractEventType::Diagnostic,
+            vec![symbol("fn_return"), symbol(function)],
+        )
+    }
+
+    #[test]
+    fn test_timeline_interleaves_frames_events_and_writes() {
+        let operation = Operation {
+            source_account: None,
+            body: OperationBody::InvokeHostFunction(InvokeHostFunctionOp {
+                host_function: HostFunction::UploadContractWasm(Default::default()),
+                auth: Default::default(),
+            }),
+        };
+        let events = [
+            fn_call(1, "swap"),
+            fn_call(2, "transfer"),
+            event(ContractEventType::Contract, vec![symbol("transfer")]),
+            fn_call(3, "hook"),
+            fn_return("transfer"),
+            fn_return("swap"),
+        ];
+        let write = StorageAccess {
+            key: "AAAABg==".to_string(),
+            entry_type: "ContractData".to_string(),
+            access: "read_write",
+            operation: "update",
+            origin: "snapshot".to_string(),
+            entry_bytes: 10,
+            live_until_before: None,
+            live_until_after: None,
+        };
+        let read = StorageAccess {
+            operation: "read",
+            ..write.clone()
+        };
+
+        let entries = timeline(&[operation], &events, &[read, write]);
+        let kinds = entries
+            .iter()
+            .map(|e| (e.kind, e.depth, e.function.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                ("operation_start", 0, None),
+                ("frame_enter", 0, Some("swap")),
+                ("frame_enter", 1, Some("transfer")),
+                ("event", 2, None),
+                ("frame_enter", 2, Some("hook")),
+                ("frame_exit", 2, Some("hook")),
+                ("frame_exit", 1, Some("transfer")),
+                ("frame_exit", 0, Some("swap")),
+                ("storage_write", 0, None),
+                ("operation_end", 0, None),
+            ]
+        );
+        assert!(entries.iter().enumerate().all(|(i, e)| e.seq == i));
+        assert_eq!(
+            entries[3].contract,
+            Some(ScAddress::Contract(ContractId(Hash([2; 32]))).to_string())
+        );
+        assert_eq!(entries[3].event_index, Some(2));
+        assert!(entries[5].failed);
+        assert_eq!(entries[5].event_index, None);
+        assert!(!entries[6].failed);
+        assert_eq!(entries[6].event_index, Some(4));
+        assert_eq!(entries[8].change, Some("update"));
+    }
+}
@@ -0,0 +1,210 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Ceilings on the events and logs a response returns.
+//!
+//! A pathological transaction can emit enough events, or fail with a value
+//! large enough, to make a response hundreds of megabytes. [`truncate`] keeps
+//! the events and log lines, in order, up to the count and size ceilings of
+//! [`MemoryLimits`], drops the rest, and says so in the response's
+//! `truncated`. Which items are kept depends only on the response, so the same
+//! request is always cut at the same place.
+//!
+//! An event's size is that of its rendered contract, topics and data. Events
+//! are counted in `diagnostic_events`, which lists every event; the other
+//! event lists keep the events it kept.
+
+use crate::memory::MemoryLimits;
+use crate::types::{DiagnosticEvent, SimulationResponse};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt::{self, Write as _};
+use std::sync::Arc;
+
+/// What a response left out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct Truncation {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub events: Option<Kept>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logs: Option<Kept>,
+}
+
+/// How many items of a list a response kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct Kept {
+    pub kept: usize,
+    pub total: usize,
+}
+
+/// Cuts the events and logs of `response` to `limits`, returning what was
+/// cut, if anything.
+pub fn truncate(response: &mut SimulationResponse, limits: &MemoryLimits) -> Option<Truncation> {
+    let events = truncate_events(response, limits);
+    let logs = kept(
+        response.logs.iter().map(String::len),
+        limits.max_logs,
+        limits.max_log_bytes,
+    )
+    .inspect(|kept| response.logs.truncate(kept.kept));
+    (events.is_some() || logs.is_some()).then_some(Truncation { events, logs })
+}
+
+fn truncate_events(response: &mut SimulationResponse, limits: &MemoryLimits) -> Option<Kept> {
+    let kept = kept(
+        response.diagnostic_events.iter().map(|event| size(event)),
+        limits.max_events,
+        limits.max_event_bytes,
+    )?;
+    // `events` renders the same events, one for one, unless they could not
+    // be retrieved.
+    if response.events.len() == kept.total {
+        response.events.truncate(kept.kept);
+    }
+    response.diagnostic_events.truncate(kept.kept);
+    let retained = response
+        .diagnostic_events
+        .iter()
+        .map(Arc::as_ptr)
+        .collect::<HashSet<_>>();
+    let retain = |events: &mut Vec<Arc<DiagnosticEvent>>| {
+        events.retain(|event| retained.contains(&Arc::as_ptr(event)));
+    };
+    retain(&mut response.contract_events);
+    retain(&mut response.system_events);
+    response
+        .categorized_events
+        .retain(|categorized| retained.contains(&Arc::as_ptr(&categorized.event)));
+    Some(kept)
+}
+
+/// How many of the items, of `sizes` bytes, fit in `max_count` items and
+/// `max_bytes`; `None` when all of them do.
+fn kept(
+    sizes: impl ExactSizeIterator<Item = usize>,
+    max_count: usize,
+    max_bytes: usize,
+) -> Option<Kept> {
+    let total = sizes.len();
+    let mut bytes = 0usize;
+    let fitting = sizes
+        .take(max_count)
+        .take_while(|size| {
+            bytes = bytes.saturating_add(*size);
+            bytes <= max_bytes
+        })
+        .count();
+    (fitting < total).then_some(Kept {
+        kept: fitting,
+        total,
+    })
+}
+
+/// The length of `event`'s rendered contract, topics and data, counted
+/// without rendering them into a string.
+fn size(event: &DiagnosticEvent) -> usize {
+    let mut len = Len(0);
+    if let Some(id) = event.contract_id() {
+        let _ = write!(len, "{id:?}");
+    }
+    for topic in event.topics() {
+        let _ = write!(len, "{topic:?}");
+    }
+    let _ = write!(len, "{:?}", event.data());
+    len.0
+}
+
+struct Len(usize);
+
+impl fmt::Write for Len {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.len();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation;
+    use crate::types::{CategorizedEvent, EventLine};
+    use soroban_env_host::events::HostEvent;
+    use soroban_env_host::xdr::{
+        ContractEvent, ContractEventBody, ContractEventType, ContractEventV0, ExtensionPoint,
+        ScString, ScSymbol, ScVal,
+    };
+
+    fn host_event(data: &str, type_: ContractEventType) -> HostEvent {
+        HostEvent {
+            event: ContractEvent {
+                ext: ExtensionPoint::V0,
+                contract_id: None,
+                type_,
+                body: ContractEventBody::V0(ContractEventV0 {
+                    topics: vec![ScVal::Symbol(ScSymbol("t".try_into().unwrap()))]
+                        .try_into()
+                        .unwrap(),
+                    data: ScVal::String(ScString(data.try_into().unwrap())),
+                }),
+            },
+            failed_call: false,
+        }
+    }
+
+    #[test]
+    fn test_truncates_every_event_list_at_the_same_event() {
+        let host_events: Arc<[HostEvent]> = (0..6)
+            .map(|i| {
+                let type_ = if i % 2 == 0 {
+                    ContractEventType::Contract
+                } else {
+                    ContractEventType::Diagnostic
+                };
+                host_event(&"x".repeat(10 * i), type_)
+            })
+            .collect();
+        let events = (0..6)
+            .map(|i| Arc::new(DiagnosticEvent::new(Arc::clone(&host_events), i)))
+            .collect::<Vec<_>>();
+        let mut response = simulation::error_response(String::new());
+        response.events = events.iter().cloned().map(EventLine::Event).collect();
+        response.contract_events = events.iter().step_by(2).cloned().collect();
+        response.categorized_events = events
+            .iter()
+            .map(|event| CategorizedEvent {
+                category: "core",
+                event: Arc::clone(event),
+            })
+            .collect();
+        response.diagnostic_events = events;
+        response.logs = vec!["a".repeat(10), "b".repeat(10), "c".repeat(10)];
+
+        let limits = MemoryLimits {
+            max_events: 5,
+            max_event_bytes: 1_000,
+            max_logs: 10,
+            max_log_bytes: 25,
+            ..MemoryLimits::default()
+        };
+        let truncation = truncate(&mut response, &limits).unwrap();
+        assert_eq!(truncation.events, Some(Kept { kept: 5, total: 6 }));
+        assert_eq!(truncation.logs, Some(Kept { kept: 2, total: 3 }));
+        assert_eq!(response.events.len(), 5);
+        assert_eq!(response.categorized_events.len(), 5);
+        // Contract events 0, 2 and 4 are among the five kept.
+        assert_eq!(response.contract_events.len(), 3);
+
+        // Each event is 10 bytes longer than the one before.
+        let limits = MemoryLimits {
+            max_event_bytes: size(&response.diagnostic_events[0])
+                + size(&response.diagnostic_events[1]),
+            ..limits
+        };
+        let truncation = truncate(&mut response, &limits).unwrap();
+        assert_eq!(truncation.events, Some(Kept { kept: 2, total: 5 }));
+        assert_eq!(truncation.logs, None);
+        assert_eq!(response.contract_events.len(), 1);
+        assert_eq!(truncate(&mut response, &limits), None);
+    }
+}
@@ -0,0 +1,292 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Trustline and Stellar Asset Contract (SAC) diagnostics.
+//!
+//! A SAC moving a classic asset reads the trustlines of the accounts
+//! involved and fails with a generic contract error when one is missing or
+//! not authorized. [`trustlines`] reports the flags of every trustline the
+//! simulation read, so such a failure can be named precisely, and
+//! [`sac_admin`] reports who administers an invoked SAC.
+
+use crate::simulation;
+use schemars::JsonSchema;
+use serde::Serialize;
+use soroban_env_host::storage::SnapshotSource;
+use soroban_env_host::xdr::{
+    ContractDataDurability, ContractExecutable, HostFunction, InvokeContractArgs, LedgerEntryData,
+    LedgerKey, LedgerKeyContractData, LedgerKeyTrustLine, OperationBody, ScAddress, ScVal, ScVec,
+    TransactionEnvelope, TrustLineAsset, TrustLineFlags,
+};
+use std::collections::HashSet;
+use std::rc::Rc;
+
+/// Flags of a trustline read during the simulation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct TrustlineState {
+    /// Account holding the trustline.
+    pub account: String,
+    /// `CODE:ISSUER`, or `pool:<id>` for pool shares.
+    pub asset: String,
+    pub exists: bool,
+    pub authorized: bool,
+    pub authorized_to_maintain_liabilities: bool,
+    pub clawback_enabled: bool,
+    /// Why the SAC would reject a balance change on this trustline.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub problem: Option<String>,
+}
+
+/// Returns the state of every trustline among `keys`, once each, in order.
+///
+/// `function` is the invoked SAC function: `clawback` needs the trustline's
+/// clawback flag, every other balance change needs it fully authorized.
+pub fn trustlines(
+    keys: &[LedgerKey],
+    state: &impl SnapshotSource,
+    function: Option<&str>,
+) -> Vec<TrustlineState> {
+    let mut seen = HashSet::new();
+    keys.iter()
+        .filter_map(|key| match key {
+            LedgerKey::Trustline(trustline) if seen.insert(trustline) => Some(trustline),
+            _ => None,
+        })
+        .map(|key| trustline_state(key, state, function))
+        .collect()
+}
+
+fn trustline_state(
+    key: &LedgerKeyTrustLine,
+    state: &impl SnapshotSource,
+    function: Option<&str>,
+) -> TrustlineState {
+    let account = key.account_id.to_string();
+    let asset = asset_name(&key.asset);
+    let flags = match state.get(&Rc::new(LedgerKey::Trustline(key.clone()))) {
+        Ok(Some((entry, _))) => match &entry.data {
+            LedgerEntryData::Trustline(trustline) => Some(trustline.flags),
+            _ => None,
+        },
+        _ => None,
+    };
+    let has = |flag: TrustLineFlags| flags.is_some_and(|f| f & flag as u32 != 0);
+    let authorized = has(TrustLineFlags::AuthorizedFlag);
+    let authorized_to_maintain_liabilities =
+        has(TrustLineFlags::AuthorizedToMaintainLiabilitiesFlag);
+    let clawback_enabled = has(TrustLineFlags::TrustlineClawbackEnabledFlag);
+
+    let problem = if flags.is_none() {
+        Some(format!("{account} has no trustline for {asset}"))
+    } else if function == Some("clawback") {
+        (!clawback_enabled)
+            .then(|| format!("clawback is not enabled on {account}'s trustline for {asset}"))
+    } else if authorized {
+        None
+    } else if authorized_to_maintain_liabilities {
+        Some(format!(
+            "{account}'s trustline for {asset} is only authorized to maintain liabilities"
+        ))
+    } else {
+        Some(format!(
+            "{account}'s trustline for {asset} is not authorized"
+        ))
+    };
+
+    TrustlineState {
+        account,
+        asset,
+        exists: flags.is_some(),
+        authorized,
+        authorized_to_maintain_liabilities,
+        clawback_enabled,
+        problem,
+    }
+}
+
+fn asset_name(asset: &TrustLineAsset) -> String {
+    match asset {
+        TrustLineAsset::Native => "native".to_string(),
+        TrustLineAsset::CreditAlphanum4(a) => format!("{}:{}", a.asset_code, a.issuer),
+        TrustLineAsset::CreditAlphanum12(a) => format!("{}:{}", a.asset_code, a.issuer),
+        TrustLineAsset::PoolShare(id) => format!("pool:{}", crate::network::hex(&id.0 .0)),
+    }
+}
+
+/// Returns the contract call made by the envelope's `InvokeHostFunction`
+/// operation, if it calls a contract.
+pub fn invoked_contract(envelope: &TransactionEnvelope) -> Option<&InvokeContractArgs> {
+    simulation::envelope_operations(envelope)
+        .iter()
+        .find_map(|op| match &op.body {
+            OperationBody::InvokeHostFunction(invoke) => match &invoke.host_function {
+                HostFunction::InvokeContract(args) => Some(args),
+                _ => None,
+            },
+            _ => None,
+        })
+}
+
+/// Returns the admin of `contract` if it is a Stellar Asset Contract.
+pub fn sac_admin(contract: &ScAddress, state: &impl SnapshotSource) -> Option<String> {
+    let key = Rc::new(LedgerKey::ContractData(LedgerKeyContractData {
+        contract: contract.clone(),
+        key: ScVal::LedgerKeyContractInstance,
+        durability: ContractDataDurability::Persistent,
+    }));
+    let Ok(Some((entry, _))) = state.get(&key) else {
+        return None;
+    };
+    let LedgerEntryData::ContractData(data) = &entry.data else {
+        return None;
+    };
+    let ScVal::ContractInstance(instance) = &data.val else {
+        return None;
+    };
+    if instance.executable != ContractExecutable::StellarAsset {
+        return None;
+    }
+    let admin_key = ScVal::Vec(Some(ScVec(
+        vec![ScVal::Symbol("Admin".try_into().ok()?)]
+            .try_into()
+            .ok()?,
+    )));
+    instance
+        .storage
+        .as_ref()?
+        .iter()
+        .find(|entry| entry.key == admin_key)
+        .and_then(|entry| match &entry.val {
+            ScVal::Address(admin) => Some(admin.to_string()),
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::LedgerSnapshot;
+    use crate::source::{LayeredSource, SnapshotLedgerSource};
+    use soroban_env_host::xdr::{
+        AccountId, AlphaNum4, AssetCode4, ContractDataEntry, ContractId, ExtensionPoint, Hash,
+        LedgerEntry, LedgerEntryExt, PublicKey, ScContractInstance, ScMap, ScMapEntry,
+        TrustLineEntry, TrustLineEntryExt, Uint256,
+    };
+
+    fn account(seed: u8) -> AccountId {
+        AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([seed; 32])))
+    }
+
+    fn usdc_key(holder: u8) -> LedgerKeyTrustLine {
+        LedgerKeyTrustLine {
+            account_id: account(holder),
+            asset: TrustLineAsset::CreditAlphanum4(AlphaNum4 {
+                asset_code: AssetCode4(*b"USDC"),
+                issuer: account(9),
+            }),
+        }
+    }
+
+    fn insert_trustline(snapshot: &mut LedgerSnapshot, holder: u8, flags: u32) {
+        let key = usdc_key(holder);
+        let entry = LedgerEntry {
+            last_modified_ledger_seq: 1,
+            data: LedgerEntryData::Trustline(TrustLineEntry {
+                account_id: key.account_id.clone(),
+                asset: key.asset.clone(),
+                balance: 100,
+                limit: i64::MAX,
+                flags,
+                ext: TrustLineEntryExt::V0,
+            }),
+            ext: LedgerEntryExt::V0,
+        };
+        snapshot
+            .insert_entry(&LedgerKey::Trustline(key), entry)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_trustline_problems() {
+        let mut snapshot = LedgerSnapshot::new();
+        insert_trustline(&mut snapshot, 1, TrustLineFlags::AuthorizedFlag as u32);
+        insert_trustline(
+            &mut snapshot,
+            2,
+            TrustLineFlags::AuthorizedToMaintainLiabilitiesFlag as u32,
+        );
+        let state = LayeredSource::new(SnapshotLedgerSource::new(snapshot), None, 1);
+        let keys: Vec<LedgerKey> = [1, 2, 3, 1]
+            .into_iter()
+            .map(|holder| LedgerKey::Trustline(usdc_key(holder)))
+            .collect();
+
+        let states = trustlines(&keys, &state, Some("transfer"));
+        assert_eq!(states.len(), 3);
+        assert!(states[0].authorized && states[0].problem.is_none());
+        assert!(states[0].asset.starts_with("USDC:G"));
+        assert!(states[1]
+            .problem
+            .as_ref()
+            .unwrap()
+            .ends_with("only authorized to maintain liabilities"));
+        assert!(!states[2].exists);
+        assert!(states[2]
+            .problem
+            .as_ref()
+            .unwrap()
+            .contains("has no trustline"));
+
+        let states = trustlines(&keys[..1], &state, Some("clawback"));
+        assert!(states[0]
+            .problem
+            .as_ref()
+            .unwrap()
+            .starts_with("clawback is not enabled"));
+    }
+
+    #[test]
+    fn test_sac_admin_read_from_instance_storage() {
+        let contract = ScAddress::Contract(ContractId(Hash([5; 32])));
+        let admin = ScAddress::Account(account(9));
+        let admin_key = ScVal::Vec(Some(ScVec(
+            vec![ScVal::Symbol("Admin".try_into().unwrap())]
+                .try_into()
+                .unwrap(),
+        )));
+        let instance = ScVal::ContractInstance(ScContractInstance {
+            executable: ContractExecutable::StellarAsset,
+            storage: Some(ScMap(
+                vec![ScMapEntry {
+                    key: admin_key,
+                    val: ScVal::Address(admin.clone()),
+                }]
+                .try_into()
+                .unwrap(),
+            )),
+        });
+        let key = LedgerKey::ContractData(LedgerKeyContractData {
+            contract: contract.clone(),
+            key: ScVal::LedgerKeyContractInstance,
+            durability: ContractDataDurability::Persistent,
+        });
+        let entry = LedgerEntry {
+            last_modified_ledger_seq: 1,
+            data: LedgerEntryData::ContractData(ContractDataEntry {
+                ext: ExtensionPoint::V0,
+                contract: contract.clone(),
+                key: ScVal::LedgerKeyContractInstance,
+                durability: ContractDataDurability::Persistent,
+                val: instance,
+            }),
+            ext: LedgerEntryExt::V0,
+        };
+        let mut snapshot = LedgerSnapshot::new();
+        snapshot.insert_entry(&key, entry).unwrap();
+        let state = LayeredSource::new(SnapshotLedgerSource::new(snapshot), None, 1);
+
+        assert_eq!(sac_admin(&contract, &state), Some(admin.to_string()));
+        let other = ScAddress::Contract(ContractId(Hash([6; 32])));
+        assert_eq!(sac_admin(&other, &state), None);
+    }
+}
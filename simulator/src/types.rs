@@ -3,13 +3,40 @@
 
 #![allow(dead_code)]
 
+use crate::assemble::Assembled;
+use crate::attribution::CostAttribution;
+use crate::auth_lint::AuthWarning;
+use crate::authorization::{AuthPayload, ConsumedNonce, SourceAccount};
+use crate::balances::{BalanceChange, TokenEvent};
+use crate::calls::{Call, Reentrancy};
+use crate::custom_account::CustomAccountAuth;
+use crate::diff::ResponseDiff;
+use crate::fee_source::FeeSource;
 use crate::gas_optimizer::OptimizationReport;
+use crate::intents::Intent;
+use crate::limits::LimitCheck;
+use crate::multisig::SignatureCheck;
+use crate::rent::RentBreakdown;
+use crate::resources::Resources;
+use crate::solver::{Candidate, CandidateOutput, SolverReport};
+use crate::source::cache::CacheStats;
 use crate::stack_trace::WasmStackTrace;
-use serde::{ Deserialize, Serialize };
-use std::collections::HashMap;
+use crate::storage_log::StorageAccess;
+use crate::timeline::TimelineEntry;
+use crate::truncation::Truncation;
+use crate::trustline::TrustlineState;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use soroban_env_host::events::HostEvent;
+use soroban_env_host::xdr::{ContractEventBody, ContractEventType, ContractId, ScVal};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct SimulationRequest {
+    /// Base64 `TransactionEnvelope` to simulate. Leave empty when `invoke`
+    /// describes the call instead.
+    #[serde(default)]
     pub envelope_xdr: String,
     pub result_meta_xdr: String,
     pub ledger_entries: Option<HashMap<String, String>>,
@@ -25,9 +52,270 @@ pub struct SimulationRequest {
     pub mock_gas_price: Option<u64>,
     #[serde(default)]
     pub restore_preamble: Option<serde_json::Value>,
+    #[serde(default)]
+    pub resource_calibration: Option<ResourceCalibration>,
+    /// soroban-rpc endpoint to fetch entries missing from `ledger_entries`.
+    /// Ignored in server mode, which uses its own `--rpc-url`.
+    #[serde(default)]
+    pub rpc_url: Option<String>,
+    /// Horizon endpoint to fill in accounts and trustlines that `rpc_url`
+    /// does not serve. Ignored in server mode, which uses `--horizon-url`.
+    #[serde(default)]
+    pub horizon_url: Option<String>,
+    /// Network preset (`pubnet`, `testnet`, `futurenet`) supplying the
+    /// passphrase, default RPC endpoint and base fee. Defaults to pubnet.
+    #[serde(default)]
+    pub network: Option<String>,
+    /// Overrides the network preset's passphrase for every hash the host
+    /// computes (auth payloads, contract IDs, transaction hash).
+    #[serde(default)]
+    pub network_passphrase: Option<String>,
+    /// Signed base64 `SorobanAuthorizationEntry` values to enforce, replacing
+    /// those on the envelope's operation. With no entries from either place,
+    /// authorization is recorded rather than enforced.
+    #[serde(default)]
+    pub auth_entries: Option<Vec<String>>,
+    /// Server mode only: duplicate requests with the same key replay the
+    /// first response. The `Idempotency-Key` header is equivalent.
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+    /// Builder mode: invoke a contract by ID instead of supplying
+    /// `envelope_xdr`. The contract's instance and code are resolved from
+    /// the configured ledger state.
+    #[serde(default)]
+    pub invoke: Option<InvokeSpec>,
+    /// Intent mode: a transfer or swap to build the transaction for, in
+    /// place of `envelope_xdr` or `invoke`. Implies `assemble`.
+    #[serde(default)]
+    pub intent: Option<Intent>,
+    /// Solver mode: other transactions meant to achieve what this one does,
+    /// to simulate at the same ledger and rank in `solver`. Implies
+    /// `assemble`.
+    #[serde(default)]
+    pub candidates: Vec<Candidate>,
+    /// Rank `candidates` first by how much of an asset an address receives.
+    #[serde(default)]
+    pub candidate_output: Option<CandidateOutput>,
+    /// Ledger and cost settings to simulate under instead of the network
+    /// preset's.
+    #[serde(default)]
+    pub protocol: Option<ProtocolConfig>,
+    /// Regression mode: simulate the request again under these settings and
+    /// report how the second run differs in `protocol_comparison`.
+    #[serde(default)]
+    pub compare_protocol: Option<ProtocolConfig>,
+    /// Stepping mode: run in slices of this many CPU instructions,
+    /// reporting where execution stood after each in `checkpoints`.
+    #[serde(default)]
+    pub step_instructions: Option<u64>,
+    /// Stop once this many CPU instructions have run, failing with a
+    /// budget error and a checkpoint of where execution stood.
+    #[serde(default)]
+    pub stop_at_instructions: Option<u64>,
+    /// Share of each network limit, in percent, from which `limit_checks`
+    /// warns. Defaults to 90.
+    #[serde(default)]
+    pub soft_limit_percent: Option<u64>,
+    /// Break the budget down by call in `call_tree`, at the price of
+    /// rerunning the transaction a few times per call.
+    #[serde(default)]
+    pub call_costs: bool,
+    /// SEP-40 price oracles to replace with fixed prices.
+    #[serde(default)]
+    pub mock_oracles: Vec<MockOracle>,
+    /// Sequence of the simulated ledger. Defaults to the remote state
+    /// source's latest ledger, or else the newest entry's
+    /// `last_modified_ledger_seq`.
+    #[serde(default)]
+    pub ledger_sequence: Option<u32>,
+    /// Close time of the simulated ledger, in Unix seconds. Defaults to now.
+    #[serde(default)]
+    pub ledger_close_time: Option<u64>,
+    /// Return the envelope with the simulated resources, authorization
+    /// entries and fee applied, ready to sign.
+    #[serde(default)]
+    pub assemble: bool,
+    /// Margins to add to the assembled resources and fee.
+    #[serde(default)]
+    pub resource_padding: ResourcePadding,
+    /// Failures to inject, for testing clients against each failure mode.
+    #[serde(default)]
+    pub fault_injection: FaultInjection,
+    /// Response sections to fill in, for callers that only read a few;
+    /// every section when absent. `status`, `error`, the result and the
+    /// ledger simulated at are always returned.
+    #[serde(default)]
+    pub include: Option<Vec<ResponseSection>>,
+    /// Fill in `read_entries` even when the transaction succeeds. Set by the
+    /// CLI, not part of the request format.
+    #[serde(skip)]
+    pub record_reads: bool,
+}
+
+/// A contract call to simulate without a prepared envelope.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct InvokeSpec {
+    /// Contract to call (`C...`).
+    pub contract_id: String,
+    /// Name of the function to call.
+    pub function: String,
+    /// Base64 XDR `ScVal` arguments, in order.
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Transaction source account (`G...` or `M...`). Defaults to the
+    /// all-zero account.
+    #[serde(default)]
+    pub source_account: Option<String>,
+}
+
+/// A SEP-40 price oracle answering from fixed prices.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct MockOracle {
+    /// Oracle contract to replace (`C...`).
+    pub contract_id: String,
+    /// Price of each asset, as a decimal string such as `"0.12"`. Assets
+    /// are contract addresses (`C...`) for Stellar assets, and symbols such
+    /// as `BTC` for others.
+    pub prices: BTreeMap<String, String>,
+    /// Unix time of the prices. Defaults to the simulated ledger's close
+    /// time.
+    #[serde(default)]
+    pub timestamp: Option<u64>,
+    /// Decimals prices are scaled by. Defaults to 14.
+    #[serde(default)]
+    pub decimals: Option<u32>,
+    /// Seconds between price records. Defaults to 300.
+    #[serde(default)]
+    pub resolution: Option<u32>,
+    /// Asset prices are quoted in. Defaults to `USD`.
+    #[serde(default)]
+    pub base: Option<String>,
+}
+
+/// Margins added to what a simulation measured when assembling, for state
+/// that changes before the transaction is applied. A percentage and an
+/// absolute margin of the same resource add up.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+pub struct ResourcePadding {
+    #[serde(default)]
+    pub instructions_pct: Option<u32>,
+    #[serde(default)]
+    pub instructions: Option<u32>,
+    /// Disk read bytes.
+    #[serde(default)]
+    pub read_bytes_pct: Option<u32>,
+    #[serde(default)]
+    pub read_bytes: Option<u32>,
+    #[serde(default)]
+    pub write_bytes_pct: Option<u32>,
+    #[serde(default)]
+    pub write_bytes: Option<u32>,
+    /// Resource fee, on top of the fee of the padded resources.
+    #[serde(default)]
+    pub fee_pct: Option<u32>,
+    /// Resource fee, in stroops.
+    #[serde(default)]
+    pub fee: Option<u32>,
+}
+
+impl ResourcePadding {
+    /// `value` with `pct` percent, rounded up, and `absolute` added.
+    pub fn pad(value: u64, pct: Option<u32>, absolute: Option<u32>) -> u64 {
+        let margin = pct.map_or(0, |pct| value.saturating_mul(u64::from(pct)).div_ceil(100));
+        value
+            .saturating_add(margin)
+            .saturating_add(absolute.map_or(0, u64::from))
+    }
+}
+
+/// A group of response fields that `include` selects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseSection {
+    /// `events` and the typed event lists.
+    Events,
+    /// `logs` and `stack_trace`.
+    Logs,
+    /// `budget_usage`, `optimization_report`, `cost_attribution` and
+    /// `flamegraph`.
+    Budget,
+    /// `resources` and `limit_checks`.
+    Footprint,
+    /// `required_fee`, `fee_source` and `rent`.
+    Fees,
+    /// `auth_payloads`, `consumed_nonces`, `signature_checks`,
+    /// `auth_warnings` and `custom_accounts`.
+    Auth,
+    /// `call_tree`, `pretty_invocations` and `reentrancy`.
+    Calls,
+    /// `result_meta_xdr` and `storage_accesses`.
+    State,
+    /// `balance_changes`, `token_events` and `trustlines`.
+    Balances,
+    /// `timeline`.
+    Timeline,
+}
+
+/// Failures to inject into a simulation. See the `fault` module.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+pub struct FaultInjection {
+    /// Ledger keys (base64 XDR) to answer as missing from the state.
+    #[serde(default)]
+    pub drop_entries: Vec<String>,
+    /// CPU instructions the transaction's budget allows.
+    #[serde(default)]
+    pub cpu_limit: Option<u64>,
+    /// Memory bytes the transaction's budget allows.
+    #[serde(default)]
+    pub memory_limit: Option<u64>,
+    /// Contract functions to fail with a contract error instead of running.
+    #[serde(default)]
+    pub contract_errors: Vec<ContractFault>,
+}
+
+/// A contract function that fails with `Error(Contract, #code)`.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct ContractFault {
+    /// Contract whose function fails (`C...`).
+    pub contract_id: String,
+    pub function: String,
+    /// Contract error code, as numbered in the contract's error enum.
+    pub code: u32,
+}
+
+/// Protocol-dependent settings of a simulation. Unset fields keep the
+/// network preset's values.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+pub struct ProtocolConfig {
+    /// Ledger protocol version.
+    #[serde(default)]
+    pub protocol_version: Option<u32>,
+    /// Base reserve, in stroops.
+    #[serde(default)]
+    pub base_reserve: Option<u32>,
+    #[serde(default)]
+    pub min_temp_entry_ttl: Option<u32>,
+    #[serde(default)]
+    pub min_persistent_entry_ttl: Option<u32>,
+    #[serde(default)]
+    pub max_entry_ttl: Option<u32>,
+    /// Base64 `ContractCostParams` for CPU instructions, as in the network's
+    /// `CONFIG_SETTING_CONTRACT_COST_PARAMS_CPU_INSTRUCTIONS`. Must be given
+    /// together with `memory_cost_params`.
+    #[serde(default)]
+    pub cpu_cost_params: Option<String>,
+    /// Base64 `ContractCostParams` for memory bytes.
+    #[serde(default)]
+    pub memory_cost_params: Option<String>,
+    /// Replaces `mock_base_fee`.
+    #[serde(default)]
+    pub base_fee: Option<u32>,
+    /// Replaces `mock_gas_price`.
+    #[serde(default)]
+    pub gas_price: Option<u64>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
 pub struct ResourceCalibration {
     pub sha256_fixed: u64,
     pub sha256_per_byte: u64,
@@ -38,42 +326,371 @@ pub struct ResourceCalibration {
 
 use crate::source_mapper::SourceLocation;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct SimulationResponse {
     pub status: String,
     pub error: Option<String>,
-    pub events: Vec<String>,
-    pub diagnostic_events: Vec<DiagnosticEvent>,
+    /// Every event rendered with `Debug`, for older clients; the typed
+    /// lists below are easier to consume.
+    pub events: Vec<EventLine>,
+    /// Contract events of the calls that succeeded, the events the
+    /// transaction's `TransactionMeta` carries; none if it failed.
+    pub contract_events: Vec<Arc<DiagnosticEvent>>,
+    /// System events of the calls that succeeded; none if the transaction
+    /// failed.
+    pub system_events: Vec<Arc<DiagnosticEvent>>,
+    /// Every event the host emitted, contract and system events included,
+    /// like the meta's `diagnostic_events`. Shared with the lists above and
+    /// `categorized_events`, which lists the same events.
+    pub diagnostic_events: Vec<Arc<DiagnosticEvent>>,
     pub categorized_events: Vec<CategorizedEvent>,
     pub logs: Vec<String>,
     pub flamegraph: Option<String>,
+    /// Where the budget went per storage access, event and host cost type,
+    /// present with `profile`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_attribution: Option<CostAttribution>,
     pub optimization_report: Option<OptimizationReport>,
     pub budget_usage: Option<BudgetUsage>,
+    /// Wall-clock time spent in each phase of the simulation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timings: Option<Timings>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub source_location: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stack_trace: Option<WasmStackTrace>,
     pub wasm_offset: Option<u64>,
+    /// Hex transaction hash on the simulated network.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction_hash: Option<String>,
+    /// Entry cache statistics, present when a remote state source is used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_stats: Option<CacheStats>,
+    /// Authorizations that must be signed before the transaction is submitted.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub auth_payloads: Vec<AuthPayload>,
+    /// Nonces consumed by enforced authorization entries.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub consumed_nonces: Vec<ConsumedNonce>,
+    /// Classic signature threshold checks for a signed envelope.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub signature_checks: Vec<SignatureCheck>,
+    /// The (inner) transaction's source account.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_account: Option<SourceAccount>,
+    /// Trustlines read during the simulation.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub trustlines: Vec<TrustlineState>,
+    /// Footprint and byte resources to declare in `SorobanResources`,
+    /// present unless execution failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resources: Option<Resources>,
+    /// Network limits the transaction exceeds or comes close to.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub limit_checks: Vec<LimitCheck>,
+    /// Each `InvokeHostFunction` operation rendered as a call, e.g.
+    /// `CBXK…WXYZ::transfer(from=GABC…DEFG, amount=100_0000000)`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pretty_invocations: Vec<String>,
+    /// Token balance changes announced by the transaction's events.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub balance_changes: Vec<BalanceChange>,
+    /// Token events (`transfer`, `mint`, `burn`, `clawback`, `approve`) of
+    /// any contract, decoded, in emission order.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub token_events: Vec<TokenEvent>,
+    /// Contract calls the transaction made, nested by caller.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub call_tree: Vec<Call>,
+    /// Changes to an address's state that no authorization of that address
+    /// covers; a heuristic for auditors to triage.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub auth_warnings: Vec<AuthWarning>,
+    /// Enforced authorization entries of custom accounts (smart wallets),
+    /// and how their `__check_auth` went.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub custom_accounts: Vec<CustomAccountAuth>,
+    /// Calls into a contract already on their own call stack.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub reentrancy: Vec<Reentrancy>,
+    /// Rent paid for entries the transaction created, grew or extended.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rent: Option<RentBreakdown>,
+    /// Ledger entries the host accessed, in the order it first accessed them.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub storage_accesses: Vec<StorageAccess>,
+    /// Admin of the invoked Stellar Asset Contract.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sac_admin: Option<String>,
+    /// Ledger sequence the transaction was simulated at.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ledger_sequence: Option<u32>,
+    /// Close time of the ledger the transaction was simulated at, in Unix
+    /// seconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ledger_close_time: Option<u64>,
+    /// Base64 `TransactionResult` the transaction would produce.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result_xdr: Option<String>,
+    /// Base64 `TransactionMeta` (v4) of the execution, with its ledger
+    /// changes and events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result_meta_xdr: Option<String>,
+    /// Fee required by the mocked fee model, when `mock_base_fee`,
+    /// `mock_gas_price` or `network` enable it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required_fee: Option<u64>,
+    /// The account paying the fee, each portion of it, and whether its
+    /// balance covers it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee_source: Option<FeeSource>,
+    /// The request's envelope with the simulation applied, when the request
+    /// set `assemble` and the transaction succeeded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assembled: Option<Assembled>,
+    /// How many events and log lines the response left out, when there were
+    /// more than the configured ceilings allow.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub truncated: Option<Truncation>,
+    /// Entries the simulation read, base64 key to base64 entry, when the
+    /// transaction failed or the request set `record_reads`; what a failure
+    /// bundle needs to replay it (see [`crate::bundle`]).
+    #[serde(skip)]
+    pub read_entries: BTreeMap<String, String>,
+    /// How the run under `compare_protocol` differs from this one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol_comparison: Option<ProtocolComparison>,
+    /// The request's transaction and its `candidates`, ranked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub solver: Option<SolverReport>,
+    /// Where execution stood at each slice boundary of a stepped or
+    /// stopped simulation.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub checkpoints: Vec<Checkpoint>,
+    /// Operation boundaries, contract frames, events and storage writes in
+    /// the order they happened.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub timeline: Vec<TimelineEntry>,
+    /// Which simulator and host produced this response.
+    pub meta: ResponseMeta,
 }
 
-#[derive(Debug, Serialize)]
+/// Versions of the engine behind a response.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct ResponseMeta {
+    /// Version of erst-sim.
+    pub simulator_version: &'static str,
+    /// Version of the newest linked soroban-env-host.
+    pub host_version: &'static str,
+    /// Oldest ledger protocol the linked hosts run.
+    pub min_protocol: u32,
+    /// Newest ledger protocol the linked hosts run.
+    pub max_protocol: u32,
+    /// Git commit erst-sim was built from, when known at build time.
+    pub git_hash: Option<&'static str>,
+}
+
+/// Result of simulating a request under two protocol configurations.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ProtocolComparison {
+    /// Protocol version of this response's run.
+    pub baseline_protocol: u32,
+    /// Protocol version of the `compare_protocol` run.
+    pub candidate_protocol: u32,
+    /// Changes from the baseline to the candidate run; empty when the
+    /// protocol change does not affect the transaction.
+    pub differences: ResponseDiff,
+}
+
+/// An event the host emitted. Shares the host's recorded events rather than
+/// copying them: the contract, topics and data are rendered with `Debug`
+/// straight into the serializer, without a string per event.
+#[derive(Debug)]
 pub struct DiagnosticEvent {
-    pub event_type: String,
-    pub contract_id: Option<String>,
-    pub topics: Vec<String>,
-    pub data: String,
-    pub in_successful_contract_call: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    events: Arc<[HostEvent]>,
+    index: usize,
     pub wasm_instruction: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+impl DiagnosticEvent {
+    /// The event at `index` of `events`.
+    pub fn new(events: Arc<[HostEvent]>, index: usize) -> Self {
+        let wasm_instruction = crate::simulation::wasm_instruction(&events[index]);
+        Self {
+            events,
+            index,
+            wasm_instruction,
+        }
+    }
+
+    pub fn event(&self) -> &HostEvent {
+        &self.events[self.index]
+    }
+
+    pub fn event_type(&self) -> &'static str {
+        match self.event().event.type_ {
+            ContractEventType::Contract => "contract",
+            ContractEventType::System => "system",
+            ContractEventType::Diagnostic => "diagnostic",
+        }
+    }
+
+    pub fn contract_id(&self) -> Option<&ContractId> {
+        self.event().event.contract_id.as_ref()
+    }
+
+    pub fn topics(&self) -> &[ScVal] {
+        let ContractEventBody::V0(body) = &self.event().event.body;
+        &body.topics
+    }
+
+    pub fn data(&self) -> &ScVal {
+        let ContractEventBody::V0(body) = &self.event().event.body;
+        &body.data
+    }
+
+    /// `failed_call` means the call that emitted the event failed, so this
+    /// is its inverse.
+    pub fn in_successful_contract_call(&self) -> bool {
+        !self.event().failed_call
+    }
+}
+
+impl Serialize for DiagnosticEvent {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut event = serializer.serialize_struct("DiagnosticEvent", 6)?;
+        event.serialize_field("event_type", self.event_type())?;
+        event.serialize_field("contract_id", &self.contract_id().map(Debugged))?;
+        event.serialize_field("topics", &DebuggedSeq(self.topics()))?;
+        event.serialize_field("data", &Debugged(self.data()))?;
+        event.serialize_field(
+            "in_successful_contract_call",
+            &self.in_successful_contract_call(),
+        )?;
+        match &self.wasm_instruction {
+            Some(instruction) => event.serialize_field("wasm_instruction", instruction)?,
+            None => event.skip_field("wasm_instruction")?,
+        }
+        event.end()
+    }
+}
+
+impl JsonSchema for DiagnosticEvent {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "DiagnosticEvent".into()
+    }
+
+    fn json_schema(generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        DiagnosticEventSchema::json_schema(generator)
+    }
+}
+
+/// The serialized form of [`DiagnosticEvent`], for its schema.
+#[allow(unused)]
+#[derive(JsonSchema)]
+struct DiagnosticEventSchema {
+    event_type: String,
+    contract_id: Option<String>,
+    topics: Vec<String>,
+    data: String,
+    in_successful_contract_call: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    wasm_instruction: Option<String>,
+}
+
+/// Serializes a value as its `Debug` rendering.
+struct Debugged<'a, T>(&'a T);
+
+impl<T: std::fmt::Debug> Serialize for Debugged<'_, T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("{:?}", self.0))
+    }
+}
+
+/// Serializes values as a list of their `Debug` renderings.
+struct DebuggedSeq<'a, T>(&'a [T]);
+
+impl<T: std::fmt::Debug> Serialize for DebuggedSeq<'_, T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(Debugged))
+    }
+}
+
+/// An entry of [`SimulationResponse::events`]: an event, rendered with
+/// `Debug` as it is serialized, or a note in place of the events.
+#[derive(Debug, Clone)]
+pub enum EventLine {
+    Event(Arc<DiagnosticEvent>),
+    Note(&'static str),
+}
+
+impl std::fmt::Display for EventLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Event(event) => write!(f, "{:?}", event.event()),
+            Self::Note(note) => f.write_str(note),
+        }
+    }
+}
+
+impl Serialize for EventLine {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl JsonSchema for EventLine {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        String::schema_name()
+    }
+
+    fn json_schema(generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        String::json_schema(generator)
+    }
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct CategorizedEvent {
-    pub category: String,
-    pub event: DiagnosticEvent,
+    pub category: &'static str,
+    pub event: Arc<DiagnosticEvent>,
 }
 
-#[derive(Debug, Serialize)]
+/// A point a stepped simulation reached.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct Checkpoint {
+    /// CPU instructions executed.
+    pub instructions: u64,
+    pub memory_bytes: u64,
+    /// Contract calls in progress, outermost first, each like
+    /// `CBXK…WXYZ::swap`.
+    pub frames: Vec<String>,
+}
+
+/// Milliseconds a simulation spent in each phase. Unlike the metered
+/// `budget_usage`, these depend on the machine, its load and the state
+/// backend, not only on the contract.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, JsonSchema)]
+pub struct Timings {
+    /// Decoding the request and its envelope.
+    pub parse_ms: f64,
+    /// Loading ledger state: the request's entries, the base snapshot and
+    /// remote fetches made before execution.
+    pub snapshot_ms: f64,
+    /// Setting up the host and running the transaction, including entries
+    /// fetched on demand.
+    pub execution_ms: f64,
+    /// Building the response after execution: decoding events, diffing
+    /// state, fees, the timeline and the lints. Encoding the response is
+    /// not included; the server reports it in `Server-Timing`.
+    pub post_processing_ms: f64,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct BudgetUsage {
     pub cpu_instructions: u64,
     pub memory_bytes: u64,
@@ -1,27 +1,526 @@
 // Copyright 2025 Erst Users
 // SPDX-License-Identifier: Apache-2.0
 
-use wasmparser::{Operator, Parser, Payload};
+//! Contract WASM validation, run before the host sees the module.
+//!
+//! The host rejects a module it cannot instantiate with a bare
+//! `(WasmVm, InvalidInput)` and no location. This pass applies the same rules
+//! stellar-core's host applies on upload and instantiation, and reports every
+//! violation it finds with the byte offset it was found at:
+//! - only the post-MVP features the host enables (bulk memory, mutable
+//!   globals, sign extension); no floats, SIMD, reference types, multi-value,
+//!   threads or tail calls;
+//! - no start, tag, component or unknown sections;
+//! - a single 32-bit, unshared memory of at most 4 GiB;
+//! - constant expressions made of constants only;
+//! - data and element segments that fit the memory and tables;
+//! - a `contractenvmetav0` interface version the ledger protocol accepts;
+//! - imports only of host functions, with their exact signature, available
+//!   in both the contract's and the ledger's protocol;
+//! - at most [`Vm::MAX_VM_ARGS`] parameters and results per export.
+//!
+//! The maximum code size is a network setting and checked in
+//! [`limits`](crate::limits).
 
-pub fn enforce_soroban_compatibility(wasm: &[u8]) -> Result<(), String> {
+use soroban_env_host::meta::{ENV_META_V0_SECTION_NAME, INTERFACE_VERSION};
+use soroban_env_host::xdr::{Limited, Limits, ReadXdr, ScEnvMetaEntry};
+use soroban_env_host::Vm;
+use std::fmt;
+use std::io::Cursor;
+use wasmparser::{
+    ConstExpr, DataKind, ElementItems, ElementKind, ExternalKind, FuncType, Operator, Parser,
+    Payload, TableInit, TypeRef, ValType, Validator, WasmFeatures,
+};
+
+const WASM_PAGE_SIZE: u64 = 64 * 1024;
+
+/// A rule the module breaks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// Byte offset into the module.
+    pub offset: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at offset {:#x})", self.reason, self.offset)
+    }
+}
+
+/// A function the host links for contracts to import.
+pub struct HostFunction {
+    /// Import module, e.g. `x`.
+    pub module: &'static str,
+    /// Import name, e.g. `3`.
+    pub name: &'static str,
+    /// The host's name for it, e.g. `get_ledger_sequence`.
+    pub function: &'static str,
+    pub arity: usize,
+    pub min_protocol: Option<u32>,
+    pub max_protocol: Option<u32>,
+}
+
+macro_rules! optional {
+    () => {
+        None
+    };
+    ($value:literal) => {
+        Some($value)
+    };
+}
+
+// Callback for the host's x-macro, which lists every host function.
+macro_rules! host_functions {
+    {
+        $(
+            $(#[$mod_attr:meta])*
+            mod $mod_id:ident $mod_str:literal
+            {
+                $(
+                    $(#[$fn_attr:meta])*
+                    { $fn_id:literal, $($min_proto:literal)?, $($max_proto:literal)?, fn $func_id:ident ($($arg:ident : $ty:ty),*) -> $ret:ty }
+                )*
+            }
+        )*
+    } => {
+        static HOST_FUNCTIONS: &[HostFunction] = &[
+            $(
+                $(
+                    HostFunction {
+                        module: $mod_str,
+                        name: $fn_id,
+                        function: stringify!($func_id),
+                        arity: <[&str]>::len(&[$(stringify!($arg)),*]),
+                        min_protocol: optional!($($min_proto)?),
+                        max_protocol: optional!($($max_proto)?),
+                    },
+                )*
+            )*
+        ];
+    };
+}
+
+soroban_env_host::call_macro_with_all_host_functions! { host_functions }
+
+/// The host function contracts import as `module.name`, if any.
+pub fn host_function(module: &str, name: &str) -> Option<&'static HostFunction> {
+    HOST_FUNCTIONS
+        .iter()
+        .find(|f| f.module == module && f.name == name)
+}
+
+/// The features the host's wasmi engine is configured with.
+fn host_features() -> WasmFeatures {
+    WasmFeatures {
+        mutable_global: true,
+        saturating_float_to_int: false,
+        sign_extension: true,
+        reference_types: false,
+        multi_value: false,
+        bulk_memory: true,
+        simd: false,
+        relaxed_simd: false,
+        threads: false,
+        tail_call: false,
+        floats: false,
+        multi_memory: false,
+        exceptions: false,
+        memory64: false,
+        extended_const: false,
+        component_model: false,
+        function_references: false,
+        memory_control: false,
+        gc: false,
+        component_model_values: false,
+    }
+}
+
+/// Checks `wasm` against the rules the host enforces at `ledger_protocol`.
+/// Returns every violation found, or none if the host will accept it.
+pub fn validate(wasm: &[u8], ledger_protocol: u32) -> Vec<Violation> {
+    if !Parser::is_core_wasm(wasm) {
+        return vec![Violation {
+            offset: 0,
+            reason: "not a core WebAssembly module".to_string(),
+        }];
+    }
+    let mut violations = Vec::new();
+    if let Err(e) = check_module(wasm, ledger_protocol, &mut violations) {
+        violations.push(Violation {
+            offset: e.offset(),
+            reason: format!("malformed module: {}", e.message()),
+        });
+    }
+    // The validator stops at the first error, so it only runs once the
+    // checks above, which report everything, have passed.
+    if violations.is_empty() {
+        if let Err(e) = Validator::new_with_features(host_features()).validate_all(wasm) {
+            violations.push(Violation {
+                offset: e.offset(),
+                reason: e.message().to_string(),
+            });
+        }
+    }
+    violations
+}
+
+fn check_module(
+    wasm: &[u8],
+    ledger_protocol: u32,
+    violations: &mut Vec<Violation>,
+) -> wasmparser::Result<()> {
+    let mut types: Vec<FuncType> = Vec::new();
+    // Type index of every function, imported ones first.
+    let mut functions: Vec<u32> = Vec::new();
+    let mut imported_functions = 0;
+    let mut host_imports = Vec::new();
+    let mut exports = Vec::new();
+    let mut contract_protocol = None;
+    let mut meta_found = false;
+    let mut memory_bytes: u64 = 0;
+    let mut data_bytes: u64 = 0;
+    let mut table_entries: u64 = 0;
+    let mut elements: u64 = 0;
+    let mut bodies = 0;
     for payload in Parser::new(0).parse_all(wasm) {
-        let payload = payload.map_err(|e| e.to_string())?;
-        if let Payload::CodeSectionEntry(body) = payload {
-            let mut ops = body.get_operators_reader().map_err(|e| e.to_string())?;
-            while !ops.eof() {
-                let op = ops.read().map_err(|e| e.to_string())?;
-                if is_float_op(&op) {
-                    return Err(
-                        "floating-point instructions are not allowed under strict Soroban compatibility"
-                            .to_string(),
-                    );
+        match payload? {
+            Payload::TypeSection(section) => {
+                for ty in section.into_iter_err_on_gc_types() {
+                    types.push(ty?);
+                }
+            }
+            Payload::ImportSection(section) => {
+                for item in section.into_iter_with_offsets() {
+                    let (offset, import) = item?;
+                    match import.ty {
+                        TypeRef::Func(ty) => {
+                            functions.push(ty);
+                            imported_functions += 1;
+                            host_imports.push((offset, import.module, import.name, ty));
+                        }
+                        other => violations.push(Violation {
+                            offset,
+                            reason: format!(
+                                "imports {} `{}.{}`; only host functions can be imported",
+                                import_kind(&other),
+                                import.module,
+                                import.name
+                            ),
+                        }),
+                    }
+                }
+            }
+            Payload::FunctionSection(section) => {
+                for ty in section {
+                    functions.push(ty?);
+                }
+            }
+            Payload::TableSection(section) => {
+                for item in section.into_iter_with_offsets() {
+                    let (_, table) = item?;
+                    table_entries += u64::from(table.ty.initial);
+                    if let TableInit::Expr(expr) = table.init {
+                        check_const_expr(&expr, "table initializer", violations)?;
+                    }
+                }
+            }
+            Payload::MemorySection(section) => {
+                for item in section.into_iter_with_offsets() {
+                    let (offset, memory) = item?;
+                    let reason = if memory.memory64 {
+                        Some("64-bit memory is not supported".to_string())
+                    } else if memory.shared {
+                        Some("shared memory is not supported".to_string())
+                    } else if memory.initial.saturating_mul(WASM_PAGE_SIZE) > u64::from(u32::MAX) {
+                        Some(format!(
+                            "initial memory of {} pages exceeds 4 GiB",
+                            memory.initial
+                        ))
+                    } else {
+                        None
+                    };
+                    match reason {
+                        Some(reason) => violations.push(Violation { offset, reason }),
+                        None => memory_bytes += memory.initial * WASM_PAGE_SIZE,
+                    }
+                }
+            }
+            Payload::GlobalSection(section) => {
+                for global in section {
+                    check_const_expr(&global?.init_expr, "global initializer", violations)?;
+                }
+            }
+            Payload::ExportSection(section) => {
+                for item in section.into_iter_with_offsets() {
+                    let (offset, export) = item?;
+                    if export.kind == ExternalKind::Func {
+                        exports.push((offset, export.name, export.index));
+                    }
+                }
+            }
+            Payload::ElementSection(section) => {
+                for element in section {
+                    let element = element?;
+                    if let ElementKind::Active { offset_expr, .. } = element.kind {
+                        check_const_expr(&offset_expr, "element segment offset", violations)?;
+                    }
+                    match element.items {
+                        ElementItems::Functions(items) => elements += u64::from(items.count()),
+                        ElementItems::Expressions(_, items) => {
+                            elements += u64::from(items.count());
+                            for expr in items {
+                                check_const_expr(&expr?, "element segment item", violations)?;
+                            }
+                        }
+                    }
+                }
+            }
+            Payload::DataSection(section) => {
+                for data in section {
+                    let data = data?;
+                    data_bytes += data.data.len() as u64;
+                    if let DataKind::Active { offset_expr, .. } = data.kind {
+                        check_const_expr(&offset_expr, "data segment offset", violations)?;
+                    }
+                }
+            }
+            Payload::CodeSectionEntry(body) => {
+                let index = imported_functions + bodies;
+                bodies += 1;
+                let mut ops = body.get_operators_reader()?;
+                while !ops.eof() {
+                    let offset = ops.original_position();
+                    let op = ops.read()?;
+                    if is_float_op(&op) {
+                        violations.push(Violation {
+                            offset,
+                            reason: format!(
+                                "function {index} uses floating-point instruction {}",
+                                operator_name(&op)
+                            ),
+                        });
+                        break;
+                    }
+                }
+            }
+            Payload::CustomSection(section) if section.name() == ENV_META_V0_SECTION_NAME => {
+                meta_found = true;
+                let offset = section.data_offset();
+                match interface_version(section.data()) {
+                    Some((protocol, pre_release)) => {
+                        contract_protocol = Some(protocol);
+                        if let Some(reason) =
+                            check_interface_version(protocol, pre_release, ledger_protocol)
+                        {
+                            violations.push(Violation { offset, reason });
+                        }
+                    }
+                    None => violations.push(Violation {
+                        offset,
+                        reason: format!(
+                            "`{ENV_META_V0_SECTION_NAME}` has no environment interface version"
+                        ),
+                    }),
                 }
             }
+            Payload::StartSection { range, .. } => violations.push(Violation {
+                offset: range.start,
+                reason: "start section is not supported".to_string(),
+            }),
+            Payload::TagSection(section) => violations.push(Violation {
+                offset: section.range().start,
+                reason: "tag section is not supported".to_string(),
+            }),
+            Payload::UnknownSection { id, range, .. } => violations.push(Violation {
+                offset: range.start,
+                reason: format!("unknown section {id} is not supported"),
+            }),
+            _ => {}
+        }
+    }
+    if !meta_found {
+        violations.push(Violation {
+            offset: 0,
+            reason: format!("missing `{ENV_META_V0_SECTION_NAME}` metadata section"),
+        });
+    }
+    for (offset, module, name, ty) in host_imports {
+        let reason = check_import(
+            module,
+            name,
+            types.get(ty as usize),
+            contract_protocol,
+            ledger_protocol,
+        );
+        if let Some(reason) = reason {
+            violations.push(Violation { offset, reason });
+        }
+    }
+    for (offset, name, index) in exports {
+        let Some(ty) = functions
+            .get(index as usize)
+            .and_then(|&ty| types.get(ty as usize))
+        else {
+            continue;
+        };
+        if ty.params().len() > Vm::MAX_VM_ARGS || ty.results().len() > Vm::MAX_VM_ARGS {
+            violations.push(Violation {
+                offset,
+                reason: format!(
+                    "export `{name}` takes {} arguments and returns {} values; at most {} of each are allowed",
+                    ty.params().len(),
+                    ty.results().len(),
+                    Vm::MAX_VM_ARGS
+                ),
+            });
+        }
+    }
+    if data_bytes > memory_bytes {
+        violations.push(Violation {
+            offset: 0,
+            reason: format!(
+                "data segments hold {data_bytes} bytes, more than the {memory_bytes} bytes of memory"
+            ),
+        });
+    }
+    if elements > table_entries {
+        violations.push(Violation {
+            offset: 0,
+            reason: format!(
+                "element segments hold {elements} entries, more than the {table_entries} table entries"
+            ),
+        });
+    }
+    Ok(())
+}
+
+fn check_const_expr(
+    expr: &ConstExpr,
+    context: &str,
+    violations: &mut Vec<Violation>,
+) -> wasmparser::Result<()> {
+    let mut ops = expr.get_operators_reader();
+    while !ops.eof() {
+        let offset = ops.original_position();
+        let op = ops.read()?;
+        if !matches!(
+            op,
+            Operator::I32Const { .. }
+                | Operator::I64Const { .. }
+                | Operator::RefFunc { .. }
+                | Operator::RefNull { .. }
+                | Operator::End
+        ) {
+            violations.push(Violation {
+                offset,
+                reason: format!(
+                    "{context} uses {}; only constants are allowed",
+                    operator_name(&op)
+                ),
+            });
+            break;
         }
     }
     Ok(())
 }
 
+/// The `(protocol, pre_release)` interface version in a `contractenvmetav0`
+/// section.
+pub fn interface_version(section: &[u8]) -> Option<(u32, u32)> {
+    let mut cursor = Limited::new(Cursor::new(section), Limits::len(section.len()));
+    match ScEnvMetaEntry::read_xdr_iter(&mut cursor).next()?.ok()? {
+        ScEnvMetaEntry::ScEnvMetaKindInterfaceVersion(version) => {
+            Some((version.protocol, version.pre_release))
+        }
+    }
+}
+
+fn check_interface_version(protocol: u32, pre_release: u32, ledger: u32) -> Option<String> {
+    if protocol > ledger {
+        Some(format!(
+            "contract is built for protocol {protocol}, newer than ledger protocol {ledger}"
+        ))
+    } else if protocol < ledger && pre_release != 0 {
+        Some(format!(
+            "contract is built for pre-release {pre_release} of old protocol {protocol}; only final releases are accepted"
+        ))
+    } else if protocol == ledger
+        && protocol == INTERFACE_VERSION.protocol
+        && pre_release != INTERFACE_VERSION.pre_release
+    {
+        Some(format!(
+            "contract is built for pre-release {pre_release} of protocol {protocol}, but the host is pre-release {}",
+            INTERFACE_VERSION.pre_release
+        ))
+    } else {
+        None
+    }
+}
+
+fn check_import(
+    module: &str,
+    name: &str,
+    ty: Option<&FuncType>,
+    contract_protocol: Option<u32>,
+    ledger_protocol: u32,
+) -> Option<String> {
+    let Some(function) = host_function(module, name) else {
+        return Some(format!(
+            "imports `{module}.{name}`, which is not a host function"
+        ));
+    };
+    let expected = ty.is_some_and(|ty| {
+        ty.params().len() == function.arity
+            && ty.params().iter().all(|&p| p == ValType::I64)
+            && ty.results() == [ValType::I64]
+    });
+    if !expected {
+        return Some(format!(
+            "imports host function `{module}.{name}` with the wrong signature; it takes {} i64 arguments and returns an i64",
+            function.arity
+        ));
+    }
+    let protocols = [Some(ledger_protocol), contract_protocol];
+    let oldest = protocols.iter().flatten().min().copied();
+    let newest = protocols.iter().flatten().max().copied();
+    if let (Some(min), Some(oldest)) = (function.min_protocol, oldest) {
+        if oldest < min {
+            return Some(format!(
+                "imports host function `{module}.{name}`, which is only available from protocol {min}"
+            ));
+        }
+    }
+    if let (Some(max), Some(newest)) = (function.max_protocol, newest) {
+        if newest > max {
+            return Some(format!(
+                "imports host function `{module}.{name}`, which was removed after protocol {max}"
+            ));
+        }
+    }
+    None
+}
+
+fn import_kind(ty: &TypeRef) -> &'static str {
+    match ty {
+        TypeRef::Func(_) => "function",
+        TypeRef::Table(_) => "table",
+        TypeRef::Memory(_) => "memory",
+        TypeRef::Global(_) => "global",
+        TypeRef::Tag(_) => "tag",
+    }
+}
+
+/// The operator's name without its immediates, e.g. `F32Const`.
+fn operator_name(op: &Operator) -> String {
+    let debug = format!("{op:?}");
+    debug
+        .split([' ', '{'])
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
 fn is_float_op(op: &Operator) -> bool {
     use Operator::*;
     matches!(
@@ -110,5 +609,128 @@ fn is_float_op(op: &Operator) -> bool {
             | I64TruncSatF32U
             | I64TruncSatF64S
             | I64TruncSatF64U
+            | F32Load { .. }
+            | F32Store { .. }
+            | F64Load { .. }
+            | F64Store { .. }
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::xdr::{ScEnvMetaEntryInterfaceVersion, WriteXdr};
+
+    /// Appends a `contractenvmetav0` section declaring the given version.
+    fn with_meta(mut wasm: Vec<u8>, protocol: u32, pre_release: u32) -> Vec<u8> {
+        let entry = ScEnvMetaEntry::ScEnvMetaKindInterfaceVersion(ScEnvMetaEntryInterfaceVersion {
+            protocol,
+            pre_release,
+        })
+        .to_xdr(Limits::none())
+        .unwrap();
+        let name = ENV_META_V0_SECTION_NAME.as_bytes();
+        let mut body = vec![name.len() as u8];
+        body.extend_from_slice(name);
+        body.extend_from_slice(&entry);
+        wasm.push(0);
+        wasm.push(body.len() as u8);
+        wasm.extend_from_slice(&body);
+        wasm
+    }
+
+    fn module(wat: &str) -> Vec<u8> {
+        with_meta(
+            wat::parse_str(wat).unwrap(),
+            INTERFACE_VERSION.protocol,
+            INTERFACE_VERSION.pre_release,
+        )
+    }
+
+    #[test]
+    fn test_accepts_contract_importing_host_functions() {
+        let wasm = module(
+            r#"(module
+                (import "x" "3" (func (param) (result i64)))
+                (memory 1)
+                (data (i32.const 0) "hi")
+                (func (export "run") (param i64) (result i64) call 0))"#,
+        );
+        assert_eq!(validate(&wasm, INTERFACE_VERSION.protocol), vec![]);
+    }
+
+    #[test]
+    fn test_reports_every_violation() {
+        let wasm = module(
+            r#"(module
+                (import "env" "abort" (func))
+                (import "x" "3" (func (param i64) (result i64)))
+                (import "env" "g" (global i32))
+                (global i32 (global.get 0))
+                (func (export "f") (result i64)
+                    f64.const 1.5
+                    drop
+                    i64.const 0)
+                (start 2))"#,
+        );
+        let reasons = validate(&wasm, INTERFACE_VERSION.protocol)
+            .into_iter()
+            .map(|v| v.reason)
+            .collect::<Vec<_>>();
+        let expected = [
+            "imports global `env.g`",
+            "global initializer uses GlobalGet",
+            "start section",
+            "function 2 uses floating-point instruction F64Const",
+            "`env.abort`, which is not a host function",
+            "`x.3` with the wrong signature",
+        ];
+        for fragment in expected {
+            assert!(
+                reasons.iter().any(|r| r.contains(fragment)),
+                "{fragment:?} not in {reasons:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_checks_interface_version_against_ledger() {
+        let wasm = wat::parse_str("(module)").unwrap();
+        let missing = validate(&wasm, 22);
+        assert!(missing[0].reason.contains("missing `contractenvmetav0`"));
+
+        let newer = validate(&with_meta(wasm.clone(), 23, 0), 22);
+        assert!(newer[0].reason.contains("newer than ledger protocol 22"));
+        let old_pre_release = validate(&with_meta(wasm.clone(), 21, 3), 22);
+        assert!(old_pre_release[0].reason.contains("pre-release 3"));
+        assert_eq!(validate(&with_meta(wasm, 21, 0), 22), vec![]);
+    }
+
+    #[test]
+    fn test_rejects_features_the_host_disables() {
+        let wasm =
+            module(r#"(module (func (export "f") (result i64 i64) i64.const 0 i64.const 1))"#);
+        let violations = validate(&wasm, INTERFACE_VERSION.protocol);
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].offset > 0);
+    }
+
+    #[test]
+    fn test_rejects_too_many_export_arguments() {
+        let params = "i64 ".repeat(Vm::MAX_VM_ARGS + 1);
+        let wasm = module(&format!(
+            r#"(module (func (export "wide") (param {params}) (result i64) i64.const 0))"#
+        ));
+        let violations = validate(&wasm, INTERFACE_VERSION.protocol);
+        assert!(violations[0]
+            .reason
+            .contains("export `wide` takes 33 arguments"));
+    }
+
+    #[test]
+    fn test_rejects_oversized_data_segments() {
+        let wasm = module(r#"(module (memory 0) (data (i32.const 0) "x"))"#);
+        let violations = validate(&wasm, INTERFACE_VERSION.protocol);
+        assert!(violations[0].reason.contains("data segments hold 1 bytes"));
+    }
+}
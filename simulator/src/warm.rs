@@ -0,0 +1,259 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Compiled contract modules reused across simulations in server mode.
+//!
+//! Every simulation starts a fresh host, and every contract call in it
+//! parses and validates the contract's Wasm and links a new wasmi module
+//! from scratch. For a preflight service simulating the same handful of
+//! contracts thousands of times a minute, that setup dominates latency.
+//!
+//! A [`HostPool`] keeps idle [`WarmModules`]: a host [`ModuleCache`] with its
+//! wasmi engine and linker, holding the modules of contracts earlier
+//! simulations ran. A simulation checks one out, installs it in its host,
+//! and checks it back in with the contract code it read added. Only the
+//! per-request state, storage and budget, is built anew.
+//!
+//! Costs are unchanged. Outside enforcing mode the host already charges a
+//! call to live contract code as a module cache hit, so the pool only saves
+//! the parsing the host did off-budget. Modules whose code is not live in a
+//! request's state are dropped before it runs, so calls to archived or
+//! just-uploaded code are still charged as misses. Slots are keyed by the
+//! ledger protocol, and requests with custom cost parameters, whose wasmi
+//! fuel costs differ, do not use the pool.
+
+use crate::source::ContractCode;
+use serde::Serialize;
+use soroban_env_host::vm::VersionedContractCodeCostInputs;
+use soroban_env_host::xdr::{Hash, LedgerKey, LedgerKeyContractCode};
+use soroban_env_host::{Host, ModuleCache};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Default number of idle module caches kept.
+pub const DEFAULT_POOL_SIZE: usize = 8;
+
+/// Modules a cache holds before it is emptied and starts over.
+pub const MAX_MODULES: usize = 256;
+
+/// Idle module caches, ready for the next simulation.
+pub struct HostPool {
+    capacity: usize,
+    idle: Mutex<Vec<WarmModules>>,
+    reused: AtomicU64,
+    created: AtomicU64,
+}
+
+/// Pool statistics reported by `/health`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct HostPoolStats {
+    /// Caches waiting for a simulation.
+    pub idle: usize,
+    /// Simulations that got a cache from the pool.
+    pub reused: u64,
+    /// Simulations that found none for their protocol and built one.
+    pub created: u64,
+}
+
+impl HostPool {
+    /// Keeps up to `capacity` idle caches. Returns `None` when it is zero.
+    pub fn new(capacity: usize) -> Option<Self> {
+        (capacity > 0).then(|| Self {
+            capacity,
+            idle: Mutex::new(Vec::new()),
+            reused: AtomicU64::new(0),
+            created: AtomicU64::new(0),
+        })
+    }
+
+    /// Takes an idle cache for ledger protocol `protocol`, or builds one.
+    pub fn checkout(&self, protocol: u32) -> Option<WarmModules> {
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(index) = idle.iter().position(|warm| warm.protocol == protocol) {
+            self.reused.fetch_add(1, Ordering::Relaxed);
+            return Some(idle.swap_remove(index));
+        }
+        drop(idle);
+        self.created.fetch_add(1, Ordering::Relaxed);
+        let cache = ModuleCache::new(&Host::default())
+            .map_err(|e| eprintln!("Failed to create module cache: {e:?}"))
+            .ok()?;
+        Some(WarmModules {
+            protocol,
+            cache,
+            hashes: Vec::new(),
+        })
+    }
+
+    /// Returns `warm` to the pool, unless the pool is full.
+    pub fn checkin(&self, warm: WarmModules) {
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        if idle.len() < self.capacity {
+            idle.push(warm);
+        }
+    }
+
+    pub fn stats(&self) -> HostPoolStats {
+        HostPoolStats {
+            idle: self.idle.lock().unwrap_or_else(|e| e.into_inner()).len(),
+            reused: self.reused.load(Ordering::Relaxed),
+            created: self.created.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A module cache and the contract code it holds.
+pub struct WarmModules {
+    protocol: u32,
+    cache: ModuleCache,
+    hashes: Vec<Hash>,
+}
+
+impl WarmModules {
+    /// The cache to install in a host. Clones share the modules.
+    pub fn cache(&self) -> ModuleCache {
+        self.cache.clone()
+    }
+
+    /// Drops the modules whose code is not live at `ledger_sequence`
+    /// according to `lookup`.
+    pub fn retain_live<'a>(
+        &mut self,
+        lookup: impl Fn(&Hash) -> Option<ContractCode<'a>>,
+        ledger_sequence: u32,
+    ) {
+        let cache = &self.cache;
+        self.hashes.retain(|hash| {
+            let live = live_code(hash, &lookup, ledger_sequence).is_some();
+            if !live {
+                let _ = cache.remove_module(hash);
+            }
+            live
+        });
+    }
+
+    /// Parses the live contract code among `keys` that the cache lacks.
+    pub fn learn<'a>(
+        &mut self,
+        keys: &[LedgerKey],
+        lookup: impl Fn(&Hash) -> Option<ContractCode<'a>>,
+        ledger_sequence: u32,
+    ) {
+        let context = Host::default();
+        for key in keys {
+            let LedgerKey::ContractCode(LedgerKeyContractCode { hash }) = key else {
+                continue;
+            };
+            if self.hashes.contains(hash) {
+                continue;
+            }
+            let Some(code) = live_code(hash, &lookup, ledger_sequence) else {
+                continue;
+            };
+            if self.hashes.len() >= MAX_MODULES {
+                let _ = self.cache.clear();
+                self.hashes.clear();
+            }
+            let parsed = self.cache.parse_and_cache_module(
+                &context,
+                self.protocol,
+                hash,
+                &code.wasm,
+                VersionedContractCodeCostInputs::V0 {
+                    wasm_bytes: code.wasm.len(),
+                },
+            );
+            if parsed.is_ok() {
+                self.hashes.push(hash.clone());
+            }
+        }
+    }
+}
+
+/// Contract code `hash`, if it is live at `ledger_sequence`.
+fn live_code<'a>(
+    hash: &Hash,
+    lookup: impl Fn(&Hash) -> Option<ContractCode<'a>>,
+    ledger_sequence: u32,
+) -> Option<ContractCode<'a>> {
+    lookup(hash).filter(|code| {
+        code.live_until
+            .is_none_or(|live_until| live_until >= ledger_sequence)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::meta::{ENV_META_V0_SECTION_NAME, INTERFACE_VERSION};
+    use soroban_env_host::xdr::{Limits, ScEnvMetaEntry, ScEnvMetaEntryInterfaceVersion, WriteXdr};
+    use std::borrow::Cow;
+
+    /// An empty contract built for the host's interface version.
+    fn contract() -> Vec<u8> {
+        let entry = ScEnvMetaEntry::ScEnvMetaKindInterfaceVersion(ScEnvMetaEntryInterfaceVersion {
+            protocol: INTERFACE_VERSION.protocol,
+            pre_release: INTERFACE_VERSION.pre_release,
+        })
+        .to_xdr(Limits::none())
+        .unwrap();
+        let name = ENV_META_V0_SECTION_NAME.as_bytes();
+        let mut body = vec![name.len() as u8];
+        body.extend_from_slice(name);
+        body.extend_from_slice(&entry);
+        let mut wasm = wat::parse_str("(module)").unwrap();
+        wasm.push(0);
+        wasm.push(body.len() as u8);
+        wasm.extend_from_slice(&body);
+        wasm
+    }
+
+    fn code(byte: u8, live_until: u32) -> (Hash, Vec<u8>, u32) {
+        (Hash([byte; 32]), contract(), live_until)
+    }
+
+    #[test]
+    fn test_pool_reuses_modules_while_their_code_is_live() {
+        let pool = HostPool::new(1).unwrap();
+        let state = [code(1, 100), code(2, 10)];
+        let lookup = |hash: &Hash| {
+            state
+                .iter()
+                .find(|(h, _, _)| h == hash)
+                .map(|(_, wasm, live_until)| ContractCode {
+                    wasm: Cow::Borrowed(wasm.as_slice()),
+                    live_until: Some(*live_until),
+                })
+        };
+        let keys = state
+            .iter()
+            .map(|(hash, _, _)| {
+                LedgerKey::ContractCode(LedgerKeyContractCode { hash: hash.clone() })
+            })
+            .collect::<Vec<_>>();
+        let protocol = INTERFACE_VERSION.protocol;
+
+        let mut warm = pool.checkout(protocol).unwrap();
+        warm.learn(&keys, lookup, 50);
+        assert_eq!(warm.hashes.len(), 1);
+        assert!(warm.cache().contains_module(&Hash([1; 32])).unwrap());
+        pool.checkin(warm);
+
+        let mut warm = pool.checkout(protocol).unwrap();
+        assert_eq!(warm.hashes.len(), 1);
+        warm.retain_live(lookup, 200);
+        assert_eq!(warm.hashes.len(), 0);
+        assert!(!warm.cache().contains_module(&Hash([1; 32])).unwrap());
+        pool.checkin(warm);
+
+        assert!(pool.checkout(protocol - 1).is_some());
+        assert_eq!(
+            pool.stats(),
+            HostPoolStats {
+                idle: 1,
+                reused: 1,
+                created: 2,
+            }
+        );
+    }
+}
@@ -0,0 +1,307 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! `erst-sim watch`: re-running a scenario whenever its files change.
+//!
+//! `erst-sim watch vault.json --wasm target/wasm32v1-none/release/vault.wasm`
+//! runs the scenario, then polls the modification times of the scenario and
+//! the WASM and runs it again each time one changes, for an edit, compile,
+//! simulate loop. Each run prints one line with how many steps passed, and
+//! below it only what changed since the previous run: steps that started
+//! failing, with their first failed assertion, and steps that were fixed. The
+//! first run lists every failing step.
+//!
+//! With `--wasm`, the module is installed into the scenario's initial state
+//! as the code of the contracts named with `--contract`. Without
+//! `--contract`, it replaces the code of the scenario's only WASM contract. A
+//! named contract the state does not have is deployed with empty storage.
+
+use crate::scenario::{ScenarioOutcome, StepOutcome};
+use crate::snapshot;
+use sha2::{Digest, Sha256};
+use soroban_env_host::xdr::{
+    ContractCodeEntry, ContractCodeEntryExt, ContractDataDurability, ContractDataEntry,
+    ContractExecutable, ExtensionPoint, Hash, LedgerEntry, LedgerEntryData, LedgerEntryExt,
+    LedgerKey, LedgerKeyContractCode, LedgerKeyContractData, Limits, ScAddress, ScContractInstance,
+    ScVal, WriteXdr,
+};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Whether a step passed, and why not.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepResult {
+    pub name: String,
+    pub passed: bool,
+    /// The first failed assertion.
+    pub failure: Option<String>,
+}
+
+/// The results of the steps of `outcome`.
+pub fn results(outcome: &ScenarioOutcome) -> Vec<StepResult> {
+    outcome.steps.iter().map(result).collect()
+}
+
+fn result(step: &StepOutcome) -> StepResult {
+    let failed = step.assertions.iter().find(|a| !a.passed);
+    StepResult {
+        name: step.name.clone(),
+        passed: failed.is_none(),
+        failure: failed.map(|a| a.message.clone().unwrap_or_else(|| a.name.clone())),
+    }
+}
+
+/// The lines reporting `current`, with the steps whose result changed since
+/// `previous`, or every failing step when there is no previous run.
+pub fn report(name: &str, previous: Option<&[StepResult]>, current: &[StepResult]) -> Vec<String> {
+    let passed = current.iter().filter(|step| step.passed).count();
+    let mut lines = vec![format!("{name}: {passed}/{} steps pass", current.len())];
+    let before = |step: &StepResult| {
+        previous.and_then(|previous| previous.iter().find(|other| other.name == step.name))
+    };
+    for step in current {
+        let line = match (before(step).map(|before| before.passed), step.passed) {
+            (Some(true) | None, false) => format!(
+                "  ✗ {}: {}",
+                step.name,
+                step.failure.as_deref().unwrap_or("failed")
+            ),
+            (Some(false), true) => format!("  ✓ {} (fixed)", step.name),
+            _ => continue,
+        };
+        lines.push(line);
+    }
+    if let Some(previous) = previous {
+        for gone in previous
+            .iter()
+            .filter(|step| !current.iter().any(|other| other.name == step.name))
+        {
+            lines.push(format!("  - {} (removed)", gone.name));
+        }
+    }
+    lines
+}
+
+/// Installs `wasm` into `entries` as the code of `contracts`, or of the only
+/// WASM contract in `entries` when `contracts` is empty. Returns the
+/// contracts that run it.
+pub fn install(
+    entries: &mut HashMap<String, String>,
+    wasm: &[u8],
+    contracts: &[String],
+) -> Result<Vec<String>, String> {
+    let hash = Hash(Sha256::digest(wasm).into());
+    let targets = if contracts.is_empty() {
+        let deployed = wasm_contracts(entries);
+        match deployed.len() {
+            1 => deployed.into_iter().collect(),
+            0 => return Err("The scenario has no WASM contract; name one with --contract".into()),
+            n => {
+                return Err(format!(
+                    "The scenario has {n} WASM contracts; name the one to replace with --contract"
+                ))
+            }
+        }
+    } else {
+        contracts.to_vec()
+    };
+    let encode = |xdr: Result<String, soroban_env_host::xdr::Error>| {
+        xdr.map_err(|e| format!("Failed to encode the WASM entries: {e}"))
+    };
+    let code = LedgerEntry {
+        last_modified_ledger_seq: 0,
+        data: LedgerEntryData::ContractCode(ContractCodeEntry {
+            ext: ContractCodeEntryExt::V0,
+            hash: hash.clone(),
+            code: wasm.to_vec().try_into().map_err(|e| format!("WASM: {e}"))?,
+        }),
+        ext: LedgerEntryExt::V0,
+    };
+    entries.insert(
+        encode(
+            LedgerKey::ContractCode(LedgerKeyContractCode { hash: hash.clone() })
+                .to_xdr_base64(Limits::none()),
+        )?,
+        encode(code.to_xdr_base64(Limits::none()))?,
+    );
+    for contract_id in &targets {
+        let contract = contract_id
+            .parse::<ScAddress>()
+            .map_err(|e| format!("Invalid contract {contract_id:?}: {e}"))?;
+        let key = LedgerKey::ContractData(LedgerKeyContractData {
+            contract: contract.clone(),
+            key: ScVal::LedgerKeyContractInstance,
+            durability: ContractDataDurability::Persistent,
+        });
+        let key_xdr = encode(key.to_xdr_base64(Limits::none()))?;
+        let mut entry = match entries.get(&key_xdr) {
+            Some(entry) => snapshot::decode_ledger_entry(entry).map_err(|e| e.to_string())?,
+            None => LedgerEntry {
+                last_modified_ledger_seq: 0,
+                data: LedgerEntryData::ContractData(ContractDataEntry {
+                    ext: ExtensionPoint::V0,
+                    contract,
+                    key: ScVal::LedgerKeyContractInstance,
+                    durability: ContractDataDurability::Persistent,
+                    val: ScVal::ContractInstance(ScContractInstance {
+                        executable: ContractExecutable::Wasm(hash.clone()),
+                        storage: None,
+                    }),
+                }),
+                ext: LedgerEntryExt::V0,
+            },
+        };
+        let LedgerEntryData::ContractData(ContractDataEntry {
+            val: ScVal::ContractInstance(instance),
+            ..
+        }) = &mut entry.data
+        else {
+            return Err(format!("{contract_id} has no contract instance"));
+        };
+        if !matches!(instance.executable, ContractExecutable::Wasm(_)) {
+            return Err(format!("{contract_id} is not a WASM contract"));
+        }
+        instance.executable = ContractExecutable::Wasm(hash.clone());
+        entries.insert(key_xdr, encode(entry.to_xdr_base64(Limits::none()))?);
+    }
+    Ok(targets)
+}
+
+/// The contracts of `entries` whose instance runs a WASM.
+fn wasm_contracts(entries: &HashMap<String, String>) -> BTreeSet<String> {
+    entries
+        .values()
+        .filter_map(|entry| {
+            let entry = snapshot::decode_ledger_entry(entry).ok()?;
+            match entry.data {
+                LedgerEntryData::ContractData(ContractDataEntry {
+                    contract,
+                    val:
+                        ScVal::ContractInstance(ScContractInstance {
+                            executable: ContractExecutable::Wasm(_),
+                            ..
+                        }),
+                    ..
+                }) => Some(contract.to_string()),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Modification times of a set of files.
+pub struct Watched {
+    paths: Vec<PathBuf>,
+    stamps: Vec<Option<SystemTime>>,
+}
+
+impl Watched {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let stamps = stamps(&paths);
+        Self { paths, stamps }
+    }
+
+    /// Waits until one of the files changes and stays unchanged for
+    /// `interval`, so a file still being written is not read half done.
+    pub fn wait(&mut self, interval: Duration) {
+        loop {
+            std::thread::sleep(interval);
+            let current = stamps(&self.paths);
+            if current == self.stamps {
+                continue;
+            }
+            self.stamps = current;
+            loop {
+                std::thread::sleep(interval);
+                let settled = stamps(&self.paths);
+                if settled == self.stamps {
+                    return;
+                }
+                self.stamps = settled;
+            }
+        }
+    }
+}
+
+fn stamps(paths: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    paths.iter().map(|path| modified(path)).collect()
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(name: &str, passed: bool) -> StepResult {
+        StepResult {
+            name: name.to_string(),
+            passed,
+            failure: (!passed).then(|| format!("{name} broke")),
+        }
+    }
+
+    #[test]
+    fn test_report_lists_what_changed() {
+        let first = [step("a", true), step("b", false), step("c", true)];
+        assert_eq!(
+            report("vault", None, &first),
+            ["vault: 2/3 steps pass", "  ✗ b: b broke"]
+        );
+        let second = [step("a", false), step("b", true), step("d", true)];
+        assert_eq!(
+            report("vault", Some(&first), &second),
+            [
+                "vault: 2/3 steps pass",
+                "  ✗ a: a broke",
+                "  ✓ b (fixed)",
+                "  - c (removed)"
+            ]
+        );
+        assert_eq!(
+            report("vault", Some(&second), &second),
+            ["vault: 2/3 steps pass"]
+        );
+    }
+
+    #[test]
+    fn test_install_replaces_the_only_contract_code() {
+        let wasm = wat::parse_str("(module)").unwrap();
+        let mut entries = HashMap::new();
+        assert!(install(&mut entries, &wasm, &[])
+            .unwrap_err()
+            .contains("no WASM contract"));
+
+        let contract = ScAddress::Contract(soroban_env_host::xdr::ContractId(Hash([3; 32])));
+        let deployed = install(&mut entries, &wasm, &[contract.to_string()]).unwrap();
+        assert_eq!(deployed, [contract.to_string()]);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(wasm_contracts(&entries).len(), 1);
+
+        let rebuilt = wat::parse_str("(module (memory 1))").unwrap();
+        assert_eq!(
+            install(&mut entries, &rebuilt, &[]).unwrap(),
+            [contract.to_string()]
+        );
+        // The new code beside the old, and the instance pointed at it.
+        assert_eq!(entries.len(), 3);
+        let hash = Hash(Sha256::digest(&rebuilt).into());
+        let instance = entries
+            .values()
+            .filter_map(|entry| snapshot::decode_ledger_entry(entry).ok())
+            .find_map(|entry| match entry.data {
+                LedgerEntryData::ContractData(data) => Some(data.val),
+                _ => None,
+            });
+        assert!(matches!(
+            instance,
+            Some(ScVal::ContractInstance(ScContractInstance {
+                executable: ContractExecutable::Wasm(running),
+                ..
+            })) if running == hash
+        ));
+    }
+}
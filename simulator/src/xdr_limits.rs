@@ -0,0 +1,138 @@
+// Copyright 2025 Erst Users
+// SPDX-License-Identifier: Apache-2.0
+
+//! Bounds applied when decoding untrusted XDR.
+//!
+//! `Limits::none()` lets a crafted payload nest arbitrarily deep (overflowing
+//! the stack) or declare huge lengths (forcing large allocations), which is a
+//! denial-of-service vector on a shared simulator. Every envelope, ledger key
+//! and ledger entry in a request is decoded through [`read`], which enforces a
+//! recursion depth and a length budget.
+//!
+//! Limits are configured through `ERST_XDR_MAX_DEPTH` and `ERST_XDR_MAX_LEN`.
+
+use soroban_env_host::xdr::{self, Limits, ReadXdr};
+use std::env;
+use std::sync::OnceLock;
+
+/// Default recursion depth, matching the Soroban host's own XDR limit.
+pub const DEFAULT_XDR_MAX_DEPTH: u32 = soroban_env_host::DEFAULT_XDR_RW_LIMITS.depth;
+/// Default length budget for a single decoded value (16 MiB).
+pub const DEFAULT_XDR_MAX_LEN: usize = 16 * 1024 * 1024;
+
+/// Configured XDR decoding limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XdrLimits {
+    /// Maximum nesting depth of a decoded value.
+    pub max_depth: u32,
+    /// Maximum number of bytes a single decoded value may consume.
+    pub max_len: usize,
+}
+
+impl Default for XdrLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_XDR_MAX_DEPTH,
+            max_len: DEFAULT_XDR_MAX_LEN,
+        }
+    }
+}
+
+impl XdrLimits {
+    /// Reads the limits from the environment, falling back to the defaults
+    /// for unset or unparsable values.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            max_depth: env_or("ERST_XDR_MAX_DEPTH", defaults.max_depth),
+            max_len: env_or("ERST_XDR_MAX_LEN", defaults.max_len),
+        }
+    }
+
+    /// Limits for decoding an input of `input_len` bytes.
+    ///
+    /// The length budget never exceeds the input size, so a declared length
+    /// larger than the remaining input is rejected before anything is allocated.
+    pub fn for_input(&self, input_len: usize) -> Limits {
+        Limits {
+            depth: self.max_depth,
+            len: self.max_len.min(input_len),
+        }
+    }
+}
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(default)
+}
+
+/// Returns the process-wide limits, read from the environment once.
+pub fn xdr_limits() -> &'static XdrLimits {
+    static LIMITS: OnceLock<XdrLimits> = OnceLock::new();
+    LIMITS.get_or_init(XdrLimits::from_env)
+}
+
+/// Decodes `bytes` as `T` under the process-wide limits.
+pub fn read<T: ReadXdr>(bytes: &[u8]) -> Result<T, xdr::Error> {
+    read_with(xdr_limits(), bytes)
+}
+
+/// Decodes `bytes` as `T` under `limits`.
+pub fn read_with<T: ReadXdr>(limits: &XdrLimits, bytes: &[u8]) -> Result<T, xdr::Error> {
+    if bytes.len() > limits.max_len {
+        return Err(xdr::Error::LengthLimitExceeded);
+    }
+    T::from_xdr(bytes, limits.for_input(bytes.len()))
+}
+
+/// Returns true if `e` was raised because a limit was hit rather than
+/// because the input is malformed.
+pub fn is_exceeded(e: &xdr::Error) -> bool {
+    matches!(
+        e,
+        xdr::Error::DepthLimitExceeded | xdr::Error::LengthLimitExceeded
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_env_host::xdr::{ScVal, ScVec, WriteXdr};
+
+    fn nested_vec(depth: usize) -> Vec<u8> {
+        let mut val = ScVal::Void;
+        for _ in 0..depth {
+            val = ScVal::Vec(Some(ScVec(vec![val].try_into().unwrap())));
+        }
+        val.to_xdr(Limits::none()).unwrap()
+    }
+
+    #[test]
+    fn test_read_rejects_excessive_depth() {
+        let limits = XdrLimits {
+            max_depth: 20,
+            ..XdrLimits::default()
+        };
+        let err = read_with::<ScVal>(&limits, &nested_vec(50)).unwrap_err();
+        assert!(is_exceeded(&err));
+        assert!(read_with::<ScVal>(&limits, &nested_vec(2)).is_ok());
+    }
+
+    #[test]
+    fn test_read_rejects_oversized_input() {
+        let limits = XdrLimits {
+            max_len: 8,
+            ..XdrLimits::default()
+        };
+        let err = read_with::<ScVal>(&limits, &nested_vec(5)).unwrap_err();
+        assert!(is_exceeded(&err));
+    }
+
+    #[test]
+    fn test_malformed_input_is_not_a_limit_error() {
+        let err = read_with::<ScVal>(&XdrLimits::default(), &[0xff, 0xff, 0xff, 0xff]).unwrap_err();
+        assert!(!is_exceeded(&err));
+    }
+}